@@ -0,0 +1,47 @@
+//! This example creates a ble server with a custom advertisement. The advertisement has the battery
+//! service uuid, the appearance of a generic sensor, the tx power level and manufacturer data with a
+//! counter. The complete name of the server does not fit, so it is spilled into the scan response.
+//! The counter in the manufacturer data is increased and advertised again every 5 seconds.
+
+use esp32framework::{
+    ble::{
+        utils::{ble_standard_uuids::StandardServiceId, AdvertisementBuilder, Service},
+        BleId,
+    },
+    Microcontroller,
+};
+
+/// Company identifier reserved by the Bluetooth SIG for testing
+const TEST_COMPANY_ID: u16 = 0xFFFF;
+const GENERIC_SENSOR_APPEARANCE: u16 = 0x0540;
+const TX_POWER_DBM: i8 = 0;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::from_standard_service(StandardServiceId::Battery);
+    let service = Service::new(&service_id, vec![]).unwrap();
+    let mut server = micro
+        .ble_server("Example Advertisement Server".to_string(), &vec![service])
+        .unwrap();
+
+    let mut counter: u32 = 0;
+    server.start().unwrap();
+    loop {
+        let advertisement = AdvertisementBuilder::new()
+            .service_uuid(&service_id)
+            .appearance(GENERIC_SENSOR_APPEARANCE)
+            .tx_power_level(TX_POWER_DBM)
+            .manufacturer_data(TEST_COMPANY_ID, &counter.to_le_bytes())
+            .name("Example Advertisement Server")
+            .spill_to_scan_response(true);
+        println!(
+            "Advertising counter {} ({} bytes before spilling)",
+            counter,
+            advertisement.encoded_size()
+        );
+
+        server.set_advertisement(&advertisement).unwrap();
+        counter += 1;
+        micro.wait_for_updates(Some(5000));
+    }
+}
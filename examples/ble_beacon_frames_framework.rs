@@ -0,0 +1,59 @@
+//! Example using ESP32 as a BLE beacon that interleaves an iBeacon frame with the Eddystone UID,
+//! URL and TLM frames, changing frame every second. The temperature of the TLM frame is read
+//! from a ds3231 using pin GPIO5 (sda) and GPIO6 (scl), and updated every 10 seconds.
+
+use std::time::{Duration, Instant};
+
+use esp32framework::{
+    ble::utils::{BeaconFrame, EddystoneFrame, EddystoneTlm, IBeacon},
+    sensors::DS3231,
+    Microcontroller,
+};
+use uuid::Uuid;
+
+const BATTERY_VOLTAGE_MV: u16 = 3300;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let i2c = micro.set_pins_for_i2c_master(5, 6).unwrap();
+    let mut ds3231 = DS3231::new(i2c);
+    let start = Instant::now();
+
+    let mut beacon = micro.ble_beacon("My Beacon".to_string(), &vec![]).unwrap();
+    beacon
+        .set_frame(BeaconFrame::IBeacon(IBeacon {
+            proximity_uuid: Uuid::parse_str("e2c56db5-dffb-48d2-b060-d0f5a71096e0").unwrap(),
+            major: 1,
+            minor: 1,
+            measured_power: -59,
+        }))
+        .unwrap()
+        .set_frame(BeaconFrame::Eddystone(EddystoneFrame::Uid {
+            tx_power: -20,
+            namespace: [0x8B, 0x0C, 0xA7, 0x50, 0xE7, 0xA7, 0x4E, 0x14, 0xBD, 0x99],
+            instance: [0, 0, 0, 0, 0, 1],
+        }))
+        .unwrap()
+        .set_frame(BeaconFrame::Eddystone(EddystoneFrame::Url {
+            tx_power: -20,
+            url: "https://github.com/".to_string(),
+        }))
+        .unwrap();
+    beacon.set_time_per_service(Duration::from_secs(1));
+    beacon.advertise_all_service_data().unwrap();
+    beacon.start().unwrap();
+
+    loop {
+        let tlm = EddystoneTlm {
+            battery_voltage: BATTERY_VOLTAGE_MV,
+            temperature: ds3231.get_temperature().ok(),
+            // The amount of advertisements is not tracked by this example
+            advertising_count: 0,
+            uptime: start.elapsed(),
+        };
+        beacon
+            .set_frame(BeaconFrame::Eddystone(EddystoneFrame::Tlm(tlm)))
+            .unwrap();
+        micro.wait_for_updates(Some(10000));
+    }
+}
@@ -0,0 +1,86 @@
+//! Example of a BLE beacon following an advertising schedule. On each round it advertises the
+//! data of a temperature service for 2 seconds, an iBeacon frame for 1 second with a fast
+//! advertising interval, and a custom advertisement for half a second. The temperature is
+//! updated every second without restarting the rotation, and after 20 seconds the custom
+//! advertisement changes its data.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::{
+        utils::{
+            AdvertisementBuilder, BeaconFrame, BeaconFrameKind, IBeacon, ScheduleContent,
+            ScheduleEntry, ScheduleTime, Service,
+        },
+        BleId,
+    },
+    Microcontroller,
+};
+use uuid::Uuid;
+
+const MANUFACTURER_ID: u16 = 0xFFFF;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::FromUuid16(0x181A);
+    let mut temperature: u8 = 20;
+    let service = Service::new(&service_id, vec![temperature]).unwrap();
+    let mut beacon = micro
+        .ble_beacon("Scheduled Beacon".to_string(), &vec![service])
+        .unwrap();
+
+    beacon
+        .set_frame(BeaconFrame::IBeacon(IBeacon {
+            proximity_uuid: Uuid::parse_str("e2c56db5-dffb-48d2-b060-d0f5a71096e0").unwrap(),
+            major: 1,
+            minor: 2,
+            measured_power: -59,
+        }))
+        .unwrap();
+    beacon.set_time_per_service(Duration::from_secs(1));
+
+    let custom_entry = beacon
+        .set_schedule(vec![
+            ScheduleEntry::new(
+                ScheduleContent::Service(service_id.clone()),
+                ScheduleTime::Weight(2),
+            ),
+            ScheduleEntry::new(
+                ScheduleContent::Frame(BeaconFrameKind::IBeacon),
+                ScheduleTime::Weight(1),
+            )
+            .interval(32, 48),
+        ])
+        .unwrap()
+        .add_schedule_entry(ScheduleEntry::new(
+            ScheduleContent::Raw(custom_advertisement(0)),
+            ScheduleTime::Fixed(Duration::from_millis(500)),
+        ))
+        .unwrap();
+
+    beacon.advertise_schedule().unwrap();
+    beacon.start().unwrap();
+
+    for second in 1..=40 {
+        micro.wait_for_updates(Some(1000));
+        temperature = 20 + second % 5;
+        let service = Service::new(&service_id, vec![temperature]).unwrap();
+        beacon.set_service(&service).unwrap();
+        if second == 20 {
+            println!("Changing the custom advertisement");
+            beacon
+                .update_schedule_entry(custom_entry, ScheduleContent::Raw(custom_advertisement(1)))
+                .unwrap();
+        }
+    }
+
+    println!("stop");
+    beacon.stop().unwrap();
+    micro.wait_for_updates(None);
+}
+
+fn custom_advertisement(version: u8) -> AdvertisementBuilder {
+    AdvertisementBuilder::new()
+        .name("Scheduled")
+        .manufacturer_data(MANUFACTURER_ID, &[version])
+}
@@ -0,0 +1,53 @@
+//! Example of a BLE client that keeps its connection to a server alive. The client connects to
+//! a server advertising the heart rate service and prints every connection, disconnection and
+//! change of the connection parameters. Once connected it asks for a slower connection to save
+//! energy. If the server is turned off, the client reconnects to it as soon as it comes back,
+//! waiting 1, 2, 4 and up to 30 seconds between attempts.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::{utils::ReconnectPolicy, BleId},
+    Microcontroller,
+};
+
+const HEART_RATE_SERVICE: u16 = 0x180D;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut client = micro.ble_client().unwrap();
+
+    client
+        .on_connect(|connection| {
+            println!("Connected to {:?}", connection.address());
+            // Between 100 ms and 200 ms, skipping up to 4 events, lost after 6 s
+            if let Err(err) = connection.update_connection_params(80, 160, 4, 600) {
+                println!("Could not update the connection parameters: {:?}", err);
+            }
+        })
+        .on_disconnect(|_, reason| println!("Disconnected, reason: {:#x}", reason))
+        .on_params_updated(|_, params| {
+            println!(
+                "Interval: {} ms, latency: {}, timeout: {} ms",
+                params.interval as f32 * 1.25,
+                params.latency,
+                params.timeout as u32 * 10
+            )
+        });
+    client.set_auto_reconnect(Some(
+        ReconnectPolicy::new().max_delay(Duration::from_secs(30)),
+    ));
+
+    println!("Looking for the server");
+    let device = client
+        .find_device_with_service(None, &BleId::FromUuid16(HEART_RATE_SERVICE))
+        .unwrap();
+    client.connect_to_device(device).unwrap();
+
+    loop {
+        micro.wait_for_updates(Some(5000));
+        if client.is_reconnecting() {
+            println!("Waiting for the server to come back");
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! This example creates a ble server offering the firmware update service, printing the progress
+//! of every transfer. Only a client paired with the passkey `PASSKEY` can send it a new image,
+//! for example another ESP32 running [DfuClient](esp32framework::ble::DfuClient::update). Once
+//! the image is verified and applied, the device reboots into it.
+//!
+//! The partition table of the device must have two OTA partitions.
+
+use esp32framework::{
+    ble::{
+        utils::{IOCapabilities, Security},
+        DfuServer,
+    },
+    Microcontroller,
+};
+
+const PASSKEY: u32 = 123456;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut security = Security::new(PASSKEY, IOCapabilities::DisplayOnly).unwrap();
+    security
+        .allow_bonding(true)
+        .man_in_the_middle(true)
+        .secure_connection(true);
+    let mut server = micro
+        .ble_secure_server("Example Dfu".to_string(), &vec![], security)
+        .unwrap();
+    let dfu = DfuServer::new(&mut server).unwrap();
+    server.start().unwrap();
+
+    let mut last_progress = None;
+    loop {
+        micro.wait_for_updates(Some(1000));
+        let progress = dfu.progress();
+        if progress != last_progress {
+            if let Some((received, size)) = progress {
+                println!("Received {} of {} bytes", received, size);
+            }
+            last_progress = progress;
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Example of the discovery of the whole GATT table of a ble server. The client connects to the
+//! device named "ESP32-Server", walks all its services, characteristics and descriptors and prints
+//! the snapshot as JSON and, in a readable form, over the UART 1. Then it reconnects to show that
+//! the snapshot is taken from the cache instead of being discovered again.
+//! The connection of the UART should be as follows:
+//! TX: Pin 16
+//! RX: Pin 17
+
+use std::time::Instant;
+
+use esp32framework::{serial::WRITER, Microcontroller};
+
+const SERVER_NAME: &str = "ESP32-Server";
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut uart = micro.set_pins_for_default_uart(16, 17, 1).unwrap();
+    let mut client = micro.ble_client().unwrap();
+
+    println!("Attempting connection");
+    let device = client
+        .find_device_of_name(None, SERVER_NAME.to_string())
+        .unwrap();
+    client.connect_to_device(device).unwrap();
+
+    let start = Instant::now();
+    let snapshot = client.discover_all().unwrap();
+    println!("Discovered in {:?}", start.elapsed());
+    println!("{}", snapshot.to_json());
+    snapshot.write_to(&mut uart).unwrap();
+
+    client.disconnect().unwrap();
+    micro.wait_for_updates(Some(1000));
+    let device = client
+        .find_device_of_name(None, SERVER_NAME.to_string())
+        .unwrap();
+    client.connect_to_device(device).unwrap();
+
+    let start = Instant::now();
+    let cached = client.discover_all().unwrap();
+    println!("Cached snapshot in {:?}", start.elapsed());
+    assert_eq!(cached, snapshot);
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
@@ -0,0 +1,102 @@
+//! Example of a device running two advertising sets at the same time: a legacy connectable set
+//! that phones can connect to, serving the services of a ble server, and a long range beacon set
+//! on the coded PHY with 300 bytes of data that also advertises periodically. The data of the
+//! beacon set changes every second without stopping it.
+//!
+//! It needs `CONFIG_BT_NIMBLE_EXT_ADV=y` and `CONFIG_BT_NIMBLE_ENABLE_PERIODIC_ADV=y` in the
+//! sdkconfig, with `CONFIG_BT_NIMBLE_MAX_EXT_ADV_INSTANCES` of at least 2, as set by
+//! `sdkconfig.ext_adv.defaults`:
+//! `ESP_IDF_SDKCONFIG_DEFAULTS="sdkconfig.defaults;sdkconfig.ext_adv.defaults" cargo run --example ble_extended_advertising_framework`
+
+#[cfg(all(esp_idf_bt_nimble_ext_adv, esp_idf_bt_nimble_enable_periodic_adv))]
+fn main() {
+    use esp32framework::{
+        ble::{
+            utils::{
+                AdvertisementBuilder, AdvertisingSet, AdvertisingSetEvent, BlePhy, Characteristic,
+                PeriodicAdvertising, Service,
+            },
+            BleId,
+        },
+        Microcontroller,
+    };
+
+    const SERVER_SET: u8 = 0;
+    const BEACON_SET: u8 = 1;
+    const COMPANY_ID: u16 = 0xFFFF;
+
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::from_name("Extended service");
+    let characteristic = Characteristic::new(&BleId::from_name("Counter"), vec![0]).readable(true);
+    let service = Service::new(&service_id, vec![])
+        .unwrap()
+        .add_characteristic(&characteristic);
+    let _server = micro
+        .ble_server("Extended Server".to_string(), &vec![service])
+        .unwrap();
+
+    let mut advertiser = micro.ble_advertiser();
+    let server_data = AdvertisementBuilder::new()
+        .service_uuid(&service_id)
+        .name("Extended Server")
+        .spill_to_scan_response(true)
+        .build()
+        .unwrap();
+    advertiser
+        .set(
+            AdvertisingSet::new(SERVER_SET)
+                .legacy(true)
+                .connectable(true)
+                .scannable(true)
+                .interval(100, 150)
+                .advertisement(&server_data),
+        )
+        .unwrap();
+
+    let beacon_data = |counter: u8| {
+        AdvertisementBuilder::new()
+            .name("Long range beacon")
+            .manufacturer_data(COMPANY_ID, &[counter; 300])
+            .build_extended()
+            .unwrap()
+    };
+    advertiser
+        .set(
+            AdvertisingSet::new(BEACON_SET)
+                .phy(BlePhy::LeCoded, BlePhy::LeCoded)
+                .interval(500, 600)
+                .include_tx_power(true)
+                .data(beacon_data(0))
+                .periodic(Some(
+                    PeriodicAdvertising::new(1000, 1000).data(vec![3, 0xFF, 0xFF, 0xFF]),
+                )),
+        )
+        .unwrap();
+
+    advertiser.event_handler(|advertiser, event| match event {
+        AdvertisingSetEvent::Connected { instance, .. } => {
+            println!("Client connected through set {}", instance);
+            // A set stops after a connection, it is started again to accept other clients
+            advertiser.start(instance).unwrap();
+        }
+        AdvertisingSetEvent::Completed { instance } => println!("Set {} completed", instance),
+    });
+    advertiser.start(SERVER_SET).unwrap();
+    advertiser.start(BEACON_SET).unwrap();
+
+    let mut counter: u8 = 0;
+    loop {
+        counter = counter.wrapping_add(1);
+        advertiser
+            .set_data(BEACON_SET, beacon_data(counter))
+            .unwrap();
+        micro.wait_for_updates(Some(1000));
+    }
+}
+
+#[cfg(not(all(esp_idf_bt_nimble_ext_adv, esp_idf_bt_nimble_enable_periodic_adv)))]
+fn main() {
+    println!(
+        "This example needs CONFIG_BT_NIMBLE_EXT_ADV and CONFIG_BT_NIMBLE_ENABLE_PERIODIC_ADV"
+    );
+}
@@ -0,0 +1,59 @@
+//! Example of a gateway that forwards the readings of nearby BLE sensors to an HTTPS server.
+//! Readings of devices advertising the environmental sensing service or whose name starts with
+//! "Sensor" are forwarded at most once every 10 seconds per device, in batches of up to 10
+//! readings. The temperature of the environmental sensing service (a sint16 in hundredths of a
+//! degree) is decoded before being sent. After a minute the gateway also starts forwarding
+//! iBeacons of Apple devices, and the rate limit is lowered to 5 seconds.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::{
+        utils::{GatewayConfig, GatewayFilter},
+        BleGateway, BleId,
+    },
+    Microcontroller,
+};
+use futures::future::join;
+use serde_json::json;
+
+const SSID: &str = "WIFI_SSID";
+const PASSWORD: &str = "WIFI_PASS";
+const URI: &str = "https://example.com/api/readings";
+const TOKEN: &str = "API_TOKEN";
+const APPLE_COMPANY_ID: u16 = 0x004C;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+
+    let mut wifi = micro.get_wifi_driver().unwrap();
+    wifi.connect(SSID, Some(PASSWORD.to_string()), None)
+        .unwrap();
+    let https = wifi.get_https_client().unwrap();
+
+    let environmental_sensing = BleId::FromUuid16(0x181A);
+    let config = GatewayConfig::new(URI)
+        .token(TOKEN)
+        .gateway_id("esp32-gateway")
+        .filter(GatewayFilter::Service(environmental_sensing.clone()))
+        .filter(GatewayFilter::NamePrefix("Sensor".to_string()))
+        .min_interval(Duration::from_secs(10))
+        .batch_size(10);
+
+    let mut client = micro.ble_client().unwrap();
+    let mut gateway = BleGateway::new(&mut client, https, config).unwrap();
+    gateway.set_decoder(&environmental_sensing, |data| {
+        let temperature = i16::from_le_bytes(data.get(..2)?.try_into().ok()?);
+        Some(json!({ "temperature": temperature as f32 / 100.0 }))
+    });
+
+    let mut timer_driver = micro.get_timer_driver().unwrap();
+    let mut reconfigured = gateway.clone();
+    micro.block_on(join(gateway.run_async(), async move {
+        timer_driver.delay(60_000).await.unwrap();
+        println!("Forwarding iBeacons too");
+        reconfigured.add_filter(GatewayFilter::Manufacturer(APPLE_COMPANY_ID));
+        reconfigured.set_min_interval(Duration::from_secs(5));
+        println!("Readings waiting to be sent: {}", reconfigured.pending());
+    }));
+}
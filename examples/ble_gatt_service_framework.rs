@@ -0,0 +1,72 @@
+//! This example creates a ble server whose service is declared with the `GattService` derive macro.
+//! The Environmental Sensing service has two characteristics:
+//! - Temperature: Readable and notifiable, in hundredths of a degree Celsius. It is read from a ds3231 using
+//!   pin GPIO5 (sda) and GPIO6 (scl) and notified every 2 seconds.
+//! - Setpoint: A custom characteristic the clients can read and write as an `f32`.
+//!
+//! Values are encoded and decoded by the framework, so there is no manual byte handling.
+
+use esp32framework::{
+    ble::{
+        utils::{
+            ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+            TypedCharacteristic,
+        },
+        GattService,
+    },
+    sensors::DS3231,
+    Microcontroller,
+};
+
+const DEGREES_CELSIUS: u16 = 0x272F;
+
+#[derive(GattService)]
+#[service(uuid = StandardServiceId::EnvironmentalSensing)]
+struct Environment {
+    #[characteristic(
+        uuid = StandardCharacteristicId::Temperature,
+        read,
+        notify,
+        description = "Temperature",
+        unit = DEGREES_CELSIUS,
+        exponent = -2
+    )]
+    temperature: TypedCharacteristic<i16>,
+    #[characteristic(
+        uuid = "c0de0001-0000-1000-8000-00805f9b34fb",
+        read,
+        write,
+        description = "Setpoint",
+        initial = 20.0
+    )]
+    setpoint: TypedCharacteristic<f32>,
+}
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let i2c = micro.set_pins_for_i2c_master(5, 6).unwrap();
+    let mut ds3231 = DS3231::new(i2c);
+
+    let mut environment = Environment::new();
+    environment.setpoint.on_write(|setpoint, _client| {
+        println!("New setpoint: {}", setpoint);
+        Ok(())
+    });
+
+    let mut server = micro
+        .ble_server(
+            "Example Gatt Service".to_string(),
+            &vec![environment.service().unwrap()],
+        )
+        .unwrap();
+    server.start().unwrap();
+
+    loop {
+        let temperature = ds3231.get_temperature().unwrap();
+        server
+            .set(&mut environment.temperature, (temperature * 100.0) as i16)
+            .unwrap();
+        println!("Setpoint: {}", server.get(&environment.setpoint).unwrap());
+        micro.wait_for_updates(Some(2000));
+    }
+}
@@ -0,0 +1,59 @@
+//! This example turns the ESP32 into a Bluetooth keyboard, mouse and media keys that a computer
+//! or phone can pair with, like any other wireless keyboard. Once a host is connected, every five
+//! seconds the example:
+//! - Types "hello from esp32" followed by a new line.
+//! - Moves the mouse pointer around a square.
+//! - Raises the volume.
+//!
+//! The battery level reported to the host decreases by 1% on every round, and the state of the
+//! caps lock LED set by the host is printed.
+
+use esp32framework::{
+    ble::{
+        profiles::{ConsumerKey, HidConfig, HidDevice},
+        utils::{IOCapabilities, Security},
+    },
+    Microcontroller,
+};
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut security = Security::new(0, IOCapabilities::NoInputNoOutput).unwrap();
+    security.allow_bonding(true).secure_connection(true);
+    let mut server = micro
+        .ble_secure_server("Example HID".to_string(), &vec![], security)
+        .unwrap();
+
+    let config = HidConfig::new()
+        .keyboard(true)
+        .mouse(true)
+        .consumer_control(true);
+    let mut hid = HidDevice::new(&mut server, &config).unwrap();
+    server
+        .set_advertisement(&hid.advertisement("Example HID"))
+        .unwrap();
+    server.start().unwrap();
+
+    let mut battery = 100;
+    loop {
+        micro.wait_for_updates(Some(5000));
+        if server.amount_of_clients() == 0 {
+            continue;
+        }
+
+        let keyboard = hid.keyboard().unwrap();
+        keyboard.type_str("hello from esp32\n").unwrap();
+        println!("Caps lock on: {}", keyboard.leds().caps_lock);
+
+        let mouse = hid.mouse().unwrap();
+        for (dx, dy) in [(200, 0), (0, 200), (-200, 0), (0, -200)] {
+            mouse.move_by(dx, dy);
+            micro.wait_for_updates(Some(200));
+        }
+
+        hid.consumer_control().unwrap().click(ConsumerKey::VolumeUp);
+
+        battery = if battery == 0 { 100 } else { battery - 1 };
+        hid.set_battery_level(battery).unwrap();
+    }
+}
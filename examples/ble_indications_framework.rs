@@ -0,0 +1,57 @@
+//! This example creates a ble server with an indicatable and notifiable counter characteristic.
+//! Every two seconds the counter is incremented and indicated to the clients subscribed to its
+//! indications, printing whether all of them confirmed it. The clients subscribed to its
+//! notifications are notified as well. Each subscription change made by a client is printed.
+
+use esp32framework::{
+    ble::{
+        utils::{Characteristic, Service},
+        BleId,
+    },
+    Microcontroller,
+};
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::FromUuid16(0x1234);
+    let mut counter = Characteristic::new(&BleId::FromUuid128([0x03; 16]), vec![0x00])
+        .readable(true)
+        .notifiable(true)
+        .indicatable(true);
+
+    let service = Service::new(&service_id, vec![])
+        .unwrap()
+        .add_characteristic(&counter);
+
+    let mut server = micro
+        .ble_server("Example Indications Server".to_string(), &vec![service])
+        .unwrap();
+    server.subscription_handler(|_, event| {
+        if event.is_subscribed() {
+            println!(
+                "The client {:?} subscribed (notify: {}, indicate: {})",
+                event.client.address, event.notify, event.indicate
+            );
+        } else {
+            println!("The client {:?} unsubscribed", event.client.address);
+        }
+    });
+    server.start().unwrap();
+
+    let mut value: u8 = 0;
+    loop {
+        micro.wait_for_updates(Some(2000));
+        value = value.wrapping_add(1);
+        counter.update_data(vec![value]);
+
+        let subscribers = server.subscribers(&service_id, &counter);
+        println!("{} clients subscribed", subscribers.len());
+        if let Err(err) = server.notify_value(&service_id, &counter) {
+            println!("Could not notify the counter: {:?}", err);
+        }
+        match server.indicate_value(&service_id, &counter) {
+            Ok(()) => println!("Indication {} confirmed", value),
+            Err(err) => println!("Indication {} not confirmed: {:?}", value, err),
+        }
+    }
+}
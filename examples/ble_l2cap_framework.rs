@@ -0,0 +1,100 @@
+//! Example of a bulk transfer over an L2CAP connection-oriented channel between two boards. Flash
+//! one with `IS_SERVER` set to true and the other with it set to false. The server advertises a
+//! service so the client can find it, and listens on a PSM. The client connects, opens a channel
+//! on that PSM and sends a 32 KB buffer, split in SDUs of the MTU of the server. Both sides print
+//! how long the transfer took. Credit based flow control makes the client wait whenever the
+//! server has no room for more SDUs, so no data is lost.
+
+use std::time::Instant;
+
+use esp32framework::{
+    ble::{utils::Service, BleId, L2capChannel},
+    Microcontroller,
+};
+
+const IS_SERVER: bool = true;
+const PSM: u16 = 0x0080;
+const MTU: u16 = 2048;
+const TRANSFER_SIZE: usize = 32 * 1024;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::from_name("L2CAP service");
+
+    if IS_SERVER {
+        run_server(&mut micro, &service_id)
+    } else {
+        run_client(&mut micro, &service_id)
+    }
+}
+
+fn run_server(micro: &mut Microcontroller, service_id: &BleId) {
+    let service = Service::new(service_id, vec![]).unwrap();
+    let mut server = micro
+        .ble_server("L2CAP Server".to_string(), &vec![service])
+        .unwrap();
+    let mut listener = server.l2cap_listen(PSM, MTU).unwrap();
+    server.start().unwrap();
+    println!("Listening on PSM {:#06x}", listener.psm());
+
+    micro.block_on(async {
+        loop {
+            let mut channel = listener.accept_async().await;
+            println!(
+                "Channel opened, receiving SDUs of up to {} bytes",
+                channel.mtu()
+            );
+            receive_transfer(&mut channel).await;
+        }
+    });
+}
+
+async fn receive_transfer(channel: &mut L2capChannel) {
+    let start = Instant::now();
+    let mut received = 0;
+    while received < TRANSFER_SIZE {
+        match channel.receive_async().await {
+            Ok(sdu) => received += sdu.len(),
+            Err(err) => {
+                println!("Channel closed after {} bytes: {:?}", received, err);
+                return;
+            }
+        }
+    }
+    print_throughput("Received", received, start);
+}
+
+fn run_client(micro: &mut Microcontroller, service_id: &BleId) {
+    let mut client = micro.ble_client().unwrap();
+    println!("Looking for the server");
+    let device = client.find_device_with_service(None, service_id).unwrap();
+    client.connect_to_device(device).unwrap();
+
+    let data: Vec<u8> = (0..TRANSFER_SIZE).map(|i| i as u8).collect();
+    micro.block_on(async {
+        let mut channel = client.l2cap_connect_async(PSM, MTU).await.unwrap();
+        println!(
+            "Channel opened, sending SDUs of up to {} bytes",
+            channel.peer_mtu()
+        );
+        let start = Instant::now();
+        channel.send_all_async(&data).await.unwrap();
+        print_throughput("Sent", data.len(), start);
+        channel.disconnect().unwrap();
+    });
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
+
+fn print_throughput(action: &str, bytes: usize, start: Instant) {
+    let seconds = start.elapsed().as_secs_f32();
+    println!(
+        "{} {} bytes in {:.2} s ({:.1} KB/s)",
+        action,
+        bytes,
+        seconds,
+        bytes as f32 / 1024.0 / seconds
+    );
+}
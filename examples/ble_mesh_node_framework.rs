@@ -0,0 +1,82 @@
+//! Example of a BLE Mesh node for building automation. The primary element has a Generic OnOff
+//! Server bound to the led on GPIO 2, a Generic OnOff Client and a vendor model that answers the
+//! uptime of the node. A second element has a Generic Level Server that dims the led on GPIO 4.
+//! The node is provisioned through PB-GATT, so a phone app like nRF Mesh can add it to a network,
+//! showing a 4 digits number on the console to authenticate it, and rejoins that network after a
+//! restart. Once the provisioner configures the publication of the client, the node toggles the
+//! lights of that address every 10 seconds.
+
+use std::time::{Duration, Instant};
+
+use esp32framework::{
+    ble_mesh::{
+        AccessMessage, GenericStatus, MeshNodeConfig, Opcode, ProvisioningAuth, ProvisioningBearer,
+    },
+    Microcontroller,
+};
+
+const COMPANY_ID: u16 = 0x02E5;
+const UPTIME_MODEL_ID: u16 = 0x0001;
+const UPTIME_GET: u8 = 0x01;
+const UPTIME_STATUS: u8 = 0x02;
+const LOCATION_SECOND: u16 = 0x0002;
+const UUID: [u8; 16] = [
+    0x70, 0xCF, 0x7C, 0x97, 0x32, 0xA3, 0x45, 0xB6, 0x91, 0x49, 0x48, 0x10, 0xD2, 0xE9, 0xCB, 0xF4,
+];
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let led = micro.set_pin_as_digital_out(2).unwrap();
+    let dimmer = micro.set_pin_as_default_analog_out(4).unwrap();
+
+    let config = MeshNodeConfig::new(UUID)
+        .name("Mesh Light")
+        .bearer(ProvisioningBearer::Gatt)
+        .auth(ProvisioningAuth::OutputNumeric { digits: 4 });
+    let mut node = micro.ble_mesh_node(config).unwrap();
+
+    let start = Instant::now();
+    node.bind_onoff_server(0, led).unwrap();
+    node.add_onoff_client(0).unwrap();
+    node.add_vendor_model(0, COMPANY_ID, UPTIME_MODEL_ID, move |context, message| {
+        if message.opcode != Opcode::vendor(UPTIME_GET, COMPANY_ID) {
+            return None;
+        }
+        println!("Uptime asked by {:04X}", context.src);
+        let uptime = start.elapsed().as_secs() as u32;
+        Some(AccessMessage::new(
+            Opcode::vendor(UPTIME_STATUS, COMPANY_ID),
+            &uptime.to_le_bytes(),
+        ))
+    })
+    .unwrap();
+    let second = node.add_element(LOCATION_SECOND).unwrap();
+    node.bind_level_server(second, dimmer).unwrap();
+
+    node.on_output_number(|number| println!("Enter {number} in the provisioner"))
+        .on_provisioned(|address| println!("Provisioned with address {address:04X}"))
+        .on_reset(|| println!("Removed from the network"))
+        .on_generic_status(|context, status| match status {
+            GenericStatus::OnOff(on) => println!("Light {:04X} is on: {on}", context.src),
+            GenericStatus::Level(level) => println!("Light {:04X} level: {level}", context.src),
+        });
+    node.start().unwrap();
+    // The node keeps its network in the NVS, so after a restart it does not wait to be provisioned
+    if let Some(address) = node.address() {
+        println!("Rejoined the network with address {address:04X}");
+    }
+
+    let mut on = false;
+    let mut last_toggle = Instant::now();
+    loop {
+        micro.wait_for_updates(Some(1000));
+        if !node.is_provisioned() || last_toggle.elapsed() < Duration::from_secs(10) {
+            continue;
+        }
+        last_toggle = Instant::now();
+        on = !on;
+        if let Err(err) = node.onoff_set(0, on, true) {
+            println!("Could not toggle the lights: {err:?}");
+        }
+    }
+}
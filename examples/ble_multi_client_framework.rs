@@ -0,0 +1,57 @@
+//! Example of a ble client connected to many servers at the same time. The client looks for up
+//! to `MAX_SENSORS` devices advertising the Environmental Sensing service (0x181A), opens a
+//! connection to each one and prints every temperature notification received, tagged with the
+//! address of the sensor. When a sensor disconnects, its connection is reported and the
+//! remaining ones keep working.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::{
+        utils::ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+        BleConnection, BleId,
+    },
+    Microcontroller,
+};
+
+const MAX_SENSORS: usize = 3;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut client = micro.ble_client().unwrap();
+    let service_id = BleId::from_standard_service(StandardServiceId::EnvironmentalSensing);
+    let temperature_id = BleId::from_standard_characteristic(StandardCharacteristicId::Temperature);
+
+    let mut sensors: Vec<BleConnection> = vec![];
+    let mut addresses = vec![];
+    while sensors.len() < MAX_SENSORS {
+        let device = match client.find_device(Some(Duration::from_secs(10)), |adv| {
+            adv.is_advertising_service(&service_id) && !addresses.contains(adv.addr())
+        }) {
+            Ok(device) => device,
+            Err(_) => break,
+        };
+        let address = *device.addr();
+        let mut connection = client.connect(device).unwrap();
+        addresses.push(address);
+
+        let mut temperature = connection
+            .get_characteristic(&service_id, &temperature_id)
+            .unwrap();
+        temperature
+            .on_notify(move |data| {
+                let value = i16::from_le_bytes([data[0], data[1]]) as f32 / 100.0;
+                println!("Sensor {:?}: {} °C", address, value);
+            })
+            .unwrap();
+        connection.on_disconnect(move |_, reason| {
+            println!("Sensor {:?} disconnected, reason: {}", address, reason)
+        });
+        sensors.push(connection);
+    }
+    println!("Connected to {} sensors", sensors.len());
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
@@ -0,0 +1,35 @@
+//! This example creates a ble server with the Nordic UART Service, so any BLE terminal app (like
+//! nRF Connect or nRF Toolbox) can be used as a serial console. Every line received is answered:
+//! - Lines with `key=value` pairs (like `led=on, speed=3`) are parsed, and each pair is sent back.
+//! - Any other line is sent back in uppercase.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::NordicUartServer,
+    serial::{parse_key_values, WRITER},
+    Microcontroller,
+};
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut server = micro
+        .ble_server("Example Uart".to_string(), &vec![])
+        .unwrap();
+    let mut uart = NordicUartServer::new(&mut server).unwrap();
+    server.start().unwrap();
+
+    loop {
+        if let Some(line) = uart.read_line(Some(Duration::ZERO)) {
+            let pairs = parse_key_values(&line);
+            if pairs.is_empty() {
+                uart.write_line(&line.to_uppercase()).unwrap();
+            }
+            for (key, value) in pairs {
+                uart.parse_and_write(0, format!("{key} -> {value}\r\n").as_bytes())
+                    .unwrap();
+            }
+        }
+        micro.wait_for_updates(Some(100));
+    }
+}
@@ -0,0 +1,39 @@
+//! Example of a continuous ble scan used for presence detection. The client scans passively,
+//! reporting each device at most once per second, and a PresenceTracker prints when a device
+//! appears or disappears, together with its smoothed RSSI. Devices not seen for 30 seconds are
+//! considered gone.
+
+use std::time::Duration;
+
+use esp32framework::{
+    ble::{
+        utils::{PresenceEvent, PresenceTracker},
+        ScanConfig,
+    },
+    Microcontroller,
+};
+use futures::StreamExt;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut client = micro.ble_client().unwrap();
+    let config = ScanConfig::new()
+        .active(false)
+        .filter_duplicates(Some(Duration::from_secs(1)));
+    let mut stream = client.scan_stream(config).unwrap();
+    let mut tracker = PresenceTracker::new(Duration::from_secs(30), 0.3);
+
+    micro.block_on(async {
+        while let Some(device) = stream.next().await {
+            if let Some(PresenceEvent::Appeared { address, rssi }) = tracker.track(&device) {
+                println!("{:?} ({}) appeared, rssi: {}", address, device.name(), rssi);
+            }
+            for event in tracker.check_timeouts() {
+                if let PresenceEvent::Disappeared { address } = event {
+                    println!("{:?} disappeared", address);
+                }
+            }
+            println!("{} devices present", tracker.present().len());
+        }
+    });
+}
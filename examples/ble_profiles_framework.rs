@@ -0,0 +1,78 @@
+//! This example creates a ble server with the standard Battery, Device Information, Environmental Sensing
+//! and Heart Rate services, so generic client apps (like nRF Connect) show their values without any
+//! configuration. The values are simulated and updated every second:
+//! - Battery level: Decreases by 1% every update, starting again from 100% when it reaches 0%.
+//! - Temperature, humidity and pressure: Fixed values with a small variation.
+//! - Heart rate: A measurement with its RR interval is notified on every update.
+
+use esp32framework::{
+    ble::{
+        profiles::{
+            BatteryService, BodySensorLocation, DeviceInformationService,
+            EnvironmentalSensingService, HeartRateMeasurement, HeartRateService, PnpId,
+            VendorIdSource,
+        },
+        GattService,
+    },
+    Microcontroller,
+};
+
+fn main() {
+    let mut micro = Microcontroller::take();
+
+    let mut battery = BatteryService::new();
+    let mut environment = EnvironmentalSensingService::new();
+    let mut heart_rate = HeartRateService::new_with_location(BodySensorLocation::Wrist);
+    let device_information = DeviceInformationService::new()
+        .manufacturer_name("Espressif")
+        .model_number("ESP32-C6")
+        .firmware_revision(env!("CARGO_PKG_VERSION"))
+        .pnp_id(PnpId {
+            vendor_id_source: VendorIdSource::BluetoothSig,
+            vendor_id: 0x02E5,
+            product_id: 0x0001,
+            product_version: 0x0100,
+        });
+
+    let mut server = micro
+        .ble_server("Example Profiles".to_string(), &vec![])
+        .unwrap();
+    server.set_service(&battery.service().unwrap()).unwrap();
+    server
+        .set_service(&device_information.service().unwrap())
+        .unwrap();
+    server.set_service(&environment.service().unwrap()).unwrap();
+    server.set_service(&heart_rate.service().unwrap()).unwrap();
+    server.start().unwrap();
+
+    let mut tick: u32 = 0;
+    loop {
+        let variation = (tick % 10) as f32 / 10.0;
+        battery
+            .set_level(&mut server, 100 - (tick % 101) as u8)
+            .unwrap();
+        environment
+            .set_temperature(&mut server, 21.5 + variation)
+            .unwrap();
+        environment
+            .set_humidity(&mut server, 45.5 + variation)
+            .unwrap();
+        environment.set_pressure(&mut server, 101325.0).unwrap();
+
+        let bpm = 70 + (tick % 10) as u16;
+        let measurement = HeartRateMeasurement {
+            bpm,
+            sensor_contact: Some(true),
+            energy_expended: None,
+            rr_intervals: vec![HeartRateMeasurement::rr_interval_from_millis(
+                60_000 / bpm as u32,
+            )],
+        };
+        heart_rate
+            .notify_measurement(&mut server, measurement)
+            .unwrap();
+
+        tick += 1;
+        micro.wait_for_updates(Some(1000));
+    }
+}
@@ -0,0 +1,42 @@
+//! Example of a ble client pairing with a secure server, like the one of ble_server_framework.
+//! The client has a keyboard, so when the server displays its passkey the client answers with
+//! `PASSKEY`. Once paired, the server is bonded, so later connections restore the encryption
+//! without asking for the passkey again. The bonded servers are listed, and if the pairing
+//! fails every bond is deleted to start from scratch on the next run.
+
+use esp32framework::{
+    ble::{
+        utils::{IOCapabilities, Security},
+        BleId,
+    },
+    Microcontroller,
+};
+
+const PASSKEY: u32 = 1234;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut security = Security::new(0, IOCapabilities::KeyboardOnly).unwrap();
+    security
+        .allow_bonding(true)
+        .man_in_the_middle(true)
+        .secure_connection(true);
+    let mut client = micro.ble_secure_client(security).unwrap();
+    client.on_passkey_request(|| PASSKEY);
+
+    let service_id = BleId::FromUuid16(0x180F);
+    let device = client.find_device_with_service(None, &service_id).unwrap();
+    client.connect_to_device(device).unwrap();
+
+    match client.secure_connection() {
+        Ok(_) => println!("Paired, bonded servers: {:?}", client.bonded_addresses()),
+        Err(err) => {
+            println!("Pairing failed: {:?}, deleting bonds", err);
+            client.clear_bonds().unwrap();
+        }
+    }
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
@@ -0,0 +1,59 @@
+//! This example creates a ble server with one service and two characteristics that react to the clients:
+//! - Writable characteristic: Controls a led in pin GPIO15. Writing 0x00 turns it off and 0x01 turns it on,
+//!   any other value is rejected with a "Value not allowed" ATT error.
+//! - Readable characteristic: Each time a client reads it, its value is produced with the amount of
+//!   writes received so far.
+//!
+//! Both callbacks are executed on the main loop, so they can use any driver.
+
+use std::{cell::RefCell, rc::Rc};
+
+use esp32framework::{
+    ble::{
+        utils::{AttErrorCode, Characteristic, Service},
+        BleId,
+    },
+    Microcontroller,
+};
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut led = micro.set_pin_as_digital_out(15).unwrap();
+    let writes = Rc::new(RefCell::new(0_u32));
+
+    let writes_ref = writes.clone();
+    let led_characteristic = Characteristic::new(&BleId::FromUuid128([0x01; 16]), vec![0x00])
+        .writable(true)
+        .on_write(move |value, client| {
+            let result = match value {
+                [0x00] => led.set_low(),
+                [0x01] => led.set_high(),
+                _ => return Err(AttErrorCode::ValueNotAllowed),
+            };
+            result.map_err(|_| AttErrorCode::UnlikelyError)?;
+            *writes_ref.borrow_mut() += 1;
+            println!("The client {:?} wrote {:?}", client.address, value);
+            Ok(())
+        });
+
+    let counter_characteristic = Characteristic::new(&BleId::FromUuid128([0x02; 16]), vec![])
+        .readable(true)
+        .on_read(move |client| {
+            println!("The client {:?} read the counter", client.address);
+            writes.borrow().to_le_bytes().to_vec()
+        });
+
+    let service = Service::new(&BleId::FromUuid16(0x1234), vec![])
+        .unwrap()
+        .add_characteristic(&led_characteristic)
+        .add_characteristic(&counter_characteristic);
+
+    let mut server = micro
+        .ble_server("Example Callbacks Server".to_string(), &vec![service])
+        .unwrap();
+    server.start().unwrap();
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
@@ -0,0 +1,63 @@
+//! Example of a ble server tuned for bulk transfers. When a client connects, the server asks for
+//! a bigger MTU, enables the Data Length Extension and prefers the 2M PHY, printing the values
+//! negotiated with the client as they change. Then it notifies a 200 byte characteristic as fast
+//! as the link allows.
+
+use esp32framework::{
+    ble::{
+        utils::{BlePhy, Characteristic, CodedPhyScheme, LinkChange, Service},
+        BleId,
+    },
+    Microcontroller,
+};
+
+const MTU: u16 = 247;
+const DATA_LENGTH: u16 = 251;
+const PAYLOAD_SIZE: usize = 200;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let service_id = BleId::from_name("Throughput service");
+    let mut characteristic =
+        Characteristic::new(&BleId::from_name("Throughput data"), vec![0; PAYLOAD_SIZE])
+            .notifiable(true);
+    let service = Service::new(&service_id, vec![])
+        .unwrap()
+        .add_characteristic(&characteristic);
+
+    let mut server = micro
+        .ble_server("Throughput Server".to_string(), &vec![service])
+        .unwrap();
+
+    server.connection_handler(|server, info| {
+        println!(
+            "Client {:?} connected with an MTU of {}",
+            info.address, info.mtu
+        );
+        if let Err(err) = server.request_mtu(info, MTU) {
+            println!("MTU not requested: {:?}", err);
+        }
+        server.set_data_length(info, DATA_LENGTH).unwrap();
+        server
+            .set_preferred_phy(info, BlePhy::Le2M, BlePhy::Le2M, CodedPhyScheme::Any)
+            .unwrap();
+    });
+    server.link_change_handler(|_server, info, change| match change {
+        LinkChange::Mtu(mtu) => println!("MTU: {}", mtu),
+        LinkChange::DataLength { .. } => println!(
+            "Data length: {} bytes sent, {} bytes received",
+            info.max_tx_octets, info.max_rx_octets
+        ),
+        LinkChange::Phy { tx_phy, rx_phy } => println!("PHY: {:?} / {:?}", tx_phy, rx_phy),
+        LinkChange::ConnectionParams(params) => println!("Interval: {}", params.interval),
+    });
+    server.start().unwrap();
+
+    let mut counter: u8 = 0;
+    loop {
+        characteristic.update_data(vec![counter; PAYLOAD_SIZE]);
+        _ = server.notify_value(&service_id, &characteristic);
+        counter = counter.wrapping_add(1);
+        micro.wait_for_updates(Some(10));
+    }
+}
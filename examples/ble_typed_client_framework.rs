@@ -0,0 +1,55 @@
+//! Example of a ble client using typed reads and the notification lifecycle. The client connects
+//! to a server with the Environmental Sensing service (0x181A), reads its temperature as an `i16`
+//! in hundredths of a degree and subscribes to its notifications. After ten notifications it
+//! unsubscribes. If the server goes away while subscribed, the error callback is executed.
+
+use std::{cell::Cell, rc::Rc};
+
+use esp32framework::{
+    ble::{BleError, BleId},
+    Microcontroller,
+};
+
+const NOTIFICATIONS: u32 = 10;
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let mut client = micro.ble_client().unwrap();
+    let service_id = BleId::FromUuid16(0x181A);
+    let temperature_id = BleId::FromUuid16(0x2A6E);
+
+    println!("Attempting connection");
+    let device = client.find_device_with_service(None, &service_id).unwrap();
+    client.connect_to_device(device).unwrap();
+    println!("Connected");
+
+    let mut temperature = client
+        .get_characteristic(&service_id, &temperature_id)
+        .unwrap();
+    let value: i16 = temperature.read_as().unwrap();
+    println!("Temperature: {} °C", value as f32 / 100.0);
+
+    let received = Rc::new(Cell::new(0));
+    let received_ref = received.clone();
+    temperature
+        .on_notify(move |data| {
+            received_ref.set(received_ref.get() + 1);
+            if let Ok(bytes) = data.try_into() {
+                println!("Notified: {} °C", i16::from_le_bytes(bytes) as f32 / 100.0);
+            }
+        })
+        .unwrap();
+    temperature.on_error(|err: BleError| println!("Subscription lost: {:?}", err));
+
+    while received.get() < NOTIFICATIONS && temperature.is_subscribed() {
+        micro.wait_for_updates(Some(1000));
+    }
+    if temperature.is_subscribed() {
+        temperature.unsubscribe().unwrap();
+        println!("Unsubscribed after {} notifications", NOTIFICATIONS);
+    }
+
+    loop {
+        micro.wait_for_updates(None);
+    }
+}
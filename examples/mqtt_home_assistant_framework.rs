@@ -0,0 +1,57 @@
+//! Example on how to expose framework drivers to Home Assistant using MQTT discovery.
+//! After connecting to wifi, the device connects to the broker Home Assistant uses and announces:
+//! - A switch that controls a led in pin GPIO15 (DigitalOut).
+//! - A dimmable light that controls a led in pin GPIO3 (AnalogOut).
+//! - A binary sensor with the state of a button in pin GPIO9 (DigitalIn).
+//! - A temperature sensor read from a ds3231 using pin GPIO5 (sda) and GPIO6 (scl).
+//!
+//! The states of the sensors are published every 5 seconds. If the device disconnects, Home Assistant
+//! will show every entity as unavailable thanks to the last will of the connection.
+
+use esp32framework::{
+    sensors::DS3231,
+    wifi::mqtt::{HomeAssistantDevice, MqttConfiguration},
+    Microcontroller,
+};
+
+const SSID: &str = "WIFI_SSID";
+const PASSWORD: &str = "WIFI_PASS";
+const BROKER_URI: &str = "mqtt://192.168.0.10:1883";
+const BROKER_USER: &str = "MQTT_USER";
+const BROKER_PASS: &str = "MQTT_PASS";
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let led = micro.set_pin_as_digital_out(15).unwrap();
+    let dimmable_led = micro.set_pin_as_default_analog_out(3).unwrap();
+    let button = micro.set_pin_as_digital_in(9).unwrap();
+    let i2c = micro.set_pins_for_i2c_master(5, 6).unwrap();
+    let ds3231 = DS3231::new(i2c);
+
+    let mut wifi = micro.get_wifi_driver().unwrap();
+    wifi.connect(SSID, Some(PASSWORD.to_string()), None)
+        .unwrap();
+
+    let configuration =
+        MqttConfiguration::new("esp32_living_room").set_credentials(BROKER_USER, BROKER_PASS);
+    let mut device = HomeAssistantDevice::new(
+        &mut micro,
+        BROKER_URI,
+        configuration,
+        "esp32_living_room",
+        "Living room",
+    )
+    .unwrap();
+
+    device.add_switch("Led", led).unwrap();
+    device.add_light("Dimmable led", dimmable_led).unwrap();
+    device.add_binary_sensor("Button", None, button).unwrap();
+    device
+        .add_ds3231_temperature_sensor("Temperature", ds3231)
+        .unwrap();
+
+    loop {
+        micro.wait_for_updates(Some(5000));
+        device.publish_states().unwrap();
+    }
+}
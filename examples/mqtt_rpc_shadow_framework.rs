@@ -0,0 +1,85 @@
+//! Example on how to use remote procedure calls and a device shadow over MQTT.
+//! After connecting to wifi, the device connects to the broker and:
+//! - Registers the procedures `set_led` (params `{"on": bool}`) and `uptime`.
+//! - Keeps a shadow with the state of a led in pin GPIO15 (DigitalOut). When the desired state
+//!   changes, the led is updated and the new state is reported.
+//!
+//! Every 10 seconds the device calls its own `uptime` procedure through the broker.
+//!
+//! It can be tested end to end against a local broker with the mosquitto clients:
+//! - `mosquitto_sub -t 'esp32framework/#' -v` to see every message.
+//! - `mosquitto_pub -t esp32framework/esp32_desk/rpc/request -m '{"id":"1","method":"set_led","params":{"on":true},"reply_to":"test/reply"}'`
+//!   calls a procedure, the response is published in `test/reply`.
+//! - `mosquitto_pub -r -t esp32framework/esp32_desk/shadow/desired -m '{"led":false}'` changes
+//!   the desired state.
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use esp32framework::{
+    gpio::digital::{DigitalOut, DigitalOutError},
+    wifi::mqtt::{DeviceShadow, MqttConfiguration, RpcClient, RpcError, RpcServer},
+    Microcontroller,
+};
+use serde_json::{json, Value};
+
+const SSID: &str = "WIFI_SSID";
+const PASSWORD: &str = "WIFI_PASS";
+const BROKER_URI: &str = "mqtt://192.168.0.10:1883";
+const DEVICE_ID: &str = "esp32_desk";
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let led = Rc::new(RefCell::new(micro.set_pin_as_digital_out(15).unwrap()));
+    let timer_driver = micro.get_timer_driver().unwrap();
+
+    let mut wifi = micro.get_wifi_driver().unwrap();
+    wifi.connect(SSID, Some(PASSWORD.to_string()), None)
+        .unwrap();
+
+    let mqtt = micro
+        .mqtt_client(BROKER_URI, &MqttConfiguration::new(DEVICE_ID))
+        .unwrap();
+
+    let mut server = RpcServer::new(&mqtt, DEVICE_ID).unwrap();
+    let mut shadow = DeviceShadow::new(&mqtt, DEVICE_ID).unwrap();
+    shadow.report(&json!({ "led": false })).ok();
+
+    let mut shadow_ref = shadow.clone();
+    let rpc_led = led.clone();
+    server.register("set_led", move |params| {
+        let on = params
+            .get("on")
+            .and_then(Value::as_bool)
+            .ok_or_else(|| RpcError::invalid_params("Expected {\"on\": bool}"))?;
+        set_led(&mut rpc_led.borrow_mut(), on)
+            .map_err(|_| RpcError::internal("Could not set led"))?;
+        shadow_ref.report(&json!({ "led": on })).ok();
+        Ok(json!({ "led": on }))
+    });
+
+    let start = std::time::Instant::now();
+    server.register("uptime", move |_| Ok(json!(start.elapsed().as_secs())));
+
+    let shadow_led = led;
+    shadow.on_delta(move |delta| {
+        let on = delta.get("led")?.as_bool()?;
+        set_led(&mut shadow_led.borrow_mut(), on).ok()?;
+        Some(json!({ "led": on }))
+    });
+
+    let mut client = RpcClient::new(&mqtt, timer_driver, "esp32_desk_client").unwrap();
+    loop {
+        micro.wait_for_updates(Some(10000));
+        match client.call(DEVICE_ID, "uptime", Value::Null, Duration::from_secs(2)) {
+            Ok(uptime) => println!("Uptime: {} seconds", uptime),
+            Err(err) => println!("Rpc call failed: {:?}", err),
+        }
+    }
+}
+
+fn set_led(led: &mut DigitalOut, on: bool) -> Result<(), DigitalOutError> {
+    match on {
+        true => led.set_high(),
+        false => led.set_low(),
+    }
+}
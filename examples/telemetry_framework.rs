@@ -0,0 +1,60 @@
+//! Example using pin GPIO5 (sda) and GPIO6 (scl) with i2c to communicate with a ds3231 sensor.
+//! Every 10 seconds the temperature of the sensor is read and timestamped with the ds3231 clock,
+//! then it is pushed to an InfluxDB server as a point and to a Prometheus Pushgateway as a gauge.
+//! Points are batched, so they are only sent to InfluxDB every 6 readings.
+
+use esp32framework::{
+    sensors::DS3231,
+    telemetry::{InfluxDbConfig, InfluxDbPusher, Metric, Point, PrometheusPusher, TimestampSource},
+    Microcontroller,
+};
+
+const SSID: &str = "WIFI_SSID";
+const PASSWORD: &str = "WIFI_PASS";
+const INFLUX_URL: &str = "https://influx.example.com:8086";
+const INFLUX_TOKEN: &str = "INFLUX_TOKEN";
+const PUSHGATEWAY_URL: &str = "http://pushgateway.example.com:9091";
+
+fn main() {
+    let mut micro = Microcontroller::take();
+    let i2c = micro.set_pins_for_i2c_master(5, 6).unwrap();
+    let mut ds3231 = DS3231::new(i2c);
+
+    let mut wifi = micro.get_wifi_driver().unwrap();
+    wifi.connect(SSID, Some(PASSWORD.to_string()), None)
+        .unwrap();
+
+    let config = InfluxDbConfig {
+        url: INFLUX_URL.to_string(),
+        org: String::from("home"),
+        bucket: String::from("sensors"),
+        token: Some(INFLUX_TOKEN.to_string()),
+    };
+    let mut influx =
+        InfluxDbPusher::new_with_batch_size(wifi.get_https_client().unwrap(), config, 6);
+    let mut prometheus =
+        PrometheusPusher::new(wifi.get_http_client().unwrap(), PUSHGATEWAY_URL, "esp32")
+            .with_grouping_label("instance", "kitchen");
+
+    loop {
+        let temperature = ds3231.get_temperature().unwrap();
+        let timestamp = ds3231.now_ns().unwrap();
+
+        let point = Point::new("temperature")
+            .tag("room", "kitchen")
+            .field("celsius", temperature)
+            .timestamp(timestamp);
+        if let Err(err) = influx.add(point) {
+            println!("Error pushing to InfluxDB: {:?}", err);
+        }
+
+        let metric = Metric::gauge("temperature_celsius", temperature as f64)
+            .help("Temperature read by the DS3231")
+            .label("room", "kitchen");
+        if let Err(err) = prometheus.add(metric).and_then(|_| prometheus.flush()) {
+            println!("Error pushing to the Pushgateway: {:?}", err);
+        }
+
+        micro.wait_for_updates(Some(10000));
+    }
+}
@@ -0,0 +1,356 @@
+extern crate proc_macro2;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Expr,
+    ExprLit, Fields, Ident, Lit, LitStr,
+};
+
+/// Property flags accepted by the `characteristic` attribute, with the `Characteristic` method
+/// that sets each one
+const PROPERTIES: [(&str, &str); 12] = [
+    ("read", "readable"),
+    ("read_enc", "readable_enc"),
+    ("read_authen", "readable_authen"),
+    ("read_author", "readable_author"),
+    ("write", "writable"),
+    ("write_no_rsp", "writable_no_rsp"),
+    ("write_enc", "writable_enc"),
+    ("write_authen", "writable_authen"),
+    ("write_author", "writable_author"),
+    ("notify", "notifiable"),
+    ("indicate", "indicatable"),
+    ("broadcast", "broadcastable"),
+];
+
+/// This macro derives `GattService` for a struct whose fields are `TypedCharacteristic`s, and
+/// creates a `new` method (and a `Default` implementation) that builds every characteristic.
+///
+/// The struct must have a `#[service(uuid = ...)]` attribute, and every field a
+/// `#[characteristic(uuid = ..., ...)]` attribute. The uuids can be:
+/// - An integer literal, for 16 or 32 bit uuids (e.g. `0x181A`).
+/// - A string literal, for 128 bit uuids (e.g. `"c0de0001-0000-1000-8000-00805f9b34fb"`).
+/// - A path to a standard id (e.g. `StandardServiceId::EnvironmentalSensing` on the service, or
+///   `StandardCharacteristicId::Temperature` on a characteristic).
+///
+/// The `characteristic` attribute also accepts:
+/// - The properties `read`, `read_enc`, `read_authen`, `read_author`, `write`, `write_no_rsp`,
+///   `write_enc`, `write_authen`, `write_author`, `notify`, `indicate` and `broadcast`.
+/// - `initial = expr`: The initial value, by default the `Default` of the type.
+/// - `description = "..."`: Adds a characteristic user description descriptor.
+/// - `unit = expr`, `exponent = expr` or `presentation`: Adds a characteristic presentation format
+///   descriptor with the given unit (unitless by default) and exponent (0 by default).
+///
+/// # Example
+///
+/// ```
+/// #[derive(GattService)]
+/// #[service(uuid = 0x181A)]
+/// struct Environment {
+///     #[characteristic(uuid = 0x2A6E, read, notify, description = "Temperature")]
+///     temperature: TypedCharacteristic<f32>,
+///     #[characteristic(uuid = "c0de0001-0000-1000-8000-00805f9b34fb", read, write, initial = 20.0)]
+///     setpoint: TypedCharacteristic<f32>,
+/// }
+/// ```
+///
+/// # Once expanded the following will be added
+///
+/// ```
+/// impl Environment {
+///     pub fn new() -> Self {
+///         let service_id = ::esp32framework::ble::BleId::FromUuid16(0x181A);
+///         Self {
+///             temperature: ::esp32framework::ble::utils::TypedCharacteristic::new(
+///                 service_id.clone(),
+///                 ::esp32framework::ble::utils::Characteristic::new(
+///                     &::esp32framework::ble::BleId::FromUuid16(0x2A6E),
+///                     ::std::vec::Vec::new(),
+///                 )
+///                 .readable(true)
+///                 .notifiable(true),
+///                 ::core::default::Default::default(),
+///             )
+///             .with_description("Temperature"),
+///             setpoint: /* ... */,
+///         }
+///     }
+/// }
+///
+/// impl ::core::default::Default for Environment { /* calls new */ }
+///
+/// impl ::esp32framework::ble::utils::GattService for Environment {
+///     fn service_id(&self) -> ::esp32framework::ble::BleId { /* ... */ }
+///     fn characteristics(&self) -> Vec<::esp32framework::ble::utils::Characteristic> { /* ... */ }
+/// }
+/// ```
+#[proc_macro_derive(GattService, attributes(service, characteristic))]
+pub fn derive_gatt_service(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match expand_gatt_service(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+/// Kind of attribute whose uuid is being parsed, used to know how to convert standard ids
+#[derive(Clone, Copy)]
+enum UuidContext {
+    Service,
+    Characteristic,
+}
+
+/// Information of a field parsed from its `characteristic` attribute
+struct CharacteristicField {
+    ident: Ident,
+    uuid: TokenStream2,
+    properties: Vec<Ident>,
+    initial: Option<Expr>,
+    description: Option<LitStr>,
+    unit: Option<Expr>,
+    exponent: Option<Expr>,
+    presentation: bool,
+}
+
+fn expand_gatt_service(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "GattService can not be derived for generic structs",
+        ));
+    }
+    let service_uuid = parse_service_attribute(&input.attrs, input.span())?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "GattService can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "GattService can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut characteristics = Vec::new();
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        let attribute = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("characteristic"))
+            .ok_or_else(|| {
+                syn::Error::new(
+                    field.span(),
+                    "every field must have a #[characteristic(...)] attribute",
+                )
+            })?;
+        characteristics.push(parse_characteristic_attribute(ident, attribute)?);
+    }
+
+    let initializers = characteristics.iter().map(characteristic_initializer);
+    let idents = characteristics.iter().map(|c| &c.ident);
+
+    Ok(quote! {
+        impl #name {
+            /// Creates the service with every characteristic on its initial value
+            pub fn new() -> Self {
+                let service_id = #service_uuid;
+                Self {
+                    #(#initializers),*
+                }
+            }
+        }
+
+        impl ::core::default::Default for #name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl ::esp32framework::ble::utils::GattService for #name {
+            fn service_id(&self) -> ::esp32framework::ble::BleId {
+                #service_uuid
+            }
+
+            fn characteristics(&self) -> ::std::vec::Vec<::esp32framework::ble::utils::Characteristic> {
+                ::std::vec![#(self.#idents.characteristic().clone()),*]
+            }
+        }
+    })
+}
+
+/// Gets the uuid of the `service` attribute of the struct
+fn parse_service_attribute(
+    attrs: &[Attribute],
+    span: proc_macro2::Span,
+) -> syn::Result<TokenStream2> {
+    let attribute = attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("service"))
+        .ok_or_else(|| syn::Error::new(span, "missing #[service(uuid = ...)] attribute"))?;
+
+    let mut uuid = None;
+    attribute.parse_nested_meta(|meta| {
+        if meta.path.is_ident("uuid") {
+            uuid = Some(parse_uuid(&meta, UuidContext::Service)?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported service attribute, expected `uuid`"))
+        }
+    })?;
+    uuid.ok_or_else(|| syn::Error::new(attribute.span(), "missing `uuid` in service attribute"))
+}
+
+/// Parses every option of the `characteristic` attribute of a field
+fn parse_characteristic_attribute(
+    ident: Ident,
+    attribute: &Attribute,
+) -> syn::Result<CharacteristicField> {
+    let mut field = CharacteristicField {
+        ident,
+        uuid: TokenStream2::new(),
+        properties: Vec::new(),
+        initial: None,
+        description: None,
+        unit: None,
+        exponent: None,
+        presentation: false,
+    };
+    let mut has_uuid = false;
+
+    attribute.parse_nested_meta(|meta| {
+        if meta.path.is_ident("uuid") {
+            field.uuid = parse_uuid(&meta, UuidContext::Characteristic)?;
+            has_uuid = true;
+        } else if meta.path.is_ident("initial") {
+            field.initial = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("description") {
+            field.description = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("unit") {
+            field.unit = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("exponent") {
+            field.exponent = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("presentation") {
+            field.presentation = true;
+        } else if let Some((_, method)) = PROPERTIES
+            .iter()
+            .find(|(property, _)| meta.path.is_ident(property))
+        {
+            field.properties.push(format_ident!("{}", method));
+        } else {
+            return Err(meta.error("unsupported characteristic attribute"));
+        }
+        Ok(())
+    })?;
+
+    if !has_uuid {
+        return Err(syn::Error::new(
+            attribute.span(),
+            "missing `uuid` in characteristic attribute",
+        ));
+    }
+    Ok(field)
+}
+
+/// Parses the value of a `uuid` option into an expression that creates the BleId
+fn parse_uuid(meta: &ParseNestedMeta, context: UuidContext) -> syn::Result<TokenStream2> {
+    let expr: Expr = meta.value()?.parse()?;
+    match &expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        }) => {
+            let uuid: u32 = int.base10_parse().map_err(|_| {
+                syn::Error::new(int.span(), "integer uuids must be 16 or 32 bit uuids")
+            })?;
+            Ok(match u16::try_from(uuid) {
+                Ok(uuid) => quote! { ::esp32framework::ble::BleId::FromUuid16(#uuid) },
+                Err(_) => quote! { ::esp32framework::ble::BleId::FromUuid32(#uuid) },
+            })
+        }
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(string),
+            ..
+        }) => {
+            let bytes = parse_uuid128(&string.value())
+                .ok_or_else(|| syn::Error::new(string.span(), "invalid 128 bit uuid"))?;
+            Ok(quote! { ::esp32framework::ble::BleId::FromUuid128([#(#bytes),*]) })
+        }
+        Expr::Path(_) => Ok(match context {
+            UuidContext::Service => {
+                quote! { ::esp32framework::ble::BleId::from_standard_service(#expr) }
+            }
+            UuidContext::Characteristic => {
+                quote! { ::esp32framework::ble::BleId::from_standard_characteristic(#expr) }
+            }
+        }),
+        _ => Err(syn::Error::new(
+            expr.span(),
+            "expected an integer, a string or a standard id",
+        )),
+    }
+}
+
+/// Parses a 128 bit uuid with the format `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` into its bytes
+/// in little endian, which is the order used by the BLE stack
+fn parse_uuid128(uuid: &str) -> Option<Vec<u8>> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 || uuid.len() != 36 {
+        return None;
+    }
+    let mut bytes = (0..32)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// Creates the expression that initializes a field
+fn characteristic_initializer(field: &CharacteristicField) -> TokenStream2 {
+    let ident = &field.ident;
+    let uuid = &field.uuid;
+    let properties = &field.properties;
+    let initial = match &field.initial {
+        Some(initial) => quote! { #initial },
+        None => quote! { ::core::default::Default::default() },
+    };
+    let description = field
+        .description
+        .as_ref()
+        .map(|description| quote! { .with_description(#description) });
+    let presentation = if field.presentation || field.unit.is_some() || field.exponent.is_some() {
+        let exponent = match &field.exponent {
+            Some(exponent) => quote! { #exponent },
+            None => quote! { 0 },
+        };
+        let unit = match &field.unit {
+            Some(unit) => quote! { ::core::option::Option::Some(#unit) },
+            None => quote! { ::core::option::Option::None },
+        };
+        Some(quote! { .with_presentation_format(#exponent, #unit) })
+    } else {
+        None
+    };
+
+    quote! {
+        #ident: ::esp32framework::ble::utils::TypedCharacteristic::new(
+            service_id.clone(),
+            ::esp32framework::ble::utils::Characteristic::new(&#uuid, ::std::vec::Vec::new())
+                #(.#properties(true))*,
+            #initial,
+        )
+        #description
+        #presentation
+    }
+}
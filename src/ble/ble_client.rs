@@ -1,9 +1,23 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
 
-use esp32_nimble::{BLEClient, BLEDevice, BLEScan};
-use esp_idf_svc::hal::task::block_on;
+use esp32_nimble::{BLEAddress, BLEDevice, BLEError, BLEScan};
+use esp_idf_svc::{hal::task::block_on, sys, timer::EspTaskTimerService};
+use futures::{
+    channel::mpsc::{self, Receiver, Sender},
+    FutureExt, Stream, StreamExt,
+};
 const BLOCK: i32 = i32::MAX;
 const MS_BETWEEN_SCANS: u16 = 100;
+const SCAN_STREAM_CAPACITY: usize = 32;
+/// Duration in ms of each round of a continuous scan. The devices remembered by the BLE stack are
+/// forgotten between rounds, to bound the memory used by the scan.
+const CONTINUOUS_SCAN_ROUND_MS: i32 = 10_000;
 
 use crate::{
     utils::{
@@ -14,32 +28,60 @@ use crate::{
     InterruptDriver,
 };
 
-use super::utils::{BleAdvertisedDevice, BleError, BleId, RemoteCharacteristic};
+use super::{
+    utils::{
+        bonded_addresses, clear_bonds, delete_bond, set_preferred_mtu, BleAdvertisedDevice,
+        BleError, BleId, BlePhy, CodedPhyScheme, ConnectionParams, DuplicateFilter, GattSnapshot,
+        GattSnapshotCache, LinkChange, PairingCallbacks, ReconnectPolicy, RemoteCharacteristic,
+        DEFAULT_MTU,
+    },
+    BleConnection, L2capChannel,
+};
 
 /// Driver responsible for handling the client-end of ble connections. Can be used to read, write or notify
 /// on characteristics of services of connected clients
+/// - `ble_scan`: The scanner of the BLE stack, or `None` while a continuous scan owns it
+/// - `scan`: The continuous scan in progress. The BLE stack keeps a pointer into the future, so
+///   it must not be dropped before the scan is cancelled.
+/// - `connection`: The connection used by the single connection methods, set by [BleClient::connect_to_device]
+/// - `gatt_cache`: The snapshots of the servers discovered with [BleClient::discover_all]
+/// - `reconnect_policy`: The backoff used to reconnect when the connection is lost, if enabled
+/// - `remembered_address`: The address of the server of the connection, used to reconnect
+/// - `reconnection`: The attempt to reconnect in progress
 struct _BleClient {
-    ble_client: BLEClient,
-    ble_scan: &'static mut BLEScan,
-    connected: bool,
+    ble_scan: Option<&'static mut BLEScan>,
+    scan: Option<Pin<Box<dyn Future<Output = BLEError>>>>,
+    connection: Option<BleConnection>,
+    gatt_cache: GattSnapshotCache,
     time_between_scans: u16,
     notifier: Notifier,
+    pairing: PairingCallbacks,
+    reconnect_policy: Option<ReconnectPolicy>,
+    remembered_address: Option<BLEAddress>,
+    reconnection: Option<Reconnection>,
 }
 
+/// Auxiliary struct used for the updating of every open connection of the client
+/// - `new_connections`: Connections whose connect callback has not been executed yet
 #[derive(Default)]
 struct BleClientUpdater {
-    remote_characteristics: HashMap<BleId, RemoteCharacteristic>,
+    connections: Vec<BleConnection>,
+    new_connections: Vec<BleConnection>,
+    user_on_connect: Option<Box<dyn FnMut(&mut BleConnection)>>,
+    user_on_disconnect: Option<Box<dyn FnMut(&mut BleConnection, i32)>>,
+    user_on_params_updated: Option<Box<dyn FnMut(&mut BleConnection, ConnectionParams)>>,
 }
 
-impl BleClientUpdater {
-    fn add_characteristic(&mut self, characteristic: &RemoteCharacteristic) {
-        self.remote_characteristics
-            .insert(characteristic.id(), characteristic.clone());
-    }
+/// An attempt to reconnect to the remembered server. It waits the delay of the attempt and then
+/// connects, being polled on each update of the client.
+struct Reconnection {
+    attempt: u32,
+    connecting: Pin<Box<dyn Future<Output = Result<BleConnection, BleError>>>>,
 }
 
 /// Driver responsible for handling the client-end of ble connections. Can be used to read, write or notify
-/// on characteristics of services of connected clients
+/// on characteristics of services of connected clients. Many connections can be open at the same
+/// time with [BleClient::connect], each one handled by its own [BleConnection].
 pub struct BleClient {
     inner: SharableRef<_BleClient>,
     updater: SharableRef<BleClientUpdater>,
@@ -58,11 +100,16 @@ impl _BleClient {
     /// A [_BleClient] with the default time_between_scans `TIME_BETWEEN_SCANS`, ready to connect to a ble server
     fn new(ble_device: &mut BLEDevice, notifier: Notifier) -> Self {
         _BleClient {
-            ble_client: BLEClient::new(),
-            ble_scan: ble_device.get_scan(),
-            connected: false,
+            ble_scan: Some(ble_device.get_scan()),
+            scan: None,
+            connection: None,
+            gatt_cache: GattSnapshotCache::new(),
             time_between_scans: MS_BETWEEN_SCANS,
             notifier,
+            pairing: PairingCallbacks::default(),
+            reconnect_policy: None,
+            remembered_address: None,
+            reconnection: None,
         }
     }
 
@@ -82,6 +129,7 @@ impl _BleClient {
     /// # Errors
     ///
     /// - `BleError::DeviceNotFound`: if didnt find any device that meets condition in `timeout`
+    /// - `BleError::ScanInProgress`: if a continuous scan is running
     /// - `BleError::Code`: on other errors
     pub fn find_device<C: Fn(&BleAdvertisedDevice) -> bool + Send + Sync>(
         &mut self,
@@ -97,14 +145,13 @@ impl _BleClient {
         timeout: Option<Duration>,
         condition: C,
     ) -> Result<BleAdvertisedDevice, BleError> {
-        self._start_scan();
         let timeout = match timeout {
             Some(timeout) => timeout.as_millis().min(i32::MAX as u128) as i32,
             None => BLOCK,
         };
 
         let device = self
-            .ble_scan
+            ._start_scan()?
             .find_device(timeout, |adv| condition(&BleAdvertisedDevice::from(adv)))
             .await?;
 
@@ -129,6 +176,7 @@ impl _BleClient {
     /// # Errors
     ///
     /// - `BleError::DeviceNotFound`: if didnt find any device that meets condition in `timeout`
+    /// - `BleError::ScanInProgress`: if a continuous scan is running
     /// - `BleError::Code`: on other errors
     pub fn find_device_with_service(
         &mut self,
@@ -153,6 +201,7 @@ impl _BleClient {
     /// # Errors
     ///
     /// - `BleError::DeviceNotFound`: if didnt find any device that meets condition in `timeout`
+    /// - `BleError::ScanInProgress`: if a continuous scan is running
     /// - `BleError::Code`: on other errors
     pub fn find_device_of_name(
         &mut self,
@@ -182,46 +231,6 @@ impl _BleClient {
             .await
     }
 
-    /// Blocking method that attempts to connect to a device.
-    ///
-    /// # Arguments
-    ///
-    /// - `timeout`: A duration in which the client will attempt to connect to a device that fullfills the condition. If it is None then
-    /// the client will attempt to connect indefinitely
-    /// - `condition`: A closure that receives a `&BleAdvertisedDevice`, and returns a bool. The client will connect to any device where applying
-    /// this clossure returns true. This way the client can connect to any device that advertises itslef in a certain way.
-    ///
-    /// # Returns
-    ///
-    /// A `Ok(())` if it was able to connect to a device that satisfice the condition before `timeout`, or a `Err(BleError)` if a connection
-    /// cant be set before `timeout`.
-    ///
-    /// # Errors
-    ///
-    /// - `BleError::AlreadyConnected`: if already connected
-    /// - `BleError::DeviceNotFound`: if the device was not found when trying to connect to it
-    /// - `BleError::DeviceNotConnectable`: if found device does not accept_connections
-    /// - `BleError::Code`: on other errors
-    pub fn connect_to_device(&mut self, device: BleAdvertisedDevice) -> Result<(), BleError> {
-        block_on(self.connect_to_device_async(device))
-    }
-
-    /// Non blocking async version of [Self::connect_to_device]
-    pub async fn connect_to_device_async(
-        &mut self,
-        device: BleAdvertisedDevice,
-    ) -> Result<(), BleError> {
-        if !device.is_connectable() {
-            return Err(BleError::DeviceNotConnectable);
-        }
-        self.ble_client
-            .connect(device.addr())
-            .await
-            .map_err(BleError::from_connection_context)?;
-        self.connected = true;
-        Ok(())
-    }
-
     /// Blocking method that attempts to get all service ids of a given of the current connection
     ///
     /// # Returns
@@ -233,60 +242,20 @@ impl _BleClient {
     /// - `BleError::DeviceNotFound`: if the device has unexpectidly disconnected
     /// - `BleError::Code`: on other errors
     pub fn get_all_service_ids(&mut self) -> Result<Vec<BleId>, BleError> {
-        block_on(self.get_all_service_ids_async())
+        self.main_connection()?.get_all_service_ids()
     }
 
     /// Non blocking async version of [BleClient::get_all_service_ids]
     pub async fn get_all_service_ids_async(&mut self) -> Result<Vec<BleId>, BleError> {
-        self.is_connected()?;
-        let remote_services = self.ble_client.get_services().await?;
-        let services = remote_services
-            .map(|remote_service| BleId::from(remote_service.uuid()))
-            .collect();
-        Ok(services)
+        self.main_connection()?.get_all_service_ids_async().await
     }
 
-    /// Inner version of [BleClient::get_characteristic_async]
-    async fn _get_characteristic_async(
-        &mut self,
-        service_id: &BleId,
-        characteristic_id: &BleId,
-    ) -> Result<RemoteCharacteristic, BleError> {
-        self.is_connected()?;
-        let remote_service = self
-            .ble_client
-            .get_service(service_id.to_uuid())
-            .await
-            .map_err(BleError::from_service_context)?;
-        let remote_characteristic = remote_service
-            .get_characteristic(characteristic_id.to_uuid())
-            .await
-            .map_err(BleError::from_characteristic_context)?;
-        Ok(RemoteCharacteristic::new(
-            remote_characteristic,
-            self.notifier.clone(),
-        ))
-    }
-
-    /// Inner version of [BleClient::get_all_characteristics_async]
-    async fn _get_all_characteristics_async(
-        &mut self,
-        service_id: &BleId,
-    ) -> Result<Vec<RemoteCharacteristic>, BleError> {
-        self.is_connected()?;
-        let remote_service = self
-            .ble_client
-            .get_service(service_id.to_uuid())
-            .await
-            .map_err(BleError::from_service_context)?;
-        let remote_characteristics = remote_service
-            .get_characteristics()
-            .await?
-            .map(|remote_characteristic| {
-                RemoteCharacteristic::new(remote_characteristic, self.notifier.clone())
-            })
-            .collect();
-        Ok(remote_characteristics)
+    /// Gets the connection used by the single connection methods of the client
+    fn main_connection(&self) -> Result<BleConnection, BleError> {
+        match &self.connection {
+            Some(connection) if connection.is_connected() => Ok(connection.clone()),
+            _ => Err(BleError::Disconnected),
+        }
     }
 
     /// Sets the amount of ms for between scans
@@ -294,16 +263,101 @@ impl _BleClient {
         self.time_between_scans = ms_between_scans
     }
 
-    /// Starts a scan in order to find devices to connect to
-    fn _start_scan(&mut self) {
-        self.ble_scan
+    /// Prepares the scanner in order to find devices to connect to
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ScanInProgress`: if a continuous scan owns the scanner
+    fn _start_scan(&mut self) -> Result<&mut BLEScan, BleError> {
+        let time_between_scans = self.time_between_scans;
+        let ble_scan = self
+            .ble_scan
+            .as_deref_mut()
+            .ok_or(BleError::ScanInProgress)?;
+        Ok(ble_scan
             .active_scan(true)
-            .interval(self.time_between_scans.max(1))
-            .window(self.time_between_scans.max(2) - 1);
+            .interval(time_between_scans.max(1))
+            .window(time_between_scans.max(2) - 1))
+    }
+
+    /// Starts a scan that keeps running until [Self::stop_scan] is called, sending every
+    /// advertisement received through the sender. Advertisements are dropped while the
+    /// channel is full. The scan is made of rounds of `CONTINUOUS_SCAN_ROUND_MS`, and the next
+    /// round starts when the scan is polled with [Self::poll_scan].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ScanInProgress`: if a continuous scan is already running
+    /// - `BleError::Code`: if the BLE stack fails to start the scan
+    fn start_continuous_scan(
+        &mut self,
+        config: &ScanConfig,
+        mut sender: Sender<BleAdvertisedDevice>,
+    ) -> Result<(), BleError> {
+        let ble_scan = self.ble_scan.take().ok_or(BleError::ScanInProgress)?;
+        let interval = config.interval_ms.max(1);
+        ble_scan
+            .active_scan(config.active)
+            .interval(interval)
+            .window(config.window_ms.clamp(1, interval))
+            .filter_duplicates(false)
+            .on_result(move |_, device| {
+                _ = sender.try_send(BleAdvertisedDevice::from(device));
+            });
+        // The future owns the scanner until the scan is stopped. The results are cleared between
+        // rounds, since the BLE stack iterates them while the callback runs.
+        let mut scan: Pin<Box<dyn Future<Output = BLEError>>> = Box::pin(async move {
+            loop {
+                if let Err(err) = ble_scan.start(CONTINUOUS_SCAN_ROUND_MS).await {
+                    return err;
+                }
+                ble_scan.clear_results();
+            }
+        });
+        match scan.as_mut().now_or_never() {
+            Some(err) => {
+                self.ble_scan = Some(BLEDevice::take().get_scan());
+                Err(BleError::from(err))
+            }
+            None => {
+                self.scan = Some(scan);
+                Ok(())
+            }
+        }
+    }
+
+    /// Polls the scan started by [Self::start_continuous_scan], starting its next round if the
+    /// previous one ended
+    ///
+    /// # Returns
+    ///
+    /// Whether the scan is still running. It is stopped if the BLE stack fails to start a round.
+    fn poll_scan(&mut self, cx: &mut Context<'_>) -> bool {
+        let running = match self.scan.as_mut() {
+            Some(scan) => scan.as_mut().poll(cx).is_pending(),
+            None => false,
+        };
+        if !running {
+            self.stop_scan();
+        }
+        running
+    }
+
+    /// Stops the scan started by [Self::start_continuous_scan], taking back the scanner once
+    /// the future that owns it is dropped
+    fn stop_scan(&mut self) {
+        if let Some(scan) = self.scan.take() {
+            // The scan is cancelled before dropping the future the BLE stack points to
+            _ = unsafe { sys::ble_gap_disc_cancel() };
+            drop(scan);
+            let ble_scan = BLEDevice::take().get_scan();
+            ble_scan.clear_results();
+            self.ble_scan = Some(ble_scan);
+        }
     }
 
-    /// The conn_handle is obtained with the ConnectionInformation inside the closure of
-    /// connection_handler
+    /// Sets the connection parameters of the current connection. Same as
+    /// [BleConnection::set_connection_settings].
     ///
     /// # Arguments
     ///
@@ -321,6 +375,7 @@ impl _BleClient {
     ///
     /// # Errors
     /// - `BleError::Disconnected`: if there is no connection stablished to go look for a service
+    /// - `BleError::InvalidParameters`: if any of the parameters is out of its range
     /// - `BleError::DeviceNotFound`: if the device has unexpectidly disconnected
     /// - `BleError::Code`: on other errors
     pub fn set_connection_settings(
@@ -330,23 +385,231 @@ impl _BleClient {
         latency: u16,
         timeout: u16,
     ) -> Result<(), BleError> {
-        self.ble_client
-            .update_conn_params(min_interval, max_interval, latency, timeout)
-            .map_err(BleError::from_connection_params_context)
+        self.main_connection()?.set_connection_settings(
+            min_interval,
+            max_interval,
+            latency,
+            timeout,
+        )
     }
 
-    fn is_connected(&mut self) -> Result<(), BleError> {
-        if !self.connected || !self.ble_client.connected() {
-            return Err(BleError::Disconnected);
+    /// Starts a negotiation of the parameters of the current connection. Same as
+    /// [BleConnection::update_connection_params]. The resulting parameters are reported to
+    /// [BleClient::on_params_updated].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::InvalidParameters`: if any of the parameters is out of its range
+    /// - `BleError::DeviceNotFound`: if the device has unexpectidly disconnected
+    /// - `BleError::Code`: on other errors
+    pub fn update_connection_params(
+        &mut self,
+        min_interval: u16,
+        max_interval: u16,
+        latency: u16,
+        timeout: u16,
+    ) -> Result<(), BleError> {
+        self.main_connection()?.update_connection_params(
+            min_interval,
+            max_interval,
+            latency,
+            timeout,
+        )
+    }
+
+    /// Sets whether the client reconnects to the server of the connection opened with
+    /// [BleClient::connect_to_device] when it is lost. The address of the server is remembered
+    /// when connecting, and each attempt waits the delay given by the policy, growing after each
+    /// failed attempt. Closing the connection with [BleClient::disconnect] forgets the address.
+    ///
+    /// Note: The attempts are made on the updates of the client, so the method
+    /// [crate::Microcontroller::wait_for_updates] must be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `policy`: The [ReconnectPolicy] with the backoff between attempts, or `None` to stop
+    ///   reconnecting, cancelling any attempt in progress
+    pub fn set_auto_reconnect(&mut self, policy: Option<ReconnectPolicy>) {
+        if policy.is_none() {
+            self.reconnection = None;
         }
-        Ok(())
+        self.reconnect_policy = policy;
+    }
+
+    /// Returns whether the client is waiting to reconnect, or reconnecting, to the lost server
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnection.is_some()
     }
 
-    /// Disconnects the client from the current connection
+    /// Sets the MTU offered by the client in the MTU exchange made when connecting to a server.
+    /// It applies to the next connections, of every role.
     ///
     /// # Arguments
     ///
-    /// - `service_id`: The id of the service .
+    /// - `mtu`: The MTU offered, between 23 and 517
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: if the MTU is not between 23 and 517
+    /// - `BleError::Code`: on other errors
+    pub fn set_preferred_mtu(&mut self, mtu: u16) -> Result<(), BleError> {
+        set_preferred_mtu(mtu)
+    }
+
+    /// Starts an MTU exchange on the current connection. Same as [BleConnection::request_mtu].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::InvalidParameters`: if the MTU is not between 23 and 517
+    /// - `BleError::MtuAlreadyExchanged`: if the MTU of the connection was already exchanged
+    /// - `BleError::Code`: on other errors
+    pub fn request_mtu(&mut self, mtu: u16) -> Result<(), BleError> {
+        self.main_connection()?.request_mtu(mtu)
+    }
+
+    /// Enables the LE Data Length Extension on the current connection. Same as
+    /// [BleConnection::set_data_length].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::InvalidParameters`: if `tx_octets` is not between 27 and 251
+    /// - `BleError::Code`: on other errors
+    pub fn set_data_length(&mut self, tx_octets: u16) -> Result<(), BleError> {
+        self.main_connection()?.set_data_length(tx_octets)
+    }
+
+    /// Sets the PHYs preferred on the current connection. Same as
+    /// [BleConnection::set_preferred_phy].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::Code`: if the PHYs are not supported by the controller, or on other errors
+    pub fn set_preferred_phy(
+        &mut self,
+        tx_phy: BlePhy,
+        rx_phy: BlePhy,
+        coded_scheme: CodedPhyScheme,
+    ) -> Result<(), BleError> {
+        self.main_connection()?
+            .set_preferred_phy(tx_phy, rx_phy, coded_scheme)
+    }
+
+    /// Blocking method that opens an L2CAP connection-oriented channel on the current connection.
+    /// Same as [BleConnection::l2cap_connect].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::InvalidParameters`: if the PSM or the MTU are not valid
+    /// - `BleError::CouldNotConnectToDevice`: if the server refused the channel
+    /// - `BleError::Code`: if the BLE stack fails to open the channel
+    pub fn l2cap_connect(&mut self, psm: u16, mtu: u16) -> Result<L2capChannel, BleError> {
+        block_on(self.l2cap_connect_async(psm, mtu))
+    }
+
+    /// Non blocking async version of [BleClient::l2cap_connect]
+    pub async fn l2cap_connect_async(
+        &mut self,
+        psm: u16,
+        mtu: u16,
+    ) -> Result<L2capChannel, BleError> {
+        self.main_connection()?.l2cap_connect_async(psm, mtu).await
+    }
+
+    /// Blocking method that secures the current connection. Same as [BleConnection::secure_connection].
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::Code`: if the pairing failed, for example by a wrong passkey
+    pub fn secure_connection(&mut self) -> Result<(), BleError> {
+        self.main_connection()?.secure_connection()
+    }
+
+    /// Non blocking async version of [BleClient::secure_connection]
+    pub async fn secure_connection_async(&mut self) -> Result<(), BleError> {
+        self.main_connection()?.secure_connection_async().await
+    }
+
+    /// Sets the callback that provides the passkey when pairing with a server that displays one.
+    /// It is needed when the client was created with the `KeyboardOnly` or `KeyboardDisplay`
+    /// [crate::ble::utils::IOCapabilities].
+    ///
+    /// Note: The callback is executed by the BLE stack, not on the main loop, so it can not
+    /// use other drivers and should return quickly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that returns the 6 digit passkey shown by the server
+    ///
+    /// # Returns
+    ///
+    /// The BleClient itself
+    pub fn on_passkey_request<C: FnMut() -> u32 + Send + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.pairing.set_passkey(callback);
+        self
+    }
+
+    /// Sets the callback that confirms the pin shown on both devices when pairing with numeric
+    /// comparison. It is needed when the client was created with the `DisplayYesNo` or
+    /// `KeyboardDisplay` [crate::ble::utils::IOCapabilities].
+    ///
+    /// Note: The callback is executed by the BLE stack, not on the main loop, so it can not
+    /// use other drivers and should return quickly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the pin and returns whether it matches the one shown
+    ///   by the server
+    ///
+    /// # Returns
+    ///
+    /// The BleClient itself
+    pub fn on_confirm_pin<C: FnMut(u32) -> bool + Send + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.pairing.set_confirm_pin(callback);
+        self
+    }
+
+    /// Gets the addresses of every server bonded with this device
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the bonds could not be read from storage
+    pub fn bonded_addresses(&self) -> Result<Vec<BLEAddress>, BleError> {
+        bonded_addresses()
+    }
+
+    /// Deletes the bond with a device, so it must pair again on its next connection
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If there is no bond with the device or it could not be deleted
+    pub fn delete_bond(&mut self, address: &BLEAddress) -> Result<(), BleError> {
+        delete_bond(address)
+    }
+
+    /// Deletes every bond of this device. Bonds are shared by the client and the server roles.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the bonds could not be deleted
+    pub fn clear_bonds(&mut self) -> Result<(), BleError> {
+        clear_bonds()
+    }
+
+    /// Disconnects the client from the current connection. Other connections opened with
+    /// [BleClient::connect] are not affected. The server is forgotten, so the client does not
+    /// reconnect to it even if [BleClient::set_auto_reconnect] is enabled.
     ///
     /// # Returns
     ///
@@ -354,15 +617,53 @@ impl _BleClient {
     /// occured or `BleError` on failure.
     ///
     pub fn disconnect(&mut self) -> Result<(), BleError> {
-        self.connected = false;
-        match self.ble_client.disconnect().map_err(BleError::from) {
-            Ok(_) => Ok(()),
-            Err(err) => match err {
-                BleError::DeviceNotFound => Ok(()),
-                _ => Err(err),
-            },
+        self.remembered_address = None;
+        self.reconnection = None;
+        match self.connection.take() {
+            Some(mut connection) => connection.disconnect(),
+            None => Ok(()),
         }
     }
+
+    /// Forgets the snapshots of every server, so they are discovered again by
+    /// [BleClient::discover_all]
+    pub fn clear_gatt_cache(&mut self) {
+        self.gatt_cache.clear()
+    }
+
+    /// Starts the attempt to reconnect to the remembered server, if the client must reconnect.
+    /// Once the attempts of the policy are exhausted the server is forgotten.
+    ///
+    /// # Arguments
+    ///
+    /// - `attempt`: The number of the attempt, starting at 0
+    fn schedule_reconnection(&mut self, attempt: u32) {
+        let (Some(policy), Some(address)) = (self.reconnect_policy, self.remembered_address) else {
+            return;
+        };
+        let Some(delay) = policy.delay(attempt) else {
+            log::warn!(
+                "Could not reconnect to {} after {} attempts",
+                address,
+                attempt
+            );
+            self.remembered_address = None;
+            return;
+        };
+        let mut connection = BleConnection::new(self.notifier.clone(), &self.pairing);
+        let timer = EspTaskTimerService::new().and_then(|service| service.timer_async());
+        let connecting = async move {
+            timer?.after(delay).await?;
+            connection.connect_async(&address).await?;
+            Ok::<_, BleError>(connection)
+        };
+        self.reconnection = Some(Reconnection {
+            attempt,
+            connecting: Box::pin(connecting),
+        });
+        // The attempt starts on its first poll, made on the next update
+        self.notifier.notify();
+    }
 }
 
 impl BleClient {
@@ -382,6 +683,202 @@ impl BleClient {
         }
     }
 
+    /// Blocking method that attempts to connect to a device. The connection is used by the
+    /// single connection methods of the client, like [Self::get_characteristic]. To open more
+    /// connections at the same time use [Self::connect].
+    ///
+    /// # Arguments
+    ///
+    /// - `device`: The device to connect to, found with [Self::find_device] or similar methods
+    ///
+    /// # Returns
+    ///
+    /// A `Ok(())` if it was able to connect to the device, or a `Err(BleError)` if the connection
+    /// could not be set.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AlreadyConnected`: if already connected
+    /// - `BleError::DeviceNotFound`: if the device was not found when trying to connect to it
+    /// - `BleError::DeviceNotConnectable`: if found device does not accept_connections
+    /// - `BleError::Code`: on other errors
+    pub fn connect_to_device(&mut self, device: BleAdvertisedDevice) -> Result<(), BleError> {
+        block_on(self.connect_to_device_async(device))
+    }
+
+    /// Non blocking async version of [Self::connect_to_device]
+    pub async fn connect_to_device_async(
+        &mut self,
+        device: BleAdvertisedDevice,
+    ) -> Result<(), BleError> {
+        if self.inner.deref().main_connection().is_ok() {
+            return Err(BleError::AlreadyConnected);
+        }
+        self.inner.deref_mut().reconnection = None;
+        let address = *device.addr();
+        let connection = self.connect_async(device).await?;
+        let mut inner = self.inner.deref_mut();
+        inner.connection = Some(connection);
+        inner.remembered_address = Some(address);
+        Ok(())
+    }
+
+    /// Blocking method that opens a new connection to a device, keeping the other connections
+    /// of the client open.
+    ///
+    /// # Arguments
+    ///
+    /// - `device`: The device to connect to, found with [Self::find_device] or similar methods
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [BleConnection] used to interact with the device, or a `BleError` if
+    /// the connection could not be set.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::DeviceNotFound`: if the device was not found when trying to connect to it
+    /// - `BleError::DeviceNotConnectable`: if found device does not accept_connections
+    /// - `BleError::Code`: on other errors, like reaching the maximum amount of connections
+    pub fn connect(&mut self, device: BleAdvertisedDevice) -> Result<BleConnection, BleError> {
+        block_on(self.connect_async(device))
+    }
+
+    /// Non blocking async version of [Self::connect]
+    pub async fn connect_async(
+        &mut self,
+        device: BleAdvertisedDevice,
+    ) -> Result<BleConnection, BleError> {
+        if !device.is_connectable() {
+            return Err(BleError::DeviceNotConnectable);
+        }
+        let inner = self.inner.deref();
+        let mut connection = BleConnection::new(inner.notifier.clone(), &inner.pairing);
+        drop(inner);
+        connection.connect_async(device.addr()).await?;
+        self.add_connection(&connection);
+        Ok(connection)
+    }
+
+    /// Sets a callback to be executed each time a connection of the client is opened, either by
+    /// [Self::connect], by [Self::connect_to_device] or by reconnecting to a lost server.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the new connection
+    ///
+    /// # Returns
+    ///
+    /// The BleClient itself
+    pub fn on_connect<C: FnMut(&mut BleConnection) + 'static>(&mut self, callback: C) -> &mut Self {
+        self.updater.deref_mut().user_on_connect = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback to be executed each time a connection of the client is closed, either by
+    /// the server, by losing the signal, or by the client. It is executed after the callback set
+    /// with [BleConnection::on_disconnect] on the connection.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the closed connection and the reason code of the
+    ///   disconnection
+    ///
+    /// # Returns
+    ///
+    /// The BleClient itself
+    pub fn on_disconnect<C: FnMut(&mut BleConnection, i32) + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.updater.deref_mut().user_on_disconnect = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback to be executed each time the interval, latency or supervision timeout of a
+    /// connection of the client change, whichever side started the negotiation.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the connection and its new [ConnectionParams]
+    ///
+    /// # Returns
+    ///
+    /// The BleClient itself
+    pub fn on_params_updated<C: FnMut(&mut BleConnection, ConnectionParams) + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.updater.deref_mut().user_on_params_updated = Some(Box::new(callback));
+        self
+    }
+
+    /// Starts a continuous scan, whose advertisements can be awaited as a `futures::Stream`
+    /// inside [crate::Microcontroller::block_on]. The scan stops once the stream is dropped.
+    /// While the stream is alive, methods that scan like [Self::find_device] fail with
+    /// `BleError::ScanInProgress`.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: The [ScanConfig] with the settings of the scan
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [ScanStream], or a `BleError` if the scan could not be started
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ScanInProgress`: If a continuous scan is already running
+    /// - `BleError::Code`: If the BLE stack fails to start the scan
+    pub fn scan_stream(&mut self, config: ScanConfig) -> Result<ScanStream, BleError> {
+        let (sender, receiver) = mpsc::channel(SCAN_STREAM_CAPACITY);
+        self.inner
+            .deref_mut()
+            .start_continuous_scan(&config, sender)?;
+        Ok(ScanStream {
+            client: self.inner.clone(),
+            receiver,
+            filter: config.duplicate_window.map(DuplicateFilter::new),
+        })
+    }
+
+    /// Gets every open connection of the client, including the one of [Self::connect_to_device]
+    pub fn connections(&self) -> Vec<BleConnection> {
+        self.updater
+            .deref()
+            .connections
+            .iter()
+            .filter(|connection| connection.is_connected())
+            .map(|connection| connection.clone())
+            .collect()
+    }
+
+    /// Closes every open connection of the client
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: if a connection could not be closed
+    pub fn disconnect_all(&mut self) -> Result<(), BleError> {
+        let mut inner = self.inner.deref_mut();
+        inner.connection = None;
+        inner.remembered_address = None;
+        inner.reconnection = None;
+        drop(inner);
+        for mut connection in self.connections() {
+            connection.disconnect()?;
+        }
+        Ok(())
+    }
+
     /// Blocking method that attempts to get a characteristic from a service of the current connection.
     ///
     /// # Arguments
@@ -436,15 +933,10 @@ impl BleClient {
         service_id: &BleId,
         characteristic_id: &BleId,
     ) -> Result<RemoteCharacteristic, BleError> {
-        let characteristic = self
-            .inner
-            .deref_mut()
-            ._get_characteristic_async(service_id, characteristic_id)
-            .await?;
-        self.updater
-            .borrow_mut()
-            .add_characteristic(&characteristic);
-        Ok(characteristic)
+        let mut connection = self.inner.deref().main_connection()?;
+        connection
+            .get_characteristic_async(service_id, characteristic_id)
+            .await
     }
 
     /// Non blocking async version of [Self::get_all_characteristics]
@@ -452,24 +944,296 @@ impl BleClient {
         &mut self,
         service_id: &BleId,
     ) -> Result<Vec<RemoteCharacteristic>, BleError> {
-        let characteristics = self
-            .inner
-            .deref_mut()
-            ._get_all_characteristics_async(service_id)
-            .await?;
-        for c in &characteristics {
-            self.updater.borrow_mut().add_characteristic(c);
+        let mut connection = self.inner.deref().main_connection()?;
+        connection.get_all_characteristics_async(service_id).await
+    }
+
+    /// Blocking method that gets the whole GATT table of the server of the current connection, as
+    /// [BleConnection::discover_all] does. Snapshots are cached by the address of the server, so
+    /// reconnecting to a known server does not discover it again. Use [Self::rediscover_all] if the
+    /// server may have changed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GattSnapshot` of the server, or `BleError` if a failure occured.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::Code`: on other errors
+    pub fn discover_all(&mut self) -> Result<GattSnapshot, BleError> {
+        block_on(self.discover_all_async())
+    }
+
+    /// Non blocking async version of [Self::discover_all]
+    pub async fn discover_all_async(&mut self) -> Result<GattSnapshot, BleError> {
+        let connection = self.inner.deref().main_connection()?;
+        let address = connection.address()?;
+        if let Some(snapshot) = self.inner.deref().gatt_cache.get(&address) {
+            return Ok(snapshot.clone());
         }
-        Ok(characteristics)
+        self.rediscover_all_async().await
+    }
+
+    /// Blocking method that discovers the GATT table of the server of the current connection
+    /// again, replacing its cached snapshot.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GattSnapshot` of the server, or `BleError` if a failure occured.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if there is no connection stablished
+    /// - `BleError::Code`: on other errors
+    pub fn rediscover_all(&mut self) -> Result<GattSnapshot, BleError> {
+        block_on(self.rediscover_all_async())
+    }
+
+    /// Non blocking async version of [Self::rediscover_all]
+    pub async fn rediscover_all_async(&mut self) -> Result<GattSnapshot, BleError> {
+        let mut connection = self.inner.deref().main_connection()?;
+        let snapshot = connection.discover_all_async().await?;
+        self.inner.deref_mut().gatt_cache.insert(snapshot.clone());
+        Ok(snapshot)
+    }
+}
+
+impl BleClient {
+    /// Efectibly clones the client, but is only allowed in the crate
+    pub(crate) fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            updater: self.updater.clone(),
+        }
+    }
+
+    /// Gets the MTU of the current connection, or the default MTU if it can not be known
+    pub(crate) fn mtu(&self) -> u16 {
+        self.inner
+            .deref()
+            .main_connection()
+            .map(|connection| connection.mtu())
+            .unwrap_or(DEFAULT_MTU)
+    }
+
+    /// Starts updating a new connection, whose connect callback is executed on the next update
+    fn add_connection(&mut self, connection: &BleConnection) {
+        let mut updater = self.updater.deref_mut();
+        updater.connections.push(connection.clone());
+        updater.new_connections.push(connection.clone());
+        self.inner.deref().notifier.notify();
+    }
+
+    /// Executes the connect callback for every connection opened since the last update
+    fn handle_new_connections(&mut self) {
+        let new_connections: Vec<BleConnection> =
+            self.updater.deref_mut().new_connections.drain(..).collect();
+        let Some(mut callback) = self.updater.deref_mut().user_on_connect.take() else {
+            return;
+        };
+        for mut connection in new_connections {
+            callback(&mut connection)
+        }
+        let mut updater = self.updater.deref_mut();
+        if updater.user_on_connect.is_none() {
+            updater.user_on_connect = Some(callback);
+        }
+    }
+
+    /// Executes the disconnect callback for a closed connection
+    fn handle_disconnection(&mut self, connection: &mut BleConnection, reason: i32) {
+        let Some(mut callback) = self.updater.deref_mut().user_on_disconnect.take() else {
+            return;
+        };
+        callback(connection, reason);
+        let mut updater = self.updater.deref_mut();
+        if updater.user_on_disconnect.is_none() {
+            updater.user_on_disconnect = Some(callback);
+        }
+    }
+
+    /// Executes the params updated callback for every change of the connection parameters
+    fn handle_link_changes(&mut self, connection: &mut BleConnection, changes: Vec<LinkChange>) {
+        let Some(mut callback) = self.updater.deref_mut().user_on_params_updated.take() else {
+            return;
+        };
+        for change in changes {
+            if let LinkChange::ConnectionParams(params) = change {
+                callback(connection, params)
+            }
+        }
+        let mut updater = self.updater.deref_mut();
+        if updater.user_on_params_updated.is_none() {
+            updater.user_on_params_updated = Some(callback);
+        }
+    }
+
+    /// Forgets the connection of [Self::connect_to_device] once it is closed, starting to
+    /// reconnect to its server if auto reconnect is enabled
+    fn handle_lost_connection(&mut self) {
+        let mut inner = self.inner.deref_mut();
+        if inner
+            .connection
+            .as_ref()
+            .is_some_and(|connection| connection.is_closed())
+        {
+            inner.connection = None;
+            inner.schedule_reconnection(0);
+        }
+    }
+
+    /// Polls the attempt to reconnect in progress. If it succeeds its connection becomes the one
+    /// of [Self::connect_to_device], otherwise the next attempt is scheduled.
+    fn poll_reconnection(&mut self) {
+        let Some(mut reconnection) = self.inner.deref_mut().reconnection.take() else {
+            return;
+        };
+        let waker = Waker::from(Arc::new(self.inner.deref().notifier.clone()));
+        let result = match reconnection
+            .connecting
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+        {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
+                self.inner.deref_mut().reconnection = Some(reconnection);
+                return;
+            }
+        };
+        match result {
+            Ok(connection) => {
+                self.inner.deref_mut().connection = Some(connection.clone());
+                self.add_connection(&connection);
+            }
+            Err(err) => {
+                log::warn!("Could not reconnect: {:?}", err);
+                self.inner
+                    .deref_mut()
+                    .schedule_reconnection(reconnection.attempt + 1);
+            }
+        }
+    }
+}
+
+/// Settings of a continuous scan started with [BleClient::scan_stream]
+/// - `active`: Whether scan requests are sent to get the scan response of the devices
+/// - `interval_ms`: Time between the start of two scan windows
+/// - `window_ms`: Time the radio listens on each interval, at most `interval_ms`
+/// - `duplicate_window`: If set, each device is reported at most once per this duration
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    active: bool,
+    interval_ms: u16,
+    window_ms: u16,
+    duplicate_window: Option<Duration>,
+}
+
+impl ScanConfig {
+    /// Creates a new ScanConfig with an active scan, the default interval and window, and
+    /// no duplicate filtering
+    pub fn new() -> Self {
+        Self {
+            active: true,
+            interval_ms: MS_BETWEEN_SCANS,
+            window_ms: MS_BETWEEN_SCANS - 1,
+            duplicate_window: None,
+        }
+    }
+
+    /// Sets whether the scan is active, requesting the scan response of each device, or
+    /// passive, only listening. Passive scans use less power and do not reveal the scanner.
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Sets the time in ms between the start of two scan windows
+    pub fn interval(mut self, interval_ms: u16) -> Self {
+        self.interval_ms = interval_ms;
+        self
+    }
+
+    /// Sets the time in ms the radio listens on each interval. It can not exceed the interval.
+    pub fn window(mut self, window_ms: u16) -> Self {
+        self.window_ms = window_ms;
+        self
+    }
+
+    /// Sets the duplicate filtering. With `Some(duration)` each device is reported at most
+    /// once every `duration`, with `None` every advertisement is reported.
+    pub fn filter_duplicates(mut self, duplicate_window: Option<Duration>) -> Self {
+        self.duplicate_window = duplicate_window;
+        self
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stream of the advertisements received by a continuous scan, created with
+/// [BleClient::scan_stream]. The scan stops when the stream is dropped, and the stream ends if
+/// the BLE stack fails to keep the scan running.
+pub struct ScanStream {
+    client: SharableRef<_BleClient>,
+    receiver: Receiver<BleAdvertisedDevice>,
+    filter: Option<DuplicateFilter>,
+}
+
+impl Stream for ScanStream {
+    type Item = BleAdvertisedDevice;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let device = match self.receiver.poll_next_unpin(cx) {
+                Poll::Ready(Some(device)) => device,
+                Poll::Pending if self.client.deref_mut().poll_scan(cx) => return Poll::Pending,
+                _ => return Poll::Ready(None),
+            };
+            let accepted = match self.filter.as_mut() {
+                Some(filter) => filter.accept(*device.addr(), Instant::now()),
+                None => true,
+            };
+            if accepted {
+                return Poll::Ready(Some(device));
+            }
+        }
+    }
+}
+
+impl Drop for ScanStream {
+    fn drop(&mut self) {
+        self.client.deref_mut().stop_scan()
     }
 }
 
 impl<'a> InterruptDriver<'a> for BleClient {
-    /// Updates all characteristics that have been gotten
+    /// Updates every connection, executing the callbacks of its characteristics, its link
+    /// changes and its disconnection, and then the callbacks of the client. Connections already
+    /// closed are forgotten, and the lost server is reconnected to if auto reconnect is enabled.
     fn update_interrupt(&mut self) -> Result<(), Esp32FrameworkError> {
-        for c in self.updater.deref_mut().remote_characteristics.values_mut() {
-            c.execute_if_notified()
+        let mut connections: Vec<BleConnection> = self
+            .updater
+            .deref()
+            .connections
+            .iter()
+            .map(|connection| connection.clone())
+            .collect();
+        for connection in connections.iter_mut() {
+            let changes = connection.update();
+            self.handle_link_changes(connection, changes);
+            if let Some(reason) = connection.disconnection_reason() {
+                self.handle_disconnection(connection, reason)
+            }
         }
+        self.updater
+            .deref_mut()
+            .connections
+            .retain(|connection| !connection.is_closed());
+        self.handle_new_connections();
+        self.handle_lost_connection();
+        self.poll_reconnection();
         Ok(())
     }
 
@@ -0,0 +1,682 @@
+use std::{collections::HashMap, sync::mpsc::Receiver};
+
+use esp32_nimble::{BLEAddress, BLEClient};
+use esp_idf_svc::{hal::task::block_on, sys};
+
+use crate::utils::{
+    auxiliary::{SharableRef, SharableRefExt},
+    isr_queues::{ISRQueue, ISRQueueTrait},
+    notification::Notifier,
+};
+
+use super::{
+    l2cap::connect_channel,
+    utils::{
+        check_connection_params, connection_mtu, find_connection, request_mtu, set_data_length,
+        set_preferred_phy, subscribe_link_changes, BleError, BleId, BlePhy, CodedPhyScheme,
+        ConnectionInformation, GattSnapshot, LinkChange, LinkEvent, PairingCallbacks,
+        RemoteCharacteristic, ServiceSnapshot, DEFAULT_MTU,
+    },
+    L2capChannel,
+};
+
+/// A connection of a [crate::ble::BleClient] to a ble server. Each connection owns the remote
+/// characteristics gotten through it, so many servers can be used at the same time.
+pub struct BleConnection {
+    inner: SharableRef<_BleConnection>,
+    updater: SharableRef<BleConnectionUpdater>,
+}
+
+/// A connection of a [crate::ble::BleClient] to a ble server. Each connection owns the remote
+/// characteristics gotten through it, so many servers can be used at the same time.
+struct _BleConnection {
+    ble_client: BLEClient,
+    notifier: Notifier,
+    address: Option<BLEAddress>,
+}
+
+/// Auxiliary struct used for the updating of the connection. Executes the notify callbacks of its
+/// characteristics and the user callbacks on link changes and on disconnection.
+/// - `closed`: Whether the disconnection of the connection has already been handled
+/// - `disconnection_reason`: The reason of the disconnection, once it was handled
+/// - `link_changes`: Link changes of every connection sent by the BLE stack, only the ones of
+///   this connection are handled
+struct BleConnectionUpdater {
+    remote_characteristics: HashMap<BleId, RemoteCharacteristic>,
+    user_on_disconnection: Option<Box<dyn FnMut(&mut BleConnection, i32)>>,
+    user_on_link_change: Option<Box<dyn FnMut(&mut BleConnection, LinkChange)>>,
+    disconnection_queue: ISRQueue<i32>,
+    link_changes: Receiver<LinkEvent>,
+    closed: bool,
+    disconnection_reason: Option<i32>,
+}
+
+impl BleConnectionUpdater {
+    fn add_characteristic(&mut self, characteristic: &RemoteCharacteristic) {
+        self.remote_characteristics
+            .insert(characteristic.id(), characteristic.clone());
+    }
+}
+
+#[sharable_reference_macro::sharable_reference_wrapper]
+impl _BleConnection {
+    /// Creates a new disconnected _BleConnection
+    fn new(notifier: Notifier) -> Self {
+        Self {
+            ble_client: BLEClient::new(),
+            notifier,
+            address: None,
+        }
+    }
+
+    /// Gets the address of the connected server
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    pub fn address(&self) -> Result<BLEAddress, BleError> {
+        Ok(self.connection_information()?.address)
+    }
+
+    /// Returns whether the connection is still open
+    pub fn is_connected(&self) -> bool {
+        self.ble_client.connected()
+    }
+
+    /// Gets the information of the connection, like its interval, latency, MTU or security
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    pub fn connection_information(&self) -> Result<ConnectionInformation, BleError> {
+        let desc = self.gap_desc()?;
+        Ok(ConnectionInformation::from_gap_desc(&desc))
+    }
+
+    /// Gets the MTU of the connection, or the minimum MTU if it can not be known
+    pub fn mtu(&self) -> u16 {
+        self.gap_desc()
+            .map(|desc| connection_mtu(desc.conn_handle))
+            .unwrap_or(DEFAULT_MTU)
+    }
+
+    /// Blocking method that attempts to get all service ids of the server
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `Vec<BleID>` or `BleError` if a failure occured.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::Code`: on other errors
+    pub fn get_all_service_ids(&mut self) -> Result<Vec<BleId>, BleError> {
+        block_on(self.get_all_service_ids_async())
+    }
+
+    /// Non blocking async version of [BleConnection::get_all_service_ids]
+    pub async fn get_all_service_ids_async(&mut self) -> Result<Vec<BleId>, BleError> {
+        self.check_connected()?;
+        let remote_services = self.ble_client.get_services().await?;
+        let services = remote_services
+            .map(|remote_service| BleId::from(remote_service.uuid()))
+            .collect();
+        Ok(services)
+    }
+
+    /// Inner version of [BleConnection::get_characteristic_async]
+    async fn _get_characteristic_async(
+        &mut self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+    ) -> Result<RemoteCharacteristic, BleError> {
+        self.check_connected()?;
+        let remote_service = self
+            .ble_client
+            .get_service(service_id.to_uuid())
+            .await
+            .map_err(BleError::from_service_context)?;
+        let remote_characteristic = remote_service
+            .get_characteristic(characteristic_id.to_uuid())
+            .await
+            .map_err(BleError::from_characteristic_context)?;
+        Ok(RemoteCharacteristic::new(
+            remote_characteristic,
+            self.notifier.clone(),
+        ))
+    }
+
+    /// Inner version of [BleConnection::get_all_characteristics_async]
+    async fn _get_all_characteristics_async(
+        &mut self,
+        service_id: &BleId,
+    ) -> Result<Vec<RemoteCharacteristic>, BleError> {
+        self.check_connected()?;
+        let remote_service = self
+            .ble_client
+            .get_service(service_id.to_uuid())
+            .await
+            .map_err(BleError::from_service_context)?;
+        let remote_characteristics = remote_service
+            .get_characteristics()
+            .await?
+            .map(|remote_characteristic| {
+                RemoteCharacteristic::new(remote_characteristic, self.notifier.clone())
+            })
+            .collect();
+        Ok(remote_characteristics)
+    }
+
+    /// Sets the connection parameters of this connection. Same as
+    /// [BleConnection::update_connection_params].
+    ///
+    /// # Arguments
+    ///
+    /// - `min_interval`: The minimum connection interval, time between BLE events. This value
+    /// must range between 7.5ms and 4000ms in 1.25ms units, this interval will be used while transferring data
+    /// in max speed.
+    /// - `max_interval`: The maximum connection interval, time between BLE events. This value
+    /// must range between 7.5ms and 4000ms in 1.25ms units, this interval will be used to save energy.
+    /// - `latency`: The number of packets that can be skipped (packets will be skipped only if there is no data to answer).
+    /// - `timeout`: The maximum time to wait after the last packet arrived to consider connection lost.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the configuration of connection settings completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::InvalidParameters`: if any of the parameters is out of its range
+    /// - `BleError::DeviceNotFound`: if the device has unexpectidly disconnected
+    /// - `BleError::Code`: on other errors
+    pub fn set_connection_settings(
+        &mut self,
+        min_interval: u16,
+        max_interval: u16,
+        latency: u16,
+        timeout: u16,
+    ) -> Result<(), BleError> {
+        self.update_connection_params(min_interval, max_interval, latency, timeout)
+    }
+
+    /// Starts a negotiation of the parameters of the open connection. The server may accept
+    /// them or answer with its own, the resulting ones are reported to
+    /// [BleConnection::on_link_change] and [crate::ble::BleClient::on_params_updated].
+    ///
+    /// # Arguments
+    ///
+    /// - `min_interval`: The minimum connection interval, from 6 (7.5 ms) to 3200 (4 s) in
+    ///   1.25 ms units
+    /// - `max_interval`: The maximum connection interval, at least `min_interval`
+    /// - `latency`: The number of connection events the server can skip, up to 499
+    /// - `timeout`: The supervision timeout, from 10 (100 ms) to 3200 (32 s) in 10 ms units. It
+    ///   must be longer than `2 * (1 + latency) * max_interval`
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::InvalidParameters`: if any of the parameters is out of its range
+    /// - `BleError::DeviceNotFound`: if the device has unexpectidly disconnected
+    /// - `BleError::Code`: on other errors
+    pub fn update_connection_params(
+        &mut self,
+        min_interval: u16,
+        max_interval: u16,
+        latency: u16,
+        timeout: u16,
+    ) -> Result<(), BleError> {
+        self.check_connected()?;
+        check_connection_params(min_interval, max_interval, latency, timeout)?;
+        self.ble_client
+            .update_conn_params(min_interval, max_interval, latency, timeout)
+            .map_err(BleError::from_connection_params_context)
+    }
+
+    /// Starts an MTU exchange with the server, offering the given MTU. The MTU is exchanged only
+    /// once per connection and the client already starts it when connecting, offering the MTU set
+    /// with [crate::ble::BleClient::set_preferred_mtu], so this is only useful if it failed. The
+    /// negotiated MTU is reported to [BleConnection::on_link_change].
+    ///
+    /// # Arguments
+    ///
+    /// - `mtu`: The MTU offered, between 23 and 517
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::InvalidParameters`: if the MTU is not between 23 and 517
+    /// - `BleError::MtuAlreadyExchanged`: if the MTU of the connection was already exchanged
+    /// - `BleError::Code`: on other errors
+    pub fn request_mtu(&mut self, mtu: u16) -> Result<(), BleError> {
+        request_mtu(self.connection_information()?.conn_handle, mtu)
+    }
+
+    /// Enables the LE Data Length Extension on the connection, so link layer packets carry up
+    /// to `tx_octets` bytes instead of 27. The negotiated lengths are reported to
+    /// [BleConnection::on_link_change] and in the [ConnectionInformation].
+    ///
+    /// # Arguments
+    ///
+    /// - `tx_octets`: The biggest payload of the packets sent, between 27 and 251
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::InvalidParameters`: if `tx_octets` is not between 27 and 251
+    /// - `BleError::Code`: on other errors
+    pub fn set_data_length(&mut self, tx_octets: u16) -> Result<(), BleError> {
+        set_data_length(self.connection_information()?.conn_handle, tx_octets)
+    }
+
+    /// Sets the PHYs preferred on the connection. The controller changes to them only if the
+    /// server supports them, the PHYs in use are reported to [BleConnection::on_link_change] and
+    /// in the [ConnectionInformation].
+    ///
+    /// # Arguments
+    ///
+    /// - `tx_phy`: The PHY preferred to send
+    /// - `rx_phy`: The PHY preferred to receive
+    /// - `coded_scheme`: The coding used if any of the PHYs is [BlePhy::LeCoded]
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::Code`: if the PHYs are not supported by the controller, or on other errors
+    pub fn set_preferred_phy(
+        &mut self,
+        tx_phy: BlePhy,
+        rx_phy: BlePhy,
+        coded_scheme: CodedPhyScheme,
+    ) -> Result<(), BleError> {
+        let conn_handle = self.connection_information()?.conn_handle;
+        set_preferred_phy(conn_handle, tx_phy, rx_phy, coded_scheme)
+    }
+
+    /// Blocking method that opens an L2CAP connection-oriented channel to a PSM the server
+    /// listens on, for bulk transfers that do not fit in characteristics
+    ///
+    /// # Arguments
+    ///
+    /// - `psm`: The PSM, from 0x0080 to 0x00FF for the ones not assigned by the Bluetooth SIG
+    /// - `mtu`: The biggest SDU this side receives, at least 23 bytes
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the open [L2capChannel], or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::InvalidParameters`: if the PSM or the MTU are not valid
+    /// - `BleError::CouldNotConnectToDevice`: if the server refused the channel
+    /// - `BleError::Code`: if the BLE stack fails to open the channel
+    pub fn l2cap_connect(&mut self, psm: u16, mtu: u16) -> Result<L2capChannel, BleError> {
+        block_on(self.l2cap_connect_async(psm, mtu))
+    }
+
+    /// Non blocking async version of [BleConnection::l2cap_connect]
+    pub async fn l2cap_connect_async(
+        &mut self,
+        psm: u16,
+        mtu: u16,
+    ) -> Result<L2capChannel, BleError> {
+        let conn_handle = self.connection_information()?.conn_handle;
+        connect_channel(conn_handle, psm, mtu).await
+    }
+
+    /// Blocking method that secures the connection. If the server is not bonded, the pairing is
+    /// started using the security set with [crate::Microcontroller::ble_secure_client], otherwise
+    /// the encryption of the bond is restored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the connection was encrypted, or a `BleError` if the pairing failed
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::Code`: if the pairing failed, for example by a wrong passkey
+    pub fn secure_connection(&mut self) -> Result<(), BleError> {
+        block_on(self.secure_connection_async())
+    }
+
+    /// Non blocking async version of [BleConnection::secure_connection]
+    pub async fn secure_connection_async(&mut self) -> Result<(), BleError> {
+        self.check_connected()?;
+        Ok(self.ble_client.secure_connection().await?)
+    }
+
+    fn check_connected(&self) -> Result<(), BleError> {
+        if !self.ble_client.connected() {
+            return Err(BleError::Disconnected);
+        }
+        Ok(())
+    }
+
+    /// Gets the descriptor of the connection from the BLE stack, looking it up by the address of
+    /// the server
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: if the connection is closed
+    fn gap_desc(&self) -> Result<sys::ble_gap_conn_desc, BleError> {
+        self.check_connected()?;
+        let address = self.address.as_ref().ok_or(BleError::Disconnected)?;
+        find_connection(address).map_err(|_| BleError::Disconnected)
+    }
+
+    /// Closes the connection. The disconnection callback, if set, is executed once the
+    /// disconnection is completed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if it was able to disconnect or `BleError` on failure.
+    pub fn disconnect(&mut self) -> Result<(), BleError> {
+        match self.ble_client.disconnect().map_err(BleError::from) {
+            Ok(_) => Ok(()),
+            Err(err) => match err {
+                BleError::DeviceNotFound => Ok(()),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl BleConnection {
+    /// Creates a new disconnected BleConnection
+    ///
+    /// # Arguments
+    ///
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] after an interrupt
+    /// - `pairing`: The callbacks of the client used when pairing
+    pub(crate) fn new(notifier: Notifier, pairing: &PairingCallbacks) -> Self {
+        let disconnection_queue = ISRQueue::new(10);
+        let link_changes = subscribe_link_changes(notifier.clone());
+        let mut inner = _BleConnection::new(notifier.clone());
+        let mut queue_ref = disconnection_queue.clone();
+        let passkey_ref = pairing.clone();
+        let confirm_ref = pairing.clone();
+        inner
+            .ble_client
+            .on_disconnect(move |reason| {
+                notifier.notify();
+                _ = queue_ref.send_timeout(reason, 1_000);
+            })
+            .on_passkey_request(move || passkey_ref.passkey())
+            .on_confirm_pin(move |pin| confirm_ref.confirm_pin(pin));
+
+        Self {
+            inner: SharableRef::new_sharable(inner),
+            updater: SharableRef::new_sharable(BleConnectionUpdater {
+                remote_characteristics: HashMap::new(),
+                user_on_disconnection: None,
+                user_on_link_change: None,
+                disconnection_queue,
+                link_changes,
+                closed: false,
+                disconnection_reason: None,
+            }),
+        }
+    }
+
+    /// Connects to the server of the given address
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::DeviceNotFound`: if the device was not found when trying to connect to it
+    /// - `BleError::Code`: on other errors
+    pub(crate) async fn connect_async(&mut self, address: &BLEAddress) -> Result<(), BleError> {
+        self.inner
+            .deref_mut()
+            .ble_client
+            .connect(address)
+            .await
+            .map_err(BleError::from_connection_context)?;
+        self.inner.deref_mut().address = Some(*address);
+        Ok(())
+    }
+
+    /// Efectibly clones the connection, but is only allowed in the crate
+    pub(crate) fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            updater: self.updater.clone(),
+        }
+    }
+
+    /// Returns whether the disconnection of the connection has already been handled
+    pub(crate) fn is_closed(&self) -> bool {
+        self.updater.deref().closed
+    }
+
+    /// Gets the reason of the disconnection, once it was handled
+    pub(crate) fn disconnection_reason(&self) -> Option<i32> {
+        self.updater.deref().disconnection_reason
+    }
+
+    /// Sets a callback to be executed when the connection is closed, either by the server, by
+    /// losing the signal, or by calling [Self::disconnect].
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the connection and the reason of the disconnection
+    ///
+    /// # Returns
+    ///
+    /// The BleConnection itself
+    pub fn on_disconnect<C: FnMut(&mut BleConnection, i32) + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.updater.deref_mut().user_on_disconnection = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback to be executed each time the MTU, the data length or the PHYs of the
+    /// connection change.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the connection and the [LinkChange]
+    ///
+    /// # Returns
+    ///
+    /// The BleConnection itself
+    pub fn on_link_change<C: FnMut(&mut BleConnection, LinkChange) + 'static>(
+        &mut self,
+        callback: C,
+    ) -> &mut Self {
+        self.updater.deref_mut().user_on_link_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Blocking method that attempts to get a characteristic from a service of the server.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The id of the service which owns the characteristic.
+    /// - `characteristic_id`: The id of the desired characterisitc, of the given service.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `RemoteCharacteristic` if it was able to find the characteristic in the
+    /// specified service or `BleError` if a failure occured.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::ServiceNotFound`: if the device does not have a service of the specified id
+    /// - `BleError::CharacteristicNotFound`: if the devices's service does not have a characteristic of the
+    ///    specified id
+    /// - `BleError::Code`: on other errors
+    pub fn get_characteristic(
+        &mut self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+    ) -> Result<RemoteCharacteristic, BleError> {
+        block_on(self.get_characteristic_async(service_id, characteristic_id))
+    }
+
+    /// Blocking method that attempts to get all characteristics of a given service of the server
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The id of the service .
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `Vec<RemoteCharacteristic>` if it was able to find the specified service or
+    /// `BleError` if a failure occured.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::ServiceNotFound`: if the device does not have a service of the specified id
+    /// - `BleError::Code`: on other errors
+    pub fn get_all_characteristics(
+        &mut self,
+        service_id: &BleId,
+    ) -> Result<Vec<RemoteCharacteristic>, BleError> {
+        block_on(self.get_all_characteristics_async(service_id))
+    }
+
+    /// Non blocking async version of [Self::get_characteristic]
+    pub async fn get_characteristic_async(
+        &mut self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+    ) -> Result<RemoteCharacteristic, BleError> {
+        let characteristic = self
+            .inner
+            .deref_mut()
+            ._get_characteristic_async(service_id, characteristic_id)
+            .await?;
+        self.updater.deref_mut().add_characteristic(&characteristic);
+        Ok(characteristic)
+    }
+
+    /// Non blocking async version of [Self::get_all_characteristics]
+    pub async fn get_all_characteristics_async(
+        &mut self,
+        service_id: &BleId,
+    ) -> Result<Vec<RemoteCharacteristic>, BleError> {
+        let characteristics = self
+            .inner
+            .deref_mut()
+            ._get_all_characteristics_async(service_id)
+            .await?;
+        for c in &characteristics {
+            self.updater.deref_mut().add_characteristic(c);
+        }
+        Ok(characteristics)
+    }
+
+    /// Blocking method that walks the whole GATT table of the server: every service, every
+    /// characteristic with its properties and value, and every descriptor with its value.
+    /// Standard attributes are named after their standard ids, like
+    /// [crate::ble::utils::ble_standard_uuids::StandardServiceId].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GattSnapshot` of the server, or `BleError` if a failure occured.
+    /// Values that can not be read, for example because they need a secure connection, are left
+    /// as `None` instead of failing the discovery.
+    ///
+    /// # Errors
+    /// - `BleError::Disconnected`: if the connection is closed
+    /// - `BleError::Code`: on other errors
+    pub fn discover_all(&mut self) -> Result<GattSnapshot, BleError> {
+        block_on(self.discover_all_async())
+    }
+
+    /// Non blocking async version of [Self::discover_all]
+    pub async fn discover_all_async(&mut self) -> Result<GattSnapshot, BleError> {
+        let mut snapshot = GattSnapshot::new(self.address()?);
+        for service_id in self.get_all_service_ids_async().await? {
+            let characteristics = self
+                .inner
+                .deref_mut()
+                ._get_all_characteristics_async(&service_id)
+                .await?;
+            let mut service = ServiceSnapshot::new(service_id);
+            for mut characteristic in characteristics {
+                let characteristic = characteristic.snapshot_async().await?;
+                service.characteristics.push(characteristic);
+            }
+            snapshot.services.push(service);
+        }
+        Ok(snapshot)
+    }
+
+    /// Executes the notify callbacks of the characteristics of the connection, the link change
+    /// callback and the disconnection callback if the connection was closed. On disconnection,
+    /// the subscriptions of its characteristics are dropped and reported to their error callbacks
+    /// first.
+    ///
+    /// # Returns
+    ///
+    /// The link changes of the connection handled
+    pub(crate) fn update(&mut self) -> Vec<LinkChange> {
+        for c in self.updater.deref_mut().remote_characteristics.values_mut() {
+            c.execute_if_notified()
+        }
+        let changes = self.handle_link_changes();
+
+        let mut queue = self.updater.deref().disconnection_queue.clone();
+        while let Ok(reason) = queue.try_recv() {
+            let characteristics: Vec<RemoteCharacteristic> = self
+                .updater
+                .deref()
+                .remote_characteristics
+                .values()
+                .map(|c| c.clone())
+                .collect();
+            for mut c in characteristics {
+                c.handle_disconnection()
+            }
+            let callback = self.updater.deref_mut().user_on_disconnection.take();
+            if let Some(mut callback) = callback {
+                callback(self, reason);
+                let mut updater = self.updater.deref_mut();
+                if updater.user_on_disconnection.is_none() {
+                    updater.user_on_disconnection = Some(callback);
+                }
+            }
+            let mut updater = self.updater.deref_mut();
+            updater.closed = true;
+            updater.disconnection_reason = Some(reason);
+        }
+        changes
+    }
+
+    /// Executes the link change callback for every link change of this connection
+    ///
+    /// # Returns
+    ///
+    /// The link changes of this connection
+    fn handle_link_changes(&mut self) -> Vec<LinkChange> {
+        let events: Vec<LinkEvent> = self.updater.deref().link_changes.try_iter().collect();
+        let Ok(conn_handle) = self.connection_information().map(|info| info.conn_handle) else {
+            return vec![];
+        };
+        let changes: Vec<LinkChange> = events
+            .iter()
+            .filter(|e| e.conn_handle == conn_handle)
+            .map(|e| e.change)
+            .collect();
+        let Some(mut callback) = self.updater.deref_mut().user_on_link_change.take() else {
+            return changes;
+        };
+        for change in changes.iter() {
+            callback(self, *change);
+        }
+        let mut updater = self.updater.deref_mut();
+        if updater.user_on_link_change.is_none() {
+            updater.user_on_link_change = Some(callback);
+        }
+        changes
+    }
+}
@@ -1,6 +1,15 @@
-use super::utils::{
-    BleError, BleId, Characteristic, ConnectionInformation, ConnectionMode, DiscoverableMode,
-    Service,
+use super::{
+    utils::{
+        bonded_addresses, characteristic_value_handle, clear_bonds, delete_bond,
+        forward_characteristic_accesses, legacy_advertising, request_mtu, send_value_update,
+        set_data_length, set_preferred_phy, set_raw_advertising_data, subscribe_link_changes,
+        track_characteristic_subscriptions, AdvertisementBuilder, AttributeRequest, BleError,
+        BleId, BlePhy, Characteristic, CharacteristicHandlers, ClientFilter, CodedPhyScheme,
+        ConnectionInformation, ConnectionMode, DiscoverableMode, GattValue, IndicationSlot,
+        LinkChange, LinkEvent, PendingIndication, Service, SubscriptionEvent, SubscriptionTable,
+        TypedCharacteristic,
+    },
+    L2capListener,
 };
 use crate::{
     utils::{
@@ -12,42 +21,110 @@ use crate::{
     InterruptDriver,
 };
 use esp32_nimble::{
-    utilities::mutex::Mutex, BLEAdvertisementData, BLEAdvertising, BLECharacteristic, BLEDevice,
+    utilities::mutex::Mutex, BLEAddress, BLEAdvertising, BLECharacteristic, BLEConnDesc, BLEDevice,
     BLEServer, BLEService, NimbleProperties,
 };
 use esp_idf_svc::hal::task;
 use sharable_reference_macro::sharable_reference_wrapper;
-use std::sync::{
-    atomic::{AtomicU8, Ordering},
-    Arc,
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
 };
 
 const DEFAULT_MAX_CLIENTS: u8 = 1;
 
 type ConnUserCallback<'a> = dyn FnMut(&mut BleServer<'a>, &ConnectionInformation) + 'a;
 type ConnCountingCallback<'a> = dyn FnMut(&mut BleServer<'a>) + 'a;
+type SubscriptionCallback<'a> = dyn FnMut(&mut BleServer<'a>, &SubscriptionEvent) + 'a;
+type LinkCallback<'a> = dyn FnMut(&mut BleServer<'a>, &ConnectionInformation, LinkChange) + 'a;
 
 /// Abstraction to create a BLE server, the side that has the information to be used in a connection
 /// oriented relationship. Contains:
 /// * `advertising_name`: Clients scanning will see the advertising name before connection.
 /// * `ble_server`: BleServer driver.
 /// * `services`: The servere will hace information for the clients to see. All this information will be encapsulated on different services.
-/// * `advertisement`: Abstraction that represents the serve's advertisement.
+/// * `advertisement`: Abstraction that represents the serve's advertisement, or `None` if the extended advertising is enabled.
+/// * `custom_advertisement`: Advertisement data set by the user, replacing the name and services advertised by default.
 /// * `remaining_connections`: maximum amount of simultaneous clients.
 /// * `user_on_connection`: Callback that will be executed for each client connected.
 /// * `user_on_disconnection`: Callback that will be executed for each client disconnected.
+/// * `attribute_handlers`: Read and write callbacks of each characteristic, identified by its service and characteristic ids.
+/// * `attribute_requests`: Reads and writes made by clients that must be answered by the attribute handlers.
+/// * `attribute_sender`: Sender used by the BLE stack to send the attribute requests.
+/// * `attribute_notifier`: Notifier used to wake up the microcontroller on attribute requests.
+/// * `client_admission`: Filter of the clients allowed to connect, shared with the BLE stack.
+/// * `subscriptions`: Clients subscribed to each characteristic, kept up to date by the BLE stack.
+/// * `subscription_events`: Subscription changes made by clients, to be handled on the main loop.
+/// * `subscription_sender`: Sender used by the BLE stack to send the subscription changes.
+/// * `user_on_subscription`: Callback that will be executed for each subscription change.
+/// * `indications`: Where the BLE stack sends the result of the indications of each characteristic.
+/// * `link_changes`: Changes of the MTU, data length or PHY of the connections, sent by the BLE stack.
+/// * `user_on_link_change`: Callback that will be executed for each link change of a client.
 struct _BleServer<'a> {
     advertising_name: String,
     ble_server: &'a mut BLEServer,
     services: Vec<Service>,
-    advertisement: &'a Mutex<BLEAdvertising>,
+    advertisement: Option<&'a Mutex<BLEAdvertising>>,
+    custom_advertisement: Option<AdvertisementBuilder>,
     remaining_connections: RemainingConnections,
     user_on_connection: Option<ConnectionCallback<'a>>,
     user_on_disconnection: Option<ConnectionCallback<'a>>,
+    attribute_handlers: Vec<(BleId, BleId, CharacteristicHandlers)>,
+    attribute_requests: Receiver<AttributeRequest>,
+    attribute_sender: Sender<AttributeRequest>,
+    attribute_notifier: Notifier,
+    client_admission: Arc<Mutex<ClientAdmission>>,
+    subscriptions: Arc<Mutex<SubscriptionTable>>,
+    subscription_events: Receiver<SubscriptionEvent>,
+    subscription_sender: Sender<SubscriptionEvent>,
+    user_on_subscription: Option<Box<SubscriptionCallback<'a>>>,
+    indications: HashMap<(BleId, BleId), IndicationSlot>,
+    link_changes: Receiver<LinkEvent>,
+    user_on_link_change: Option<Box<LinkCallback<'a>>>,
+}
+
+/// The [ClientFilter] of a server, together with the connections it rejected. The callbacks of
+/// the rejected connections are not executed.
+#[derive(Default)]
+struct ClientAdmission {
+    filter: ClientFilter,
+    rejected: Vec<u16>,
+}
+
+impl ClientAdmission {
+    /// Returns whether a new connection is accepted, remembering it if it is not
+    fn admit(&mut self, desc: &BLEConnDesc) -> bool {
+        let bonded = if self.filter.needs_bonds() {
+            bonded_addresses().unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let accepted = self.filter.accepts(&desc.address(), &bonded)
+            || self.filter.accepts(&desc.id_address(), &bonded);
+        if !accepted {
+            self.rejected.push(desc.conn_handle());
+        }
+        accepted
+    }
+
+    /// Returns whether a connection that ended had been rejected, forgetting it
+    fn forget_rejected(&mut self, conn_handle: u16) -> bool {
+        let amount = self.rejected.len();
+        self.rejected.retain(|handle| *handle != conn_handle);
+        self.rejected.len() != amount
+    }
 }
 
 /// Abstraction to create a BLE server, the side that has the information to be used in a connection
 /// oriented relationship.
+///
+/// If the extended advertising is enabled with `CONFIG_BT_NIMBLE_EXT_ADV`, the server can not
+/// advertise by itself and its advertising settings have no effect. It is advertised with a
+/// connectable set of a `BleAdvertiser` instead.
 pub struct BleServer<'a> {
     inner: SharableRef<_BleServer<'a>>,
 }
@@ -207,6 +284,8 @@ impl<'a> _BleServer<'a> {
     /// - `services`: A vector with multiple Service that will contain the server information
     /// - `connection_notifier`: A Notifier used to notify when the connection callback should be executed
     /// - `disconnection_notifier`: A Notifier used to notify when the disconnection callback should be executed
+    /// - `attribute_notifier`: A Notifier used to notify when a read or write callback, a subscription
+    ///   handler or a link change handler should be executed
     ///
     /// # Returns
     ///
@@ -223,15 +302,32 @@ impl<'a> _BleServer<'a> {
         services: &Vec<Service>,
         connection_notifier: Notifier,
         disconnection_notifier: Notifier,
+        attribute_notifier: Notifier,
     ) -> Result<Self, BleError> {
+        let (attribute_sender, attribute_requests) = channel();
+        let (subscription_sender, subscription_events) = channel();
+        let link_changes = subscribe_link_changes(attribute_notifier.clone());
         let mut server = _BleServer {
             advertising_name: name,
             ble_server: ble_device.get_server(),
             services: services.clone(),
-            advertisement: ble_device.get_advertising(),
+            advertisement: legacy_advertising(ble_device).ok(),
+            custom_advertisement: None,
             remaining_connections: RemainingConnections::new(DEFAULT_MAX_CLIENTS),
             user_on_connection: Some(ConnectionCallback::new(connection_notifier)),
             user_on_disconnection: Some(ConnectionCallback::new(disconnection_notifier)),
+            attribute_handlers: Vec::new(),
+            attribute_requests,
+            attribute_sender,
+            attribute_notifier,
+            client_admission: Arc::new(Mutex::new(ClientAdmission::default())),
+            subscriptions: Arc::new(Mutex::new(SubscriptionTable::default())),
+            subscription_events,
+            subscription_sender,
+            user_on_subscription: None,
+            indications: HashMap::new(),
+            link_changes,
+            user_on_link_change: None,
         };
 
         for service in services {
@@ -246,7 +342,12 @@ impl<'a> _BleServer<'a> {
         let user_on_connection = self.user_on_connection.as_mut().unwrap();
         let notifier_ref = user_on_connection.notifier.clone();
         let mut con_info_ref = user_on_connection.info_queue.clone();
-        self.ble_server.on_connect(move |_, info| {
+        let admission_ref = self.client_admission.clone();
+        self.ble_server.on_connect(move |server, info| {
+            if !admission_ref.lock().admit(info) {
+                _ = server.disconnect(info.conn_handle());
+                return;
+            }
             notifier_ref.notify();
             _ = con_info_ref.send_timeout(
                 ConnectionInformation::from_bleconn_desc(info, true, Ok(())),
@@ -260,8 +361,12 @@ impl<'a> _BleServer<'a> {
         let user_on_disconnection = self.user_on_disconnection.as_mut().unwrap();
         let notifier_ref = user_on_disconnection.notifier.clone();
         let mut con_info_ref = user_on_disconnection.info_queue.clone();
+        let admission_ref = self.client_admission.clone();
 
         self.ble_server.on_disconnect(move |info, res| {
+            if admission_ref.lock().forget_rejected(info.conn_handle()) {
+                return;
+            }
             notifier_ref.notify();
             _ = con_info_ref.send_timeout(
                 ConnectionInformation::from_bleconn_desc(info, false, res),
@@ -320,6 +425,51 @@ impl<'a> _BleServer<'a> {
         self
     }
 
+    /// Sets which clients can connect to the server. Clients not accepted by the filter are
+    /// disconnected as soon as they connect, and the connection and disconnection handlers are
+    /// not executed for them.
+    ///
+    /// # Arguments
+    ///
+    /// - `filter`: The [ClientFilter] deciding which clients are accepted
+    ///
+    /// # Returns
+    ///
+    /// The _BleServer itself
+    pub fn set_client_filter(&mut self, filter: ClientFilter) -> &mut Self {
+        self.client_admission.lock().filter = filter;
+        self.subscribe_on_connection();
+        self.subscribe_on_disconnection();
+        self
+    }
+
+    /// Gets the addresses of every client bonded with this device
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the bonds could not be read from storage
+    pub fn bonded_addresses(&self) -> Result<Vec<BLEAddress>, BleError> {
+        bonded_addresses()
+    }
+
+    /// Deletes the bond with a device, so it must pair again on its next connection
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If there is no bond with the device or it could not be deleted
+    pub fn delete_bond(&mut self, address: &BLEAddress) -> Result<(), BleError> {
+        delete_bond(address)
+    }
+
+    /// Deletes every bond of this device. Bonds are shared by the client and the server roles.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the bonds could not be deleted
+    pub fn clear_bonds(&mut self) -> Result<(), BleError> {
+        clear_bonds()
+    }
+
     /// The conn_handle is obtained with the ConnectionInformation inside the closure of
     /// connection_handler
     ///
@@ -361,6 +511,127 @@ impl<'a> _BleServer<'a> {
             .map_err(BleError::from_connection_params_context)
     }
 
+    /// Starts an MTU exchange with a client, offering the given MTU. The MTU is exchanged only
+    /// once per connection, usually started by the client when connecting, so this is only
+    /// useful with clients that do not start it. The negotiated MTU is reported to the
+    /// [Self::link_change_handler] and in the `mtu` of the [ConnectionInformation].
+    ///
+    /// # Arguments
+    ///
+    /// - `info`: The ConnectionInformation of the client
+    /// - `mtu`: The MTU offered, between 23 and 517
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the exchange was started, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the MTU is not between 23 and 517
+    /// - `BleError::MtuAlreadyExchanged`: If the MTU of the connection was already exchanged
+    /// - `BleError::Code`: on other errors.
+    pub fn request_mtu(&mut self, info: &ConnectionInformation, mtu: u16) -> Result<(), BleError> {
+        request_mtu(info.conn_handle, mtu)
+    }
+
+    /// Enables the LE Data Length Extension on the connection with a client, so link layer
+    /// packets carry up to `tx_octets` bytes instead of 27. The negotiated lengths are reported to
+    /// the [Self::link_change_handler] and in the [ConnectionInformation].
+    ///
+    /// # Arguments
+    ///
+    /// - `info`: The ConnectionInformation of the client
+    /// - `tx_octets`: The biggest payload of the packets sent, between 27 and 251
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the request was sent, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If `tx_octets` is not between 27 and 251
+    /// - `BleError::Code`: on other errors.
+    pub fn set_data_length(
+        &mut self,
+        info: &ConnectionInformation,
+        tx_octets: u16,
+    ) -> Result<(), BleError> {
+        set_data_length(info.conn_handle, tx_octets)
+    }
+
+    /// Sets the PHYs preferred on the connection with a client. The controller changes to them
+    /// only if the client supports them, the PHYs in use are reported to the
+    /// [Self::link_change_handler] and in the [ConnectionInformation].
+    ///
+    /// # Arguments
+    ///
+    /// - `info`: The ConnectionInformation of the client
+    /// - `tx_phy`: The PHY preferred to send
+    /// - `rx_phy`: The PHY preferred to receive
+    /// - `coded_scheme`: The coding used if any of the PHYs is [BlePhy::LeCoded]
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the request was sent, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the PHYs are not supported by the controller, or on other errors.
+    pub fn set_preferred_phy(
+        &mut self,
+        info: &ConnectionInformation,
+        tx_phy: BlePhy,
+        rx_phy: BlePhy,
+        coded_scheme: CodedPhyScheme,
+    ) -> Result<(), BleError> {
+        set_preferred_phy(info.conn_handle, tx_phy, rx_phy, coded_scheme)
+    }
+
+    /// Sets a callback to be executed each time the MTU, the data length or the PHYs of the
+    /// connection with a client change.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler`: A closure that receives the updated [ConnectionInformation] of the client and
+    ///   the [LinkChange]
+    ///
+    /// # Returns
+    ///
+    /// The _BleServer itself
+    pub fn link_change_handler<
+        C: FnMut(&mut BleServer<'a>, &ConnectionInformation, LinkChange) + 'a,
+    >(
+        &mut self,
+        handler: C,
+    ) -> &mut Self {
+        self.user_on_link_change = Some(Box::new(handler));
+        self
+    }
+
+    /// Starts listening for L2CAP connection-oriented channels on a PSM. Clients open them with
+    /// [crate::ble::BleClient::l2cap_connect], and they are gotten with [L2capListener::accept].
+    ///
+    /// # Arguments
+    ///
+    /// - `psm`: The PSM, from 0x0080 to 0x00FF for the ones not assigned by the Bluetooth SIG
+    /// - `mtu`: The biggest SDU this side receives, at least 23 bytes
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [L2capListener], or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: if the PSM or the MTU are not valid
+    /// - `BleError::Code`: if the BLE stack fails to register the PSM
+    pub fn l2cap_listen(&mut self, psm: u16, mtu: u16) -> Result<L2capListener, BleError> {
+        L2capListener::new(psm, mtu)
+    }
+
     /// Sets the max amount of clients the server can be connected to concurrently at any given time.
     /// After each connection a new advertisement will be made if there are still connections left to be done.
     ///
@@ -403,10 +674,12 @@ impl<'a> _BleServer<'a> {
     ///
     /// The _BleServer itself
     pub fn set_advertising_interval(&mut self, min_interval: u16, max_interval: u16) -> &mut Self {
-        self.advertisement
-            .lock()
-            .min_interval(min_interval)
-            .max_interval(max_interval);
+        if let Some(advertisement) = self.advertisement {
+            advertisement
+                .lock()
+                .min_interval(min_interval)
+                .max_interval(max_interval);
+        }
         self
     }
 
@@ -418,7 +691,9 @@ impl<'a> _BleServer<'a> {
     ///
     /// The _BleServer itself
     pub fn set_high_advertising_duty_cycle(&mut self) -> &mut Self {
-        self.advertisement.lock().high_duty_cycle(true);
+        if let Some(advertisement) = self.advertisement {
+            advertisement.lock().high_duty_cycle(true);
+        }
         self
     }
 
@@ -430,7 +705,9 @@ impl<'a> _BleServer<'a> {
     ///
     /// The _BleServer itself
     pub fn set_low_advertising_duty_cycle(&mut self) -> &mut Self {
-        self.advertisement.lock().high_duty_cycle(false);
+        if let Some(advertisement) = self.advertisement {
+            advertisement.lock().high_duty_cycle(false);
+        }
         self
     }
 
@@ -444,21 +721,48 @@ impl<'a> _BleServer<'a> {
     ///
     /// The _BleServer itself
     pub fn set_discoverable_mode(&mut self, disc_mode: DiscoverableMode) -> &mut Self {
+        let Some(advertisement) = self.advertisement else {
+            return self;
+        };
         match disc_mode {
             DiscoverableMode::NonDiscoverable => {
-                self.advertisement.lock().disc_mode(disc_mode.get_code())
+                advertisement.lock().disc_mode(disc_mode.get_code())
             }
-            DiscoverableMode::GeneralDiscoverable(min_interval, max_interval) => self
-                .advertisement
+            DiscoverableMode::GeneralDiscoverable(min_interval, max_interval) => advertisement
                 .lock()
                 .disc_mode(disc_mode.get_code())
                 .min_interval(min_interval)
                 .max_interval(max_interval),
         };
-        self.advertisement.lock().disc_mode(disc_mode.get_code());
+        advertisement.lock().disc_mode(disc_mode.get_code());
         self
     }
 
+    /// Sets the data the server advertises, instead of its name and the uuids of its services. If
+    /// the server is already advertising, the advertised data is updated.
+    ///
+    /// # Arguments
+    ///
+    /// - `advertisement`: The AdvertisementBuilder with the data to advertise
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the advertisement is valid, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data does not fit in the advertisement and the scan response
+    /// - `BleError::AdvertisementError`: If the advertising operation failed
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled
+    pub fn set_advertisement(
+        &mut self,
+        advertisement: &AdvertisementBuilder,
+    ) -> Result<(), BleError> {
+        advertisement.build()?;
+        self.custom_advertisement = Some(advertisement.clone());
+        self.create_advertisement_data()
+    }
+
     ///Sets the connection mode of the advertisment.
     ///
     /// # Arguments
@@ -469,9 +773,11 @@ impl<'a> _BleServer<'a> {
     ///
     /// The _BleServer itself
     pub fn set_connection_mode(&mut self, conn_mode: ConnectionMode) -> &mut Self {
-        self.advertisement
-            .lock()
-            .advertisement_type(conn_mode.get_code());
+        if let Some(advertisement) = self.advertisement {
+            advertisement
+                .lock()
+                .advertisement_type(conn_mode.get_code());
+        }
         self
     }
 
@@ -519,6 +825,7 @@ impl<'a> _BleServer<'a> {
     }
 
     /// Set a new characteristic or update the value in an existent characteristic to the server.
+    /// If the characteristic has read or write callbacks, they replace the previous ones.
     ///
     /// # Arguments
     ///
@@ -541,15 +848,132 @@ impl<'a> _BleServer<'a> {
         let server_service =
             task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await });
 
-        match server_service {
-            Some(service) => {
-                match self.try_to_update_characteristic(service, characteristic, false) {
-                    Ok(_) => Ok(()),
-                    Err(_) => self.create_new_characteristic(characteristic, service),
-                }
+        let service = server_service.ok_or(BleError::ServiceNotFound)?;
+        if self
+            .try_to_update_characteristic(service, service_id, characteristic, false)
+            .is_err()
+        {
+            self.create_new_characteristic(characteristic, service)?;
+        }
+        self.set_attribute_handlers(service_id, characteristic)?;
+        self.track_subscriptions(service_id, characteristic)
+    }
+
+    /// Makes the BLE stack forward the reads and writes of a characteristic to its callbacks,
+    /// if it has any.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: A BleId to identify the service the charactersitic is part of.
+    /// - `characteristic`: The Characteristic with the callbacks
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    fn set_attribute_handlers(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<(), BleError> {
+        if characteristic.handlers.is_empty() {
+            return Ok(());
+        }
+        let service =
+            task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await })
+                .ok_or(BleError::ServiceNotFound)?
+                .clone();
+        let locked_service = service.lock();
+        let server_characteristic = task::block_on(async {
+            locked_service
+                .get_characteristic(characteristic.id.to_uuid())
+                .await
+        })
+        .ok_or(BleError::CharacteristicNotFound)?;
+
+        let position = self
+            .attribute_handlers
+            .iter()
+            .position(|(s_id, c_id, _)| s_id == service_id && *c_id == characteristic.id);
+        let index = match position {
+            Some(index) => {
+                self.attribute_handlers[index].2 = characteristic.handlers.clone();
+                index
             }
-            None => Err(BleError::ServiceNotFound),
+            None => {
+                self.attribute_handlers.push((
+                    service_id.clone(),
+                    characteristic.id.clone(),
+                    characteristic.handlers.clone(),
+                ));
+                self.attribute_handlers.len() - 1
+            }
+        };
+
+        forward_characteristic_accesses(
+            server_characteristic,
+            &characteristic.handlers,
+            index,
+            &self.attribute_sender,
+            &self.attribute_notifier,
+        );
+        Ok(())
+    }
+
+    /// Makes the BLE stack keep track of the clients subscribed to a characteristic, if it is
+    /// notifiable or indicatable.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: A BleId to identify the service the charactersitic is part of.
+    /// - `characteristic`: The Characteristic to track
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    fn track_subscriptions(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<(), BleError> {
+        if !characteristic.is_notifiable() && !characteristic.is_indicatable() {
+            return Ok(());
         }
+        let service =
+            task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await })
+                .ok_or(BleError::ServiceNotFound)?
+                .clone();
+        let locked_service = service.lock();
+        let server_characteristic = task::block_on(async {
+            locked_service
+                .get_characteristic(characteristic.id.to_uuid())
+                .await
+        })
+        .ok_or(BleError::CharacteristicNotFound)?;
+
+        let indications = self
+            .indications
+            .entry((service_id.clone(), characteristic.id.clone()))
+            .or_default();
+        track_characteristic_subscriptions(
+            server_characteristic,
+            service_id,
+            &characteristic.id,
+            &self.subscriptions,
+            &self.subscription_sender,
+            &self.attribute_notifier,
+            indications,
+        );
+        Ok(())
     }
 
     /// Set a new characteristic
@@ -562,7 +986,7 @@ impl<'a> _BleServer<'a> {
     ///
     /// # Returns
     ///  
-    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    /// A `Result` with the characteristic of the BLE stack, or an `BleError` if it fails.
     ///
     /// # Errors
     ///
@@ -571,7 +995,7 @@ impl<'a> _BleServer<'a> {
         &self,
         characteristic: &Characteristic,
         service: &Arc<Mutex<BLEService>>,
-    ) -> Result<(), BleError> {
+    ) -> Result<Arc<Mutex<BLECharacteristic>>, BleError> {
         match NimbleProperties::from_bits(characteristic.properties.to_le()) {
             Some(properties) => {
                 let charac = service
@@ -590,19 +1014,49 @@ impl<'a> _BleServer<'a> {
                         Err(_) => return Err(BleError::PropertiesError),
                     };
                 }
+                drop(unlocked_char);
 
-                Ok(())
+                Ok(charac)
             }
             None => Err(BleError::PropertiesError),
         }
     }
 
+    /// Adds a new characteristic to a service, even if the service already has one with the same
+    /// id, as services like HID need. Since it can not be told apart from the others by its id, the
+    /// characteristic is only accessible through the returned characteristic of the BLE stack.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: A BleId to identify the service the charactersitic is part of.
+    /// - `characteristic`: A Characteristic struct with the information of the characteristic
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the characteristic of the BLE stack, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::PropertiesError`: If the characteristic or its descriptors have an invalid property
+    /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server
+    pub(crate) fn add_raw_characteristic(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<Arc<Mutex<BLECharacteristic>>, BleError> {
+        let service =
+            task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await })
+                .ok_or(BleError::ServiceNotFound)?;
+        self.create_new_characteristic(characteristic, service)
+    }
+
     /// Checks if there is a BLECharacteristic on the BLEService with the corresponding id. If it exists, it updates its value. Apart from that,
-    /// depending on the notify boolean parameter, it may notify the connected device of the changed value.
+    /// depending on the notify boolean parameter, it may notify the changed value to the clients subscribed to its notifications.
     ///
     /// # Arguments
     ///
     /// - `service`: An `Arc<Mutex<BLEService>>` that contains the service that has the characteristic to update
+    /// - `service_id`: The BleId of the service
     /// - `characteristic`: A Characteristic struct that contains the updated information
     /// - `notify`: A boolean that indicates wheter to notify the characteristic or not.
     ///
@@ -616,6 +1070,7 @@ impl<'a> _BleServer<'a> {
     fn try_to_update_characteristic(
         &self,
         service: &Arc<Mutex<BLEService>>,
+        service_id: &BleId,
         characteristic: &Characteristic,
         notify: bool,
     ) -> Result<(), BleError> {
@@ -630,14 +1085,28 @@ impl<'a> _BleServer<'a> {
             let mut res_characteristic = server_characteristic.lock();
             res_characteristic.set_value(&characteristic.data);
             if notify {
-                res_characteristic.notify();
+                let subscribers = self
+                    .subscriptions
+                    .lock()
+                    .notified(service_id, &characteristic.id);
+                if !subscribers.is_empty() {
+                    let value_handle = characteristic_value_handle(service_id, &characteristic.id)?;
+                    for conn_handle in subscribers {
+                        _ = send_value_update(
+                            conn_handle,
+                            value_handle,
+                            &characteristic.data,
+                            false,
+                        );
+                    }
+                }
             }
             return Ok(());
         }
         Err(BleError::CharacteristicNotFound)
     }
 
-    /// Notifies to the client the value of the characteristic
+    /// Notifies the value of the characteristic to the clients subscribed to its notifications
     ///
     /// # Arguments
     ///
@@ -663,12 +1132,180 @@ impl<'a> _BleServer<'a> {
         let server_service =
             task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await });
         if let Some(service) = server_service {
-            self.try_to_update_characteristic(service, characteristic, true)?;
+            self.try_to_update_characteristic(service, service_id, characteristic, true)?;
             return Ok(());
         }
         Err(BleError::ServiceNotFound)
     }
 
+    /// Sets the value of an indicatable characteristic and sends it as an indication to the
+    /// clients subscribed to its indications. Used by [BleServer::indicate_value_async].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the PendingIndication to wait for the confirmations, or a `BleError` if
+    /// it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotIndicatable`: If the characteristic is not indicatable
+    /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    /// - `BleError::IndicationNotConfirmed`: If the indication could not be sent to a client
+    fn send_indication(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<PendingIndication, BleError> {
+        if !characteristic.is_indicatable() {
+            return Err(BleError::CharacteristicNotIndicatable);
+        }
+        let service =
+            task::block_on(async { self.ble_server.get_service(service_id.to_uuid()).await })
+                .ok_or(BleError::ServiceNotFound)?
+                .clone();
+        let locked_service = service.lock();
+        let server_characteristic = task::block_on(async {
+            locked_service
+                .get_characteristic(characteristic.id.to_uuid())
+                .await
+        })
+        .ok_or(BleError::CharacteristicNotFound)?;
+        let slot = self
+            .indications
+            .get(&(service_id.clone(), characteristic.id.clone()))
+            .ok_or(BleError::CharacteristicNotFound)?;
+
+        let mut server_characteristic = server_characteristic.lock();
+        server_characteristic.set_value(&characteristic.data);
+        let mut pending = PendingIndication::new(slot);
+        let subscribers = self
+            .subscriptions
+            .lock()
+            .indicated(service_id, &characteristic.id);
+        if subscribers.is_empty() {
+            return Ok(pending);
+        }
+        let value_handle = characteristic_value_handle(service_id, &characteristic.id)?;
+        for conn_handle in subscribers {
+            send_value_update(conn_handle, value_handle, &characteristic.data, true)
+                .map_err(|_| BleError::IndicationNotConfirmed)?;
+            pending.sent();
+        }
+        Ok(pending)
+    }
+
+    /// Sets the subscription handler. The handler is a callback that will be executed when a client
+    /// subscribes to or unsubscribes from the notifications or indications of a characteristic.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler`: A closure that receives the [SubscriptionEvent] of each change
+    ///
+    /// # Returns
+    ///
+    /// The _BleServer itself
+    pub fn subscription_handler<C: FnMut(&mut BleServer<'a>, &SubscriptionEvent) + 'a>(
+        &mut self,
+        handler: C,
+    ) -> &mut Self {
+        self.user_on_subscription = Some(Box::new(handler));
+        self
+    }
+
+    /// Gets the connected clients subscribed to the notifications or indications of a characteristic
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: A BleId to identify the service the charactersitic is part of.
+    /// - `characteristic`: The Characteristic to query
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<ConnectionInformation>` with the information of each subscribed client
+    pub fn subscribers(
+        &self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Vec<ConnectionInformation> {
+        let subscribed = self
+            .subscriptions
+            .lock()
+            .subscribed(service_id, &characteristic.id);
+        self.ble_server
+            .connections()
+            .filter(|desc| subscribed.contains(&desc.conn_handle()))
+            .map(|desc| ConnectionInformation::from_bleconn_desc(&desc, true, Ok(())))
+            .collect()
+    }
+
+    /// Sets the value of a typed characteristic, encoding it. If the characteristic is notifiable
+    /// the clients subscribed to its notifications are notified.
+    ///
+    /// # Arguments
+    ///
+    /// - `characteristic`: The TypedCharacteristic to update
+    /// - `value`: The new value
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service of the characteristic is not set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    pub fn set<T: GattValue + 'static>(
+        &mut self,
+        characteristic: &mut TypedCharacteristic<T>,
+        value: T,
+    ) -> Result<(), BleError> {
+        characteristic.set_value(&value);
+        let server_service = task::block_on(async {
+            self.ble_server
+                .get_service(characteristic.service_id().to_uuid())
+                .await
+        })
+        .ok_or(BleError::ServiceNotFound)?;
+        let untyped = characteristic.characteristic();
+        self.try_to_update_characteristic(
+            server_service,
+            characteristic.service_id(),
+            untyped,
+            untyped.is_notifiable(),
+        )
+    }
+
+    /// Gets the value of a typed characteristic, decoding it
+    ///
+    /// # Arguments
+    ///
+    /// - `characteristic`: The TypedCharacteristic to read
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the value if the operation is succesful, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotFound`: If the characteristic is not in the indicated service
+    /// - `BleError::ServiceNotFound`: If the services is not in the BleServer itself
+    /// - `BleError::InvalidValue`: If the data of the characteristic does not represent a value of the type
+    pub fn get<T: GattValue + 'static>(
+        &self,
+        characteristic: &TypedCharacteristic<T>,
+    ) -> Result<T, BleError> {
+        let data = self.get_characteristic_data(
+            characteristic.service_id(),
+            &characteristic.characteristic().id,
+        )?;
+        T::from_bytes(&data)
+    }
+
     /// Starts the server and its advertisement
     ///
     /// # Returns
@@ -679,9 +1316,11 @@ impl<'a> _BleServer<'a> {
     ///
     /// - `BleError::AdvertisementError`: If the advertising operation failed
     /// - `BleError::StartingAdvertisementError`: If the starting operation failed
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled. The
+    ///   server is then advertised with a connectable set of a `BleAdvertiser`.
     pub fn start(&mut self) -> Result<(), BleError> {
         self.create_advertisement_data()?;
-        self.advertisement
+        self.advertising()?
             .lock()
             .start()
             .map_err(|_| BleError::StartingAdvertisementError)
@@ -699,8 +1338,9 @@ impl<'a> _BleServer<'a> {
     ///
     /// - `BleError::AdvertisementError`: If the advertising operation failed
     /// - `BleError::StoppingFailure`: If the stopping operation failed
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled
     pub fn stop_advertisement(&mut self) -> Result<(), BleError> {
-        self.advertisement
+        self.advertising()?
             .lock()
             .stop()
             .map_err(|_| BleError::StoppingFailure)
@@ -768,7 +1408,9 @@ impl<'a> _BleServer<'a> {
         self.ble_server.connected_count()
     }
 
-    /// Creates the necessary advertisement data with the user settings
+    /// Creates the necessary advertisement data with the user settings. If the user did not set an
+    /// advertisement, the name and services of the server are advertised, spilling into the scan
+    /// response what does not fit.
     ///
     /// # Returns
     ///
@@ -776,17 +1418,33 @@ impl<'a> _BleServer<'a> {
     ///
     /// # Errors
     ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data does not fit in the advertisement and the scan response
     /// - `BleError::AdvertisementError`: If the advertising operation failed
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled
     fn create_advertisement_data(&mut self) -> Result<(), BleError> {
-        let mut adv_data = BLEAdvertisementData::new();
-        adv_data.name(&self.advertising_name);
-        for service in &self.services {
-            adv_data.add_service_uuid(service.id.to_uuid());
-        }
+        let advertisement = self.advertising()?;
+        let builder = match &self.custom_advertisement {
+            Some(builder) => builder.clone(),
+            None => self
+                .services
+                .iter()
+                .fold(AdvertisementBuilder::new(), |builder, service| {
+                    builder.service_uuid(&service.id)
+                })
+                .name(&self.advertising_name)
+                .spill_to_scan_response(true),
+        };
+        set_raw_advertising_data(advertisement, &builder.build()?)
+    }
+
+    /// Gets the legacy advertising of the server
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled
+    fn advertising(&self) -> Result<&'a Mutex<BLEAdvertising>, BleError> {
         self.advertisement
-            .lock()
-            .set_data(&mut adv_data)
-            .map_err(|_| BleError::AdvertisementError)
+            .ok_or(BleError::LegacyAdvertisingUnavailable)
     }
 
     /// Gets the data of a specific characteristic
@@ -892,6 +1550,9 @@ impl<'a> InterruptDriver<'a> for BleServer<'a> {
         user_on_connection.handle_connection_changes(self);
         user_on_disconnection.handle_connection_changes(self);
         self.set_connection_callbacks(user_on_connection, user_on_disconnection);
+        self.handle_attribute_requests();
+        self.handle_subscription_events();
+        self.handle_link_changes();
         Ok(())
     }
 
@@ -912,6 +1573,7 @@ impl<'a> BleServer<'a> {
     /// - `services`: A vector with multiple Service that will contain the server information
     /// - `connection_notifier`: An Notifier used to notify when the connection callback should be executed
     /// - `disconnection_notifier`: An Notifier used to notify when the disconnection callback should be executed
+    /// - `attribute_notifier`: An Notifier used to notify when a read or write callback should be executed
     ///
     /// # Returns
     ///
@@ -928,6 +1590,7 @@ impl<'a> BleServer<'a> {
         services: &Vec<Service>,
         connection_notifier: Notifier,
         disconnection_notifier: Notifier,
+        attribute_notifier: Notifier,
     ) -> Result<Self, BleError> {
         Ok(Self {
             inner: SharableRef::new_sharable(_BleServer::new(
@@ -936,6 +1599,7 @@ impl<'a> BleServer<'a> {
                 services,
                 connection_notifier,
                 disconnection_notifier,
+                attribute_notifier,
             )?),
         })
     }
@@ -965,4 +1629,173 @@ impl<'a> BleServer<'a> {
         self.inner.deref_mut().user_on_connection = Some(user_on_connection);
         self.inner.deref_mut().user_on_disconnection = Some(user_on_disconnection);
     }
+
+    /// Efectibly clones the server, but is only allowed in the crate
+    pub(crate) fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Sets a callback executed by the BLE stack every time a client writes a characteristic.
+    /// Unlike [Characteristic::on_write], the callback is not executed on the main loop, so it
+    /// must not block.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The BleId of the service the characteristic is part of
+    /// - `characteristic_id`: The BleId of the characteristic
+    /// - `callback`: The callback, which receives the written data
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    pub(crate) fn on_raw_write<C: FnMut(&[u8]) + Send + Sync + 'static>(
+        &self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+        mut callback: C,
+    ) -> Result<(), BleError> {
+        self.raw_characteristic(service_id, characteristic_id)?
+            .lock()
+            .on_write(move |args| callback(args.recv_data()));
+        Ok(())
+    }
+
+    /// Gets the characteristic of the BLE stack, which can be used without borrowing the server
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The BleId of the service the characteristic is part of
+    /// - `characteristic_id`: The BleId of the characteristic
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    pub(crate) fn raw_characteristic(
+        &self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+    ) -> Result<Arc<Mutex<BLECharacteristic>>, BleError> {
+        let inner = self.inner.deref();
+        let service =
+            task::block_on(async { inner.ble_server.get_service(service_id.to_uuid()).await })
+                .ok_or(BleError::ServiceNotFound)?;
+        let locked_service = service.lock();
+        task::block_on(async {
+            locked_service
+                .get_characteristic(characteristic_id.to_uuid())
+                .await
+        })
+        .cloned()
+        .ok_or(BleError::CharacteristicNotFound)
+    }
+
+    /// Sets the value of an indicatable characteristic and sends it as an indication to the clients
+    /// subscribed to its indications, waiting until every one of them confirms it. If no client is
+    /// subscribed, it returns immediately.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: A BleId to identify the service the charactersitic is part of.
+    /// - `characteristic`: A Characteristic struct that represents the characteristic to indicate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if every subscribed client confirmed the indication, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotIndicatable`: If the characteristic is not indicatable
+    /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server
+    /// - `BleError::CharacteristicNotFound`: If the characteristic was not setted before on the server
+    /// - `BleError::IndicationNotConfirmed`: If a client did not confirm the indication before the
+    ///   ATT timeout of 30 seconds, or it could not be sent
+    pub fn indicate_value(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<(), BleError> {
+        task::block_on(self.indicate_value_async(service_id, characteristic))
+    }
+
+    /// Async version of [Self::indicate_value]
+    pub async fn indicate_value_async(
+        &mut self,
+        service_id: &BleId,
+        characteristic: &Characteristic,
+    ) -> Result<(), BleError> {
+        let pending = self
+            .inner
+            .deref_mut()
+            .send_indication(service_id, characteristic)?;
+        if pending.confirmed().await {
+            Ok(())
+        } else {
+            Err(BleError::IndicationNotConfirmed)
+        }
+    }
+
+    /// Executes the subscription handler for every subscription change made by the clients
+    fn handle_subscription_events(&mut self) {
+        let events: Vec<SubscriptionEvent> =
+            self.inner.deref().subscription_events.try_iter().collect();
+        if events.is_empty() {
+            return;
+        }
+        let Some(mut handler) = self.inner.deref_mut().user_on_subscription.take() else {
+            return;
+        };
+        for event in &events {
+            handler(self, event);
+        }
+        self.inner
+            .deref_mut()
+            .user_on_subscription
+            .get_or_insert(handler);
+    }
+
+    /// Executes the link change handler for every link change of a connected client
+    fn handle_link_changes(&mut self) {
+        let events: Vec<LinkEvent> = self.inner.deref().link_changes.try_iter().collect();
+        if events.is_empty() {
+            return;
+        }
+        let Some(mut handler) = self.inner.deref_mut().user_on_link_change.take() else {
+            return;
+        };
+        for event in &events {
+            let client = self
+                .list_clients()
+                .into_iter()
+                .find(|client| client.conn_handle == event.conn_handle);
+            if let Some(client) = client {
+                handler(self, &client, event.change);
+            }
+        }
+        self.inner
+            .deref_mut()
+            .user_on_link_change
+            .get_or_insert(handler);
+    }
+
+    /// Executes the read and write callbacks of every access made by the clients, answering
+    /// to the BLE stack with their results
+    fn handle_attribute_requests(&mut self) {
+        let requests: Vec<AttributeRequest> =
+            self.inner.deref().attribute_requests.try_iter().collect();
+        for request in requests {
+            let handlers = self
+                .inner
+                .deref()
+                .attribute_handlers
+                .get(request.handlers)
+                .map(|(_, _, handlers)| handlers.clone());
+            if let Some(handlers) = handlers {
+                request.answer(&handlers);
+            }
+        }
+    }
 }
@@ -1,4 +1,8 @@
-use super::utils::{BleError, BleId, Service};
+use super::utils::{
+    legacy_advertising, set_raw_advertising_data, AdvertisementBuilder, AdvertisementData,
+    AdvertisingSchedule, BeaconFrame, BeaconFrameKind, BleError, BleId, ScheduleContent,
+    ScheduleEntry, ScheduleTime, Service, APPLE_COMPANY_ID, EDDYSTONE_SERVICE_UUID,
+};
 use crate::utils::{
     auxiliary::{SharableRef, SharableRefExt},
     timer_driver::TimerDriver,
@@ -6,17 +10,30 @@ use crate::utils::{
 use esp32_nimble::{
     utilities::mutex::Mutex, BLEAdvertisementData, BLEAdvertising, BLEDevice, BLEError,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Time between the checks of the schedule, the smallest time an entry can be advertised
+const SCHEDULE_RESOLUTION: Duration = Duration::from_millis(20);
 
 /// The Beacon advertises information in small packets of data at regular intervals.
 /// The small packets can be detected by other devices and get the information.
+/// Besides the data of its services, the beacon can advertise iBeacon and Eddystone frames, and
+/// rotate through all of them following an [AdvertisingSchedule].
+/// - `schedule_all_data`: Whether the schedule is kept with every service and frame of the
+///   beacon, as set by [BleBeacon::advertise_all_service_data]
 pub struct BleBeacon<'a> {
     advertising_name: String,
-    ble_device: &'a mut BLEDevice,
-    services: SharableRef<HashMap<BleId, Service>>,
+    ble_advertising: &'a Mutex<BLEAdvertising>,
+    services: SharableRef<Vec<Service>>,
+    frames: SharableRef<Vec<BeaconFrame>>,
     advertisement: SharableRef<BLEAdvertisementData>,
     timer_driver: TimerDriver<'a>,
-    time_per_service: Duration,
+    schedule: SharableRef<AdvertisingSchedule>,
+    schedule_all_data: bool,
 }
 
 impl<'a> BleBeacon<'a> {
@@ -35,6 +52,8 @@ impl<'a> BleBeacon<'a> {
     ///
     /// # Errors
     ///
+    /// - `BleError::LegacyAdvertisingUnavailable`: If the extended advertising is enabled, use a
+    ///   legacy set of a `BleAdvertiser` instead
     /// - `BleError::PropertiesError`: If a characteristic on the service has an invalid property.
     /// - `BleError::ServiceNotFound`: If the service_id doesnt match with the id of a service already set on the server.
     pub(crate) fn new(
@@ -47,11 +66,13 @@ impl<'a> BleBeacon<'a> {
         advertisement.name(&advertising_name);
         let mut beacon = BleBeacon {
             advertising_name,
-            ble_device,
-            services: SharableRef::new_sharable(HashMap::new()),
+            ble_advertising: legacy_advertising(ble_device)?,
+            services: SharableRef::new_sharable(Vec::new()),
+            frames: SharableRef::new_sharable(Vec::new()),
             advertisement: Rc::new(RefCell::from(advertisement)),
             timer_driver,
-            time_per_service: Duration::from_secs(1),
+            schedule: SharableRef::new_sharable(AdvertisingSchedule::default()),
+            schedule_all_data: false,
         };
         beacon.set_services(services)?;
         Ok(beacon)
@@ -79,17 +100,28 @@ impl<'a> BleBeacon<'a> {
     ///
     /// - `service`: A Service struct that will contain de information of the service to add
     fn insert_service(&mut self, service: &Service) {
-        add_service_to_advertising(
-            &mut self.advertisement.deref_mut(),
-            service,
-            self.services.deref().contains_key(&service.id),
-        );
-        self.services
+        let already_set = {
+            let mut services = self.services.deref_mut();
+            match services.iter_mut().find(|s| s.id == service.id) {
+                Some(old_service) => {
+                    *old_service = service.clone();
+                    true
+                }
+                None => {
+                    services.push(service.clone());
+                    false
+                }
+            }
+        };
+        add_service_to_advertising(&mut self.advertisement.deref_mut(), service, already_set);
+        self.schedule
             .deref_mut()
-            .insert(service.id.clone(), service.clone());
+            .refresh_if(|content| content == &ScheduleContent::Service(service.id.clone()));
+        self.sync_all_data_schedule();
     }
 
-    /// Updates the advertising of the beacon. Needed when changing the data of the advertisment
+    /// Updates the advertising of the beacon. Needed when changing the data of the advertisment.
+    /// While the schedule rotates the data is advertised by it instead, so nothing is done.
     ///
     /// # Returns
     ///
@@ -100,10 +132,10 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::ServiceDoesNotFit`: if advertising is too big
     /// - `BleError::Code` on other errors
     fn update_advertisement(&mut self) -> Result<(), BleError> {
-        set_advertising_data(
-            self.ble_device.get_advertising(),
-            &mut self.advertisement.deref_mut(),
-        )
+        if self.schedule.deref().current().is_some() {
+            return Ok(());
+        }
+        set_advertising_data(self.ble_advertising, &mut self.advertisement.deref_mut())
     }
 
     /// Adds a service to the beacon which can be advertised. If Service is already set, then the
@@ -159,7 +191,7 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::Code`: on other errors
     fn reset_advertisement(&mut self) -> Result<(), BleError> {
         let mut advertisement = BLEAdvertisementData::new();
-        for service in self.services.deref().values() {
+        for service in self.services.deref().iter() {
             add_service_to_advertising(&mut advertisement, service, false);
         }
         self.advertisement.replace(advertisement);
@@ -181,7 +213,8 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::ServiceDoesNotFit`: if advertising is too big
     /// - `BleError::Code` on other errors
     pub fn remove_service(&mut self, service_id: &BleId) -> Result<&mut Self, BleError> {
-        self.services.deref_mut().remove(service_id);
+        self.services.deref_mut().retain(|s| &s.id != service_id);
+        self.sync_all_data_schedule();
         self.reset_advertisement()?;
         Ok(self)
     }
@@ -200,12 +233,118 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::ServiceDoesNotFit`: if advertising is too big
     /// - `BleError::Code` on other errors
     pub fn remove_services(&mut self, service_ids: &Vec<BleId>) -> Result<(), BleError> {
-        for service_id in service_ids {
-            self.services.deref_mut().remove(service_id);
-        }
+        self.services
+            .deref_mut()
+            .retain(|s| !service_ids.contains(&s.id));
+        self.sync_all_data_schedule();
         self.reset_advertisement()
     }
 
+    /// Adds a frame to the beacon which can be advertised. A beacon has at most one frame of each
+    /// [BeaconFrameKind], so if there is already a frame of the same kind it is replaced. Frames are
+    /// advertised on their own, without the name nor the services of the beacon, since they take
+    /// almost the whole advertisement.
+    ///
+    /// # Arguments
+    ///
+    /// - `frame`: The BeaconFrame to set on the beacon
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BleBeacon` itself, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the frame can not be encoded, for example an url that does not fit
+    /// - `BleError::AdvertisementDoesNotFit`: If the frame does not fit in an advertisement
+    pub fn set_frame(&mut self, frame: BeaconFrame) -> Result<&mut Self, BleError> {
+        frame_advertisement(&frame)?;
+        {
+            let mut frames = self.frames.deref_mut();
+            let kind = frame.kind();
+            match frames.iter_mut().find(|f| f.kind() == kind) {
+                Some(old_frame) => *old_frame = frame,
+                None => frames.push(frame),
+            }
+            self.schedule
+                .deref_mut()
+                .refresh_if(|content| content == &ScheduleContent::Frame(kind));
+        }
+        self.sync_all_data_schedule();
+        Ok(self)
+    }
+
+    /// Removes the frame of the specified kind from the beacon, if there is one
+    ///
+    /// # Arguments
+    ///
+    /// - `kind`: The BeaconFrameKind of the frame to remove
+    ///
+    /// # Returns
+    ///
+    /// The BleBeacon itself
+    pub fn remove_frame(&mut self, kind: BeaconFrameKind) -> &mut Self {
+        self.frames.deref_mut().retain(|frame| frame.kind() != kind);
+        self.sync_all_data_schedule();
+        self
+    }
+
+    /// Set the beacon to advertise a frame. If beacon was looping data then it stops.
+    ///
+    /// # Arguments
+    ///
+    /// - `kind`: The BeaconFrameKind of the frame to advertise, it must be set with [Self::set_frame]
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no frame of the kind set on the beacon
+    /// - `BleError::StartingFailure`: If the starting operation fails
+    /// - `BleError::TimerDriverErorr(TimerDriverError)`: If the underlying timer_driver fails
+    /// - `BleError::Code`: on other errors
+    pub fn advertise_frame(&mut self, kind: BeaconFrameKind) -> Result<(), BleError> {
+        self.stop_looping_data()?;
+        let frame = self
+            .frames
+            .deref()
+            .iter()
+            .find(|frame| frame.kind() == kind)
+            .cloned()
+            .ok_or(BleError::NotFound)?;
+        set_raw_beacon_data(self.ble_advertising, &frame_advertisement(&frame)?)?;
+        self.start()
+    }
+
+    /// Set the beacon to advertise custom data, instead of its services and frames. If beacon was
+    /// looping data then it stops.
+    ///
+    /// # Arguments
+    ///
+    /// - `advertisement`: The AdvertisementBuilder with the data to advertise
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data does not fit in the advertisement and the scan response
+    /// - `BleError::AdvertisementError`: If the advertising operation failed
+    /// - `BleError::StartingFailure`: If the starting operation fails
+    /// - `BleError::TimerDriverErorr(TimerDriverError)`: If the underlying timer_driver fails
+    pub fn advertise_custom(
+        &mut self,
+        advertisement: &AdvertisementBuilder,
+    ) -> Result<(), BleError> {
+        let data = advertisement.build()?;
+        self.stop_looping_data()?;
+        set_raw_beacon_data(self.ble_advertising, &data)?;
+        self.start()
+    }
+
     /// Start advertising one particular service data
     ///
     /// # Arguments
@@ -223,15 +362,12 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::ServiceUnkown`:  if asked to change to data of an unkown service
     /// - `BleError::Code`: on other errors
     fn change_advertised_service_data(&mut self, service_id: &BleId) -> Result<(), BleError> {
-        match self.services.deref().get(service_id) {
+        match self.services.deref().iter().find(|s| &s.id == service_id) {
             Some(request_service) => {
                 self.advertisement
                     .borrow_mut()
                     .service_data(request_service.id.to_uuid(), &request_service.data);
-                set_advertising_data(
-                    self.ble_device.get_advertising(),
-                    &mut self.advertisement.deref_mut(),
-                )?;
+                set_advertising_data(self.ble_advertising, &mut self.advertisement.deref_mut())?;
                 self.start()
             }
             None => Err(BleError::ServiceUnknown),
@@ -248,6 +384,7 @@ impl<'a> BleBeacon<'a> {
     ///
     /// - `BleError::TimerDriverError`: If the underlying timer_driver fails.
     fn stop_looping_data(&mut self) -> Result<(), BleError> {
+        self.schedule.deref_mut().restart();
         self.timer_driver
             .remove_interrupt()
             .map_err(BleError::TimerDriverError)
@@ -276,18 +413,139 @@ impl<'a> BleBeacon<'a> {
         self.change_advertised_service_data(service_id)
     }
 
-    /// Sets the time the beacon will advertise the data of a service if [Self::advertise_all_service_data]
-    /// was called
+    /// Sets the time each unit of weight of a [ScheduleTime::Weight] lasts, which is the time
+    /// the beacon advertises the data of a service or a frame if
+    /// [Self::advertise_all_service_data] was called
     ///
     /// # Arguments
     ///
     /// - `dur`: The Duration wanted for each service to be on advertising
     pub fn set_time_per_service(&mut self, dur: Duration) {
-        self.time_per_service = dur
+        self.schedule.deref_mut().set_time_per_weight(dur)
+    }
+
+    /// Replaces the schedule of the beacon with new entries, advertised in order. The rotation
+    /// starts again from the first entry. The schedule is advertised with [Self::advertise_schedule].
+    ///
+    /// # Arguments
+    ///
+    /// - `entries`: The ScheduleEntries to advertise, each one for its own time
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BleBeacon` itself, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data of a raw entry does not fit in the
+    ///   advertisement and the scan response
+    pub fn set_schedule(&mut self, entries: Vec<ScheduleEntry>) -> Result<&mut Self, BleError> {
+        for entry in &entries {
+            check_schedule_content(&entry.content)?;
+        }
+        self.schedule_all_data = false;
+        self.schedule.deref_mut().set_entries(entries);
+        Ok(self)
+    }
+
+    /// Adds an entry at the end of the schedule of the beacon, without restarting the rotation
+    ///
+    /// # Arguments
+    ///
+    /// - `entry`: The ScheduleEntry to add
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the index of the new entry, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data of a raw entry does not fit in the
+    ///   advertisement and the scan response
+    pub fn add_schedule_entry(&mut self, entry: ScheduleEntry) -> Result<usize, BleError> {
+        check_schedule_content(&entry.content)?;
+        self.schedule_all_data = false;
+        Ok(self.schedule.deref_mut().push(entry))
+    }
+
+    /// Removes an entry of the schedule of the beacon. If it was being advertised, the next
+    /// entry is advertised right away.
+    ///
+    /// # Arguments
+    ///
+    /// - `index`: The position of the entry in the schedule
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the removed ScheduleEntry, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn remove_schedule_entry(&mut self, index: usize) -> Result<ScheduleEntry, BleError> {
+        self.schedule_all_data = false;
+        self.schedule.deref_mut().remove(index)
+    }
+
+    /// Changes the data of an entry of the schedule, without restarting the rotation. If the
+    /// entry is being advertised, the new data is advertised right away. The data of service and
+    /// frame entries can also be changed with [Self::set_service] and [Self::set_frame].
+    ///
+    /// # Arguments
+    ///
+    /// - `index`: The position of the entry in the schedule
+    /// - `content`: The new ScheduleContent of the entry
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BleBeacon` itself, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    /// - `BleError::AdvertisementDoesNotFit`: If the data of a raw entry does not fit in the
+    ///   advertisement and the scan response
+    pub fn update_schedule_entry(
+        &mut self,
+        index: usize,
+        content: ScheduleContent,
+    ) -> Result<&mut Self, BleError> {
+        check_schedule_content(&content)?;
+        self.schedule.deref_mut().update_content(index, content)?;
+        Ok(self)
     }
 
-    /// The beacon advertises the data of each service every fixed duration. If services are added or
-    /// removed this is reflected. The time per service can be set with [Self::set_time_per_service]
+    /// Changes the advertising intervals used while an entry of the schedule is advertised
+    ///
+    /// # Arguments
+    ///
+    /// - `index`: The position of the entry in the schedule
+    /// - `interval`: The min and max intervals in 0.625ms units, or None for the default ones
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BleBeacon` itself, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn set_schedule_entry_interval(
+        &mut self,
+        index: usize,
+        interval: Option<(u16, u16)>,
+    ) -> Result<&mut Self, BleError> {
+        self.schedule.deref_mut().update_interval(index, interval)?;
+        Ok(self)
+    }
+
+    /// Gets the schedule of the beacon, with the entry being advertised
+    pub fn schedule(&self) -> AdvertisingSchedule {
+        self.schedule.deref().clone()
+    }
+
+    /// The beacon advertises the data of each service, followed by each frame, for the time set
+    /// with [Self::set_time_per_service]. If services or frames are added or removed this is
+    /// reflected. This replaces the schedule of the beacon, see [Self::advertise_schedule].
     ///
     /// Note: For the advertised data to change, the method [crate::Microcontroller::wait_for_updates] must
     /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
@@ -305,27 +563,66 @@ impl<'a> BleBeacon<'a> {
     ///
     /// It may panic if the setting of the advertising data fails
     pub fn advertise_all_service_data(&mut self) -> Result<(), BleError> {
+        self.schedule_all_data = true;
+        self.sync_all_data_schedule();
+        self.advertise_schedule()
+    }
+
+    /// The beacon advertises the entries of its schedule in order, each one for its own time and
+    /// with its own advertising intervals, starting again after the last one. Entries whose
+    /// service or frame is not set on the beacon are skipped. Entries can be added, removed or
+    /// changed while the schedule is advertised.
+    ///
+    /// Note: For the advertised data to change, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the advertise operation completed successfully, or a `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::TimerDriverError`: If the enabling of the TimerDriver fails
+    ///
+    /// # Panics
+    ///
+    /// It may panic if the setting of the advertising data fails
+    pub fn advertise_schedule(&mut self) -> Result<(), BleError> {
         let services = self.services.clone();
-        let advertising = self.ble_device.get_advertising();
+        let frames = self.frames.clone();
+        let schedule = self.schedule.clone();
+        let advertising = self.ble_advertising;
         let advertisement = self.advertisement.clone();
-        let mut i = 0;
+        let mut last_check = Instant::now();
+        let mut applied_interval = None;
+        schedule.deref_mut().restart();
 
         let callback = move || {
-            let services = services.deref();
-            i %= services.len();
-            let service = services.values().collect::<Vec<&Service>>()[i];
-            advertisement
-                .borrow_mut()
-                .service_data(service.id.to_uuid(), &service.data);
-            set_advertising_data(advertising, &mut advertisement.borrow_mut()).unwrap();
-            i += 1
+            let now = Instant::now();
+            let index = schedule.deref_mut().advance(now - last_check);
+            last_check = now;
+            let Some(entry) = index.and_then(|index| schedule.deref().entry(index).cloned()) else {
+                return;
+            };
+            match advertise_schedule_content(
+                advertising,
+                &mut advertisement.borrow_mut(),
+                &services.deref(),
+                &frames.deref(),
+                &entry.content,
+            ) {
+                Err(BleError::ServiceUnknown | BleError::NotFound) => return,
+                res => res.unwrap(),
+            }
+            if entry.interval != applied_interval {
+                set_advertising_interval(advertising, entry.interval).unwrap();
+                applied_interval = entry.interval;
+            }
         };
 
         self.timer_driver.interrupt_after_n_times(
-            self.time_per_service
-                .as_micros()
-                .try_into()
-                .unwrap_or(u64::MAX),
+            SCHEDULE_RESOLUTION.as_micros() as u64,
             None,
             true,
             callback,
@@ -335,6 +632,30 @@ impl<'a> BleBeacon<'a> {
             .map_err(BleError::TimerDriverError)
     }
 
+    /// If the schedule follows every service and frame of the beacon, it is rebuilt with them.
+    /// The rotation only restarts if the services or frames changed.
+    fn sync_all_data_schedule(&mut self) {
+        if !self.schedule_all_data {
+            return;
+        }
+        let services = self.services.deref();
+        let frames = self.frames.deref();
+        let entries: Vec<ScheduleEntry> = services
+            .iter()
+            .map(|service| ScheduleContent::Service(service.id.clone()))
+            .chain(
+                frames
+                    .iter()
+                    .map(|frame| ScheduleContent::Frame(frame.kind())),
+            )
+            .map(|content| ScheduleEntry::new(content, ScheduleTime::Weight(1)))
+            .collect();
+        let mut schedule = self.schedule.deref_mut();
+        if schedule.entries() != entries.as_slice() {
+            schedule.set_entries(entries);
+        }
+    }
+
     /// Start advertising set services of the beacon
     ///
     /// # Returns
@@ -345,7 +666,7 @@ impl<'a> BleBeacon<'a> {
     ///
     /// - `BleError::StartingFailure`: If the starting operation fails
     pub fn start(&self) -> Result<(), BleError> {
-        let mut ble_adv = self.ble_device.get_advertising().lock();
+        let mut ble_adv = self.ble_advertising.lock();
         ble_adv.start().map_err(|_| BleError::StartingFailure)
     }
 
@@ -360,7 +681,7 @@ impl<'a> BleBeacon<'a> {
     /// - `BleError::StoppingFailure`: If the stopping operation fails
     pub fn stop(&mut self) -> Result<(), BleError> {
         self.stop_looping_data()?;
-        let ble_adv = self.ble_device.get_advertising().lock();
+        let ble_adv = self.ble_advertising.lock();
         match ble_adv.stop() {
             Ok(_) => Ok(()),
             Err(err) => match err.code() {
@@ -400,3 +721,108 @@ fn add_service_to_advertising(data: &mut BLEAdvertisementData, service: &Service
         data.service_data(service.id.to_uuid(), &service.data);
     }
 }
+
+/// Checks the data of an entry of the schedule can be advertised
+///
+/// # Errors
+///
+/// - `BleError::AdvertisementDoesNotFit`: If the data of a raw entry does not fit in the
+///   advertisement and the scan response
+fn check_schedule_content(content: &ScheduleContent) -> Result<(), BleError> {
+    if let ScheduleContent::Raw(builder) = content {
+        builder.build()?;
+    }
+    Ok(())
+}
+
+/// Sets the data of an entry of the schedule as the advertisement of the beacon
+///
+/// # Errors
+///
+/// - `BleError::ServiceUnknown`: If the service of the entry is not set on the beacon
+/// - `BleError::NotFound`: If the frame of the entry is not set on the beacon
+/// - `BleError::ServiceDoesNotFit`: if the advertising data is too big
+/// - `BleError::AdvertisementError`: If the advertising operation failed
+fn advertise_schedule_content(
+    ble_adv: &Mutex<BLEAdvertising>,
+    advertisement: &mut BLEAdvertisementData,
+    services: &[Service],
+    frames: &[BeaconFrame],
+    content: &ScheduleContent,
+) -> Result<(), BleError> {
+    match content {
+        ScheduleContent::Service(id) => {
+            let service = services
+                .iter()
+                .find(|service| &service.id == id)
+                .ok_or(BleError::ServiceUnknown)?;
+            advertisement.service_data(service.id.to_uuid(), &service.data);
+            set_advertising_data(ble_adv, advertisement)
+        }
+        ScheduleContent::Frame(kind) => {
+            let frame = frames
+                .iter()
+                .find(|frame| frame.kind() == *kind)
+                .ok_or(BleError::NotFound)?;
+            set_raw_beacon_data(ble_adv, &frame_advertisement(frame)?)
+        }
+        ScheduleContent::Raw(builder) => set_raw_beacon_data(ble_adv, &builder.build()?),
+    }
+}
+
+/// Sets the advertising intervals, restarting the advertising if it is on so they are used
+///
+/// # Errors
+///
+/// - `BleError::StoppingFailure`: If the advertising can not be stopped
+/// - `BleError::StartingFailure`: If the advertising can not be started again
+fn set_advertising_interval(
+    ble_adv: &Mutex<BLEAdvertising>,
+    interval: Option<(u16, u16)>,
+) -> Result<(), BleError> {
+    let (min_interval, max_interval) = interval.unwrap_or((0, 0));
+    let mut ble_adv = ble_adv.lock();
+    ble_adv
+        .min_interval(min_interval)
+        .max_interval(max_interval);
+    if unsafe { esp_idf_svc::sys::ble_gap_adv_active() } == 0 {
+        return Ok(());
+    }
+    ble_adv.stop().map_err(|_| BleError::StoppingFailure)?;
+    ble_adv.start().map_err(|_| BleError::StartingFailure)
+}
+
+/// Creates an advertisement with only the data of a frame
+///
+/// # Errors
+///
+/// - `BleError::InvalidValue`: If the frame can not be encoded
+/// - `BleError::AdvertisementDoesNotFit`: If the frame does not fit in an advertisement
+fn frame_advertisement(frame: &BeaconFrame) -> Result<AdvertisementData, BleError> {
+    let builder = match frame {
+        BeaconFrame::IBeacon(ibeacon) => AdvertisementBuilder::new()
+            .manufacturer_data(APPLE_COMPANY_ID, &ibeacon.to_manufacturer_data()[2..]),
+        BeaconFrame::Eddystone(eddystone) => {
+            let uuid = BleId::FromUuid16(EDDYSTONE_SERVICE_UUID);
+            AdvertisementBuilder::new()
+                .service_uuid(&uuid)
+                .service_data(&uuid, &eddystone.to_service_data()?)
+        }
+    };
+    builder.build()
+}
+
+/// Sets the encoded advertisement as the non connectable advertisement of the beacon
+///
+/// # Errors
+///
+/// - `BleError::AdvertisementError`: If the advertising operation failed
+fn set_raw_beacon_data(
+    ble_adv: &Mutex<BLEAdvertising>,
+    data: &AdvertisementData,
+) -> Result<(), BleError> {
+    ble_adv
+        .lock()
+        .advertisement_type(esp32_nimble::enums::ConnMode::Non);
+    set_raw_advertising_data(ble_adv, data)
+}
@@ -0,0 +1,734 @@
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    collections::BTreeMap,
+    ffi::{c_int, c_void},
+    pin::Pin,
+    ptr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use esp32_nimble::{BLEAddress, BLEDevice, BLEError};
+use esp_idf_svc::sys;
+use futures::{channel::mpsc, Stream, StreamExt};
+
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+use super::utils::PeriodicSyncEvent;
+use super::{
+    utils::{
+        address_key, AddressKey, AdvertisingSet, AdvertisingSetEvent, BleError, BlePhy, DataStatus,
+        ExtendedAdvertisement, ExtendedScanConfig, ReportAssembler,
+    },
+    BleClient,
+};
+use crate::{
+    utils::{
+        auxiliary::{SharableRef, SharableRefExt},
+        esp32_framework_error::Esp32FrameworkError,
+        notification::Notifier,
+    },
+    InterruptDriver,
+};
+
+const EXTENDED_SCAN_CAPACITY: usize = 32;
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+const PERIODIC_SYNC_CAPACITY: usize = 16;
+/// Sync timeout of a periodic sync, in units of 10 ms
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+const PERIODIC_SYNC_TIMEOUT: u16 = 1000;
+/// Transmission power reported when it is not available, and requested to let the controller
+/// choose it
+const TX_POWER_UNAVAILABLE: i8 = 127;
+
+type SetEventCallback<'a> = dyn FnMut(&mut BleAdvertiser<'a>, AdvertisingSetEvent) + 'a;
+
+/// Receiver of the events of the advertising sets, only one advertiser exists at a time
+static SET_EVENTS: Mutex<Option<(Sender<AdvertisingSetEvent>, Notifier)>> = Mutex::new(None);
+
+/// Gets the PHY value of the BLE stack
+fn raw_phy(phy: BlePhy) -> u8 {
+    match phy {
+        BlePhy::Le1M => sys::BLE_HCI_LE_PHY_1M as u8,
+        BlePhy::Le2M => sys::BLE_HCI_LE_PHY_2M as u8,
+        BlePhy::LeCoded => sys::BLE_HCI_LE_PHY_CODED as u8,
+    }
+}
+
+/// Copies a payload into a buffer of the BLE stack, which takes its ownership
+fn payload_buffer(data: &[u8]) -> Result<*mut sys::os_mbuf, BleError> {
+    let buffer =
+        unsafe { sys::ble_hs_mbuf_from_flat(data.as_ptr() as *const c_void, data.len() as u16) };
+    if buffer.is_null() {
+        return Err(BleError::AdvertisementError);
+    }
+    Ok(buffer)
+}
+
+/// Driver of the Bluetooth 5 extended advertising
+/// - `sets`: The configured sets, by instance
+/// - `events`: Receiver of the events of the sets, sent by the BLE stack
+/// - `user_on_event`: Handler of the events of the sets
+struct _BleAdvertiser<'a> {
+    sets: BTreeMap<u8, AdvertisingSet>,
+    events: Receiver<AdvertisingSetEvent>,
+    user_on_event: Option<Box<SetEventCallback<'a>>>,
+}
+
+/// Driver of the Bluetooth 5 extended advertising, which runs many [AdvertisingSet] at the same
+/// time. Each set has its own data, interval and PHYs, so a device can keep a connectable set
+/// for its [super::BleServer] while a non connectable set on the coded PHY broadcasts long range
+/// data, or periodic data for synchronized receivers.
+///
+/// Connections made through a connectable set are served with the GATT services of the device,
+/// and reported with [AdvertisingSetEvent::Connected]. A set stops advertising after a connection,
+/// so it must be started again to accept another one.
+///
+/// Needs `CONFIG_BT_NIMBLE_EXT_ADV=y` in the sdkconfig, as set by `sdkconfig.ext_adv.defaults`.
+/// Periodic advertising also needs `CONFIG_BT_NIMBLE_ENABLE_PERIODIC_ADV=y`.
+pub struct BleAdvertiser<'a> {
+    inner: SharableRef<_BleAdvertiser<'a>>,
+}
+
+impl<'a> BleAdvertiser<'a> {
+    /// Creates a new BleAdvertiser
+    ///
+    /// # Arguments
+    ///
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] after an event
+    ///   of a set
+    ///
+    /// # Returns
+    ///
+    /// The new BleAdvertiser, with no sets
+    pub(crate) fn new(notifier: Notifier) -> Self {
+        // Starts the BLE stack if no other BLE driver started it
+        BLEDevice::take();
+        let (sender, receiver) = channel();
+        if let Ok(mut events) = SET_EVENTS.lock() {
+            *events = Some((sender, notifier));
+        }
+        Self {
+            inner: SharableRef::new_sharable(_BleAdvertiser {
+                sets: BTreeMap::new(),
+                events: receiver,
+                user_on_event: None,
+            }),
+        }
+    }
+
+    /// Configures an advertising set, replacing the set of the same instance. The set is not
+    /// started until [Self::start] is called.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: The [AdvertisingSet] to configure
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the set was configured, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data of the set is too long
+    /// - `BleError::InvalidParameters`: If the settings of the set are not valid
+    /// - `BleError::StartingFailure`: If the services of the server could not be registered for a
+    ///   connectable set
+    /// - `BleError::AdvertisementError`: If the data could not be copied to the BLE stack
+    /// - `BleError::Code`: If the BLE stack rejects the set, like when the instance is over
+    ///   `CONFIG_BT_NIMBLE_MAX_EXT_ADV_INSTANCES`
+    pub fn set(&mut self, set: AdvertisingSet) -> Result<(), BleError> {
+        set.validate()?;
+        if self.inner.deref().sets.contains_key(&set.instance) {
+            self.remove(set.instance)?;
+        }
+        if set.connectable {
+            // The GATT services must be registered before a client connects through the set
+            BLEDevice::take()
+                .get_server()
+                .start()
+                .map_err(|_| BleError::StartingFailure)?;
+        }
+        let mut params: sys::ble_gap_ext_adv_params = unsafe { std::mem::zeroed() };
+        params.set_connectable(set.connectable as _);
+        params.set_scannable(set.scannable as _);
+        params.set_legacy_pdu(set.legacy as _);
+        params.set_include_tx_power(set.include_tx_power as _);
+        params.itvl_min = set.min_interval;
+        params.itvl_max = set.max_interval;
+        params.own_addr_type = sys::BLE_OWN_ADDR_PUBLIC as u8;
+        params.primary_phy = raw_phy(set.primary_phy);
+        params.secondary_phy = raw_phy(set.secondary_phy);
+        params.tx_power = set.tx_power.unwrap_or(TX_POWER_UNAVAILABLE);
+        params.sid = set.sid;
+        let rc = unsafe {
+            sys::ble_gap_ext_adv_configure(
+                set.instance,
+                &params,
+                ptr::null_mut(),
+                Some(on_set_event),
+                ptr::null_mut(),
+            )
+        };
+        BLEError::convert(rc as u32)?;
+        set_set_data(set.instance, &set.data)?;
+        if set.scannable {
+            let buffer = payload_buffer(&set.scan_response)?;
+            let rc = unsafe { sys::ble_gap_ext_adv_rsp_set_data(set.instance, buffer) };
+            BLEError::convert(rc as u32)?;
+        }
+        #[cfg(esp_idf_bt_nimble_enable_periodic_adv)]
+        if let Some(periodic) = &set.periodic {
+            let mut params: sys::ble_gap_periodic_adv_params = unsafe { std::mem::zeroed() };
+            params.set_include_tx_power(periodic.include_tx_power as _);
+            params.itvl_min = periodic.min_interval;
+            params.itvl_max = periodic.max_interval;
+            let rc = unsafe { sys::ble_gap_periodic_adv_configure(set.instance, &params) };
+            BLEError::convert(rc as u32)?;
+            set_periodic_data(set.instance, &periodic.data)?;
+        }
+        self.inner.deref_mut().sets.insert(set.instance, set);
+        Ok(())
+    }
+
+    /// Starts advertising a configured set, for the duration of the set. Its periodic
+    /// advertising, if it has one, is started too.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance
+    /// - `BleError::StartingAdvertisementError`: If the BLE stack fails to start the set
+    pub fn start(&mut self, instance: u8) -> Result<(), BleError> {
+        let set = self.configured(instance)?;
+        let rc = unsafe { sys::ble_gap_ext_adv_start(instance, set.duration_units() as c_int, 0) };
+        BLEError::convert(rc as u32).map_err(|_| BleError::StartingAdvertisementError)?;
+        #[cfg(esp_idf_bt_nimble_enable_periodic_adv)]
+        if set.periodic.is_some() {
+            let rc = unsafe { sys::ble_gap_periodic_adv_start(instance) };
+            BLEError::convert(rc as u32).map_err(|_| BleError::StartingAdvertisementError)?;
+        }
+        Ok(())
+    }
+
+    /// Stops advertising a set, keeping its configuration so it can be started again
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance
+    /// - `BleError::StoppingFailure`: If the BLE stack fails to stop the set
+    pub fn stop(&mut self, instance: u8) -> Result<(), BleError> {
+        let _set = self.configured(instance)?;
+        #[cfg(esp_idf_bt_nimble_enable_periodic_adv)]
+        if _set.periodic.is_some() {
+            unsafe { sys::ble_gap_periodic_adv_stop(instance) };
+        }
+        let rc = unsafe { sys::ble_gap_ext_adv_stop(instance) };
+        if rc != 0 && rc as u32 != sys::BLE_HS_EALREADY {
+            return Err(BleError::StoppingFailure);
+        }
+        Ok(())
+    }
+
+    /// Stops a set and removes its configuration
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance
+    /// - `BleError::StoppingFailure`: If the BLE stack fails to stop the set
+    /// - `BleError::Code`: If the BLE stack fails to remove the set
+    pub fn remove(&mut self, instance: u8) -> Result<(), BleError> {
+        self.stop(instance)?;
+        let rc = unsafe { sys::ble_gap_ext_adv_remove(instance) };
+        BLEError::convert(rc as u32)?;
+        self.inner.deref_mut().sets.remove(&instance);
+        Ok(())
+    }
+
+    /// Replaces the advertised data of a set without stopping it
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance
+    /// - `BleError::AdvertisementDoesNotFit`: If the data is too long for the set
+    /// - `BleError::InvalidParameters`: If the set is an extended scannable set, which can only
+    ///   send data in its scan response
+    /// - `BleError::Code`: If the BLE stack rejects the data
+    pub fn set_data(&mut self, instance: u8, data: Vec<u8>) -> Result<(), BleError> {
+        let set = self.configured(instance)?.data(data);
+        set.validate()?;
+        set_set_data(instance, &set.data)?;
+        self.inner.deref_mut().sets.insert(instance, set);
+        Ok(())
+    }
+
+    /// Replaces the periodic data of a set without stopping it
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance, or it has no periodic
+    ///   advertising
+    /// - `BleError::AdvertisementDoesNotFit`: If the data is too long
+    /// - `BleError::Code`: If the BLE stack rejects the data
+    #[cfg(esp_idf_bt_nimble_enable_periodic_adv)]
+    pub fn set_periodic_data(&mut self, instance: u8, data: Vec<u8>) -> Result<(), BleError> {
+        let set = self.configured(instance)?;
+        let periodic = set.periodic.clone().ok_or(BleError::NotFound)?.data(data);
+        let set = set.periodic(Some(periodic));
+        set.validate()?;
+        if let Some(periodic) = &set.periodic {
+            set_periodic_data(instance, &periodic.data)?;
+        }
+        self.inner.deref_mut().sets.insert(instance, set);
+        Ok(())
+    }
+
+    /// Gets the instances of the configured sets
+    pub fn instances(&self) -> Vec<u8> {
+        self.inner.deref().sets.keys().copied().collect()
+    }
+
+    /// Sets a handler for the events of the sets, executed on the main loop when a client
+    /// connects through a connectable set or a set ends its duration
+    ///
+    /// # Arguments
+    ///
+    /// - `handler`: A closure that receives the advertiser and the [AdvertisingSetEvent]
+    ///
+    /// # Returns
+    ///
+    /// The BleAdvertiser itself
+    pub fn event_handler<C: FnMut(&mut BleAdvertiser<'a>, AdvertisingSetEvent) + 'a>(
+        &mut self,
+        handler: C,
+    ) -> &mut Self {
+        self.inner.deref_mut().user_on_event = Some(Box::new(handler));
+        self
+    }
+
+    /// Gets a copy of a configured set
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no set of the instance
+    fn configured(&self, instance: u8) -> Result<AdvertisingSet, BleError> {
+        self.inner
+            .deref()
+            .sets
+            .get(&instance)
+            .cloned()
+            .ok_or(BleError::NotFound)
+    }
+
+    /// Executes the event handler for every event of the sets
+    fn handle_set_events(&mut self) {
+        let events: Vec<AdvertisingSetEvent> = self.inner.deref().events.try_iter().collect();
+        if events.is_empty() {
+            return;
+        }
+        let Some(mut handler) = self.inner.deref_mut().user_on_event.take() else {
+            return;
+        };
+        for event in events {
+            handler(self, event);
+        }
+        self.inner.deref_mut().user_on_event.get_or_insert(handler);
+    }
+}
+
+impl<'a> InterruptDriver<'a> for BleAdvertiser<'a> {
+    fn update_interrupt(&mut self) -> Result<(), Esp32FrameworkError> {
+        self.handle_set_events();
+        Ok(())
+    }
+
+    fn get_updater(&self) -> Box<dyn InterruptDriver<'a> + 'a> {
+        Box::new(Self {
+            inner: self.inner.clone(),
+        })
+    }
+}
+
+/// Sets the advertised data of a set on the BLE stack
+fn set_set_data(instance: u8, data: &[u8]) -> Result<(), BleError> {
+    let buffer = payload_buffer(data)?;
+    let rc = unsafe { sys::ble_gap_ext_adv_set_data(instance, buffer) };
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+/// Sets the periodic data of a set on the BLE stack
+#[cfg(esp_idf_bt_nimble_enable_periodic_adv)]
+fn set_periodic_data(instance: u8, data: &[u8]) -> Result<(), BleError> {
+    let buffer = payload_buffer(data)?;
+    let rc = unsafe { sys::ble_gap_periodic_adv_set_data(instance, buffer) };
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+/// Handler of the GAP events of the advertising sets, executed by the BLE stack
+extern "C" fn on_set_event(event: *mut sys::ble_gap_event, _arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    if event.type_ as u32 != sys::BLE_GAP_EVENT_ADV_COMPLETE {
+        return 0;
+    }
+    let complete = unsafe { &event.__bindgen_anon_1.adv_complete };
+    let set_event = match complete.reason {
+        0 => AdvertisingSetEvent::Connected {
+            instance: complete.instance,
+            conn_handle: complete.conn_handle,
+        },
+        _ => AdvertisingSetEvent::Completed {
+            instance: complete.instance,
+        },
+    };
+    if let Ok(events) = SET_EVENTS.lock() {
+        if let Some((sender, notifier)) = events.as_ref() {
+            if sender.send(set_event).is_ok() {
+                notifier.notify();
+            }
+        }
+    }
+    0
+}
+
+/// Receiver of the running extended scan: the sender of its stream and the fragments of the
+/// advertisements not yet complete, keyed by address and SID
+struct ExtendedScanState {
+    sender: mpsc::Sender<ExtendedAdvertisement>,
+    assembler: ReportAssembler<(AddressKey, u8)>,
+}
+
+static EXTENDED_SCAN: Mutex<Option<ExtendedScanState>> = Mutex::new(None);
+
+/// Stream of the advertisements received by an extended scan, created with
+/// [BleClient::extended_scan_stream]. The scan stops when the stream is dropped, and the stream
+/// ends when the duration of the scan is over.
+pub struct ExtendedScanStream {
+    receiver: mpsc::Receiver<ExtendedAdvertisement>,
+}
+
+impl Stream for ExtendedScanStream {
+    type Item = ExtendedAdvertisement;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for ExtendedScanStream {
+    fn drop(&mut self) {
+        unsafe { sys::ble_gap_disc_cancel() };
+        if let Ok(mut scan) = EXTENDED_SCAN.lock() {
+            *scan = None;
+        }
+    }
+}
+
+/// Gets the scan parameters of the BLE stack for one PHY
+fn scan_params(config: &ExtendedScanConfig) -> sys::ble_gap_ext_disc_params {
+    let mut params: sys::ble_gap_ext_disc_params = unsafe { std::mem::zeroed() };
+    params.itvl = config.interval;
+    params.window = config.window;
+    params.set_passive(!config.active as _);
+    params
+}
+
+/// Handler of the GAP events of the extended scan, executed by the BLE stack. Fragments are
+/// joined, and complete advertisements sent to the stream.
+extern "C" fn on_scan_event(event: *mut sys::ble_gap_event, _arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    let Ok(mut scan) = EXTENDED_SCAN.lock() else {
+        return 0;
+    };
+    match event.type_ as u32 {
+        sys::BLE_GAP_EVENT_EXT_DISC => {
+            let Some(state) = scan.as_mut() else {
+                return 0;
+            };
+            let desc = unsafe { &event.__bindgen_anon_1.ext_disc };
+            let address = BLEAddress::from(desc.addr);
+            let fragment = if desc.data.is_null() {
+                &[][..]
+            } else {
+                unsafe { std::slice::from_raw_parts(desc.data, desc.length_data as usize) }
+            };
+            let status = DataStatus::from_raw(desc.data_status);
+            let Some((data, complete)) =
+                state
+                    .assembler
+                    .push((address_key(&address), desc.sid), status, fragment)
+            else {
+                return 0;
+            };
+            let mut advertisement = ExtendedAdvertisement {
+                address,
+                sid: desc.sid,
+                rssi: desc.rssi,
+                tx_power: (desc.tx_power != TX_POWER_UNAVAILABLE).then_some(desc.tx_power),
+                primary_phy: BlePhy::from_raw(desc.prim_phy).unwrap_or_default(),
+                secondary_phy: BlePhy::from_raw(desc.sec_phy),
+                periodic_interval: (desc.periodic_adv_itvl != 0).then_some(desc.periodic_adv_itvl),
+                connectable: false,
+                scannable: false,
+                scan_response: false,
+                legacy: false,
+                complete,
+                data,
+            };
+            advertisement.set_properties(desc.props);
+            // Advertisements are dropped while the stream is full
+            _ = state.sender.try_send(advertisement);
+        }
+        sys::BLE_GAP_EVENT_DISC_COMPLETE => {
+            // Dropping the sender ends the stream
+            *scan = None;
+        }
+        _ => {}
+    }
+    0
+}
+
+/// Receiver of a periodic sync: the sender of its stream and the fragments of the periodic data
+/// not yet complete, keyed by sync handle
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+struct PeriodicSyncState {
+    sender: mpsc::Sender<PeriodicSyncEvent>,
+    assembler: ReportAssembler<u16>,
+    sync_handle: Option<u16>,
+}
+
+/// Receivers of the periodic syncs, keyed by the id passed to the BLE stack as argument
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+static PERIODIC_SYNCS: Mutex<BTreeMap<usize, PeriodicSyncState>> = Mutex::new(BTreeMap::new());
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+static NEXT_SYNC_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Stream of the events of a periodic advertising train, created with
+/// [BleClient::periodic_sync_stream]. The sync is terminated when the stream is dropped, and the
+/// stream ends when the sync is lost.
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+pub struct PeriodicSyncStream {
+    id: usize,
+    receiver: mpsc::Receiver<PeriodicSyncEvent>,
+}
+
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+impl Stream for PeriodicSyncStream {
+    type Item = PeriodicSyncEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+impl Drop for PeriodicSyncStream {
+    fn drop(&mut self) {
+        let Ok(mut syncs) = PERIODIC_SYNCS.lock() else {
+            return;
+        };
+        match syncs.remove(&self.id).and_then(|state| state.sync_handle) {
+            Some(sync_handle) => unsafe {
+                sys::ble_gap_periodic_adv_sync_terminate(sync_handle);
+            },
+            None => unsafe {
+                sys::ble_gap_periodic_adv_sync_create_cancel();
+            },
+        }
+    }
+}
+
+/// Handler of the GAP events of a periodic sync, executed by the BLE stack. The argument is the
+/// id of its receiver.
+#[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+extern "C" fn on_sync_event(event: *mut sys::ble_gap_event, arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    let Ok(mut syncs) = PERIODIC_SYNCS.lock() else {
+        return 0;
+    };
+    let id = arg as usize;
+    let Some(state) = syncs.get_mut(&id) else {
+        return 0;
+    };
+    let sync_event = unsafe {
+        match event.type_ as u32 {
+            sys::BLE_GAP_EVENT_PERIODIC_SYNC => {
+                let sync = &event.__bindgen_anon_1.periodic_sync;
+                if sync.status != 0 {
+                    PeriodicSyncEvent::Lost {
+                        sync_handle: sync.sync_handle,
+                        reason: sync.status as i32,
+                    }
+                } else {
+                    state.sync_handle = Some(sync.sync_handle);
+                    PeriodicSyncEvent::Established {
+                        sync_handle: sync.sync_handle,
+                        address: BLEAddress::from(sync.adv_addr),
+                        sid: sync.sid,
+                        phy: BlePhy::from_raw(sync.adv_phy).unwrap_or_default(),
+                        interval: sync.per_adv_ival,
+                    }
+                }
+            }
+            sys::BLE_GAP_EVENT_PERIODIC_REPORT => {
+                let report = &event.__bindgen_anon_1.periodic_report;
+                let fragment = if report.data.is_null() {
+                    &[][..]
+                } else {
+                    std::slice::from_raw_parts(report.data, report.data_length as usize)
+                };
+                let status = DataStatus::from_raw(report.data_status);
+                let Some((data, complete)) =
+                    state.assembler.push(report.sync_handle, status, fragment)
+                else {
+                    return 0;
+                };
+                PeriodicSyncEvent::Report {
+                    sync_handle: report.sync_handle,
+                    rssi: report.rssi,
+                    tx_power: (report.tx_power != TX_POWER_UNAVAILABLE).then_some(report.tx_power),
+                    complete,
+                    data,
+                }
+            }
+            sys::BLE_GAP_EVENT_PERIODIC_SYNC_LOST => {
+                let lost = &event.__bindgen_anon_1.periodic_sync_lost;
+                state.assembler.forget(&lost.sync_handle);
+                state.sync_handle = None;
+                PeriodicSyncEvent::Lost {
+                    sync_handle: lost.sync_handle,
+                    reason: lost.reason,
+                }
+            }
+            _ => return 0,
+        }
+    };
+    let lost = matches!(sync_event, PeriodicSyncEvent::Lost { .. });
+    _ = state.sender.try_send(sync_event);
+    if lost {
+        // Dropping the sender ends the stream
+        syncs.remove(&id);
+    }
+    0
+}
+
+impl BleClient {
+    /// Starts an extended scan, which receives the advertisements of Bluetooth 5 advertising
+    /// sets, joined from all their fragments, as well as legacy advertisements. They can be
+    /// awaited as a `futures::Stream` inside [crate::Microcontroller::block_on]. While the stream
+    /// is alive, methods that scan like [Self::find_device] must not be used.
+    ///
+    /// Needs `CONFIG_BT_NIMBLE_EXT_ADV=y` in the sdkconfig.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: The [ExtendedScanConfig] with the settings of the scan
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [ExtendedScanStream], or a `BleError` if the scan could not be started
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the window of the config is not valid
+    /// - `BleError::Code`: If the BLE stack fails to start the scan, like when another scan is
+    ///   running
+    pub fn extended_scan_stream(
+        &mut self,
+        config: ExtendedScanConfig,
+    ) -> Result<ExtendedScanStream, BleError> {
+        config.validate()?;
+        let (sender, receiver) = mpsc::channel(EXTENDED_SCAN_CAPACITY);
+        if let Ok(mut scan) = EXTENDED_SCAN.lock() {
+            *scan = Some(ExtendedScanState {
+                sender,
+                assembler: ReportAssembler::new(),
+            });
+        }
+        let params = scan_params(&config);
+        let coded_params = config.coded.then_some(&params);
+        let rc = unsafe {
+            sys::ble_gap_ext_disc(
+                sys::BLE_OWN_ADDR_PUBLIC as u8,
+                config.duration_units(),
+                0,
+                config.filter_duplicates as u8,
+                sys::BLE_HCI_SCAN_FILT_NO_WL as u8,
+                0,
+                &params,
+                coded_params.map_or(ptr::null(), |params| params as *const _),
+                Some(on_scan_event),
+                ptr::null_mut(),
+            )
+        };
+        let stream = ExtendedScanStream { receiver };
+        BLEError::convert(rc as u32)?;
+        Ok(stream)
+    }
+
+    /// Synchronizes with the periodic advertising of a set, found with
+    /// [Self::extended_scan_stream] by its `periodic_interval`. Its events can be awaited as a
+    /// `futures::Stream` inside [crate::Microcontroller::block_on]. The extended scan must keep
+    /// running until the sync is established.
+    ///
+    /// Needs `CONFIG_BT_NIMBLE_EXT_ADV=y` and `CONFIG_BT_NIMBLE_ENABLE_PERIODIC_SYNC=y` in the
+    /// sdkconfig.
+    ///
+    /// # Arguments
+    ///
+    /// - `address`: The address of the advertiser
+    /// - `sid`: The advertising SID of the set
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [PeriodicSyncStream], or a `BleError` if the sync could not be started
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the BLE stack fails to start the sync, like when another sync is
+    ///   being established
+    #[cfg(esp_idf_bt_nimble_enable_periodic_sync)]
+    pub fn periodic_sync_stream(
+        &mut self,
+        address: BLEAddress,
+        sid: u8,
+    ) -> Result<PeriodicSyncStream, BleError> {
+        let (sender, receiver) = mpsc::channel(PERIODIC_SYNC_CAPACITY);
+        let id = NEXT_SYNC_ID.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut syncs) = PERIODIC_SYNCS.lock() {
+            syncs.insert(
+                id,
+                PeriodicSyncState {
+                    sender,
+                    assembler: ReportAssembler::new(),
+                    sync_handle: None,
+                },
+            );
+        }
+        let mut params: sys::ble_gap_periodic_sync_params = unsafe { std::mem::zeroed() };
+        params.sync_timeout = PERIODIC_SYNC_TIMEOUT;
+        let addr = sys::ble_addr_t {
+            type_: address.addr_type() as u8,
+            val: address.val(),
+        };
+        let rc = unsafe {
+            sys::ble_gap_periodic_adv_sync_create(
+                &addr,
+                sid,
+                &params,
+                Some(on_sync_event),
+                id as *mut c_void,
+            )
+        };
+        let stream = PeriodicSyncStream { id, receiver };
+        BLEError::convert(rc as u32)?;
+        Ok(stream)
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    time::{Duration, Instant},
+};
+
+use esp_idf_svc::hal::task::block_on;
+use futures::StreamExt;
+use serde_json::Value;
+
+use super::{
+    utils::{
+        readings_to_json, BleAdvertisedDevice, BleError, BleId, GatewayConfig, GatewayFilter,
+        GatewayReading, ReadingQueue,
+    },
+    BleClient, ScanConfig, ScanStream,
+};
+use crate::{
+    utils::auxiliary::{SharableRef, SharableRefExt},
+    wifi::http::{Http, HttpHeader, HttpHeaderType},
+};
+
+const RESPONSE_BUFFER_SIZE: usize = 256;
+
+type Decoder = Box<dyn Fn(&[u8]) -> Option<Value>>;
+
+/// Forwards the advertisements of nearby BLE devices to an HTTP server
+/// - `http`: The client used to post the readings
+/// - `config`: The filters, rate limit and batching of the gateway
+/// - `queue`: The readings waiting to be forwarded
+/// - `scan`: The continuous scan the advertisements are received from
+/// - `decoders`: Decode the data of known services into JSON values
+struct _BleGateway<H: Http> {
+    http: H,
+    config: GatewayConfig,
+    queue: ReadingQueue,
+    scan: ScanStream,
+    decoders: HashMap<BleId, Decoder>,
+}
+
+/// Forwards the advertisements of nearby BLE devices to an HTTP server. Advertisements are
+/// filtered by service, manufacturer or name, rate limited per device, and posted as JSON in
+/// batches. Service data with a registered decoder and beacon frames are decoded into their
+/// fields. The configuration can be changed at any time, even while [BleGateway::run] is
+/// running on a clone of the gateway.
+pub struct BleGateway<H: Http> {
+    inner: SharableRef<_BleGateway<H>>,
+}
+
+impl<H: Http> Clone for BleGateway<H> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<H: Http> BleGateway<H> {
+    /// Creates a new BleGateway, starting a continuous scan on the client
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: The BleClient used to scan
+    /// - `http`: The Http client used to post the readings, [crate::wifi::http::HttpsClient]
+    ///   is expected for servers behind TLS
+    /// - `config`: The configuration of the gateway
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the new BleGateway, or a `BleError` if the scan could not be started
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the scan could not be started
+    pub fn new(client: &mut BleClient, http: H, config: GatewayConfig) -> Result<Self, BleError> {
+        let scan = client.scan_stream(ScanConfig::new())?;
+        Ok(Self {
+            inner: SharableRef::new_sharable(_BleGateway {
+                http,
+                queue: ReadingQueue::new(&config),
+                config,
+                scan,
+                decoders: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Forwards every advertisement received until the scan ends. Errors while posting are
+    /// logged and the readings of the failed batch are discarded.
+    pub async fn run_async(&mut self) {
+        loop {
+            let device = poll_fn(|cx| self.inner.deref_mut().scan.poll_next_unpin(cx)).await;
+            let Some(device) = device else {
+                return;
+            };
+            if let Err(err) = self.process(&device) {
+                log::warn!("Could not forward BLE readings: {:?}", err)
+            }
+        }
+    }
+
+    /// Same as [Self::run_async], blocking until the scan ends
+    pub fn run(&mut self) {
+        block_on(self.run_async())
+    }
+}
+
+#[sharable_reference_macro::sharable_reference_wrapper]
+impl<H: Http> _BleGateway<H> {
+    /// Gets a copy of the current configuration
+    pub fn config(&self) -> GatewayConfig {
+        self.config.clone()
+    }
+
+    /// Replaces the configuration. Readings already queued are kept.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: The new configuration
+    pub fn set_config(&mut self, config: GatewayConfig) {
+        self.queue.reconfigure(&config);
+        self.config = config;
+    }
+
+    /// Adds a filter to the configuration. Once there is any filter, only the readings that
+    /// match at least one of them are forwarded.
+    pub fn add_filter(&mut self, filter: GatewayFilter) {
+        self.config.filters.push(filter);
+    }
+
+    /// Sets the minimum time between two readings forwarded of the same device
+    pub fn set_min_interval(&mut self, min_interval: Duration) {
+        self.config.min_interval = min_interval;
+        self.queue.reconfigure(&self.config);
+    }
+
+    /// Sets the function used to decode the data advertised of a service. The value it returns
+    /// is added to the reading as `decoded`.
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The id of the service whose data is decoded
+    /// - `decoder`: Gets the JSON value of the data, or `None` if it can not be decoded
+    pub fn set_decoder<F>(&mut self, service_id: &BleId, decoder: F)
+    where
+        F: Fn(&[u8]) -> Option<Value> + 'static,
+    {
+        self.decoders.insert(service_id.clone(), Box::new(decoder));
+    }
+
+    /// Gets the amount of readings waiting to be forwarded
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Queues the advertisement if it meets the filters and the rate limit of its device, and
+    /// forwards the queued readings once the batch is full or the oldest one is too old.
+    ///
+    /// # Arguments
+    ///
+    /// - `device`: The advertisement received
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with whether the advertisement was queued, or a `BleError` if the readings
+    /// could not be forwarded
+    ///
+    /// # Errors
+    ///
+    /// Same as [Self::flush].
+    pub fn process(&mut self, device: &BleAdvertisedDevice) -> Result<bool, BleError> {
+        let queued = self.queue.offer(GatewayReading::from(device), &self.config);
+        if self.queue.is_due(&self.config, Instant::now()) {
+            self.flush()?;
+        }
+        Ok(queued)
+    }
+
+    /// Posts every queued reading to the server. The queue is emptied even if the post fails.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the readings were accepted or there was nothing to post, or a
+    /// `BleError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Http`: If the request could not be sent or the response could not be read.
+    /// - `BleError::UnexpectedStatus`: If the server answered with a non 2xx status.
+    pub fn flush(&mut self) -> Result<(), BleError> {
+        let readings = self.queue.take();
+        if readings.is_empty() {
+            return Ok(());
+        }
+        let decoders = &self.decoders;
+        let body = readings_to_json(
+            self.config.gateway_id.as_deref(),
+            &readings,
+            Instant::now(),
+            |id, data| decoders.get(id).and_then(|decode| decode(data)),
+        );
+
+        let mut headers = vec![HttpHeader::new(
+            HttpHeaderType::ContentType,
+            String::from("application/json"),
+        )];
+        if let Some(token) = &self.config.token {
+            headers.push(HttpHeader::new(
+                HttpHeaderType::Authorization,
+                format!("Bearer {}", token),
+            ));
+        }
+        self.http
+            .post(&self.config.uri, headers, Some(body.to_string()))?;
+        let mut buffer = [0_u8; RESPONSE_BUFFER_SIZE];
+        self.http.wait_for_response(&mut buffer)?;
+        let status = self.http.response_status();
+        if !(200..300).contains(&status) {
+            return Err(BleError::UnexpectedStatus(status));
+        }
+        Ok(())
+    }
+}
+
+impl From<&BleAdvertisedDevice> for GatewayReading {
+    /// Creates a GatewayReading with the data of the advertisement, received now
+    fn from(device: &BleAdvertisedDevice) -> Self {
+        let name = device.name();
+        GatewayReading {
+            address: *device.addr(),
+            name: (!name.is_empty()).then_some(name),
+            rssi: device.rssi(),
+            service_uuids: device.get_service_uuids(),
+            service_data: device
+                .get_service_data_list()
+                .into_iter()
+                .map(|(id, data)| (id, data.to_vec()))
+                .collect(),
+            manufacturer_data: device.get_manufacture_data().map(|data| data.to_vec()),
+            received_at: Instant::now(),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    cell::RefCell,
+    ptr,
+    rc::Rc,
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+
+use esp32_nimble::{utilities::mutex::Mutex, BLECharacteristic};
+use esp_idf_svc::{hal::delay::FreeRtos, sys};
+
+use super::{
+    utils::{
+        BleError, BleId, Characteristic, DfuCommand, DfuErrorCode, DfuProgress, DfuReceiver,
+        DfuSender, DfuStatus, FirmwareSink, RemoteCharacteristic, Service,
+    },
+    BleClient, BleServer,
+};
+
+/// Id of the firmware update service: 8E7F0001-6C2B-4F4E-9D3A-1B0C5E6D7A80
+pub const DFU_SERVICE_ID: BleId = BleId::FromUuid128([
+    0x80, 0x7A, 0x6D, 0x5E, 0x0C, 0x1B, 0x3A, 0x9D, 0x4E, 0x4F, 0x2B, 0x6C, 0x01, 0x00, 0x7F, 0x8E,
+]);
+/// Id of the control characteristic, written by the client: 8E7F0002-6C2B-4F4E-9D3A-1B0C5E6D7A80
+pub const DFU_CONTROL_ID: BleId = BleId::FromUuid128([
+    0x80, 0x7A, 0x6D, 0x5E, 0x0C, 0x1B, 0x3A, 0x9D, 0x4E, 0x4F, 0x2B, 0x6C, 0x02, 0x00, 0x7F, 0x8E,
+]);
+/// Id of the data characteristic, written by the client: 8E7F0003-6C2B-4F4E-9D3A-1B0C5E6D7A80
+pub const DFU_DATA_ID: BleId = BleId::FromUuid128([
+    0x80, 0x7A, 0x6D, 0x5E, 0x0C, 0x1B, 0x3A, 0x9D, 0x4E, 0x4F, 0x2B, 0x6C, 0x03, 0x00, 0x7F, 0x8E,
+]);
+/// Id of the status characteristic, notified by the server: 8E7F0004-6C2B-4F4E-9D3A-1B0C5E6D7A80
+pub const DFU_STATUS_ID: BleId = BleId::FromUuid128([
+    0x80, 0x7A, 0x6D, 0x5E, 0x0C, 0x1B, 0x3A, 0x9D, 0x4E, 0x4F, 0x2B, 0x6C, 0x04, 0x00, 0x7F, 0x8E,
+]);
+
+const DEFAULT_WINDOW: u16 = 8;
+/// Time given to the BLE stack to send the last status before restarting
+const REBOOT_DELAY_MS: u32 = 200;
+const DEFAULT_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_RETRIES: u8 = 3;
+
+/// Writes the received image into the inactive OTA partition
+struct OtaWriter {
+    partition: *const sys::esp_partition_t,
+    handle: Option<sys::esp_ota_handle_t>,
+}
+
+impl OtaWriter {
+    /// Creates a new OtaWriter for the next OTA partition
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::FirmwareUpdate`: If the partition table has no OTA partition to update
+    fn new() -> Result<Self, BleError> {
+        let partition = unsafe { sys::esp_ota_get_next_update_partition(ptr::null()) };
+        if partition.is_null() {
+            return Err(BleError::FirmwareUpdate(DfuErrorCode::FlashError));
+        }
+        Ok(Self {
+            partition,
+            handle: None,
+        })
+    }
+
+    /// Gets the size of the partition, which is the biggest image it can receive
+    fn capacity(&self) -> u32 {
+        unsafe { (*self.partition).size }
+    }
+}
+
+impl FirmwareSink for OtaWriter {
+    fn begin(&mut self, _size: u32) -> Result<(), DfuErrorCode> {
+        self.abort();
+        let mut handle: sys::esp_ota_handle_t = 0;
+        // Sequential writes erase each sector when it is reached, instead of the whole
+        // partition at once, so no write takes longer than the BLE stack waits for it
+        sys::esp!(unsafe {
+            sys::esp_ota_begin(
+                self.partition,
+                sys::OTA_WITH_SEQUENTIAL_WRITES as usize,
+                &mut handle,
+            )
+        })
+        .map_err(|_| DfuErrorCode::FlashError)?;
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), DfuErrorCode> {
+        let handle = self.handle.ok_or(DfuErrorCode::NotStarted)?;
+        sys::esp!(unsafe { sys::esp_ota_write(handle, data.as_ptr() as *const _, data.len()) })
+            .map_err(|_| DfuErrorCode::FlashError)
+    }
+
+    fn finish(&mut self) -> Result<(), DfuErrorCode> {
+        let handle = self.handle.take().ok_or(DfuErrorCode::NotStarted)?;
+        // Validates the image, and with signed apps enabled also checks its signature, so an
+        // image that is not signed never becomes the boot partition
+        sys::esp!(unsafe { sys::esp_ota_end(handle) }).map_err(|_| DfuErrorCode::FlashError)?;
+        sys::esp!(unsafe { sys::esp_ota_set_boot_partition(self.partition) })
+            .map_err(|_| DfuErrorCode::FlashError)
+    }
+
+    fn abort(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            unsafe { sys::esp_ota_abort(handle) };
+        }
+    }
+}
+
+/// State shared by the handlers of the characteristics of the [DfuServer]
+struct DfuState {
+    receiver: DfuReceiver<OtaWriter>,
+    status: Option<Arc<Mutex<BLECharacteristic>>>,
+}
+
+impl DfuState {
+    /// Notifies each status to the clients. After [DfuStatus::Rebooting] the device restarts
+    /// into the new image.
+    fn reply(&mut self, statuses: Vec<DfuStatus>) {
+        let Some(characteristic) = self.status.as_ref() else {
+            return;
+        };
+        for status in statuses {
+            let mut characteristic = characteristic.lock();
+            characteristic.set_value(&status.to_bytes());
+            characteristic.notify();
+            drop(characteristic);
+            if status == DfuStatus::Rebooting {
+                FreeRtos::delay_ms(REBOOT_DELAY_MS);
+                unsafe { sys::esp_restart() };
+            }
+        }
+    }
+}
+
+/// Firmware update service of a BleServer. A client sends an image in chunks that fit the MTU,
+/// which are written into the inactive OTA partition as they arrive. The server acknowledges
+/// every window of chunks, so the client never sends more than it can handle, and verifies
+/// the CRC-32 and SHA-256 of the image before it can be applied. Applying it reboots the
+/// device into the new image.
+///
+/// If the client disconnects, the transfer is kept and starting it again with the same image
+/// resumes it from the last byte written. A restart of the device discards it.
+///
+/// The chunks are written from the main loop, so [crate::Microcontroller::wait_for_updates]
+/// must be called periodicly during the transfer.
+///
+/// Writing the control and data characteristics requires an encrypted and authenticated
+/// connection, so the server must be created with [crate::Microcontroller::ble_secure_server]
+/// and a [crate::ble::utils::Security] with man in the middle protection. Only clients that
+/// paired with the passkey can send images.
+///
+/// The CRC-32 and SHA-256 only detect corrupted transfers, images are not signed. A paired
+/// client can install any image, unless signed apps (`CONFIG_SECURE_SIGNED_APPS_NO_SECURE_BOOT`)
+/// or secure boot are enabled, in which case images not signed with the key of the project are
+/// rejected before being applied.
+///
+/// The partition table must have two OTA partitions.
+pub struct DfuServer {
+    state: Rc<RefCell<DfuState>>,
+}
+
+impl DfuServer {
+    /// Sets the firmware update service on the server
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer that will offer the service
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the DfuServer, or a `BleError` if the service could not be set
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::FirmwareUpdate`: If the partition table has no OTA partition to update
+    /// - `BleError::ServiceNotFound`: If the service could not be set on the server
+    pub fn new(server: &mut BleServer) -> Result<Self, BleError> {
+        Self::new_with_window(server, DEFAULT_WINDOW)
+    }
+
+    /// Same as [Self::new], but with the amount of chunks the client can send before waiting
+    /// for an acknowledgement. Bigger windows are faster, but need the BLE stack to buffer more
+    /// chunks.
+    pub fn new_with_window(server: &mut BleServer, window: u16) -> Result<Self, BleError> {
+        let writer = OtaWriter::new()?;
+        let max_size = writer.capacity();
+        let state = Rc::new(RefCell::new(DfuState {
+            receiver: DfuReceiver::new(writer, max_size, window),
+            status: None,
+        }));
+
+        let control_state = state.clone();
+        let control = Characteristic::new(&DFU_CONTROL_ID, vec![])
+            .writable(true)
+            .writable_no_rsp(true)
+            .writable_enc(true)
+            .writable_authen(true)
+            .on_write(move |bytes, _| {
+                let mut state = control_state.borrow_mut();
+                let statuses = state.receiver.handle_control(bytes);
+                state.reply(statuses);
+                Ok(())
+            });
+        let data_state = state.clone();
+        let data = Characteristic::new(&DFU_DATA_ID, vec![])
+            .writable_no_rsp(true)
+            .writable_enc(true)
+            .writable_authen(true)
+            .on_write(move |bytes, _| {
+                let mut state = data_state.borrow_mut();
+                let statuses = state.receiver.handle_data(bytes);
+                state.reply(statuses);
+                Ok(())
+            });
+        let status = Characteristic::new(&DFU_STATUS_ID, vec![])
+            .readable(true)
+            .notifiable(true);
+        let service = Service::new(&DFU_SERVICE_ID, vec![])?
+            .add_characteristics(&vec![control, data, status]);
+        server.set_service(&service)?;
+
+        state.borrow_mut().status =
+            Some(server.raw_characteristic(&DFU_SERVICE_ID, &DFU_STATUS_ID)?);
+        Ok(Self { state })
+    }
+
+    /// Gets the progress of the transfer in progress
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the amount of bytes received and the size of the image, or `None` if
+    /// there is no transfer in progress
+    pub fn progress(&self) -> Option<(u32, u32)> {
+        self.state.borrow().receiver.progress()
+    }
+}
+
+/// Sends firmware images to a device offering the service of a [DfuServer], allowing an ESP32
+/// to update another one. The server only accepts images from paired clients, so the client
+/// must be created with [crate::Microcontroller::ble_secure_client] and pair before the update.
+///
+/// # Example
+///
+/// ```ignore
+/// let device = client.find_device_with_service(None, &DFU_SERVICE_ID)?;
+/// client.connect_to_device(device)?;
+/// client.secure_connection()?;
+/// let mut dfu = DfuClient::new(&mut client)?;
+/// dfu.update(include_bytes!("firmware.bin"), |written, size| {
+///     println!("{}/{}", written, size)
+/// })?;
+/// ```
+pub struct DfuClient {
+    client: BleClient,
+    control: RemoteCharacteristic,
+    data: RemoteCharacteristic,
+    statuses: Receiver<DfuStatus>,
+    timeout: Duration,
+    retries: u8,
+}
+
+impl DfuClient {
+    /// Subscribes to the firmware update service of the device the client is connected to
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: A BleClient already connected to a device offering the service
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the DfuClient, or a `BleError` if the service could not be used
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the client is not connected
+    /// - `BleError::ServiceNotFound`: If the device does not offer the service
+    /// - `BleError::CharacteristicNotFound`: If the service lacks one of its characteristics
+    /// - `BleError::CharacteristicNotNotifiable`: If the status characteristic is not notifiable
+    pub fn new(client: &mut BleClient) -> Result<Self, BleError> {
+        let control = client.get_characteristic(&DFU_SERVICE_ID, &DFU_CONTROL_ID)?;
+        let data = client.get_characteristic(&DFU_SERVICE_ID, &DFU_DATA_ID)?;
+        let mut status = client.get_characteristic(&DFU_SERVICE_ID, &DFU_STATUS_ID)?;
+
+        let (sender, statuses) = channel();
+        let sender = StdMutex::new(sender);
+        status.on_raw_notify(move |bytes| {
+            if let (Some(status), Ok(sender)) = (DfuStatus::from_bytes(bytes), sender.lock()) {
+                _ = sender.send(status);
+            }
+        })?;
+        Ok(Self {
+            client: client.clone(),
+            control,
+            data,
+            statuses,
+            timeout: DEFAULT_STATUS_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+        })
+    }
+
+    /// Sets how long to wait for the device to answer, and how many times the transfer is
+    /// resumed when it does not, before giving up
+    pub fn set_timeout(&mut self, timeout: Duration, retries: u8) -> &mut Self {
+        self.timeout = timeout;
+        self.retries = retries;
+        self
+    }
+
+    /// Sends a firmware image and makes the device reboot into it. If a previous transfer of
+    /// the same image was interrupted, it is resumed.
+    ///
+    /// # Arguments
+    ///
+    /// - `image`: The firmware image, as found in the `.bin` file of a build
+    /// - `on_progress`: Executed every time the device acknowledges chunks, with the amount of
+    ///   bytes written and the size of the image
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with () once the device is rebooting into the image, or a `BleError` if
+    /// the update failed
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::FirmwareUpdate`: If the device reported an error, like a checksum mismatch
+    /// - `BleError::TimeOut`: If the device stopped answering
+    /// - `BleError::Disconnected`: If connection to the device is lost
+    /// - `BleError::Code`: If a write fails
+    pub fn update<C: FnMut(u32, u32)>(
+        &mut self,
+        image: &[u8],
+        mut on_progress: C,
+    ) -> Result<(), BleError> {
+        let mut sender = DfuSender::new(image, self.client.mtu());
+        while self.statuses.try_recv().is_ok() {}
+        self.control.write(&sender.start_command())?;
+
+        let mut retries = 0;
+        loop {
+            while let Some(chunk) = sender.next_chunk() {
+                self.data.write(&chunk)?;
+            }
+            let status = match self.statuses.recv_timeout(self.timeout) {
+                Ok(status) => status,
+                Err(RecvTimeoutError::Timeout) if retries < self.retries => {
+                    retries += 1;
+                    self.control.write(&sender.start_command())?;
+                    continue;
+                }
+                Err(_) => return Err(BleError::TimeOut),
+            };
+            retries = 0;
+            match sender
+                .handle_status(status)
+                .map_err(BleError::FirmwareUpdate)?
+            {
+                DfuProgress::Sending => {
+                    let (written, size) = sender.progress();
+                    on_progress(written, size);
+                }
+                DfuProgress::Verified => self.control.write(&DfuCommand::Apply.to_bytes())?,
+                DfuProgress::Done => return Ok(()),
+            }
+        }
+    }
+
+    /// Cancels the transfer in progress on the device, discarding the data it received
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If connection to the device is lost
+    /// - `BleError::Code`: If the write fails
+    pub fn abort(&mut self) -> Result<(), BleError> {
+        self.control.write(&DfuCommand::Abort.to_bytes())
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ffi::{c_int, c_void},
+    future::poll_fn,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Poll, Waker},
+};
+
+use esp32_nimble::BLEError;
+use esp_idf_svc::{hal::task::block_on, sys};
+
+use super::utils::{
+    check_l2cap_mtu, check_psm, sdu_segments, BleError, SduQueue, DEFAULT_RECEIVE_WINDOW,
+};
+
+/// Channels waiting to be accepted by each listener
+const ACCEPT_BACKLOG: usize = 4;
+
+/// Locks a mutex, recovering its data if a thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Gets an empty buffer of the BLE stack where an SDU is received
+fn receive_buffer() -> Result<*mut sys::os_mbuf, BleError> {
+    let buffer = unsafe { sys::os_msys_get_pkthdr(0, 0) };
+    if buffer.is_null() {
+        return Err(BleError::Code(
+            sys::BLE_HS_ENOMEM,
+            "No buffer for L2CAP".to_string(),
+        ));
+    }
+    Ok(buffer)
+}
+
+/// Gives the channel a buffer for the next SDU, which lets the BLE stack give the peer credits
+fn give_credit(chan: usize) {
+    if let Ok(buffer) = receive_buffer() {
+        unsafe { sys::ble_l2cap_recv_ready(chan as *mut sys::ble_l2cap_chan, buffer) };
+    }
+}
+
+/// Copies an SDU out of a buffer of the BLE stack and frees the buffer
+fn take_sdu(buffer: *mut sys::os_mbuf) -> Vec<u8> {
+    if buffer.is_null() {
+        return Vec::new();
+    }
+    unsafe {
+        let len = sys::os_mbuf_len(buffer);
+        let mut sdu = vec![0; len as usize];
+        sys::os_mbuf_copydata(buffer, 0, len as c_int, sdu.as_mut_ptr() as *mut c_void);
+        sys::os_mbuf_free_chain(buffer);
+        sdu
+    }
+}
+
+/// State of a channel, updated by the BLE stack
+/// - `chan`: The channel of the BLE stack, 0 until it is connected
+/// - `unstalls`: Times the channel was able to send again after running out of credits
+/// - `closed`: Whether the channel disconnected or failed to connect
+struct ChannelState {
+    chan: usize,
+    conn_handle: u16,
+    psm: u16,
+    mtu: u16,
+    peer_mtu: u16,
+    received: SduQueue,
+    unstalls: usize,
+    connected: bool,
+    closed: bool,
+    wakers: Vec<Waker>,
+}
+
+impl ChannelState {
+    fn new(conn_handle: u16, psm: u16, mtu: u16) -> Self {
+        Self {
+            chan: 0,
+            conn_handle,
+            psm,
+            mtu,
+            peer_mtu: 0,
+            received: SduQueue::new(DEFAULT_RECEIVE_WINDOW),
+            unstalls: 0,
+            connected: false,
+            closed: false,
+            wakers: Vec::new(),
+        }
+    }
+
+    /// Wakes every future waiting for a change of the channel
+    fn wake(&mut self) {
+        self.wakers.drain(..).for_each(Waker::wake);
+    }
+
+    /// Sets the channel as connected, with the MTUs negotiated
+    fn connect(&mut self, chan: *mut sys::ble_l2cap_chan) {
+        let mut info: sys::ble_l2cap_chan_info = unsafe { std::mem::zeroed() };
+        unsafe { sys::ble_l2cap_get_chan_info(chan, &mut info) };
+        self.chan = chan as usize;
+        self.mtu = info.our_coc_mtu;
+        self.peer_mtu = info.peer_coc_mtu;
+        self.connected = true;
+        self.wake();
+    }
+
+    /// Sets the channel as closed
+    fn close(&mut self) {
+        self.connected = false;
+        self.closed = true;
+        self.wake();
+    }
+}
+
+/// Channels waiting to be accepted on a PSM
+struct ListenerState {
+    pending: VecDeque<Arc<Mutex<ChannelState>>>,
+    wakers: Vec<Waker>,
+}
+
+/// Owner of the events of a listener or of a channel being connected, passed to the BLE stack as
+/// the argument of its callback
+enum Endpoint {
+    Listener(Arc<Mutex<ListenerState>>),
+    Connecting(Arc<Mutex<ChannelState>>),
+}
+
+static ENDPOINTS: Mutex<BTreeMap<usize, Endpoint>> = Mutex::new(BTreeMap::new());
+static NEXT_ENDPOINT: AtomicUsize = AtomicUsize::new(1);
+/// Open channels, by their channel of the BLE stack
+static CHANNELS: Mutex<BTreeMap<usize, Arc<Mutex<ChannelState>>>> = Mutex::new(BTreeMap::new());
+/// PSMs already listened on, the BLE stack can not stop listening
+static LISTENED: Mutex<BTreeMap<u16, usize>> = Mutex::new(BTreeMap::new());
+
+/// Handler of the L2CAP events, executed by the BLE stack. The argument is the id of the
+/// [Endpoint] of the event.
+extern "C" fn on_l2cap_event(event: *mut sys::ble_l2cap_event, arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    unsafe {
+        match event.type_ as u32 {
+            sys::BLE_L2CAP_EVENT_COC_ACCEPT => {
+                let accept = &event.__bindgen_anon_1.accept;
+                on_accept(arg as usize, accept.conn_handle, accept.chan)
+            }
+            sys::BLE_L2CAP_EVENT_COC_CONNECTED => {
+                let connect = &event.__bindgen_anon_1.connect;
+                on_connected(arg as usize, connect.status, connect.chan);
+                0
+            }
+            sys::BLE_L2CAP_EVENT_COC_DATA_RECEIVED => {
+                let receive = &event.__bindgen_anon_1.receive;
+                on_received(receive.chan, take_sdu(receive.sdu_rx));
+                0
+            }
+            sys::BLE_L2CAP_EVENT_COC_TX_UNSTALLED => {
+                let unstalled = &event.__bindgen_anon_1.tx_unstalled;
+                if let Some(channel) = lock(&CHANNELS).get(&(unstalled.chan as usize)) {
+                    let mut channel = lock(channel);
+                    channel.unstalls += 1;
+                    channel.wake();
+                }
+                0
+            }
+            sys::BLE_L2CAP_EVENT_COC_DISCONNECTED => {
+                let disconnect = &event.__bindgen_anon_1.disconnect;
+                if let Some(channel) = lock(&CHANNELS).remove(&(disconnect.chan as usize)) {
+                    lock(&channel).close();
+                }
+                0
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// A client asked to open a channel on a listened PSM. The channel is given a buffer to receive
+/// its first SDU and waits to be accepted.
+fn on_accept(endpoint: usize, conn_handle: u16, chan: *mut sys::ble_l2cap_chan) -> c_int {
+    let endpoints = lock(&ENDPOINTS);
+    let Some(Endpoint::Listener(listener)) = endpoints.get(&endpoint) else {
+        return sys::BLE_HS_ENOTSUP as c_int;
+    };
+    let mut listener = lock(listener);
+    if listener.pending.len() >= ACCEPT_BACKLOG {
+        return sys::BLE_HS_ENOMEM as c_int;
+    }
+    let mut info: sys::ble_l2cap_chan_info = unsafe { std::mem::zeroed() };
+    unsafe { sys::ble_l2cap_get_chan_info(chan, &mut info) };
+    let channel = Arc::new(Mutex::new(ChannelState::new(
+        conn_handle,
+        info.psm,
+        info.our_coc_mtu,
+    )));
+    lock(&CHANNELS).insert(chan as usize, channel.clone());
+    listener.pending.push_back(channel);
+    give_credit(chan as usize);
+    0
+}
+
+/// A channel finished connecting, or failed to
+fn on_connected(endpoint: usize, status: c_int, chan: *mut sys::ble_l2cap_chan) {
+    let mut endpoints = lock(&ENDPOINTS);
+    match endpoints.get(&endpoint) {
+        Some(Endpoint::Listener(listener)) => {
+            let Some(channel) = lock(&CHANNELS).get(&(chan as usize)).cloned() else {
+                return;
+            };
+            if status != 0 {
+                lock(&CHANNELS).remove(&(chan as usize));
+                lock(&channel).close();
+                return;
+            }
+            lock(&channel).connect(chan);
+            let mut listener = lock(listener);
+            listener.wakers.drain(..).for_each(Waker::wake);
+        }
+        Some(Endpoint::Connecting(_)) => {
+            let Some(Endpoint::Connecting(channel)) = endpoints.remove(&endpoint) else {
+                return;
+            };
+            if status != 0 {
+                lock(&channel).close();
+                return;
+            }
+            lock(&CHANNELS).insert(chan as usize, channel.clone());
+            lock(&channel).connect(chan);
+        }
+        None => {}
+    }
+}
+
+/// An SDU was received on a channel. The next buffer is given now only if there is room for the
+/// SDU, otherwise the peer waits until the reader catches up.
+fn on_received(chan: *mut sys::ble_l2cap_chan, sdu: Vec<u8>) {
+    let Some(channel) = lock(&CHANNELS).get(&(chan as usize)).cloned() else {
+        return;
+    };
+    let mut channel = lock(&channel);
+    if channel.received.push(sdu) {
+        give_credit(chan as usize);
+    }
+    channel.wake();
+}
+
+/// An L2CAP connection-oriented channel, opened with [L2capListener::accept] on a server or with
+/// [super::BleClient::l2cap_connect] on a client. Data is sent and received in SDUs of up to the MTU of
+/// the receiver, with credit based flow control: the sender waits while the receiver has no room
+/// for more SDUs, so bulk transfers go as fast as both sides allow without losing data.
+///
+/// Needs `CONFIG_BT_NIMBLE_L2CAP_COC_MAX_NUM` of at least 1 in the sdkconfig.
+#[derive(Clone)]
+pub struct L2capChannel {
+    state: Arc<Mutex<ChannelState>>,
+}
+
+impl L2capChannel {
+    /// Gets the handle of the connection the channel was opened on
+    pub fn conn_handle(&self) -> u16 {
+        lock(&self.state).conn_handle
+    }
+
+    /// Gets the PSM of the channel
+    pub fn psm(&self) -> u16 {
+        lock(&self.state).psm
+    }
+
+    /// Gets the biggest SDU this side receives
+    pub fn mtu(&self) -> u16 {
+        lock(&self.state).mtu
+    }
+
+    /// Gets the biggest SDU the peer receives, and so the biggest that can be sent
+    pub fn peer_mtu(&self) -> u16 {
+        lock(&self.state).peer_mtu
+    }
+
+    /// Returns whether the channel is still open
+    pub fn is_connected(&self) -> bool {
+        lock(&self.state).connected
+    }
+
+    /// Gets the amount of received SDUs not yet read
+    pub fn available(&self) -> usize {
+        lock(&self.state).received.len()
+    }
+
+    /// Blocking method that sends an SDU, waiting while the peer has no credits left
+    ///
+    /// # Arguments
+    ///
+    /// - `sdu`: The data to send, of at most [Self::peer_mtu] bytes
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the SDU was handed to the BLE stack, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the channel is closed
+    /// - `BleError::InvalidParameters`: If the SDU is bigger than the MTU of the peer
+    /// - `BleError::Code`: If the BLE stack fails to send it, like when it runs out of buffers
+    pub fn send(&mut self, sdu: &[u8]) -> Result<(), BleError> {
+        block_on(self.send_async(sdu))
+    }
+
+    /// Non blocking async version of [Self::send]
+    pub async fn send_async(&mut self, sdu: &[u8]) -> Result<(), BleError> {
+        if sdu.len() > self.peer_mtu() as usize {
+            return Err(BleError::InvalidParameters);
+        }
+        loop {
+            let (chan, unstalls) = {
+                let state = lock(&self.state);
+                if !state.connected {
+                    return Err(BleError::Disconnected);
+                }
+                (state.chan, state.unstalls)
+            };
+            let buffer = unsafe {
+                sys::ble_hs_mbuf_from_flat(sdu.as_ptr() as *const c_void, sdu.len() as u16)
+            };
+            if buffer.is_null() {
+                return Err(BleError::Code(
+                    sys::BLE_HS_ENOMEM,
+                    "No buffer for L2CAP".to_string(),
+                ));
+            }
+            let rc = unsafe { sys::ble_l2cap_send(chan as *mut sys::ble_l2cap_chan, buffer) };
+            match rc as u32 {
+                0 => return Ok(()),
+                // The SDU was queued, but the next one must wait for new credits
+                sys::BLE_HS_ESTALLED => return self.wait_unstall(unstalls).await,
+                // The previous SDU is still being sent, so this one is sent again later
+                sys::BLE_HS_EBUSY => {
+                    unsafe { sys::os_mbuf_free_chain(buffer) };
+                    self.wait_unstall(unstalls).await?;
+                }
+                _ => return Ok(BLEError::convert(rc as u32)?),
+            }
+        }
+    }
+
+    /// Blocking method that sends a buffer of any size, split in SDUs of the MTU of the peer
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the channel is closed before sending every SDU
+    /// - `BleError::Code`: If the BLE stack fails to send an SDU
+    pub fn send_all(&mut self, data: &[u8]) -> Result<(), BleError> {
+        block_on(self.send_all_async(data))
+    }
+
+    /// Non blocking async version of [Self::send_all]
+    pub async fn send_all_async(&mut self, data: &[u8]) -> Result<(), BleError> {
+        let peer_mtu = self.peer_mtu();
+        for sdu in sdu_segments(data, peer_mtu) {
+            self.send_async(sdu).await?;
+        }
+        Ok(())
+    }
+
+    /// Blocking method that waits for the next SDU received
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the channel closes with no SDUs left to read
+    pub fn receive(&mut self) -> Result<Vec<u8>, BleError> {
+        block_on(self.receive_async())
+    }
+
+    /// Non blocking async version of [Self::receive]
+    pub async fn receive_async(&mut self) -> Result<Vec<u8>, BleError> {
+        poll_fn(|cx| match self.try_receive() {
+            Ok(Some(sdu)) => Poll::Ready(Ok(sdu)),
+            Ok(None) => {
+                lock(&self.state).wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        })
+        .await
+    }
+
+    /// Reads the next SDU received, if there is any
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the channel closed with no SDUs left to read
+    pub fn try_receive(&mut self) -> Result<Option<Vec<u8>>, BleError> {
+        let (sdu, credit, chan, closed) = {
+            let mut state = lock(&self.state);
+            let (sdu, credit) = state.received.pop();
+            (sdu, credit && state.connected, state.chan, state.closed)
+        };
+        if credit {
+            give_credit(chan);
+        }
+        match sdu {
+            None if closed => Err(BleError::Disconnected),
+            sdu => Ok(sdu),
+        }
+    }
+
+    /// Closes the channel
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the channel is already closed
+    /// - `BleError::Code`: If the BLE stack fails to close it
+    pub fn disconnect(&mut self) -> Result<(), BleError> {
+        let chan = {
+            let state = lock(&self.state);
+            if !state.connected {
+                return Err(BleError::Disconnected);
+            }
+            state.chan
+        };
+        let rc = unsafe { sys::ble_l2cap_disconnect(chan as *mut sys::ble_l2cap_chan) };
+        Ok(BLEError::convert(rc as u32)?)
+    }
+
+    /// Waits until the channel can send again after `unstalls` times
+    async fn wait_unstall(&self, unstalls: usize) -> Result<(), BleError> {
+        poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            if state.unstalls > unstalls {
+                Poll::Ready(Ok(()))
+            } else if !state.connected {
+                Poll::Ready(Err(BleError::Disconnected))
+            } else {
+                state.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Waits until a channel being connected is open
+    async fn connected(self) -> Result<Self, BleError> {
+        poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            if state.connected {
+                Poll::Ready(Ok(()))
+            } else if state.closed {
+                Poll::Ready(Err(BleError::CouldNotConnectToDevice))
+            } else {
+                state.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await?;
+        Ok(self)
+    }
+}
+
+/// Listener of the L2CAP channels that clients open on a PSM, created with
+/// [super::BleServer::l2cap_listen]. The BLE stack keeps listening for as long as the program runs,
+/// so listening again on the same PSM gives another listener of the same channels.
+///
+/// Needs `CONFIG_BT_NIMBLE_L2CAP_COC_MAX_NUM` of at least 1 in the sdkconfig.
+#[derive(Clone)]
+pub struct L2capListener {
+    psm: u16,
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl L2capListener {
+    /// Starts listening on a PSM
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the PSM is not between 0x0001 and 0x00FF or the MTU is
+    ///   smaller than 23
+    /// - `BleError::Code`: If the BLE stack fails to listen, like when L2CAP channels are not
+    ///   enabled in the sdkconfig
+    pub(crate) fn new(psm: u16, mtu: u16) -> Result<Self, BleError> {
+        check_psm(psm)?;
+        check_l2cap_mtu(mtu)?;
+        let mut listened = lock(&LISTENED);
+        if let Some(id) = listened.get(&psm) {
+            if let Some(Endpoint::Listener(state)) = lock(&ENDPOINTS).get(id) {
+                return Ok(Self {
+                    psm,
+                    state: state.clone(),
+                });
+            }
+        }
+        let id = NEXT_ENDPOINT.fetch_add(1, Ordering::Relaxed);
+        let state = Arc::new(Mutex::new(ListenerState {
+            pending: VecDeque::new(),
+            wakers: Vec::new(),
+        }));
+        lock(&ENDPOINTS).insert(id, Endpoint::Listener(state.clone()));
+        let rc = unsafe {
+            sys::ble_l2cap_create_server(psm, mtu, Some(on_l2cap_event), id as *mut c_void)
+        };
+        if let Err(err) = BLEError::convert(rc as u32) {
+            lock(&ENDPOINTS).remove(&id);
+            return Err(err.into());
+        }
+        listened.insert(psm, id);
+        Ok(Self { psm, state })
+    }
+
+    /// Gets the PSM listened on
+    pub fn psm(&self) -> u16 {
+        self.psm
+    }
+
+    /// Blocking method that waits for a client to open a channel
+    pub fn accept(&mut self) -> L2capChannel {
+        block_on(self.accept_async())
+    }
+
+    /// Non blocking async version of [Self::accept]
+    pub async fn accept_async(&mut self) -> L2capChannel {
+        poll_fn(|cx| match self.try_accept() {
+            Some(channel) => Poll::Ready(channel),
+            None => {
+                lock(&self.state).wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Gets a channel opened by a client, if there is any. Channels that closed before being
+    /// accepted are skipped.
+    pub fn try_accept(&mut self) -> Option<L2capChannel> {
+        let mut state = lock(&self.state);
+        while let Some(channel) = state.pending.front() {
+            let (connected, closed) = {
+                let channel = lock(channel);
+                (channel.connected, channel.closed)
+            };
+            if !connected && !closed {
+                return None;
+            }
+            let channel = state.pending.pop_front()?;
+            if connected {
+                return Some(L2capChannel { state: channel });
+            }
+        }
+        None
+    }
+}
+
+/// Opens a channel on a connection, waiting until the server accepts it
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the PSM or the MTU are not valid
+/// - `BleError::CouldNotConnectToDevice`: If the server refused the channel
+/// - `BleError::Code`: If the BLE stack fails to open the channel
+pub(crate) async fn connect_channel(
+    conn_handle: u16,
+    psm: u16,
+    mtu: u16,
+) -> Result<L2capChannel, BleError> {
+    check_psm(psm)?;
+    check_l2cap_mtu(mtu)?;
+    let buffer = receive_buffer()?;
+    let id = NEXT_ENDPOINT.fetch_add(1, Ordering::Relaxed);
+    let state = Arc::new(Mutex::new(ChannelState::new(conn_handle, psm, mtu)));
+    lock(&ENDPOINTS).insert(id, Endpoint::Connecting(state.clone()));
+    let rc = unsafe {
+        sys::ble_l2cap_connect(
+            conn_handle,
+            psm,
+            mtu,
+            buffer,
+            Some(on_l2cap_event),
+            id as *mut c_void,
+        )
+    };
+    if let Err(err) = BLEError::convert(rc as u32) {
+        lock(&ENDPOINTS).remove(&id);
+        unsafe { sys::os_mbuf_free_chain(buffer) };
+        return Err(err.into());
+    }
+    L2capChannel { state }.connected().await
+}
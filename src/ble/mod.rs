@@ -1,9 +1,25 @@
 mod ble_client;
+mod ble_connection;
 mod ble_connection_oriented;
 mod ble_connectionless;
+#[cfg(esp_idf_bt_nimble_ext_adv)]
+mod ble_extended_advertising;
+mod ble_gateway;
+mod dfu;
+mod l2cap;
+mod nordic_uart;
+pub mod profiles;
 pub mod utils;
 
 pub use ble_client::*;
+pub use ble_connection::*;
 pub use ble_connection_oriented::*;
 pub use ble_connectionless::*;
-pub use utils::{BleError, BleId};
+#[cfg(esp_idf_bt_nimble_ext_adv)]
+pub use ble_extended_advertising::*;
+pub use ble_gateway::*;
+pub use dfu::*;
+pub use gatt_service_macro::GattService;
+pub use l2cap::*;
+pub use nordic_uart::*;
+pub use utils::{BleError, BleId, GattService};
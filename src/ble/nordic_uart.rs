@@ -0,0 +1,298 @@
+use std::{collections::HashMap, time::Duration};
+
+use super::{
+    utils::{
+        line_from_bytes, mtu_chunks, BleError, BleId, Characteristic, RemoteCharacteristic,
+        Service, SharedUartBuffer, DEFAULT_MTU,
+    },
+    BleClient, BleServer,
+};
+use crate::serial::{parse_key_values, SerialError, READER, WRITER};
+
+/// Id of the Nordic UART Service: 6E400001-B5A3-F393-E0A9-E50E24DCCA9E
+pub const NORDIC_UART_SERVICE_ID: BleId = BleId::FromUuid128([
+    0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x01, 0x00, 0x40, 0x6E,
+]);
+/// Id of the RX characteristic, written by the client: 6E400002-B5A3-F393-E0A9-E50E24DCCA9E
+pub const NORDIC_UART_RX_ID: BleId = BleId::FromUuid128([
+    0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x02, 0x00, 0x40, 0x6E,
+]);
+/// Id of the TX characteristic, notified by the server: 6E400003-B5A3-F393-E0A9-E50E24DCCA9E
+pub const NORDIC_UART_TX_ID: BleId = BleId::FromUuid128([
+    0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x03, 0x00, 0x40, 0x6E,
+]);
+
+const DEFAULT_BUFFER_CAPACITY: usize = 512;
+
+/// Server end of the Nordic UART Service, a serial link over BLE supported by most BLE
+/// terminal apps. Writes are split in notifications that fit the MTU of the connected clients,
+/// and received bytes are stored in a buffer until they are read.
+pub struct NordicUartServer<'a> {
+    server: BleServer<'a>,
+    tx: Characteristic,
+    buffer: SharedUartBuffer,
+}
+
+impl<'a> NordicUartServer<'a> {
+    /// Sets the Nordic UART Service on the server
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer that will offer the service
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the NordicUartServer, or a `BleError` if the service could not be set
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service could not be set on the server
+    pub fn new(server: &mut BleServer<'a>) -> Result<Self, BleError> {
+        Self::new_with_capacity(server, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    /// Same as [Self::new], but with the capacity of the receive buffer. Once full, the oldest
+    /// bytes received are discarded.
+    pub fn new_with_capacity(
+        server: &mut BleServer<'a>,
+        capacity: usize,
+    ) -> Result<Self, BleError> {
+        let rx = Characteristic::new(&NORDIC_UART_RX_ID, vec![])
+            .writable(true)
+            .writable_no_rsp(true);
+        let tx = Characteristic::new(&NORDIC_UART_TX_ID, vec![]).notifiable(true);
+        let service = Service::new(&NORDIC_UART_SERVICE_ID, vec![])?
+            .add_characteristics(&vec![rx, tx.clone()]);
+        server.set_service(&service)?;
+
+        let buffer = SharedUartBuffer::new(capacity);
+        let receiver = buffer.clone();
+        server.on_raw_write(&NORDIC_UART_SERVICE_ID, &NORDIC_UART_RX_ID, move |bytes| {
+            receiver.push(bytes)
+        })?;
+        Ok(Self {
+            server: server.clone(),
+            tx,
+            buffer,
+        })
+    }
+
+    /// Sends data to every connected client, split in notifications that fit the smallest MTU
+    /// of the connections.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is no longer set on the server
+    pub fn write(&mut self, data: &[u8]) -> Result<(), BleError> {
+        let mtu = self
+            .server
+            .list_clients()
+            .iter()
+            .map(|client| client.mtu)
+            .min()
+            .unwrap_or(DEFAULT_MTU);
+        for chunk in mtu_chunks(data, mtu) {
+            self.tx.update_data(chunk.to_vec());
+            self.server
+                .notify_value(&NORDIC_UART_SERVICE_ID, &self.tx)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a line, appending "\r\n" to it
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is no longer set on the server
+    pub fn write_line(&mut self, line: &str) -> Result<(), BleError> {
+        self.write(format!("{}\r\n", line).as_bytes())
+    }
+
+    /// Gets the amount of received bytes available to read
+    pub fn available(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Reads the received bytes that fit in `buffer`, without waiting
+    ///
+    /// # Returns
+    ///
+    /// The amount of bytes read
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        self.buffer.read(buffer)
+    }
+
+    /// Waits until the delimiter is received, returning the bytes before it
+    ///
+    /// # Arguments
+    ///
+    /// - `delimiter`: The byte that ends the message
+    /// - `timeout`: The maximum time to wait, or `None` to wait indefinitely
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the bytes before the delimiter, or `None` if the timeout was reached
+    pub fn read_until(&mut self, delimiter: u8, timeout: Option<Duration>) -> Option<Vec<u8>> {
+        self.buffer.read_until(delimiter, timeout)
+    }
+
+    /// Waits until a line ended in "\n" or "\r\n" is received
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the line without its ending, or `None` if the timeout was reached
+    pub fn read_line(&mut self, timeout: Option<Duration>) -> Option<String> {
+        self.read_until(b'\n', timeout).map(line_from_bytes)
+    }
+}
+
+impl READER for NordicUartServer<'_> {
+    /// Blocks until a line is received and parses its `key=value` pairs, as
+    /// [parse_key_values] does.
+    fn read_and_parse(&mut self) -> HashMap<String, String> {
+        self.read_line(None)
+            .map(|line| parse_key_values(&line))
+            .unwrap_or_default()
+    }
+}
+
+impl WRITER for NordicUartServer<'_> {
+    /// Sends the bytes to every connected client. The address is ignored.
+    ///
+    /// # Errors
+    ///
+    /// - `SerialError::ErrorInWriteValue`: If the notifications could not be sent.
+    fn parse_and_write(&mut self, _addr: u8, bytes_to_write: &[u8]) -> Result<(), SerialError> {
+        self.write(bytes_to_write)
+            .map_err(|_| SerialError::ErrorInWriteValue)
+    }
+}
+
+/// Client end of the Nordic UART Service. Writes are split in chunks that fit the MTU of the
+/// connection, and the notifications received are stored in a buffer until they are read.
+pub struct NordicUartClient {
+    client: BleClient,
+    rx: RemoteCharacteristic,
+    buffer: SharedUartBuffer,
+}
+
+impl NordicUartClient {
+    /// Subscribes to the Nordic UART Service of the device the client is connected to
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: A BleClient already connected to a device offering the service
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the NordicUartClient, or a `BleError` if the service could not be used
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If the client is not connected
+    /// - `BleError::ServiceNotFound`: If the device does not offer the service
+    /// - `BleError::CharacteristicNotFound`: If the service lacks the RX or TX characteristic
+    /// - `BleError::CharacteristicNotNotifiable`: If the TX characteristic is not notifiable
+    pub fn new(client: &mut BleClient) -> Result<Self, BleError> {
+        Self::new_with_capacity(client, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    /// Same as [Self::new], but with the capacity of the receive buffer. Once full, the oldest
+    /// bytes received are discarded.
+    pub fn new_with_capacity(client: &mut BleClient, capacity: usize) -> Result<Self, BleError> {
+        let rx = client.get_characteristic(&NORDIC_UART_SERVICE_ID, &NORDIC_UART_RX_ID)?;
+        let mut tx = client.get_characteristic(&NORDIC_UART_SERVICE_ID, &NORDIC_UART_TX_ID)?;
+
+        let buffer = SharedUartBuffer::new(capacity);
+        let receiver = buffer.clone();
+        tx.on_raw_notify(move |bytes| receiver.push(bytes))?;
+        Ok(Self {
+            client: client.clone(),
+            rx,
+            buffer,
+        })
+    }
+
+    /// Sends data to the device, split in writes that fit the MTU of the connection
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotWritable`: If the RX characteristic is not writable
+    /// - `BleError::Code`: If a write fails
+    pub fn write(&mut self, data: &[u8]) -> Result<(), BleError> {
+        let mtu = self.client.mtu();
+        for chunk in mtu_chunks(data, mtu) {
+            self.rx.write(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a line, appending "\r\n" to it
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotWritable`: If the RX characteristic is not writable
+    /// - `BleError::Code`: If a write fails
+    pub fn write_line(&mut self, line: &str) -> Result<(), BleError> {
+        self.write(format!("{}\r\n", line).as_bytes())
+    }
+
+    /// Gets the amount of received bytes available to read
+    pub fn available(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Reads the received bytes that fit in `buffer`, without waiting
+    ///
+    /// # Returns
+    ///
+    /// The amount of bytes read
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        self.buffer.read(buffer)
+    }
+
+    /// Waits until the delimiter is received, returning the bytes before it
+    ///
+    /// # Arguments
+    ///
+    /// - `delimiter`: The byte that ends the message
+    /// - `timeout`: The maximum time to wait, or `None` to wait indefinitely
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the bytes before the delimiter, or `None` if the timeout was reached
+    pub fn read_until(&mut self, delimiter: u8, timeout: Option<Duration>) -> Option<Vec<u8>> {
+        self.buffer.read_until(delimiter, timeout)
+    }
+
+    /// Waits until a line ended in "\n" or "\r\n" is received
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the line without its ending, or `None` if the timeout was reached
+    pub fn read_line(&mut self, timeout: Option<Duration>) -> Option<String> {
+        self.read_until(b'\n', timeout).map(line_from_bytes)
+    }
+}
+
+impl READER for NordicUartClient {
+    /// Blocks until a line is received and parses its `key=value` pairs, as
+    /// [parse_key_values] does.
+    fn read_and_parse(&mut self) -> HashMap<String, String> {
+        self.read_line(None)
+            .map(|line| parse_key_values(&line))
+            .unwrap_or_default()
+    }
+}
+
+impl WRITER for NordicUartClient {
+    /// Sends the bytes to the device. The address is ignored.
+    ///
+    /// # Errors
+    ///
+    /// - `SerialError::ErrorInWriteValue`: If a write fails.
+    fn parse_and_write(&mut self, _addr: u8, bytes_to_write: &[u8]) -> Result<(), SerialError> {
+        self.write(bytes_to_write)
+            .map_err(|_| SerialError::ErrorInWriteValue)
+    }
+}
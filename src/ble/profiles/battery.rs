@@ -0,0 +1,65 @@
+use crate::ble::{
+    utils::{
+        ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+        TypedCharacteristic,
+    },
+    BleError, BleServer, GattService,
+};
+
+const MAX_BATTERY_LEVEL: u8 = 100;
+
+/// Battery Service (0x180F). Exposes the battery level as a percentage, which clients can
+/// read or subscribe to.
+#[derive(GattService)]
+#[service(uuid = StandardServiceId::Battery)]
+pub struct BatteryService {
+    #[characteristic(uuid = StandardCharacteristicId::BatteryLevel, read, notify, initial = MAX_BATTERY_LEVEL)]
+    level: TypedCharacteristic<u8>,
+}
+
+impl BatteryService {
+    /// Sets the battery level, notifying the subscribed clients
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer the service was set on
+    /// - `level`: The battery level, as a percentage from 0 to 100
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the level is greater than 100.
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server.
+    pub fn set_level(&mut self, server: &mut BleServer<'_>, level: u8) -> Result<(), BleError> {
+        if level > MAX_BATTERY_LEVEL {
+            return Err(BleError::InvalidValue);
+        }
+        server.set(&mut self.level, level)
+    }
+
+    /// Gets the last battery level set
+    pub fn level(&self) -> u8 {
+        self.level.value().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble::BleId;
+
+    #[test]
+    fn battery_01_service_layout() {
+        let battery = BatteryService::new();
+        let service = battery.service().unwrap();
+        assert_eq!(service.id, BleId::FromUuid16(0x180F));
+        assert_eq!(service.characteristics.len(), 1);
+        assert_eq!(service.characteristics[0].id, BleId::FromUuid16(0x2A19));
+        assert_eq!(service.characteristics[0].data, vec![100]);
+        assert!(service.characteristics[0].is_readable());
+        assert!(service.characteristics[0].is_notifiable());
+    }
+}
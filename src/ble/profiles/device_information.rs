@@ -0,0 +1,227 @@
+use crate::ble::{
+    utils::{
+        ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+        Characteristic,
+    },
+    BleError, BleId, GattService,
+};
+
+const MAX_MANUFACTURER_IDENTIFIER: u64 = 0xFF_FFFF_FFFF;
+const MAX_OUI: u32 = 0xFF_FFFF;
+
+/// Organization that assigned the vendor id of a [PnpId]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VendorIdSource {
+    BluetoothSig = 1,
+    UsbImplementersForum = 2,
+}
+
+/// Plug and Play identification of a device.
+/// - `vendor_id_source`: Who assigned the vendor id.
+/// - `vendor_id`: The id of the manufacturer.
+/// - `product_id`: The id of the product, assigned by the manufacturer.
+/// - `product_version`: The version of the product, assigned by the manufacturer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PnpId {
+    pub vendor_id_source: VendorIdSource,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_version: u16,
+}
+
+impl PnpId {
+    /// Encodes the PnpId as the PnP ID characteristic defines
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.vendor_id_source as u8];
+        bytes.extend(self.vendor_id.to_le_bytes());
+        bytes.extend(self.product_id.to_le_bytes());
+        bytes.extend(self.product_version.to_le_bytes());
+        bytes
+    }
+}
+
+/// Device Information Service (0x180A). Exposes read only information about the device, only
+/// the characteristics that were set are part of the service. Since the values do not change,
+/// they must be set before setting the service on a server.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceInformationService {
+    manufacturer_name: Option<String>,
+    model_number: Option<String>,
+    serial_number: Option<String>,
+    hardware_revision: Option<String>,
+    firmware_revision: Option<String>,
+    software_revision: Option<String>,
+    system_id: Option<Vec<u8>>,
+    pnp_id: Option<PnpId>,
+}
+
+impl DeviceInformationService {
+    /// Creates a new DeviceInformationService without any characteristic
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of the manufacturer of the device
+    pub fn manufacturer_name(mut self, name: &str) -> Self {
+        self.manufacturer_name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the model number assigned by the manufacturer
+    pub fn model_number(mut self, model: &str) -> Self {
+        self.model_number = Some(model.to_string());
+        self
+    }
+
+    /// Sets the serial number of this particular device
+    pub fn serial_number(mut self, serial: &str) -> Self {
+        self.serial_number = Some(serial.to_string());
+        self
+    }
+
+    /// Sets the hardware revision of the device
+    pub fn hardware_revision(mut self, revision: &str) -> Self {
+        self.hardware_revision = Some(revision.to_string());
+        self
+    }
+
+    /// Sets the firmware revision of the device
+    pub fn firmware_revision(mut self, revision: &str) -> Self {
+        self.firmware_revision = Some(revision.to_string());
+        self
+    }
+
+    /// Sets the software revision of the device
+    pub fn software_revision(mut self, revision: &str) -> Self {
+        self.software_revision = Some(revision.to_string());
+        self
+    }
+
+    /// Sets the system id of the device
+    ///
+    /// # Arguments
+    ///
+    /// - `manufacturer_identifier`: The 40 bit identifier assigned by the manufacturer
+    /// - `oui`: The 24 bit Organizationally Unique Identifier of the manufacturer
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the DeviceInformationService itself, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If any of the identifiers does not fit in its size.
+    pub fn system_id(mut self, manufacturer_identifier: u64, oui: u32) -> Result<Self, BleError> {
+        if manufacturer_identifier > MAX_MANUFACTURER_IDENTIFIER || oui > MAX_OUI {
+            return Err(BleError::InvalidValue);
+        }
+        let mut bytes = manufacturer_identifier.to_le_bytes()[..5].to_vec();
+        bytes.extend(&oui.to_le_bytes()[..3]);
+        self.system_id = Some(bytes);
+        Ok(self)
+    }
+
+    /// Sets the Plug and Play identification of the device
+    pub fn pnp_id(mut self, pnp_id: PnpId) -> Self {
+        self.pnp_id = Some(pnp_id);
+        self
+    }
+}
+
+impl GattService for DeviceInformationService {
+    fn service_id(&self) -> BleId {
+        BleId::from_standard_service(StandardServiceId::DeviceInformation)
+    }
+
+    fn characteristics(&self) -> Vec<Characteristic> {
+        let strings = [
+            (
+                StandardCharacteristicId::ManufacturerNameString,
+                &self.manufacturer_name,
+            ),
+            (
+                StandardCharacteristicId::ModelNumberString,
+                &self.model_number,
+            ),
+            (
+                StandardCharacteristicId::SerialNumberString,
+                &self.serial_number,
+            ),
+            (
+                StandardCharacteristicId::HardwareRevisionString,
+                &self.hardware_revision,
+            ),
+            (
+                StandardCharacteristicId::FirmwareRevisionString,
+                &self.firmware_revision,
+            ),
+            (
+                StandardCharacteristicId::SoftwareRevisionString,
+                &self.software_revision,
+            ),
+        ];
+        let mut values: Vec<(StandardCharacteristicId, Vec<u8>)> = strings
+            .into_iter()
+            .filter_map(|(id, value)| value.as_ref().map(|value| (id, value.as_bytes().to_vec())))
+            .collect();
+        if let Some(system_id) = &self.system_id {
+            values.push((StandardCharacteristicId::SystemID, system_id.clone()));
+        }
+        if let Some(pnp_id) = &self.pnp_id {
+            values.push((StandardCharacteristicId::PnPID, pnp_id.to_bytes()));
+        }
+
+        values
+            .into_iter()
+            .map(|(id, data)| {
+                Characteristic::new(&BleId::from_standard_characteristic(id), data).readable(true)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn device_information_01_pnp_id() {
+        let pnp_id = PnpId {
+            vendor_id_source: VendorIdSource::UsbImplementersForum,
+            vendor_id: 0x05AC,
+            product_id: 0x820A,
+            product_version: 0x0100,
+        };
+        assert_eq!(
+            pnp_id.to_bytes(),
+            vec![0x02, 0xAC, 0x05, 0x0A, 0x82, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn device_information_02_system_id() {
+        let service = DeviceInformationService::new()
+            .system_id(0x01_2345_6789, 0xABCDEF)
+            .unwrap();
+        assert_eq!(
+            service.system_id,
+            Some(vec![0x89, 0x67, 0x45, 0x23, 0x01, 0xEF, 0xCD, 0xAB])
+        );
+        assert!(DeviceInformationService::new()
+            .system_id(0x100_0000_0000, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn device_information_03_only_set_characteristics() {
+        let characteristics = DeviceInformationService::new()
+            .manufacturer_name("Espressif")
+            .firmware_revision("1.0.0")
+            .characteristics();
+        assert_eq!(characteristics.len(), 2);
+        assert_eq!(characteristics[0].id, BleId::FromUuid16(0x2A29));
+        assert_eq!(characteristics[0].data, b"Espressif".to_vec());
+        assert_eq!(characteristics[1].id, BleId::FromUuid16(0x2A26));
+        assert!(characteristics[1].is_readable());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::ble::{
+    utils::{
+        ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+        TypedCharacteristic,
+    },
+    BleError, BleServer, GattService,
+};
+
+const TEMPERATURE_RESOLUTION: f32 = 0.01;
+const HUMIDITY_RESOLUTION: f32 = 0.01;
+const PRESSURE_RESOLUTION: f32 = 0.1;
+const MAX_HUMIDITY: f32 = 100.0;
+
+/// Environmental Sensing Service (0x181A) with temperature, humidity and pressure
+/// characteristics, which clients can read or subscribe to. Values are converted to the
+/// fixed point representation each characteristic defines:
+/// - Temperature: `i16` in 0.01 degrees Celsius.
+/// - Humidity: `u16` in 0.01 percent.
+/// - Pressure: `u32` in 0.1 Pascal.
+#[derive(GattService)]
+#[service(uuid = StandardServiceId::EnvironmentalSensing)]
+pub struct EnvironmentalSensingService {
+    #[characteristic(uuid = StandardCharacteristicId::Temperature, read, notify)]
+    temperature: TypedCharacteristic<i16>,
+    #[characteristic(uuid = StandardCharacteristicId::Humidity, read, notify)]
+    humidity: TypedCharacteristic<u16>,
+    #[characteristic(uuid = StandardCharacteristicId::Pressure, read, notify)]
+    pressure: TypedCharacteristic<u32>,
+}
+
+impl EnvironmentalSensingService {
+    /// Sets the temperature, notifying the subscribed clients
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer the service was set on
+    /// - `celsius`: The temperature in degrees Celsius
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the temperature can not be represented, it must be between
+    ///   -273.15 and 327.67 degrees.
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server.
+    pub fn set_temperature(
+        &mut self,
+        server: &mut BleServer<'_>,
+        celsius: f32,
+    ) -> Result<(), BleError> {
+        server.set(&mut self.temperature, encode_temperature(celsius)?)
+    }
+
+    /// Sets the relative humidity, notifying the subscribed clients
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer the service was set on
+    /// - `percentage`: The relative humidity, from 0 to 100
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the humidity is not between 0 and 100.
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server.
+    pub fn set_humidity(
+        &mut self,
+        server: &mut BleServer<'_>,
+        percentage: f32,
+    ) -> Result<(), BleError> {
+        server.set(&mut self.humidity, encode_humidity(percentage)?)
+    }
+
+    /// Sets the pressure, notifying the subscribed clients
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer the service was set on
+    /// - `pascals`: The pressure in Pascal
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the pressure is negative or too big to be represented.
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server.
+    pub fn set_pressure(
+        &mut self,
+        server: &mut BleServer<'_>,
+        pascals: f32,
+    ) -> Result<(), BleError> {
+        server.set(&mut self.pressure, encode_pressure(pascals)?)
+    }
+
+    /// Gets the last temperature set, in degrees Celsius
+    pub fn temperature(&self) -> f32 {
+        self.temperature.value().unwrap_or_default() as f32 * TEMPERATURE_RESOLUTION
+    }
+
+    /// Gets the last relative humidity set, as a percentage
+    pub fn humidity(&self) -> f32 {
+        self.humidity.value().unwrap_or_default() as f32 * HUMIDITY_RESOLUTION
+    }
+
+    /// Gets the last pressure set, in Pascal
+    pub fn pressure(&self) -> f32 {
+        self.pressure.value().unwrap_or_default() as f32 * PRESSURE_RESOLUTION
+    }
+}
+
+/// Converts a value into its fixed point representation, checking it is in range
+fn to_fixed_point(value: f32, resolution: f32, min: f64, max: f64) -> Result<f64, BleError> {
+    let fixed = (value / resolution).round() as f64;
+    if !value.is_finite() || fixed < min || fixed > max {
+        return Err(BleError::InvalidValue);
+    }
+    Ok(fixed)
+}
+
+fn encode_temperature(celsius: f32) -> Result<i16, BleError> {
+    // -273.15 is the absolute zero, the lowest value allowed by the specification
+    to_fixed_point(celsius, TEMPERATURE_RESOLUTION, -27315.0, i16::MAX as f64).map(|t| t as i16)
+}
+
+fn encode_humidity(percentage: f32) -> Result<u16, BleError> {
+    let max = (MAX_HUMIDITY / HUMIDITY_RESOLUTION) as f64;
+    to_fixed_point(percentage, HUMIDITY_RESOLUTION, 0.0, max).map(|h| h as u16)
+}
+
+fn encode_pressure(pascals: f32) -> Result<u32, BleError> {
+    to_fixed_point(pascals, PRESSURE_RESOLUTION, 0.0, u32::MAX as f64).map(|p| p as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble::utils::GattValue;
+
+    #[test]
+    fn environmental_sensing_01_temperature() {
+        assert_eq!(
+            encode_temperature(21.5).unwrap().to_bytes(),
+            vec![0x66, 0x08]
+        );
+        assert_eq!(
+            encode_temperature(-10.0).unwrap().to_bytes(),
+            vec![0x18, 0xFC]
+        );
+        assert!(encode_temperature(-300.0).is_err());
+        assert!(encode_temperature(400.0).is_err());
+        assert!(encode_temperature(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn environmental_sensing_02_humidity() {
+        assert_eq!(encode_humidity(45.5).unwrap().to_bytes(), vec![0xC6, 0x11]);
+        assert_eq!(encode_humidity(100.0).unwrap(), 10000);
+        assert!(encode_humidity(-1.0).is_err());
+        assert!(encode_humidity(100.5).is_err());
+    }
+
+    #[test]
+    fn environmental_sensing_03_pressure() {
+        assert_eq!(
+            encode_pressure(101325.0).unwrap().to_bytes(),
+            vec![0x02, 0x76, 0x0F, 0x00]
+        );
+        assert!(encode_pressure(-1.0).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::ble::{
+    utils::{
+        ble_standard_uuids::{StandardCharacteristicId, StandardServiceId},
+        GattValue, TypedCharacteristic,
+    },
+    BleError, BleServer, GattService,
+};
+
+const FLAG_HEART_RATE_U16: u8 = 0x01;
+const FLAG_SENSOR_CONTACT_DETECTED: u8 = 0x02;
+const FLAG_SENSOR_CONTACT_SUPPORTED: u8 = 0x04;
+const FLAG_ENERGY_EXPENDED: u8 = 0x08;
+const FLAG_RR_INTERVALS: u8 = 0x10;
+
+/// Maximum size of a notification with the default ATT MTU
+const MAX_MEASUREMENT_SIZE: usize = 20;
+const RR_INTERVAL_RESOLUTION: u64 = 1024;
+
+/// Location of the heart rate sensor on the body, as defined by the Body Sensor Location characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodySensorLocation {
+    Other = 0,
+    Chest = 1,
+    Wrist = 2,
+    Finger = 3,
+    Hand = 4,
+    EarLobe = 5,
+    Foot = 6,
+}
+
+/// A Heart Rate Measurement, encoded as the characteristic of the same name defines.
+/// - `bpm`: The heart rate in beats per minute. Values greater than 255 are sent as an `u16`.
+/// - `sensor_contact`: `None` if the sensor does not support contact detection, otherwise whether
+///   it has contact with the skin.
+/// - `energy_expended`: The accumulated energy expended in kilo Joules, if supported.
+/// - `rr_intervals`: The RR intervals in units of 1/1024 seconds, oldest first. If they do not fit
+///   in a single notification only the most recent ones are sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeartRateMeasurement {
+    pub bpm: u16,
+    pub sensor_contact: Option<bool>,
+    pub energy_expended: Option<u16>,
+    pub rr_intervals: Vec<u16>,
+}
+
+impl HeartRateMeasurement {
+    /// Creates a new HeartRateMeasurement with just the heart rate
+    pub fn new(bpm: u16) -> Self {
+        Self {
+            bpm,
+            ..Default::default()
+        }
+    }
+
+    /// Converts an RR interval in miliseconds into units of 1/1024 seconds
+    pub fn rr_interval_from_millis(millis: u32) -> u16 {
+        (millis as u64 * RR_INTERVAL_RESOLUTION / 1000).min(u16::MAX as u64) as u16
+    }
+}
+
+impl GattValue for HeartRateMeasurement {
+    const FORMAT: u8 = 0x1B;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        let mut fields = Vec::new();
+        match u8::try_from(self.bpm) {
+            Ok(bpm) => fields.push(bpm),
+            Err(_) => {
+                flags |= FLAG_HEART_RATE_U16;
+                fields.extend(self.bpm.to_le_bytes());
+            }
+        }
+        match self.sensor_contact {
+            Some(true) => flags |= FLAG_SENSOR_CONTACT_SUPPORTED | FLAG_SENSOR_CONTACT_DETECTED,
+            Some(false) => flags |= FLAG_SENSOR_CONTACT_SUPPORTED,
+            None => {}
+        }
+        if let Some(energy) = self.energy_expended {
+            flags |= FLAG_ENERGY_EXPENDED;
+            fields.extend(energy.to_le_bytes());
+        }
+        if !self.rr_intervals.is_empty() {
+            flags |= FLAG_RR_INTERVALS;
+            let fitting = (MAX_MEASUREMENT_SIZE - 1 - fields.len()) / 2;
+            let skipped = self.rr_intervals.len().saturating_sub(fitting);
+            for rr in &self.rr_intervals[skipped..] {
+                fields.extend(rr.to_le_bytes());
+            }
+        }
+
+        let mut bytes = vec![flags];
+        bytes.extend(fields);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        let (flags, mut rest) = bytes.split_first().ok_or(BleError::InvalidValue)?;
+        let bpm = if flags & FLAG_HEART_RATE_U16 != 0 {
+            take_u16(&mut rest)?
+        } else {
+            let (bpm, tail) = rest.split_first().ok_or(BleError::InvalidValue)?;
+            rest = tail;
+            *bpm as u16
+        };
+        let sensor_contact = match flags & FLAG_SENSOR_CONTACT_SUPPORTED != 0 {
+            true => Some(flags & FLAG_SENSOR_CONTACT_DETECTED != 0),
+            false => None,
+        };
+        let energy_expended = match flags & FLAG_ENERGY_EXPENDED != 0 {
+            true => Some(take_u16(&mut rest)?),
+            false => None,
+        };
+        let mut rr_intervals = Vec::new();
+        if flags & FLAG_RR_INTERVALS != 0 {
+            while !rest.is_empty() {
+                rr_intervals.push(take_u16(&mut rest)?);
+            }
+        }
+        Ok(Self {
+            bpm,
+            sensor_contact,
+            energy_expended,
+            rr_intervals,
+        })
+    }
+}
+
+/// Takes an `u16` from the start of the bytes, advancing them
+fn take_u16(bytes: &mut &[u8]) -> Result<u16, BleError> {
+    if bytes.len() < 2 {
+        return Err(BleError::InvalidValue);
+    }
+    let (value, rest) = bytes.split_at(2);
+    *bytes = rest;
+    Ok(u16::from_le_bytes([value[0], value[1]]))
+}
+
+/// Heart Rate Service (0x180D). Clients subscribe to the Heart Rate Measurement characteristic
+/// to receive each measurement, and can read where the sensor is placed.
+#[derive(GattService)]
+#[service(uuid = StandardServiceId::HeartRate)]
+pub struct HeartRateService {
+    #[characteristic(uuid = StandardCharacteristicId::HeartRateMeasurement, notify)]
+    measurement: TypedCharacteristic<HeartRateMeasurement>,
+    #[characteristic(uuid = StandardCharacteristicId::BodySensorLocation, read)]
+    location: TypedCharacteristic<u8>,
+}
+
+impl HeartRateService {
+    /// Creates a new HeartRateService with the location of the sensor
+    pub fn new_with_location(location: BodySensorLocation) -> Self {
+        let mut service = Self::new();
+        service.location.set_value(&(location as u8));
+        service
+    }
+
+    /// Notifies a new measurement to the subscribed clients
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer the service was set on
+    /// - `measurement`: The new HeartRateMeasurement
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the operation completed successfully, or an `BleError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is not set on the server.
+    pub fn notify_measurement(
+        &mut self,
+        server: &mut BleServer<'_>,
+        measurement: HeartRateMeasurement,
+    ) -> Result<(), BleError> {
+        server.set(&mut self.measurement, measurement)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heart_rate_01_u8_heart_rate_with_contact() {
+        let measurement = HeartRateMeasurement {
+            bpm: 72,
+            sensor_contact: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(measurement.to_bytes(), vec![0x06, 0x48]);
+    }
+
+    #[test]
+    fn heart_rate_02_u16_heart_rate() {
+        assert_eq!(
+            HeartRateMeasurement::new(300).to_bytes(),
+            vec![0x01, 0x2C, 0x01]
+        );
+    }
+
+    #[test]
+    fn heart_rate_03_energy_and_rr_intervals() {
+        let measurement = HeartRateMeasurement {
+            bpm: 60,
+            sensor_contact: Some(false),
+            energy_expended: Some(0x0102),
+            rr_intervals: vec![1024, 512],
+        };
+        let bytes = measurement.to_bytes();
+        assert_eq!(bytes, vec![0x1C, 0x3C, 0x02, 0x01, 0x00, 0x04, 0x00, 0x02]);
+        assert_eq!(
+            HeartRateMeasurement::from_bytes(&bytes).unwrap(),
+            measurement
+        );
+    }
+
+    #[test]
+    fn heart_rate_04_only_recent_rr_intervals_fit() {
+        let measurement = HeartRateMeasurement {
+            bpm: 60,
+            rr_intervals: (0..12).collect(),
+            ..Default::default()
+        };
+        let bytes = measurement.to_bytes();
+        assert_eq!(bytes.len(), 20);
+        let decoded = HeartRateMeasurement::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.rr_intervals, (3..12).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn heart_rate_05_rr_interval_from_millis() {
+        assert_eq!(HeartRateMeasurement::rr_interval_from_millis(1000), 1024);
+        assert_eq!(HeartRateMeasurement::rr_interval_from_millis(500), 512);
+        assert_eq!(
+            HeartRateMeasurement::rr_interval_from_millis(u32::MAX),
+            u16::MAX
+        );
+    }
+
+    #[test]
+    fn heart_rate_06_truncated_measurement_is_invalid() {
+        assert!(HeartRateMeasurement::from_bytes(&[]).is_err());
+        assert!(HeartRateMeasurement::from_bytes(&[0x01, 0x2C]).is_err());
+        assert!(HeartRateMeasurement::from_bytes(&[0x10, 0x3C, 0x00]).is_err());
+    }
+}
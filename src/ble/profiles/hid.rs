@@ -0,0 +1,511 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU8, Ordering},
+    Arc,
+};
+
+use esp32_nimble::{utilities::mutex::Mutex, BLECharacteristic};
+use esp_idf_svc::hal::delay::FreeRtos;
+
+use super::{
+    hid_reports::{
+        relative_steps, CONSUMER_CONTROL_REPORT_ID, GAMEPAD_REPORT_ID, INPUT_REPORT,
+        KEYBOARD_REPORT_ID, MOUSE_REPORT_ID, OUTPUT_REPORT,
+    },
+    BatteryService, ConsumerKey, DeviceInformationService, GamepadReport, HatSwitch, HidConfig,
+    Key, KeyboardLeds, KeyboardReport, Modifier, MouseButton, MouseReport,
+};
+use crate::ble::{
+    utils::{
+        ble_standard_uuids::{StandardCharacteristicId, StandardDescriptorId, StandardServiceId},
+        AdvertisementBuilder, Characteristic, Descriptor, Service,
+    },
+    BleError, BleId, BleServer, GattService,
+};
+
+/// Version 1.11 of the HID specification
+const HID_VERSION: u16 = 0x0111;
+const HID_COUNTRY_CODE: u8 = 0;
+const HID_FLAG_NORMALLY_CONNECTABLE: u8 = 0x02;
+const CONTROL_POINT_SUSPEND: u8 = 0;
+const PROTOCOL_MODE_REPORT: u8 = 1;
+const GAMEPAD_BUTTONS: u8 = 16;
+/// Time between the reports sent when typing, so the host does not miss any of them
+const TYPING_DELAY_MS: u32 = 10;
+
+const HID_SERVICE_ID: BleId = BleId::from_standard_service(StandardServiceId::HumanInterfaceDevice);
+
+/// Input report of a HID device, sent as a notification to the subscribed hosts
+struct InputReport {
+    characteristic: Arc<Mutex<BLECharacteristic>>,
+}
+
+impl InputReport {
+    /// Adds the Report characteristic of an input report to the HID service
+    fn new(server: &mut BleServer<'_>, id: u8, size: usize) -> Result<Self, BleError> {
+        let characteristic = report_characteristic(id, INPUT_REPORT, size)
+            .readable(true)
+            .readable_enc(true)
+            .notifiable(true);
+        Ok(Self {
+            characteristic: server.add_raw_characteristic(&HID_SERVICE_ID, &characteristic)?,
+        })
+    }
+
+    /// Sets the value of the report, notifying it to the subscribed hosts
+    fn send(&self, bytes: &[u8]) {
+        let mut characteristic = self.characteristic.lock();
+        characteristic.set_value(bytes);
+        characteristic.notify();
+    }
+}
+
+/// Creates a Report characteristic with its Report Reference descriptor, without properties
+fn report_characteristic(id: u8, report_type: u8, size: usize) -> Characteristic {
+    let reference = Descriptor::new(
+        BleId::from_standard_descriptor(StandardDescriptorId::ReportReference),
+        vec![id, report_type],
+    )
+    .readable(true)
+    .readable_enc(true);
+    Characteristic::new(
+        &BleId::from_standard_characteristic(StandardCharacteristicId::Report),
+        vec![0; size],
+    )
+    .add_descriptor(&reference)
+}
+
+/// HID over GATT device: a keyboard, mouse, media keys and gamepad, or any combination of them,
+/// that computers and phones can use without drivers. Together with the HID service, the
+/// Battery and Device Information services that hosts require are set on the server.
+///
+/// Hosts only use HID devices over encrypted connections, so the server must be created with
+/// [crate::Microcontroller::ble_secure_server] allowing bonding.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut hid = HidDevice::new(&mut server, &HidConfig::new().keyboard(true))?;
+/// server.set_advertisement(&hid.advertisement("Keyboard"))?;
+/// server.start()?;
+/// hid.keyboard().unwrap().type_str("hello")?;
+/// ```
+pub struct HidDevice<'a> {
+    server: BleServer<'a>,
+    battery: BatteryService,
+    appearance: u16,
+    suspended: Arc<AtomicBool>,
+    keyboard: Option<HidKeyboard>,
+    mouse: Option<HidMouse>,
+    consumer_control: Option<HidConsumerControl>,
+    gamepad: Option<HidGamepad>,
+}
+
+impl<'a> HidDevice<'a> {
+    /// Sets the HID, Battery and Device Information services on the server. It must be done
+    /// before starting the server.
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer that will offer the services
+    /// - `config`: The HidConfig with the reports of the device
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the HidDevice, or a `BleError` if the services could not be set
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the config has no report
+    /// - `BleError::PropertiesError`: If a characteristic could not be created
+    /// - `BleError::ServiceNotFound`: If a service could not be set on the server
+    pub fn new(server: &mut BleServer<'a>, config: &HidConfig) -> Result<Self, BleError> {
+        let report_map = config.report_map();
+        if report_map.is_empty() {
+            return Err(BleError::InvalidParameters);
+        }
+        let battery = BatteryService::new();
+        server.set_service(&battery.service()?)?;
+        let device_information = DeviceInformationService::new().pnp_id(config.pnp_id);
+        server.set_service(&device_information.service()?)?;
+
+        let mut information = HID_VERSION.to_le_bytes().to_vec();
+        information.extend([HID_COUNTRY_CODE, HID_FLAG_NORMALLY_CONNECTABLE]);
+        let control_point_id =
+            BleId::from_standard_characteristic(StandardCharacteristicId::HIDControlPoint);
+        let service = Service::new(&HID_SERVICE_ID, vec![])?
+            .add_characteristic(
+                &Characteristic::new(
+                    &BleId::from_standard_characteristic(StandardCharacteristicId::HIDInformation),
+                    information,
+                )
+                .readable(true),
+            )
+            .add_characteristic(
+                &Characteristic::new(
+                    &BleId::from_standard_characteristic(StandardCharacteristicId::ReportMap),
+                    report_map,
+                )
+                .readable(true)
+                .readable_enc(true),
+            )
+            .add_characteristic(
+                &Characteristic::new(&control_point_id, vec![0]).writable_no_rsp(true),
+            )
+            .add_characteristic(
+                &Characteristic::new(
+                    &BleId::from_standard_characteristic(StandardCharacteristicId::ProtocolMode),
+                    vec![PROTOCOL_MODE_REPORT],
+                )
+                .readable(true)
+                .writable_no_rsp(true),
+            );
+        server.set_service(&service)?;
+
+        let suspended = Arc::new(AtomicBool::new(false));
+        let suspended_ref = suspended.clone();
+        server.on_raw_write(&HID_SERVICE_ID, &control_point_id, move |data| {
+            suspended_ref.store(
+                data.first() == Some(&CONTROL_POINT_SUSPEND),
+                Ordering::SeqCst,
+            )
+        })?;
+
+        Ok(Self {
+            server: server.clone(),
+            battery,
+            appearance: config.appearance(),
+            suspended,
+            keyboard: match config.keyboard {
+                true => Some(HidKeyboard::new(server)?),
+                false => None,
+            },
+            mouse: match config.mouse {
+                true => Some(HidMouse::new(server)?),
+                false => None,
+            },
+            consumer_control: match config.consumer_control {
+                true => Some(HidConsumerControl::new(server)?),
+                false => None,
+            },
+            gamepad: match config.gamepad {
+                true => Some(HidGamepad::new(server)?),
+                false => None,
+            },
+        })
+    }
+
+    /// Creates the advertisement hosts look for when pairing a HID device: the name, the
+    /// appearance of the device and the HID service
+    pub fn advertisement(&self, name: &str) -> AdvertisementBuilder {
+        AdvertisementBuilder::new()
+            .appearance(self.appearance)
+            .service_uuid(&HID_SERVICE_ID)
+            .name(name)
+            .spill_to_scan_response(true)
+    }
+
+    /// Gets the keyboard, or `None` if the device is not a keyboard
+    pub fn keyboard(&mut self) -> Option<&mut HidKeyboard> {
+        self.keyboard.as_mut()
+    }
+
+    /// Gets the mouse, or `None` if the device is not a mouse
+    pub fn mouse(&mut self) -> Option<&mut HidMouse> {
+        self.mouse.as_mut()
+    }
+
+    /// Gets the media keys, or `None` if the device does not have them
+    pub fn consumer_control(&mut self) -> Option<&mut HidConsumerControl> {
+        self.consumer_control.as_mut()
+    }
+
+    /// Gets the gamepad, or `None` if the device is not a gamepad
+    pub fn gamepad(&mut self) -> Option<&mut HidGamepad> {
+        self.gamepad.as_mut()
+    }
+
+    /// Sets the battery level reported to the hosts
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the level is greater than 100.
+    pub fn set_battery_level(&mut self, level: u8) -> Result<(), BleError> {
+        self.battery.set_level(&mut self.server, level)
+    }
+
+    /// Returns whether the host suspended the device, for example because it went to sleep. A
+    /// suspended device can save power, but reports are still sent.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+}
+
+/// Keyboard of a [HidDevice]
+pub struct HidKeyboard {
+    input: InputReport,
+    report: KeyboardReport,
+    leds: Arc<AtomicU8>,
+}
+
+impl HidKeyboard {
+    /// Adds the input and output reports of the keyboard to the HID service
+    fn new(server: &mut BleServer<'_>) -> Result<Self, BleError> {
+        let input = InputReport::new(
+            server,
+            KEYBOARD_REPORT_ID,
+            KeyboardReport::default().to_bytes().len(),
+        )?;
+        let output = report_characteristic(KEYBOARD_REPORT_ID, OUTPUT_REPORT, 1)
+            .readable(true)
+            .readable_enc(true)
+            .writable(true)
+            .writable_no_rsp(true)
+            .writable_enc(true);
+        let leds = Arc::new(AtomicU8::new(0));
+        let leds_ref = leds.clone();
+        server
+            .add_raw_characteristic(&HID_SERVICE_ID, &output)?
+            .lock()
+            .on_write(move |args| {
+                if let Some(byte) = args.recv_data().first() {
+                    leds_ref.store(*byte, Ordering::SeqCst);
+                }
+            });
+        Ok(Self {
+            input,
+            report: KeyboardReport::default(),
+            leds,
+        })
+    }
+
+    /// Presses a key, keeping it pressed until it is released. Up to 6 keys can be pressed at
+    /// the same time, further keys are ignored.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the key can not be typed with the US layout
+    pub fn press(&mut self, key: Key) -> Result<(), BleError> {
+        let (modifiers, usage) = key.usage().ok_or(BleError::InvalidValue)?;
+        self.report.press_with_modifiers(modifiers, usage);
+        self.input.send(&self.report.to_bytes());
+        Ok(())
+    }
+
+    /// Releases a pressed key, and the modifiers it needed. Modifiers pressed with
+    /// [Self::press_modifier] stay pressed.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the key can not be typed with the US layout
+    pub fn release(&mut self, key: Key) -> Result<(), BleError> {
+        let (_, usage) = key.usage().ok_or(BleError::InvalidValue)?;
+        self.report.release(usage);
+        self.input.send(&self.report.to_bytes());
+        Ok(())
+    }
+
+    /// Presses a modifier, keeping it pressed until it is released
+    pub fn press_modifier(&mut self, modifier: Modifier) {
+        self.report.press_modifier(modifier);
+        self.input.send(&self.report.to_bytes());
+    }
+
+    /// Releases a pressed modifier
+    pub fn release_modifier(&mut self, modifier: Modifier) {
+        self.report.release_modifier(modifier);
+        self.input.send(&self.report.to_bytes());
+    }
+
+    /// Releases every key and modifier
+    pub fn release_all(&mut self) {
+        self.report = KeyboardReport::default();
+        self.input.send(&self.report.to_bytes());
+    }
+
+    /// Presses and releases a key
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the key can not be typed with the US layout
+    pub fn write(&mut self, key: Key) -> Result<(), BleError> {
+        self.press(key)?;
+        FreeRtos::delay_ms(TYPING_DELAY_MS);
+        self.release(key)?;
+        FreeRtos::delay_ms(TYPING_DELAY_MS);
+        Ok(())
+    }
+
+    /// Types a text with the US layout, as if each character was written
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If a character can not be typed with the US layout. In that
+    ///   case nothing is typed.
+    pub fn type_str(&mut self, text: &str) -> Result<(), BleError> {
+        if text.chars().any(|c| Key::Char(c).usage().is_none()) {
+            return Err(BleError::InvalidValue);
+        }
+        for c in text.chars() {
+            self.write(Key::Char(c))?;
+        }
+        Ok(())
+    }
+
+    /// Gets the state of the LEDs set by the host
+    pub fn leds(&self) -> KeyboardLeds {
+        KeyboardLeds::from_byte(self.leds.load(Ordering::SeqCst))
+    }
+}
+
+/// Mouse of a [HidDevice]
+pub struct HidMouse {
+    input: InputReport,
+    buttons: u8,
+}
+
+impl HidMouse {
+    /// Adds the input report of the mouse to the HID service
+    fn new(server: &mut BleServer<'_>) -> Result<Self, BleError> {
+        let size = MouseReport::default().to_bytes().len();
+        Ok(Self {
+            input: InputReport::new(server, MOUSE_REPORT_ID, size)?,
+            buttons: 0,
+        })
+    }
+
+    /// Sends a report with the pressed buttons
+    fn send(&self, x: i8, y: i8, wheel: i8) {
+        let report = MouseReport {
+            buttons: self.buttons,
+            x,
+            y,
+            wheel,
+        };
+        self.input.send(&report.to_bytes());
+    }
+
+    /// Moves the pointer relative to its position. Positive values move it right and down.
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        for (x, y) in relative_steps(dx, dy) {
+            self.send(x, y, 0);
+        }
+    }
+
+    /// Scrolls the wheel. Positive values scroll up.
+    pub fn scroll(&mut self, amount: i32) {
+        for (wheel, _) in relative_steps(amount, 0) {
+            self.send(0, 0, wheel);
+        }
+    }
+
+    /// Presses a button, keeping it pressed until it is released
+    pub fn press(&mut self, button: MouseButton) {
+        self.buttons |= button as u8;
+        self.send(0, 0, 0);
+    }
+
+    /// Releases a pressed button
+    pub fn release(&mut self, button: MouseButton) {
+        self.buttons &= !(button as u8);
+        self.send(0, 0, 0);
+    }
+
+    /// Presses and releases a button
+    pub fn click(&mut self, button: MouseButton) {
+        self.press(button);
+        FreeRtos::delay_ms(TYPING_DELAY_MS);
+        self.release(button);
+    }
+}
+
+/// Media keys of a [HidDevice]
+pub struct HidConsumerControl {
+    input: InputReport,
+}
+
+impl HidConsumerControl {
+    /// Adds the input report of the media keys to the HID service
+    fn new(server: &mut BleServer<'_>) -> Result<Self, BleError> {
+        let size = ConsumerKey::Mute.to_bytes().len();
+        Ok(Self {
+            input: InputReport::new(server, CONSUMER_CONTROL_REPORT_ID, size)?,
+        })
+    }
+
+    /// Presses a key, keeping it pressed until it is released. Only one key can be pressed at
+    /// the same time.
+    pub fn press(&mut self, key: ConsumerKey) {
+        self.input.send(&key.to_bytes());
+    }
+
+    /// Releases the pressed key
+    pub fn release(&mut self) {
+        self.input.send(&0_u16.to_le_bytes());
+    }
+
+    /// Presses and releases a key
+    pub fn click(&mut self, key: ConsumerKey) {
+        self.press(key);
+        FreeRtos::delay_ms(TYPING_DELAY_MS);
+        self.release();
+    }
+}
+
+/// Gamepad of a [HidDevice]
+pub struct HidGamepad {
+    input: InputReport,
+    report: GamepadReport,
+}
+
+impl HidGamepad {
+    /// Adds the input report of the gamepad to the HID service
+    fn new(server: &mut BleServer<'_>) -> Result<Self, BleError> {
+        let size = GamepadReport::default().to_bytes().len();
+        Ok(Self {
+            input: InputReport::new(server, GAMEPAD_REPORT_ID, size)?,
+            report: GamepadReport::default(),
+        })
+    }
+
+    /// Sets the position of the X, Y, Z and Rz axes, in that order
+    pub fn set_axes(&mut self, axes: [i8; 4]) {
+        self.report.axes = axes;
+        self.input.send(&self.report.to_bytes());
+    }
+
+    /// Sets the direction of the hat switch
+    pub fn set_hat(&mut self, hat: HatSwitch) {
+        self.report.hat = hat;
+        self.input.send(&self.report.to_bytes());
+    }
+
+    /// Presses a button, keeping it pressed until it is released
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the button is not between 1 and 16
+    pub fn press(&mut self, button: u8) -> Result<(), BleError> {
+        self.report.buttons |= button_mask(button)?;
+        self.input.send(&self.report.to_bytes());
+        Ok(())
+    }
+
+    /// Releases a pressed button
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the button is not between 1 and 16
+    pub fn release(&mut self, button: u8) -> Result<(), BleError> {
+        self.report.buttons &= !button_mask(button)?;
+        self.input.send(&self.report.to_bytes());
+        Ok(())
+    }
+}
+
+/// Gets the bit of a gamepad button in its report
+fn button_mask(button: u8) -> Result<u16, BleError> {
+    if !(1..=GAMEPAD_BUTTONS).contains(&button) {
+        return Err(BleError::InvalidValue);
+    }
+    Ok(1 << (button - 1))
+}
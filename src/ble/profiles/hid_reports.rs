@@ -0,0 +1,683 @@
+use super::{PnpId, VendorIdSource};
+
+pub(crate) const KEYBOARD_REPORT_ID: u8 = 1;
+pub(crate) const CONSUMER_CONTROL_REPORT_ID: u8 = 2;
+pub(crate) const MOUSE_REPORT_ID: u8 = 3;
+pub(crate) const GAMEPAD_REPORT_ID: u8 = 4;
+
+/// Report types used in the Report Reference descriptor
+pub(crate) const INPUT_REPORT: u8 = 1;
+pub(crate) const OUTPUT_REPORT: u8 = 2;
+
+const APPEARANCE_GENERIC_HID: u16 = 0x03C0;
+const APPEARANCE_KEYBOARD: u16 = 0x03C1;
+const APPEARANCE_MOUSE: u16 = 0x03C2;
+const APPEARANCE_GAMEPAD: u16 = 0x03C4;
+
+const USAGE_KEY_A: u8 = 0x04;
+const USAGE_KEY_1: u8 = 0x1E;
+const USAGE_KEY_0: u8 = 0x27;
+const USAGE_KEY_F1: u8 = 0x3A;
+const MAX_FUNCTION_KEY: u8 = 12;
+const MAX_PRESSED_KEYS: usize = 6;
+
+#[rustfmt::skip]
+const KEYBOARD_REPORT_MAP: [u8; 65] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xA1, 0x01, // Collection (Application)
+    0x85, KEYBOARD_REPORT_ID, // Report ID
+    0x05, 0x07, // Usage Page (Keyboard)
+    0x19, 0xE0, // Usage Minimum (Left Control)
+    0x29, 0xE7, // Usage Maximum (Right GUI)
+    0x15, 0x00, // Logical Minimum (0)
+    0x25, 0x01, // Logical Maximum (1)
+    0x75, 0x01, // Report Size (1)
+    0x95, 0x08, // Report Count (8)
+    0x81, 0x02, // Input (Data, Variable, Absolute): Modifiers
+    0x95, 0x01, // Report Count (1)
+    0x75, 0x08, // Report Size (8)
+    0x81, 0x01, // Input (Constant): Reserved byte
+    0x95, 0x05, // Report Count (5)
+    0x75, 0x01, // Report Size (1)
+    0x05, 0x08, // Usage Page (LEDs)
+    0x19, 0x01, // Usage Minimum (Num Lock)
+    0x29, 0x05, // Usage Maximum (Kana)
+    0x91, 0x02, // Output (Data, Variable, Absolute): LEDs
+    0x95, 0x01, // Report Count (1)
+    0x75, 0x03, // Report Size (3)
+    0x91, 0x01, // Output (Constant): Padding
+    0x95, 0x06, // Report Count (6)
+    0x75, 0x08, // Report Size (8)
+    0x15, 0x00, // Logical Minimum (0)
+    0x25, 0x65, // Logical Maximum (101)
+    0x05, 0x07, // Usage Page (Keyboard)
+    0x19, 0x00, // Usage Minimum (0)
+    0x29, 0x65, // Usage Maximum (101)
+    0x81, 0x00, // Input (Data, Array): Pressed keys
+    0xC0, // End Collection
+];
+
+#[rustfmt::skip]
+const CONSUMER_CONTROL_REPORT_MAP: [u8; 25] = [
+    0x05, 0x0C, // Usage Page (Consumer)
+    0x09, 0x01, // Usage (Consumer Control)
+    0xA1, 0x01, // Collection (Application)
+    0x85, CONSUMER_CONTROL_REPORT_ID, // Report ID
+    0x15, 0x00, // Logical Minimum (0)
+    0x26, 0xFF, 0x03, // Logical Maximum (1023)
+    0x19, 0x00, // Usage Minimum (0)
+    0x2A, 0xFF, 0x03, // Usage Maximum (1023)
+    0x75, 0x10, // Report Size (16)
+    0x95, 0x01, // Report Count (1)
+    0x81, 0x00, // Input (Data, Array): Pressed control
+    0xC0, // End Collection
+];
+
+#[rustfmt::skip]
+const MOUSE_REPORT_MAP: [u8; 54] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x02, // Usage (Mouse)
+    0xA1, 0x01, // Collection (Application)
+    0x85, MOUSE_REPORT_ID, // Report ID
+    0x09, 0x01, // Usage (Pointer)
+    0xA1, 0x00, // Collection (Physical)
+    0x05, 0x09, // Usage Page (Buttons)
+    0x19, 0x01, // Usage Minimum (1)
+    0x29, 0x05, // Usage Maximum (5)
+    0x15, 0x00, // Logical Minimum (0)
+    0x25, 0x01, // Logical Maximum (1)
+    0x95, 0x05, // Report Count (5)
+    0x75, 0x01, // Report Size (1)
+    0x81, 0x02, // Input (Data, Variable, Absolute): Buttons
+    0x95, 0x01, // Report Count (1)
+    0x75, 0x03, // Report Size (3)
+    0x81, 0x01, // Input (Constant): Padding
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x30, // Usage (X)
+    0x09, 0x31, // Usage (Y)
+    0x09, 0x38, // Usage (Wheel)
+    0x15, 0x81, // Logical Minimum (-127)
+    0x25, 0x7F, // Logical Maximum (127)
+    0x75, 0x08, // Report Size (8)
+    0x95, 0x03, // Report Count (3)
+    0x81, 0x06, // Input (Data, Variable, Relative): Movement
+    0xC0, // End Collection
+    0xC0, // End Collection
+];
+
+#[rustfmt::skip]
+const GAMEPAD_REPORT_MAP: [u8; 63] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x05, // Usage (Gamepad)
+    0xA1, 0x01, // Collection (Application)
+    0x85, GAMEPAD_REPORT_ID, // Report ID
+    0x05, 0x09, // Usage Page (Buttons)
+    0x19, 0x01, // Usage Minimum (1)
+    0x29, 0x10, // Usage Maximum (16)
+    0x15, 0x00, // Logical Minimum (0)
+    0x25, 0x01, // Logical Maximum (1)
+    0x75, 0x01, // Report Size (1)
+    0x95, 0x10, // Report Count (16)
+    0x81, 0x02, // Input (Data, Variable, Absolute): Buttons
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x30, // Usage (X)
+    0x09, 0x31, // Usage (Y)
+    0x09, 0x32, // Usage (Z)
+    0x09, 0x35, // Usage (Rz)
+    0x15, 0x81, // Logical Minimum (-127)
+    0x25, 0x7F, // Logical Maximum (127)
+    0x75, 0x08, // Report Size (8)
+    0x95, 0x04, // Report Count (4)
+    0x81, 0x02, // Input (Data, Variable, Absolute): Axes
+    0x09, 0x39, // Usage (Hat Switch)
+    0x15, 0x00, // Logical Minimum (0)
+    0x25, 0x07, // Logical Maximum (7)
+    0x75, 0x04, // Report Size (4)
+    0x95, 0x01, // Report Count (1)
+    0x81, 0x42, // Input (Data, Variable, Absolute, Null State): Hat switch
+    0x75, 0x04, // Report Size (4)
+    0x95, 0x01, // Report Count (1)
+    0x81, 0x01, // Input (Constant): Padding
+    0xC0, // End Collection
+];
+
+/// Reports a HID device offers, together with the information the host uses to identify it.
+/// Every report is disabled by default.
+///
+/// - `keyboard`: A keyboard with modifiers, up to 6 simultaneous keys and LEDs
+/// - `mouse`: A mouse with 5 buttons, movement and a wheel
+/// - `consumer_control`: Media keys, like volume or play and pause
+/// - `gamepad`: A gamepad with 16 buttons, 4 axes and a hat switch
+/// - `pnp_id`: The Plug and Play identification of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HidConfig {
+    pub(crate) keyboard: bool,
+    pub(crate) mouse: bool,
+    pub(crate) consumer_control: bool,
+    pub(crate) gamepad: bool,
+    pub(crate) pnp_id: PnpId,
+}
+
+impl Default for HidConfig {
+    fn default() -> Self {
+        Self {
+            keyboard: false,
+            mouse: false,
+            consumer_control: false,
+            gamepad: false,
+            pnp_id: PnpId {
+                vendor_id_source: VendorIdSource::BluetoothSig,
+                vendor_id: 0x02E5,
+                product_id: 0x0001,
+                product_version: 0x0100,
+            },
+        }
+    }
+}
+
+impl HidConfig {
+    /// Creates a new HidConfig without any report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the device is a keyboard
+    pub fn keyboard(mut self, value: bool) -> Self {
+        self.keyboard = value;
+        self
+    }
+
+    /// Sets whether the device is a mouse
+    pub fn mouse(mut self, value: bool) -> Self {
+        self.mouse = value;
+        self
+    }
+
+    /// Sets whether the device has media keys
+    pub fn consumer_control(mut self, value: bool) -> Self {
+        self.consumer_control = value;
+        self
+    }
+
+    /// Sets whether the device is a gamepad
+    pub fn gamepad(mut self, value: bool) -> Self {
+        self.gamepad = value;
+        self
+    }
+
+    /// Sets the Plug and Play identification of the device, used by some hosts to choose a driver
+    pub fn pnp_id(mut self, pnp_id: PnpId) -> Self {
+        self.pnp_id = pnp_id;
+        self
+    }
+
+    /// Generates the report map describing every enabled report
+    pub fn report_map(&self) -> Vec<u8> {
+        let maps: [(bool, &[u8]); 4] = [
+            (self.keyboard, &KEYBOARD_REPORT_MAP),
+            (self.consumer_control, &CONSUMER_CONTROL_REPORT_MAP),
+            (self.mouse, &MOUSE_REPORT_MAP),
+            (self.gamepad, &GAMEPAD_REPORT_MAP),
+        ];
+        maps.iter()
+            .filter(|(enabled, _)| *enabled)
+            .flat_map(|(_, map)| map.iter().copied())
+            .collect()
+    }
+
+    /// Gets the appearance to advertise, that of the main kind of device enabled
+    pub fn appearance(&self) -> u16 {
+        if self.keyboard {
+            APPEARANCE_KEYBOARD
+        } else if self.mouse {
+            APPEARANCE_MOUSE
+        } else if self.gamepad {
+            APPEARANCE_GAMEPAD
+        } else {
+            APPEARANCE_GENERIC_HID
+        }
+    }
+}
+
+/// Modifier keys of a keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    LeftCtrl = 0x01,
+    LeftShift = 0x02,
+    LeftAlt = 0x04,
+    LeftGui = 0x08,
+    RightCtrl = 0x10,
+    RightShift = 0x20,
+    RightAlt = 0x40,
+    RightGui = 0x80,
+}
+
+/// Keys of a keyboard with the US layout. `Char` covers the printable ASCII characters, the
+/// space, the tab and the new line, pressing shift when needed. `F` goes from F1 to F12.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    CapsLock,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Right,
+    Left,
+    Down,
+    Up,
+    F(u8),
+}
+
+impl Key {
+    /// Gets the modifiers and the usage id of the key
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the modifiers and usage id, or `None` if the key has no usage id
+    pub fn usage(&self) -> Option<(u8, u8)> {
+        let code = match self {
+            Key::Char(c) => return char_usage(*c),
+            Key::Enter => 0x28,
+            Key::Escape => 0x29,
+            Key::Backspace => 0x2A,
+            Key::Tab => 0x2B,
+            Key::CapsLock => 0x39,
+            Key::Insert => 0x49,
+            Key::Home => 0x4A,
+            Key::PageUp => 0x4B,
+            Key::Delete => 0x4C,
+            Key::End => 0x4D,
+            Key::PageDown => 0x4E,
+            Key::Right => 0x4F,
+            Key::Left => 0x50,
+            Key::Down => 0x51,
+            Key::Up => 0x52,
+            Key::F(number) if (1..=MAX_FUNCTION_KEY).contains(number) => USAGE_KEY_F1 + number - 1,
+            Key::F(_) => return None,
+        };
+        Some((0, code))
+    }
+}
+
+/// Gets the modifiers and usage id that type a character with the US layout
+fn char_usage(c: char) -> Option<(u8, u8)> {
+    const SHIFT: u8 = Modifier::LeftShift as u8;
+    let usage = match c {
+        'a'..='z' => (0, USAGE_KEY_A + (c as u8 - b'a')),
+        'A'..='Z' => (SHIFT, USAGE_KEY_A + (c as u8 - b'A')),
+        '1'..='9' => (0, USAGE_KEY_1 + (c as u8 - b'1')),
+        '0' => (0, USAGE_KEY_0),
+        '\n' => (0, 0x28),
+        '\t' => (0, 0x2B),
+        ' ' => (0, 0x2C),
+        '!' => (SHIFT, 0x1E),
+        '@' => (SHIFT, 0x1F),
+        '#' => (SHIFT, 0x20),
+        '$' => (SHIFT, 0x21),
+        '%' => (SHIFT, 0x22),
+        '^' => (SHIFT, 0x23),
+        '&' => (SHIFT, 0x24),
+        '*' => (SHIFT, 0x25),
+        '(' => (SHIFT, 0x26),
+        ')' => (SHIFT, 0x27),
+        '-' => (0, 0x2D),
+        '_' => (SHIFT, 0x2D),
+        '=' => (0, 0x2E),
+        '+' => (SHIFT, 0x2E),
+        '[' => (0, 0x2F),
+        '{' => (SHIFT, 0x2F),
+        ']' => (0, 0x30),
+        '}' => (SHIFT, 0x30),
+        '\\' => (0, 0x31),
+        '|' => (SHIFT, 0x31),
+        ';' => (0, 0x33),
+        ':' => (SHIFT, 0x33),
+        '\'' => (0, 0x34),
+        '"' => (SHIFT, 0x34),
+        '`' => (0, 0x35),
+        '~' => (SHIFT, 0x35),
+        ',' => (0, 0x36),
+        '<' => (SHIFT, 0x36),
+        '.' => (0, 0x37),
+        '>' => (SHIFT, 0x37),
+        '/' => (0, 0x38),
+        '?' => (SHIFT, 0x38),
+        _ => return None,
+    };
+    Some(usage)
+}
+
+/// Input report of a keyboard: the pressed modifiers and up to 6 pressed keys
+/// - `modifiers`: The modifiers pressed by themselves, like with [KeyboardReport::press_modifier].
+/// - `keys`: The usage ids of the pressed keys, 0 for a free slot.
+/// - `key_modifiers`: The modifiers needed by each pressed key, like shift for an uppercase
+///   letter. They are released with their key, without releasing the ones in `modifiers`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardReport {
+    pub modifiers: u8,
+    pub keys: [u8; MAX_PRESSED_KEYS],
+    key_modifiers: [u8; MAX_PRESSED_KEYS],
+}
+
+impl KeyboardReport {
+    /// Adds a pressed key. If 6 keys are already pressed the key is ignored, as most keyboards do.
+    pub fn press(&mut self, usage: u8) {
+        self.press_with_modifiers(0, usage)
+    }
+
+    /// Adds a pressed key that needs some modifiers, which stay pressed until the key is
+    /// released. If 6 keys are already pressed the key is ignored, as most keyboards do.
+    ///
+    /// # Arguments
+    ///
+    /// - `modifiers`: The modifiers the key needs, as returned by [Key::usage]
+    /// - `usage`: The usage id of the key
+    pub fn press_with_modifiers(&mut self, modifiers: u8, usage: u8) {
+        if self.keys.contains(&usage) {
+            return;
+        }
+        if let Some(free) = self.keys.iter().position(|key| *key == 0) {
+            self.keys[free] = usage;
+            self.key_modifiers[free] = modifiers;
+        }
+    }
+
+    /// Removes a pressed key, and the modifiers it needed
+    pub fn release(&mut self, usage: u8) {
+        for (key, modifiers) in self.keys.iter_mut().zip(self.key_modifiers.iter_mut()) {
+            if *key == usage {
+                *key = 0;
+                *modifiers = 0;
+            }
+        }
+    }
+
+    /// Presses a modifier by itself, keeping it pressed until it is released
+    pub fn press_modifier(&mut self, modifier: Modifier) {
+        self.modifiers |= modifier as u8;
+    }
+
+    /// Releases a modifier pressed by itself. It stays pressed while a key that needs it is
+    /// pressed.
+    pub fn release_modifier(&mut self, modifier: Modifier) {
+        self.modifiers &= !(modifier as u8);
+    }
+
+    /// Encodes the report as the keyboard report map defines
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let modifiers = self
+            .key_modifiers
+            .iter()
+            .fold(self.modifiers, |all, modifiers| all | modifiers);
+        let mut bytes = vec![modifiers, 0];
+        bytes.extend(self.keys);
+        bytes
+    }
+}
+
+/// State of the LEDs of a keyboard, set by the host through the output report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardLeds {
+    pub num_lock: bool,
+    pub caps_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl KeyboardLeds {
+    /// Decodes the LEDs from the output report of a keyboard
+    pub fn from_byte(byte: u8) -> Self {
+        Self {
+            num_lock: byte & 0x01 != 0,
+            caps_lock: byte & 0x02 != 0,
+            scroll_lock: byte & 0x04 != 0,
+        }
+    }
+}
+
+/// Buttons of a mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left = 0x01,
+    Right = 0x02,
+    Middle = 0x04,
+    Back = 0x08,
+    Forward = 0x10,
+}
+
+/// Input report of a mouse: the pressed buttons and the relative movement since the last report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseReport {
+    pub buttons: u8,
+    pub x: i8,
+    pub y: i8,
+    pub wheel: i8,
+}
+
+impl MouseReport {
+    /// Encodes the report as the mouse report map defines
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![self.buttons, self.x as u8, self.y as u8, self.wheel as u8]
+    }
+}
+
+/// Splits a relative movement in the steps that fit in mouse reports, whose movement can not
+/// exceed 127 in each axis
+///
+/// # Returns
+///
+/// The steps of the movement, with at least one step
+pub(crate) fn relative_steps(mut dx: i32, mut dy: i32) -> Vec<(i8, i8)> {
+    let mut steps = vec![];
+    loop {
+        let step_x = dx.clamp(-(i8::MAX as i32), i8::MAX as i32);
+        let step_y = dy.clamp(-(i8::MAX as i32), i8::MAX as i32);
+        steps.push((step_x as i8, step_y as i8));
+        dx -= step_x;
+        dy -= step_y;
+        if dx == 0 && dy == 0 {
+            return steps;
+        }
+    }
+}
+
+/// Media keys of a consumer control device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumerKey {
+    PlayPause = 0xCD,
+    NextTrack = 0xB5,
+    PreviousTrack = 0xB6,
+    Stop = 0xB7,
+    Mute = 0xE2,
+    VolumeUp = 0xE9,
+    VolumeDown = 0xEA,
+}
+
+impl ConsumerKey {
+    /// Encodes the input report of a consumer control device with the key pressed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        (*self as u16).to_le_bytes().to_vec()
+    }
+}
+
+/// Direction of the hat switch of a gamepad
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HatSwitch {
+    Up = 0,
+    UpRight = 1,
+    Right = 2,
+    DownRight = 3,
+    Down = 4,
+    DownLeft = 5,
+    Left = 6,
+    UpLeft = 7,
+    #[default]
+    Centered = 8,
+}
+
+/// Input report of a gamepad: its 16 buttons, the X, Y, Z and Rz axes and the hat switch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GamepadReport {
+    pub buttons: u16,
+    pub axes: [i8; 4],
+    pub hat: HatSwitch,
+}
+
+impl GamepadReport {
+    /// Encodes the report as the gamepad report map defines
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.buttons.to_le_bytes().to_vec();
+        bytes.extend(self.axes.map(|axis| axis as u8));
+        bytes.push(self.hat as u8);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Gets the report ids of a report map, checking its collections are balanced
+    fn report_ids(map: &[u8]) -> Vec<u8> {
+        let mut ids = vec![];
+        let mut depth = 0;
+        let mut i = 0;
+        while i < map.len() {
+            let prefix = map[i];
+            let size = match prefix & 0x03 {
+                3 => 4,
+                size => size as usize,
+            };
+            match prefix & 0xFC {
+                0xA0 => depth += 1,
+                0xC0 => depth -= 1,
+                0x84 => ids.push(map[i + 1]),
+                _ => {}
+            }
+            assert!(depth >= 0);
+            i += 1 + size;
+        }
+        assert_eq!(i, map.len());
+        assert_eq!(depth, 0);
+        ids
+    }
+
+    #[test]
+    fn hid_reports_01_report_map_of_enabled_reports() {
+        assert!(HidConfig::new().report_map().is_empty());
+        let keyboard = HidConfig::new().keyboard(true);
+        assert_eq!(report_ids(&keyboard.report_map()), vec![KEYBOARD_REPORT_ID]);
+        assert_eq!(keyboard.appearance(), APPEARANCE_KEYBOARD);
+        let all = HidConfig::new()
+            .keyboard(true)
+            .mouse(true)
+            .consumer_control(true)
+            .gamepad(true);
+        assert_eq!(
+            report_ids(&all.report_map()),
+            vec![
+                KEYBOARD_REPORT_ID,
+                CONSUMER_CONTROL_REPORT_ID,
+                MOUSE_REPORT_ID,
+                GAMEPAD_REPORT_ID
+            ]
+        );
+        assert_eq!(
+            HidConfig::new().gamepad(true).appearance(),
+            APPEARANCE_GAMEPAD
+        );
+    }
+
+    #[test]
+    fn hid_reports_02_char_usages() {
+        assert_eq!(Key::Char('a').usage(), Some((0, 0x04)));
+        assert_eq!(Key::Char('Z').usage(), Some((0x02, 0x1D)));
+        assert_eq!(Key::Char('1').usage(), Some((0, 0x1E)));
+        assert_eq!(Key::Char('0').usage(), Some((0, 0x27)));
+        assert_eq!(Key::Char('?').usage(), Some((0x02, 0x38)));
+        assert_eq!(Key::Char('\n').usage(), Key::Enter.usage());
+        assert_eq!(Key::Char('ñ').usage(), None);
+        assert_eq!(Key::F(12).usage(), Some((0, 0x45)));
+        assert_eq!(Key::F(13).usage(), None);
+    }
+
+    #[test]
+    fn hid_reports_03_keyboard_report() {
+        let mut report = KeyboardReport::default();
+        report.modifiers = Modifier::LeftCtrl as u8;
+        report.press(0x06);
+        report.press(0x06);
+        report.press(0x19);
+        assert_eq!(report.to_bytes(), vec![0x01, 0, 0x06, 0x19, 0, 0, 0, 0]);
+        report.release(0x06);
+        assert_eq!(report.keys, [0, 0x19, 0, 0, 0, 0]);
+        for usage in 0x04..0x0C {
+            report.press(usage);
+        }
+        assert_eq!(report.keys, [0x04, 0x19, 0x05, 0x06, 0x07, 0x08]);
+    }
+
+    #[test]
+    fn hid_reports_04_mouse_report_and_steps() {
+        let report = MouseReport {
+            buttons: MouseButton::Left as u8,
+            x: -1,
+            y: 10,
+            wheel: 0,
+        };
+        assert_eq!(report.to_bytes(), vec![0x01, 0xFF, 0x0A, 0x00]);
+        assert_eq!(relative_steps(0, 0), vec![(0, 0)]);
+        assert_eq!(
+            relative_steps(300, -20),
+            vec![(127, -20), (127, 0), (46, 0)]
+        );
+    }
+
+    #[test]
+    fn hid_reports_05_gamepad_and_consumer_reports() {
+        let report = GamepadReport {
+            buttons: 0x0102,
+            axes: [-127, 0, 127, -1],
+            hat: HatSwitch::Left,
+        };
+        assert_eq!(
+            report.to_bytes(),
+            vec![0x02, 0x01, 0x81, 0x00, 0x7F, 0xFF, 0x06]
+        );
+        assert_eq!(GamepadReport::default().to_bytes()[6], 8);
+        assert_eq!(ConsumerKey::VolumeUp.to_bytes(), vec![0xE9, 0x00]);
+        assert_eq!(
+            KeyboardLeds::from_byte(0x03),
+            KeyboardLeds {
+                num_lock: true,
+                caps_lock: true,
+                scroll_lock: false
+            }
+        );
+    }
+
+    #[test]
+    fn hid_reports_06_shifted_key_keeps_pressed_shift() {
+        let mut report = KeyboardReport::default();
+        report.press_modifier(Modifier::LeftShift);
+        let (modifiers, usage) = Key::Char('A').usage().unwrap();
+        report.press_with_modifiers(modifiers, usage);
+        assert_eq!(report.to_bytes(), vec![0x02, 0, 0x04, 0, 0, 0, 0, 0]);
+        report.release(usage);
+        assert_eq!(report.to_bytes(), vec![0x02, 0, 0, 0, 0, 0, 0, 0]);
+
+        report.release_modifier(Modifier::LeftShift);
+        report.press_with_modifiers(modifiers, usage);
+        report.press(0x05);
+        assert_eq!(report.to_bytes()[0], 0x02);
+        report.release(usage);
+        assert_eq!(report.to_bytes(), vec![0, 0, 0, 0x05, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,13 @@
+mod battery;
+mod device_information;
+mod environmental_sensing;
+mod heart_rate;
+mod hid;
+mod hid_reports;
+
+pub use battery::*;
+pub use device_information::*;
+pub use environmental_sensing::*;
+pub use heart_rate::*;
+pub use hid::*;
+pub use hid_reports::*;
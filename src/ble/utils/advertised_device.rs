@@ -3,7 +3,7 @@ use esp32_nimble::{
     BLEAddress, BLEAdvertisedDevice,
 };
 
-use super::BleId;
+use super::{BleId, EddystoneFrame, IBeacon, EDDYSTONE_SERVICE_UUID};
 
 #[derive(Debug)]
 pub struct BleAdvertisedDevice {
@@ -113,6 +113,26 @@ impl BleAdvertisedDevice {
         self.device.get_manufacture_data()
     }
 
+    /// Gets the iBeacon frame the device is advertising
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the IBeacon, or `None` if the manufacture data is not an iBeacon frame
+    pub fn ibeacon(&self) -> Option<IBeacon> {
+        IBeacon::from_manufacturer_data(self.get_manufacture_data()?)
+    }
+
+    /// Gets the Eddystone frame the device is advertising
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the EddystoneFrame, or `None` if the device is not advertising a valid
+    /// Eddystone frame
+    pub fn eddystone(&self) -> Option<EddystoneFrame> {
+        let (_, data) = self.get_service_data(BleId::FromUuid16(EDDYSTONE_SERVICE_UUID))?;
+        EddystoneFrame::from_service_data(data)
+    }
+
     /// Returns wether or not a device is connectable acording to the advertisement type
     pub fn is_connectable(&self) -> bool {
         adv_type_is_connectable(&self.adv_type())
@@ -127,6 +147,15 @@ impl From<&BLEAdvertisedDevice> for BleAdvertisedDevice {
     }
 }
 
+/// Key of a device address in maps. [BLEAddress] can not be hashed, and its equality ignores the
+/// address type, so a public and a random address with the same bytes would be mixed.
+pub(crate) type AddressKey = ([u8; 6], u8);
+
+/// Gets the [AddressKey] of an address, made of its bytes and its type
+pub(crate) fn address_key(address: &BLEAddress) -> AddressKey {
+    (address.val(), address.addr_type() as u8)
+}
+
 /// Returns wether or not a device is connectable acording to the advertisement type
 fn adv_type_is_connectable(adv_type: &AdvType) -> bool {
     match adv_type {
@@ -0,0 +1,523 @@
+use esp32_nimble::{utilities::mutex::Mutex, BLEAdvertising, BLEDevice};
+
+use super::{BleError, BleId, MAX_EXTENDED_ADVERTISEMENT_SIZE};
+
+/// Maximum size of a legacy advertisement or scan response
+pub const MAX_ADVERTISEMENT_SIZE: usize = 31;
+
+const AD_FLAGS: u8 = 0x01;
+const AD_COMPLETE_UUID16_LIST: u8 = 0x03;
+const AD_COMPLETE_UUID32_LIST: u8 = 0x05;
+const AD_COMPLETE_UUID128_LIST: u8 = 0x07;
+const AD_SHORTENED_NAME: u8 = 0x08;
+const AD_COMPLETE_NAME: u8 = 0x09;
+const AD_TX_POWER_LEVEL: u8 = 0x0A;
+const AD_SERVICE_DATA_UUID16: u8 = 0x16;
+const AD_APPEARANCE: u8 = 0x19;
+const AD_SERVICE_DATA_UUID32: u8 = 0x20;
+const AD_SERVICE_DATA_UUID128: u8 = 0x21;
+const AD_MANUFACTURER_DATA: u8 = 0xFF;
+
+/// LE General Discoverable Mode and BR/EDR Not Supported
+const DEFAULT_FLAGS: u8 = 0x06;
+
+/// Each AD structure has a length byte and a type byte before its data
+const AD_HEADER_SIZE: usize = 2;
+
+/// An AD structure: a piece of information inside an advertisement
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AdStructure {
+    ad_type: u8,
+    data: Vec<u8>,
+}
+
+impl AdStructure {
+    fn encoded_size(&self) -> usize {
+        AD_HEADER_SIZE + self.data.len()
+    }
+
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push((self.data.len() + 1) as u8);
+        bytes.push(self.ad_type);
+        bytes.extend(&self.data);
+    }
+}
+
+/// The encoded payloads of an advertisement, as they are sent over the air.
+/// - `advertisement`: The data sent in every advertising packet.
+/// - `scan_response`: The data sent to clients that request it when scanning actively. Empty
+///   if there is no scan response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdvertisementData {
+    pub advertisement: Vec<u8>,
+    pub scan_response: Vec<u8>,
+}
+
+/// Composes the AD structures of an advertisement, validating it fits in the 31 bytes of a legacy
+/// advertisement. Structures are encoded in the order they were added, with the flags first.
+/// Service uuids are grouped in a single list per uuid size.
+///
+/// If the structures do not fit, they can be spilled into the scan response with
+/// [Self::spill_to_scan_response].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisementBuilder {
+    flags: Option<u8>,
+    structures: Vec<AdStructure>,
+    uuids16: Vec<u16>,
+    uuids32: Vec<u32>,
+    uuids128: Vec<[u8; 16]>,
+    scan_response: Vec<AdStructure>,
+    spill: bool,
+}
+
+impl Default for AdvertisementBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdvertisementBuilder {
+    /// Creates a new AdvertisementBuilder with the general discoverable flags and no other data
+    pub fn new() -> Self {
+        Self {
+            flags: Some(DEFAULT_FLAGS),
+            structures: Vec::new(),
+            uuids16: Vec::new(),
+            uuids32: Vec::new(),
+            uuids128: Vec::new(),
+            scan_response: Vec::new(),
+            spill: false,
+        }
+    }
+
+    /// Sets the flags of the advertisement, or removes them if `None`
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn flags(mut self, flags: Option<u8>) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Adds the complete name of the device
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn name(mut self, name: &str) -> Self {
+        self.structures
+            .push(ad_structure(AD_COMPLETE_NAME, name.as_bytes()));
+        self
+    }
+
+    /// Adds the name of the device truncated to a maximum amount of bytes, so clients know it is
+    /// not the complete name. If the name is not longer than the maximum it is added complete.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the device
+    /// - `max_len`: The maximum amount of bytes of the name
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn shortened_name(mut self, name: &str, max_len: usize) -> Self {
+        if name.len() <= max_len {
+            return self.name(name);
+        }
+        let mut end = max_len;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.structures
+            .push(ad_structure(AD_SHORTENED_NAME, &name.as_bytes()[..end]));
+        self
+    }
+
+    /// Adds a service uuid to the list of services of the device. The uuid is encoded in its
+    /// shortest form.
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn service_uuid(mut self, id: &BleId) -> Self {
+        match id.shortest() {
+            BleId::FromUuid16(uuid) if !self.uuids16.contains(&uuid) => self.uuids16.push(uuid),
+            BleId::FromUuid32(uuid) if !self.uuids32.contains(&uuid) => self.uuids32.push(uuid),
+            BleId::FromUuid128(uuid) if !self.uuids128.contains(&uuid) => self.uuids128.push(uuid),
+            _ => {}
+        }
+        self
+    }
+
+    /// Adds the data of a service. The uuid is encoded in its shortest form.
+    ///
+    /// # Arguments
+    ///
+    /// - `id`: The BleId of the service
+    /// - `data`: The data of the service
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn service_data(mut self, id: &BleId, data: &[u8]) -> Self {
+        let structure = match id.shortest() {
+            BleId::FromUuid16(uuid) => {
+                let mut bytes = uuid.to_le_bytes().to_vec();
+                bytes.extend(data);
+                ad_structure(AD_SERVICE_DATA_UUID16, &bytes)
+            }
+            BleId::FromUuid32(uuid) => {
+                let mut bytes = uuid.to_le_bytes().to_vec();
+                bytes.extend(data);
+                ad_structure(AD_SERVICE_DATA_UUID32, &bytes)
+            }
+            BleId::FromUuid128(uuid) => {
+                let mut bytes = uuid.to_vec();
+                bytes.extend(data);
+                ad_structure(AD_SERVICE_DATA_UUID128, &bytes)
+            }
+        };
+        self.structures.push(structure);
+        self
+    }
+
+    /// Adds manufacturer specific data
+    ///
+    /// # Arguments
+    ///
+    /// - `company_id`: The company identifier assigned by the Bluetooth SIG
+    /// - `data`: The data, its format is defined by the company
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn manufacturer_data(mut self, company_id: u16, data: &[u8]) -> Self {
+        let mut bytes = company_id.to_le_bytes().to_vec();
+        bytes.extend(data);
+        self.structures
+            .push(ad_structure(AD_MANUFACTURER_DATA, &bytes));
+        self
+    }
+
+    /// Adds the appearance of the device, a value assigned by the Bluetooth SIG that clients use to
+    /// show an icon
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn appearance(mut self, appearance: u16) -> Self {
+        self.structures
+            .push(ad_structure(AD_APPEARANCE, &appearance.to_le_bytes()));
+        self
+    }
+
+    /// Adds the tx power level in dBm, so clients can estimate the path loss
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn tx_power_level(mut self, dbm: i8) -> Self {
+        self.structures
+            .push(ad_structure(AD_TX_POWER_LEVEL, &[dbm as u8]));
+        self
+    }
+
+    /// Sets the data sent in the scan response. The structures of the scan response are placed
+    /// before any spilled structure. Flags are never part of a scan response.
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn scan_response(mut self, scan_response: AdvertisementBuilder) -> Self {
+        self.scan_response = scan_response.all_structures(false);
+        self
+    }
+
+    /// Sets whether the structures that do not fit in the advertisement are moved to the scan
+    /// response instead of failing
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisementBuilder itself
+    pub fn spill_to_scan_response(mut self, value: bool) -> Self {
+        self.spill = value;
+        self
+    }
+
+    /// Gets the size the advertisement would have if every structure was placed in it
+    pub fn encoded_size(&self) -> usize {
+        self.all_structures(true)
+            .iter()
+            .map(AdStructure::encoded_size)
+            .sum()
+    }
+
+    /// Encodes the advertisement and the scan response
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the AdvertisementData, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the structures do not fit in the advertisement,
+    ///   or in the scan response when spilling them.
+    pub fn build(&self) -> Result<AdvertisementData, BleError> {
+        let mut data = AdvertisementData::default();
+        for structure in &self.scan_response {
+            append_if_fits(&mut data.scan_response, structure, MAX_ADVERTISEMENT_SIZE)?;
+        }
+        for structure in self.all_structures(true) {
+            if append_if_fits(&mut data.advertisement, &structure, MAX_ADVERTISEMENT_SIZE).is_err()
+            {
+                if !self.spill {
+                    return Err(BleError::AdvertisementDoesNotFit);
+                }
+                append_if_fits(&mut data.scan_response, &structure, MAX_ADVERTISEMENT_SIZE)?;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Encodes every structure in a single payload for an extended advertising set, which fits
+    /// up to [MAX_EXTENDED_ADVERTISEMENT_SIZE] bytes. The scan response structures are ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the encoded payload, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the structures do not fit in the payload, or a
+    ///   single structure is longer than 254 bytes.
+    pub fn build_extended(&self) -> Result<Vec<u8>, BleError> {
+        let mut payload = Vec::new();
+        for structure in self.all_structures(true) {
+            append_if_fits(&mut payload, &structure, MAX_EXTENDED_ADVERTISEMENT_SIZE)?;
+        }
+        Ok(payload)
+    }
+
+    /// Gets every structure of the advertisement in the order they are encoded
+    fn all_structures(&self, with_flags: bool) -> Vec<AdStructure> {
+        let mut structures = Vec::new();
+        if let (true, Some(flags)) = (with_flags, self.flags) {
+            structures.push(ad_structure(AD_FLAGS, &[flags]));
+        }
+        if !self.uuids16.is_empty() {
+            let bytes: Vec<u8> = self.uuids16.iter().flat_map(|u| u.to_le_bytes()).collect();
+            structures.push(ad_structure(AD_COMPLETE_UUID16_LIST, &bytes));
+        }
+        if !self.uuids32.is_empty() {
+            let bytes: Vec<u8> = self.uuids32.iter().flat_map(|u| u.to_le_bytes()).collect();
+            structures.push(ad_structure(AD_COMPLETE_UUID32_LIST, &bytes));
+        }
+        if !self.uuids128.is_empty() {
+            let bytes: Vec<u8> = self.uuids128.concat();
+            structures.push(ad_structure(AD_COMPLETE_UUID128_LIST, &bytes));
+        }
+        structures.extend(self.structures.iter().cloned());
+        structures
+    }
+}
+
+fn ad_structure(ad_type: u8, data: &[u8]) -> AdStructure {
+    AdStructure {
+        ad_type,
+        data: data.to_vec(),
+    }
+}
+
+/// Appends the structure to the payload if there is enough space left
+///
+/// # Errors
+///
+/// - `BleError::AdvertisementDoesNotFit`: If the payload would exceed `max_size`, or the
+///   structure is too long for its length byte
+fn append_if_fits(
+    payload: &mut Vec<u8>,
+    structure: &AdStructure,
+    max_size: usize,
+) -> Result<(), BleError> {
+    if payload.len() + structure.encoded_size() > max_size
+        || structure.data.len() >= u8::MAX as usize
+    {
+        return Err(BleError::AdvertisementDoesNotFit);
+    }
+    structure.encode_into(payload);
+    Ok(())
+}
+
+/// Gets the legacy advertising of the device, used by the [crate::ble::BleServer] and the
+/// [crate::ble::BleBeacon]
+///
+/// # Errors
+///
+/// - `BleError::LegacyAdvertisingUnavailable`: If `CONFIG_BT_NIMBLE_EXT_ADV` is enabled, since
+///   NimBLE only advertises through extended advertising sets then
+#[cfg(not(esp_idf_bt_nimble_ext_adv))]
+pub(crate) fn legacy_advertising(
+    ble_device: &BLEDevice,
+) -> Result<&'static Mutex<BLEAdvertising>, BleError> {
+    Ok(ble_device.get_advertising())
+}
+
+/// Gets the legacy advertising of the device, used by the [crate::ble::BleServer] and the
+/// [crate::ble::BleBeacon]
+///
+/// # Errors
+///
+/// - `BleError::LegacyAdvertisingUnavailable`: If `CONFIG_BT_NIMBLE_EXT_ADV` is enabled, since
+///   NimBLE only advertises through extended advertising sets then
+#[cfg(esp_idf_bt_nimble_ext_adv)]
+pub(crate) fn legacy_advertising(
+    _ble_device: &BLEDevice,
+) -> Result<&'static Mutex<BLEAdvertising>, BleError> {
+    Err(BleError::LegacyAdvertisingUnavailable)
+}
+
+/// Sets the encoded advertisement and scan response on the advertising of the device
+///
+/// # Errors
+///
+/// - `BleError::AdvertisementError`: If the advertising operation failed
+pub(crate) fn set_raw_advertising_data(
+    ble_adv: &Mutex<BLEAdvertising>,
+    data: &AdvertisementData,
+) -> Result<(), BleError> {
+    let mut ble_adv = ble_adv.lock();
+    ble_adv
+        .set_raw_data(&data.advertisement)
+        .map_err(|_| BleError::AdvertisementError)?;
+    ble_adv.scan_response(!data.scan_response.is_empty());
+    if !data.scan_response.is_empty() {
+        ble_adv
+            .set_raw_scan_response_data(&data.scan_response)
+            .map_err(|_| BleError::AdvertisementError)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn advertisement_builder_01_encodes_structures_in_order() {
+        let data = AdvertisementBuilder::new()
+            .service_uuid(&BleId::FromUuid16(0x180F))
+            .service_uuid(&BleId::FromUuid16(0x180A))
+            .name("esp")
+            .tx_power_level(-4)
+            .build()
+            .unwrap();
+        assert_eq!(
+            data.advertisement,
+            vec![
+                0x02, 0x01, 0x06, 0x05, 0x03, 0x0F, 0x18, 0x0A, 0x18, 0x04, 0x09, b'e', b's', b'p',
+                0x02, 0x0A, 0xFC
+            ]
+        );
+        assert!(data.scan_response.is_empty());
+    }
+
+    #[test]
+    fn advertisement_builder_02_manufacturer_and_service_data() {
+        let builder = AdvertisementBuilder::new()
+            .flags(None)
+            .manufacturer_data(0x02E5, &[1, 2])
+            .service_data(&BleId::FromUuid16(0xFEAA), &[3])
+            .appearance(0x03C1);
+        let data = builder.build().unwrap();
+        assert_eq!(
+            data.advertisement,
+            vec![0x05, 0xFF, 0xE5, 0x02, 1, 2, 0x04, 0x16, 0xAA, 0xFE, 3, 0x03, 0x19, 0xC1, 0x03]
+        );
+        assert_eq!(builder.encoded_size(), data.advertisement.len());
+    }
+
+    #[test]
+    fn advertisement_builder_03_too_big_fails() {
+        let builder =
+            AdvertisementBuilder::new().name("a name that is too long for an advertisement");
+        assert_eq!(builder.encoded_size(), 3 + 2 + 44);
+        assert!(matches!(
+            builder.build(),
+            Err(BleError::AdvertisementDoesNotFit)
+        ));
+    }
+
+    #[test]
+    fn advertisement_builder_04_spills_into_scan_response() {
+        let data = AdvertisementBuilder::new()
+            .service_uuid(&BleId::FromUuid128([0xAB; 16]))
+            .name("sensor-kitchen")
+            .spill_to_scan_response(true)
+            .build()
+            .unwrap();
+        assert_eq!(data.advertisement.len(), 3 + 18);
+        assert_eq!(data.scan_response[..2], [15, AD_COMPLETE_NAME]);
+    }
+
+    #[test]
+    fn advertisement_builder_05_explicit_scan_response_goes_first() {
+        let data = AdvertisementBuilder::new()
+            .manufacturer_data(0xFFFF, &[0; 24])
+            .appearance(0x0040)
+            .scan_response(AdvertisementBuilder::new().shortened_name("framework", 4))
+            .spill_to_scan_response(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            data.scan_response,
+            vec![0x05, 0x08, b'f', b'r', b'a', b'm', 0x03, 0x19, 0x40, 0x00]
+        );
+    }
+
+    #[test]
+    fn advertisement_builder_06_spill_fails_when_scan_response_is_full() {
+        let builder = AdvertisementBuilder::new()
+            .manufacturer_data(0xFFFF, &[0; 25])
+            .manufacturer_data(0xFFFF, &[0; 25])
+            .manufacturer_data(0xFFFF, &[0; 25])
+            .spill_to_scan_response(true);
+        assert!(matches!(
+            builder.build(),
+            Err(BleError::AdvertisementDoesNotFit)
+        ));
+    }
+
+    #[test]
+    fn advertisement_builder_07_uuids_in_shortest_form() {
+        let battery: BleId = "0000180f-0000-1000-8000-00805f9b34fb".parse().unwrap();
+        let data = AdvertisementBuilder::new()
+            .flags(None)
+            .service_uuid(&battery)
+            .service_uuid(&BleId::FromUuid16(0x180F))
+            .service_uuid(&BleId::FromUuid32(0x0001_180F))
+            .build()
+            .unwrap();
+        assert_eq!(
+            data.advertisement,
+            vec![0x03, 0x03, 0x0F, 0x18, 0x05, 0x05, 0x0F, 0x18, 0x01, 0x00]
+        );
+    }
+
+    #[test]
+    fn advertisement_builder_08_extended_payload() {
+        let builder = AdvertisementBuilder::new()
+            .name("esp")
+            .manufacturer_data(0xFFFF, &[7; 100])
+            .service_data(&BleId::FromUuid16(0x180F), &[1; 100]);
+        assert!(builder.build().is_err());
+        let payload = builder.build_extended().unwrap();
+        assert_eq!(payload.len(), builder.encoded_size());
+        assert_eq!(&payload[..3], &[2, AD_FLAGS, DEFAULT_FLAGS]);
+        assert!(AdvertisementBuilder::new()
+            .manufacturer_data(0xFFFF, &[0; 300])
+            .build_extended()
+            .is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use std::time::Duration;
+
+use super::{AdvertisementBuilder, BeaconFrameKind, BleError, BleId};
+
+/// Data advertised by an entry of an [AdvertisingSchedule]
+/// - `Service`: The data of the service of the beacon with that id, so the changes made with
+///   [crate::ble::BleBeacon::set_service] are advertised right away
+/// - `Frame`: The frame of that kind set on the beacon
+/// - `Raw`: Custom advertisement data
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleContent {
+    Service(BleId),
+    Frame(BeaconFrameKind),
+    Raw(AdvertisementBuilder),
+}
+
+/// Time an entry of an [AdvertisingSchedule] is advertised on each round
+/// - `Fixed`: A fixed duration
+/// - `Weight`: A multiple of the time per weight of the schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleTime {
+    Fixed(Duration),
+    Weight(u32),
+}
+
+/// An entry of an [AdvertisingSchedule]
+/// - `content`: The data advertised
+/// - `time`: How long the data is advertised on each round
+/// - `interval`: The min and max advertising intervals used while the entry is advertised, in
+///   0.625ms units. If None, the default intervals of the BLE stack are used
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleEntry {
+    pub content: ScheduleContent,
+    pub time: ScheduleTime,
+    pub interval: Option<(u16, u16)>,
+}
+
+impl ScheduleEntry {
+    /// Creates a new ScheduleEntry, advertised with the default intervals
+    pub fn new(content: ScheduleContent, time: ScheduleTime) -> Self {
+        Self {
+            content,
+            time,
+            interval: None,
+        }
+    }
+
+    /// Sets the advertising intervals used while the entry is advertised
+    ///
+    /// # Arguments
+    ///
+    /// - `min_interval`: The minimum advertising interval, from 20ms to 10240ms in 0.625ms units
+    /// - `max_interval`: The maximum advertising interval, from 20ms to 10240ms in 0.625ms units
+    ///
+    /// # Returns
+    ///
+    /// The ScheduleEntry itself
+    pub fn interval(mut self, min_interval: u16, max_interval: u16) -> Self {
+        self.interval = Some((min_interval, max_interval));
+        self
+    }
+}
+
+/// Ordered entries a beacon advertises one after the other, each one for its own time. Entries
+/// can be added, removed or changed while the schedule runs, without restarting the rotation.
+/// - `current`: The entry being advertised, None if the rotation did not start
+/// - `elapsed`: The time the current entry has been advertised on this round
+/// - `changed`: Whether the current entry must be advertised again, because it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvertisingSchedule {
+    entries: Vec<ScheduleEntry>,
+    time_per_weight: Duration,
+    current: Option<usize>,
+    elapsed: Duration,
+    changed: bool,
+}
+
+impl Default for AdvertisingSchedule {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl AdvertisingSchedule {
+    /// Creates a new empty AdvertisingSchedule
+    ///
+    /// # Arguments
+    ///
+    /// - `time_per_weight`: The time each unit of weight of a [ScheduleTime::Weight] lasts
+    pub fn new(time_per_weight: Duration) -> Self {
+        Self {
+            entries: Vec::new(),
+            time_per_weight,
+            current: None,
+            elapsed: Duration::ZERO,
+            changed: false,
+        }
+    }
+
+    /// Gets the entries of the schedule, in the order they are advertised
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries
+    }
+
+    /// Gets an entry of the schedule
+    pub fn entry(&self, index: usize) -> Option<&ScheduleEntry> {
+        self.entries.get(index)
+    }
+
+    /// Gets the index of the entry being advertised, if the rotation started
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Sets the time each unit of weight of a [ScheduleTime::Weight] lasts
+    pub fn set_time_per_weight(&mut self, time_per_weight: Duration) {
+        self.time_per_weight = time_per_weight
+    }
+
+    /// Gets how long an entry is advertised on each round
+    pub fn entry_duration(&self, index: usize) -> Option<Duration> {
+        self.entries.get(index).map(|entry| match entry.time {
+            ScheduleTime::Fixed(duration) => duration,
+            ScheduleTime::Weight(weight) => self.time_per_weight.saturating_mul(weight),
+        })
+    }
+
+    /// Gets how long a whole round of the schedule lasts
+    pub fn cycle_duration(&self) -> Duration {
+        (0..self.entries.len())
+            .filter_map(|index| self.entry_duration(index))
+            .fold(Duration::ZERO, Duration::saturating_add)
+    }
+
+    /// Replaces every entry of the schedule, restarting the rotation from the first one
+    pub fn set_entries(&mut self, entries: Vec<ScheduleEntry>) {
+        self.entries = entries;
+        self.restart();
+    }
+
+    /// Adds an entry at the end of the schedule
+    ///
+    /// # Returns
+    ///
+    /// The index of the new entry
+    pub fn push(&mut self, entry: ScheduleEntry) -> usize {
+        self.entries.push(entry);
+        self.entries.len() - 1
+    }
+
+    /// Adds an entry at a position of the schedule, moving the following ones back
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the index is bigger than the amount of entries
+    pub fn insert(&mut self, index: usize, entry: ScheduleEntry) -> Result<(), BleError> {
+        if index > self.entries.len() {
+            return Err(BleError::InvalidParameters);
+        }
+        self.entries.insert(index, entry);
+        if let Some(current) = self.current.as_mut() {
+            if index <= *current {
+                *current += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes an entry of the schedule. If it was being advertised, the next entry starts now.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn remove(&mut self, index: usize) -> Result<ScheduleEntry, BleError> {
+        if index >= self.entries.len() {
+            return Err(BleError::NotFound);
+        }
+        let entry = self.entries.remove(index);
+        if let Some(current) = self.current {
+            if index < current {
+                self.current = Some(current - 1);
+            } else if index == current {
+                self.current = self.next_entry(current, true);
+                self.elapsed = Duration::ZERO;
+                self.changed = true;
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Changes the data of an entry, keeping its position on the rotation. If it is being
+    /// advertised, the new data is advertised right away.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn update_content(
+        &mut self,
+        index: usize,
+        content: ScheduleContent,
+    ) -> Result<(), BleError> {
+        self.entries
+            .get_mut(index)
+            .ok_or(BleError::NotFound)?
+            .content = content;
+        self.changed |= self.current == Some(index);
+        Ok(())
+    }
+
+    /// Changes the time an entry is advertised on each round
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn update_time(&mut self, index: usize, time: ScheduleTime) -> Result<(), BleError> {
+        self.entries.get_mut(index).ok_or(BleError::NotFound)?.time = time;
+        Ok(())
+    }
+
+    /// Changes the advertising intervals of an entry. If it is being advertised, the new
+    /// intervals are used right away.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::NotFound`: If there is no entry at the index
+    pub fn update_interval(
+        &mut self,
+        index: usize,
+        interval: Option<(u16, u16)>,
+    ) -> Result<(), BleError> {
+        self.entries
+            .get_mut(index)
+            .ok_or(BleError::NotFound)?
+            .interval = interval;
+        self.changed |= self.current == Some(index);
+        Ok(())
+    }
+
+    /// Marks the entry being advertised to be advertised again if its content matches, used when
+    /// the data of a service or a frame referenced by it changes
+    pub(crate) fn refresh_if<F: Fn(&ScheduleContent) -> bool>(&mut self, matches: F) {
+        if let Some(entry) = self.current.and_then(|current| self.entries.get(current)) {
+            self.changed |= matches(&entry.content);
+        }
+    }
+
+    /// Restarts the rotation, so the first entry is advertised on the next [Self::advance]
+    pub(crate) fn restart(&mut self) {
+        self.current = None;
+        self.elapsed = Duration::ZERO;
+        self.changed = false;
+    }
+
+    /// Advances the rotation by the time elapsed since the last call. Entries with no time are
+    /// skipped.
+    ///
+    /// # Returns
+    ///
+    /// The index of the entry to advertise now, if it is not the one already advertised or if it
+    /// changed. None if nothing has to be advertised again
+    pub(crate) fn advance(&mut self, elapsed: Duration) -> Option<usize> {
+        let Some(mut current) = self.current else {
+            self.restart();
+            self.current = self.next_entry(0, true);
+            return self.current;
+        };
+        let cycle = self.cycle_duration();
+        if cycle.is_zero() {
+            self.restart();
+            return None;
+        }
+        let mut changed = std::mem::take(&mut self.changed);
+        self.elapsed = self.elapsed.saturating_add(elapsed);
+        if self.elapsed >= cycle {
+            self.elapsed =
+                Duration::from_nanos((self.elapsed.as_nanos() % cycle.as_nanos()) as u64);
+        }
+        loop {
+            let duration = self.entry_duration(current).unwrap_or_default();
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            current = self.next_entry(current, false)?;
+            changed = true;
+        }
+        self.current = Some(current);
+        changed.then_some(current)
+    }
+
+    /// Gets the next entry with some time after `index`, going back to the first one after the
+    /// last. If `include` is set, the entry at `index` itself is also considered.
+    fn next_entry(&self, index: usize, include: bool) -> Option<usize> {
+        let len = self.entries.len();
+        let start = if include { index } else { index + 1 };
+        (start..start + len)
+            .map(|i| i % len.max(1))
+            .find(|&i| !self.entry_duration(i).unwrap_or_default().is_zero())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn service_entry(id: u16, time: ScheduleTime) -> ScheduleEntry {
+        ScheduleEntry::new(ScheduleContent::Service(BleId::FromUuid16(id)), time)
+    }
+
+    fn schedule() -> AdvertisingSchedule {
+        let mut schedule = AdvertisingSchedule::new(Duration::from_millis(100));
+        schedule.push(service_entry(1, ScheduleTime::Weight(2)));
+        schedule.push(service_entry(
+            2,
+            ScheduleTime::Fixed(Duration::from_millis(50)),
+        ));
+        schedule.push(
+            ScheduleEntry::new(
+                ScheduleContent::Frame(BeaconFrameKind::IBeacon),
+                ScheduleTime::Weight(1),
+            )
+            .interval(160, 320),
+        );
+        schedule
+    }
+
+    #[test]
+    fn advertising_schedule_01_rotates_in_order_with_each_time() {
+        let mut schedule = schedule();
+        assert_eq!(schedule.cycle_duration(), Duration::from_millis(350));
+        assert_eq!(schedule.entry(2).unwrap().interval, Some((160, 320)));
+
+        let ms = Duration::from_millis;
+        assert_eq!(schedule.advance(ms(10)), Some(0));
+        assert_eq!(schedule.advance(ms(150)), None);
+        assert_eq!(schedule.advance(ms(50)), Some(1));
+        assert_eq!(schedule.advance(ms(40)), None);
+        assert_eq!(schedule.advance(ms(10)), Some(2));
+        assert_eq!(schedule.advance(ms(100)), Some(0));
+        // A long stall goes around the whole cycle only once
+        assert_eq!(schedule.advance(ms(350 * 3 + 260)), Some(2));
+        assert_eq!(schedule.current(), Some(2));
+    }
+
+    #[test]
+    fn advertising_schedule_02_updates_without_restarting() {
+        let mut schedule = schedule();
+        let ms = Duration::from_millis;
+        assert_eq!(schedule.advance(ms(0)), Some(0));
+        assert_eq!(schedule.advance(ms(100)), None);
+
+        let raw = ScheduleContent::Raw(AdvertisementBuilder::new().name("Beacon"));
+        schedule.update_content(0, raw.clone()).unwrap();
+        assert_eq!(schedule.advance(ms(10)), Some(0));
+        assert_eq!(schedule.entry(0).unwrap().content, raw);
+        assert_eq!(schedule.advance(ms(90)), Some(1));
+
+        schedule.update_content(2, raw).unwrap();
+        assert_eq!(schedule.advance(ms(10)), None);
+        schedule.refresh_if(|content| content == &ScheduleContent::Service(BleId::FromUuid16(2)));
+        assert_eq!(schedule.advance(ms(10)), Some(1));
+
+        schedule
+            .insert(0, service_entry(3, ScheduleTime::Weight(1)))
+            .unwrap();
+        assert_eq!(schedule.current(), Some(2));
+        assert!(matches!(
+            schedule.update_time(9, ScheduleTime::Weight(1)),
+            Err(BleError::NotFound)
+        ));
+        assert!(matches!(
+            schedule.insert(9, service_entry(4, ScheduleTime::Weight(1))),
+            Err(BleError::InvalidParameters)
+        ));
+    }
+
+    #[test]
+    fn advertising_schedule_03_removes_and_skips_entries_without_time() {
+        let mut schedule = schedule();
+        let ms = Duration::from_millis;
+        assert_eq!(schedule.advance(ms(0)), Some(0));
+        schedule.update_time(1, ScheduleTime::Weight(0)).unwrap();
+        assert_eq!(schedule.advance(ms(200)), Some(2));
+
+        schedule.remove(2).unwrap();
+        assert_eq!(schedule.current(), Some(0));
+        assert_eq!(schedule.advance(ms(0)), Some(0));
+
+        schedule.remove(0).unwrap();
+        assert_eq!(schedule.current(), None);
+        assert_eq!(schedule.advance(ms(0)), None);
+        assert!(matches!(schedule.remove(3), Err(BleError::NotFound)));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc::{sync_channel, Receiver, Sender, SyncSender},
+        Arc,
+    },
+    time::Duration,
+};
+
+use esp32_nimble::{utilities::mutex::Mutex, BLECharacteristic};
+
+use super::ConnectionInformation;
+use crate::utils::notification::Notifier;
+
+/// Maximum time the BLE stack waits for a handler to be executed on the main loop, and then for
+/// the answer of a handler already executing. After it, reads answer with the current value and
+/// writes are rejected with [AttErrorCode::UnlikelyError].
+const HANDLER_TIMEOUT_MS: u64 = 1000;
+
+const ACCESS_PENDING: u8 = 0;
+const ACCESS_TAKEN: u8 = 1;
+const ACCESS_ABANDONED: u8 = 2;
+
+const APPLICATION_ERROR_BASE: u8 = 0x80;
+const APPLICATION_ERROR_MASK: u8 = 0x1F;
+
+type OnWriteCallback = dyn FnMut(&[u8], &ConnectionInformation) -> Result<(), AttErrorCode>;
+type OnReadCallback = dyn FnMut(&ConnectionInformation) -> Vec<u8>;
+
+/// ATT error codes a write handler can answer with to reject a write.
+/// - `Application(u8)`: Application specific error. Its value is the offset inside the
+///   application error range (0x80 - 0x9F), so only the 5 lower bits are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttErrorCode {
+    Application(u8),
+    InsufficientAuthentication,
+    InsufficientAuthorization,
+    InsufficientEncryption,
+    InvalidAttributeValueLength,
+    InvalidOffset,
+    OutOfRange,
+    UnlikelyError,
+    ValueNotAllowed,
+    WriteNotPermitted,
+    WriteRequestRejected,
+}
+
+impl AttErrorCode {
+    /// Gets the code sent to the client
+    pub fn code(&self) -> u8 {
+        match self {
+            AttErrorCode::Application(offset) => {
+                APPLICATION_ERROR_BASE | (offset & APPLICATION_ERROR_MASK)
+            }
+            AttErrorCode::InsufficientAuthentication => 0x05,
+            AttErrorCode::InsufficientAuthorization => 0x08,
+            AttErrorCode::InsufficientEncryption => 0x0F,
+            AttErrorCode::InvalidAttributeValueLength => 0x0D,
+            AttErrorCode::InvalidOffset => 0x07,
+            AttErrorCode::OutOfRange => 0xFF,
+            AttErrorCode::UnlikelyError => 0x0E,
+            AttErrorCode::ValueNotAllowed => 0x13,
+            AttErrorCode::WriteNotPermitted => 0x03,
+            AttErrorCode::WriteRequestRejected => 0xFC,
+        }
+    }
+}
+
+/// User callbacks of a characteristic, executed on the main loop when a client reads or
+/// writes it. Clones of a characteristic share the same callbacks.
+#[derive(Clone, Default)]
+pub(crate) struct CharacteristicHandlers {
+    pub(crate) on_write: Option<Rc<RefCell<Box<OnWriteCallback>>>>,
+    pub(crate) on_read: Option<Rc<RefCell<Box<OnReadCallback>>>>,
+}
+
+impl fmt::Debug for CharacteristicHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CharacteristicHandlers")
+            .field("on_write", &self.on_write.is_some())
+            .field("on_read", &self.on_read.is_some())
+            .finish()
+    }
+}
+
+impl CharacteristicHandlers {
+    /// Returns true if no callback is set
+    pub(crate) fn is_empty(&self) -> bool {
+        self.on_write.is_none() && self.on_read.is_none()
+    }
+}
+
+/// Whether the BLE stack still waits for an access. The first side that changes it decides
+/// what happens: either the main loop takes the access and the BLE stack waits for its answer,
+/// or the BLE stack abandons it after the timeout and the main loop drops it without executing
+/// the handler. So a write the client was told failed never changes the value later.
+#[derive(Clone, Default)]
+pub(crate) struct AccessState(Arc<AtomicU8>);
+
+impl AccessState {
+    /// Marks the access as taken by the main loop
+    ///
+    /// # Returns
+    ///
+    /// False if the BLE stack already abandoned it
+    fn take(&self) -> bool {
+        self.transition(ACCESS_TAKEN)
+    }
+
+    /// Marks the access as abandoned by the BLE stack
+    ///
+    /// # Returns
+    ///
+    /// False if the main loop already took it
+    fn abandon(&self) -> bool {
+        self.transition(ACCESS_ABANDONED)
+    }
+
+    fn transition(&self, state: u8) -> bool {
+        self.0
+            .compare_exchange(ACCESS_PENDING, state, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+/// Waits on the BLE stack for the answer of an access. After the timeout the access is
+/// abandoned, unless the main loop is already executing its handler, in which case its answer
+/// is waited for up to another timeout, so a blocked handler can not stall the BLE stack.
+///
+/// # Returns
+///
+/// The answer of the handler, or `None` if it did not arrive in time
+fn wait_answer<T>(receiver: &Receiver<T>, state: &AccessState, timeout: Duration) -> Option<T> {
+    match receiver.recv_timeout(timeout) {
+        Ok(answer) => Some(answer),
+        Err(_) if state.abandon() => None,
+        Err(_) => receiver.recv_timeout(timeout).ok(),
+    }
+}
+
+/// Access made by a client that must be answered by a user callback
+pub(crate) enum AttributeAccess {
+    Read(SyncSender<Vec<u8>>),
+    Write(Vec<u8>, SyncSender<Result<(), AttErrorCode>>),
+}
+
+/// Request sent from the BLE stack to the main loop.
+/// - `handlers`: The index of the handlers of the characteristic on the server.
+/// - `client`: The client accessing the characteristic.
+/// - `access`: The kind of access and where to send the answer.
+/// - `state`: Whether the BLE stack still waits for the answer.
+pub(crate) struct AttributeRequest {
+    pub(crate) handlers: usize,
+    pub(crate) client: ConnectionInformation,
+    pub(crate) access: AttributeAccess,
+    pub(crate) state: AccessState,
+}
+
+impl AttributeRequest {
+    /// Executes the corresponding handler and sends its answer back to the BLE stack. If the
+    /// BLE stack stopped waiting for it, the request is dropped without executing the handler.
+    ///
+    /// # Arguments
+    ///
+    /// - `handlers`: The handlers of the characteristic being accessed
+    pub(crate) fn answer(self, handlers: &CharacteristicHandlers) {
+        if !self.state.take() {
+            return;
+        }
+        match self.access {
+            AttributeAccess::Read(sender) => {
+                if let Some(on_read) = &handlers.on_read {
+                    _ = sender.send((on_read.borrow_mut())(&self.client));
+                }
+            }
+            AttributeAccess::Write(data, sender) => {
+                if let Some(on_write) = &handlers.on_write {
+                    _ = sender.send((on_write.borrow_mut())(&data, &self.client));
+                }
+            }
+        }
+    }
+}
+
+/// Sets the BLE stack callbacks of a characteristic, so each access is forwarded to the main
+/// loop and waits for the answer of the user callback.
+///
+/// Note: The BLE stack task is blocked while it waits, up to [HANDLER_TIMEOUT_MS] per access, or
+/// twice that if the handler was already executing when it expired, so every connection and GAP
+/// event is delayed meanwhile. Main loops that spend long periods without updating the
+/// microcontroller make accesses time out, and those accesses are never executed. Handlers that
+/// take too long have their answer ignored: reads answer with the current value and writes are
+/// rejected with [AttErrorCode::UnlikelyError].
+///
+/// # Arguments
+///
+/// - `characteristic`: The characteristic of the server
+/// - `handlers`: The user callbacks, only the ones that are set are forwarded
+/// - `index`: The index of the handlers on the server
+/// - `requests`: Where to send the requests
+/// - `notifier`: A notifier to wake up the [crate::Microcontroller]
+pub(crate) fn forward_characteristic_accesses(
+    characteristic: &Mutex<BLECharacteristic>,
+    handlers: &CharacteristicHandlers,
+    index: usize,
+    requests: &Sender<AttributeRequest>,
+    notifier: &Notifier,
+) {
+    let mut characteristic = characteristic.lock();
+    let timeout = Duration::from_millis(HANDLER_TIMEOUT_MS);
+
+    if handlers.on_read.is_some() {
+        let requests = requests.clone();
+        let notifier = notifier.clone();
+        characteristic.on_read(move |value, desc| {
+            let (sender, receiver) = sync_channel(1);
+            let state = AccessState::default();
+            let request = AttributeRequest {
+                handlers: index,
+                client: ConnectionInformation::from_bleconn_desc(desc, true, Ok(())),
+                access: AttributeAccess::Read(sender),
+                state: state.clone(),
+            };
+            if requests.send(request).is_ok() {
+                notifier.notify();
+                if let Some(data) = wait_answer(&receiver, &state, timeout) {
+                    value.set_value(&data);
+                }
+            }
+        });
+    }
+
+    if handlers.on_write.is_some() {
+        let requests = requests.clone();
+        let notifier = notifier.clone();
+        characteristic.on_write(move |args| {
+            let (sender, receiver) = sync_channel(1);
+            let state = AccessState::default();
+            let request = AttributeRequest {
+                handlers: index,
+                client: ConnectionInformation::from_bleconn_desc(args.desc(), true, Ok(())),
+                access: AttributeAccess::Write(args.recv_data().to_vec(), sender),
+                state: state.clone(),
+            };
+            if requests.send(request).is_err() {
+                return;
+            }
+            notifier.notify();
+            let answer =
+                wait_answer(&receiver, &state, timeout).unwrap_or(Err(AttErrorCode::UnlikelyError));
+            if let Err(err) = answer {
+                args.reject_with_error_code(err.code());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn attribute_handlers_01_pending_access_is_abandoned_after_timeout() {
+        let (_sender, receiver) = sync_channel::<u8>(1);
+        let state = AccessState::default();
+        assert_eq!(wait_answer(&receiver, &state, TIMEOUT), None);
+        assert!(!state.take());
+    }
+
+    #[test]
+    fn attribute_handlers_02_answer_of_taken_access_is_waited_for() {
+        let (sender, receiver) = sync_channel(1);
+        let state = AccessState::default();
+        assert!(state.take());
+        let handler = std::thread::spawn(move || {
+            std::thread::sleep(TIMEOUT + TIMEOUT / 2);
+            sender.send(7).unwrap();
+        });
+        assert_eq!(wait_answer(&receiver, &state, TIMEOUT), Some(7));
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn attribute_handlers_03_wait_for_taken_access_is_bounded() {
+        let (_sender, receiver) = sync_channel::<u8>(1);
+        let state = AccessState::default();
+        assert!(state.take());
+        assert_eq!(wait_answer(&receiver, &state, TIMEOUT), None);
+    }
+}
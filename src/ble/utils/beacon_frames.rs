@@ -0,0 +1,369 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use super::BleError;
+
+/// Company identifier of Apple, used by iBeacon frames
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+const IBEACON_TYPE: u8 = 0x02;
+const IBEACON_LENGTH: u8 = 0x15;
+const IBEACON_FRAME_SIZE: usize = 25;
+
+/// 16 bit service uuid all Eddystone frames are advertised with
+pub const EDDYSTONE_SERVICE_UUID: u16 = 0xFEAA;
+const EDDYSTONE_UID: u8 = 0x00;
+const EDDYSTONE_URL: u8 = 0x10;
+const EDDYSTONE_TLM: u8 = 0x20;
+const EDDYSTONE_UID_SIZE: usize = 20;
+const EDDYSTONE_TLM_SIZE: usize = 14;
+const TLM_VERSION: u8 = 0x00;
+const TLM_UNKNOWN_TEMPERATURE: u16 = 0x8000;
+const TLM_TIME_RESOLUTION_MS: u128 = 100;
+const MAX_ENCODED_URL_SIZE: usize = 17;
+
+const URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+const URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+/// An Apple iBeacon frame, advertised as manufacturer data.
+/// - `proximity_uuid`: Identifies the beacons of an organization or deployment.
+/// - `major`: Identifies a group of beacons, for example the ones in a building.
+/// - `minor`: Identifies a beacon inside its group.
+/// - `measured_power`: The rssi measured at 1 meter of the beacon, used to estimate distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IBeacon {
+    pub proximity_uuid: Uuid,
+    pub major: u16,
+    pub minor: u16,
+    pub measured_power: i8,
+}
+
+impl IBeacon {
+    /// Encodes the frame as the manufacturer data of an advertisement
+    pub fn to_manufacturer_data(&self) -> Vec<u8> {
+        let mut bytes = APPLE_COMPANY_ID.to_le_bytes().to_vec();
+        bytes.extend([IBEACON_TYPE, IBEACON_LENGTH]);
+        bytes.extend(self.proximity_uuid.as_bytes());
+        bytes.extend(self.major.to_be_bytes());
+        bytes.extend(self.minor.to_be_bytes());
+        bytes.push(self.measured_power as u8);
+        bytes
+    }
+
+    /// Decodes the frame from the manufacturer data of an advertisement
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the IBeacon, or `None` if the data is not an iBeacon frame
+    pub fn from_manufacturer_data(data: &[u8]) -> Option<Self> {
+        if data.len() != IBEACON_FRAME_SIZE
+            || data[..4] != [0x4C, 0x00, IBEACON_TYPE, IBEACON_LENGTH]
+        {
+            return None;
+        }
+        Some(Self {
+            proximity_uuid: Uuid::from_bytes(data[4..20].try_into().ok()?),
+            major: u16::from_be_bytes([data[20], data[21]]),
+            minor: u16::from_be_bytes([data[22], data[23]]),
+            measured_power: data[24] as i8,
+        })
+    }
+}
+
+/// Telemetry of an Eddystone beacon.
+/// - `battery_voltage`: The battery voltage in milivolts, 0 if the beacon is not battery powered.
+/// - `temperature`: The temperature of the beacon in degrees Celsius, if it is known. It is sent with
+///   a resolution of 1/256 degrees.
+/// - `advertising_count`: The amount of advertisements sent since the beacon was powered on.
+/// - `uptime`: The time since the beacon was powered on, sent with a resolution of 0.1 seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EddystoneTlm {
+    pub battery_voltage: u16,
+    pub temperature: Option<f32>,
+    pub advertising_count: u32,
+    pub uptime: Duration,
+}
+
+/// The frames an Eddystone beacon can advertise, as the service data of [EDDYSTONE_SERVICE_UUID].
+/// - `Uid`: A 10 bytes namespace and a 6 bytes instance that identify the beacon. The tx power
+///   is the rssi measured at 0 meters.
+/// - `Url`: An url the clients can open. The tx power is the rssi measured at 0 meters.
+/// - `Tlm`: Telemetry of the beacon.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EddystoneFrame {
+    Uid {
+        tx_power: i8,
+        namespace: [u8; 10],
+        instance: [u8; 6],
+    },
+    Url {
+        tx_power: i8,
+        url: String,
+    },
+    Tlm(EddystoneTlm),
+}
+
+impl EddystoneFrame {
+    /// Encodes the frame as the service data of an advertisement
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the data of the frame, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the url does not start with a valid scheme, has non ascii
+    ///   characters or does not fit in a frame once compressed.
+    pub fn to_service_data(&self) -> Result<Vec<u8>, BleError> {
+        let bytes = match self {
+            EddystoneFrame::Uid {
+                tx_power,
+                namespace,
+                instance,
+            } => {
+                let mut bytes = vec![EDDYSTONE_UID, *tx_power as u8];
+                bytes.extend(namespace);
+                bytes.extend(instance);
+                bytes.extend([0, 0]);
+                bytes
+            }
+            EddystoneFrame::Url { tx_power, url } => {
+                let mut bytes = vec![EDDYSTONE_URL, *tx_power as u8];
+                bytes.extend(encode_url(url)?);
+                bytes
+            }
+            EddystoneFrame::Tlm(tlm) => {
+                let temperature = match tlm.temperature {
+                    Some(temperature) => ((temperature * 256.0).round() as i16) as u16,
+                    None => TLM_UNKNOWN_TEMPERATURE,
+                };
+                let uptime = tlm.uptime.as_millis() / TLM_TIME_RESOLUTION_MS;
+                let mut bytes = vec![EDDYSTONE_TLM, TLM_VERSION];
+                bytes.extend(tlm.battery_voltage.to_be_bytes());
+                bytes.extend(temperature.to_be_bytes());
+                bytes.extend(tlm.advertising_count.to_be_bytes());
+                bytes.extend((uptime.min(u32::MAX as u128) as u32).to_be_bytes());
+                bytes
+            }
+        };
+        Ok(bytes)
+    }
+
+    /// Decodes the frame from the service data of an advertisement
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the EddystoneFrame, or `None` if the data is not a valid Eddystone frame
+    pub fn from_service_data(data: &[u8]) -> Option<Self> {
+        match *data.first()? {
+            EDDYSTONE_UID if data.len() >= EDDYSTONE_UID_SIZE - 2 => Some(EddystoneFrame::Uid {
+                tx_power: data[1] as i8,
+                namespace: data[2..12].try_into().ok()?,
+                instance: data[12..18].try_into().ok()?,
+            }),
+            EDDYSTONE_URL if data.len() > 2 => Some(EddystoneFrame::Url {
+                tx_power: data[1] as i8,
+                url: decode_url(&data[2..])?,
+            }),
+            EDDYSTONE_TLM if data.len() == EDDYSTONE_TLM_SIZE && data[1] == TLM_VERSION => {
+                let temperature = u16::from_be_bytes([data[4], data[5]]);
+                let uptime = u32::from_be_bytes([data[10], data[11], data[12], data[13]]);
+                Some(EddystoneFrame::Tlm(EddystoneTlm {
+                    battery_voltage: u16::from_be_bytes([data[2], data[3]]),
+                    temperature: (temperature != TLM_UNKNOWN_TEMPERATURE)
+                        .then_some(temperature as i16 as f32 / 256.0),
+                    advertising_count: u32::from_be_bytes([data[6], data[7], data[8], data[9]]),
+                    uptime: Duration::from_millis(uptime as u64 * TLM_TIME_RESOLUTION_MS as u64),
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Compresses an url with the schemes and expansions defined by Eddystone-URL
+///
+/// # Errors
+///
+/// - `BleError::InvalidValue`: If the url does not start with a valid scheme, has non ascii
+///   characters or does not fit in a frame once compressed.
+fn encode_url(url: &str) -> Result<Vec<u8>, BleError> {
+    let (scheme, prefix) = URL_SCHEMES
+        .iter()
+        .enumerate()
+        .find(|(_, scheme)| url.starts_with(*scheme))
+        .ok_or(BleError::InvalidValue)?;
+    if !url.is_ascii() {
+        return Err(BleError::InvalidValue);
+    }
+
+    let mut bytes = vec![scheme as u8];
+    let mut rest = &url[prefix.len()..];
+    while let Some(c) = rest.chars().next() {
+        match URL_EXPANSIONS
+            .iter()
+            .position(|expansion| rest.starts_with(expansion))
+        {
+            Some(code) => {
+                bytes.push(code as u8);
+                rest = &rest[URL_EXPANSIONS[code].len()..];
+            }
+            None => {
+                bytes.push(c as u8);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    // The scheme prefix byte is not part of the encoded url
+    if bytes.len() > 1 + MAX_ENCODED_URL_SIZE {
+        return Err(BleError::InvalidValue);
+    }
+    Ok(bytes)
+}
+
+/// Expands an url compressed with the schemes and expansions defined by Eddystone-URL
+fn decode_url(bytes: &[u8]) -> Option<String> {
+    let (scheme, rest) = bytes.split_first()?;
+    let mut url = URL_SCHEMES.get(*scheme as usize)?.to_string();
+    for byte in rest {
+        match URL_EXPANSIONS.get(*byte as usize) {
+            Some(expansion) => url.push_str(expansion),
+            None if byte.is_ascii_graphic() => url.push(*byte as char),
+            None => return None,
+        }
+    }
+    Some(url)
+}
+
+/// A frame a [crate::ble::BleBeacon] can advertise
+#[derive(Debug, Clone, PartialEq)]
+pub enum BeaconFrame {
+    IBeacon(IBeacon),
+    Eddystone(EddystoneFrame),
+}
+
+/// Kinds of [BeaconFrame], a beacon can advertise one frame of each kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BeaconFrameKind {
+    IBeacon,
+    EddystoneUid,
+    EddystoneUrl,
+    EddystoneTlm,
+}
+
+impl BeaconFrame {
+    /// Gets the kind of the frame
+    pub fn kind(&self) -> BeaconFrameKind {
+        match self {
+            BeaconFrame::IBeacon(_) => BeaconFrameKind::IBeacon,
+            BeaconFrame::Eddystone(EddystoneFrame::Uid { .. }) => BeaconFrameKind::EddystoneUid,
+            BeaconFrame::Eddystone(EddystoneFrame::Url { .. }) => BeaconFrameKind::EddystoneUrl,
+            BeaconFrame::Eddystone(EddystoneFrame::Tlm(_)) => BeaconFrameKind::EddystoneTlm,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn beacon_frames_01_ibeacon() {
+        let ibeacon = IBeacon {
+            proximity_uuid: Uuid::parse_str("e2c56db5-dffb-48d2-b060-d0f5a71096e0").unwrap(),
+            major: 1,
+            minor: 0x0203,
+            measured_power: -59,
+        };
+        let data = ibeacon.to_manufacturer_data();
+        assert_eq!(
+            data,
+            vec![
+                0x4C, 0x00, 0x02, 0x15, 0xE2, 0xC5, 0x6D, 0xB5, 0xDF, 0xFB, 0x48, 0xD2, 0xB0, 0x60,
+                0xD0, 0xF5, 0xA7, 0x10, 0x96, 0xE0, 0x00, 0x01, 0x02, 0x03, 0xC5
+            ]
+        );
+        assert_eq!(IBeacon::from_manufacturer_data(&data), Some(ibeacon));
+        assert_eq!(IBeacon::from_manufacturer_data(&data[..24]), None);
+    }
+
+    #[test]
+    fn beacon_frames_02_eddystone_uid() {
+        let frame = EddystoneFrame::Uid {
+            tx_power: -20,
+            namespace: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            instance: [0xA, 0xB, 0xC, 0xD, 0xE, 0xF],
+        };
+        let data = frame.to_service_data().unwrap();
+        assert_eq!(
+            data,
+            vec![0x00, 0xEC, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF, 0, 0]
+        );
+        assert_eq!(EddystoneFrame::from_service_data(&data), Some(frame));
+    }
+
+    #[test]
+    fn beacon_frames_03_eddystone_url_compression() {
+        let frame = EddystoneFrame::Url {
+            tx_power: -10,
+            url: "https://www.example.com/".to_string(),
+        };
+        let data = frame.to_service_data().unwrap();
+        assert_eq!(
+            data,
+            [&[0x10, 0xF6, 0x01][..], b"example", &[0x00]].concat()
+        );
+        assert_eq!(EddystoneFrame::from_service_data(&data), Some(frame));
+
+        assert_eq!(
+            encode_url("http://goo.gl/S6zT6P").unwrap(),
+            [&[0x02][..], b"goo.gl/S6zT6P"].concat()
+        );
+        assert_eq!(encode_url("https://a.org").unwrap(), vec![0x03, b'a', 0x08]);
+    }
+
+    #[test]
+    fn beacon_frames_04_invalid_urls() {
+        assert!(encode_url("ftp://example.com").is_err());
+        assert!(encode_url("https://www.a-very-long-domain-name.com").is_err());
+        assert_eq!(encode_url("https://abcdefghijklmnopq").unwrap().len(), 18);
+        assert!(encode_url("https://abcdefghijklmnopqr").is_err());
+        assert!(encode_url("https://ñ.com").is_err());
+        assert_eq!(decode_url(&[0x09, b'a']), None);
+    }
+
+    #[test]
+    fn beacon_frames_05_eddystone_tlm() {
+        let frame = EddystoneFrame::Tlm(EddystoneTlm {
+            battery_voltage: 3000,
+            temperature: Some(-1.5),
+            advertising_count: 0x01020304,
+            uptime: Duration::from_secs(60),
+        });
+        let data = frame.to_service_data().unwrap();
+        assert_eq!(
+            data,
+            vec![
+                0x20, 0x00, 0x0B, 0xB8, 0xFE, 0x80, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x02, 0x58
+            ]
+        );
+        assert_eq!(EddystoneFrame::from_service_data(&data), Some(frame));
+    }
+
+    #[test]
+    fn beacon_frames_06_eddystone_tlm_unknown_temperature() {
+        let frame = EddystoneFrame::Tlm(EddystoneTlm {
+            battery_voltage: 0,
+            temperature: None,
+            advertising_count: 0,
+            uptime: Duration::ZERO,
+        });
+        let data = frame.to_service_data().unwrap();
+        assert_eq!(&data[4..6], &[0x80, 0x00]);
+        assert_eq!(EddystoneFrame::from_service_data(&data), Some(frame));
+    }
+}
@@ -0,0 +1,266 @@
+use super::{BleError, GattValue};
+
+const MEDFLOAT16_FORMAT: u8 = 0x16;
+const MEDFLOAT32_FORMAT: u8 = 0x17;
+
+/// A value that can be read from or written to a remote characteristic. Every [GattValue]
+/// is a BleCodec, so integers, floats, bools, strings and the IEEE-11073 floats
+/// [MedFloat16] and [MedFloat32] are supported out of the box.
+pub trait BleCodec: Sized {
+    /// Encodes the value into the bytes of a characteristic
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decodes the value from the bytes of a characteristic
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the bytes do not represent a value of this type.
+    fn decode(bytes: &[u8]) -> Result<Self, BleError>;
+}
+
+impl<T: GattValue> BleCodec for T {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, BleError> {
+        T::from_bytes(bytes)
+    }
+}
+
+/// Layout of an IEEE-11073 float: a signed mantissa in the lower bits and a signed exponent of
+/// base 10 in the upper ones. The mantissas closest to the limits are reserved for special values.
+struct MedicalFloat {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+}
+
+const MEDFLOAT16: MedicalFloat = MedicalFloat {
+    mantissa_bits: 12,
+    exponent_bits: 4,
+};
+const MEDFLOAT32: MedicalFloat = MedicalFloat {
+    mantissa_bits: 24,
+    exponent_bits: 8,
+};
+
+impl MedicalFloat {
+    /// Gets the biggest mantissa that is not a special value
+    fn max_mantissa(&self) -> i64 {
+        (1 << (self.mantissa_bits - 1)) - 3
+    }
+
+    /// Gets the mantissa of the special values, which are encoded with a zero exponent
+    fn nan(&self) -> u32 {
+        (1 << (self.mantissa_bits - 1)) - 1
+    }
+
+    fn nres(&self) -> u32 {
+        1 << (self.mantissa_bits - 1)
+    }
+
+    fn positive_infinity(&self) -> u32 {
+        (1 << (self.mantissa_bits - 1)) - 2
+    }
+
+    fn negative_infinity(&self) -> u32 {
+        (1 << (self.mantissa_bits - 1)) + 2
+    }
+
+    /// Encodes a value with the smallest exponent that fits its mantissa, keeping as much
+    /// precision as possible
+    fn encode(&self, value: f64) -> u32 {
+        if value.is_nan() {
+            return self.nan();
+        }
+        if value == 0.0 {
+            return 0;
+        }
+        let min_exponent = -(1 << (self.exponent_bits - 1));
+        let max_exponent = (1 << (self.exponent_bits - 1)) - 1;
+        for exponent in min_exponent..=max_exponent {
+            let mantissa = (value / 10f64.powi(exponent)).round();
+            if mantissa.abs() <= self.max_mantissa() as f64 {
+                return self.pack(mantissa as i64, exponent);
+            }
+        }
+        if value > 0.0 {
+            self.positive_infinity()
+        } else {
+            self.negative_infinity()
+        }
+    }
+
+    /// Packs a mantissa and exponent that fit the layout
+    fn pack(&self, mantissa: i64, exponent: i32) -> u32 {
+        let mantissa_mask = (1u32 << self.mantissa_bits) - 1;
+        let exponent_mask = (1u32 << self.exponent_bits) - 1;
+        ((exponent as u32 & exponent_mask) << self.mantissa_bits)
+            | (mantissa as u32 & mantissa_mask)
+    }
+
+    /// Decodes a raw value, special values other than the infinities are decoded as NaN
+    fn decode(&self, raw: u32) -> f64 {
+        let mantissa_mask = (1u32 << self.mantissa_bits) - 1;
+        let raw_mantissa = raw & mantissa_mask;
+        if raw_mantissa == self.positive_infinity() {
+            return f64::INFINITY;
+        }
+        if raw_mantissa == self.negative_infinity() {
+            return f64::NEG_INFINITY;
+        }
+        if raw_mantissa == self.nan() || (self.nres()..=self.nres() + 1).contains(&raw_mantissa) {
+            return f64::NAN;
+        }
+        let mantissa = sign_extend(raw_mantissa, self.mantissa_bits);
+        let exponent = sign_extend(raw >> self.mantissa_bits, self.exponent_bits);
+        mantissa as f64 * 10f64.powi(exponent)
+    }
+}
+
+/// Interprets the lower `bits` of a value as a two's complement number
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// IEEE-11073 16 bit float (SFLOAT), used by the health profiles of the Bluetooth SIG, like
+/// the blood pressure or the thermometer. It has a 12 bit mantissa and a 4 bit exponent of base
+/// 10, so it keeps about 3 significant digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MedFloat16(u16);
+
+impl MedFloat16 {
+    /// Not a number, also used for values that are not available
+    pub const NAN: MedFloat16 = MedFloat16(0x07FF);
+    /// Not at this resolution
+    pub const NRES: MedFloat16 = MedFloat16(0x0800);
+    pub const POSITIVE_INFINITY: MedFloat16 = MedFloat16(0x07FE);
+    pub const NEGATIVE_INFINITY: MedFloat16 = MedFloat16(0x0802);
+
+    /// Creates a MedFloat16 from its raw 16 bits
+    pub fn from_raw(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    /// Gets the raw 16 bits
+    pub fn raw(&self) -> u16 {
+        self.0
+    }
+
+    /// Creates the MedFloat16 closest to a value. Values too big become an infinity.
+    pub fn from_f32(value: f32) -> Self {
+        Self(MEDFLOAT16.encode(value as f64) as u16)
+    }
+
+    /// Gets the value as a float. NaN and NRes are decoded as NaN.
+    pub fn to_f32(self) -> f32 {
+        MEDFLOAT16.decode(self.0 as u32) as f32
+    }
+}
+
+impl GattValue for MedFloat16 {
+    const FORMAT: u8 = MEDFLOAT16_FORMAT;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        let bytes = bytes.try_into().map_err(|_| BleError::InvalidValue)?;
+        Ok(Self(u16::from_le_bytes(bytes)))
+    }
+}
+
+/// IEEE-11073 32 bit float (FLOAT), used by the health profiles of the Bluetooth SIG, like the
+/// weight scale or the glucose meter. It has a 24 bit mantissa and an 8 bit exponent of base 10.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MedFloat32(u32);
+
+impl MedFloat32 {
+    /// Not a number, also used for values that are not available
+    pub const NAN: MedFloat32 = MedFloat32(0x007F_FFFF);
+    /// Not at this resolution
+    pub const NRES: MedFloat32 = MedFloat32(0x0080_0000);
+    pub const POSITIVE_INFINITY: MedFloat32 = MedFloat32(0x007F_FFFE);
+    pub const NEGATIVE_INFINITY: MedFloat32 = MedFloat32(0x0080_0002);
+
+    /// Creates a MedFloat32 from its raw 32 bits
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    /// Gets the raw 32 bits
+    pub fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Creates the MedFloat32 closest to a value. Values too big become an infinity.
+    pub fn from_f64(value: f64) -> Self {
+        Self(MEDFLOAT32.encode(value))
+    }
+
+    /// Gets the value as a float. NaN and NRes are decoded as NaN.
+    pub fn to_f64(self) -> f64 {
+        MEDFLOAT32.decode(self.0)
+    }
+}
+
+impl GattValue for MedFloat32 {
+    const FORMAT: u8 = MEDFLOAT32_FORMAT;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        let bytes = bytes.try_into().map_err(|_| BleError::InvalidValue)?;
+        Ok(Self(u32::from_le_bytes(bytes)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ble_codec_01_builtin_values() {
+        assert_eq!(0x1234_u16.encode(), vec![0x34, 0x12]);
+        assert_eq!(i16::decode(&[0xFE, 0xFF]).unwrap(), -2);
+        assert_eq!(f32::decode(&1.5_f32.encode()).unwrap(), 1.5);
+        assert_eq!(String::decode(b"abc").unwrap(), "abc");
+        assert!(u32::decode(&[1]).is_err());
+    }
+
+    #[test]
+    fn ble_codec_02_medfloat16() {
+        // 36.4 °C as sent by a thermometer: mantissa 364, exponent -1
+        assert_eq!(MedFloat16::from_f32(36.4).raw(), 0xF16C);
+        assert!((MedFloat16::from_raw(0xF16C).to_f32() - 36.4).abs() < 1e-4);
+        assert_eq!(MedFloat16::from_f32(-1.5).to_f32(), -1.5);
+        assert_eq!(MedFloat16::from_f32(0.0).raw(), 0);
+        assert_eq!(MedFloat16::from_f32(1e12), MedFloat16::POSITIVE_INFINITY);
+        assert_eq!(MedFloat16::from_f32(-1e12), MedFloat16::NEGATIVE_INFINITY);
+        assert_eq!(MedFloat16::from_f32(f32::NAN), MedFloat16::NAN);
+        assert!(MedFloat16::NRES.to_f32().is_nan());
+        assert_eq!(MedFloat16::POSITIVE_INFINITY.to_f32(), f32::INFINITY);
+        assert_eq!(
+            MedFloat16::decode(&[0x6C, 0xF1]).unwrap(),
+            MedFloat16::from_raw(0xF16C)
+        );
+    }
+
+    #[test]
+    fn ble_codec_03_medfloat32() {
+        let weight = MedFloat32::from_f64(72.35);
+        assert!((weight.to_f64() - 72.35).abs() < 1e-9);
+        assert!((MedFloat32::from_raw(0xFE00_1C43).to_f64() - 72.35).abs() < 1e-9);
+        assert_eq!(MedFloat32::from_f64(-8.0).to_f64(), -8.0);
+        assert!(MedFloat32::NAN.to_f64().is_nan());
+        assert_eq!(MedFloat32::NEGATIVE_INFINITY.to_f64(), f64::NEG_INFINITY);
+        assert_eq!(
+            MedFloat32::decode(&weight.encode()).unwrap().raw(),
+            weight.raw()
+        );
+    }
+}
@@ -1,6 +1,11 @@
 use esp32_nimble::BLEError;
+use esp_idf_svc::sys::EspError;
 
-use crate::{microcontroller_src::peripherals::PeripheralError, timer_driver::TimerDriverError};
+use super::DfuErrorCode;
+use crate::{
+    microcontroller_src::peripherals::PeripheralError, timer_driver::TimerDriverError,
+    wifi::http::HttpError,
+};
 
 const ATTRIBUTE_CANNOT_BE_READ: u32 = 258;
 const ATTRIBUTE_CANNOT_BE_WRITTEN: u32 = 259;
@@ -8,10 +13,12 @@ const ATTRIBUTE_CANNOT_BE_WRITTEN: u32 = 259;
 /// Enums the different errors possible when working with BLE  
 #[derive(Debug)]
 pub enum BleError {
+    AdvertisementDoesNotFit,
     AdvertisementError,
     AlreadyConnected,
     CanOnlyBeOneBleDriver,
     CharacteristicNotFound,
+    CharacteristicNotIndicatable,
     CharacteristicNotNotifiable,
     CharacteristicNotReadable,
     CharacteristicNotWritable,
@@ -24,14 +31,21 @@ pub enum BleError {
     DescriptorNotWritable,
     DeviceNotFound,
     Disconnected,
+    FirmwareUpdate(DfuErrorCode),
+    Http(HttpError),
     IncorrectHandle,
+    IndicationNotConfirmed,
     InvalidPasskey,
     InvalidParameters,
+    InvalidValue,
+    LegacyAdvertisingUnavailable,
+    MtuAlreadyExchanged,
     NotFound,
     NotReadable,
     NotWritable,
     PeripheralError(PeripheralError),
     PropertiesError,
+    ScanInProgress,
     ServiceDoesNotFit,
     ServiceNotFound,
     ServiceTooBig,
@@ -41,6 +55,7 @@ pub enum BleError {
     StoppingFailure,
     TimeOut,
     TimerDriverError(TimerDriverError),
+    UnexpectedStatus(u16),
 }
 
 impl From<BLEError> for BleError {
@@ -68,6 +83,12 @@ impl From<BLEError> for BleError {
     }
 }
 
+impl From<EspError> for BleError {
+    fn from(value: EspError) -> Self {
+        Self::Code(value.code() as u32, value.to_string())
+    }
+}
+
 impl From<TimerDriverError> for BleError {
     fn from(value: TimerDriverError) -> Self {
         Self::TimerDriverError(value)
@@ -80,6 +101,12 @@ impl From<PeripheralError> for BleError {
     }
 }
 
+impl From<HttpError> for BleError {
+    fn from(value: HttpError) -> Self {
+        Self::Http(value)
+    }
+}
+
 impl BleError {
     /// Creates a more specif BleError from a BLEError, taking into acount its in a service context
     ///
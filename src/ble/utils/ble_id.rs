@@ -1,23 +1,47 @@
 use esp32_nimble::utilities::BleUuid;
-use std::hash::Hash;
+use std::{fmt, hash::Hash, str::FromStr};
 use uuid::Uuid;
 
-use super::ble_standard_uuids::{
-    StandardCharacteristicId, StandardDescriptorId, StandardServiceId,
+use super::{
+    ble_standard_uuids::{StandardCharacteristicId, StandardDescriptorId, StandardServiceId},
+    BleError,
 };
 
+/// Bluetooth base UUID 00000000-0000-1000-8000-00805F9B34FB in little endian. 16 and 32 bit
+/// uuids are the bytes 12 to 15 of it.
+const BASE_UUID: [u8; 16] = [
+    0xFB, 0x34, 0x9B, 0x5F, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const SHORT_UUID_OFFSET: usize = 12;
+/// Positions of the dashes in the canonical string of an uuid
+const UUID_DASHES: [usize; 4] = [8, 13, 18, 23];
+const UUID_STRING_LEN: usize = 36;
+
 /// Enums the possible types of Ids:
 /// - `FromUuid16`: A way to get a BLE id from an `u16`.
-/// - `FromUuid128`: A way to get a BLE id from an `[u8;16]`.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// - `FromUuid32`: A way to get a BLE id from an `u32`.
+/// - `FromUuid128`: A way to get a BLE id from an `[u8;16]`, in little endian.
+///
+/// Ids are compared by their 128 bit value, expanding 16 and 32 bit ids with the Bluetooth base
+/// uuid, so `FromUuid16(0x180F)` equals the id of `0000180f-0000-1000-8000-00805f9b34fb`.
+#[derive(Debug, Clone)]
 pub enum BleId {
     FromUuid16(u16),
+    FromUuid32(u32),
     FromUuid128([u8; 16]),
 }
 
+impl PartialEq for BleId {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_uuid128() == other.to_uuid128()
+    }
+}
+
+impl Eq for BleId {}
+
 impl Hash for BleId {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.to_uuid().to_string().hash(state)
+        self.to_uuid128().hash(state)
     }
 }
 
@@ -25,12 +49,7 @@ impl From<BleUuid> for BleId {
     fn from(value: BleUuid) -> Self {
         match value {
             BleUuid::Uuid16(id) => BleId::FromUuid16(id),
-            BleUuid::Uuid32(id) => {
-                let mut output: [u8; 16] = [0; 16];
-                let input = id.to_le_bytes();
-                output[..4].copy_from_slice(&input);
-                BleId::FromUuid128(output)
-            }
+            BleUuid::Uuid32(id) => BleId::FromUuid32(id),
             BleUuid::Uuid128(id) => BleId::FromUuid128(id),
         }
     }
@@ -42,6 +61,69 @@ impl From<&BleUuid> for BleId {
     }
 }
 
+impl fmt::Display for BleId {
+    /// Writes the canonical string of the 128 bit uuid, like
+    /// `0000180f-0000-1000-8000-00805f9b34fb`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.to_uuid128().iter().rev().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BleId {
+    type Err = BleError;
+
+    /// Parses an uuid string. Accepts the canonical string of a 128 bit uuid, or 4 or 8 hex
+    /// digits for 16 and 32 bit uuids, optionally prefixed by `0x`. Hex digits are case
+    /// insensitive.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the string is not an uuid
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let short = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        if short.chars().all(|c| c.is_ascii_hexdigit()) {
+            return match short.len() {
+                4 => Ok(BleId::FromUuid16(parse_hex(short)? as u16)),
+                8 => Ok(BleId::FromUuid32(parse_hex(short)?)),
+                _ => Err(BleError::InvalidValue),
+            };
+        }
+
+        let well_formed = s.len() == UUID_STRING_LEN
+            && s.char_indices().all(|(i, c)| {
+                if UUID_DASHES.contains(&i) {
+                    c == '-'
+                } else {
+                    c.is_ascii_hexdigit()
+                }
+            });
+        if !well_formed {
+            return Err(BleError::InvalidValue);
+        }
+        let hex: String = s.chars().filter(|c| *c != '-').collect();
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().rev().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| BleError::InvalidValue)?;
+        }
+        Ok(BleId::FromUuid128(bytes))
+    }
+}
+
+/// Parses up to 8 hex digits
+fn parse_hex(hex: &str) -> Result<u32, BleError> {
+    u32::from_str_radix(hex, 16).map_err(|_| BleError::InvalidValue)
+}
+
 impl BleId {
     /// Creates a `BleId::FromUuid16` from a StandardService
     ///
@@ -82,7 +164,8 @@ impl BleId {
         BleId::FromUuid16(id as u16)
     }
 
-    /// Creates a `BleId::FromUuid16` from a `&str`
+    /// Creates a `BleId::FromUuid16` from a `&str`. Only 16 bits of the hash of the name are
+    /// kept, so different names can get the same id. Use [Self::from_name_128] to avoid it.
     ///
     /// # Arguments
     ///
@@ -98,15 +181,56 @@ impl BleId {
         BleId::FromUuid16(u16::from_be_bytes(arr))
     }
 
-    /// Creates a BleUuid from a BleId
+    /// Creates a `BleId::FromUuid128` from a `&str`, using the whole version 3 uuid of the name
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: A string to be mapped into a `BleId::FromUuid128`
+    ///
+    /// # Returns
+    ///
+    /// A new BleId
+    pub fn from_name_128(name: &str) -> BleId {
+        let mut bytes = Uuid::new_v3(&Uuid::NAMESPACE_OID, name.as_bytes()).into_bytes();
+        bytes.reverse();
+        BleId::FromUuid128(bytes)
+    }
+
+    /// Gets the 128 bit value of the id in little endian, expanding 16 and 32 bit ids with the
+    /// Bluetooth base uuid
+    pub fn to_uuid128(&self) -> [u8; 16] {
+        match self {
+            BleId::FromUuid16(uuid) => expand_short_uuid(*uuid as u32),
+            BleId::FromUuid32(uuid) => expand_short_uuid(*uuid),
+            BleId::FromUuid128(uuid) => *uuid,
+        }
+    }
+
+    /// Gets the same id in its shortest form. A 128 bit id derived from the Bluetooth base uuid
+    /// becomes a 16 or 32 bit id, and a 32 bit id that fits in 16 bits becomes a 16 bit id.
+    pub fn shortest(&self) -> BleId {
+        let uuid = self.to_uuid128();
+        if uuid[..SHORT_UUID_OFFSET] != BASE_UUID[..SHORT_UUID_OFFSET] {
+            return BleId::FromUuid128(uuid);
+        }
+        let short = u32::from_le_bytes([uuid[12], uuid[13], uuid[14], uuid[15]]);
+        match u16::try_from(short) {
+            Ok(short) => BleId::FromUuid16(short),
+            Err(_) => BleId::FromUuid32(short),
+        }
+    }
+
+    /// Creates a BleUuid from a BleId, in its shortest form so it matches the same id of any
+    /// width
     ///
     /// # Returns
     ///
     /// The corresponfing BleUuid
     pub(crate) fn to_uuid(&self) -> BleUuid {
-        match self {
-            BleId::FromUuid16(uuid) => BleUuid::from_uuid16(*uuid),
-            BleId::FromUuid128(uuid) => BleUuid::from_uuid128(*uuid),
+        match self.shortest() {
+            BleId::FromUuid16(uuid) => BleUuid::from_uuid16(uuid),
+            BleId::FromUuid32(uuid) => BleUuid::from_uuid32(uuid),
+            BleId::FromUuid128(uuid) => BleUuid::from_uuid128(uuid),
         }
     }
 
@@ -118,7 +242,105 @@ impl BleId {
     pub fn byte_size(&self) -> usize {
         match self {
             BleId::FromUuid16(_) => 2,
+            BleId::FromUuid32(_) => 4,
             BleId::FromUuid128(_) => 16,
         }
     }
 }
+
+/// Expands a 16 or 32 bit uuid with the Bluetooth base uuid
+fn expand_short_uuid(uuid: u32) -> [u8; 16] {
+    let mut bytes = BASE_UUID;
+    bytes[SHORT_UUID_OFFSET..].copy_from_slice(&uuid.to_le_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn ble_id_01_widths_are_equal_after_expansion() {
+        let expanded: BleId = "0000180f-0000-1000-8000-00805f9b34fb".parse().unwrap();
+        assert_eq!(BleId::FromUuid16(0x180F), expanded);
+        assert_eq!(BleId::FromUuid32(0x180F), BleId::FromUuid16(0x180F));
+        assert_ne!(BleId::FromUuid16(0x180F), BleId::FromUuid16(0x180A));
+        let set: HashSet<BleId> = [
+            BleId::FromUuid16(0x180F),
+            BleId::FromUuid32(0x180F),
+            expanded,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn ble_id_02_display_and_parse() {
+        assert_eq!(
+            BleId::FromUuid16(0x2A19).to_string(),
+            "00002a19-0000-1000-8000-00805f9b34fb"
+        );
+        assert_eq!(
+            BleId::FromUuid32(0x12345678).to_string(),
+            "12345678-0000-1000-8000-00805f9b34fb"
+        );
+        let nordic: BleId = "6E400001-B5A3-F393-E0A9-E50E24DCCA9E".parse().unwrap();
+        assert_eq!(
+            nordic,
+            BleId::FromUuid128([
+                0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x01, 0x00,
+                0x40, 0x6E,
+            ])
+        );
+        assert_eq!(nordic.to_string(), "6e400001-b5a3-f393-e0a9-e50e24dcca9e");
+        assert_eq!(nordic.to_string().parse::<BleId>().unwrap(), nordic);
+    }
+
+    #[test]
+    fn ble_id_03_parse_short_forms_and_errors() {
+        assert!(matches!("180f".parse(), Ok(BleId::FromUuid16(0x180F))));
+        assert!(matches!("0x2A37".parse(), Ok(BleId::FromUuid16(0x2A37))));
+        assert!(matches!(
+            "0001180F".parse(),
+            Ok(BleId::FromUuid32(0x0001180F))
+        ));
+        for invalid in [
+            "",
+            "18f",
+            "0x",
+            "180g",
+            "0000180f-0000-1000-8000-00805f9b34f",
+            "0000180f00000-1000-8000-00805f9b34fb",
+        ] {
+            assert!(invalid.parse::<BleId>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn ble_id_04_shortest() {
+        let expanded: BleId = "0000180f-0000-1000-8000-00805f9b34fb".parse().unwrap();
+        assert!(matches!(expanded.shortest(), BleId::FromUuid16(0x180F)));
+        assert!(matches!(
+            BleId::FromUuid32(0x0001_0000).shortest(),
+            BleId::FromUuid32(0x0001_0000)
+        ));
+        assert!(matches!(
+            BleId::FromUuid128([0xAB; 16]).shortest(),
+            BleId::FromUuid128([0xAB, ..])
+        ));
+        assert_eq!(BleId::FromUuid32(1).byte_size(), 4);
+    }
+
+    #[test]
+    fn ble_id_05_from_name_128() {
+        let id = BleId::from_name_128("temperature");
+        assert_eq!(id, BleId::from_name_128("temperature"));
+        assert_ne!(id, BleId::from_name_128("humidity"));
+        assert_eq!(
+            id.to_string(),
+            Uuid::new_v3(&Uuid::NAMESPACE_OID, b"temperature").to_string()
+        );
+    }
+}
@@ -82,6 +82,91 @@ impl StandardServiceId {
     pub fn byte_size(&self) -> usize {
         2
     }
+
+    /// Gets the standard service of a 16 bit UUID
+    ///
+    /// # Returns
+    ///
+    /// `Some(StandardServiceId)` if the UUID belongs to a standard service, `None` otherwise
+    pub fn from_uuid16(uuid: u16) -> Option<Self> {
+        match uuid {
+            0x1800 => Some(Self::GAP),
+            0x1801 => Some(Self::GATT),
+            0x1802 => Some(Self::ImmediateAlert),
+            0x1803 => Some(Self::LinkLoss),
+            0x1804 => Some(Self::TxPower),
+            0x1805 => Some(Self::CurrentTime),
+            0x1806 => Some(Self::ReferenceTimeUpdate),
+            0x1807 => Some(Self::NextDSTChange),
+            0x1808 => Some(Self::Glucose),
+            0x1809 => Some(Self::HealthThermometer),
+            0x180A => Some(Self::DeviceInformation),
+            0x180D => Some(Self::HeartRate),
+            0x180E => Some(Self::PhoneAlertStatus),
+            0x180F => Some(Self::Battery),
+            0x1810 => Some(Self::BloodPressure),
+            0x1811 => Some(Self::AlertNotification),
+            0x1812 => Some(Self::HumanInterfaceDevice),
+            0x1813 => Some(Self::ScanParameters),
+            0x1814 => Some(Self::RunningSpeedAndCadence),
+            0x1815 => Some(Self::AutomationIO),
+            0x1816 => Some(Self::CyclingSpeedAndCadence),
+            0x1818 => Some(Self::CyclingPower),
+            0x1819 => Some(Self::LocationAndNavigation),
+            0x181A => Some(Self::EnvironmentalSensing),
+            0x181B => Some(Self::BodyComposition),
+            0x181C => Some(Self::UserData),
+            0x181D => Some(Self::WeightScale),
+            0x181E => Some(Self::BondManagement),
+            0x181F => Some(Self::ContinuousGlucoseMonitoring),
+            0x1820 => Some(Self::InternetProtocolSupport),
+            0x1821 => Some(Self::IndoorPositioning),
+            0x1822 => Some(Self::PulseOximeter),
+            0x1823 => Some(Self::HTTPProxy),
+            0x1824 => Some(Self::TransportDiscovery),
+            0x1825 => Some(Self::ObjectTransfer),
+            0x1826 => Some(Self::FitnessMachine),
+            0x1827 => Some(Self::MeshProvisioning),
+            0x1828 => Some(Self::MeshProxy),
+            0x1829 => Some(Self::ReconnectionConfiguration),
+            0x183A => Some(Self::InsulinDelivery),
+            0x183B => Some(Self::BinarySensor),
+            0x183C => Some(Self::EmergencyConfiguration),
+            0x183D => Some(Self::AuthorizationControl),
+            0x183E => Some(Self::PhysicalActivityMonitor),
+            0x183F => Some(Self::ElapsedTime),
+            0x1840 => Some(Self::GenericHealthSensor),
+            0x1843 => Some(Self::AudioInputControl),
+            0x1844 => Some(Self::VolumeControl),
+            0x1845 => Some(Self::VolumeOffsetControl),
+            0x1846 => Some(Self::CoordinatedSetIdentification),
+            0x1847 => Some(Self::DeviceTime),
+            0x1848 => Some(Self::MediaControl),
+            0x1849 => Some(Self::GenericMediaControl),
+            0x184A => Some(Self::ConstantToneExtension),
+            0x184B => Some(Self::TelephoneBearer),
+            0x184C => Some(Self::GenericTelephoneBearer),
+            0x184D => Some(Self::MicrophoneControl),
+            0x184E => Some(Self::AudioStreamControl),
+            0x184F => Some(Self::BroadcastAudioScan),
+            0x1850 => Some(Self::PublishedAudioCapabilities),
+            0x1851 => Some(Self::BasicAudioAnnouncement),
+            0x1852 => Some(Self::BroadcastAudioAnnouncement),
+            0x1853 => Some(Self::CommonAudio),
+            0x1854 => Some(Self::HearingAccess),
+            0x1855 => Some(Self::TelephonyAndMediaAudio),
+            0x1856 => Some(Self::PublicBroadcastAnnouncement),
+            0x1857 => Some(Self::ElectronicShelfLabel),
+            0x1858 => Some(Self::GamingAudio),
+            0x1859 => Some(Self::MeshProxySolicitation),
+            _ => None,
+        }
+    }
+
+    /// Gets the name of the standard service
+    pub fn name(&self) -> String {
+        format!("{self:?}")
+    }
 }
 
 /// Enums the UUIDs of standard Bluetooth Low Energy (BLE) characteristics.
@@ -550,6 +635,473 @@ impl StandardCharacteristicId {
     pub fn byte_size(&self) -> usize {
         2
     }
+
+    /// Gets the standard characteristic of a 16 bit UUID
+    ///
+    /// # Returns
+    ///
+    /// `Some(StandardCharacteristicId)` if the UUID belongs to a standard characteristic, `None` otherwise
+    pub fn from_uuid16(uuid: u16) -> Option<Self> {
+        match uuid {
+            0x2A00 => Some(Self::DeviceName),
+            0x2A01 => Some(Self::Appearance),
+            0x2A02 => Some(Self::PeripheralPrivacyFlag),
+            0x2A03 => Some(Self::ReconnectionAddress),
+            0x2A04 => Some(Self::PeripheralPreferredConnectionParameters),
+            0x2A05 => Some(Self::ServiceChanged),
+            0x2A06 => Some(Self::AlertLevel),
+            0x2A07 => Some(Self::TxPowerLevel),
+            0x2A08 => Some(Self::DateTime),
+            0x2A09 => Some(Self::DayofWeek),
+            0x2A0A => Some(Self::DayDateTime),
+            0x2A0C => Some(Self::ExactTime256),
+            0x2A0D => Some(Self::DSTOffset),
+            0x2A0E => Some(Self::TimeZone),
+            0x2A0F => Some(Self::LocalTimeInformation),
+            0x2A11 => Some(Self::TimewithDST),
+            0x2A12 => Some(Self::TimeAccuracy),
+            0x2A13 => Some(Self::TimeSource),
+            0x2A14 => Some(Self::ReferenceTimeInformation),
+            0x2A16 => Some(Self::TimeUpdateControlPoint),
+            0x2A17 => Some(Self::TimeUpdateState),
+            0x2A18 => Some(Self::GlucoseMeasurement),
+            0x2A19 => Some(Self::BatteryLevel),
+            0x2A1C => Some(Self::TemperatureMeasurement),
+            0x2A1D => Some(Self::TemperatureType),
+            0x2A1E => Some(Self::IntermediateTemperature),
+            0x2A21 => Some(Self::MeasurementInterval),
+            0x2A22 => Some(Self::BootKeyboardInputReport),
+            0x2A23 => Some(Self::SystemID),
+            0x2A24 => Some(Self::ModelNumberString),
+            0x2A25 => Some(Self::SerialNumberString),
+            0x2A26 => Some(Self::FirmwareRevisionString),
+            0x2A27 => Some(Self::HardwareRevisionString),
+            0x2A28 => Some(Self::SoftwareRevisionString),
+            0x2A29 => Some(Self::ManufacturerNameString),
+            0x2A2A => Some(Self::IEEE1107320601RegulatoryCertificationDataList),
+            0x2A2B => Some(Self::CurrentTime),
+            0x2A2C => Some(Self::MagneticDeclination),
+            0x2A31 => Some(Self::ScanRefresh),
+            0x2A32 => Some(Self::BootKeyboardOutputReport),
+            0x2A33 => Some(Self::BootMouseInputReport),
+            0x2A34 => Some(Self::GlucoseMeasurementContext),
+            0x2A35 => Some(Self::BloodPressureMeasurement),
+            0x2A36 => Some(Self::IntermediateCuffPressure),
+            0x2A37 => Some(Self::HeartRateMeasurement),
+            0x2A38 => Some(Self::BodySensorLocation),
+            0x2A39 => Some(Self::HeartRateControlPoint),
+            0x2A3F => Some(Self::AlertStatus),
+            0x2A40 => Some(Self::RingerControlPoint),
+            0x2A41 => Some(Self::RingerSetting),
+            0x2A42 => Some(Self::AlertCategoryIDBitMask),
+            0x2A43 => Some(Self::AlertCategoryID),
+            0x2A44 => Some(Self::AlertNotificationControlPoint),
+            0x2A45 => Some(Self::UnreadAlertStatus),
+            0x2A46 => Some(Self::NewAlert),
+            0x2A47 => Some(Self::SupportedNewAlertCategory),
+            0x2A48 => Some(Self::SupportedUnreadAlertCategory),
+            0x2A49 => Some(Self::BloodPressureFeature),
+            0x2A4A => Some(Self::HIDInformation),
+            0x2A4B => Some(Self::ReportMap),
+            0x2A4C => Some(Self::HIDControlPoint),
+            0x2A4D => Some(Self::Report),
+            0x2A4E => Some(Self::ProtocolMode),
+            0x2A4F => Some(Self::ScanIntervalWindow),
+            0x2A50 => Some(Self::PnPID),
+            0x2A51 => Some(Self::GlucoseFeature),
+            0x2A52 => Some(Self::RecordAccessControlPoint),
+            0x2A53 => Some(Self::RSCMeasurement),
+            0x2A54 => Some(Self::RSCFeature),
+            0x2A55 => Some(Self::SCControlPoint),
+            0x2A5A => Some(Self::Aggregate),
+            0x2A5B => Some(Self::CSCMeasurement),
+            0x2A5C => Some(Self::CSCFeature),
+            0x2A5D => Some(Self::SensorLocation),
+            0x2A5E => Some(Self::PLXSpotCheckMeasurement),
+            0x2A5F => Some(Self::PLXContinuousMeasurement),
+            0x2A60 => Some(Self::PLXFeatures),
+            0x2A63 => Some(Self::CyclingPowerMeasurement),
+            0x2A64 => Some(Self::CyclingPowerVector),
+            0x2A65 => Some(Self::CyclingPowerFeature),
+            0x2A66 => Some(Self::CyclingPowerControlPoint),
+            0x2A67 => Some(Self::LocationandSpeed),
+            0x2A68 => Some(Self::Navigation),
+            0x2A69 => Some(Self::PositionQuality),
+            0x2A6A => Some(Self::LNFeature),
+            0x2A6B => Some(Self::LNControlPoint),
+            0x2A6C => Some(Self::Elevation),
+            0x2A6D => Some(Self::Pressure),
+            0x2A6E => Some(Self::Temperature),
+            0x2A6F => Some(Self::Humidity),
+            0x2A70 => Some(Self::TrueWindSpeed),
+            0x2A71 => Some(Self::TrueWindDirection),
+            0x2A72 => Some(Self::ApparentWindSpeed),
+            0x2A73 => Some(Self::ApparentWindDirection),
+            0x2A74 => Some(Self::GustFactor),
+            0x2A75 => Some(Self::PollenConcentration),
+            0x2A76 => Some(Self::UVIndex),
+            0x2A77 => Some(Self::Irradiance),
+            0x2A78 => Some(Self::Rainfall),
+            0x2A79 => Some(Self::WindChill),
+            0x2A7A => Some(Self::HeatIndex),
+            0x2A7B => Some(Self::DewPoint),
+            0x2A7D => Some(Self::DescriptorValueChanged),
+            0x2A7E => Some(Self::AerobicHeartRateLowerLimit),
+            0x2A7F => Some(Self::AerobicThreshold),
+            0x2A80 => Some(Self::Age),
+            0x2A81 => Some(Self::AnaerobicHeartRateLowerLimit),
+            0x2A82 => Some(Self::AnaerobicHeartRateUpperLimit),
+            0x2A83 => Some(Self::AnaerobicThreshold),
+            0x2A84 => Some(Self::AerobicHeartRateUpperLimit),
+            0x2A85 => Some(Self::DateofBirth),
+            0x2A86 => Some(Self::DateofThresholdAssessment),
+            0x2A87 => Some(Self::EmailAddress),
+            0x2A88 => Some(Self::FatBurnHeartRateLowerLimit),
+            0x2A89 => Some(Self::FatBurnHeartRateUpperLimit),
+            0x2A8A => Some(Self::FirstName),
+            0x2A8B => Some(Self::FiveZoneHeartRateLimits),
+            0x2A8C => Some(Self::Gender),
+            0x2A8D => Some(Self::HeartRateMax),
+            0x2A8E => Some(Self::Height),
+            0x2A8F => Some(Self::HipCircumference),
+            0x2A90 => Some(Self::LastName),
+            0x2A91 => Some(Self::MaximumRecommendedHeartRate),
+            0x2A92 => Some(Self::RestingHeartRate),
+            0x2A93 => Some(Self::SportTypeforAerobicandAnaerobicThresholds),
+            0x2A94 => Some(Self::ThreeZoneHeartRateLimits),
+            0x2A95 => Some(Self::TwoZoneHeartRateLimits),
+            0x2A96 => Some(Self::VO2Max),
+            0x2A97 => Some(Self::WaistCircumference),
+            0x2A98 => Some(Self::Weight),
+            0x2A99 => Some(Self::DatabaseChangeIncrement),
+            0x2A9A => Some(Self::UserIndex),
+            0x2A9B => Some(Self::BodyCompositionFeature),
+            0x2A9C => Some(Self::BodyCompositionMeasurement),
+            0x2A9D => Some(Self::WeightMeasurement),
+            0x2A9E => Some(Self::WeightScaleFeature),
+            0x2A9F => Some(Self::UserControlPoint),
+            0x2AA0 => Some(Self::MagneticFluxDensity2D),
+            0x2AA1 => Some(Self::MagneticFluxDensity3D),
+            0x2AA2 => Some(Self::Language),
+            0x2AA3 => Some(Self::BarometricPressureTrend),
+            0x2AA4 => Some(Self::BondManagementControlPoint),
+            0x2AA5 => Some(Self::BondManagementFeature),
+            0x2AA6 => Some(Self::CentralAddressResolution),
+            0x2AA7 => Some(Self::CGMMeasurement),
+            0x2AA8 => Some(Self::CGMFeature),
+            0x2AA9 => Some(Self::CGMStatus),
+            0x2AAA => Some(Self::CGMSessionStartTime),
+            0x2AAB => Some(Self::CGMSessionRunTime),
+            0x2AAC => Some(Self::CGMSpecificOpsControlPoint),
+            0x2AAD => Some(Self::IndoorPositioningConfiguration),
+            0x2AAE => Some(Self::Latitude),
+            0x2AAF => Some(Self::Longitude),
+            0x2AB0 => Some(Self::LocalNorthCoordinate),
+            0x2AB1 => Some(Self::LocalEastCoordinate),
+            0x2AB2 => Some(Self::FloorNumber),
+            0x2AB3 => Some(Self::Altitude),
+            0x2AB4 => Some(Self::Uncertainty),
+            0x2AB5 => Some(Self::LocationName),
+            0x2AB6 => Some(Self::URI),
+            0x2AB7 => Some(Self::HTTPHeaders),
+            0x2AB8 => Some(Self::HTTPStatusCode),
+            0x2AB9 => Some(Self::HTTPEntityBody),
+            0x2ABA => Some(Self::HTTPControlPoint),
+            0x2ABB => Some(Self::HTTPSSecurity),
+            0x2ABC => Some(Self::TDSControlPoint),
+            0x2ABD => Some(Self::OTSFeature),
+            0x2ABE => Some(Self::ObjectName),
+            0x2ABF => Some(Self::ObjectType),
+            0x2AC0 => Some(Self::ObjectSize),
+            0x2AC1 => Some(Self::ObjectFirstCreated),
+            0x2AC2 => Some(Self::ObjectLastModified),
+            0x2AC3 => Some(Self::ObjectID),
+            0x2AC4 => Some(Self::ObjectProperties),
+            0x2AC5 => Some(Self::ObjectActionControlPoint),
+            0x2AC6 => Some(Self::ObjectListControlPoint),
+            0x2AC7 => Some(Self::ObjectListFilter),
+            0x2AC8 => Some(Self::ObjectChanged),
+            0x2AC9 => Some(Self::ResolvablePrivateAddressOnly),
+            0x2ACC => Some(Self::FitnessMachineFeature),
+            0x2ACD => Some(Self::TreadmillData),
+            0x2ACE => Some(Self::CrossTrainerData),
+            0x2ACF => Some(Self::StepClimberData),
+            0x2AD0 => Some(Self::StairClimberData),
+            0x2AD1 => Some(Self::RowerData),
+            0x2AD2 => Some(Self::IndoorBikeData),
+            0x2AD3 => Some(Self::TrainingStatus),
+            0x2AD4 => Some(Self::SupportedSpeedRange),
+            0x2AD5 => Some(Self::SupportedInclinationRange),
+            0x2AD6 => Some(Self::SupportedResistanceLevelRange),
+            0x2AD7 => Some(Self::SupportedHeartRateRange),
+            0x2AD8 => Some(Self::SupportedPowerRange),
+            0x2AD9 => Some(Self::FitnessMachineControlPoint),
+            0x2ADA => Some(Self::FitnessMachineStatus),
+            0x2ADB => Some(Self::MeshProvisioningDataIn),
+            0x2ADC => Some(Self::MeshProvisioningDataOut),
+            0x2ADD => Some(Self::MeshProxyDataIn),
+            0x2ADE => Some(Self::MeshProxyDataOut),
+            0x2AE0 => Some(Self::AverageCurrent),
+            0x2AE1 => Some(Self::AverageVoltage),
+            0x2AE2 => Some(Self::Boolean),
+            0x2AE3 => Some(Self::ChromaticDistancefromPlanckian),
+            0x2AE4 => Some(Self::ChromaticityCoordinates),
+            0x2AE5 => Some(Self::ChromaticityinCCTandDuvValues),
+            0x2AE6 => Some(Self::ChromaticityTolerance),
+            0x2AE7 => Some(Self::CIE1331995ColorRenderingIndex),
+            0x2AE8 => Some(Self::Coefficient),
+            0x2AE9 => Some(Self::CorrelatedColorTemperature),
+            0x2AEA => Some(Self::Count16),
+            0x2AEB => Some(Self::Count24),
+            0x2AEC => Some(Self::CountryCode),
+            0x2AED => Some(Self::DateUTC),
+            0x2AEE => Some(Self::ElectricCurrent),
+            0x2AEF => Some(Self::ElectricCurrentRange),
+            0x2AF0 => Some(Self::ElectricCurrentSpecification),
+            0x2AF1 => Some(Self::ElectricCurrentStatistics),
+            0x2AF2 => Some(Self::Energy),
+            0x2AF3 => Some(Self::EnergyinaPeriodofDay),
+            0x2AF4 => Some(Self::EventStatistics),
+            0x2AF5 => Some(Self::FixedString16),
+            0x2AF6 => Some(Self::FixedString24),
+            0x2AF7 => Some(Self::FixedString36),
+            0x2AF8 => Some(Self::FixedString8),
+            0x2AF9 => Some(Self::GenericLevel),
+            0x2AFA => Some(Self::GlobalTradeItemNumber),
+            0x2AFB => Some(Self::Illuminance),
+            0x2AFC => Some(Self::LuminousEfficacy),
+            0x2AFD => Some(Self::LuminousEnergy),
+            0x2AFE => Some(Self::LuminousExposure),
+            0x2AFF => Some(Self::LuminousFlux),
+            0x2B00 => Some(Self::LuminousFluxRange),
+            0x2B01 => Some(Self::LuminousIntensity),
+            0x2B02 => Some(Self::MassFlow),
+            0x2B03 => Some(Self::PerceivedLightness),
+            0x2B04 => Some(Self::Percentage8),
+            0x2B05 => Some(Self::Power),
+            0x2B06 => Some(Self::PowerSpecification),
+            0x2B07 => Some(Self::RelativeRuntimeinaCurrentRange),
+            0x2B08 => Some(Self::RelativeRuntimeinaGenericLevelRange),
+            0x2B09 => Some(Self::RelativeValueinaVoltageRange),
+            0x2B0A => Some(Self::RelativeValueinanIlluminanceRange),
+            0x2B0B => Some(Self::RelativeValueinaPeriodofDay),
+            0x2B0C => Some(Self::RelativeValueinaTemperatureRange),
+            0x2B0D => Some(Self::Temperature8),
+            0x2B0E => Some(Self::Temperature8inaPeriodofDay),
+            0x2B0F => Some(Self::Temperature8Statistics),
+            0x2B10 => Some(Self::TemperatureRange),
+            0x2B11 => Some(Self::TemperatureStatistics),
+            0x2B12 => Some(Self::TimeDecihour8),
+            0x2B13 => Some(Self::TimeExponential8),
+            0x2B14 => Some(Self::TimeHour24),
+            0x2B15 => Some(Self::TimeMillisecond24),
+            0x2B16 => Some(Self::TimeSecond16),
+            0x2B17 => Some(Self::TimeSecond8),
+            0x2B18 => Some(Self::Voltage),
+            0x2B19 => Some(Self::VoltageSpecification),
+            0x2B1A => Some(Self::VoltageStatistics),
+            0x2B1B => Some(Self::VolumeFlow),
+            0x2B1C => Some(Self::ChromaticityCoordinate),
+            0x2B1D => Some(Self::RCFeature),
+            0x2B1E => Some(Self::RCSettings),
+            0x2B1F => Some(Self::ReconnectionConfigurationControlPoint),
+            0x2B20 => Some(Self::IDDStatusChanged),
+            0x2B21 => Some(Self::IDDStatus),
+            0x2B22 => Some(Self::IDDAnnunciationStatus),
+            0x2B23 => Some(Self::IDDFeatures),
+            0x2B24 => Some(Self::IDDStatusReaderControlPoint),
+            0x2B25 => Some(Self::IDDCommandControlPoint),
+            0x2B26 => Some(Self::IDDCommandData),
+            0x2B27 => Some(Self::IDDRecordAccessControlPoint),
+            0x2B28 => Some(Self::IDDHistoryData),
+            0x2B29 => Some(Self::ClientSupportedFeatures),
+            0x2B2A => Some(Self::DatabaseHash),
+            0x2B2B => Some(Self::BSSControlPoint),
+            0x2B2C => Some(Self::BSSResponse),
+            0x2B2D => Some(Self::EmergencyID),
+            0x2B2E => Some(Self::EmergencyText),
+            0x2B2F => Some(Self::ACSStatus),
+            0x2B30 => Some(Self::ACSDataIn),
+            0x2B31 => Some(Self::ACSDataOutNotify),
+            0x2B32 => Some(Self::ACSDataOutIndicate),
+            0x2B33 => Some(Self::ACSControlPoint),
+            0x2B34 => Some(Self::EnhancedBloodPressureMeasurement),
+            0x2B35 => Some(Self::EnhancedIntermediateCuffPressure),
+            0x2B36 => Some(Self::BloodPressureRecord),
+            0x2B37 => Some(Self::RegisteredUser),
+            0x2B38 => Some(Self::BREDRHandoverData),
+            0x2B39 => Some(Self::BluetoothSIGData),
+            0x2B3A => Some(Self::ServerSupportedFeatures),
+            0x2B3B => Some(Self::PhysicalActivityMonitorFeatures),
+            0x2B3C => Some(Self::GeneralActivityInstantaneousData),
+            0x2B3D => Some(Self::GeneralActivitySummaryData),
+            0x2B3E => Some(Self::CardioRespiratoryActivityInstantaneousData),
+            0x2B3F => Some(Self::CardioRespiratoryActivitySummaryData),
+            0x2B40 => Some(Self::StepCounterActivitySummaryData),
+            0x2B41 => Some(Self::SleepActivityInstantaneousData),
+            0x2B42 => Some(Self::SleepActivitySummaryData),
+            0x2B43 => Some(Self::PhysicalActivityMonitorControlPoint),
+            0x2B44 => Some(Self::PhysicalActivityCurrentSession),
+            0x2B45 => Some(Self::PhysicalActivitySessionDescriptor),
+            0x2B46 => Some(Self::PreferredUnits),
+            0x2B47 => Some(Self::HighResolutionHeight),
+            0x2B48 => Some(Self::MiddleName),
+            0x2B49 => Some(Self::StrideLength),
+            0x2B4A => Some(Self::Handedness),
+            0x2B4B => Some(Self::DeviceWearingPosition),
+            0x2B4C => Some(Self::FourZoneHeartRateLimits),
+            0x2B4D => Some(Self::HighIntensityExerciseThreshold),
+            0x2B4E => Some(Self::ActivityGoal),
+            0x2B4F => Some(Self::SedentaryIntervalNotification),
+            0x2B50 => Some(Self::CaloricIntake),
+            0x2B51 => Some(Self::TMAPRole),
+            0x2B77 => Some(Self::AudioInputState),
+            0x2B78 => Some(Self::GainSettingsAttribute),
+            0x2B79 => Some(Self::AudioInputType),
+            0x2B7A => Some(Self::AudioInputStatus),
+            0x2B7B => Some(Self::AudioInputControlPoint),
+            0x2B7C => Some(Self::AudioInputDescription),
+            0x2B7D => Some(Self::VolumeState),
+            0x2B7E => Some(Self::VolumeControlPoint),
+            0x2B7F => Some(Self::VolumeFlags),
+            0x2B80 => Some(Self::VolumeOffsetState),
+            0x2B81 => Some(Self::AudioLocation),
+            0x2B82 => Some(Self::VolumeOffsetControlPoint),
+            0x2B83 => Some(Self::AudioOutputDescription),
+            0x2B84 => Some(Self::SetIdentityResolvingKey),
+            0x2B85 => Some(Self::CoordinatedSetSize),
+            0x2B86 => Some(Self::SetMemberLock),
+            0x2B87 => Some(Self::SetMemberRank),
+            0x2B88 => Some(Self::EncryptedDataKeyMaterial),
+            0x2B89 => Some(Self::ApparentEnergy32),
+            0x2B8A => Some(Self::ApparentPower),
+            0x2B8B => Some(Self::LiveHealthObservations),
+            0x2B8C => Some(Self::COtextsubscript2Concentration),
+            0x2B8D => Some(Self::CosineoftheAngle),
+            0x2B8E => Some(Self::DeviceTimeFeature),
+            0x2B8F => Some(Self::DeviceTimeParameters),
+            0x2B90 => Some(Self::DeviceTime),
+            0x2B91 => Some(Self::DeviceTimeControlPoint),
+            0x2B92 => Some(Self::TimeChangeLogData),
+            0x2B93 => Some(Self::MediaPlayerName),
+            0x2B94 => Some(Self::MediaPlayerIconObjectID),
+            0x2B95 => Some(Self::MediaPlayerIconURL),
+            0x2B96 => Some(Self::TrackChanged),
+            0x2B97 => Some(Self::TrackTitle),
+            0x2B98 => Some(Self::TrackDuration),
+            0x2B99 => Some(Self::TrackPosition),
+            0x2B9A => Some(Self::PlaybackSpeed),
+            0x2B9B => Some(Self::SeekingSpeed),
+            0x2B9C => Some(Self::CurrentTrackSegmentsObjectID),
+            0x2B9D => Some(Self::CurrentTrackObjectID),
+            0x2B9E => Some(Self::NextTrackObjectID),
+            0x2B9F => Some(Self::ParentGroupObjectID),
+            0x2BA0 => Some(Self::CurrentGroupObjectID),
+            0x2BA1 => Some(Self::PlayingOrder),
+            0x2BA2 => Some(Self::PlayingOrdersSupported),
+            0x2BA3 => Some(Self::MediaState),
+            0x2BA4 => Some(Self::MediaControlPoint),
+            0x2BA5 => Some(Self::MediaControlPointOpcodesSupported),
+            0x2BA6 => Some(Self::SearchResultsObjectID),
+            0x2BA7 => Some(Self::SearchControlPoint),
+            0x2BA8 => Some(Self::Energy32),
+            0x2BAD => Some(Self::ConstantToneExtensionEnable),
+            0x2BAE => Some(Self::AdvertisingConstantToneExtensionMinimumLength),
+            0x2BAF => Some(Self::AdvertisingConstantToneExtensionMinimumTransmitCount),
+            0x2BB0 => Some(Self::AdvertisingConstantToneExtensionTransmitDuration),
+            0x2BB1 => Some(Self::AdvertisingConstantToneExtensionInterval),
+            0x2BB2 => Some(Self::AdvertisingConstantToneExtensionPHY),
+            0x2BB3 => Some(Self::BearerProviderName),
+            0x2BB4 => Some(Self::BearerUCI),
+            0x2BB5 => Some(Self::BearerTechnology),
+            0x2BB6 => Some(Self::BearerURISchemesSupportedList),
+            0x2BB7 => Some(Self::BearerSignalStrength),
+            0x2BB8 => Some(Self::BearerSignalStrengthReportingInterval),
+            0x2BB9 => Some(Self::BearerListCurrentCalls),
+            0x2BBA => Some(Self::ContentControlID),
+            0x2BBB => Some(Self::StatusFlags),
+            0x2BBC => Some(Self::IncomingCallTargetBearerURI),
+            0x2BBD => Some(Self::CallState),
+            0x2BBE => Some(Self::CallControlPoint),
+            0x2BBF => Some(Self::CallControlPointOptionalOpcodes),
+            0x2BC0 => Some(Self::TerminationReason),
+            0x2BC1 => Some(Self::IncomingCall),
+            0x2BC2 => Some(Self::CallFriendlyName),
+            0x2BC3 => Some(Self::Mute),
+            0x2BC4 => Some(Self::SinkASE),
+            0x2BC5 => Some(Self::SourceASE),
+            0x2BC6 => Some(Self::ASEControlPoint),
+            0x2BC7 => Some(Self::BroadcastAudioScanControlPoint),
+            0x2BC8 => Some(Self::BroadcastReceiveState),
+            0x2BC9 => Some(Self::SinkPAC),
+            0x2BCA => Some(Self::SinkAudioLocations),
+            0x2BCB => Some(Self::SourcePAC),
+            0x2BCC => Some(Self::SourceAudioLocations),
+            0x2BCD => Some(Self::AvailableAudioContexts),
+            0x2BCE => Some(Self::SupportedAudioContexts),
+            0x2BCF => Some(Self::AmmoniaConcentration),
+            0x2BD0 => Some(Self::CarbonMonoxideConcentration),
+            0x2BD1 => Some(Self::MethaneConcentration),
+            0x2BD2 => Some(Self::NitrogenDioxideConcentration),
+            0x2BD3 => Some(Self::NonMethaneVolatileOrganicCompoundsConcentration),
+            0x2BD4 => Some(Self::OzoneConcentration),
+            0x2BD5 => Some(Self::ParticulateMatterPM1Concentration),
+            0x2BD6 => Some(Self::ParticulateMatterPM25Concentration),
+            0x2BD7 => Some(Self::ParticulateMatterPM10Concentration),
+            0x2BD8 => Some(Self::SulfurDioxideConcentration),
+            0x2BD9 => Some(Self::SulfurHexafluorideConcentration),
+            0x2BDA => Some(Self::HearingAidFeatures),
+            0x2BDB => Some(Self::HearingAidPresetControlPoint),
+            0x2BDC => Some(Self::ActivePresetIndex),
+            0x2BDD => Some(Self::StoredHealthObservations),
+            0x2BDE => Some(Self::FixedString64),
+            0x2BDF => Some(Self::HighTemperature),
+            0x2BE0 => Some(Self::HighVoltage),
+            0x2BE1 => Some(Self::LightDistribution),
+            0x2BE2 => Some(Self::LightOutput),
+            0x2BE3 => Some(Self::LightSourceType),
+            0x2BE4 => Some(Self::Noise),
+            0x2BE5 => Some(Self::RelativeRuntimeinaCorrelatedColorTemperatureRange),
+            0x2BE6 => Some(Self::TimeSecond32),
+            0x2BE7 => Some(Self::VOCConcentration),
+            0x2BE8 => Some(Self::VoltageFrequency),
+            0x2BE9 => Some(Self::BatteryCriticalStatus),
+            0x2BEA => Some(Self::BatteryHealthStatus),
+            0x2BEB => Some(Self::BatteryHealthInformation),
+            0x2BEC => Some(Self::BatteryInformation),
+            0x2BED => Some(Self::BatteryLevelStatus),
+            0x2BEE => Some(Self::BatteryTimeStatus),
+            0x2BEF => Some(Self::EstimatedServiceDate),
+            0x2BF0 => Some(Self::BatteryEnergyStatus),
+            0x2BF1 => Some(Self::ObservationScheduleChanged),
+            0x2BF2 => Some(Self::CurrentElapsedTime),
+            0x2BF3 => Some(Self::HealthSensorFeatures),
+            0x2BF4 => Some(Self::GHSControlPoint),
+            0x2BF5 => Some(Self::LEGATTSecurityLevels),
+            0x2BF6 => Some(Self::ESLAddress),
+            0x2BF7 => Some(Self::APSyncKeyMaterial),
+            0x2BF8 => Some(Self::ESLResponseKeyMaterial),
+            0x2BF9 => Some(Self::ESLCurrentAbsoluteTime),
+            0x2BFA => Some(Self::ESLDisplayInformation),
+            0x2BFB => Some(Self::ESLImageInformation),
+            0x2BFC => Some(Self::ESLSensorInformation),
+            0x2BFD => Some(Self::ESLLEDInformation),
+            0x2BFE => Some(Self::ESLControlPoint),
+            0x2BFF => Some(Self::UDIforMedicalDevices),
+            0x2C00 => Some(Self::GMAPRole),
+            0x2C01 => Some(Self::UGGFeatures),
+            0x2C02 => Some(Self::UGTFeatures),
+            0x2C03 => Some(Self::BGSFeatures),
+            0x2C04 => Some(Self::BGRFeatures),
+            0x2C05 => Some(Self::Percentage8Steps),
+            _ => None,
+        }
+    }
+
+    /// Gets the name of the standard characteristic
+    pub fn name(&self) -> String {
+        format!("{self:?}")
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -578,4 +1130,38 @@ impl StandardDescriptorId {
     pub fn byte_size(&self) -> usize {
         2
     }
+
+    /// Gets the standard descriptor of a 16 bit UUID
+    ///
+    /// # Returns
+    ///
+    /// `Some(StandardDescriptorId)` if the UUID belongs to a standard descriptor, `None` otherwise
+    pub fn from_uuid16(uuid: u16) -> Option<Self> {
+        match uuid {
+            0x2900 => Some(Self::CharacteristicExtendedProperties),
+            0x2901 => Some(Self::CharacteristicUserDescription),
+            0x2902 => Some(Self::ClientCharacteristicConfiguration),
+            0x2903 => Some(Self::ServerCharacteristicConfiguration),
+            0x2904 => Some(Self::CharacteristicPresentationFormat),
+            0x2905 => Some(Self::CharacteristicAggregateFormat),
+            0x2906 => Some(Self::ValidRange),
+            0x2907 => Some(Self::ExternalReportReference),
+            0x2908 => Some(Self::ReportReference),
+            0x2909 => Some(Self::NumberofDigitals),
+            0x290A => Some(Self::ValueTriggerSetting),
+            0x290B => Some(Self::EnvironmentalSensingConfiguration),
+            0x290C => Some(Self::EnvironmentalSensingMeasurement),
+            0x290D => Some(Self::EnvironmentalSensingTriggerSetting),
+            0x290E => Some(Self::TimeTriggerSetting),
+            0x290F => Some(Self::CompleteBREDRTransportBlockData),
+            0x2910 => Some(Self::ObservationSchedule),
+            0x2911 => Some(Self::ValidRangeandAccuracy),
+            _ => None,
+        }
+    }
+
+    /// Gets the name of the standard descriptor
+    pub fn name(&self) -> String {
+        format!("{self:?}")
+    }
 }
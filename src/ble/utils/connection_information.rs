@@ -1,4 +1,7 @@
 use esp32_nimble::{BLEAddress, BLEConnDesc, BLEError};
+use esp_idf_svc::sys;
+
+use super::{link_parameters, BleError, BlePhy, DEFAULT_MTU};
 
 /// Contains information about the new client connected that can be user on
 /// connection or disconnection callbacks.
@@ -10,6 +13,10 @@ use esp32_nimble::{BLEAddress, BLEConnDesc, BLEError};
 /// - `timeout`: A `u16` representing the connection timeout, measured in units of 10 ms. If no data is received during this time, the connection is considered lost.
 /// - `latency`: A `u16` representing the connection latency, indicating the number of connection intervals that can be skipped by the slave if it has no data to send.
 /// - `mtu`: A `u16` representing the Maximum Transmission Unit, the maximum size of data that can be sent in a single transmission. This includes the payload plus protocol headers.
+/// - `tx_phy`: The `BlePhy` used to send, changed with `set_preferred_phy`.
+/// - `rx_phy`: The `BlePhy` used to receive.
+/// - `max_tx_octets`: A `u16` with the biggest payload of the link layer packets sent, bigger than 27 bytes once the Data Length Extension is negotiated with `set_data_length`.
+/// - `max_rx_octets`: A `u16` with the biggest payload of the link layer packets received.
 /// - `bonded`: A `bool` indicating whether the connection is bonded, meaning the devices have exchanged security keys for secure future connections.
 /// - `encrypted`: A `bool` indicating whether the connection is encrypted, meaning the transmitted data is protected against eavesdropping.
 /// - `authenticated`: A `bool` indicating whether the connection is authenticated, meaning an authentication process has verified the identity of the devices.
@@ -25,6 +32,10 @@ pub struct ConnectionInformation {
     pub timeout: u16,
     pub latency: u16,
     pub mtu: u16,
+    pub tx_phy: BlePhy,
+    pub rx_phy: BlePhy,
+    pub max_tx_octets: u16,
+    pub max_rx_octets: u16,
     pub bonded: bool,
     pub encrypted: bool,
     pub authenticated: bool,
@@ -63,6 +74,8 @@ impl ConnectionInformation {
             Err(err) => Err(err.code()),
         };
 
+        let link = link_parameters(desc.conn_handle());
+
         ConnectionInformation {
             address: desc.address(),
             id_address: desc.id_address(),
@@ -71,6 +84,10 @@ impl ConnectionInformation {
             timeout: desc.timeout(),
             latency: desc.latency(),
             mtu: desc.mtu(),
+            tx_phy: link.tx_phy,
+            rx_phy: link.rx_phy,
+            max_tx_octets: link.max_tx_octets,
+            max_rx_octets: link.max_rx_octets,
             bonded: desc.bonded(),
             encrypted: desc.encrypted(),
             authenticated: desc.authenticated(),
@@ -79,4 +96,69 @@ impl ConnectionInformation {
             disconnection_result: res,
         }
     }
+
+    /// Creates a ConnectionInformation of an open connection from the descriptor of the BLE stack
+    ///
+    /// # Arguments
+    ///
+    /// - `desc`: The descriptor of the connection
+    ///
+    /// # Returns
+    ///
+    /// A new ConnectionInformation
+    pub(crate) fn from_gap_desc(desc: &sys::ble_gap_conn_desc) -> Self {
+        let mut rssi: i8 = 0;
+        let rssi = match unsafe { sys::ble_gap_conn_rssi(desc.conn_handle, &mut rssi) } {
+            0 => Ok(rssi),
+            rc => Err(rc as u32),
+        };
+
+        let link = link_parameters(desc.conn_handle);
+
+        ConnectionInformation {
+            address: BLEAddress::from(desc.peer_ota_addr),
+            id_address: BLEAddress::from(desc.peer_id_addr),
+            conn_handle: desc.conn_handle,
+            interval: desc.conn_itvl,
+            timeout: desc.supervision_timeout,
+            latency: desc.conn_latency,
+            mtu: connection_mtu(desc.conn_handle),
+            tx_phy: link.tx_phy,
+            rx_phy: link.rx_phy,
+            max_tx_octets: link.max_tx_octets,
+            max_rx_octets: link.max_rx_octets,
+            bonded: desc.sec_state.bonded() != 0,
+            encrypted: desc.sec_state.encrypted() != 0,
+            authenticated: desc.sec_state.authenticated() != 0,
+            sec_key_size: desc.sec_state.key_size(),
+            rssi,
+            disconnection_result: None,
+        }
+    }
+}
+
+/// Finds the descriptor of the open connection with the device of the given address
+///
+/// # Errors
+///
+/// - `BleError::NotFound`: If there is no connection with the device
+pub(crate) fn find_connection(address: &BLEAddress) -> Result<sys::ble_gap_conn_desc, BleError> {
+    let addr = sys::ble_addr_t {
+        type_: address.addr_type() as u8,
+        val: address.val(),
+    };
+    let mut desc = sys::ble_gap_conn_desc::default();
+    let rc = unsafe { sys::ble_gap_conn_find_by_addr(&addr, &mut desc) };
+    if rc != 0 {
+        return Err(BleError::NotFound);
+    }
+    Ok(desc)
+}
+
+/// Gets the ATT MTU of a connection, or the minimum MTU if it was not exchanged
+pub(crate) fn connection_mtu(conn_handle: u16) -> u16 {
+    match unsafe { sys::ble_att_mtu(conn_handle) } {
+        0 => DEFAULT_MTU,
+        mtu => mtu,
+    }
 }
@@ -0,0 +1,698 @@
+use sha2::{Digest, Sha256};
+
+const START_OPCODE: u8 = 0x01;
+const APPLY_OPCODE: u8 = 0x02;
+const ABORT_OPCODE: u8 = 0x03;
+
+const READY_OPCODE: u8 = 0x81;
+const ACK_OPCODE: u8 = 0x82;
+const VERIFIED_OPCODE: u8 = 0x83;
+const REBOOTING_OPCODE: u8 = 0x84;
+const ABORTED_OPCODE: u8 = 0x85;
+const ERROR_OPCODE: u8 = 0x8F;
+
+/// Bytes of the offset that precedes the payload of every data chunk
+pub(crate) const CHUNK_HEADER_SIZE: usize = 4;
+/// Bytes of the ATT header of a write, the rest of the MTU is payload
+const ATT_HEADER_SIZE: usize = 3;
+
+/// Size and checksums of a firmware image, sent by the client to start or resume a transfer.
+///
+/// - `size`: The size of the image in bytes
+/// - `crc32`: The CRC-32 (IEEE) of the image
+/// - `sha256`: The SHA-256 digest of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub size: u32,
+    pub crc32: u32,
+    pub sha256: [u8; 32],
+}
+
+impl ImageInfo {
+    /// Creates the ImageInfo of a firmware image
+    pub fn from_image(image: &[u8]) -> Self {
+        Self {
+            size: image.len() as u32,
+            crc32: crc32fast::hash(image),
+            sha256: Sha256::digest(image).into(),
+        }
+    }
+}
+
+/// Commands written by the client on the control characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DfuCommand {
+    /// Starts a transfer, or resumes it if the image is the one being received
+    Start(ImageInfo),
+    /// Boots into the received image once it was verified
+    Apply,
+    /// Cancels the transfer, discarding the received data
+    Abort,
+}
+
+impl DfuCommand {
+    /// Gets the bytes written on the control characteristic
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            DfuCommand::Start(info) => {
+                let mut bytes = vec![START_OPCODE];
+                bytes.extend_from_slice(&info.size.to_le_bytes());
+                bytes.extend_from_slice(&info.crc32.to_le_bytes());
+                bytes.extend_from_slice(&info.sha256);
+                bytes
+            }
+            DfuCommand::Apply => vec![APPLY_OPCODE],
+            DfuCommand::Abort => vec![ABORT_OPCODE],
+        }
+    }
+
+    /// Parses the bytes written on the control characteristic
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the command, or `None` if the bytes are not a valid command
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [START_OPCODE, rest @ ..] if rest.len() == 40 => Some(DfuCommand::Start(ImageInfo {
+                size: u32::from_le_bytes(rest[0..4].try_into().ok()?),
+                crc32: u32::from_le_bytes(rest[4..8].try_into().ok()?),
+                sha256: rest[8..40].try_into().ok()?,
+            })),
+            [APPLY_OPCODE] => Some(DfuCommand::Apply),
+            [ABORT_OPCODE] => Some(DfuCommand::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons a firmware update fails, reported by the server on the status characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DfuErrorCode {
+    InvalidCommand = 0x01,
+    NotStarted = 0x02,
+    TooLarge = 0x03,
+    InvalidChunk = 0x04,
+    CrcMismatch = 0x05,
+    ShaMismatch = 0x06,
+    FlashError = 0x07,
+    Incomplete = 0x08,
+}
+
+impl DfuErrorCode {
+    /// Gets the DfuErrorCode of a code, unknown codes are taken as [DfuErrorCode::InvalidCommand]
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x02 => DfuErrorCode::NotStarted,
+            0x03 => DfuErrorCode::TooLarge,
+            0x04 => DfuErrorCode::InvalidChunk,
+            0x05 => DfuErrorCode::CrcMismatch,
+            0x06 => DfuErrorCode::ShaMismatch,
+            0x07 => DfuErrorCode::FlashError,
+            0x08 => DfuErrorCode::Incomplete,
+            _ => DfuErrorCode::InvalidCommand,
+        }
+    }
+}
+
+/// Status notified by the server on the status characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DfuStatus {
+    /// The transfer can continue from `offset`, sending up to `window` chunks before
+    /// waiting for an acknowledgement
+    Ready { offset: u32, window: u16 },
+    /// Every byte before `offset` was written. Also sent when a chunk arrives out of order,
+    /// so the client resends from `offset`.
+    Ack { offset: u32 },
+    /// The whole image was received and its checksums match
+    Verified,
+    /// The image was set as the boot image and the device is restarting
+    Rebooting,
+    /// The transfer was cancelled
+    Aborted,
+    /// The last command or chunk failed
+    Error(DfuErrorCode),
+}
+
+impl DfuStatus {
+    /// Gets the bytes notified on the status characteristic
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            DfuStatus::Ready { offset, window } => {
+                let mut bytes = vec![READY_OPCODE];
+                bytes.extend_from_slice(&offset.to_le_bytes());
+                bytes.extend_from_slice(&window.to_le_bytes());
+                bytes
+            }
+            DfuStatus::Ack { offset } => {
+                let mut bytes = vec![ACK_OPCODE];
+                bytes.extend_from_slice(&offset.to_le_bytes());
+                bytes
+            }
+            DfuStatus::Verified => vec![VERIFIED_OPCODE],
+            DfuStatus::Rebooting => vec![REBOOTING_OPCODE],
+            DfuStatus::Aborted => vec![ABORTED_OPCODE],
+            DfuStatus::Error(code) => vec![ERROR_OPCODE, code as u8],
+        }
+    }
+
+    /// Parses the bytes notified on the status characteristic
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the status, or `None` if the bytes are not a valid status
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [READY_OPCODE, o0, o1, o2, o3, w0, w1] => Some(DfuStatus::Ready {
+                offset: u32::from_le_bytes([*o0, *o1, *o2, *o3]),
+                window: u16::from_le_bytes([*w0, *w1]),
+            }),
+            [ACK_OPCODE, o0, o1, o2, o3] => Some(DfuStatus::Ack {
+                offset: u32::from_le_bytes([*o0, *o1, *o2, *o3]),
+            }),
+            [VERIFIED_OPCODE] => Some(DfuStatus::Verified),
+            [REBOOTING_OPCODE] => Some(DfuStatus::Rebooting),
+            [ABORTED_OPCODE] => Some(DfuStatus::Aborted),
+            [ERROR_OPCODE, code] => Some(DfuStatus::Error(DfuErrorCode::from_code(*code))),
+            _ => None,
+        }
+    }
+}
+
+/// Where a [DfuReceiver] writes the received image. Bytes are always written in order.
+pub(crate) trait FirmwareSink {
+    /// Prepares the sink to receive an image of `size` bytes
+    fn begin(&mut self, size: u32) -> Result<(), DfuErrorCode>;
+    /// Writes the next bytes of the image
+    fn write(&mut self, data: &[u8]) -> Result<(), DfuErrorCode>;
+    /// Makes the received image the one to boot
+    fn finish(&mut self) -> Result<(), DfuErrorCode>;
+    /// Discards the received image
+    fn abort(&mut self);
+}
+
+/// Transfer in progress on a [DfuReceiver]
+struct Transfer {
+    info: ImageInfo,
+    offset: u32,
+    unacked: u16,
+    resyncing: bool,
+    verified: bool,
+    crc: crc32fast::Hasher,
+    sha: Sha256,
+}
+
+impl Transfer {
+    /// Creates a new Transfer of an image, with nothing received yet
+    fn new(info: ImageInfo) -> Self {
+        Self {
+            info,
+            offset: 0,
+            unacked: 0,
+            resyncing: false,
+            verified: false,
+            crc: crc32fast::Hasher::new(),
+            sha: Sha256::new(),
+        }
+    }
+}
+
+/// Server side of the firmware update protocol. It writes the chunks received in order into a
+/// [FirmwareSink], acknowledging every `window` chunks, and verifies the checksums of the
+/// image once complete. The transfer survives disconnections: starting again with the same
+/// image resumes it from the last byte written.
+pub(crate) struct DfuReceiver<S: FirmwareSink> {
+    sink: S,
+    max_size: u32,
+    window: u16,
+    transfer: Option<Transfer>,
+}
+
+impl<S: FirmwareSink> DfuReceiver<S> {
+    /// Creates a new DfuReceiver
+    ///
+    /// # Arguments
+    ///
+    /// - `sink`: Where the image is written
+    /// - `max_size`: The biggest image accepted
+    /// - `window`: The amount of chunks the client can send before waiting for an acknowledgement
+    pub(crate) fn new(sink: S, max_size: u32, window: u16) -> Self {
+        Self {
+            sink,
+            max_size,
+            window: window.max(1),
+            transfer: None,
+        }
+    }
+
+    /// Gets the amount of bytes received of the current image and its size, if there is a
+    /// transfer in progress
+    pub(crate) fn progress(&self) -> Option<(u32, u32)> {
+        self.transfer
+            .as_ref()
+            .map(|transfer| (transfer.offset, transfer.info.size))
+    }
+
+    /// Handles a write on the control characteristic
+    ///
+    /// # Returns
+    ///
+    /// The statuses to notify to the client, in order
+    pub(crate) fn handle_control(&mut self, bytes: &[u8]) -> Vec<DfuStatus> {
+        match DfuCommand::from_bytes(bytes) {
+            Some(DfuCommand::Start(info)) => self.start(info),
+            Some(DfuCommand::Apply) => self.apply(),
+            Some(DfuCommand::Abort) => {
+                self.abort();
+                vec![DfuStatus::Aborted]
+            }
+            None => vec![DfuStatus::Error(DfuErrorCode::InvalidCommand)],
+        }
+    }
+
+    /// Handles a write on the data characteristic
+    ///
+    /// # Returns
+    ///
+    /// The statuses to notify to the client, in order
+    pub(crate) fn handle_data(&mut self, bytes: &[u8]) -> Vec<DfuStatus> {
+        let Some(transfer) = self.transfer.as_mut() else {
+            return vec![DfuStatus::Error(DfuErrorCode::NotStarted)];
+        };
+        if bytes.len() <= CHUNK_HEADER_SIZE {
+            return vec![DfuStatus::Error(DfuErrorCode::InvalidChunk)];
+        }
+        let (header, payload) = bytes.split_at(CHUNK_HEADER_SIZE);
+        let offset = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+
+        if offset != transfer.offset || transfer.verified {
+            // Only the first chunk out of order is answered, the rest of the window is ignored
+            if transfer.resyncing || transfer.verified {
+                return vec![];
+            }
+            transfer.resyncing = true;
+            transfer.unacked = 0;
+            return vec![DfuStatus::Ack {
+                offset: transfer.offset,
+            }];
+        }
+        if offset as u64 + payload.len() as u64 > transfer.info.size as u64 {
+            return vec![DfuStatus::Error(DfuErrorCode::InvalidChunk)];
+        }
+        if let Err(err) = self.sink.write(payload) {
+            self.abort();
+            return vec![DfuStatus::Error(err)];
+        }
+        transfer.crc.update(payload);
+        transfer.sha.update(payload);
+        transfer.offset += payload.len() as u32;
+        transfer.resyncing = false;
+        transfer.unacked += 1;
+
+        if transfer.offset == transfer.info.size {
+            return self.verify();
+        }
+        if transfer.unacked >= self.window {
+            transfer.unacked = 0;
+            return vec![DfuStatus::Ack {
+                offset: transfer.offset,
+            }];
+        }
+        vec![]
+    }
+
+    /// Starts a new transfer, or resumes the current one if the image is the same
+    fn start(&mut self, info: ImageInfo) -> Vec<DfuStatus> {
+        if let Some(transfer) = self.transfer.as_mut() {
+            if transfer.info == info {
+                transfer.unacked = 0;
+                transfer.resyncing = false;
+                let mut statuses = vec![DfuStatus::Ready {
+                    offset: transfer.offset,
+                    window: self.window,
+                }];
+                if transfer.verified {
+                    statuses.push(DfuStatus::Verified);
+                }
+                return statuses;
+            }
+            self.abort();
+        }
+        if info.size == 0 || info.size > self.max_size {
+            return vec![DfuStatus::Error(DfuErrorCode::TooLarge)];
+        }
+        if let Err(err) = self.sink.begin(info.size) {
+            return vec![DfuStatus::Error(err)];
+        }
+        self.transfer = Some(Transfer::new(info));
+        vec![DfuStatus::Ready {
+            offset: 0,
+            window: self.window,
+        }]
+    }
+
+    /// Checks the checksums of the complete image, discarding it if they do not match
+    fn verify(&mut self) -> Vec<DfuStatus> {
+        let Some(transfer) = self.transfer.as_mut() else {
+            return vec![DfuStatus::Error(DfuErrorCode::NotStarted)];
+        };
+        let crc = std::mem::take(&mut transfer.crc).finalize();
+        let sha: [u8; 32] = std::mem::take(&mut transfer.sha).finalize().into();
+        let error = if crc != transfer.info.crc32 {
+            Some(DfuErrorCode::CrcMismatch)
+        } else if sha != transfer.info.sha256 {
+            Some(DfuErrorCode::ShaMismatch)
+        } else {
+            None
+        };
+        if let Some(err) = error {
+            self.abort();
+            return vec![DfuStatus::Error(err)];
+        }
+        transfer.verified = true;
+        vec![
+            DfuStatus::Ack {
+                offset: transfer.offset,
+            },
+            DfuStatus::Verified,
+        ]
+    }
+
+    /// Makes the verified image the one to boot
+    fn apply(&mut self) -> Vec<DfuStatus> {
+        match self.transfer.as_ref() {
+            None => vec![DfuStatus::Error(DfuErrorCode::NotStarted)],
+            Some(transfer) if !transfer.verified => {
+                vec![DfuStatus::Error(DfuErrorCode::Incomplete)]
+            }
+            Some(_) => {
+                self.transfer = None;
+                match self.sink.finish() {
+                    Ok(()) => vec![DfuStatus::Rebooting],
+                    Err(err) => vec![DfuStatus::Error(err)],
+                }
+            }
+        }
+    }
+
+    /// Discards the current transfer, if any
+    fn abort(&mut self) {
+        if self.transfer.take().is_some() {
+            self.sink.abort();
+        }
+    }
+}
+
+/// Result of handling a status on a [DfuSender]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DfuProgress {
+    /// More chunks must be sent
+    Sending,
+    /// The server verified the image, the apply command can be sent
+    Verified,
+    /// The server is rebooting into the new image
+    Done,
+}
+
+/// Client side of the firmware update protocol. It splits the image in chunks that fit the MTU
+/// and sends them in windows, waiting for the server to acknowledge each window before going on.
+pub(crate) struct DfuSender<'i> {
+    image: &'i [u8],
+    info: ImageInfo,
+    chunk_size: usize,
+    next: u32,
+    acked: u32,
+    window: u16,
+    in_flight: u16,
+}
+
+impl<'i> DfuSender<'i> {
+    /// Creates a new DfuSender
+    ///
+    /// # Arguments
+    ///
+    /// - `image`: The firmware image to send
+    /// - `mtu`: The MTU of the connection
+    pub(crate) fn new(image: &'i [u8], mtu: u16) -> Self {
+        Self {
+            image,
+            info: ImageInfo::from_image(image),
+            chunk_size: (mtu as usize)
+                .saturating_sub(ATT_HEADER_SIZE + CHUNK_HEADER_SIZE)
+                .max(1),
+            next: 0,
+            acked: 0,
+            window: 0,
+            in_flight: 0,
+        }
+    }
+
+    /// Gets the command that starts or resumes the transfer. Until the server answers with
+    /// [DfuStatus::Ready], no chunk is sent.
+    pub(crate) fn start_command(&mut self) -> Vec<u8> {
+        self.window = 0;
+        self.in_flight = 0;
+        DfuCommand::Start(self.info).to_bytes()
+    }
+
+    /// Gets the amount of bytes acknowledged by the server and the size of the image
+    pub(crate) fn progress(&self) -> (u32, u32) {
+        (self.acked, self.info.size)
+    }
+
+    /// Gets the next chunk to write on the data characteristic
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the chunk, or `None` if the window is full or every chunk was sent
+    pub(crate) fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        if self.in_flight >= self.window || self.next >= self.info.size {
+            return None;
+        }
+        let start = self.next as usize;
+        let end = (start + self.chunk_size).min(self.image.len());
+        let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + end - start);
+        chunk.extend_from_slice(&self.next.to_le_bytes());
+        chunk.extend_from_slice(&self.image[start..end]);
+        self.next = end as u32;
+        self.in_flight += 1;
+        Some(chunk)
+    }
+
+    /// Handles a status notified by the server
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the [DfuProgress] of the transfer, or the `DfuErrorCode` reported by the
+    /// server. An aborted transfer is reported as [DfuErrorCode::NotStarted].
+    pub(crate) fn handle_status(&mut self, status: DfuStatus) -> Result<DfuProgress, DfuErrorCode> {
+        match status {
+            DfuStatus::Ready { offset, window } => {
+                self.window = window.max(1);
+                self.resend_from(offset);
+                Ok(DfuProgress::Sending)
+            }
+            DfuStatus::Ack { offset } => {
+                self.resend_from(offset);
+                Ok(DfuProgress::Sending)
+            }
+            DfuStatus::Verified => Ok(DfuProgress::Verified),
+            DfuStatus::Rebooting => Ok(DfuProgress::Done),
+            DfuStatus::Aborted => Err(DfuErrorCode::NotStarted),
+            DfuStatus::Error(err) => Err(err),
+        }
+    }
+
+    /// Continues sending from the offset the server expects
+    fn resend_from(&mut self, offset: u32) {
+        let offset = offset.min(self.info.size);
+        self.acked = offset;
+        self.next = offset;
+        self.in_flight = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct MemorySink {
+        data: Vec<u8>,
+        finished: bool,
+        aborted: bool,
+    }
+
+    impl FirmwareSink for MemorySink {
+        fn begin(&mut self, _size: u32) -> Result<(), DfuErrorCode> {
+            self.data.clear();
+            Ok(())
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<(), DfuErrorCode> {
+            self.data.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), DfuErrorCode> {
+            self.finished = true;
+            Ok(())
+        }
+
+        fn abort(&mut self) {
+            self.aborted = true;
+        }
+    }
+
+    fn image(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    /// Runs the sender against the receiver until the image is verified
+    fn transfer(
+        receiver: &mut DfuReceiver<MemorySink>,
+        sender: &mut DfuSender,
+    ) -> Result<(), DfuErrorCode> {
+        let mut statuses = receiver.handle_control(&sender.start_command());
+        loop {
+            for status in statuses.drain(..) {
+                if sender.handle_status(status)? == DfuProgress::Verified {
+                    return Ok(());
+                }
+            }
+            while let Some(chunk) = sender.next_chunk() {
+                statuses.extend(receiver.handle_data(&chunk));
+            }
+        }
+    }
+
+    #[test]
+    fn dfu_01_messages_round_trip() {
+        let command = DfuCommand::Start(ImageInfo::from_image(b"firmware"));
+        assert_eq!(DfuCommand::from_bytes(&command.to_bytes()), Some(command));
+        assert_eq!(DfuCommand::from_bytes(&[APPLY_OPCODE, 0]), None);
+        for status in [
+            DfuStatus::Ready {
+                offset: 70000,
+                window: 8,
+            },
+            DfuStatus::Ack { offset: 1234 },
+            DfuStatus::Verified,
+            DfuStatus::Error(DfuErrorCode::ShaMismatch),
+        ] {
+            assert_eq!(DfuStatus::from_bytes(&status.to_bytes()), Some(status));
+        }
+    }
+
+    #[test]
+    fn dfu_02_complete_transfer_and_apply() {
+        let image = image(1000);
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 4096, 4);
+        let mut sender = DfuSender::new(&image, 23);
+        assert_eq!(transfer(&mut receiver, &mut sender), Ok(()));
+        assert_eq!(receiver.sink.data, image);
+        assert_eq!(sender.progress(), (1000, 1000));
+        assert_eq!(
+            receiver.handle_control(&DfuCommand::Apply.to_bytes()),
+            vec![DfuStatus::Rebooting]
+        );
+        assert!(receiver.sink.finished);
+    }
+
+    #[test]
+    fn dfu_03_acknowledges_every_window() {
+        let image = image(100);
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 4096, 2);
+        let mut sender = DfuSender::new(&image, 23);
+        receiver.handle_control(&sender.start_command());
+        sender
+            .handle_status(DfuStatus::Ready {
+                offset: 0,
+                window: 2,
+            })
+            .unwrap();
+        assert!(receiver
+            .handle_data(&sender.next_chunk().unwrap())
+            .is_empty());
+        assert_eq!(
+            receiver.handle_data(&sender.next_chunk().unwrap()),
+            vec![DfuStatus::Ack { offset: 32 }]
+        );
+        assert_eq!(sender.next_chunk(), None);
+    }
+
+    #[test]
+    fn dfu_04_resumes_after_disconnect() {
+        let image = image(500);
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 4096, 4);
+        let mut sender = DfuSender::new(&image, 23);
+        let ready = receiver.handle_control(&sender.start_command());
+        sender.handle_status(ready[0]).unwrap();
+        for _ in 0..3 {
+            receiver.handle_data(&sender.next_chunk().unwrap());
+        }
+        // The connection drops: a new sender starts again with the same image
+        let mut sender = DfuSender::new(&image, 23);
+        assert_eq!(
+            receiver.handle_control(&sender.start_command()),
+            vec![DfuStatus::Ready {
+                offset: 48,
+                window: 4
+            }]
+        );
+        assert_eq!(transfer(&mut receiver, &mut sender), Ok(()));
+        assert_eq!(receiver.sink.data, image);
+    }
+
+    #[test]
+    fn dfu_05_resyncs_on_lost_chunk() {
+        let image = image(200);
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 4096, 4);
+        let mut sender = DfuSender::new(&image, 23);
+        let ready = receiver.handle_control(&sender.start_command());
+        sender.handle_status(ready[0]).unwrap();
+        receiver.handle_data(&sender.next_chunk().unwrap());
+        sender.next_chunk();
+        assert_eq!(
+            receiver.handle_data(&sender.next_chunk().unwrap()),
+            vec![DfuStatus::Ack { offset: 16 }]
+        );
+        assert!(receiver
+            .handle_data(&sender.next_chunk().unwrap())
+            .is_empty());
+        sender.handle_status(DfuStatus::Ack { offset: 16 }).unwrap();
+        assert_eq!(transfer(&mut receiver, &mut sender), Ok(()));
+        assert_eq!(receiver.sink.data, image);
+    }
+
+    #[test]
+    fn dfu_06_rejects_corrupted_image() {
+        let image = image(64);
+        let mut info = ImageInfo::from_image(&image);
+        info.sha256[0] ^= 0xFF;
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 4096, 8);
+        receiver.handle_control(&DfuCommand::Start(info).to_bytes());
+        let mut chunk = 0u32.to_le_bytes().to_vec();
+        chunk.extend_from_slice(&image);
+        assert_eq!(
+            receiver.handle_data(&chunk),
+            vec![DfuStatus::Error(DfuErrorCode::ShaMismatch)]
+        );
+        assert!(receiver.sink.aborted);
+        assert_eq!(
+            receiver.handle_control(&DfuCommand::Apply.to_bytes()),
+            vec![DfuStatus::Error(DfuErrorCode::NotStarted)]
+        );
+    }
+
+    #[test]
+    fn dfu_07_rejects_too_large_image() {
+        let image = image(300);
+        let mut receiver = DfuReceiver::new(MemorySink::default(), 256, 8);
+        let mut sender = DfuSender::new(&image, 185);
+        assert_eq!(
+            transfer(&mut receiver, &mut sender),
+            Err(DfuErrorCode::TooLarge)
+        );
+    }
+}
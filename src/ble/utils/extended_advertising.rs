@@ -0,0 +1,752 @@
+// The helpers shared with the driver are only used when extended advertising is enabled
+#![cfg_attr(not(esp_idf_bt_nimble_ext_adv), allow(dead_code))]
+
+use std::{collections::BTreeMap, time::Duration};
+
+use esp32_nimble::BLEAddress;
+
+use super::{AdvertisementData, BleError, BleId, BlePhy, MAX_ADVERTISEMENT_SIZE};
+
+/// Biggest payload of an extended advertising set or a periodic advertisement
+pub const MAX_EXTENDED_ADVERTISEMENT_SIZE: usize = 1650;
+
+/// Advertising intervals are set in units of 0.625 ms, from 20 ms up to about 10485 s
+const MIN_ADVERTISING_INTERVAL: u32 = 0x20;
+const MAX_ADVERTISING_INTERVAL: u32 = 0xFF_FFFF;
+/// Periodic advertising intervals are set in units of 1.25 ms, from 7.5 ms up to about 81.9 s
+const MIN_PERIODIC_INTERVAL: u16 = 0x06;
+/// Scan intervals and windows are set in units of 0.625 ms, from 2.5 ms up to 40.96 s
+const MIN_SCAN_INTERVAL: u16 = 0x04;
+const MAX_ADVERTISING_SID: u8 = 0x0F;
+/// Durations of advertising and scanning are set in units of 10 ms
+const DURATION_UNIT_MS: u128 = 10;
+
+const DEFAULT_ADVERTISING_INTERVAL_MS: u32 = 100;
+const DEFAULT_SCAN_INTERVAL_MS: u32 = 100;
+
+const AD_SHORTENED_NAME: u8 = 0x08;
+const AD_COMPLETE_NAME: u8 = 0x09;
+const AD_SERVICE_DATA_UUID16: u8 = 0x16;
+const AD_MANUFACTURER_DATA: u8 = 0xFF;
+
+const ADV_PROP_CONNECTABLE: u8 = 0x01;
+const ADV_PROP_SCANNABLE: u8 = 0x02;
+const ADV_PROP_SCAN_RESPONSE: u8 = 0x08;
+const ADV_PROP_LEGACY: u8 = 0x10;
+
+/// Converts milliseconds to the units of 0.625 ms used by advertising and scanning intervals
+fn ms_to_adv_units(ms: u32) -> u32 {
+    (ms as u64 * 1000 / 625).min(u32::MAX as u64) as u32
+}
+
+/// Converts a duration to the units of 10 ms used by the BLE stack, where 0 means forever
+fn duration_to_units(duration: Option<Duration>) -> u16 {
+    match duration {
+        Some(duration) => {
+            (duration.as_millis() / DURATION_UNIT_MS).clamp(1, u16::MAX as u128) as u16
+        }
+        None => 0,
+    }
+}
+
+/// An advertising set of Bluetooth 5 extended advertising. A device can run many sets at the
+/// same time, each with its own data, interval, PHYs and kind of advertisement.
+///
+/// Legacy sets send the same 31 byte packets as [crate::ble::BleBeacon] and are seen by every
+/// scanner. Extended sets carry up to [MAX_EXTENDED_ADVERTISEMENT_SIZE] bytes and can use the
+/// coded PHY for long range, but are only seen by Bluetooth 5 scanners. An extended set can be
+/// connectable or scannable, but not both, and only sets that are neither can also advertise
+/// periodically.
+///
+/// Sets are validated with [AdvertisingSet::validate] before they are configured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvertisingSet {
+    pub(crate) instance: u8,
+    pub(crate) connectable: bool,
+    pub(crate) scannable: bool,
+    pub(crate) legacy: bool,
+    pub(crate) primary_phy: BlePhy,
+    pub(crate) secondary_phy: BlePhy,
+    pub(crate) min_interval: u32,
+    pub(crate) max_interval: u32,
+    pub(crate) tx_power: Option<i8>,
+    pub(crate) include_tx_power: bool,
+    pub(crate) sid: u8,
+    pub(crate) data: Vec<u8>,
+    pub(crate) scan_response: Vec<u8>,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) periodic: Option<PeriodicAdvertising>,
+}
+
+impl AdvertisingSet {
+    /// Creates a new non connectable and non scannable extended AdvertisingSet, on the 1M PHY and
+    /// advertising every 100 ms with no data
+    ///
+    /// # Arguments
+    ///
+    /// - `instance`: The number of the set, from 0 to the amount of sets allowed by
+    ///   `CONFIG_BT_NIMBLE_MAX_EXT_ADV_INSTANCES` minus one
+    ///
+    /// # Returns
+    ///
+    /// The new AdvertisingSet
+    pub fn new(instance: u8) -> Self {
+        let interval = ms_to_adv_units(DEFAULT_ADVERTISING_INTERVAL_MS);
+        Self {
+            instance,
+            connectable: false,
+            scannable: false,
+            legacy: false,
+            primary_phy: BlePhy::Le1M,
+            secondary_phy: BlePhy::Le1M,
+            min_interval: interval,
+            max_interval: interval,
+            tx_power: None,
+            include_tx_power: false,
+            sid: instance & MAX_ADVERTISING_SID,
+            data: Vec::new(),
+            scan_response: Vec::new(),
+            duration: None,
+            periodic: None,
+        }
+    }
+
+    /// Gets the number of the set
+    pub fn instance(&self) -> u8 {
+        self.instance
+    }
+
+    /// Sets whether clients can connect to the device through this set
+    pub fn connectable(mut self, connectable: bool) -> Self {
+        self.connectable = connectable;
+        self
+    }
+
+    /// Sets whether scanners can request the scan response of this set
+    pub fn scannable(mut self, scannable: bool) -> Self {
+        self.scannable = scannable;
+        self
+    }
+
+    /// Sets whether the set uses legacy advertising packets, seen by scanners older than
+    /// Bluetooth 5. Legacy sets are limited to [MAX_ADVERTISEMENT_SIZE] bytes on the 1M PHY.
+    pub fn legacy(mut self, legacy: bool) -> Self {
+        self.legacy = legacy;
+        self
+    }
+
+    /// Sets the PHYs of the set. The primary PHY carries the advertising packets on the
+    /// advertising channels, and must be [BlePhy::Le1M] or [BlePhy::LeCoded]. The secondary
+    /// PHY carries the data of extended sets on the data channels. Using [BlePhy::LeCoded] for
+    /// both gives the longest range.
+    pub fn phy(mut self, primary: BlePhy, secondary: BlePhy) -> Self {
+        self.primary_phy = primary;
+        self.secondary_phy = secondary;
+        self
+    }
+
+    /// Sets the time between advertisements
+    ///
+    /// # Arguments
+    ///
+    /// - `min_interval_ms`: The minimum interval, from 20 ms
+    /// - `max_interval_ms`: The maximum interval, at least the minimum one
+    ///
+    /// # Returns
+    ///
+    /// The AdvertisingSet itself
+    pub fn interval(mut self, min_interval_ms: u32, max_interval_ms: u32) -> Self {
+        self.min_interval = ms_to_adv_units(min_interval_ms);
+        self.max_interval = ms_to_adv_units(max_interval_ms);
+        self
+    }
+
+    /// Sets the transmission power in dBm requested for the set, or lets the controller choose
+    /// it with `None`
+    pub fn tx_power(mut self, dbm: Option<i8>) -> Self {
+        self.tx_power = dbm;
+        self
+    }
+
+    /// Sets whether the transmission power is included in the extended advertising packets, so
+    /// scanners can estimate the distance
+    pub fn include_tx_power(mut self, include: bool) -> Self {
+        self.include_tx_power = include;
+        self
+    }
+
+    /// Sets the advertising SID, from 0 to 15, which lets scanners tell apart the sets of the
+    /// same device. By default it is the instance of the set.
+    pub fn sid(mut self, sid: u8) -> Self {
+        self.sid = sid;
+        self
+    }
+
+    /// Sets the encoded AD structures advertised by the set
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Sets the encoded AD structures sent to scanners that request the scan response
+    pub fn scan_response(mut self, scan_response: Vec<u8>) -> Self {
+        self.scan_response = scan_response;
+        self
+    }
+
+    /// Sets the advertisement and scan response built by an [super::AdvertisementBuilder]
+    pub fn advertisement(self, advertisement: &AdvertisementData) -> Self {
+        self.data(advertisement.advertisement.clone())
+            .scan_response(advertisement.scan_response.clone())
+    }
+
+    /// Sets for how long the set advertises once started, or `None` to advertise until stopped.
+    /// The duration has a resolution of 10 ms.
+    pub fn duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the periodic advertising of the set, or removes it with `None`
+    pub fn periodic(mut self, periodic: Option<PeriodicAdvertising>) -> Self {
+        self.periodic = periodic;
+        self
+    }
+
+    /// Gets the duration of the set in the units of 10 ms of the BLE stack, 0 meaning forever
+    pub(crate) fn duration_units(&self) -> u16 {
+        duration_to_units(self.duration)
+    }
+
+    /// Checks the combination of settings is allowed by the specification
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the set can be configured, or a `BleError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data or the scan response are too long for
+    ///   the kind of set
+    /// - `BleError::InvalidParameters`: If the interval, the SID, the PHYs or the kind of set
+    ///   are not valid
+    pub fn validate(&self) -> Result<(), BleError> {
+        if self.min_interval < MIN_ADVERTISING_INTERVAL
+            || self.max_interval > MAX_ADVERTISING_INTERVAL
+            || self.min_interval > self.max_interval
+            || self.sid > MAX_ADVERTISING_SID
+            || self.primary_phy == BlePhy::Le2M
+        {
+            return Err(BleError::InvalidParameters);
+        }
+        if self.legacy {
+            return self.validate_legacy();
+        }
+        if self.connectable && self.scannable {
+            return Err(BleError::InvalidParameters);
+        }
+        if self.scannable && !self.data.is_empty() {
+            return Err(BleError::InvalidParameters);
+        }
+        if !self.scannable && !self.scan_response.is_empty() {
+            return Err(BleError::InvalidParameters);
+        }
+        if self.data.len() > MAX_EXTENDED_ADVERTISEMENT_SIZE
+            || self.scan_response.len() > MAX_EXTENDED_ADVERTISEMENT_SIZE
+        {
+            return Err(BleError::AdvertisementDoesNotFit);
+        }
+        match &self.periodic {
+            Some(_) if self.connectable || self.scannable => Err(BleError::InvalidParameters),
+            Some(periodic) => periodic.validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a legacy set, which only uses the 1M PHY and can not advertise periodically
+    fn validate_legacy(&self) -> Result<(), BleError> {
+        if self.primary_phy != BlePhy::Le1M || self.periodic.is_some() {
+            return Err(BleError::InvalidParameters);
+        }
+        if self.connectable && !self.scannable {
+            // Legacy connectable advertisements are always scannable
+            return Err(BleError::InvalidParameters);
+        }
+        if self.data.len() > MAX_ADVERTISEMENT_SIZE
+            || self.scan_response.len() > MAX_ADVERTISEMENT_SIZE
+        {
+            return Err(BleError::AdvertisementDoesNotFit);
+        }
+        Ok(())
+    }
+}
+
+/// Periodic advertising of an [AdvertisingSet]. The data is sent at a fixed interval, so
+/// receivers synchronized with [crate::ble::BleClient::periodic_sync_stream] can follow it
+/// without scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodicAdvertising {
+    pub(crate) min_interval: u16,
+    pub(crate) max_interval: u16,
+    pub(crate) include_tx_power: bool,
+    pub(crate) data: Vec<u8>,
+}
+
+impl PeriodicAdvertising {
+    /// Creates a new PeriodicAdvertising with no data
+    ///
+    /// # Arguments
+    ///
+    /// - `min_interval_ms`: The minimum interval, from 7.5 ms
+    /// - `max_interval_ms`: The maximum interval, up to 81918 ms and at least the minimum one
+    ///
+    /// # Returns
+    ///
+    /// The new PeriodicAdvertising
+    pub fn new(min_interval_ms: u32, max_interval_ms: u32) -> Self {
+        Self {
+            min_interval: ms_to_periodic_units(min_interval_ms),
+            max_interval: ms_to_periodic_units(max_interval_ms),
+            include_tx_power: false,
+            data: Vec::new(),
+        }
+    }
+
+    /// Sets the encoded AD structures sent periodically
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Sets whether the transmission power is included in the periodic packets
+    pub fn include_tx_power(mut self, include: bool) -> Self {
+        self.include_tx_power = include;
+        self
+    }
+
+    /// Checks the interval and the size of the data
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::AdvertisementDoesNotFit`: If the data is too long
+    /// - `BleError::InvalidParameters`: If the interval is not valid
+    fn validate(&self) -> Result<(), BleError> {
+        if self.min_interval < MIN_PERIODIC_INTERVAL || self.min_interval > self.max_interval {
+            return Err(BleError::InvalidParameters);
+        }
+        if self.data.len() > MAX_EXTENDED_ADVERTISEMENT_SIZE {
+            return Err(BleError::AdvertisementDoesNotFit);
+        }
+        Ok(())
+    }
+}
+
+/// Converts milliseconds to the units of 1.25 ms used by periodic advertising intervals
+fn ms_to_periodic_units(ms: u32) -> u16 {
+    (ms as u64 * 100 / 125).min(u16::MAX as u64) as u16
+}
+
+/// Event of the advertising sets of a [crate::ble::BleAdvertiser]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvertisingSetEvent {
+    /// A client connected through a connectable set, which stopped advertising
+    Connected { instance: u8, conn_handle: u16 },
+    /// The set stopped advertising because its duration ended
+    Completed { instance: u8 },
+}
+
+/// Settings of an extended scan started with [crate::ble::BleClient::extended_scan_stream]
+/// - `active`: Whether scan requests are sent to get the scan response of scannable sets
+/// - `coded`: Whether the coded PHY is scanned too, to receive long range advertisements
+/// - `interval`: Time between the start of two scan windows, in units of 0.625 ms
+/// - `window`: Time the radio listens on each interval, in units of 0.625 ms
+/// - `duration`: For how long the scan runs, or `None` to scan until the stream is dropped
+/// - `filter_duplicates`: Whether the controller reports each advertisement only once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedScanConfig {
+    pub(crate) active: bool,
+    pub(crate) coded: bool,
+    pub(crate) interval: u16,
+    pub(crate) window: u16,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) filter_duplicates: bool,
+}
+
+impl ExtendedScanConfig {
+    /// Creates a new ExtendedScanConfig with a passive scan of the 1M PHY, listening the whole
+    /// interval of 100 ms, with no duplicate filtering
+    pub fn new() -> Self {
+        let interval = ms_to_adv_units(DEFAULT_SCAN_INTERVAL_MS) as u16;
+        Self {
+            active: false,
+            coded: false,
+            interval,
+            window: interval,
+            duration: None,
+            filter_duplicates: false,
+        }
+    }
+
+    /// Sets whether scan requests are sent to get the scan response of scannable sets
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Sets whether the coded PHY is scanned too. The scan time is split between both PHYs.
+    pub fn coded(mut self, coded: bool) -> Self {
+        self.coded = coded;
+        self
+    }
+
+    /// Sets the time in ms between the start of two scan windows, and the time in ms the radio
+    /// listens on each one
+    pub fn window(mut self, interval_ms: u16, window_ms: u16) -> Self {
+        self.interval = ms_to_adv_units(interval_ms as u32).min(u16::MAX as u32) as u16;
+        self.window = ms_to_adv_units(window_ms as u32).min(u16::MAX as u32) as u16;
+        self
+    }
+
+    /// Sets for how long the scan runs, or `None` to scan until the stream is dropped
+    pub fn duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets whether the controller reports each advertisement only once
+    pub fn filter_duplicates(mut self, filter_duplicates: bool) -> Self {
+        self.filter_duplicates = filter_duplicates;
+        self
+    }
+
+    /// Gets the duration of the scan in the units of 10 ms of the BLE stack, 0 meaning forever
+    pub(crate) fn duration_units(&self) -> u16 {
+        duration_to_units(self.duration)
+    }
+
+    /// Checks the interval and the window
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidParameters`: If the window is longer than the interval or they are
+    ///   shorter than 2.5 ms
+    pub(crate) fn validate(&self) -> Result<(), BleError> {
+        if self.window < MIN_SCAN_INTERVAL || self.window > self.interval {
+            return Err(BleError::InvalidParameters);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ExtendedScanConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether the data of a report is all the data of the advertisement:
+/// - `Complete`: The report has the last fragment of the data
+/// - `Incomplete`: More fragments of the data follow in other reports
+/// - `Truncated`: The controller could not receive the rest of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DataStatus {
+    Complete,
+    Incomplete,
+    Truncated,
+}
+
+impl DataStatus {
+    /// Gets the status reported by the BLE stack
+    pub(crate) fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => Self::Complete,
+            1 => Self::Incomplete,
+            _ => Self::Truncated,
+        }
+    }
+}
+
+/// Joins the fragments of the data of extended and periodic advertisements. The controller
+/// splits data that does not fit in a report, so each advertiser has its data accumulated until
+/// the report that completes or truncates it.
+#[derive(Debug)]
+pub(crate) struct ReportAssembler<K: Ord> {
+    pending: BTreeMap<K, Vec<u8>>,
+}
+
+impl<K: Ord> ReportAssembler<K> {
+    /// Creates a new ReportAssembler
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the fragment of a report
+    ///
+    /// # Arguments
+    ///
+    /// - `key`: Identifies the advertiser of the report
+    /// - `status`: The status of the report
+    /// - `fragment`: The data of the report
+    ///
+    /// # Returns
+    ///
+    /// The data of the advertisement and whether it is complete, or `None` if more fragments
+    /// must be received
+    pub(crate) fn push(
+        &mut self,
+        key: K,
+        status: DataStatus,
+        fragment: &[u8],
+    ) -> Option<(Vec<u8>, bool)> {
+        let mut data = self.pending.remove(&key).unwrap_or_default();
+        data.extend_from_slice(fragment);
+        let overflowed = data.len() > MAX_EXTENDED_ADVERTISEMENT_SIZE;
+        match status {
+            DataStatus::Incomplete if !overflowed => {
+                self.pending.insert(key, data);
+                None
+            }
+            DataStatus::Complete if !overflowed => Some((data, true)),
+            _ => {
+                data.truncate(MAX_EXTENDED_ADVERTISEMENT_SIZE);
+                Some((data, false))
+            }
+        }
+    }
+
+    /// Drops the fragments of an advertiser, like when a periodic sync is lost
+    pub(crate) fn forget(&mut self, key: &K) {
+        self.pending.remove(key);
+    }
+}
+
+/// Splits an advertising payload in its AD structures, as pairs of type and data. Parsing stops
+/// at the first malformed structure.
+fn ad_structures(payload: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut structures = Vec::new();
+    let mut rest = payload;
+    while let Some((&length, tail)) = rest.split_first() {
+        let length = length as usize;
+        if length == 0 || length > tail.len() {
+            break;
+        }
+        structures.push((tail[0], &tail[1..length]));
+        rest = &tail[length..];
+    }
+    structures
+}
+
+/// An advertisement received by an extended scan, joined from all its fragments
+/// - `address`: The address of the advertiser
+/// - `sid`: The advertising SID of the set
+/// - `rssi`: The signal strength in dBm
+/// - `tx_power`: The transmission power in dBm, if the advertiser included it
+/// - `primary_phy`: The PHY of the advertising packets
+/// - `secondary_phy`: The PHY of the data, `None` for legacy advertisements
+/// - `periodic_interval`: The interval of the periodic advertising of the set in units of 1.25 ms,
+///   if it has one
+/// - `connectable`, `scannable`, `scan_response` and `legacy`: The kind of advertisement
+/// - `complete`: Whether the whole data was received, or the controller truncated it
+/// - `data`: The encoded AD structures
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedAdvertisement {
+    pub address: BLEAddress,
+    pub sid: u8,
+    pub rssi: i8,
+    pub tx_power: Option<i8>,
+    pub primary_phy: BlePhy,
+    pub secondary_phy: Option<BlePhy>,
+    pub periodic_interval: Option<u16>,
+    pub connectable: bool,
+    pub scannable: bool,
+    pub scan_response: bool,
+    pub legacy: bool,
+    pub complete: bool,
+    pub data: Vec<u8>,
+}
+
+impl ExtendedAdvertisement {
+    /// Sets the kind of advertisement from the event properties reported by the BLE stack
+    pub(crate) fn set_properties(&mut self, properties: u8) {
+        self.connectable = properties & ADV_PROP_CONNECTABLE != 0;
+        self.scannable = properties & ADV_PROP_SCANNABLE != 0;
+        self.scan_response = properties & ADV_PROP_SCAN_RESPONSE != 0;
+        self.legacy = properties & ADV_PROP_LEGACY != 0;
+    }
+
+    /// Gets the complete or shortened name of the advertiser, if it is advertised
+    pub fn name(&self) -> Option<String> {
+        ad_structures(&self.data)
+            .into_iter()
+            .find(|(ad_type, _)| *ad_type == AD_COMPLETE_NAME || *ad_type == AD_SHORTENED_NAME)
+            .map(|(_, name)| String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// Gets the manufacturer data, starting with the company id
+    pub fn manufacturer_data(&self) -> Option<&[u8]> {
+        ad_structures(&self.data)
+            .into_iter()
+            .find(|(ad_type, _)| *ad_type == AD_MANUFACTURER_DATA)
+            .map(|(_, data)| data)
+    }
+
+    /// Gets the data of a service with a 16 bit uuid
+    pub fn service_data(&self, id: &BleId) -> Option<&[u8]> {
+        ad_structures(&self.data)
+            .into_iter()
+            .filter(|(ad_type, data)| *ad_type == AD_SERVICE_DATA_UUID16 && data.len() >= 2)
+            .find(|(_, data)| BleId::FromUuid16(u16::from_le_bytes([data[0], data[1]])) == *id)
+            .map(|(_, data)| &data[2..])
+    }
+}
+
+/// Event of a periodic advertising train followed with
+/// [crate::ble::BleClient::periodic_sync_stream]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeriodicSyncEvent {
+    /// The receiver synchronized with the train. The interval is in units of 1.25 ms.
+    Established {
+        sync_handle: u16,
+        address: BLEAddress,
+        sid: u8,
+        phy: BlePhy,
+        interval: u16,
+    },
+    /// Periodic data was received, joined from all its fragments
+    Report {
+        sync_handle: u16,
+        rssi: i8,
+        tx_power: Option<i8>,
+        complete: bool,
+        data: Vec<u8>,
+    },
+    /// The train was not received for the sync timeout, or the synchronization failed
+    Lost { sync_handle: u16, reason: i32 },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use esp32_nimble::BLEAddressType;
+
+    #[test]
+    fn extended_advertising_01_validates_sets() {
+        let beacon = AdvertisingSet::new(1)
+            .phy(BlePhy::LeCoded, BlePhy::LeCoded)
+            .interval(200, 300)
+            .data(vec![0; 1000]);
+        assert!(beacon.validate().is_ok());
+        assert_eq!(beacon.min_interval, 320);
+        assert!(matches!(
+            beacon.clone().data(vec![0; 1651]).validate(),
+            Err(BleError::AdvertisementDoesNotFit)
+        ));
+        assert!(matches!(
+            beacon.clone().phy(BlePhy::Le2M, BlePhy::Le1M).validate(),
+            Err(BleError::InvalidParameters)
+        ));
+        assert!(matches!(
+            beacon.clone().interval(10, 10).validate(),
+            Err(BleError::InvalidParameters)
+        ));
+        let periodic = PeriodicAdvertising::new(100, 100).data(vec![1, 2]);
+        assert_eq!(periodic.min_interval, 80);
+        assert!(beacon
+            .clone()
+            .periodic(Some(periodic.clone()))
+            .validate()
+            .is_ok());
+        assert!(matches!(
+            beacon
+                .clone()
+                .connectable(true)
+                .periodic(Some(periodic))
+                .validate(),
+            Err(BleError::InvalidParameters)
+        ));
+        assert!(matches!(
+            beacon.connectable(true).scannable(true).validate(),
+            Err(BleError::InvalidParameters)
+        ));
+
+        let server = AdvertisingSet::new(0)
+            .legacy(true)
+            .connectable(true)
+            .scannable(true)
+            .data(vec![0; 31]);
+        assert!(server.validate().is_ok());
+        assert!(matches!(
+            server.clone().data(vec![0; 32]).validate(),
+            Err(BleError::AdvertisementDoesNotFit)
+        ));
+        assert!(matches!(
+            server.phy(BlePhy::LeCoded, BlePhy::LeCoded).validate(),
+            Err(BleError::InvalidParameters)
+        ));
+        assert_eq!(
+            AdvertisingSet::new(2)
+                .duration(Some(Duration::from_millis(1234)))
+                .duration_units(),
+            123
+        );
+    }
+
+    #[test]
+    fn extended_advertising_02_assembles_fragments() {
+        let mut assembler = ReportAssembler::new();
+        assert_eq!(assembler.push(1, DataStatus::Incomplete, &[1, 2]), None);
+        assert_eq!(
+            assembler.push(2, DataStatus::Complete, &[9]),
+            Some((vec![9], true))
+        );
+        assert_eq!(
+            assembler.push(1, DataStatus::Complete, &[3]),
+            Some((vec![1, 2, 3], true))
+        );
+        assert_eq!(assembler.push(1, DataStatus::Incomplete, &[4]), None);
+        assert_eq!(
+            assembler.push(1, DataStatus::Truncated, &[5]),
+            Some((vec![4, 5], false))
+        );
+        assert_eq!(assembler.push(3, DataStatus::Incomplete, &[6]), None);
+        assembler.forget(&3);
+        assert_eq!(
+            assembler.push(3, DataStatus::Complete, &[7]),
+            Some((vec![7], true))
+        );
+        assert_eq!(DataStatus::from_raw(1), DataStatus::Incomplete);
+        assert_eq!(DataStatus::from_raw(2), DataStatus::Truncated);
+    }
+
+    #[test]
+    fn extended_advertising_03_reads_received_data() {
+        let mut advertisement = ExtendedAdvertisement {
+            address: BLEAddress::new([1, 2, 3, 4, 5, 6], BLEAddressType::Public),
+            sid: 1,
+            rssi: -60,
+            tx_power: None,
+            primary_phy: BlePhy::LeCoded,
+            secondary_phy: Some(BlePhy::LeCoded),
+            periodic_interval: None,
+            connectable: false,
+            scannable: false,
+            scan_response: false,
+            legacy: false,
+            complete: true,
+            data: vec![
+                4, 0x09, b'e', b's', b'p', 5, 0x16, 0x0F, 0x18, 80, 81, 3, 0xFF, 0x59, 0x00, 0,
+            ],
+        };
+        advertisement.set_properties(ADV_PROP_CONNECTABLE | ADV_PROP_LEGACY);
+        assert!(advertisement.connectable && advertisement.legacy && !advertisement.scannable);
+        assert_eq!(advertisement.name(), Some("esp".to_string()));
+        assert_eq!(
+            advertisement.service_data(&BleId::FromUuid16(0x180F)),
+            Some(&[80, 81][..])
+        );
+        assert_eq!(advertisement.service_data(&BleId::FromUuid16(0x180A)), None);
+        assert_eq!(advertisement.manufacturer_data(), Some(&[0x59, 0x00][..]));
+    }
+}
@@ -0,0 +1,448 @@
+use std::time::{Duration, Instant};
+
+use esp32_nimble::BLEAddress;
+use serde_json::{json, Value};
+
+use super::{
+    hex_string, id_string, BleId, DuplicateFilter, EddystoneFrame, IBeacon, EDDYSTONE_SERVICE_UUID,
+};
+use crate::telemetry::Batch;
+
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_BATCH_SIZE: usize = 20;
+const DEFAULT_MAX_BATCH_AGE: Duration = Duration::from_secs(30);
+
+/// Condition an advertisement must meet to be forwarded by a [crate::ble::BleGateway]
+/// - `Service`: The device advertises the service, or data of it
+/// - `Manufacturer`: The device advertises manufacturer data of the company id
+/// - `Name`: The device advertises exactly this name
+/// - `NamePrefix`: The name of the device starts with this prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayFilter {
+    Service(BleId),
+    Manufacturer(u16),
+    Name(String),
+    NamePrefix(String),
+}
+
+impl GatewayFilter {
+    /// Returns whether the reading meets the condition of the filter
+    pub fn matches(&self, reading: &GatewayReading) -> bool {
+        match self {
+            GatewayFilter::Service(id) => {
+                reading.service_uuids.contains(id)
+                    || reading.service_data.iter().any(|(uuid, _)| uuid == id)
+            }
+            GatewayFilter::Manufacturer(company_id) => reading.company_id() == Some(*company_id),
+            GatewayFilter::Name(name) => reading.name.as_ref() == Some(name),
+            GatewayFilter::NamePrefix(prefix) => reading
+                .name
+                .as_ref()
+                .is_some_and(|name| name.starts_with(prefix.as_str())),
+        }
+    }
+}
+
+/// Configuration of a [crate::ble::BleGateway], that can be changed while it runs
+/// - `uri`: The uri the readings are posted to
+/// - `token`: An optional token, sent as `Authorization: Bearer <token>`
+/// - `gateway_id`: An optional id of the gateway, sent with every batch
+/// - `filters`: Readings are forwarded if they match any filter. With no filters every reading
+///   is forwarded
+/// - `min_interval`: Each device is forwarded at most once per this duration
+/// - `batch_size`: The amount of readings sent together
+/// - `max_batch_age`: Readings are sent once the oldest one is this old, even if the batch is
+///   not full
+#[derive(Debug, Clone, PartialEq)]
+pub struct GatewayConfig {
+    pub uri: String,
+    pub token: Option<String>,
+    pub gateway_id: Option<String>,
+    pub filters: Vec<GatewayFilter>,
+    pub min_interval: Duration,
+    pub batch_size: usize,
+    pub max_batch_age: Duration,
+}
+
+impl GatewayConfig {
+    /// Creates a new GatewayConfig with no filters, forwarding each device at most once every
+    /// 10 seconds in batches of 20 readings, sent at least every 30 seconds
+    ///
+    /// # Arguments
+    ///
+    /// - `uri`: The uri the readings are posted to
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.to_string(),
+            token: None,
+            gateway_id: None,
+            filters: Vec::new(),
+            min_interval: DEFAULT_MIN_INTERVAL,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_batch_age: DEFAULT_MAX_BATCH_AGE,
+        }
+    }
+
+    /// Sets the token sent as `Authorization: Bearer <token>`
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Sets the id of the gateway sent with every batch
+    pub fn gateway_id(mut self, gateway_id: &str) -> Self {
+        self.gateway_id = Some(gateway_id.to_string());
+        self
+    }
+
+    /// Adds a filter. Readings are forwarded if they match any of the filters
+    pub fn filter(mut self, filter: GatewayFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Sets the minimum time between two readings of the same device
+    pub fn min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Sets the amount of readings sent together. A value of 0 is treated as 1
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the age of the oldest reading after which the batch is sent even if it is not full
+    pub fn max_batch_age(mut self, max_batch_age: Duration) -> Self {
+        self.max_batch_age = max_batch_age;
+        self
+    }
+
+    /// Returns whether the reading meets any of the filters, or there are no filters
+    pub fn accepts(&self, reading: &GatewayReading) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|filter| filter.matches(reading))
+    }
+}
+
+/// An advertisement received by a gateway
+/// - `name`: The advertised name, if there is one
+/// - `service_uuids`: The services advertised
+/// - `service_data`: The data advertised of each service
+/// - `manufacturer_data`: The manufacturer data, starting with the company id
+/// - `received_at`: When the advertisement was received
+#[derive(Debug, Clone, PartialEq)]
+pub struct GatewayReading {
+    pub address: BLEAddress,
+    pub name: Option<String>,
+    pub rssi: i32,
+    pub service_uuids: Vec<BleId>,
+    pub service_data: Vec<(BleId, Vec<u8>)>,
+    pub manufacturer_data: Option<Vec<u8>>,
+    pub received_at: Instant,
+}
+
+impl GatewayReading {
+    /// Gets the company id of the manufacturer data, if there is any
+    pub fn company_id(&self) -> Option<u16> {
+        match self.manufacturer_data.as_deref()? {
+            [low, high, ..] => Some(u16::from_le_bytes([*low, *high])),
+            _ => None,
+        }
+    }
+
+    /// Gets the iBeacon frame of the reading, if it has one
+    pub fn ibeacon(&self) -> Option<IBeacon> {
+        IBeacon::from_manufacturer_data(self.manufacturer_data.as_deref()?)
+    }
+
+    /// Gets the Eddystone frame of the reading, if it has a valid one
+    pub fn eddystone(&self) -> Option<EddystoneFrame> {
+        let eddystone_id = BleId::FromUuid16(EDDYSTONE_SERVICE_UUID);
+        let (_, data) = self
+            .service_data
+            .iter()
+            .find(|(uuid, _)| uuid == &eddystone_id)?;
+        EddystoneFrame::from_service_data(data)
+    }
+
+    /// Gets the reading as a JSON value. Data is written as hexadecimal strings, and beacon frames
+    /// are decoded into their fields.
+    ///
+    /// # Arguments
+    ///
+    /// - `now`: The current instant, used to write how long ago the reading was received
+    /// - `decode`: Decodes the data of a service into a JSON value, added as `decoded`
+    pub fn to_json<F: Fn(&BleId, &[u8]) -> Option<Value>>(&self, now: Instant, decode: F) -> Value {
+        let mut reading = json!({
+            "address": self.address.to_string(),
+            "name": self.name,
+            "rssi": self.rssi,
+            "age_ms": now.saturating_duration_since(self.received_at).as_millis() as u64,
+        });
+        if !self.service_data.is_empty() {
+            reading["service_data"] = self
+                .service_data
+                .iter()
+                .map(|(id, data)| {
+                    let mut service = json!({"uuid": id_string(id), "data": hex_string(data)});
+                    if let Some(decoded) = decode(id, data) {
+                        service["decoded"] = decoded;
+                    }
+                    service
+                })
+                .collect();
+        }
+        if let (Some(company_id), Some(data)) = (self.company_id(), &self.manufacturer_data) {
+            reading["manufacturer_data"] = json!({
+                "company_id": format!("0x{company_id:04X}"),
+                "data": hex_string(&data[2..]),
+            });
+        }
+        if let Some(ibeacon) = self.ibeacon() {
+            reading["ibeacon"] = json!({
+                "uuid": ibeacon.proximity_uuid.to_string(),
+                "major": ibeacon.major,
+                "minor": ibeacon.minor,
+                "measured_power": ibeacon.measured_power,
+            });
+        }
+        if let Some(eddystone) = self.eddystone() {
+            reading["eddystone"] = eddystone_json(&eddystone);
+        }
+        reading
+    }
+}
+
+fn eddystone_json(frame: &EddystoneFrame) -> Value {
+    match frame {
+        EddystoneFrame::Uid {
+            tx_power,
+            namespace,
+            instance,
+        } => json!({
+            "frame": "uid",
+            "tx_power": tx_power,
+            "namespace": hex_string(namespace),
+            "instance": hex_string(instance),
+        }),
+        EddystoneFrame::Url { tx_power, url } => json!({
+            "frame": "url",
+            "tx_power": tx_power,
+            "url": url,
+        }),
+        EddystoneFrame::Tlm(tlm) => json!({
+            "frame": "tlm",
+            "battery_voltage": tlm.battery_voltage,
+            "temperature": tlm.temperature,
+            "advertising_count": tlm.advertising_count,
+            "uptime_s": tlm.uptime.as_secs(),
+        }),
+    }
+}
+
+/// Creates the body of the request that forwards a batch of readings
+///
+/// # Arguments
+///
+/// - `gateway_id`: The id of the gateway, if it has one
+/// - `readings`: The readings of the batch
+/// - `now`: The current instant
+/// - `decode`: Decodes the data of a service into a JSON value
+pub fn readings_to_json<F: Fn(&BleId, &[u8]) -> Option<Value>>(
+    gateway_id: Option<&str>,
+    readings: &[GatewayReading],
+    now: Instant,
+    decode: F,
+) -> Value {
+    json!({
+        "gateway": gateway_id,
+        "readings": readings.iter().map(|reading| reading.to_json(now, &decode)).collect::<Vec<_>>(),
+    })
+}
+
+/// Readings waiting to be forwarded, already filtered and rate limited per device
+pub(crate) struct ReadingQueue {
+    batch: Batch<GatewayReading>,
+    limiter: DuplicateFilter,
+    batch_size: usize,
+    min_interval: Duration,
+}
+
+impl ReadingQueue {
+    /// Creates a new empty ReadingQueue
+    pub(crate) fn new(config: &GatewayConfig) -> Self {
+        Self {
+            batch: Batch::new(config.batch_size),
+            limiter: DuplicateFilter::new(config.min_interval),
+            batch_size: config.batch_size,
+            min_interval: config.min_interval,
+        }
+    }
+
+    /// Applies a new configuration, keeping the readings already queued
+    pub(crate) fn reconfigure(&mut self, config: &GatewayConfig) {
+        if config.batch_size != self.batch_size {
+            let mut batch = Batch::new(config.batch_size);
+            for reading in self.batch.take() {
+                batch.push(reading);
+            }
+            self.batch = batch;
+            self.batch_size = config.batch_size;
+        }
+        if config.min_interval != self.min_interval {
+            self.limiter = DuplicateFilter::new(config.min_interval);
+            self.min_interval = config.min_interval;
+        }
+    }
+
+    /// Queues a reading if it meets the filters and its device was not forwarded recently
+    ///
+    /// # Returns
+    ///
+    /// Whether the reading was queued
+    pub(crate) fn offer(&mut self, reading: GatewayReading, config: &GatewayConfig) -> bool {
+        if !config.accepts(&reading) || !self.limiter.accept(reading.address, reading.received_at) {
+            return false;
+        }
+        self.batch.push(reading);
+        true
+    }
+
+    /// Returns whether the readings must be sent, because the batch is full or the oldest
+    /// reading is older than the max age
+    pub(crate) fn is_due(&self, config: &GatewayConfig, now: Instant) -> bool {
+        self.batch.is_full()
+            || self.batch.items().first().is_some_and(|oldest| {
+                now.saturating_duration_since(oldest.received_at) >= config.max_batch_age
+            })
+    }
+
+    /// Removes every queued reading
+    pub(crate) fn take(&mut self) -> Vec<GatewayReading> {
+        self.batch.take()
+    }
+
+    /// Gets the amount of queued readings
+    pub(crate) fn len(&self) -> usize {
+        self.batch.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use esp32_nimble::BLEAddressType;
+
+    fn reading(last_byte: u8, name: &str, received_at: Instant) -> GatewayReading {
+        GatewayReading {
+            address: BLEAddress::new([0, 1, 2, 3, 4, last_byte], BLEAddressType::Public),
+            name: Some(name.to_string()),
+            rssi: -60,
+            service_uuids: vec![BleId::FromUuid16(0x181A)],
+            service_data: vec![(BleId::FromUuid16(0x181A), vec![0x10, 0x01])],
+            manufacturer_data: Some(vec![0xFF, 0xFF, 0xAB]),
+            received_at,
+        }
+    }
+
+    #[test]
+    fn gateway_01_filters() {
+        let reading = reading(1, "Sensor-01", Instant::now());
+        assert!(GatewayFilter::Service(BleId::FromUuid16(0x181A)).matches(&reading));
+        assert!(!GatewayFilter::Service(BleId::FromUuid16(0x180F)).matches(&reading));
+        assert!(GatewayFilter::Manufacturer(0xFFFF).matches(&reading));
+        assert!(!GatewayFilter::Manufacturer(0x004C).matches(&reading));
+        assert!(GatewayFilter::Name("Sensor-01".to_string()).matches(&reading));
+        assert!(GatewayFilter::NamePrefix("Sensor-".to_string()).matches(&reading));
+        assert!(!GatewayFilter::NamePrefix("Lamp".to_string()).matches(&reading));
+
+        let config = GatewayConfig::new("http://backend/readings");
+        assert!(config.accepts(&reading));
+        let config = config.filter(GatewayFilter::Name("Other".to_string()));
+        assert!(!config.accepts(&reading));
+        let config = config.filter(GatewayFilter::Manufacturer(0xFFFF));
+        assert!(config.accepts(&reading));
+    }
+
+    #[test]
+    fn gateway_02_json_with_decoded_payloads() {
+        let now = Instant::now();
+        let mut reading = reading(1, "Sensor-01", now);
+        let decode = |id: &BleId, data: &[u8]| {
+            (id == &BleId::FromUuid16(0x181A)).then(
+                || json!({"temperature": i16::from_le_bytes([data[0], data[1]]) as f64 / 10.0}),
+            )
+        };
+        let body = readings_to_json(Some("hall"), &[reading.clone()], now, decode);
+        assert_eq!(
+            body,
+            json!({
+                "gateway": "hall",
+                "readings": [{
+                    "address": "00:01:02:03:04:01",
+                    "name": "Sensor-01",
+                    "rssi": -60,
+                    "age_ms": 0,
+                    "service_data": [{"uuid": "0x181A", "data": "1001", "decoded": {"temperature": 27.2}}],
+                    "manufacturer_data": {"company_id": "0xFFFF", "data": "ab"},
+                }]
+            })
+        );
+
+        let uuid = uuid::Uuid::from_bytes([7; 16]);
+        reading.manufacturer_data = Some(
+            IBeacon {
+                proximity_uuid: uuid,
+                major: 1,
+                minor: 2,
+                measured_power: -59,
+            }
+            .to_manufacturer_data(),
+        );
+        reading.service_data = vec![(
+            BleId::FromUuid16(EDDYSTONE_SERVICE_UUID),
+            EddystoneFrame::Url {
+                tx_power: -20,
+                url: "https://example.com".to_string(),
+            }
+            .to_service_data()
+            .unwrap(),
+        )];
+        let json = reading.to_json(now, |_, _| None);
+        assert_eq!(json["ibeacon"]["major"], 1);
+        assert_eq!(json["ibeacon"]["uuid"], uuid.to_string());
+        assert_eq!(json["eddystone"]["frame"], "url");
+        assert_eq!(json["eddystone"]["url"], "https://example.com");
+    }
+
+    #[test]
+    fn gateway_03_rate_limits_and_batches() {
+        let start = Instant::now();
+        let mut config = GatewayConfig::new("http://backend/readings")
+            .min_interval(Duration::from_secs(10))
+            .batch_size(3)
+            .max_batch_age(Duration::from_secs(30));
+        let mut queue = ReadingQueue::new(&config);
+
+        assert!(queue.offer(reading(1, "A", start), &config));
+        assert!(!queue.offer(reading(1, "A", start + Duration::from_secs(5)), &config));
+        assert!(queue.offer(reading(2, "B", start + Duration::from_secs(5)), &config));
+        assert!(!queue.is_due(&config, start + Duration::from_secs(29)));
+        assert!(queue.is_due(&config, start + Duration::from_secs(30)));
+        assert!(queue.offer(reading(1, "A", start + Duration::from_secs(11)), &config));
+        assert!(queue.is_due(&config, start + Duration::from_secs(11)));
+        assert_eq!(queue.take().len(), 3);
+        assert!(!queue.is_due(&config, start + Duration::from_secs(60)));
+
+        config = config
+            .batch_size(1)
+            .min_interval(Duration::ZERO)
+            .filter(GatewayFilter::NamePrefix("A".to_string()));
+        queue.reconfigure(&config);
+        assert!(!queue.offer(reading(2, "B", start + Duration::from_secs(12)), &config));
+        assert!(queue.offer(reading(1, "A", start + Duration::from_secs(12)), &config));
+        assert_eq!(queue.len(), 1);
+        assert!(queue.is_due(&config, start + Duration::from_secs(12)));
+    }
+}
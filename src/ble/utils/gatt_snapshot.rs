@@ -0,0 +1,422 @@
+use std::{collections::HashMap, fmt};
+
+use esp32_nimble::BLEAddress;
+use serde_json::{json, Value};
+
+use crate::serial::{SerialError, WRITER};
+
+use super::{
+    address_key,
+    ble_standard_uuids::{StandardCharacteristicId, StandardDescriptorId, StandardServiceId},
+    AddressKey, BleId,
+};
+
+/// Properties of a remote characteristic, as declared by the server
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CharacteristicProperties {
+    pub read: bool,
+    pub write: bool,
+    pub write_no_response: bool,
+    pub notify: bool,
+    pub indicate: bool,
+    pub broadcast: bool,
+}
+
+impl CharacteristicProperties {
+    /// Gets the names of the properties that are set
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<&str>` with names like `"read"` or `"notify"`
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.read, "read"),
+            (self.write, "write"),
+            (self.write_no_response, "write_no_response"),
+            (self.notify, "notify"),
+            (self.indicate, "indicate"),
+            (self.broadcast, "broadcast"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
+/// A descriptor discovered on a remote characteristic
+/// - `id`: The id of the descriptor
+/// - `name`: The name of the descriptor, if it is a standard one
+/// - `value`: The value read from the descriptor, or `None` if it could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorSnapshot {
+    pub id: BleId,
+    pub name: Option<String>,
+    pub value: Option<Vec<u8>>,
+}
+
+impl DescriptorSnapshot {
+    /// Creates a new DescriptorSnapshot, resolving its name from the standard descriptors
+    pub fn new(id: BleId, value: Option<Vec<u8>>) -> Self {
+        let name = uuid16(&id)
+            .and_then(StandardDescriptorId::from_uuid16)
+            .map(|descriptor| descriptor.name());
+        Self { id, name, value }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uuid": id_string(&self.id),
+            "name": self.name,
+            "value": self.value.as_deref().map(hex_string),
+        })
+    }
+}
+
+/// A characteristic discovered on a remote service
+/// - `id`: The id of the characteristic
+/// - `name`: The name of the characteristic, if it is a standard one
+/// - `properties`: The properties of the characteristic
+/// - `value`: The value read from the characteristic, or `None` if it is not readable or the
+///   read failed
+/// - `descriptors`: The descriptors of the characteristic
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacteristicSnapshot {
+    pub id: BleId,
+    pub name: Option<String>,
+    pub properties: CharacteristicProperties,
+    pub value: Option<Vec<u8>>,
+    pub descriptors: Vec<DescriptorSnapshot>,
+}
+
+impl CharacteristicSnapshot {
+    /// Creates a new CharacteristicSnapshot without descriptors, resolving its name from the
+    /// standard characteristics
+    pub fn new(id: BleId, properties: CharacteristicProperties, value: Option<Vec<u8>>) -> Self {
+        let name = uuid16(&id)
+            .and_then(StandardCharacteristicId::from_uuid16)
+            .map(|characteristic| characteristic.name());
+        Self {
+            id,
+            name,
+            properties,
+            value,
+            descriptors: vec![],
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uuid": id_string(&self.id),
+            "name": self.name,
+            "properties": self.properties.names(),
+            "value": self.value.as_deref().map(hex_string),
+            "descriptors": self.descriptors.iter().map(DescriptorSnapshot::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A service discovered on a remote server
+/// - `id`: The id of the service
+/// - `name`: The name of the service, if it is a standard one
+/// - `characteristics`: The characteristics of the service
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceSnapshot {
+    pub id: BleId,
+    pub name: Option<String>,
+    pub characteristics: Vec<CharacteristicSnapshot>,
+}
+
+impl ServiceSnapshot {
+    /// Creates a new ServiceSnapshot without characteristics, resolving its name from the
+    /// standard services
+    pub fn new(id: BleId) -> Self {
+        let name = uuid16(&id)
+            .and_then(StandardServiceId::from_uuid16)
+            .map(|service| service.name());
+        Self {
+            id,
+            name,
+            characteristics: vec![],
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uuid": id_string(&self.id),
+            "name": self.name,
+            "characteristics": self.characteristics.iter().map(CharacteristicSnapshot::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The whole GATT table of a remote server, as gotten with [crate::ble::BleConnection::discover_all].
+/// It can be exported as JSON with [Self::to_json], or printed in a readable form through its
+/// `Display` implementation or over a serial port with [Self::write_to].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GattSnapshot {
+    pub address: BLEAddress,
+    pub services: Vec<ServiceSnapshot>,
+}
+
+impl GattSnapshot {
+    /// Creates a new GattSnapshot of the server of the given address without services
+    pub fn new(address: BLEAddress) -> Self {
+        Self {
+            address,
+            services: vec![],
+        }
+    }
+
+    /// Gets a characteristic of the snapshot
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The id of the service which owns the characteristic
+    /// - `characteristic_id`: The id of the characteristic
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the characteristic, or `None` if the server does not have it
+    pub fn characteristic(
+        &self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+    ) -> Option<&CharacteristicSnapshot> {
+        self.services
+            .iter()
+            .find(|service| &service.id == service_id)?
+            .characteristics
+            .iter()
+            .find(|characteristic| &characteristic.id == characteristic_id)
+    }
+
+    /// Gets the snapshot as a JSON value. Ids are written as `0x` hexadecimals when they are
+    /// 16 or 32 bits long, values are written as hexadecimal strings and names are `null` for non
+    /// standard attributes.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "address": self.address.to_string(),
+            "services": self.services.iter().map(ServiceSnapshot::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Writes the snapshot in a readable form, for example over a UART
+    ///
+    /// # Arguments
+    ///
+    /// - `writer`: The serial port to write to
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the snapshot was written, or a `SerialError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - Any `SerialError` returned by the writer
+    pub fn write_to<W: WRITER>(&self, writer: &mut W) -> Result<(), SerialError> {
+        writer.parse_and_write(0, self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for GattSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "GATT of {}", self.address)?;
+        for service in &self.services {
+            writeln!(
+                f,
+                "Service {}",
+                attribute_string(&service.id, &service.name)
+            )?;
+            for characteristic in &service.characteristics {
+                write!(
+                    f,
+                    "  Characteristic {} [{}]",
+                    attribute_string(&characteristic.id, &characteristic.name),
+                    characteristic.properties.names().join(", ")
+                )?;
+                write_value(f, &characteristic.value)?;
+                for descriptor in &characteristic.descriptors {
+                    write!(
+                        f,
+                        "    Descriptor {}",
+                        attribute_string(&descriptor.id, &descriptor.name)
+                    )?;
+                    write_value(f, &descriptor.value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Snapshots of many servers, kept by address so the discovery can be skipped when reconnecting
+/// to a known server.
+#[derive(Debug, Default)]
+pub struct GattSnapshotCache {
+    snapshots: HashMap<AddressKey, GattSnapshot>,
+}
+
+impl GattSnapshotCache {
+    /// Creates a new empty GattSnapshotCache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the snapshot of the server of the given address, if it was cached
+    pub fn get(&self, address: &BLEAddress) -> Option<&GattSnapshot> {
+        self.snapshots.get(&address_key(address))
+    }
+
+    /// Caches a snapshot, replacing the previous one of the same server
+    pub fn insert(&mut self, snapshot: GattSnapshot) {
+        self.snapshots
+            .insert(address_key(&snapshot.address), snapshot);
+    }
+
+    /// Removes the snapshot of the server of the given address, so it is discovered again
+    ///
+    /// # Returns
+    ///
+    /// The removed snapshot, if there was one
+    pub fn remove(&mut self, address: &BLEAddress) -> Option<GattSnapshot> {
+        self.snapshots.remove(&address_key(address))
+    }
+
+    /// Removes every cached snapshot
+    pub fn clear(&mut self) {
+        self.snapshots.clear()
+    }
+}
+
+/// Gets the 16 bit uuid of an id, if it is a Bluetooth SIG one
+fn uuid16(id: &BleId) -> Option<u16> {
+    match id.shortest() {
+        BleId::FromUuid16(uuid) => Some(uuid),
+        _ => None,
+    }
+}
+
+/// Gets an id as text, short ids as `0x` hexadecimals and the rest in the canonical form
+pub(crate) fn id_string(id: &BleId) -> String {
+    match id.shortest() {
+        BleId::FromUuid16(uuid) => format!("0x{uuid:04X}"),
+        BleId::FromUuid32(uuid) => format!("0x{uuid:08X}"),
+        long => long.to_string(),
+    }
+}
+
+fn attribute_string(id: &BleId, name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("{} ({})", id_string(id), name),
+        None => id_string(id),
+    }
+}
+
+pub(crate) fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Option<Vec<u8>>) -> fmt::Result {
+    match value {
+        Some(value) => writeln!(f, " = {}", hex_string(value)),
+        None => writeln!(f),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use esp32_nimble::BLEAddressType;
+
+    fn snapshot() -> GattSnapshot {
+        let address = BLEAddress::new([1, 2, 3, 4, 5, 6], BLEAddressType::Public);
+        let mut battery = ServiceSnapshot::new(BleId::FromUuid16(0x180F));
+        let mut level = CharacteristicSnapshot::new(
+            BleId::FromUuid16(0x2A19),
+            CharacteristicProperties {
+                read: true,
+                notify: true,
+                ..Default::default()
+            },
+            Some(vec![0x64]),
+        );
+        level.descriptors.push(DescriptorSnapshot::new(
+            BleId::FromUuid16(0x2902),
+            Some(vec![0, 0]),
+        ));
+        battery.characteristics.push(level);
+
+        let mut custom = ServiceSnapshot::new(BleId::from_name("custom"));
+        custom.characteristics.push(CharacteristicSnapshot::new(
+            BleId::FromUuid32(0x1234_5678),
+            CharacteristicProperties {
+                write: true,
+                ..Default::default()
+            },
+            None,
+        ));
+
+        let mut snapshot = GattSnapshot::new(address);
+        snapshot.services = vec![battery, custom];
+        snapshot
+    }
+
+    #[test]
+    fn gatt_snapshot_01_resolves_standard_names() {
+        assert_eq!(
+            StandardServiceId::from_uuid16(0x180F),
+            Some(StandardServiceId::Battery)
+        );
+        assert_eq!(StandardCharacteristicId::from_uuid16(0x0001), None);
+
+        let snapshot = snapshot();
+        let battery = &snapshot.services[0];
+        assert_eq!(battery.name.as_deref(), Some("Battery"));
+        assert_eq!(
+            battery.characteristics[0].name.as_deref(),
+            Some("BatteryLevel")
+        );
+        assert_eq!(
+            battery.characteristics[0].descriptors[0].name.as_deref(),
+            Some("ClientCharacteristicConfiguration")
+        );
+        assert_eq!(snapshot.services[1].name, None);
+        assert!(snapshot
+            .characteristic(&BleId::FromUuid32(0x180F), &BleId::FromUuid16(0x2A19))
+            .is_some());
+    }
+
+    #[test]
+    fn gatt_snapshot_02_json_and_text() {
+        let snapshot = snapshot();
+        let json = snapshot.to_json();
+        let level = &json["services"][0]["characteristics"][0];
+        assert_eq!(json["address"], "01:02:03:04:05:06");
+        assert_eq!(json["services"][0]["uuid"], "0x180F");
+        assert_eq!(level["properties"], json!(["read", "notify"]));
+        assert_eq!(level["value"], "64");
+        assert_eq!(level["descriptors"][0]["value"], "0000");
+        let custom = &json["services"][1];
+        assert_eq!(custom["name"], Value::Null);
+        assert_eq!(custom["characteristics"][0]["uuid"], "0x12345678");
+        assert_eq!(custom["characteristics"][0]["value"], Value::Null);
+
+        let text = snapshot.to_string();
+        assert!(text.starts_with("GATT of 01:02:03:04:05:06\nService 0x180F (Battery)\n"));
+        assert!(text.contains("  Characteristic 0x2A19 (BatteryLevel) [read, notify] = 64\n"));
+        assert!(text.contains("    Descriptor 0x2902 (ClientCharacteristicConfiguration) = 0000\n"));
+        assert!(text.ends_with("  Characteristic 0x12345678 [write]\n"));
+    }
+
+    #[test]
+    fn gatt_snapshot_03_cache_by_address() {
+        let mut cache = GattSnapshotCache::new();
+        let snapshot = snapshot();
+        let other = BLEAddress::new([1, 1, 1, 1, 1, 1], BLEAddressType::Public);
+        cache.insert(snapshot.clone());
+        assert_eq!(cache.get(&snapshot.address), Some(&snapshot));
+        assert!(cache.get(&other).is_none());
+        assert_eq!(cache.remove(&snapshot.address), Some(snapshot.clone()));
+        assert!(cache.get(&snapshot.address).is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use std::marker::PhantomData;
+
+use super::{
+    ble_standard_uuids::StandardDescriptorId, AttErrorCode, BleError, BleId, Characteristic,
+    ConnectionInformation, Descriptor, Service,
+};
+
+const BLUETOOTH_SIG_NAMESPACE: u8 = 0x01;
+const UNITLESS: u16 = 0x2700;
+
+/// A value that can be stored in a characteristic. Values are encoded in little endian, as
+/// the Bluetooth specification requires.
+/// - `FORMAT`: The format type used in the characteristic presentation format descriptor.
+pub trait GattValue: Sized {
+    const FORMAT: u8;
+
+    /// Encodes the value into the bytes of a characteristic
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Decodes the value from the bytes of a characteristic
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the bytes do not represent a value of this type.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError>;
+}
+
+macro_rules! impl_gatt_value_for_numbers {
+    ($($t:ty => $format:expr),* $(,)?) => {
+        $(
+            impl GattValue for $t {
+                const FORMAT: u8 = $format;
+
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+                    let bytes = bytes.try_into().map_err(|_| BleError::InvalidValue)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_gatt_value_for_numbers!(
+    u8 => 0x04,
+    u16 => 0x06,
+    u32 => 0x08,
+    u64 => 0x0A,
+    i8 => 0x0C,
+    i16 => 0x0E,
+    i32 => 0x10,
+    i64 => 0x13,
+    f32 => 0x14,
+    f64 => 0x15,
+);
+
+impl GattValue for bool {
+    const FORMAT: u8 = 0x01;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(BleError::InvalidValue),
+        }
+    }
+}
+
+impl GattValue for String {
+    const FORMAT: u8 = 0x19;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| BleError::InvalidValue)
+    }
+}
+
+impl GattValue for Vec<u8> {
+    const FORMAT: u8 = 0x1B;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, BleError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// A characteristic whose value has a type. It knows the service it is part of, so it can be
+/// set and read through the [crate::ble::BleServer] without handling bytes.
+#[derive(Clone, Debug)]
+pub struct TypedCharacteristic<T: GattValue> {
+    service_id: BleId,
+    characteristic: Characteristic,
+    value_type: PhantomData<T>,
+}
+
+impl<T: GattValue + 'static> TypedCharacteristic<T> {
+    /// Creates a new TypedCharacteristic
+    ///
+    /// # Arguments
+    ///
+    /// - `service_id`: The BleId of the service the characteristic is part of
+    /// - `characteristic`: The Characteristic with its id and properties already set
+    /// - `initial`: The initial value of the characteristic
+    ///
+    /// # Returns
+    ///
+    /// The new TypedCharacteristic
+    pub fn new(service_id: BleId, mut characteristic: Characteristic, initial: T) -> Self {
+        characteristic.update_data(initial.to_bytes());
+        Self {
+            service_id,
+            characteristic,
+            value_type: PhantomData,
+        }
+    }
+
+    /// Adds a characteristic user description descriptor, with the text clients show for the characteristic
+    ///
+    /// # Returns
+    ///
+    /// The TypedCharacteristic itself
+    pub fn with_description(mut self, description: &str) -> Self {
+        let descriptor = Descriptor::new(
+            BleId::from_standard_descriptor(StandardDescriptorId::CharacteristicUserDescription),
+            description.as_bytes().to_vec(),
+        )
+        .readable(true);
+        self.characteristic = self.characteristic.add_descriptor(&descriptor);
+        self
+    }
+
+    /// Adds a characteristic presentation format descriptor, so clients know how to show the value
+    ///
+    /// # Arguments
+    ///
+    /// - `exponent`: The value shown is the value multiplied by 10 to the power of the exponent
+    /// - `unit`: The Bluetooth assigned number of the unit, or `None` if the value is unitless
+    ///
+    /// # Returns
+    ///
+    /// The TypedCharacteristic itself
+    pub fn with_presentation_format(mut self, exponent: i8, unit: Option<u16>) -> Self {
+        let mut data = vec![T::FORMAT, exponent as u8];
+        data.extend(unit.unwrap_or(UNITLESS).to_le_bytes());
+        data.push(BLUETOOTH_SIG_NAMESPACE);
+        data.extend(0_u16.to_le_bytes());
+        let descriptor = Descriptor::new(
+            BleId::from_standard_descriptor(StandardDescriptorId::CharacteristicPresentationFormat),
+            data,
+        )
+        .readable(true);
+        self.characteristic = self.characteristic.add_descriptor(&descriptor);
+        self
+    }
+
+    /// Sets a callback to be executed when a client writes the characteristic. Same as
+    /// [Characteristic::on_write], but the callback receives the decoded value. Writes that
+    /// can not be decoded are rejected with [AttErrorCode::InvalidAttributeValueLength].
+    ///
+    /// Note: The callback only takes effect once the service is set on the server.
+    ///
+    /// # Returns
+    ///
+    /// The TypedCharacteristic itself
+    pub fn on_write<C: FnMut(T, &ConnectionInformation) -> Result<(), AttErrorCode> + 'static>(
+        &mut self,
+        mut callback: C,
+    ) -> &mut Self {
+        self.characteristic = self.characteristic.clone().on_write(move |bytes, client| {
+            let value =
+                T::from_bytes(bytes).map_err(|_| AttErrorCode::InvalidAttributeValueLength)?;
+            callback(value, client)
+        });
+        self
+    }
+
+    /// Sets a callback to be executed when a client reads the characteristic. Same as
+    /// [Characteristic::on_read], but the callback returns a typed value.
+    ///
+    /// Note: The callback only takes effect once the service is set on the server.
+    ///
+    /// # Returns
+    ///
+    /// The TypedCharacteristic itself
+    pub fn on_read<C: FnMut(&ConnectionInformation) -> T + 'static>(
+        &mut self,
+        mut callback: C,
+    ) -> &mut Self {
+        self.characteristic = self
+            .characteristic
+            .clone()
+            .on_read(move |client| callback(client).to_bytes());
+        self
+    }
+
+    /// Updates the local value of the characteristic. To make it visible to clients use
+    /// [crate::ble::BleServer::set] instead.
+    pub fn set_value(&mut self, value: &T) {
+        self.characteristic.update_data(value.to_bytes());
+    }
+
+    /// Gets the local value of the characteristic
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::InvalidValue`: If the data of the characteristic does not represent a value of this type.
+    pub fn value(&self) -> Result<T, BleError> {
+        T::from_bytes(&self.characteristic.data)
+    }
+
+    /// Gets the id of the service the characteristic is part of
+    pub fn service_id(&self) -> &BleId {
+        &self.service_id
+    }
+
+    /// Gets the untyped characteristic
+    pub fn characteristic(&self) -> &Characteristic {
+        &self.characteristic
+    }
+}
+
+/// A service made of typed characteristics, usually implemented with the derive macro of the
+/// same name.
+///
+/// # Example
+///
+/// ```
+/// use esp32framework::ble::{utils::TypedCharacteristic, GattService};
+///
+/// #[derive(GattService)]
+/// #[service(uuid = 0x181A)]
+/// struct Environment {
+///     #[characteristic(uuid = 0x2A6E, read, notify, description = "Temperature", unit = 0x272F, exponent = -2)]
+///     temperature: TypedCharacteristic<i16>,
+///     #[characteristic(uuid = "c0de0001-0000-1000-8000-00805f9b34fb", read, write, initial = 20.0)]
+///     setpoint: TypedCharacteristic<f32>,
+/// }
+/// ```
+pub trait GattService {
+    /// Gets the id of the service
+    fn service_id(&self) -> BleId;
+
+    /// Gets the characteristics of the service
+    fn characteristics(&self) -> Vec<Characteristic>;
+
+    /// Creates the Service to set on a server
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceTooBig`: If the id of the service exceeds the maximum size.
+    fn service(&self) -> Result<Service, BleError> {
+        Ok(Service::new(&self.service_id(), vec![])?.add_characteristics(&self.characteristics()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gatt_value_01_numbers_are_little_endian() {
+        assert_eq!(0x1234_u16.to_bytes(), vec![0x34, 0x12]);
+        assert_eq!((-2_i32).to_bytes(), vec![0xFE, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(u16::from_bytes(&[0x34, 0x12]).unwrap(), 0x1234);
+        assert_eq!(f32::from_bytes(&21.5_f32.to_bytes()).unwrap(), 21.5);
+    }
+
+    #[test]
+    fn gatt_value_02_wrong_length_is_invalid() {
+        assert!(matches!(
+            u32::from_bytes(&[1, 2]),
+            Err(BleError::InvalidValue)
+        ));
+        assert!(matches!(
+            bool::from_bytes(&[2]),
+            Err(BleError::InvalidValue)
+        ));
+    }
+
+    #[test]
+    fn gatt_value_03_strings_and_bools() {
+        assert_eq!(String::from_bytes(b"hola").unwrap(), "hola");
+        assert!(String::from_bytes(&[0xFF]).is_err());
+        assert!(bool::from_bytes(&true.to_bytes()).unwrap());
+    }
+}
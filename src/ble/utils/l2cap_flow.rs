@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use super::BleError;
+
+/// Smallest SDU size an L2CAP connection-oriented channel can be opened with
+pub const MIN_L2CAP_MTU: u16 = 23;
+/// LE PSMs go from 0x0001 to 0x00FF. The ones up to 0x007F are assigned by the Bluetooth SIG,
+/// and the rest are free for applications.
+const MIN_PSM: u16 = 0x0001;
+const MAX_PSM: u16 = 0x00FF;
+/// Amount of received SDUs kept until the peer must wait for new credits
+pub(crate) const DEFAULT_RECEIVE_WINDOW: usize = 4;
+
+/// Checks the PSM is a valid LE PSM
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the PSM is not between 0x0001 and 0x00FF
+pub(crate) fn check_psm(psm: u16) -> Result<(), BleError> {
+    if !(MIN_PSM..=MAX_PSM).contains(&psm) {
+        return Err(BleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Checks the biggest SDU a channel is opened with
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the MTU is smaller than [MIN_L2CAP_MTU]
+pub(crate) fn check_l2cap_mtu(mtu: u16) -> Result<(), BleError> {
+    if mtu < MIN_L2CAP_MTU {
+        return Err(BleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Splits a buffer in the SDUs sent through a channel, each one of at most `peer_mtu` bytes
+pub(crate) fn sdu_segments(data: &[u8], peer_mtu: u16) -> impl Iterator<Item = &[u8]> {
+    data.chunks(peer_mtu.max(1) as usize)
+}
+
+/// SDUs received on a channel and not yet read. The peer can only send an SDU after it gets a
+/// credit, and a credit is only given while there is room in the window, so a slow reader makes
+/// the peer wait instead of losing data.
+#[derive(Debug)]
+pub(crate) struct SduQueue {
+    sdus: VecDeque<Vec<u8>>,
+    window: usize,
+    credit_withheld: bool,
+}
+
+impl SduQueue {
+    /// Creates a new empty SduQueue, that keeps up to `window` SDUs
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            sdus: VecDeque::new(),
+            window: window.max(1),
+            credit_withheld: false,
+        }
+    }
+
+    /// Stores a received SDU
+    ///
+    /// # Returns
+    ///
+    /// Whether the peer can be given a credit for the next SDU now. If not, it is given when an
+    /// SDU is read with [Self::pop].
+    pub(crate) fn push(&mut self, sdu: Vec<u8>) -> bool {
+        self.sdus.push_back(sdu);
+        self.credit_withheld = self.sdus.len() >= self.window;
+        !self.credit_withheld
+    }
+
+    /// Reads the oldest SDU
+    ///
+    /// # Returns
+    ///
+    /// The SDU, if there was any, and whether the credit withheld must be given to the peer now
+    pub(crate) fn pop(&mut self) -> (Option<Vec<u8>>, bool) {
+        let sdu = self.sdus.pop_front();
+        let give_credit = sdu.is_some() && self.credit_withheld;
+        if give_credit {
+            self.credit_withheld = false;
+        }
+        (sdu, give_credit)
+    }
+
+    /// Gets the amount of SDUs not yet read
+    pub(crate) fn len(&self) -> usize {
+        self.sdus.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn l2cap_flow_01_checks_and_segments() {
+        assert!(check_psm(0x0080).is_ok());
+        assert!(check_psm(0x00FF).is_ok());
+        assert!(check_psm(0).is_err());
+        assert!(check_psm(0x0100).is_err());
+        assert!(check_l2cap_mtu(23).is_ok());
+        assert!(check_l2cap_mtu(22).is_err());
+
+        let data: Vec<u8> = (0..=255).cycle().take(5000).map(|b| b as u8).collect();
+        let segments: Vec<&[u8]> = sdu_segments(&data, 2048).collect();
+        assert_eq!(
+            segments.iter().map(|s| s.len()).collect::<Vec<_>>(),
+            vec![2048, 2048, 904]
+        );
+        assert_eq!(segments.concat(), data);
+    }
+
+    #[test]
+    fn l2cap_flow_02_withholds_credits_while_full() {
+        let mut queue = SduQueue::new(2);
+        assert!(queue.push(vec![1]));
+        assert!(!queue.push(vec![2]));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop(), (Some(vec![1]), true));
+        assert_eq!(queue.pop(), (Some(vec![2]), false));
+        assert_eq!(queue.pop(), (None, false));
+        assert!(queue.push(vec![3]));
+        assert_eq!(queue.pop(), (Some(vec![3]), false));
+    }
+}
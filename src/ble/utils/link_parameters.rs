@@ -0,0 +1,510 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{c_int, c_void},
+    ptr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex, Once,
+    },
+};
+
+use esp32_nimble::{BLEDevice, BLEError};
+use esp_idf_svc::sys;
+
+use crate::utils::notification::Notifier;
+
+use super::{BleError, DEFAULT_MTU};
+
+/// Biggest ATT MTU allowed by the specification
+pub const MAX_MTU: u16 = 517;
+/// Payload of a link layer packet without Data Length Extension
+pub const MIN_DATA_LENGTH: u16 = 27;
+/// Biggest payload of a link layer packet with Data Length Extension
+pub const MAX_DATA_LENGTH: u16 = 251;
+/// Biggest time to send a packet, which lets the controller use the biggest packets on any PHY
+const MAX_TX_TIME_US: u16 = 17040;
+
+/// Connection intervals, in units of 1.25 ms
+const MIN_CONN_INTERVAL: u16 = 6;
+const MAX_CONN_INTERVAL: u16 = 3200;
+const MAX_CONN_LATENCY: u16 = 499;
+/// Supervision timeouts, in units of 10 ms
+const MIN_SUPERVISION_TIMEOUT: u16 = 10;
+const MAX_SUPERVISION_TIMEOUT: u16 = 3200;
+
+const PHY_1M: u8 = 1;
+const PHY_2M: u8 = 2;
+const PHY_CODED: u8 = 3;
+
+/// Physical layers of Bluetooth Low Energy 5:
+/// - `Le1M`: The 1 Mbps PHY, supported by every device and used when connecting
+/// - `Le2M`: The 2 Mbps PHY, for a higher throughput at a shorter range
+/// - `LeCoded`: The coded PHY, for a longer range at a lower throughput
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlePhy {
+    #[default]
+    Le1M,
+    Le2M,
+    LeCoded,
+}
+
+impl BlePhy {
+    /// Gets the PHY reported by the BLE stack
+    pub(crate) fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            PHY_1M => Some(Self::Le1M),
+            PHY_2M => Some(Self::Le2M),
+            PHY_CODED => Some(Self::LeCoded),
+            _ => None,
+        }
+    }
+
+    /// Gets the mask of the PHY used when setting the preferred ones
+    pub(crate) fn mask(self) -> u8 {
+        match self {
+            Self::Le1M => 0x01,
+            Self::Le2M => 0x02,
+            Self::LeCoded => 0x04,
+        }
+    }
+}
+
+/// Coding of the coded PHY. S8 reaches farther than S2, but at a quarter of the 1 Mbps throughput
+/// instead of a half.
+/// - `Any`: Lets the controller choose
+/// - `S2`: Two symbols per bit, 500 kbps
+/// - `S8`: Eight symbols per bit, 125 kbps
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CodedPhyScheme {
+    #[default]
+    Any,
+    S2,
+    S8,
+}
+
+impl CodedPhyScheme {
+    /// Gets the option of the BLE stack for the scheme
+    pub(crate) fn option(self) -> u16 {
+        match self {
+            Self::Any => 0,
+            Self::S2 => 1,
+            Self::S8 => 2,
+        }
+    }
+}
+
+/// Values of the link layer of a connection, which limit its throughput:
+/// - `tx_phy`: The PHY used to send
+/// - `rx_phy`: The PHY used to receive
+/// - `max_tx_octets`: The biggest payload of the packets sent, bigger than 27 if the Data Length
+///   Extension is in use
+/// - `max_rx_octets`: The biggest payload of the packets received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkParameters {
+    pub tx_phy: BlePhy,
+    pub rx_phy: BlePhy,
+    pub max_tx_octets: u16,
+    pub max_rx_octets: u16,
+}
+
+impl Default for LinkParameters {
+    fn default() -> Self {
+        Self {
+            tx_phy: BlePhy::Le1M,
+            rx_phy: BlePhy::Le1M,
+            max_tx_octets: MIN_DATA_LENGTH,
+            max_rx_octets: MIN_DATA_LENGTH,
+        }
+    }
+}
+
+/// Timing of a connection, negotiated by the central and changed with `update_connection_params`:
+/// - `interval`: Time between connection events, in units of 1.25 ms
+/// - `latency`: Amount of connection events the peripheral can skip if it has nothing to send
+/// - `timeout`: Time without packets after which the connection is lost, in units of 10 ms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionParams {
+    pub interval: u16,
+    pub latency: u16,
+    pub timeout: u16,
+}
+
+/// Enums the values of a connection that can be negotiated again once it is open:
+/// - `Mtu`: The new ATT MTU
+/// - `DataLength`: The new biggest payloads of the link layer packets
+/// - `Phy`: The new PHYs used to send and receive
+/// - `ConnectionParams`: The new interval, latency and supervision timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkChange {
+    Mtu(u16),
+    DataLength {
+        max_tx_octets: u16,
+        max_rx_octets: u16,
+    },
+    Phy {
+        tx_phy: BlePhy,
+        rx_phy: BlePhy,
+    },
+    ConnectionParams(ConnectionParams),
+}
+
+/// A change of the link of the connection of the given handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LinkEvent {
+    pub(crate) conn_handle: u16,
+    pub(crate) change: LinkChange,
+}
+
+/// Link parameters of every open connection, kept up to date with the events of the BLE stack
+#[derive(Debug, Default)]
+pub(crate) struct LinkTable {
+    links: BTreeMap<u16, LinkParameters>,
+}
+
+impl LinkTable {
+    const fn new() -> Self {
+        Self {
+            links: BTreeMap::new(),
+        }
+    }
+
+    /// Starts tracking a new connection, which always starts with the default parameters
+    pub(crate) fn connect(&mut self, conn_handle: u16) {
+        self.links.insert(conn_handle, LinkParameters::default());
+    }
+
+    /// Stops tracking a closed connection
+    pub(crate) fn disconnect(&mut self, conn_handle: u16) {
+        self.links.remove(&conn_handle);
+    }
+
+    /// Applies a change to the parameters of its connection
+    pub(crate) fn apply(&mut self, event: &LinkEvent) {
+        let link = self.links.entry(event.conn_handle).or_default();
+        match event.change {
+            LinkChange::Mtu(_) | LinkChange::ConnectionParams(_) => {}
+            LinkChange::DataLength {
+                max_tx_octets,
+                max_rx_octets,
+            } => {
+                link.max_tx_octets = max_tx_octets;
+                link.max_rx_octets = max_rx_octets;
+            }
+            LinkChange::Phy { tx_phy, rx_phy } => {
+                link.tx_phy = tx_phy;
+                link.rx_phy = rx_phy;
+            }
+        }
+    }
+
+    /// Gets the parameters of a connection, or the default ones if it is unknown
+    pub(crate) fn get(&self, conn_handle: u16) -> LinkParameters {
+        self.links.get(&conn_handle).copied().unwrap_or_default()
+    }
+}
+
+/// Checks that an MTU can be requested
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the MTU is not between 23 and 517
+pub(crate) fn check_mtu(mtu: u16) -> Result<(), BleError> {
+    if !(DEFAULT_MTU..=MAX_MTU).contains(&mtu) {
+        return Err(BleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Checks that connection parameters can be requested. Intervals go from 7.5 ms to 4 s, the
+/// latency up to 499 events, and the timeout from 100 ms to 32 s. The timeout must also be longer
+/// than twice the time the peripheral can go without answering, `(1 + latency) * max_interval`.
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If any of the parameters is out of its range
+pub(crate) fn check_connection_params(
+    min_interval: u16,
+    max_interval: u16,
+    latency: u16,
+    timeout: u16,
+) -> Result<(), BleError> {
+    let valid_interval = |interval| (MIN_CONN_INTERVAL..=MAX_CONN_INTERVAL).contains(&interval);
+    if !valid_interval(min_interval)
+        || !valid_interval(max_interval)
+        || min_interval > max_interval
+        || latency > MAX_CONN_LATENCY
+        || !(MIN_SUPERVISION_TIMEOUT..=MAX_SUPERVISION_TIMEOUT).contains(&timeout)
+        // timeout * 10 ms > 2 * (1 + latency) * max_interval * 1.25 ms
+        || (timeout as u32) * 4 <= (1 + latency as u32) * max_interval as u32
+    {
+        return Err(BleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Checks that a packet payload can be requested
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the payload is not between 27 and 251 bytes
+pub(crate) fn check_data_length(tx_octets: u16) -> Result<(), BleError> {
+    if !(MIN_DATA_LENGTH..=MAX_DATA_LENGTH).contains(&tx_octets) {
+        return Err(BleError::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Link parameters of every connection, of both the server and the client roles. Connection
+/// handles are unique in the BLE stack, so a single table is shared.
+static LINKS: Mutex<LinkTable> = Mutex::new(LinkTable::new());
+/// Where the link changes are sent, together with the notifier that wakes up the receiver
+static LINK_SUBSCRIBERS: Mutex<Vec<(Sender<LinkEvent>, Notifier)>> = Mutex::new(Vec::new());
+static LISTENER: Once = Once::new();
+
+/// Gets the link parameters of a connection
+pub(crate) fn link_parameters(conn_handle: u16) -> LinkParameters {
+    LINKS
+        .lock()
+        .map(|links| links.get(conn_handle))
+        .unwrap_or_default()
+}
+
+/// Subscribes to the link changes of every connection. The notifier is notified after each
+/// change is sent.
+pub(crate) fn subscribe_link_changes(notifier: Notifier) -> Receiver<LinkEvent> {
+    register_gap_listener();
+    let (sender, receiver) = channel();
+    if let Ok(mut subscribers) = LINK_SUBSCRIBERS.lock() {
+        subscribers.push((sender, notifier));
+    }
+    receiver
+}
+
+/// Sets the MTU offered in the MTU exchanges of the next connections
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the MTU is not between 23 and 517
+/// - `BleError::Code`: On other errors
+pub(crate) fn set_preferred_mtu(mtu: u16) -> Result<(), BleError> {
+    check_mtu(mtu)?;
+    Ok(BLEDevice::take().set_preferred_mtu(mtu)?)
+}
+
+/// Starts an MTU exchange on a connection, offering the given MTU. The MTU can only be exchanged
+/// once per connection.
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the MTU is not between 23 and 517
+/// - `BleError::MtuAlreadyExchanged`: If the MTU of the connection was already exchanged
+/// - `BleError::Code`: On other errors
+pub(crate) fn request_mtu(conn_handle: u16, mtu: u16) -> Result<(), BleError> {
+    set_preferred_mtu(mtu)?;
+    let rc = unsafe { sys::ble_gattc_exchange_mtu(conn_handle, None, ptr::null_mut()) };
+    if rc as u32 == sys::BLE_HS_EALREADY {
+        return Err(BleError::MtuAlreadyExchanged);
+    }
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+/// Asks the controller to use packets with a payload of up to `tx_octets` on a connection
+///
+/// # Errors
+///
+/// - `BleError::InvalidParameters`: If the payload is not between 27 and 251 bytes
+/// - `BleError::Code`: On other errors
+pub(crate) fn set_data_length(conn_handle: u16, tx_octets: u16) -> Result<(), BleError> {
+    check_data_length(tx_octets)?;
+    let rc = unsafe { sys::ble_gap_set_data_len(conn_handle, tx_octets, MAX_TX_TIME_US) };
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+/// Sets the PHYs preferred on a connection, the controller changes them if the peer supports them
+///
+/// # Errors
+///
+/// - `BleError::Code`: If the controller does not support the PHYs, or on other errors
+pub(crate) fn set_preferred_phy(
+    conn_handle: u16,
+    tx_phy: BlePhy,
+    rx_phy: BlePhy,
+    coded_scheme: CodedPhyScheme,
+) -> Result<(), BleError> {
+    let rc = unsafe {
+        sys::ble_gap_set_prefered_le_phy(
+            conn_handle,
+            tx_phy.mask(),
+            rx_phy.mask(),
+            coded_scheme.option(),
+        )
+    };
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+/// Registers, only once, the listener of the GAP events that change the link parameters
+fn register_gap_listener() {
+    LISTENER.call_once(|| {
+        // The BLE stack keeps the listener for as long as the program runs
+        let listener: &'static mut sys::ble_gap_event_listener =
+            Box::leak(Box::new(unsafe { std::mem::zeroed() }));
+        unsafe {
+            sys::ble_gap_event_listener_register(listener, Some(on_gap_event), ptr::null_mut());
+        }
+    });
+}
+
+/// Listener of the GAP events, executed by the BLE stack. It updates the link table and sends
+/// the link changes to the subscribers.
+extern "C" fn on_gap_event(event: *mut sys::ble_gap_event, _arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    let Ok(mut links) = LINKS.lock() else {
+        return 0;
+    };
+    let link_event = unsafe {
+        match event.type_ as u32 {
+            sys::BLE_GAP_EVENT_CONNECT => {
+                let connect = event.__bindgen_anon_1.connect;
+                if connect.status == 0 {
+                    links.connect(connect.conn_handle);
+                }
+                None
+            }
+            sys::BLE_GAP_EVENT_DISCONNECT => {
+                links.disconnect(event.__bindgen_anon_1.disconnect.conn.conn_handle);
+                None
+            }
+            sys::BLE_GAP_EVENT_MTU => {
+                let mtu = event.__bindgen_anon_1.mtu;
+                Some(LinkEvent {
+                    conn_handle: mtu.conn_handle,
+                    change: LinkChange::Mtu(mtu.value),
+                })
+            }
+            sys::BLE_GAP_EVENT_DATA_LEN_CHG => {
+                let data_len = event.__bindgen_anon_1.data_len_chg;
+                Some(LinkEvent {
+                    conn_handle: data_len.conn_handle,
+                    change: LinkChange::DataLength {
+                        max_tx_octets: data_len.max_tx_octets,
+                        max_rx_octets: data_len.max_rx_octets,
+                    },
+                })
+            }
+            sys::BLE_GAP_EVENT_PHY_UPDATE_COMPLETE => {
+                let phy = event.__bindgen_anon_1.phy_updated;
+                match (BlePhy::from_raw(phy.tx_phy), BlePhy::from_raw(phy.rx_phy)) {
+                    (Some(tx_phy), Some(rx_phy)) if phy.status == 0 => Some(LinkEvent {
+                        conn_handle: phy.conn_handle,
+                        change: LinkChange::Phy { tx_phy, rx_phy },
+                    }),
+                    _ => None,
+                }
+            }
+            sys::BLE_GAP_EVENT_CONN_UPDATE => {
+                let update = event.__bindgen_anon_1.conn_update;
+                let mut desc: sys::ble_gap_conn_desc = std::mem::zeroed();
+                if update.status != 0 || sys::ble_gap_conn_find(update.conn_handle, &mut desc) != 0
+                {
+                    None
+                } else {
+                    Some(LinkEvent {
+                        conn_handle: update.conn_handle,
+                        change: LinkChange::ConnectionParams(ConnectionParams {
+                            interval: desc.conn_itvl,
+                            latency: desc.conn_latency,
+                            timeout: desc.supervision_timeout,
+                        }),
+                    })
+                }
+            }
+            _ => None,
+        }
+    };
+
+    if let Some(link_event) = link_event {
+        links.apply(&link_event);
+        drop(links);
+        if let Ok(mut subscribers) = LINK_SUBSCRIBERS.lock() {
+            subscribers.retain(|(sender, notifier)| {
+                let sent = sender.send(link_event).is_ok();
+                if sent {
+                    notifier.notify();
+                }
+                sent
+            });
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn link_parameters_01_table_tracks_changes() {
+        let mut table = LinkTable::default();
+        table.connect(1);
+        assert_eq!(table.get(1), LinkParameters::default());
+        table.apply(&LinkEvent {
+            conn_handle: 1,
+            change: LinkChange::DataLength {
+                max_tx_octets: 251,
+                max_rx_octets: 251,
+            },
+        });
+        table.apply(&LinkEvent {
+            conn_handle: 1,
+            change: LinkChange::Phy {
+                tx_phy: BlePhy::Le2M,
+                rx_phy: BlePhy::LeCoded,
+            },
+        });
+        table.apply(&LinkEvent {
+            conn_handle: 1,
+            change: LinkChange::Mtu(247),
+        });
+        let link = table.get(1);
+        assert_eq!((link.max_tx_octets, link.max_rx_octets), (251, 251));
+        assert_eq!((link.tx_phy, link.rx_phy), (BlePhy::Le2M, BlePhy::LeCoded));
+
+        table.disconnect(1);
+        table.connect(1);
+        assert_eq!(table.get(1), LinkParameters::default());
+        assert_eq!(table.get(7), LinkParameters::default());
+    }
+
+    #[test]
+    fn link_parameters_02_phys_and_limits() {
+        assert_eq!(BlePhy::from_raw(2), Some(BlePhy::Le2M));
+        assert_eq!(BlePhy::from_raw(3), Some(BlePhy::LeCoded));
+        assert_eq!(BlePhy::from_raw(0), None);
+        assert_eq!(BlePhy::Le1M.mask() | BlePhy::LeCoded.mask(), 0x05);
+        assert_eq!(CodedPhyScheme::S8.option(), 2);
+
+        assert!(check_mtu(23).is_ok());
+        assert!(check_mtu(517).is_ok());
+        assert!(check_mtu(22).is_err());
+        assert!(check_mtu(518).is_err());
+        assert!(check_data_length(251).is_ok());
+        assert!(check_data_length(26).is_err());
+        assert!(check_data_length(252).is_err());
+    }
+
+    #[test]
+    fn link_parameters_03_connection_params() {
+        assert!(check_connection_params(6, 3200, 0, 3200).is_ok());
+        assert!(check_connection_params(24, 40, 4, 100).is_ok());
+        assert!(check_connection_params(5, 40, 0, 100).is_err());
+        assert!(check_connection_params(24, 3201, 0, 3200).is_err());
+        assert!(check_connection_params(40, 24, 0, 100).is_err());
+        assert!(check_connection_params(24, 40, 500, 3200).is_err());
+        assert!(check_connection_params(24, 40, 0, 9).is_err());
+        // 50 * 10 ms is exactly 2 * 5 * 40 * 1.25 ms
+        assert!(check_connection_params(24, 40, 4, 50).is_err());
+        assert!(check_connection_params(24, 40, 4, 51).is_ok());
+    }
+}
@@ -1,18 +1,50 @@
 mod advertised_device;
+mod advertisement_builder;
+mod advertising_schedule;
+mod attribute_handlers;
+mod beacon_frames;
+mod ble_codec;
 mod ble_error;
 mod ble_id;
 mod ble_server_modes;
 pub mod ble_standard_uuids;
 mod connection_information;
+mod dfu_protocol;
+mod extended_advertising;
+mod gateway;
+mod gatt_snapshot;
+mod gatt_value;
+mod l2cap_flow;
+mod link_parameters;
+mod presence_tracker;
+mod reconnect_policy;
 mod remote_service;
 mod security;
 mod service;
+mod subscriptions;
+mod uart_buffer;
 
 pub use advertised_device::*;
+pub use advertisement_builder::*;
+pub use advertising_schedule::*;
+pub use attribute_handlers::*;
+pub use beacon_frames::*;
+pub use ble_codec::*;
 pub use ble_error::*;
 pub use ble_id::*;
 pub use ble_server_modes::*;
 pub use connection_information::*;
+pub use dfu_protocol::*;
+pub use extended_advertising::*;
+pub use gateway::*;
+pub use gatt_snapshot::*;
+pub use gatt_value::*;
+pub use l2cap_flow::*;
+pub use link_parameters::*;
+pub use presence_tracker::*;
+pub use reconnect_policy::*;
 pub use remote_service::*;
 pub use security::*;
 pub use service::*;
+pub use subscriptions::*;
+pub use uart_buffer::*;
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use esp32_nimble::BLEAddress;
+
+use super::{address_key, AddressKey, BleAdvertisedDevice};
+
+/// Filters repeated advertisements of the same device, letting through only one advertisement
+/// per address every `window`.
+#[derive(Debug)]
+pub(crate) struct DuplicateFilter {
+    window: Duration,
+    last_seen: HashMap<AddressKey, Instant>,
+}
+
+impl DuplicateFilter {
+    /// Creates a new DuplicateFilter
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Returns whether an advertisement of the address received at `now` must be reported
+    pub(crate) fn accept(&mut self, address: BLEAddress, now: Instant) -> bool {
+        let window = self.window;
+        self.last_seen
+            .retain(|_, last| now.saturating_duration_since(*last) < window);
+        let key = address_key(&address);
+        if self.last_seen.contains_key(&key) {
+            return false;
+        }
+        self.last_seen.insert(key, now);
+        true
+    }
+}
+
+/// Change of presence of a device, reported by a [PresenceTracker]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresenceEvent {
+    /// The device was seen for the first time, or again after disappearing
+    Appeared { address: BLEAddress, rssi: f32 },
+    /// The device was not seen during the timeout of the tracker
+    Disappeared { address: BLEAddress },
+}
+
+/// State of a device seen by a [PresenceTracker]
+#[derive(Debug, Clone, Copy)]
+struct Presence {
+    address: BLEAddress,
+    rssi: f32,
+    last_seen: Instant,
+}
+
+/// Tracks which devices are nearby from their advertisements. A device appears when it is first
+/// seen and disappears after not being seen for the timeout. The RSSI of each device is smoothed
+/// with an exponential moving average, so a single weak advertisement does not make it look
+/// farther away.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut tracker = PresenceTracker::new(Duration::from_secs(30), 0.3);
+/// while let Some(device) = stream.next().await {
+///     if let Some(event) = tracker.track(&device) {
+///         println!("{:?}", event);
+///     }
+///     for event in tracker.check_timeouts() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PresenceTracker {
+    timeout: Duration,
+    smoothing: f32,
+    devices: HashMap<AddressKey, Presence>,
+}
+
+impl PresenceTracker {
+    /// Creates a new PresenceTracker
+    ///
+    /// # Arguments
+    ///
+    /// - `timeout`: Time without advertisements after which a device disappears
+    /// - `smoothing`: Weight of each new RSSI measurement, between 0 and 1. Lower values smooth
+    ///   more, and 1 disables the smoothing. Values out of range are clamped.
+    ///
+    /// # Returns
+    ///
+    /// The new PresenceTracker
+    pub fn new(timeout: Duration, smoothing: f32) -> Self {
+        Self {
+            timeout,
+            smoothing: smoothing.clamp(f32::EPSILON, 1.0),
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Updates the tracker with an advertisement received now
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with [PresenceEvent::Appeared] if the device was not present, otherwise `None`
+    pub fn track(&mut self, device: &BleAdvertisedDevice) -> Option<PresenceEvent> {
+        self.track_at(*device.addr(), device.rssi(), Instant::now())
+    }
+
+    /// Same as [Self::track] but with the address, rssi and time of the advertisement
+    fn track_at(&mut self, address: BLEAddress, rssi: i32, now: Instant) -> Option<PresenceEvent> {
+        let rssi = rssi as f32;
+        let key = address_key(&address);
+        match self.devices.get_mut(&key) {
+            Some(presence) => {
+                presence.rssi += self.smoothing * (rssi - presence.rssi);
+                presence.last_seen = now;
+                None
+            }
+            None => {
+                self.devices.insert(
+                    key,
+                    Presence {
+                        address,
+                        rssi,
+                        last_seen: now,
+                    },
+                );
+                Some(PresenceEvent::Appeared { address, rssi })
+            }
+        }
+    }
+
+    /// Removes the devices not seen during the timeout
+    ///
+    /// # Returns
+    ///
+    /// A [PresenceEvent::Disappeared] for each removed device
+    pub fn check_timeouts(&mut self) -> Vec<PresenceEvent> {
+        self.check_timeouts_at(Instant::now())
+    }
+
+    /// Same as [Self::check_timeouts] but at a given time
+    fn check_timeouts_at(&mut self, now: Instant) -> Vec<PresenceEvent> {
+        let timeout = self.timeout;
+        let mut events = vec![];
+        self.devices.retain(|_, presence| {
+            let present = now.saturating_duration_since(presence.last_seen) < timeout;
+            if !present {
+                events.push(PresenceEvent::Disappeared {
+                    address: presence.address,
+                });
+            }
+            present
+        });
+        events
+    }
+
+    /// Gets the smoothed RSSI of a device
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the RSSI, or `None` if the device is not present
+    pub fn rssi(&self, address: &BLEAddress) -> Option<f32> {
+        self.devices
+            .get(&address_key(address))
+            .map(|presence| presence.rssi)
+    }
+
+    /// Returns whether a device is present
+    pub fn is_present(&self, address: &BLEAddress) -> bool {
+        self.devices.contains_key(&address_key(address))
+    }
+
+    /// Gets the addresses of every present device
+    pub fn present(&self) -> Vec<BLEAddress> {
+        self.devices
+            .values()
+            .map(|presence| presence.address)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use esp32_nimble::BLEAddressType;
+
+    fn address(last: u8) -> BLEAddress {
+        BLEAddress::new([last, 0, 0, 0, 0, 0], BLEAddressType::Public)
+    }
+
+    #[test]
+    fn presence_tracker_01_appears_once() {
+        let mut tracker = PresenceTracker::new(Duration::from_secs(10), 0.5);
+        let now = Instant::now();
+        assert_eq!(
+            tracker.track_at(address(1), -60, now),
+            Some(PresenceEvent::Appeared {
+                address: address(1),
+                rssi: -60.0
+            })
+        );
+        assert_eq!(tracker.track_at(address(1), -70, now), None);
+        assert_eq!(tracker.rssi(&address(1)), Some(-65.0));
+    }
+
+    #[test]
+    fn presence_tracker_02_disappears_after_timeout() {
+        let mut tracker = PresenceTracker::new(Duration::from_secs(10), 1.0);
+        let start = Instant::now();
+        tracker.track_at(address(1), -60, start);
+        tracker.track_at(address(2), -60, start + Duration::from_secs(8));
+        let events = tracker.check_timeouts_at(start + Duration::from_secs(12));
+        assert_eq!(
+            events,
+            vec![PresenceEvent::Disappeared {
+                address: address(1)
+            }]
+        );
+        assert!(!tracker.is_present(&address(1)));
+        assert!(tracker.is_present(&address(2)));
+        assert!(tracker
+            .track_at(address(1), -50, start + Duration::from_secs(13))
+            .is_some());
+    }
+
+    #[test]
+    fn presence_tracker_03_duplicate_filter() {
+        let mut filter = DuplicateFilter::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert!(filter.accept(address(1), start));
+        assert!(filter.accept(address(2), start));
+        assert!(!filter.accept(address(1), start + Duration::from_millis(500)));
+        assert!(filter.accept(address(1), start + Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn presence_tracker_04_address_type_is_part_of_the_device() {
+        let mut tracker = PresenceTracker::new(Duration::from_secs(10), 1.0);
+        let now = Instant::now();
+        let random = BLEAddress::new([1, 0, 0, 0, 0, 0], BLEAddressType::Random);
+        tracker.track_at(address(1), -60, now);
+        assert!(tracker.track_at(random, -70, now).is_some());
+        assert_eq!(tracker.rssi(&address(1)), Some(-60.0));
+        assert_eq!(tracker.rssi(&random), Some(-70.0));
+    }
+}
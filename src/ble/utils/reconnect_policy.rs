@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+const DEFAULT_MULTIPLIER: u32 = 2;
+
+/// Exponential backoff between the attempts to reconnect to a lost server:
+/// - `initial_delay`: Time waited before the first attempt
+/// - `max_delay`: Biggest time waited between two attempts
+/// - `multiplier`: How much the time waited grows after each failed attempt
+/// - `max_attempts`: Amount of attempts after which the client gives up, or `None` to never
+///   give up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: u32,
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Creates a new ReconnectPolicy that waits 1 second before the first attempt, doubling the
+    /// time waited up to a minute, and never gives up
+    pub fn new() -> Self {
+        Self {
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            multiplier: DEFAULT_MULTIPLIER,
+            max_attempts: None,
+        }
+    }
+
+    /// Sets the time waited before the first attempt
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Sets the biggest time waited between two attempts
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets how much the time waited grows after each failed attempt. With 1 every attempt
+    /// waits the initial delay.
+    pub fn multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier.max(1);
+        self
+    }
+
+    /// Sets the amount of attempts after which the client gives up
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Gets the time to wait before an attempt
+    ///
+    /// # Arguments
+    ///
+    /// - `attempt`: The number of the attempt, starting at 0
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the time to wait, or `None` if the client must give up
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+        let factor = self.multiplier.checked_pow(attempt).unwrap_or(u32::MAX);
+        let delay = self
+            .initial_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay);
+        Some(delay.min(self.max_delay))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reconnect_policy_01_backoff_grows_up_to_max_delay() {
+        let policy = ReconnectPolicy::new()
+            .initial_delay(Duration::from_millis(500))
+            .max_delay(Duration::from_secs(5));
+        let delays: Vec<_> = (0..6).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [500, 1000, 2000, 4000, 5000, 5000].map(|ms| Some(Duration::from_millis(ms)))
+        );
+        assert_eq!(policy.delay(u32::MAX), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn reconnect_policy_02_gives_up_after_max_attempts() {
+        let policy = ReconnectPolicy::new().multiplier(1).max_attempts(3);
+        assert_eq!(policy.delay(0), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(3), None);
+        assert_eq!(ReconnectPolicy::new().multiplier(0).multiplier, 1);
+    }
+}
@@ -7,7 +7,9 @@ use crate::utils::{
     notification::Notifier,
 };
 
-use super::{BleError, BleId};
+use super::{
+    BleCodec, BleError, BleId, CharacteristicProperties, CharacteristicSnapshot, DescriptorSnapshot,
+};
 
 /// A remote characteristic representing an available characteristic of a given service of a
 /// ble connection. Can be used to read, write and notify.
@@ -22,13 +24,16 @@ pub struct RemoteCharacteristic {
 struct RemoteCharacteristicUpdater {
     notify_callback: Option<Box<dyn FnMut(Vec<u8>)>>,
     notify_queue: Option<ISRByteArrayQueue>,
+    error_callback: Option<Box<dyn FnMut(BleError)>>,
 }
 
 /// A remote characteristic representing an available characteristic of a given service of a
 /// ble connection. Can be used to read, write and notify.
+/// - `subscribed`: Whether the characteristic is subscribed to notifications or indications
 struct _RemoteCharacteristic {
     characteristic: BLERemoteCharacteristic,
     notifier: Option<Notifier>,
+    subscribed: bool,
 }
 
 impl RemoteCharacteristicUpdater {
@@ -95,11 +100,60 @@ impl RemoteCharacteristic {
     ///   non notifiable characteristic
     pub fn on_notify<C: FnMut(Vec<u8>) + 'static>(&mut self, callback: C) -> Result<(), BleError> {
         let queue = self.updater.borrow_mut().get_queue();
-        self.inner.borrow_mut().set_notification_on_notify(queue)?;
+        self.inner.borrow_mut().subscribe(queue, false)?;
         self.updater.borrow_mut().notify_callback = Some(Box::new(callback));
         Ok(())
     }
 
+    /// Same as [Self::on_notify], but subscribing to the indications of the characteristic, which
+    /// the client confirms to the server
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotIndicatable`: If the characteristic is not indicatable
+    /// - `BleError::Disconnected`: If connection to the ble server is lost
+    /// - `BleError::Code`: If the subscription fails
+    pub fn on_indicate<C: FnMut(Vec<u8>) + 'static>(
+        &mut self,
+        callback: C,
+    ) -> Result<(), BleError> {
+        let queue = self.updater.borrow_mut().get_queue();
+        self.inner.borrow_mut().subscribe(queue, true)?;
+        self.updater.borrow_mut().notify_callback = Some(Box::new(callback));
+        Ok(())
+    }
+
+    /// Stops receiving the notifications or indications of the characteristic, removing the
+    /// callback set with [Self::on_notify] or [Self::on_indicate]
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing () if the server was told to stop sending them, or a [BleError]
+    /// otherwise. The callback is removed either way.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Disconnected`: If connection to the ble server is lost
+    /// - `BleError::Code`: If the unsubscription fails
+    pub fn unsubscribe(&mut self) -> Result<(), BleError> {
+        self.updater.borrow_mut().notify_callback = None;
+        self.inner.borrow_mut().unsubscribe()
+    }
+
+    /// Sets a callback executed when a subscription of the characteristic is lost because the
+    /// connection dropped. The callback set with [Self::on_notify] or [Self::on_indicate] is
+    /// removed, so it must be set again after reconnecting.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that receives the error, `BleError::Disconnected`
+    pub fn on_error<C: FnMut(BleError) + 'static>(&mut self, callback: C) {
+        self.updater.borrow_mut().error_callback = Some(Box::new(callback));
+    }
+
     /// Efectibly clones the remote characteristic, but is only allowed in the crate
     pub(crate) fn clone(&self) -> Self {
         Self {
@@ -108,11 +162,39 @@ impl RemoteCharacteristic {
         }
     }
 
+    /// Sets a callback executed by the BLE stack every time the characteristic gets notified, and
+    /// subscribes to its notifications. Unlike [Self::on_notify], the callback is not executed on
+    /// the main loop, so it must not block.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::CharacteristicNotNotifiable`: If the characteristic is not notifiable
+    /// - `BleError::Code`: If the subscription fails
+    pub(crate) fn on_raw_notify<C: FnMut(&[u8]) + Send + Sync + 'static>(
+        &mut self,
+        callback: C,
+    ) -> Result<(), BleError> {
+        self.inner.borrow_mut().set_raw_notify(callback)
+    }
+
     /// if a user callback has been set and a notification has been received, then the user
     /// callback will be executed
     pub(crate) fn execute_if_notified(&mut self) {
         self.updater.borrow_mut().execute_if_notified()
     }
+
+    /// Drops the subscription of the characteristic once its connection was closed, reporting
+    /// it to the error callback
+    pub(crate) fn handle_disconnection(&mut self) {
+        if !std::mem::take(&mut self.inner.borrow_mut().subscribed) {
+            return;
+        }
+        let mut updater = self.updater.borrow_mut();
+        updater.notify_callback = None;
+        if let Some(callback) = updater.error_callback.as_mut() {
+            callback(BleError::Disconnected)
+        }
+    }
 }
 
 #[sharable_reference_macro::sharable_reference_wrapper]
@@ -130,6 +212,7 @@ impl _RemoteCharacteristic {
         Self {
             characteristic: characteristic.clone(),
             notifier: Some(notifier),
+            subscribed: false,
         }
     }
 
@@ -153,6 +236,16 @@ impl _RemoteCharacteristic {
         self.characteristic.can_notify()
     }
 
+    /// Returns wheter the characteristic is indicatable or not
+    pub fn is_indicatable(&self) -> bool {
+        self.characteristic.can_indicate()
+    }
+
+    /// Returns whether the characteristic is subscribed to notifications or indications
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribed
+    }
+
     /// Returns wheter the characteristic is broadcastable or not
     pub fn is_broadcastable(&self) -> bool {
         self.characteristic.can_broadcast()
@@ -215,9 +308,54 @@ impl _RemoteCharacteristic {
         block_on(self.write_async(data))
     }
 
-    /// Documented on [RemoteCharacteristic::on_notify]
-    fn set_notification_on_notify(&mut self, mut queue: ISRByteArrayQueue) -> Result<(), BleError> {
-        if !self.is_notifiable() {
+    /// Non blocking async version of [Self::read_as]
+    pub async fn read_as_async<T: BleCodec>(&mut self) -> Result<T, BleError> {
+        T::decode(&self.read_async().await?)
+    }
+
+    /// Attempts to read the characteristics value, decoding it as a `T`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoded value, or a `BleError`
+    ///
+    /// # Errors
+    ///
+    /// `BleError::InvalidValue`: If the value read can not be decoded as a `T`
+    /// `BleError::CharacteristicNotReadable`: If the characteristic is not readable
+    /// `BleError::Disconnected`: If connection to the ble server is lost
+    /// `BleError::Code`: On other errors
+    pub fn read_as<T: BleCodec>(&mut self) -> Result<T, BleError> {
+        block_on(self.read_as_async())
+    }
+
+    /// Non blocking async version of [Self::write_as]
+    pub async fn write_as_async<T: BleCodec>(&mut self, value: &T) -> Result<(), BleError> {
+        self.write_async(&value.encode()).await
+    }
+
+    /// Attempts to write an encoded value on the characteristic, same as [Self::write]
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the operation was successfull or BleError on failure
+    ///
+    /// # Error
+    /// `BleError::CharacteristicNotWritable`: If the characteristic is not writable
+    /// `BleError::Disconnected`: If connection to the ble server is lost
+    /// `BleError::Code`: On other errors
+    pub fn write_as<T: BleCodec>(&mut self, value: &T) -> Result<(), BleError> {
+        block_on(self.write_as_async(value))
+    }
+
+    /// Subscribes to the notifications or indications of the characteristic, forwarding them to
+    /// the queue. Documented on [RemoteCharacteristic::on_notify] and
+    /// [RemoteCharacteristic::on_indicate]
+    fn subscribe(&mut self, mut queue: ISRByteArrayQueue, indicate: bool) -> Result<(), BleError> {
+        if indicate && !self.is_indicatable() {
+            return Err(BleError::CharacteristicNotIndicatable);
+        }
+        if !indicate && !self.is_notifiable() {
             return Err(BleError::CharacteristicNotNotifiable);
         }
         if let Some(notifier) = self.notifier.take() {
@@ -226,6 +364,37 @@ impl _RemoteCharacteristic {
                 queue.send(bytes.to_vec())
             });
         }
+        let subscription = if indicate {
+            block_on(self.characteristic.subscribe_indicate(true))
+        } else {
+            block_on(self.characteristic.subscribe_notify(true))
+        };
+        subscription.map_err(BleError::from_characteristic_context)?;
+        self.subscribed = true;
+        Ok(())
+    }
+
+    /// Documented on [RemoteCharacteristic::unsubscribe]
+    fn unsubscribe(&mut self) -> Result<(), BleError> {
+        if !std::mem::take(&mut self.subscribed) {
+            return Ok(());
+        }
+        block_on(self.characteristic.unsubscribe(true))
+            .map_err(BleError::from_characteristic_context)
+    }
+
+    /// Documented on [RemoteCharacteristic::on_raw_notify]
+    fn set_raw_notify<C: FnMut(&[u8]) + Send + Sync + 'static>(
+        &mut self,
+        callback: C,
+    ) -> Result<(), BleError> {
+        if !self.is_notifiable() {
+            return Err(BleError::CharacteristicNotNotifiable);
+        }
+        self.characteristic.on_notify(callback);
+        block_on(self.characteristic.subscribe_notify(false))
+            .map_err(BleError::from_characteristic_context)?;
+        self.subscribed = true;
         Ok(())
     }
 
@@ -277,6 +446,37 @@ impl _RemoteCharacteristic {
         let remote_descriptors = self.characteristic.get_descriptors().await?;
         Ok(remote_descriptors.map(RemoteDescriptor::from).collect())
     }
+
+    /// Gets the properties of the characteristic, its value if it is readable and every
+    /// descriptor with its value. Values that can not be read are left as `None`.
+    ///
+    /// # Errors
+    ///
+    /// `BleError::Disconnected`: If connection to the ble server is lost
+    /// `BleError::Code`: If the descriptors could not be discovered
+    pub(crate) async fn snapshot_async(&mut self) -> Result<CharacteristicSnapshot, BleError> {
+        let properties = CharacteristicProperties {
+            read: self.is_readable(),
+            write: self.is_writable(),
+            write_no_response: self.is_writable_no_resp(),
+            notify: self.is_notifiable(),
+            indicate: self.is_indicatable(),
+            broadcast: self.is_broadcastable(),
+        };
+        let value = if properties.read {
+            optional_value(self.read_async().await)?
+        } else {
+            None
+        };
+        let mut snapshot = CharacteristicSnapshot::new(self.id(), properties, value);
+        for mut descriptor in self.get_all_descriptors_async().await? {
+            let value = optional_value(descriptor.read_async().await)?;
+            snapshot
+                .descriptors
+                .push(DescriptorSnapshot::new(descriptor.id(), value));
+        }
+        Ok(snapshot)
+    }
 }
 
 /// A remote descriptor representing an available descriptor of a given characteristic.
@@ -342,6 +542,15 @@ impl RemoteDescriptor {
     }
 }
 
+/// Turns the result of a read into an optional value, failing only if the connection was lost
+fn optional_value(read: Result<Vec<u8>, BleError>) -> Result<Option<Vec<u8>>, BleError> {
+    match read {
+        Ok(value) => Ok(Some(value)),
+        Err(BleError::Disconnected) => Err(BleError::Disconnected),
+        Err(_) => Ok(None),
+    }
+}
+
 impl From<&mut BLERemoteDescriptor> for RemoteDescriptor {
     fn from(value: &mut BLERemoteDescriptor) -> Self {
         Self {
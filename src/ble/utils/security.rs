@@ -1,8 +1,17 @@
+use std::sync::Arc;
+
 use super::BleError;
-use esp32_nimble::enums::{AuthReq, SecurityIOCap};
+use esp32_nimble::{
+    enums::{AuthReq, SecurityIOCap},
+    utilities::mutex::Mutex,
+    BLEAddress, BLEDevice,
+};
 
 const MAX_PASKEY: u32 = 999999;
 
+type PasskeyCallback = dyn FnMut() -> u32 + Send;
+type ConfirmPinCallback = dyn FnMut(u32) -> bool + Send;
+
 /// Enums the device's input and output capabilities,
 /// which help determine the level of security and the key
 /// generation method for pairing:
@@ -149,3 +158,157 @@ impl Security {
         self
     }
 }
+
+/// User callbacks of a client for the pairing methods that need an input. They are shared with
+/// every connection of the client and executed by the BLE stack.
+#[derive(Clone, Default)]
+pub(crate) struct PairingCallbacks {
+    passkey: Arc<Mutex<Option<Box<PasskeyCallback>>>>,
+    confirm_pin: Arc<Mutex<Option<Box<ConfirmPinCallback>>>>,
+}
+
+impl PairingCallbacks {
+    /// Sets the callback that returns the passkey shown by the server
+    pub(crate) fn set_passkey<C: FnMut() -> u32 + Send + 'static>(&self, callback: C) {
+        *self.passkey.lock() = Some(Box::new(callback));
+    }
+
+    /// Sets the callback that confirms the pin shown on both devices
+    pub(crate) fn set_confirm_pin<C: FnMut(u32) -> bool + Send + 'static>(&self, callback: C) {
+        *self.confirm_pin.lock() = Some(Box::new(callback));
+    }
+
+    /// Gets the passkey from the user callback. Without one the pairing fails.
+    pub(crate) fn passkey(&self) -> u32 {
+        match self.passkey.lock().as_mut() {
+            Some(callback) => callback(),
+            None => {
+                log::warn!("Passkey requested but no passkey callback was set");
+                0
+            }
+        }
+    }
+
+    /// Asks the user callback whether the pin matches. Without one the pin is rejected.
+    pub(crate) fn confirm_pin(&self, pin: u32) -> bool {
+        match self.confirm_pin.lock().as_mut() {
+            Some(callback) => callback(pin),
+            None => {
+                log::warn!(
+                    "Pin {:06} rejected since no confirmation callback was set",
+                    pin
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Decides which clients can stay connected to a server. By default every client is accepted.
+/// Once bonded clients or addresses are allowed, any other client is disconnected as soon as it
+/// connects.
+#[derive(Debug, Clone, Default)]
+pub struct ClientFilter {
+    allow_bonded: bool,
+    allowed_addresses: Vec<BLEAddress>,
+}
+
+impl ClientFilter {
+    /// Creates a new ClientFilter that accepts every client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the clients bonded with this device are accepted
+    ///
+    /// # Returns
+    ///
+    /// The ClientFilter itself
+    pub fn allow_bonded(mut self, value: bool) -> Self {
+        self.allow_bonded = value;
+        self
+    }
+
+    /// Accepts the client with the given address
+    ///
+    /// # Returns
+    ///
+    /// The ClientFilter itself
+    pub fn allow_address(mut self, address: BLEAddress) -> Self {
+        self.allowed_addresses.push(address);
+        self
+    }
+
+    /// Returns whether the filter needs the bonded addresses to decide
+    pub(crate) fn needs_bonds(&self) -> bool {
+        self.allow_bonded
+    }
+
+    /// Returns whether a client can stay connected
+    ///
+    /// # Arguments
+    ///
+    /// - `address`: The address of the client
+    /// - `bonded`: The addresses of the devices bonded with this device
+    pub(crate) fn accepts(&self, address: &BLEAddress, bonded: &[BLEAddress]) -> bool {
+        if !self.allow_bonded && self.allowed_addresses.is_empty() {
+            return true;
+        }
+        self.allowed_addresses.contains(address) || (self.allow_bonded && bonded.contains(address))
+    }
+}
+
+/// Gets the addresses of every device bonded with this one
+///
+/// # Errors
+///
+/// - `BleError::Code`: If the bonds could not be read from storage
+pub(crate) fn bonded_addresses() -> Result<Vec<BLEAddress>, BleError> {
+    Ok(BLEDevice::take().bonded_addresses()?)
+}
+
+/// Deletes the bond with a device, so it must pair again on its next connection
+///
+/// # Errors
+///
+/// - `BleError::Code`: If there is no bond with the device or it could not be deleted
+pub(crate) fn delete_bond(address: &BLEAddress) -> Result<(), BleError> {
+    Ok(BLEDevice::take().delete_bond(address)?)
+}
+
+/// Deletes every bond of this device
+///
+/// # Errors
+///
+/// - `BleError::Code`: If the bonds could not be deleted
+pub(crate) fn clear_bonds() -> Result<(), BleError> {
+    Ok(BLEDevice::take().delete_all_bonds()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use esp32_nimble::BLEAddressType;
+
+    fn address(last: u8) -> BLEAddress {
+        BLEAddress::new([last, 0, 0, 0, 0, 0], BLEAddressType::Public)
+    }
+
+    #[test]
+    fn security_01_client_filter_accepts_everyone_by_default() {
+        assert!(ClientFilter::new().accepts(&address(1), &[]));
+    }
+
+    #[test]
+    fn security_02_client_filter_bonded_and_allowed() {
+        let filter = ClientFilter::new()
+            .allow_bonded(true)
+            .allow_address(address(2));
+        let bonded = [address(1)];
+        assert!(filter.accepts(&address(1), &bonded));
+        assert!(filter.accepts(&address(2), &bonded));
+        assert!(!filter.accepts(&address(3), &bonded));
+        let only_allowed = ClientFilter::new().allow_address(address(2));
+        assert!(!only_allowed.accepts(&address(1), &bonded));
+    }
+}
@@ -1,6 +1,8 @@
+use std::{cell::RefCell, rc::Rc};
+
 use esp32_nimble::{DescriptorProperties, NimbleProperties};
 
-use super::{BleError, BleId};
+use super::{AttErrorCode, BleError, BleId, CharacteristicHandlers, ConnectionInformation};
 
 const MAX_ADV_PAYLOAD_SIZE: usize = 31;
 const PAYLOAD_FIELD_IDENTIFIER_SIZE: usize = 2;
@@ -37,14 +39,16 @@ impl Service {
     ///
     /// # Errors
     ///
-    /// - `BleError::ServiceTooBig`: If the len of data and the len of the id exceed the maximum size
+    /// - `BleError::ServiceTooBig`: If the len of data and the len of the id in its shortest form
+    ///   exceed the maximum size
     pub fn new(id: &BleId, data: Vec<u8>) -> Result<Service, BleError> {
         let header_bytes = if data.is_empty() {
             PAYLOAD_FIELD_IDENTIFIER_SIZE
         } else {
             PAYLOAD_FIELD_IDENTIFIER_SIZE * 2
         };
-        if data.len() + header_bytes + id.byte_size() > MAX_ADV_PAYLOAD_SIZE {
+        // The id is advertised in its shortest form
+        if data.len() + header_bytes + id.shortest().byte_size() > MAX_ADV_PAYLOAD_SIZE {
             Err(BleError::ServiceTooBig)
         } else {
             Ok(Service {
@@ -90,12 +94,15 @@ impl Service {
 /// - `id`: The id lets clients identified each service characteristic.
 /// - `properties`: Properties especify how the clients will be able to interact with the characteristic.
 /// - `data`: The value that the clients will be able to see or write (depending on the properties).
+/// - `descriptors`: The descriptors of the characteristic.
+/// - `handlers`: Callbacks executed when a client reads or writes the characteristic.
 #[derive(Clone, Debug)]
 pub struct Characteristic {
     pub id: BleId,
     pub(crate) properties: u16,
     pub data: Vec<u8>,
     pub descriptors: Vec<Descriptor>,
+    pub(crate) handlers: CharacteristicHandlers,
 }
 
 impl Characteristic {
@@ -116,6 +123,7 @@ impl Characteristic {
             properties: 0,
             data,
             descriptors: vec![],
+            handlers: CharacteristicHandlers::default(),
         }
     }
 
@@ -349,6 +357,59 @@ impl Characteristic {
         self
     }
 
+    /// Sets a callback to be executed when a client writes the characteristic. The callback
+    /// receives the written value and the information of the client, and can reject the write
+    /// returning an [AttErrorCode]. If it returns `Ok` the value of the characteristic is updated.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used. If the callback is not executed within a second, the write is rejected with
+    /// [AttErrorCode::UnlikelyError] and the callback is never executed for it. The BLE stack is
+    /// blocked while it waits, delaying the events of every connection, so the main loop should
+    /// not spend long periods without updating.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that will be executed on every write
+    ///
+    /// # Returns
+    ///
+    /// The Characteristic itself
+    pub fn on_write<
+        C: FnMut(&[u8], &ConnectionInformation) -> Result<(), AttErrorCode> + 'static,
+    >(
+        mut self,
+        callback: C,
+    ) -> Self {
+        self.handlers.on_write = Some(Rc::new(RefCell::new(Box::new(callback))));
+        self
+    }
+
+    /// Sets a callback to be executed when a client reads the characteristic. The value
+    /// returned by the callback is the one the client receives, and becomes the value of
+    /// the characteristic.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used. If the callback is not executed within a second, the client receives the
+    /// current value of the characteristic and the callback is never executed for it. The BLE
+    /// stack is blocked while it waits, delaying the events of every connection.
+    ///
+    /// # Arguments
+    ///
+    /// - `callback`: A closure that will be executed on every read
+    ///
+    /// # Returns
+    ///
+    /// The Characteristic itself
+    pub fn on_read<C: FnMut(&ConnectionInformation) -> Vec<u8> + 'static>(
+        mut self,
+        callback: C,
+    ) -> Self {
+        self.handlers.on_read = Some(Rc::new(RefCell::new(Box::new(callback))));
+        self
+    }
+
     /// Verifies if the characteristic satisfies any of the properties set in the received flag
     fn satisfies_at_least_one_property(&self, flag: NimbleProperties) -> bool {
         (self.properties & flag.bits()) != 0
@@ -582,3 +643,24 @@ impl Descriptor {
         self.toggle(value, DescriptorProperties::WRITE_AUTHOR)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn service_01_size_uses_shortest_id() {
+        let expanded: BleId = "0000180f-0000-1000-8000-00805f9b34fb".parse().unwrap();
+        assert!(Service::new(&expanded, vec![0; 25]).is_ok());
+        assert!(matches!(
+            Service::new(&expanded, vec![0; 26]),
+            Err(BleError::ServiceTooBig)
+        ));
+        let custom = BleId::FromUuid128([0xAB; 16]);
+        assert!(Service::new(&custom, vec![0; 11]).is_ok());
+        assert!(matches!(
+            Service::new(&custom, vec![0; 12]),
+            Err(BleError::ServiceTooBig)
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, ptr, sync::mpsc::Sender, sync::Arc};
+
+use esp32_nimble::{
+    utilities::mutex::Mutex, BLECharacteristic, BLEError, NimbleSub, NotifyTxStatus,
+};
+use esp_idf_svc::sys;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+
+use super::{BleError, BleId, ConnectionInformation};
+use crate::utils::notification::Notifier;
+
+/// Where the BLE stack sends whether each indication of a characteristic was confirmed, while
+/// an indication is in progress
+pub(crate) type IndicationSlot = Arc<Mutex<Option<UnboundedSender<bool>>>>;
+
+/// Indication being sent to the clients subscribed to a characteristic, which waits for the
+/// result of each of them. Once dropped, the results of later indications are no longer
+/// sent to it.
+pub(crate) struct PendingIndication {
+    slot: IndicationSlot,
+    receiver: UnboundedReceiver<bool>,
+    amount: usize,
+}
+
+impl PendingIndication {
+    /// Creates a new PendingIndication, replacing the one of the characteristic if there was any
+    pub(crate) fn new(slot: &IndicationSlot) -> Self {
+        let (sender, receiver) = unbounded();
+        *slot.lock() = Some(sender);
+        Self {
+            slot: slot.clone(),
+            receiver,
+            amount: 0,
+        }
+    }
+
+    /// Counts an indication sent to a client
+    pub(crate) fn sent(&mut self) {
+        self.amount += 1;
+    }
+
+    /// Waits for the result of every indication sent. The BLE stack reports an indication as
+    /// failed if it is not confirmed before the ATT timeout of 30 seconds.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every client confirmed the indication, `false` otherwise
+    pub(crate) async fn confirmed(mut self) -> bool {
+        let mut confirmed = true;
+        for _ in 0..self.amount {
+            confirmed &= self.receiver.next().await.unwrap_or(false);
+        }
+        confirmed
+    }
+}
+
+impl Drop for PendingIndication {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock();
+        if slot
+            .as_ref()
+            .is_some_and(|sender| sender.is_connected_to(&self.receiver))
+        {
+            *slot = None;
+        }
+    }
+}
+
+/// Change of the subscription of a client to a characteristic, made by writing its CCCD.
+///
+/// - `client`: The ConnectionInformation of the client
+/// - `service_id`: The BleId of the service the characteristic is part of
+/// - `characteristic_id`: The BleId of the characteristic
+/// - `notify`: Whether the client wants to receive notifications
+/// - `indicate`: Whether the client wants to receive indications
+#[derive(Debug, Clone)]
+pub struct SubscriptionEvent {
+    pub client: ConnectionInformation,
+    pub service_id: BleId,
+    pub characteristic_id: BleId,
+    pub notify: bool,
+    pub indicate: bool,
+}
+
+impl SubscriptionEvent {
+    /// Returns whether the client is subscribed after the change, or it unsubscribed
+    pub fn is_subscribed(&self) -> bool {
+        self.notify || self.indicate
+    }
+}
+
+/// Subscription of a client to a characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Subscription {
+    conn_handle: u16,
+    notify: bool,
+    indicate: bool,
+}
+
+/// Clients subscribed to each characteristic of a server, identified by the connection handle
+#[derive(Debug, Default)]
+pub(crate) struct SubscriptionTable {
+    subscriptions: HashMap<(BleId, BleId), Vec<Subscription>>,
+}
+
+impl SubscriptionTable {
+    /// Updates the subscription of a client. A client that neither wants notifications nor
+    /// indications is removed.
+    pub(crate) fn update(
+        &mut self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+        conn_handle: u16,
+        notify: bool,
+        indicate: bool,
+    ) {
+        let key = (service_id.clone(), characteristic_id.clone());
+        let subscriptions = self.subscriptions.entry(key.clone()).or_default();
+        subscriptions.retain(|subscription| subscription.conn_handle != conn_handle);
+        if notify || indicate {
+            subscriptions.push(Subscription {
+                conn_handle,
+                notify,
+                indicate,
+            });
+        } else if subscriptions.is_empty() {
+            self.subscriptions.remove(&key);
+        }
+    }
+
+    /// Gets the connection handles of the clients subscribed to a characteristic, either to
+    /// notifications or indications
+    pub(crate) fn subscribed(&self, service_id: &BleId, characteristic_id: &BleId) -> Vec<u16> {
+        self.filter(service_id, characteristic_id, |_| true)
+    }
+
+    /// Gets the connection handles of the clients subscribed to the notifications of a
+    /// characteristic
+    pub(crate) fn notified(&self, service_id: &BleId, characteristic_id: &BleId) -> Vec<u16> {
+        self.filter(service_id, characteristic_id, |subscription| {
+            subscription.notify
+        })
+    }
+
+    /// Gets the connection handles of the clients subscribed to the indications of a
+    /// characteristic
+    pub(crate) fn indicated(&self, service_id: &BleId, characteristic_id: &BleId) -> Vec<u16> {
+        self.filter(service_id, characteristic_id, |subscription| {
+            subscription.indicate
+        })
+    }
+
+    /// Gets the connection handles of the subscriptions to a characteristic that match a predicate
+    fn filter<P: Fn(&Subscription) -> bool>(
+        &self,
+        service_id: &BleId,
+        characteristic_id: &BleId,
+        predicate: P,
+    ) -> Vec<u16> {
+        self.subscriptions
+            .get(&(service_id.clone(), characteristic_id.clone()))
+            .map(|subscriptions| {
+                subscriptions
+                    .iter()
+                    .filter(|subscription| predicate(subscription))
+                    .map(|subscription| subscription.conn_handle)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Makes the BLE stack keep the subscriptions of a characteristic up to date, reporting each
+/// change to the main loop, and forward the confirmations of its indications.
+///
+/// # Arguments
+///
+/// - `characteristic`: The characteristic of the server
+/// - `service_id`: The BleId of the service the characteristic is part of
+/// - `characteristic_id`: The BleId of the characteristic
+/// - `subscriptions`: The table of subscriptions of the server
+/// - `events`: Where to send the subscription changes
+/// - `notifier`: A notifier to wake up the [crate::Microcontroller]
+/// - `indications`: Where to send the result of each indication
+pub(crate) fn track_characteristic_subscriptions(
+    characteristic: &Mutex<BLECharacteristic>,
+    service_id: &BleId,
+    characteristic_id: &BleId,
+    subscriptions: &Arc<Mutex<SubscriptionTable>>,
+    events: &Sender<SubscriptionEvent>,
+    notifier: &Notifier,
+    indications: &IndicationSlot,
+) {
+    let mut characteristic = characteristic.lock();
+
+    let service_id = service_id.clone();
+    let characteristic_id = characteristic_id.clone();
+    let subscriptions = subscriptions.clone();
+    let events = events.clone();
+    let notifier = notifier.clone();
+    characteristic.on_subscribe(move |_, desc, sub| {
+        let notify = sub.contains(NimbleSub::NOTIFY);
+        let indicate = sub.contains(NimbleSub::INDICATE);
+        subscriptions.lock().update(
+            &service_id,
+            &characteristic_id,
+            desc.conn_handle(),
+            notify,
+            indicate,
+        );
+        let event = SubscriptionEvent {
+            client: ConnectionInformation::from_bleconn_desc(desc, true, Ok(())),
+            service_id: service_id.clone(),
+            characteristic_id: characteristic_id.clone(),
+            notify,
+            indicate,
+        };
+        if events.send(event).is_ok() {
+            notifier.notify();
+        }
+    });
+
+    let indications = indications.clone();
+    characteristic.on_notify_tx(move |notify_tx| {
+        let confirmed = match notify_tx.status() {
+            NotifyTxStatus::SuccessIndicate => true,
+            NotifyTxStatus::SuccessNotify | NotifyTxStatus::ErrorNotifyDisabled => return,
+            _ => false,
+        };
+        if let Some(sender) = indications.lock().as_ref() {
+            _ = sender.unbounded_send(confirmed);
+        }
+    });
+}
+
+/// Finds the handle of the value of a characteristic in the GATT database of the server
+///
+/// # Errors
+///
+/// - `BleError::CharacteristicNotFound`: If the characteristic is not registered, for example
+///   before the server is started
+pub(crate) fn characteristic_value_handle(
+    service_id: &BleId,
+    characteristic_id: &BleId,
+) -> Result<u16, BleError> {
+    let service_uuid: sys::ble_uuid_any_t = service_id.to_uuid().into();
+    let characteristic_uuid: sys::ble_uuid_any_t = characteristic_id.to_uuid().into();
+    let mut value_handle = 0;
+    let rc = unsafe {
+        sys::ble_gatts_find_chr(
+            &service_uuid.u,
+            &characteristic_uuid.u,
+            ptr::null_mut(),
+            &mut value_handle,
+        )
+    };
+    if rc != 0 {
+        return Err(BleError::CharacteristicNotFound);
+    }
+    Ok(value_handle)
+}
+
+/// Sends a notification or an indication with the given value to a single client
+///
+/// # Arguments
+///
+/// - `conn_handle`: The connection handle of the client
+/// - `value_handle`: The handle of the value of the characteristic
+/// - `data`: The value to send
+/// - `indicate`: Whether to send an indication instead of a notification
+///
+/// # Errors
+///
+/// - `BleError::Code`: If the BLE stack could not send it
+pub(crate) fn send_value_update(
+    conn_handle: u16,
+    value_handle: u16,
+    data: &[u8],
+    indicate: bool,
+) -> Result<(), BleError> {
+    // The BLE stack takes ownership of the buffer, even when sending fails
+    let om = unsafe { sys::ble_hs_mbuf_from_flat(data.as_ptr() as _, data.len() as _) };
+    let rc = unsafe {
+        if indicate {
+            sys::ble_gattc_indicate_custom(conn_handle, value_handle, om)
+        } else {
+            sys::ble_gattc_notify_custom(conn_handle, value_handle, om)
+        }
+    };
+    Ok(BLEError::convert(rc as u32)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subscriptions_01_update_and_query() {
+        let service = BleId::FromUuid16(0x180D);
+        let characteristic = BleId::FromUuid16(0x2A37);
+        let mut table = SubscriptionTable::default();
+        table.update(&service, &characteristic, 1, true, false);
+        table.update(&service, &characteristic, 2, false, true);
+        table.update(&service, &characteristic, 3, true, true);
+        assert_eq!(table.subscribed(&service, &characteristic), vec![1, 2, 3]);
+        assert_eq!(table.notified(&service, &characteristic), vec![1, 3]);
+        assert_eq!(table.indicated(&service, &characteristic), vec![2, 3]);
+        assert!(table
+            .subscribed(&service, &BleId::FromUuid16(0x2A38))
+            .is_empty());
+    }
+
+    #[test]
+    fn subscriptions_02_unsubscribe_removes_client() {
+        let service = BleId::FromUuid16(0x180D);
+        let characteristic = BleId::FromUuid16(0x2A37);
+        let mut table = SubscriptionTable::default();
+        table.update(&service, &characteristic, 1, true, false);
+        table.update(&service, &characteristic, 1, false, true);
+        assert!(table.notified(&service, &characteristic).is_empty());
+        assert_eq!(table.indicated(&service, &characteristic), vec![1]);
+        table.update(&service, &characteristic, 1, false, false);
+        assert!(table.subscribed(&service, &characteristic).is_empty());
+        assert!(table.subscriptions.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Bytes of the ATT header of a notification or write, the rest of the MTU is payload
+const ATT_HEADER_SIZE: u16 = 3;
+/// Minimum MTU every BLE connection supports
+pub(crate) const DEFAULT_MTU: u16 = 23;
+
+/// Buffer of the bytes received over a serial link. When it is full, the oldest bytes are
+/// discarded to make room for the new ones.
+#[derive(Debug)]
+pub(crate) struct UartBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl UartBuffer {
+    /// Creates a new empty UartBuffer
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Appends bytes to the buffer
+    ///
+    /// # Returns
+    ///
+    /// The amount of old bytes discarded to fit the new ones
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> usize {
+        let skipped = bytes.len().saturating_sub(self.capacity);
+        let bytes = &bytes[skipped..];
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+        skipped + overflow
+    }
+
+    /// Gets the amount of bytes available to read
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Moves as many bytes as fit from the buffer into `buffer`
+    ///
+    /// # Returns
+    ///
+    /// The amount of bytes read
+    pub(crate) fn read(&mut self, buffer: &mut [u8]) -> usize {
+        let amount = buffer.len().min(self.data.len());
+        for (dst, src) in buffer.iter_mut().zip(self.data.drain(..amount)) {
+            *dst = src;
+        }
+        amount
+    }
+
+    /// Takes the bytes up to the first delimiter, removing the delimiter from the buffer
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the bytes before the delimiter, or `None` if there is no delimiter yet
+    pub(crate) fn read_until(&mut self, delimiter: u8) -> Option<Vec<u8>> {
+        let position = self.data.iter().position(|byte| *byte == delimiter)?;
+        let bytes = self.data.drain(..position).collect();
+        self.data.pop_front();
+        Some(bytes)
+    }
+}
+
+/// Splits data in chunks that fit in a single notification or write
+///
+/// # Arguments
+///
+/// - `data`: The data to send
+/// - `mtu`: The MTU of the connection
+pub(crate) fn mtu_chunks(data: &[u8], mtu: u16) -> std::slice::Chunks<'_, u8> {
+    let payload = mtu.max(DEFAULT_MTU) - ATT_HEADER_SIZE;
+    data.chunks(payload as usize)
+}
+
+/// Removes the carriage return of a line ended in "\r\n" and decodes it
+pub(crate) fn line_from_bytes(mut bytes: Vec<u8>) -> String {
+    if bytes.last() == Some(&b'\r') {
+        bytes.pop();
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// An [UartBuffer] shared between the BLE stack, which pushes the received bytes, and the
+/// main loop, which can wait for them.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedUartBuffer {
+    inner: Arc<(Mutex<UartBuffer>, Condvar)>,
+}
+
+impl SharedUartBuffer {
+    /// Creates a new empty SharedUartBuffer
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new((Mutex::new(UartBuffer::new(capacity)), Condvar::new())),
+        }
+    }
+
+    /// Appends bytes to the buffer, waking up anyone waiting for them
+    pub(crate) fn push(&self, bytes: &[u8]) {
+        let (buffer, received) = &*self.inner;
+        let discarded = buffer.lock().unwrap().push(bytes);
+        if discarded > 0 {
+            log::warn!("Uart buffer full, discarded {} bytes", discarded);
+        }
+        received.notify_all();
+    }
+
+    /// Gets the amount of bytes available to read
+    pub(crate) fn len(&self) -> usize {
+        self.inner.0.lock().unwrap().len()
+    }
+
+    /// Moves as many bytes as available and fit from the buffer into `buffer`, without waiting
+    pub(crate) fn read(&self, buffer: &mut [u8]) -> usize {
+        self.inner.0.lock().unwrap().read(buffer)
+    }
+
+    /// Waits for a delimiter to be received, taking the bytes up to it
+    ///
+    /// # Arguments
+    ///
+    /// - `delimiter`: The byte that ends the message
+    /// - `timeout`: The maximum time to wait, or `None` to wait indefinitely
+    ///
+    /// # Returns
+    ///
+    /// An `Option` with the bytes before the delimiter, or `None` if the timeout was reached
+    pub(crate) fn read_until(&self, delimiter: u8, timeout: Option<Duration>) -> Option<Vec<u8>> {
+        let (buffer, received) = &*self.inner;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut buffer = buffer.lock().unwrap();
+        loop {
+            if let Some(bytes) = buffer.read_until(delimiter) {
+                return Some(bytes);
+            }
+            buffer = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.checked_duration_since(Instant::now())?;
+                    received.wait_timeout(buffer, remaining).unwrap().0
+                }
+                None => received.wait(buffer).unwrap(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn uart_buffer_01_read_until_delimiter() {
+        let mut buffer = UartBuffer::new(64);
+        buffer.push(b"led=on\nled=");
+        assert_eq!(buffer.read_until(b'\n'), Some(b"led=on".to_vec()));
+        assert_eq!(buffer.read_until(b'\n'), None);
+        buffer.push(b"off\n");
+        assert_eq!(buffer.read_until(b'\n'), Some(b"led=off".to_vec()));
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn uart_buffer_02_discards_oldest_when_full() {
+        let mut buffer = UartBuffer::new(4);
+        assert_eq!(buffer.push(b"abc"), 0);
+        assert_eq!(buffer.push(b"def"), 2);
+        let mut out = [0; 8];
+        assert_eq!(buffer.read(&mut out), 4);
+        assert_eq!(&out[..4], b"cdef");
+        assert_eq!(buffer.push(b"0123456789"), 6);
+        assert_eq!(buffer.read(&mut out), 4);
+        assert_eq!(&out[..4], b"6789");
+    }
+
+    #[test]
+    fn uart_buffer_03_mtu_chunks() {
+        let data = [0_u8; 50];
+        let sizes: Vec<usize> = mtu_chunks(&data, 23).map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![20, 20, 10]);
+        let sizes: Vec<usize> = mtu_chunks(&data, 247).map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![50]);
+        assert_eq!(mtu_chunks(&data, 0).next().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn uart_buffer_04_line_from_bytes() {
+        assert_eq!(line_from_bytes(b"hello\r".to_vec()), "hello");
+        assert_eq!(line_from_bytes(b"hello".to_vec()), "hello");
+    }
+
+    #[test]
+    fn uart_buffer_05_shared_read_waits_for_delimiter() {
+        let buffer = SharedUartBuffer::new(64);
+        let writer = buffer.clone();
+        let handle = thread::spawn(move || {
+            writer.push(b"partial ");
+            thread::sleep(Duration::from_millis(20));
+            writer.push(b"line\n");
+        });
+        assert_eq!(
+            buffer.read_until(b'\n', Some(Duration::from_secs(5))),
+            Some(b"partial line".to_vec())
+        );
+        handle.join().unwrap();
+        assert_eq!(
+            buffer.read_until(b'\n', Some(Duration::from_millis(10))),
+            None
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use super::MeshError;
+
+/// Opcode of an access message. SIG opcodes are 1 or 2 bytes long, vendor opcodes are 3 bytes
+/// long with the company identifier of the vendor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    OneOctet(u8),
+    TwoOctets(u16),
+    Vendor { opcode: u8, company_id: u16 },
+}
+
+impl Opcode {
+    /// Creates the opcode of a message defined by the Bluetooth SIG. Values up to 0x7E are one
+    /// byte long, the rest two bytes long.
+    pub fn sig(opcode: u16) -> Self {
+        match opcode < 0x7F {
+            true => Opcode::OneOctet(opcode as u8),
+            false => Opcode::TwoOctets(opcode),
+        }
+    }
+
+    /// Creates the opcode of a vendor message
+    ///
+    /// # Arguments
+    ///
+    /// - `opcode`: The 6 bits opcode, defined by the vendor
+    /// - `company_id`: The company identifier of the vendor
+    pub fn vendor(opcode: u8, company_id: u16) -> Self {
+        Opcode::Vendor {
+            opcode: opcode & 0x3F,
+            company_id,
+        }
+    }
+
+    /// Gets the bytes of the opcode
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            Opcode::OneOctet(opcode) => vec![*opcode],
+            Opcode::TwoOctets(opcode) => opcode.to_be_bytes().to_vec(),
+            Opcode::Vendor { opcode, company_id } => {
+                let company_id = company_id.to_le_bytes();
+                vec![0xC0 | opcode, company_id[0], company_id[1]]
+            }
+        }
+    }
+
+    /// Parses the opcode at the start of an access payload
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the opcode and the rest of the payload, or a `MeshError` if the payload
+    /// does not start with a valid opcode
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidOpcode`: If the opcode is reserved or the payload is too short
+    pub(crate) fn decode(data: &[u8]) -> Result<(Self, &[u8]), MeshError> {
+        let Some(&first) = data.first() else {
+            return Err(MeshError::InvalidOpcode);
+        };
+        match first >> 6 {
+            _ if first == 0x7F => Err(MeshError::InvalidOpcode),
+            0b00 | 0b01 => Ok((Opcode::OneOctet(first), &data[1..])),
+            0b10 if data.len() >= 2 => Ok((
+                Opcode::TwoOctets(u16::from_be_bytes([first, data[1]])),
+                &data[2..],
+            )),
+            0b11 if data.len() >= 3 => Ok((
+                Opcode::Vendor {
+                    opcode: first & 0x3F,
+                    company_id: u16::from_le_bytes([data[1], data[2]]),
+                },
+                &data[3..],
+            )),
+            _ => Err(MeshError::InvalidOpcode),
+        }
+    }
+}
+
+/// A message of the access layer: an opcode and its parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessMessage {
+    pub opcode: Opcode,
+    pub params: Vec<u8>,
+}
+
+impl AccessMessage {
+    /// Creates a new AccessMessage
+    pub fn new(opcode: Opcode, params: &[u8]) -> Self {
+        Self {
+            opcode,
+            params: params.to_vec(),
+        }
+    }
+
+    /// Gets the access payload of the message
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut payload = self.opcode.encode();
+        payload.extend_from_slice(&self.params);
+        payload
+    }
+
+    /// Parses an access payload
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidOpcode`: If the payload does not start with a valid opcode
+    pub(crate) fn decode(payload: &[u8]) -> Result<Self, MeshError> {
+        let (opcode, params) = Opcode::decode(payload)?;
+        Ok(Self::new(opcode, params))
+    }
+}
+
+/// Information of how a message was received
+/// - `src`: The unicast address of the element that sent the message
+/// - `dst`: The address the message was sent to, the one of an element or a group
+/// - `app_key_index`: The index of the application key that encrypted the message, or `None` if
+///   it was encrypted with the device key
+/// - `ttl`: The TTL the message was received with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageContext {
+    pub src: u16,
+    pub dst: u16,
+    pub app_key_index: Option<u16>,
+    pub ttl: u8,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn access_01_encodes_and_decodes_every_opcode_size() {
+        let messages = [
+            AccessMessage::new(Opcode::sig(0x00), &[0x56, 0x34]),
+            AccessMessage::new(Opcode::sig(0x8201), &[]),
+            AccessMessage::new(Opcode::vendor(0x01, 0x02E5), &[0xFF]),
+        ];
+        let payloads: Vec<Vec<u8>> = messages.iter().map(AccessMessage::encode).collect();
+        assert_eq!(
+            payloads,
+            [
+                vec![0x00, 0x56, 0x34],
+                vec![0x82, 0x01],
+                vec![0xC1, 0xE5, 0x02, 0xFF]
+            ]
+        );
+        for (message, payload) in messages.iter().zip(payloads) {
+            assert_eq!(&AccessMessage::decode(&payload).unwrap(), message);
+        }
+        assert!(matches!(
+            AccessMessage::decode(&[0x7F]),
+            Err(MeshError::InvalidOpcode)
+        ));
+        assert!(matches!(
+            AccessMessage::decode(&[0xC1, 0xE5]),
+            Err(MeshError::InvalidOpcode)
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+use super::{
+    access::{AccessMessage, Opcode},
+    node_state::{
+        is_group, AppKey, ModelId, NodeState, Publication, ALL_NODES_ADDRESS, MAX_TTL,
+        UNASSIGNED_ADDRESS, USE_DEFAULT_TTL,
+    },
+};
+
+const APP_KEY_ADD: u16 = 0x00;
+const APP_KEY_STATUS: u16 = 0x8003;
+const COMPOSITION_DATA_GET: u16 = 0x8008;
+const COMPOSITION_DATA_STATUS: u16 = 0x02;
+const BEACON_GET: u16 = 0x8009;
+const BEACON_SET: u16 = 0x800A;
+const BEACON_STATUS: u16 = 0x800B;
+const DEFAULT_TTL_GET: u16 = 0x800C;
+const DEFAULT_TTL_SET: u16 = 0x800D;
+const DEFAULT_TTL_STATUS: u16 = 0x800E;
+const RELAY_GET: u16 = 0x8026;
+const RELAY_SET: u16 = 0x8027;
+const RELAY_STATUS: u16 = 0x8028;
+const MODEL_APP_BIND: u16 = 0x803D;
+const MODEL_APP_STATUS: u16 = 0x803E;
+const MODEL_APP_UNBIND: u16 = 0x803F;
+const MODEL_PUBLICATION_SET: u16 = 0x03;
+const MODEL_PUBLICATION_GET: u16 = 0x8018;
+const MODEL_PUBLICATION_STATUS: u16 = 0x8019;
+const MODEL_SUBSCRIPTION_ADD: u16 = 0x801B;
+const MODEL_SUBSCRIPTION_DELETE: u16 = 0x801C;
+const MODEL_SUBSCRIPTION_STATUS: u16 = 0x801F;
+const NODE_RESET: u16 = 0x8049;
+const NODE_RESET_STATUS: u16 = 0x804A;
+const MAX_APP_KEYS: usize = 16;
+const MAX_SUBSCRIPTIONS: usize = 16;
+
+/// Status codes of the answers of the configuration server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigStatus {
+    Success = 0x00,
+    InvalidAddress = 0x01,
+    InvalidModel = 0x02,
+    InvalidAppKeyIndex = 0x03,
+    InvalidNetKeyIndex = 0x04,
+    InsufficientResources = 0x05,
+    KeyIndexAlreadyStored = 0x06,
+    CannotBind = 0x0D,
+}
+
+/// Change of the node made by a configuration message, that the node must act upon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConfigChange {
+    None,
+    /// The publication of a model changed
+    Publication {
+        element: usize,
+        model: ModelId,
+    },
+    /// The node must leave the network once the answer is sent
+    Reset,
+}
+
+/// Handles a message sent to the configuration server, encrypted with the device key
+///
+/// # Arguments
+///
+/// - `state`: The state of the node, modified by the message
+/// - `message`: The message received
+///
+/// # Returns
+///
+/// The answer and the change made, or `None` if the message is unknown or not valid
+pub(crate) fn handle_config_message(
+    state: &mut NodeState,
+    message: &AccessMessage,
+) -> Option<(AccessMessage, ConfigChange)> {
+    let params = message.params.as_slice();
+    let opcode = match message.opcode {
+        Opcode::OneOctet(opcode) => opcode as u16,
+        Opcode::TwoOctets(opcode) => opcode,
+        Opcode::Vendor { .. } => return None,
+    };
+    let (status_opcode, status_params, change) = match opcode {
+        APP_KEY_ADD => (
+            APP_KEY_STATUS,
+            app_key_add(state, params)?,
+            ConfigChange::None,
+        ),
+        COMPOSITION_DATA_GET if params.len() == 1 => {
+            // Only page 0 exists
+            let data = [&[0x00], state.composition_data().as_slice()].concat();
+            (COMPOSITION_DATA_STATUS, data, ConfigChange::None)
+        }
+        BEACON_GET if params.is_empty() => {
+            (BEACON_STATUS, vec![state.beacon as u8], ConfigChange::None)
+        }
+        BEACON_SET if params.len() == 1 && params[0] <= 1 => {
+            state.beacon = params[0] == 1;
+            (BEACON_STATUS, params.to_vec(), ConfigChange::None)
+        }
+        DEFAULT_TTL_GET if params.is_empty() => (
+            DEFAULT_TTL_STATUS,
+            vec![state.default_ttl],
+            ConfigChange::None,
+        ),
+        DEFAULT_TTL_SET if params.len() == 1 && params[0] != 1 && params[0] <= MAX_TTL => {
+            state.default_ttl = params[0];
+            (DEFAULT_TTL_STATUS, params.to_vec(), ConfigChange::None)
+        }
+        RELAY_GET if params.is_empty() => (
+            RELAY_STATUS,
+            vec![state.relay as u8, state.relay_retransmit],
+            ConfigChange::None,
+        ),
+        RELAY_SET if params.len() == 2 && params[0] <= 1 => {
+            state.relay = params[0] == 1;
+            state.relay_retransmit = params[1];
+            (RELAY_STATUS, params.to_vec(), ConfigChange::None)
+        }
+        MODEL_APP_BIND | MODEL_APP_UNBIND => (
+            MODEL_APP_STATUS,
+            model_app(state, params, opcode == MODEL_APP_BIND)?,
+            ConfigChange::None,
+        ),
+        MODEL_PUBLICATION_SET => {
+            let (status, change) = model_publication_set(state, params)?;
+            (MODEL_PUBLICATION_STATUS, status, change)
+        }
+        MODEL_PUBLICATION_GET => (
+            MODEL_PUBLICATION_STATUS,
+            model_publication_get(state, params)?,
+            ConfigChange::None,
+        ),
+        MODEL_SUBSCRIPTION_ADD | MODEL_SUBSCRIPTION_DELETE => (
+            MODEL_SUBSCRIPTION_STATUS,
+            model_subscription(state, params, opcode == MODEL_SUBSCRIPTION_ADD)?,
+            ConfigChange::None,
+        ),
+        NODE_RESET if params.is_empty() => (NODE_RESET_STATUS, Vec::new(), ConfigChange::Reset),
+        _ => return None,
+    };
+    Some((
+        AccessMessage::new(Opcode::sig(status_opcode), &status_params),
+        change,
+    ))
+}
+
+/// Reads two 12 bits key indexes packed in 3 bytes
+fn decode_key_indexes(data: &[u8]) -> (u16, u16) {
+    let first = u16::from_le_bytes([data[0], data[1] & 0x0F]);
+    let second = ((data[1] >> 4) as u16) | ((data[2] as u16) << 4);
+    (first, second)
+}
+
+/// Reads a little endian u16 of the parameters
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Adds an application key
+///
+/// # Returns
+///
+/// The parameters of the AppKey Status, or `None` if the message is not valid
+fn app_key_add(state: &mut NodeState, params: &[u8]) -> Option<Vec<u8>> {
+    if params.len() != 19 {
+        return None;
+    }
+    let (net_key_index, app_key_index) = decode_key_indexes(params);
+    let key: [u8; 16] = params[3..].try_into().ok()?;
+    let status = if !state
+        .net_keys
+        .iter()
+        .any(|net_key| net_key.index == net_key_index)
+    {
+        ConfigStatus::InvalidNetKeyIndex
+    } else {
+        match state.app_key(app_key_index) {
+            Some(existing) if existing.key == key && existing.net_key_index == net_key_index => {
+                ConfigStatus::Success
+            }
+            Some(_) => ConfigStatus::KeyIndexAlreadyStored,
+            None if state.app_keys.len() >= MAX_APP_KEYS => ConfigStatus::InsufficientResources,
+            None => {
+                state
+                    .app_keys
+                    .push(AppKey::new(app_key_index, net_key_index, key));
+                ConfigStatus::Success
+            }
+        }
+    };
+    Some([&[status as u8], &params[..3]].concat())
+}
+
+/// Finds the element and the model of a configuration message
+///
+/// # Returns
+///
+/// The element index and the model id, or the status that tells why they were not found
+fn find_model(
+    state: &NodeState,
+    element_address: u16,
+    model_id: ModelId,
+) -> Result<usize, ConfigStatus> {
+    let element = state
+        .element_index(element_address)
+        .ok_or(ConfigStatus::InvalidAddress)?;
+    state
+        .model(element, model_id)
+        .ok_or(ConfigStatus::InvalidModel)?;
+    Ok(element)
+}
+
+/// Binds or unbinds an application key to a model
+///
+/// # Returns
+///
+/// The parameters of the Model App Status, or `None` if the message is not valid
+fn model_app(state: &mut NodeState, params: &[u8], bind: bool) -> Option<Vec<u8>> {
+    if params.len() != 6 && params.len() != 8 {
+        return None;
+    }
+    let element_address = read_u16(params, 0);
+    let app_key_index = read_u16(params, 2) & 0x0FFF;
+    let model_id = ModelId::decode(&params[4..])?;
+    let status = match find_model(state, element_address, model_id) {
+        Err(status) => status,
+        Ok(_) if state.app_key(app_key_index).is_none() => ConfigStatus::InvalidAppKeyIndex,
+        Ok(_) if model_id == ModelId::CONFIG_SERVER => ConfigStatus::CannotBind,
+        Ok(element) => {
+            let model = state.model_mut(element, model_id)?;
+            if bind && !model.app_keys.contains(&app_key_index) {
+                model.app_keys.push(app_key_index);
+            } else if !bind {
+                model.app_keys.retain(|index| *index != app_key_index);
+                if model
+                    .publication
+                    .is_some_and(|publication| publication.app_key_index == app_key_index)
+                {
+                    model.publication = None;
+                }
+            }
+            ConfigStatus::Success
+        }
+    };
+    Some([&[status as u8], params].concat())
+}
+
+/// Gets the parameters of a Model Publication Status
+fn publication_status(
+    status: ConfigStatus,
+    element_address: u16,
+    publication: Option<Publication>,
+    model_id: ModelId,
+) -> Vec<u8> {
+    let publication = publication.unwrap_or(Publication {
+        ttl: 0,
+        ..Publication::new(UNASSIGNED_ADDRESS, 0)
+    });
+    let key_and_flag = publication.app_key_index | ((publication.credential_flag as u16) << 12);
+    let mut data = vec![status as u8];
+    data.extend_from_slice(&element_address.to_le_bytes());
+    data.extend_from_slice(&publication.address.to_le_bytes());
+    data.extend_from_slice(&key_and_flag.to_le_bytes());
+    data.extend_from_slice(&[publication.ttl, publication.period, publication.retransmit]);
+    data.extend(model_id.encode());
+    data
+}
+
+/// Sets or clears the publication of a model
+///
+/// # Returns
+///
+/// The parameters of the Model Publication Status and the change made, or `None` if the message
+/// is not valid
+fn model_publication_set(state: &mut NodeState, params: &[u8]) -> Option<(Vec<u8>, ConfigChange)> {
+    if params.len() != 11 && params.len() != 13 {
+        return None;
+    }
+    let element_address = read_u16(params, 0);
+    let address = read_u16(params, 2);
+    let key_and_flag = read_u16(params, 4);
+    let ttl = params[6];
+    if ttl > MAX_TTL && ttl != USE_DEFAULT_TTL {
+        return None;
+    }
+    let model_id = ModelId::decode(&params[9..])?;
+    let publication = Publication {
+        address,
+        app_key_index: key_and_flag & 0x0FFF,
+        credential_flag: key_and_flag & 0x1000 != 0,
+        ttl,
+        period: params[7],
+        retransmit: params[8],
+    };
+    let element = match find_model(state, element_address, model_id) {
+        Err(status) => status,
+        Ok(_)
+            if address != UNASSIGNED_ADDRESS
+                && state.app_key(publication.app_key_index).is_none() =>
+        {
+            ConfigStatus::InvalidAppKeyIndex
+        }
+        Ok(element) => {
+            let model = state.model_mut(element, model_id)?;
+            model.publication = (address != UNASSIGNED_ADDRESS).then_some(publication);
+            let status = publication_status(
+                ConfigStatus::Success,
+                element_address,
+                model.publication,
+                model_id,
+            );
+            return Some((
+                status,
+                ConfigChange::Publication {
+                    element,
+                    model: model_id,
+                },
+            ));
+        }
+    };
+    Some((
+        publication_status(element, element_address, Some(publication), model_id),
+        ConfigChange::None,
+    ))
+}
+
+/// Gets the publication of a model
+///
+/// # Returns
+///
+/// The parameters of the Model Publication Status, or `None` if the message is not valid
+fn model_publication_get(state: &NodeState, params: &[u8]) -> Option<Vec<u8>> {
+    if params.len() != 4 && params.len() != 6 {
+        return None;
+    }
+    let element_address = read_u16(params, 0);
+    let model_id = ModelId::decode(&params[2..])?;
+    Some(match find_model(state, element_address, model_id) {
+        Err(status) => publication_status(status, element_address, None, model_id),
+        Ok(element) => publication_status(
+            ConfigStatus::Success,
+            element_address,
+            state.model(element, model_id)?.publication,
+            model_id,
+        ),
+    })
+}
+
+/// Adds or deletes a group address of the subscriptions of a model
+///
+/// # Returns
+///
+/// The parameters of the Model Subscription Status, or `None` if the message is not valid
+fn model_subscription(state: &mut NodeState, params: &[u8], add: bool) -> Option<Vec<u8>> {
+    if params.len() != 6 && params.len() != 8 {
+        return None;
+    }
+    let element_address = read_u16(params, 0);
+    let address = read_u16(params, 2);
+    let model_id = ModelId::decode(&params[4..])?;
+    let status = match find_model(state, element_address, model_id) {
+        Err(status) => status,
+        Ok(_) if !is_group(address) || address == ALL_NODES_ADDRESS => ConfigStatus::InvalidAddress,
+        Ok(element) => {
+            let model = state.model_mut(element, model_id)?;
+            let subscribed = model.subscriptions.contains(&address);
+            if add && !subscribed {
+                if model.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return Some([&[ConfigStatus::InsufficientResources as u8], params].concat());
+                }
+                model.subscriptions.push(address);
+            } else if !add {
+                model
+                    .subscriptions
+                    .retain(|subscription| *subscription != address);
+            }
+            ConfigStatus::Success
+        }
+    };
+    Some([&[status as u8], params].concat())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::{
+        mesh_crypto::hex,
+        network::NetworkKey,
+        node_state::{ModelConfig, ProductInfo},
+    };
+
+    fn provisioned_state() -> NodeState {
+        let mut state = NodeState::new(ProductInfo::default());
+        state.elements[0]
+            .models
+            .push(ModelConfig::new(ModelId::GENERIC_ONOFF_SERVER));
+        state.provision(
+            NetworkKey::new(0x456, [0x22; 16]),
+            0,
+            false,
+            0x1201,
+            [0x11; 16],
+        );
+        state
+    }
+
+    fn handle(state: &mut NodeState, opcode: u16, params: &[u8]) -> (Vec<u8>, ConfigChange) {
+        let message = AccessMessage::new(Opcode::sig(opcode), params);
+        let (answer, change) = handle_config_message(state, &message).unwrap();
+        (answer.encode(), change)
+    }
+
+    #[test]
+    fn config_server_01_adds_app_key_of_sample_message_6() {
+        let mut state = provisioned_state();
+        let params = hex("56341263964771734fbd76e3b40519d1d94a48");
+        let (answer, _) = handle(&mut state, APP_KEY_ADD, &params);
+        assert_eq!(answer, hex("800300563412"));
+        let app_key = state.app_key(0x123).unwrap();
+        assert_eq!((app_key.net_key_index, app_key.aid), (0x456, 0x26));
+
+        // Adding it again succeeds, adding another key with the same index does not
+        assert_eq!(
+            handle(&mut state, APP_KEY_ADD, &params).0,
+            hex("800300563412")
+        );
+        let mut other = params.clone();
+        other[3] ^= 0xFF;
+        assert_eq!(
+            handle(&mut state, APP_KEY_ADD, &other).0,
+            hex("800306563412")
+        );
+        other[1] = 0x30;
+        assert_eq!(
+            handle(&mut state, APP_KEY_ADD, &other).0,
+            hex("800304563012")
+        );
+    }
+
+    #[test]
+    fn config_server_02_binds_publishes_and_subscribes_models() {
+        let mut state = provisioned_state();
+        state.app_keys.push(AppKey::new(0x123, 0x456, [0x33; 16]));
+
+        let bind = hex("011223010010");
+        assert_eq!(
+            handle(&mut state, MODEL_APP_BIND, &bind).0,
+            hex("803e00011223010010")
+        );
+        assert_eq!(
+            handle(&mut state, MODEL_APP_BIND, &hex("011224010010")).0,
+            hex("803e03011224010010")
+        );
+        assert_eq!(
+            handle(&mut state, MODEL_APP_BIND, &hex("011223010000")).0,
+            hex("803e0d011223010000")
+        );
+        assert_eq!(
+            handle(&mut state, MODEL_APP_BIND, &hex("021223010010")).0,
+            hex("803e01021223010010")
+        );
+
+        let publication = hex("011200c023010532000010");
+        let (answer, change) = handle(&mut state, MODEL_PUBLICATION_SET, &publication);
+        assert_eq!(answer, [&hex("801900"), &publication[..]].concat());
+        assert_eq!(
+            change,
+            ConfigChange::Publication {
+                element: 0,
+                model: ModelId::GENERIC_ONOFF_SERVER
+            }
+        );
+        let model = state.model(0, ModelId::GENERIC_ONOFF_SERVER).unwrap();
+        assert_eq!(model.app_keys, [0x123]);
+        let configured = model.publication.unwrap();
+        assert_eq!((configured.address, configured.ttl), (0xC000, 5));
+        assert_eq!(
+            handle(&mut state, MODEL_PUBLICATION_GET, &hex("01120010")).0,
+            [&hex("801900"), &publication[..]].concat()
+        );
+
+        assert_eq!(
+            handle(&mut state, MODEL_SUBSCRIPTION_ADD, &hex("011201c00010")).0,
+            hex("801f00011201c00010")
+        );
+        assert_eq!(
+            handle(&mut state, MODEL_SUBSCRIPTION_ADD, &hex("011201000010")).0,
+            hex("801f01011201000010")
+        );
+        let model = state.model(0, ModelId::GENERIC_ONOFF_SERVER).unwrap();
+        assert_eq!(model.subscriptions, [0xC001]);
+
+        // Unbinding the key of the publication disables it
+        handle(&mut state, MODEL_APP_UNBIND, &bind);
+        let model = state.model(0, ModelId::GENERIC_ONOFF_SERVER).unwrap();
+        assert!(model.app_keys.is_empty() && model.publication.is_none());
+    }
+
+    #[test]
+    fn config_server_03_node_settings_and_reset() {
+        let mut state = provisioned_state();
+        assert_eq!(
+            handle(&mut state, DEFAULT_TTL_SET, &[0x0A]).0,
+            hex("800e0a")
+        );
+        assert_eq!(state.default_ttl, 0x0A);
+        let invalid_ttl = AccessMessage::new(Opcode::sig(DEFAULT_TTL_SET), &[0x01]);
+        assert!(handle_config_message(&mut state, &invalid_ttl).is_none());
+
+        assert_eq!(
+            handle(&mut state, RELAY_SET, &[0x00, 0x21]).0,
+            hex("80280021")
+        );
+        assert!(!state.relay);
+        let (composition, _) = handle(&mut state, COMPOSITION_DATA_GET, &[0x00]);
+        assert_eq!(composition[..2], [0x02, 0x00]);
+        assert_eq!(composition[2..], state.composition_data());
+
+        assert_eq!(
+            handle(&mut state, NODE_RESET, &[]),
+            (hex("804a"), ConfigChange::Reset)
+        );
+        let vendor = AccessMessage::new(Opcode::vendor(0x01, 0x02E5), &[]);
+        assert!(handle_config_message(&mut state, &vendor).is_none());
+    }
+}
@@ -0,0 +1,369 @@
+use std::time::{Duration, Instant};
+
+use super::access::{AccessMessage, MessageContext, Opcode};
+
+const GENERIC_ONOFF_GET: u16 = 0x8201;
+const GENERIC_ONOFF_SET: u16 = 0x8202;
+const GENERIC_ONOFF_SET_UNACKNOWLEDGED: u16 = 0x8203;
+const GENERIC_ONOFF_STATUS: u16 = 0x8204;
+const GENERIC_LEVEL_GET: u16 = 0x8205;
+const GENERIC_LEVEL_SET: u16 = 0x8206;
+const GENERIC_LEVEL_SET_UNACKNOWLEDGED: u16 = 0x8207;
+const GENERIC_LEVEL_STATUS: u16 = 0x8208;
+const GENERIC_DELTA_SET: u16 = 0x8209;
+const GENERIC_DELTA_SET_UNACKNOWLEDGED: u16 = 0x820A;
+/// Time during which a message with the same transaction identifier is a retransmission
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// State reported by a Generic OnOff or Level server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenericStatus {
+    OnOff(bool),
+    Level(i16),
+}
+
+impl GenericStatus {
+    /// Parses a Generic OnOff Status or a Generic Level Status. The target state and remaining
+    /// time of transitions are ignored.
+    ///
+    /// # Returns
+    ///
+    /// The status, or `None` if the message is not a valid status
+    pub fn decode(message: &AccessMessage) -> Option<Self> {
+        let Opcode::TwoOctets(opcode) = message.opcode else {
+            return None;
+        };
+        match (opcode, message.params.as_slice()) {
+            (GENERIC_ONOFF_STATUS, [on] | [on, _, _]) if *on <= 1 => {
+                Some(GenericStatus::OnOff(*on == 1))
+            }
+            (GENERIC_LEVEL_STATUS, [low, high] | [low, high, _, _, _]) => {
+                Some(GenericStatus::Level(i16::from_le_bytes([*low, *high])))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Result of a message handled by a server model
+/// - `answer`: The status to send back, if the message was acknowledged
+/// - `changed`: Whether the state of the server changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServerResult {
+    pub answer: Option<AccessMessage>,
+    pub changed: bool,
+}
+
+/// The last transaction of a client, to detect retransmissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transaction {
+    src: u16,
+    dst: u16,
+    tid: u8,
+    received_at: Instant,
+}
+
+/// Checks whether a message belongs to the last transaction, and remembers it otherwise
+///
+/// # Returns
+///
+/// Whether the message is new
+fn new_transaction(
+    last: &mut Option<Transaction>,
+    context: &MessageContext,
+    tid: u8,
+    now: Instant,
+) -> bool {
+    let transaction = Transaction {
+        src: context.src,
+        dst: context.dst,
+        tid,
+        received_at: now,
+    };
+    let repeated = last.is_some_and(|last| {
+        last.src == context.src
+            && last.dst == context.dst
+            && last.tid == tid
+            && now.duration_since(last.received_at) < TRANSACTION_TIMEOUT
+    });
+    *last = Some(transaction);
+    !repeated
+}
+
+/// Gets the numeric value of a SIG opcode
+fn sig_opcode(message: &AccessMessage) -> Option<u16> {
+    match message.opcode {
+        Opcode::TwoOctets(opcode) => Some(opcode),
+        _ => None,
+    }
+}
+
+/// State of a Generic OnOff server. Transitions are not supported, every change is instant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OnOffServer {
+    pub on: bool,
+    last_transaction: Option<Transaction>,
+}
+
+impl OnOffServer {
+    pub(crate) fn new(on: bool) -> Self {
+        Self {
+            on,
+            last_transaction: None,
+        }
+    }
+
+    /// Gets the Generic OnOff Status with the current state
+    pub(crate) fn status(&self) -> AccessMessage {
+        AccessMessage::new(Opcode::sig(GENERIC_ONOFF_STATUS), &[self.on as u8])
+    }
+
+    /// Handles a message sent to the server
+    ///
+    /// # Returns
+    ///
+    /// The result of the message, or `None` if it is not a valid message of the model
+    pub(crate) fn handle(
+        &mut self,
+        context: &MessageContext,
+        message: &AccessMessage,
+        now: Instant,
+    ) -> Option<ServerResult> {
+        let opcode = sig_opcode(message)?;
+        let acknowledged = match opcode {
+            GENERIC_ONOFF_GET if message.params.is_empty() => {
+                return Some(ServerResult {
+                    answer: Some(self.status()),
+                    changed: false,
+                })
+            }
+            GENERIC_ONOFF_SET => true,
+            GENERIC_ONOFF_SET_UNACKNOWLEDGED => false,
+            _ => return None,
+        };
+        let (on, tid) = match message.params.as_slice() {
+            [on, tid] | [on, tid, _, _] if *on <= 1 => (*on == 1, *tid),
+            _ => return None,
+        };
+        let changed =
+            new_transaction(&mut self.last_transaction, context, tid, now) && on != self.on;
+        if changed {
+            self.on = on;
+        }
+        Some(ServerResult {
+            answer: acknowledged.then(|| self.status()),
+            changed,
+        })
+    }
+}
+
+/// State of a Generic Level server. Transitions are not supported, every change is instant.
+/// - `delta_base`: The level at the start of the last transaction, to which the deltas of its
+///   retransmissions are added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LevelServer {
+    pub level: i16,
+    delta_base: i16,
+    last_transaction: Option<Transaction>,
+}
+
+impl LevelServer {
+    pub(crate) fn new(level: i16) -> Self {
+        Self {
+            level,
+            delta_base: level,
+            last_transaction: None,
+        }
+    }
+
+    /// Gets the Generic Level Status with the current state
+    pub(crate) fn status(&self) -> AccessMessage {
+        AccessMessage::new(Opcode::sig(GENERIC_LEVEL_STATUS), &self.level.to_le_bytes())
+    }
+
+    /// Handles a message sent to the server
+    ///
+    /// # Returns
+    ///
+    /// The result of the message, or `None` if it is not a valid message of the model
+    pub(crate) fn handle(
+        &mut self,
+        context: &MessageContext,
+        message: &AccessMessage,
+        now: Instant,
+    ) -> Option<ServerResult> {
+        let opcode = sig_opcode(message)?;
+        let params = message.params.as_slice();
+        let previous = self.level;
+        let acknowledged = match (opcode, params) {
+            (GENERIC_LEVEL_GET, []) => {
+                return Some(ServerResult {
+                    answer: Some(self.status()),
+                    changed: false,
+                })
+            }
+            (
+                GENERIC_LEVEL_SET | GENERIC_LEVEL_SET_UNACKNOWLEDGED,
+                [low, high, tid] | [low, high, tid, _, _],
+            ) => {
+                if new_transaction(&mut self.last_transaction, context, *tid, now) {
+                    self.level = i16::from_le_bytes([*low, *high]);
+                }
+                opcode == GENERIC_LEVEL_SET
+            }
+            (
+                GENERIC_DELTA_SET | GENERIC_DELTA_SET_UNACKNOWLEDGED,
+                [b0, b1, b2, b3, tid] | [b0, b1, b2, b3, tid, _, _],
+            ) => {
+                if new_transaction(&mut self.last_transaction, context, *tid, now) {
+                    self.delta_base = self.level;
+                }
+                // Every message of a transaction carries the total delta from its start
+                let delta = i32::from_le_bytes([*b0, *b1, *b2, *b3]);
+                let level = (self.delta_base as i32).saturating_add(delta);
+                self.level = level.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+                opcode == GENERIC_DELTA_SET
+            }
+            _ => return None,
+        };
+        Some(ServerResult {
+            answer: acknowledged.then(|| self.status()),
+            changed: self.level != previous,
+        })
+    }
+}
+
+/// Builds the messages of the Generic OnOff and Level clients, numbering their transactions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct GenericClient {
+    tid: u8,
+}
+
+impl GenericClient {
+    /// Takes the identifier of a new transaction
+    fn next_tid(&mut self) -> u8 {
+        self.tid = self.tid.wrapping_add(1);
+        self.tid
+    }
+
+    /// Creates a Generic OnOff Get
+    pub(crate) fn onoff_get(&self) -> AccessMessage {
+        AccessMessage::new(Opcode::sig(GENERIC_ONOFF_GET), &[])
+    }
+
+    /// Creates a Generic OnOff Set, acknowledged or not
+    pub(crate) fn onoff_set(&mut self, on: bool, acknowledged: bool) -> AccessMessage {
+        let opcode = match acknowledged {
+            true => GENERIC_ONOFF_SET,
+            false => GENERIC_ONOFF_SET_UNACKNOWLEDGED,
+        };
+        AccessMessage::new(Opcode::sig(opcode), &[on as u8, self.next_tid()])
+    }
+
+    /// Creates a Generic Level Get
+    pub(crate) fn level_get(&self) -> AccessMessage {
+        AccessMessage::new(Opcode::sig(GENERIC_LEVEL_GET), &[])
+    }
+
+    /// Creates a Generic Level Set, acknowledged or not
+    pub(crate) fn level_set(&mut self, level: i16, acknowledged: bool) -> AccessMessage {
+        let opcode = match acknowledged {
+            true => GENERIC_LEVEL_SET,
+            false => GENERIC_LEVEL_SET_UNACKNOWLEDGED,
+        };
+        let mut params = level.to_le_bytes().to_vec();
+        params.push(self.next_tid());
+        AccessMessage::new(Opcode::sig(opcode), &params)
+    }
+
+    /// Creates a Generic Delta Set, acknowledged or not, that adds `delta` to the level
+    pub(crate) fn delta_set(&mut self, delta: i32, acknowledged: bool) -> AccessMessage {
+        let opcode = match acknowledged {
+            true => GENERIC_DELTA_SET,
+            false => GENERIC_DELTA_SET_UNACKNOWLEDGED,
+        };
+        let mut params = delta.to_le_bytes().to_vec();
+        params.push(self.next_tid());
+        AccessMessage::new(Opcode::sig(opcode), &params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context(src: u16) -> MessageContext {
+        MessageContext {
+            src,
+            dst: 0x1201,
+            app_key_index: Some(0),
+            ttl: 5,
+        }
+    }
+
+    #[test]
+    fn generic_models_01_onoff_server_ignores_repeated_transactions() {
+        let mut client = GenericClient::default();
+        let mut server = OnOffServer::new(false);
+        let now = Instant::now();
+        let set = client.onoff_set(true, true);
+        assert_eq!(set.encode(), [0x82, 0x02, 0x01, 0x01]);
+
+        let result = server.handle(&context(0x0001), &set, now).unwrap();
+        assert!(result.changed && server.on);
+        assert_eq!(
+            GenericStatus::decode(&result.answer.unwrap()),
+            Some(GenericStatus::OnOff(true))
+        );
+
+        // A retransmission is answered but not applied again
+        server.on = false;
+        let result = server.handle(&context(0x0001), &set, now).unwrap();
+        assert!(!result.changed && result.answer.is_some());
+        let result = server
+            .handle(&context(0x0001), &set, now + TRANSACTION_TIMEOUT)
+            .unwrap();
+        assert!(result.changed && server.on);
+
+        let unacknowledged = client.onoff_set(false, false);
+        let result = server
+            .handle(&context(0x0001), &unacknowledged, now)
+            .unwrap();
+        assert!(result.changed && result.answer.is_none());
+        let invalid = AccessMessage::new(Opcode::sig(GENERIC_ONOFF_SET), &[0x02, 0x01]);
+        assert!(server.handle(&context(0x0001), &invalid, now).is_none());
+    }
+
+    #[test]
+    fn generic_models_02_level_server_applies_deltas_per_transaction() {
+        let mut client = GenericClient::default();
+        let mut server = LevelServer::new(0);
+        let now = Instant::now();
+
+        let set = client.level_set(-1000, false);
+        server.handle(&context(0x0002), &set, now).unwrap();
+        assert_eq!(server.level, -1000);
+
+        let delta = client.delta_set(500, true);
+        let result = server.handle(&context(0x0002), &delta, now).unwrap();
+        assert_eq!(server.level, -500);
+        assert_eq!(
+            GenericStatus::decode(&result.answer.unwrap()),
+            Some(GenericStatus::Level(-500))
+        );
+        // Retransmissions of the transaction carry the total delta from its start
+        let mut bigger = delta.clone();
+        bigger.params[..4].copy_from_slice(&800_i32.to_le_bytes());
+        server.handle(&context(0x0002), &bigger, now).unwrap();
+        assert_eq!(server.level, -200);
+
+        let huge = client.delta_set(i32::MAX, false);
+        let result = server.handle(&context(0x0002), &huge, now).unwrap();
+        assert!(result.changed);
+        assert_eq!(server.level, i16::MAX);
+        assert_eq!(
+            GenericStatus::decode(&server.status()),
+            Some(GenericStatus::Level(i16::MAX))
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    ffi::{c_int, c_void},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use esp32_nimble::BLEError;
+use esp_idf_svc::sys;
+
+use crate::{ble::BleError, utils::notification::Notifier};
+
+/// AD type of the PB-ADV payloads
+pub(crate) const PB_ADV_AD_TYPE: u8 = 0x29;
+/// AD type of the network PDUs
+pub(crate) const MESH_MESSAGE_AD_TYPE: u8 = 0x2A;
+/// AD type of the mesh beacons
+pub(crate) const MESH_BEACON_AD_TYPE: u8 = 0x2B;
+/// Advertising interval of the mesh PDUs, in units of 0.625 ms: 20 ms
+const ADVERTISING_INTERVAL: u16 = 32;
+/// Time each PDU is advertised, enough for three advertising events
+const ADVERTISING_DURATION_MS: i32 = 60;
+/// Scan interval and window, in units of 0.625 ms: the node scans all the time
+const SCAN_INTERVAL: u16 = 48;
+/// Amount of PDUs waiting to be advertised, the oldest are dropped after it
+const QUEUE_CAPACITY: usize = 32;
+
+/// Sender of the mesh AD structures scanned, with the notifier that wakes up the main loop
+type ScanSender = (Sender<(u8, Vec<u8>)>, Notifier);
+
+/// Receiver of the mesh AD structures scanned, only one mesh node exists at a time
+static RECEIVED: Mutex<Option<ScanSender>> = Mutex::new(None);
+/// Whether a PDU is being advertised
+static ADVERTISING: AtomicBool = AtomicBool::new(false);
+/// Whether the scan is running
+static SCANNING: AtomicBool = AtomicBool::new(false);
+
+/// Splits advertising data in its AD structures
+///
+/// # Returns
+///
+/// The AD type and data of every well formed structure
+fn ad_structures(mut data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut structures = Vec::new();
+    while let Some((&length, rest)) = data.split_first() {
+        let length = length as usize;
+        if length == 0 || length > rest.len() {
+            break;
+        }
+        structures.push((rest[0], &rest[1..length]));
+        data = &rest[length..];
+    }
+    structures
+}
+
+/// The advertising bearer, which sends and receives the mesh PDUs as non connectable
+/// advertisements. PDUs are advertised one after the other, and received by a passive scan that
+/// does not filter duplicates.
+/// - `received`: Receiver of the mesh AD structures scanned by the BLE stack
+/// - `queue`: The AD type and payload of the PDUs waiting to be advertised
+/// - `advertise`: Whether the queued PDUs are advertised. When the node is provisioned through
+///   GATT, the server uses the advertising until then.
+pub(crate) struct MeshBearer {
+    received: Receiver<(u8, Vec<u8>)>,
+    queue: VecDeque<(u8, Vec<u8>)>,
+    advertise: bool,
+}
+
+impl MeshBearer {
+    /// Creates a new MeshBearer, not yet scanning
+    ///
+    /// # Arguments
+    ///
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] after a PDU is
+    ///   received or advertised
+    pub(crate) fn new(notifier: Notifier) -> Self {
+        let (sender, received) = channel();
+        if let Ok(mut receiver) = RECEIVED.lock() {
+            *receiver = Some((sender, notifier));
+        }
+        Self {
+            received,
+            queue: VecDeque::new(),
+            advertise: true,
+        }
+    }
+
+    /// Sets whether the queued PDUs are advertised
+    pub(crate) fn set_advertise(&mut self, advertise: bool) {
+        self.advertise = advertise;
+    }
+
+    /// Starts the scan, if it is not running
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the BLE stack fails to start the scan
+    pub(crate) fn start_scan(&mut self) -> Result<(), BleError> {
+        if SCANNING.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut params: sys::ble_gap_disc_params = unsafe { std::mem::zeroed() };
+        params.itvl = SCAN_INTERVAL;
+        params.window = SCAN_INTERVAL;
+        params.set_passive(1);
+        params.set_filter_duplicates(0);
+        let rc = unsafe {
+            sys::ble_gap_disc(
+                sys::BLE_OWN_ADDR_PUBLIC as u8,
+                sys::BLE_HS_FOREVER as i32,
+                &params,
+                Some(on_gap_event),
+                ptr::null_mut(),
+            )
+        };
+        if rc as u32 != sys::BLE_HS_EALREADY {
+            BLEError::convert(rc as u32)?;
+        }
+        SCANNING.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Stops the scan and the advertising, dropping the queued PDUs
+    pub(crate) fn stop(&mut self) {
+        unsafe {
+            sys::ble_gap_disc_cancel();
+            if ADVERTISING.swap(false, Ordering::SeqCst) {
+                sys::ble_gap_adv_stop();
+            }
+        }
+        SCANNING.store(false, Ordering::SeqCst);
+        self.queue.clear();
+    }
+
+    /// Queues a PDU to be advertised
+    ///
+    /// # Arguments
+    ///
+    /// - `ad_type`: The AD type of the PDU
+    /// - `payload`: The PDU
+    pub(crate) fn send(&mut self, ad_type: u8, payload: Vec<u8>) {
+        if self.queue.len() >= QUEUE_CAPACITY {
+            self.queue.pop_front();
+        }
+        self.queue.push_back((ad_type, payload));
+    }
+
+    /// Gets whether there are PDUs waiting to be advertised
+    pub(crate) fn is_sending(&self) -> bool {
+        !self.queue.is_empty() || ADVERTISING.load(Ordering::SeqCst)
+    }
+
+    /// Takes the mesh AD structures received since the last call
+    pub(crate) fn received(&mut self) -> Vec<(u8, Vec<u8>)> {
+        self.received.try_iter().collect()
+    }
+
+    /// Advertises the next queued PDU once the previous one was advertised, and starts the scan
+    /// again if the BLE stack stopped it
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::Code`: If the BLE stack fails to advertise the PDU or start the scan
+    pub(crate) fn update(&mut self) -> Result<(), BleError> {
+        self.start_scan()?;
+        if !self.advertise || ADVERTISING.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let Some((ad_type, payload)) = self.queue.pop_front() else {
+            return Ok(());
+        };
+        let data = [&[payload.len() as u8 + 1, ad_type], &payload[..]].concat();
+        let rc = unsafe { sys::ble_gap_adv_set_data(data.as_ptr(), data.len() as c_int) };
+        BLEError::convert(rc as u32)?;
+
+        let mut params: sys::ble_gap_adv_params = unsafe { std::mem::zeroed() };
+        params.conn_mode = sys::BLE_GAP_CONN_MODE_NON as u8;
+        params.disc_mode = sys::BLE_GAP_DISC_MODE_NON as u8;
+        params.itvl_min = ADVERTISING_INTERVAL;
+        params.itvl_max = ADVERTISING_INTERVAL;
+        ADVERTISING.store(true, Ordering::SeqCst);
+        let rc = unsafe {
+            sys::ble_gap_adv_start(
+                sys::BLE_OWN_ADDR_PUBLIC as u8,
+                ptr::null(),
+                ADVERTISING_DURATION_MS,
+                &params,
+                Some(on_gap_event),
+                ptr::null_mut(),
+            )
+        };
+        if rc != 0 {
+            ADVERTISING.store(false, Ordering::SeqCst);
+        }
+        Ok(BLEError::convert(rc as u32)?)
+    }
+}
+
+impl Drop for MeshBearer {
+    fn drop(&mut self) {
+        self.stop();
+        if let Ok(mut receiver) = RECEIVED.lock() {
+            *receiver = None;
+        }
+    }
+}
+
+/// Wakes up the main loop, optionally sending it a mesh AD structure
+fn notify(structure: Option<(u8, Vec<u8>)>) {
+    let Ok(receiver) = RECEIVED.lock() else {
+        return;
+    };
+    let Some((sender, notifier)) = receiver.as_ref() else {
+        return;
+    };
+    if let Some(structure) = structure {
+        if sender.send(structure).is_err() {
+            return;
+        }
+    }
+    notifier.notify();
+}
+
+/// Handler of the GAP events of the scan and the advertising, executed by the BLE stack
+extern "C" fn on_gap_event(event: *mut sys::ble_gap_event, _arg: *mut c_void) -> c_int {
+    let Some(event) = (unsafe { event.as_ref() }) else {
+        return 0;
+    };
+    match event.type_ as u32 {
+        sys::BLE_GAP_EVENT_DISC => {
+            let desc = unsafe { &event.__bindgen_anon_1.disc };
+            if desc.data.is_null() {
+                return 0;
+            }
+            let data = unsafe { std::slice::from_raw_parts(desc.data, desc.length_data as usize) };
+            for (ad_type, payload) in ad_structures(data) {
+                if matches!(
+                    ad_type,
+                    PB_ADV_AD_TYPE | MESH_MESSAGE_AD_TYPE | MESH_BEACON_AD_TYPE
+                ) {
+                    notify(Some((ad_type, payload.to_vec())));
+                }
+            }
+        }
+        sys::BLE_GAP_EVENT_DISC_COMPLETE => {
+            SCANNING.store(false, Ordering::SeqCst);
+            notify(None);
+        }
+        sys::BLE_GAP_EVENT_ADV_COMPLETE => {
+            ADVERTISING.store(false, Ordering::SeqCst);
+            notify(None);
+        }
+        _ => {}
+    }
+    0
+}
@@ -0,0 +1,80 @@
+use super::{node_state::ProductInfo, provisioning::ProvisioningAuth};
+
+const DEFAULT_NAME: &str = "Mesh Node";
+
+/// How a provisioner reaches an unprovisioned [super::BleMeshNode]
+/// - `Adv`: PB-ADV, the provisioning PDUs are sent in advertisements. Every provisioner
+///   supports it.
+/// - `Gatt`: PB-GATT, the node advertises the Mesh Provisioning Service and the provisioner
+///   connects to it, as phone apps do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningBearer {
+    Adv,
+    Gatt,
+}
+
+/// Configuration of a [super::BleMeshNode]
+/// - `uuid`: The UUID of the node, with which provisioners identify it
+/// - `name`: The name advertised with the Mesh Provisioning Service
+/// - `bearer`: How the node is provisioned
+/// - `auth`: How the provisioner authenticates the node
+/// - `oob_info`: Where the OOB information of the node is available, as the OOB Information
+///   field of the spec
+/// - `product`: The identification of the node reported in its composition data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshNodeConfig {
+    pub uuid: [u8; 16],
+    pub name: String,
+    pub bearer: ProvisioningBearer,
+    pub auth: ProvisioningAuth,
+    pub oob_info: u16,
+    pub product: ProductInfo,
+}
+
+impl MeshNodeConfig {
+    /// Creates a new MeshNodeConfig, provisioned through PB-ADV without authentication
+    ///
+    /// # Arguments
+    ///
+    /// - `uuid`: The UUID of the node, it must be unique among the devices of the network
+    pub fn new(uuid: [u8; 16]) -> Self {
+        Self {
+            uuid,
+            name: DEFAULT_NAME.to_string(),
+            bearer: ProvisioningBearer::Adv,
+            auth: ProvisioningAuth::NoOob,
+            oob_info: 0,
+            product: ProductInfo::default(),
+        }
+    }
+
+    /// Sets the name advertised with the Mesh Provisioning Service
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets how the node is provisioned
+    pub fn bearer(mut self, bearer: ProvisioningBearer) -> Self {
+        self.bearer = bearer;
+        self
+    }
+
+    /// Sets how the provisioner authenticates the node
+    pub fn auth(mut self, auth: ProvisioningAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Sets where the OOB information of the node is available
+    pub fn oob_info(mut self, oob_info: u16) -> Self {
+        self.oob_info = oob_info;
+        self
+    }
+
+    /// Sets the identification of the node reported in its composition data
+    pub fn product(mut self, product: ProductInfo) -> Self {
+        self.product = product;
+        self
+    }
+}
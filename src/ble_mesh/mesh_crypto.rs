@@ -0,0 +1,221 @@
+use aes::{
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit},
+    Aes128,
+};
+use ccm::{
+    aead::AeadInPlace,
+    consts::{U13, U4, U8},
+    Ccm,
+};
+use cmac::{Cmac, Mac};
+
+use super::MeshError;
+
+/// A 128 bits key of the mesh security functions
+pub type MeshKey = [u8; 16];
+
+/// Nonce of the CCM encryption of every mesh layer
+pub(crate) type MeshNonce = [u8; 13];
+
+type Ccm4 = Ccm<Aes128, U4, U13>;
+type Ccm8 = Ccm<Aes128, U8, U13>;
+
+/// Size of the message integrity check appended by the CCM encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MicSize {
+    Short,
+    Long,
+}
+
+impl MicSize {
+    /// Gets the amount of bytes of the MIC
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            MicSize::Short => 4,
+            MicSize::Long => 8,
+        }
+    }
+}
+
+/// Encrypts a single block with AES-128, the `e` function of the mesh specification
+pub(crate) fn e(key: &MeshKey, plaintext: &[u8; 16]) -> [u8; 16] {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut block = GenericArray::clone_from_slice(plaintext);
+    cipher.encrypt_block(&mut block);
+    block.into()
+}
+
+/// Gets the AES-CMAC of the concatenation of `parts`
+pub(crate) fn aes_cmac(key: &MeshKey, parts: &[&[u8]]) -> MeshKey {
+    let mut mac = <Cmac<Aes128> as Mac>::new(GenericArray::from_slice(key));
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Salt generation function, the AES-CMAC of `m` with a zero key
+pub(crate) fn s1(m: &[u8]) -> MeshKey {
+    aes_cmac(&[0; 16], &[m])
+}
+
+/// Key derivation function used by the provisioning and the identity keys
+pub(crate) fn k1(n: &[u8], salt: &MeshKey, p: &[u8]) -> MeshKey {
+    let t = aes_cmac(salt, &[n]);
+    aes_cmac(&t, &[p])
+}
+
+/// Network key material derivation function.
+///
+/// # Returns
+///
+/// A tuple with the NID, the encryption key and the privacy key
+pub(crate) fn k2(n: &MeshKey, p: &[u8]) -> (u8, MeshKey, MeshKey) {
+    let t = aes_cmac(&s1(b"smk2"), &[n]);
+    let t1 = aes_cmac(&t, &[p, &[0x01]]);
+    let t2 = aes_cmac(&t, &[&t1, p, &[0x02]]);
+    let t3 = aes_cmac(&t, &[&t2, p, &[0x03]]);
+    (t1[15] & 0x7F, t2, t3)
+}
+
+/// Derives the public 64 bits network id of a network key
+pub(crate) fn k3(n: &MeshKey) -> [u8; 8] {
+    let t = aes_cmac(&s1(b"smk3"), &[n]);
+    let result = aes_cmac(&t, &[b"id64", &[0x01]]);
+    result[8..].try_into().unwrap()
+}
+
+/// Derives the 6 bits AID of an application key
+pub(crate) fn k4(n: &MeshKey) -> u8 {
+    let t = aes_cmac(&s1(b"smk4"), &[n]);
+    aes_cmac(&t, &[b"id6", &[0x01]])[15] & 0x3F
+}
+
+/// Encrypts and authenticates `data` with AES-CCM
+///
+/// # Returns
+///
+/// The encrypted data followed by the MIC
+pub(crate) fn ccm_encrypt(
+    key: &MeshKey,
+    nonce: &MeshNonce,
+    data: &[u8],
+    aad: &[u8],
+    mic: MicSize,
+) -> Vec<u8> {
+    let mut buffer = data.to_vec();
+    let key = GenericArray::from_slice(key);
+    let nonce = GenericArray::from_slice(nonce);
+    // Encryption only fails when the data is too long for the nonce, never with mesh PDUs
+    let tag = match mic {
+        MicSize::Short => Ccm4::new(key)
+            .encrypt_in_place_detached(nonce, aad, &mut buffer)
+            .map(|tag| tag.to_vec()),
+        MicSize::Long => Ccm8::new(key)
+            .encrypt_in_place_detached(nonce, aad, &mut buffer)
+            .map(|tag| tag.to_vec()),
+    };
+    buffer.extend(tag.unwrap_or_default());
+    buffer
+}
+
+/// Decrypts data encrypted with [ccm_encrypt], checking its MIC
+///
+/// # Returns
+///
+/// A `Result` with the decrypted data, or a `MeshError` if it could not be authenticated
+///
+/// # Errors
+///
+/// - `MeshError::AuthenticationFailed`: If the MIC does not match the data, as when it was
+///   encrypted with another key
+pub(crate) fn ccm_decrypt(
+    key: &MeshKey,
+    nonce: &MeshNonce,
+    data: &[u8],
+    aad: &[u8],
+    mic: MicSize,
+) -> Result<Vec<u8>, MeshError> {
+    let Some(split) = data.len().checked_sub(mic.len()) else {
+        return Err(MeshError::InvalidPdu);
+    };
+    let (encrypted, tag) = data.split_at(split);
+    let mut buffer = encrypted.to_vec();
+    let key = GenericArray::from_slice(key);
+    let nonce = GenericArray::from_slice(nonce);
+    let result = match mic {
+        MicSize::Short => Ccm4::new(key).decrypt_in_place_detached(
+            nonce,
+            aad,
+            &mut buffer,
+            GenericArray::from_slice(tag),
+        ),
+        MicSize::Long => Ccm8::new(key).decrypt_in_place_detached(
+            nonce,
+            aad,
+            &mut buffer,
+            GenericArray::from_slice(tag),
+        ),
+    };
+    result.map_err(|_| MeshError::AuthenticationFailed)?;
+    Ok(buffer)
+}
+
+/// Parses a hexadecimal string, used by the tests with the sample data of the specification
+#[cfg(test)]
+pub(crate) fn hex(value: &str) -> Vec<u8> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) fn hex_key(value: &str) -> MeshKey {
+    hex(value).try_into().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesh_crypto_01_s1_and_k1_match_sample_data() {
+        assert_eq!(s1(b"test"), hex_key("b73cefbd641ef2ea598c2b6efb62f79c"));
+        let key = k1(
+            &hex("3216d1509884b533248541792b877f98"),
+            &hex_key("2ba14ffa0df84a2831938d57d276cab4"),
+            &hex("5a09d60797eeb4478aada59db3352a0d"),
+        );
+        assert_eq!(key, hex_key("f6ed15a8934afbe7d83e8dcb57fcf5d7"));
+    }
+
+    #[test]
+    fn mesh_crypto_02_k2_k3_k4_match_sample_data() {
+        let n = hex_key("f7a2a44f8e8a8029064f173ddc1e2b00");
+        let (nid, encryption_key, privacy_key) = k2(&n, &[0x00]);
+        assert_eq!(nid, 0x7F);
+        assert_eq!(encryption_key, hex_key("9f589181a0f50de73c8070c7a6d27f46"));
+        assert_eq!(privacy_key, hex_key("4c715bd4a64b938f99b453351653124f"));
+        assert_eq!(k3(&n).to_vec(), hex("ff046958233db014"));
+        assert_eq!(k4(&hex_key("3216d1509884b533248541792b877f98")), 0x38);
+    }
+
+    #[test]
+    fn mesh_crypto_03_ccm_round_trip_and_rejects_tampering() {
+        let key = hex_key("0953fa93e7caac9638f58820220a398e");
+        let nonce: MeshNonce = hex("00800000011201000012345678").try_into().unwrap();
+        let encrypted = ccm_encrypt(&key, &nonce, b"mesh", &[], MicSize::Long);
+        assert_eq!(encrypted.len(), 12);
+        assert_eq!(
+            ccm_decrypt(&key, &nonce, &encrypted, &[], MicSize::Long).unwrap(),
+            b"mesh"
+        );
+        let mut tampered = encrypted.clone();
+        tampered[0] ^= 1;
+        assert!(matches!(
+            ccm_decrypt(&key, &nonce, &tampered, &[], MicSize::Long),
+            Err(MeshError::AuthenticationFailed)
+        ));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    ble::BleError,
+    gpio::{analog::AnalogOutError, digital::DigitalOutError},
+    utils::timer_driver::TimerDriverError,
+};
+
+/// Enums the different errors possible when working with BLE Mesh
+#[derive(Debug)]
+pub enum MeshError {
+    AlreadyProvisioned,
+    AnalogOut(AnalogOutError),
+    AuthenticationFailed,
+    Ble(BleError),
+    DigitalOut(DigitalOutError),
+    InvalidElement(usize),
+    InvalidKeyIndex(u16),
+    InvalidOpcode,
+    InvalidPdu,
+    MessageTooLong(usize),
+    ModelAlreadyAdded,
+    ModelNotBound,
+    ModelNotFound,
+    NoPublication,
+    NotProvisioned,
+    NvsAlreadyTaken,
+    SequenceExhausted,
+    StorageError,
+    TimerDriver(TimerDriverError),
+}
+
+impl From<AnalogOutError> for MeshError {
+    fn from(value: AnalogOutError) -> Self {
+        MeshError::AnalogOut(value)
+    }
+}
+
+impl From<BleError> for MeshError {
+    fn from(value: BleError) -> Self {
+        MeshError::Ble(value)
+    }
+}
+
+impl From<DigitalOutError> for MeshError {
+    fn from(value: DigitalOutError) -> Self {
+        MeshError::DigitalOut(value)
+    }
+}
+
+impl From<TimerDriverError> for MeshError {
+    fn from(value: TimerDriverError) -> Self {
+        MeshError::TimerDriver(value)
+    }
+}
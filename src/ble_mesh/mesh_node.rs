@@ -0,0 +1,1234 @@
+use std::{
+    ffi::c_void,
+    time::{Duration, Instant},
+};
+
+use esp32_nimble::BLEDevice;
+use esp_idf_svc::{hal::gpio::Level, nvs::EspDefaultNvsPartition, sys};
+use sharable_reference_macro::sharable_reference_wrapper;
+
+use super::{
+    access::{AccessMessage, MessageContext},
+    generic_models::{GenericClient, GenericStatus, LevelServer, OnOffServer, ServerResult},
+    mesh_bearer::{MeshBearer, MESH_BEACON_AD_TYPE, MESH_MESSAGE_AD_TYPE, PB_ADV_AD_TYPE},
+    mesh_config::{MeshNodeConfig, ProvisioningBearer},
+    mesh_stack::{AccessKey, IncomingAccess, MeshStack, StackOutput},
+    mesh_storage::MeshStorage,
+    network::NetworkKey,
+    node_state::{ElementConfig, ModelConfig, ModelId, NodeState, Publication},
+    provisioning::{Provisionee, ProvisioningData, ProvisioningStep},
+    provisioning_bearer::{unprovisioned_device_beacon, LinkEvent, PbAdvLink},
+    provisioning_service::ProvisioningService,
+    MeshError,
+};
+use crate::{
+    ble::{utils::legacy_advertising, BleServer},
+    gpio::{analog::AnalogOut, digital::DigitalOut},
+    utils::{
+        auxiliary::{SharableRef, SharableRefExt},
+        esp32_framework_error::Esp32FrameworkError,
+        notification::Notifier,
+        timer_driver::TimerDriver,
+    },
+    InterruptDriver,
+};
+
+/// Time between the updates of the node, which drive its retransmissions and publications
+const UPDATE_PERIOD: Duration = Duration::from_millis(50);
+const SECURE_BEACON_INTERVAL: Duration = Duration::from_secs(10);
+const UNPROVISIONED_BEACON_INTERVAL: Duration = Duration::from_secs(2);
+
+type VendorHandler<'a> = dyn FnMut(&MessageContext, &AccessMessage) -> Option<AccessMessage> + 'a;
+type StatusCallback<'a> = dyn FnMut(&MessageContext, GenericStatus) + 'a;
+
+/// Fills a buffer with random bytes of the hardware generator
+fn fill_random(buffer: &mut [u8]) {
+    unsafe { sys::esp_fill_random(buffer.as_mut_ptr() as *mut c_void, buffer.len()) };
+}
+
+/// Gets the duty of an analog output for a generic level: the lowest level turns it off and the
+/// highest one turns it fully on
+fn level_ratio(level: i16) -> f32 {
+    (level as i32 - i16::MIN as i32) as f32 / u16::MAX as f32
+}
+
+/// What handles the messages of a model of the node
+enum ModelHandler<'a> {
+    OnOffServer {
+        server: OnOffServer,
+        output: DigitalOut<'a>,
+    },
+    LevelServer {
+        server: LevelServer,
+        output: AnalogOut<'a>,
+    },
+    Client(GenericClient),
+    Vendor(Box<VendorHandler<'a>>),
+}
+
+impl ModelHandler<'_> {
+    /// Gets the status published by a server, or `None` for the other models
+    fn status(&self) -> Option<AccessMessage> {
+        match self {
+            ModelHandler::OnOffServer { server, .. } => Some(server.status()),
+            ModelHandler::LevelServer { server, .. } => Some(server.status()),
+            _ => None,
+        }
+    }
+}
+
+/// A model added to an element of the node
+/// - `element`: The index of the element
+/// - `id`: The id of the model
+/// - `handler`: What handles its messages
+/// - `next_publication`: When the state is published again, if it is published periodically
+struct BoundModel<'a> {
+    element: usize,
+    id: ModelId,
+    handler: ModelHandler<'a>,
+    next_publication: Option<Instant>,
+}
+
+/// A published message that must be sent again
+struct Retransmission {
+    element: usize,
+    id: ModelId,
+    message: AccessMessage,
+    remaining: u8,
+    next_attempt: Instant,
+}
+
+/// Driver of a BLE Mesh node
+/// - `config`: The configuration of the node
+/// - `stack`: The network, transport and access layers
+/// - `provisionee`: The provisioning procedure
+/// - `pb_adv`: The PB-ADV link with the provisioner
+/// - `pb_gatt`: The Mesh Provisioning Service, if the node is provisioned through PB-GATT
+/// - `bearer`: The advertising bearer of the mesh PDUs
+/// - `storage`: Keeps the state of the node in the NVS
+/// - `timer_driver`: Wakes up the node periodically
+/// - `models`: The models added to the elements, besides the configuration server
+/// - `retransmissions`: The published messages that must be sent again
+/// - `next_beacon`: When the next beacon is sent
+/// - `pending_reset`: Whether the node leaves the network once the Node Reset Status is sent
+struct _BleMeshNode<'a> {
+    config: MeshNodeConfig,
+    stack: MeshStack,
+    provisionee: Provisionee,
+    pb_adv: PbAdvLink,
+    pb_gatt: Option<ProvisioningService<'a>>,
+    bearer: MeshBearer,
+    storage: MeshStorage,
+    timer_driver: TimerDriver<'a>,
+    models: Vec<BoundModel<'a>>,
+    retransmissions: Vec<Retransmission>,
+    next_beacon: Instant,
+    pending_reset: bool,
+    user_on_provisioned: Option<Box<dyn FnMut(u16) + 'a>>,
+    user_on_output_number: Option<Box<dyn FnMut(u32) + 'a>>,
+    user_on_reset: Option<Box<dyn FnMut() + 'a>>,
+    user_on_generic_status: Option<Box<StatusCallback<'a>>>,
+}
+
+/// Driver of a BLE Mesh node, that relays the messages of other nodes and runs models in its
+/// elements. The primary element always has the Configuration Server, so a provisioner can add
+/// application keys, bind them to the models and set their publication and subscriptions.
+///
+/// Elements and models must be added before the node is provisioned. Generic OnOff and Level
+/// servers are bound to a [DigitalOut] and an [AnalogOut], which follow the state set by the
+/// clients of the network. The address, keys, IV index and configuration of the node are kept in
+/// the NVS, so after a restart it rejoins its network when started with the same elements and
+/// models.
+pub struct BleMeshNode<'a> {
+    inner: SharableRef<_BleMeshNode<'a>>,
+}
+
+#[sharable_reference_wrapper]
+impl<'a> _BleMeshNode<'a> {
+    fn new(
+        ble_device: &mut BLEDevice,
+        config: MeshNodeConfig,
+        timer_driver: TimerDriver<'a>,
+        nvs: EspDefaultNvsPartition,
+        notifier: Notifier,
+    ) -> Result<Self, MeshError> {
+        // The bearers advertise with the legacy advertising, not available with extended advertising
+        legacy_advertising(ble_device)?;
+        let now = Instant::now();
+        let pb_gatt = match config.bearer {
+            ProvisioningBearer::Adv => None,
+            ProvisioningBearer::Gatt => {
+                let server = BleServer::new(
+                    config.name.clone(),
+                    ble_device,
+                    &Vec::new(),
+                    notifier.clone(),
+                    notifier.clone(),
+                    notifier.clone(),
+                )?;
+                Some(ProvisioningService::new(
+                    server,
+                    &config.uuid,
+                    config.oob_info,
+                    notifier.clone(),
+                )?)
+            }
+        };
+        Ok(Self {
+            stack: MeshStack::new(NodeState::new(config.product)),
+            provisionee: Provisionee::new(1, config.auth, fill_random),
+            pb_adv: PbAdvLink::new(config.uuid, now),
+            pb_gatt,
+            bearer: MeshBearer::new(notifier),
+            storage: MeshStorage::new(nvs)?,
+            timer_driver,
+            models: Vec::new(),
+            retransmissions: Vec::new(),
+            next_beacon: now,
+            pending_reset: false,
+            user_on_provisioned: None,
+            user_on_output_number: None,
+            user_on_reset: None,
+            user_on_generic_status: None,
+            config,
+        })
+    }
+
+    /// Starts the node, which rejoins the network it was in before a restart, or waits to be
+    /// provisioned
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the node started, or a `MeshError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the stored state could not be read
+    /// - `MeshError::TimerDriver`: If the timer driver could not be enabled
+    /// - `MeshError::Ble`: If the scan or the advertising could not be started
+    pub fn start(&mut self) -> Result<(), MeshError> {
+        self.restore(Instant::now())?;
+        self.timer_driver.interrupt_after_n_times(
+            UPDATE_PERIOD.as_micros() as u64,
+            None,
+            true,
+            || {},
+        );
+        self.timer_driver.enable()?;
+        self.start_bearers()
+    }
+
+    /// Restores the state the node had before a restart, if it was stored with the same elements
+    /// and models, and schedules the periodic publications of its models
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the stored state could not be read
+    fn restore(&mut self, now: Instant) -> Result<(), MeshError> {
+        if self.stack.state.is_provisioned() || !self.storage.restore(&mut self.stack.state)? {
+            return Ok(());
+        }
+        for index in 0..self.models.len() {
+            let (element, id) = (self.models[index].element, self.models[index].id);
+            self.schedule_publication(element, id, now);
+        }
+        self.next_beacon = now;
+        Ok(())
+    }
+
+    /// Advertises the provisioning service while the node waits to be provisioned through
+    /// PB-GATT, or the mesh PDUs otherwise, and starts the scan
+    fn start_bearers(&mut self) -> Result<(), MeshError> {
+        match self.pb_gatt.as_mut() {
+            Some(gatt) if !self.stack.state.is_provisioned() => {
+                self.bearer.set_advertise(false);
+                gatt.start()?;
+            }
+            _ => self.bearer.set_advertise(true),
+        }
+        Ok(self.bearer.start_scan()?)
+    }
+
+    /// Gets whether the node was provisioned into a network
+    pub fn is_provisioned(&self) -> bool {
+        self.stack.state.is_provisioned()
+    }
+
+    /// Gets the unicast address of the primary element, or `None` if the node is not
+    /// provisioned
+    pub fn address(&self) -> Option<u16> {
+        self.is_provisioned().then_some(self.stack.state.address)
+    }
+
+    /// Removes the node from its network, forgetting its keys and the configuration of its
+    /// models. The node waits to be provisioned again.
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::Ble`: If the provisioning service could not be advertised again
+    pub fn reset(&mut self) -> Result<(), MeshError> {
+        self.leave_network()
+    }
+
+    /// Checks that the elements and models of the node can still be changed
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    fn check_unprovisioned(&self) -> Result<(), MeshError> {
+        if self.stack.state.is_provisioned() || self.provisionee.in_progress() {
+            return Err(MeshError::AlreadyProvisioned);
+        }
+        Ok(())
+    }
+
+    /// Adds a secondary element to the node, which has its own unicast address
+    ///
+    /// # Arguments
+    ///
+    /// - `location`: The location of the element, as a GATT Namespace description
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the index of the new element, or a `MeshError` if it fails
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    pub fn add_element(&mut self, location: u16) -> Result<usize, MeshError> {
+        self.check_unprovisioned()?;
+        self.stack.state.elements.push(ElementConfig {
+            location,
+            models: Vec::new(),
+        });
+        let elements = self.stack.state.elements.len();
+        self.provisionee = Provisionee::new(elements as u8, self.config.auth, fill_random);
+        Ok(elements - 1)
+    }
+
+    /// Adds a model to an element
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    fn add_model(
+        &mut self,
+        element: usize,
+        id: ModelId,
+        handler: ModelHandler<'a>,
+    ) -> Result<(), MeshError> {
+        self.check_unprovisioned()?;
+        let models = &mut self
+            .stack
+            .state
+            .elements
+            .get_mut(element)
+            .ok_or(MeshError::InvalidElement(element))?
+            .models;
+        if models.iter().any(|model| model.id == id) {
+            return Err(MeshError::ModelAlreadyAdded);
+        }
+        models.push(ModelConfig::new(id));
+        self.models.push(BoundModel {
+            element,
+            id,
+            handler,
+            next_publication: None,
+        });
+        Ok(())
+    }
+
+    /// Adds a Generic OnOff Server to an element, whose state is the level of a digital output.
+    /// Its initial state is the current level of the output.
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `output`: The DigitalOut set high when the state is on
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    pub fn bind_onoff_server(
+        &mut self,
+        element: usize,
+        output: DigitalOut<'a>,
+    ) -> Result<(), MeshError> {
+        let mut output = output;
+        let on = output.get_level() == Level::High;
+        self.add_model(
+            element,
+            ModelId::GENERIC_ONOFF_SERVER,
+            ModelHandler::OnOffServer {
+                server: OnOffServer::new(on),
+                output,
+            },
+        )
+    }
+
+    /// Adds a Generic Level Server to an element, whose state is the duty of an analog output:
+    /// the lowest level turns it off and the highest one turns it fully on. The initial state is
+    /// the lowest level.
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `output`: The AnalogOut that follows the level
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    /// - `MeshError::AnalogOut`: If the output could not be turned off
+    pub fn bind_level_server(
+        &mut self,
+        element: usize,
+        output: AnalogOut<'a>,
+    ) -> Result<(), MeshError> {
+        let mut output = output;
+        output.set_high_level_output_ratio(level_ratio(i16::MIN))?;
+        self.add_model(
+            element,
+            ModelId::GENERIC_LEVEL_SERVER,
+            ModelHandler::LevelServer {
+                server: LevelServer::new(i16::MIN),
+                output,
+            },
+        )
+    }
+
+    /// Adds a Generic OnOff Client to an element. Its messages are sent to the publication
+    /// address of the model, and the statuses received are given to the handler set with
+    /// [BleMeshNode::on_generic_status].
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    pub fn add_onoff_client(&mut self, element: usize) -> Result<(), MeshError> {
+        self.add_model(
+            element,
+            ModelId::GENERIC_ONOFF_CLIENT,
+            ModelHandler::Client(GenericClient::default()),
+        )
+    }
+
+    /// Adds a Generic Level Client to an element. Its messages are sent to the publication
+    /// address of the model, and the statuses received are given to the handler set with
+    /// [BleMeshNode::on_generic_status].
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    pub fn add_level_client(&mut self, element: usize) -> Result<(), MeshError> {
+        self.add_model(
+            element,
+            ModelId::GENERIC_LEVEL_CLIENT,
+            ModelHandler::Client(GenericClient::default()),
+        )
+    }
+
+    /// Gets the position of a model in the models of the node
+    fn bound_model(&self, element: usize, id: ModelId) -> Option<usize> {
+        self.models
+            .iter()
+            .position(|model| model.element == element && model.id == id)
+    }
+
+    /// Gets the configuration of a model to modify it
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    fn model_config(&mut self, element: usize, id: ModelId) -> Result<&mut ModelConfig, MeshError> {
+        self.stack
+            .state
+            .model_mut(element, id)
+            .ok_or(MeshError::ModelNotFound)
+    }
+
+    /// Sets where a model publishes its messages. This is usually done by the configuration
+    /// client of the network.
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `model`: The id of the model
+    /// - `publication`: The [Publication] of the model
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    pub fn set_publication(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        publication: Publication,
+    ) -> Result<(), MeshError> {
+        self.model_config(element, model)?.publication = Some(publication);
+        self.schedule_publication(element, model, Instant::now());
+        Ok(())
+    }
+
+    /// Subscribes a model to a group address, so it receives the messages sent to it. This is
+    /// usually done by the configuration client of the network.
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    pub fn add_subscription(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        address: u16,
+    ) -> Result<(), MeshError> {
+        let subscriptions = &mut self.model_config(element, model)?.subscriptions;
+        if !subscriptions.contains(&address) {
+            subscriptions.push(address);
+        }
+        Ok(())
+    }
+
+    /// Removes the subscription of a model to a group address
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    pub fn remove_subscription(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        address: u16,
+    ) -> Result<(), MeshError> {
+        self.model_config(element, model)?
+            .subscriptions
+            .retain(|subscription| *subscription != address);
+        Ok(())
+    }
+
+    /// Binds an application key to a model, so it receives the messages encrypted with it.
+    /// This is usually done by the configuration client of the network.
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    pub fn bind_app_key(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        app_key_index: u16,
+    ) -> Result<(), MeshError> {
+        let app_keys = &mut self.model_config(element, model)?.app_keys;
+        if !app_keys.contains(&app_key_index) {
+            app_keys.push(app_key_index);
+        }
+        Ok(())
+    }
+
+    /// Publishes a message of a model to its publication address, sending it again as many
+    /// times as the publication says
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `model`: The id of the model
+    /// - `message`: The message
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    /// - `MeshError::NoPublication`: If the model does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    /// - `MeshError::InvalidKeyIndex`: If the application key of the publication does not exist
+    /// - `MeshError::MessageTooLong`: If the message is too long
+    pub fn publish(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        message: &AccessMessage,
+    ) -> Result<(), MeshError> {
+        let now = Instant::now();
+        let publication = self
+            .stack
+            .state
+            .model(element, model)
+            .ok_or(MeshError::ModelNotFound)?
+            .publication
+            .ok_or(MeshError::NoPublication)?;
+        self.send_publication(element, &publication, message, now)?;
+        if publication.retransmit_count() > 0 {
+            self.retransmissions.push(Retransmission {
+                element,
+                id: model,
+                message: message.clone(),
+                remaining: publication.retransmit_count(),
+                next_attempt: now + publication.retransmit_interval(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Sends a message of a model to an address, encrypted with the first application key bound
+    /// to the model
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `model`: The id of the model
+    /// - `dst`: The unicast or group address the message is sent to
+    /// - `message`: The message
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the model
+    /// - `MeshError::ModelNotBound`: If no application key is bound to the model
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    /// - `MeshError::MessageTooLong`: If the message is too long
+    pub fn send(
+        &mut self,
+        element: usize,
+        model: ModelId,
+        dst: u16,
+        message: &AccessMessage,
+    ) -> Result<(), MeshError> {
+        let app_key_index = *self
+            .stack
+            .state
+            .model(element, model)
+            .ok_or(MeshError::ModelNotFound)?
+            .app_keys
+            .first()
+            .ok_or(MeshError::ModelNotBound)?;
+        let now = Instant::now();
+        let outputs = self.stack.send(
+            element,
+            dst,
+            AccessKey::Application(app_key_index),
+            None,
+            message,
+            now,
+        )?;
+        self.handle_outputs(outputs, now)
+    }
+
+    /// Builds a message with a generic client of an element and publishes it
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have the client
+    /// - The errors of [Self::publish]
+    fn client_message<F: FnOnce(&mut GenericClient) -> AccessMessage>(
+        &mut self,
+        element: usize,
+        id: ModelId,
+        build: F,
+    ) -> Result<(), MeshError> {
+        let index = self
+            .bound_model(element, id)
+            .ok_or(MeshError::ModelNotFound)?;
+        let ModelHandler::Client(client) = &mut self.models[index].handler else {
+            return Err(MeshError::ModelNotFound);
+        };
+        let message = build(client);
+        self.publish(element, id, &message)
+    }
+
+    /// Asks the servers the Generic OnOff Client of an element publishes to for their state
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have a Generic OnOff Client
+    /// - `MeshError::NoPublication`: If the client does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    pub fn onoff_get(&mut self, element: usize) -> Result<(), MeshError> {
+        self.client_message(element, ModelId::GENERIC_ONOFF_CLIENT, |client| {
+            client.onoff_get()
+        })
+    }
+
+    /// Turns on or off the servers the Generic OnOff Client of an element publishes to
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `on`: The new state
+    /// - `acknowledged`: Whether the servers answer with their status
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have a Generic OnOff Client
+    /// - `MeshError::NoPublication`: If the client does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    pub fn onoff_set(
+        &mut self,
+        element: usize,
+        on: bool,
+        acknowledged: bool,
+    ) -> Result<(), MeshError> {
+        self.client_message(element, ModelId::GENERIC_ONOFF_CLIENT, |client| {
+            client.onoff_set(on, acknowledged)
+        })
+    }
+
+    /// Asks the servers the Generic Level Client of an element publishes to for their level
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have a Generic Level Client
+    /// - `MeshError::NoPublication`: If the client does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    pub fn level_get(&mut self, element: usize) -> Result<(), MeshError> {
+        self.client_message(element, ModelId::GENERIC_LEVEL_CLIENT, |client| {
+            client.level_get()
+        })
+    }
+
+    /// Sets the level of the servers the Generic Level Client of an element publishes to
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `level`: The new level
+    /// - `acknowledged`: Whether the servers answer with their status
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have a Generic Level Client
+    /// - `MeshError::NoPublication`: If the client does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    pub fn level_set(
+        &mut self,
+        element: usize,
+        level: i16,
+        acknowledged: bool,
+    ) -> Result<(), MeshError> {
+        self.client_message(element, ModelId::GENERIC_LEVEL_CLIENT, |client| {
+            client.level_set(level, acknowledged)
+        })
+    }
+
+    /// Changes the level of the servers the Generic Level Client of an element publishes to
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `delta`: The amount added to the level of the servers
+    /// - `acknowledged`: Whether the servers answer with their status
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::ModelNotFound`: If the element does not have a Generic Level Client
+    /// - `MeshError::NoPublication`: If the client does not publish
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    pub fn delta_set(
+        &mut self,
+        element: usize,
+        delta: i32,
+        acknowledged: bool,
+    ) -> Result<(), MeshError> {
+        self.client_message(element, ModelId::GENERIC_LEVEL_CLIENT, |client| {
+            client.delta_set(delta, acknowledged)
+        })
+    }
+
+    /// Schedules the next periodic publication of a model, after a change of its publication
+    fn schedule_publication(&mut self, element: usize, id: ModelId, now: Instant) {
+        let period = self
+            .stack
+            .state
+            .model(element, id)
+            .and_then(|model| model.publication)
+            .and_then(|publication| publication.period_duration());
+        if let Some(index) = self.bound_model(element, id) {
+            self.models[index].next_publication = period.map(|period| now + period);
+        }
+    }
+
+    /// Sends a published message once
+    fn send_publication(
+        &mut self,
+        element: usize,
+        publication: &Publication,
+        message: &AccessMessage,
+        now: Instant,
+    ) -> Result<(), MeshError> {
+        let outputs = self.stack.send(
+            element,
+            publication.address,
+            AccessKey::Application(publication.app_key_index),
+            Some(publication.ttl),
+            message,
+            now,
+        )?;
+        self.handle_outputs(outputs, now)
+    }
+
+    /// Publishes the status of a server, if it publishes
+    fn publish_status(&mut self, index: usize) -> Result<(), MeshError> {
+        let model = &self.models[index];
+        let (element, id) = (model.element, model.id);
+        let Some(status) = model.handler.status() else {
+            return Ok(());
+        };
+        match self.publish(element, id, &status) {
+            Err(MeshError::NoPublication) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Handles the outputs of the stack
+    fn handle_outputs(&mut self, outputs: Vec<StackOutput>, now: Instant) -> Result<(), MeshError> {
+        for output in outputs {
+            match output {
+                StackOutput::Transmit(pdu) => self.bearer.send(MESH_MESSAGE_AD_TYPE, pdu),
+                StackOutput::Access(incoming) => self.handle_access(incoming, now)?,
+                StackOutput::PublicationChanged { element, model } => {
+                    self.schedule_publication(element, model, now)
+                }
+                // The node leaves once the Node Reset Status is advertised
+                StackOutput::Reset => self.pending_reset = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives an access message to the models that receive it. Servers apply the new state to
+    /// their output, answer the acknowledged messages and publish their changes.
+    fn handle_access(&mut self, incoming: IncomingAccess, now: Instant) -> Result<(), MeshError> {
+        let IncomingAccess {
+            context,
+            message,
+            models,
+        } = incoming;
+        for (element, id) in models {
+            let Some(index) = self.bound_model(element, id) else {
+                continue;
+            };
+            let result = match &mut self.models[index].handler {
+                ModelHandler::OnOffServer { server, output } => {
+                    let result = server.handle(&context, &message, now);
+                    match result.as_ref().filter(|result| result.changed) {
+                        Some(_) if server.on => output.set_high()?,
+                        Some(_) => output.set_low()?,
+                        None => {}
+                    }
+                    result
+                }
+                ModelHandler::LevelServer { server, output } => {
+                    let result = server.handle(&context, &message, now);
+                    if result.as_ref().is_some_and(|result| result.changed) {
+                        output.set_high_level_output_ratio(level_ratio(server.level))?;
+                    }
+                    result
+                }
+                ModelHandler::Client(_) => {
+                    self.generic_status(id, &context, &message);
+                    continue;
+                }
+                ModelHandler::Vendor(handler) => {
+                    handler(&context, &message).map(|answer| ServerResult {
+                        answer: Some(answer),
+                        changed: false,
+                    })
+                }
+            };
+            let Some(ServerResult { answer, changed }) = result else {
+                continue;
+            };
+            if let (Some(answer), Some(app_key_index)) = (answer, context.app_key_index) {
+                let outputs = self.stack.send(
+                    element,
+                    context.src,
+                    AccessKey::Application(app_key_index),
+                    None,
+                    &answer,
+                    now,
+                )?;
+                self.handle_outputs(outputs, now)?;
+            }
+            if changed {
+                self.publish_status(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives a status received by a generic client to the user handler
+    fn generic_status(&mut self, id: ModelId, context: &MessageContext, message: &AccessMessage) {
+        let Some(status) = GenericStatus::decode(message) else {
+            return;
+        };
+        let client = match status {
+            GenericStatus::OnOff(_) => ModelId::GENERIC_ONOFF_CLIENT,
+            GenericStatus::Level(_) => ModelId::GENERIC_LEVEL_CLIENT,
+        };
+        if id != client {
+            return;
+        }
+        if let Some(callback) = self.user_on_generic_status.as_mut() {
+            callback(context, status);
+        }
+    }
+
+    /// Runs the provisioning: handles the PB-ADV payloads and the PDUs written through PB-GATT
+    fn update_provisioning(
+        &mut self,
+        received: &[(u8, Vec<u8>)],
+        now: Instant,
+    ) -> Result<(), MeshError> {
+        let mut pdus = Vec::new();
+        if let Some(gatt) = self.pb_gatt.as_mut() {
+            if gatt.take_disconnection() {
+                self.provisionee.reset();
+            }
+            pdus = gatt.received();
+        }
+        let mut events = Vec::new();
+        for (_, payload) in received
+            .iter()
+            .filter(|(ad_type, _)| *ad_type == PB_ADV_AD_TYPE)
+        {
+            events.extend(self.pb_adv.receive(payload, now));
+        }
+        events.extend(self.pb_adv.poll(now));
+        for event in events {
+            match event {
+                LinkEvent::Send(payload) => self.bearer.send(PB_ADV_AD_TYPE, payload),
+                LinkEvent::Received(pdu) => pdus.push(pdu),
+                LinkEvent::Opened | LinkEvent::Closed => self.provisionee.reset(),
+            }
+        }
+        // The link stays open after the provisioning until the provisioner closes it
+        if self.stack.state.is_provisioned() {
+            return Ok(());
+        }
+        for pdu in pdus {
+            let steps = self.provisionee.handle(&pdu);
+            self.provisioning_steps(steps, now)?;
+        }
+        Ok(())
+    }
+
+    /// Takes the steps of the provisioning procedure
+    fn provisioning_steps(
+        &mut self,
+        steps: Vec<ProvisioningStep>,
+        now: Instant,
+    ) -> Result<(), MeshError> {
+        for step in steps {
+            match step {
+                ProvisioningStep::Send(pdu) => self.send_provisioning_pdu(&pdu, now)?,
+                ProvisioningStep::ShowNumber(number) => {
+                    if let Some(callback) = self.user_on_output_number.as_mut() {
+                        callback(number);
+                    }
+                }
+                ProvisioningStep::Complete(data) => self.join_network(data, now)?,
+                ProvisioningStep::Failed(_) => {
+                    for event in self.pb_adv.close(now) {
+                        if let LinkEvent::Send(payload) = event {
+                            self.bearer.send(PB_ADV_AD_TYPE, payload);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a provisioning PDU through the bearer the provisioner uses
+    fn send_provisioning_pdu(&mut self, pdu: &[u8], now: Instant) -> Result<(), MeshError> {
+        if self.pb_adv.is_open() {
+            for event in self.pb_adv.send(pdu, now) {
+                if let LinkEvent::Send(payload) = event {
+                    self.bearer.send(PB_ADV_AD_TYPE, payload);
+                }
+            }
+        } else if let Some(gatt) = self.pb_gatt.as_mut() {
+            gatt.send(pdu)?;
+        }
+        Ok(())
+    }
+
+    /// Joins the network given by the provisioner
+    fn join_network(&mut self, data: ProvisioningData, now: Instant) -> Result<(), MeshError> {
+        self.stack.state.provision(
+            NetworkKey::new(data.net_key_index, data.net_key),
+            data.iv_index,
+            data.iv_update,
+            data.address,
+            data.device_key,
+        );
+        if let Some(gatt) = self.pb_gatt.as_mut() {
+            gatt.stop()?;
+            self.bearer.set_advertise(true);
+        }
+        self.next_beacon = now;
+        if let Some(callback) = self.user_on_provisioned.as_mut() {
+            callback(data.address);
+        }
+        Ok(())
+    }
+
+    /// Leaves the network, waiting to be provisioned again
+    fn leave_network(&mut self) -> Result<(), MeshError> {
+        self.pending_reset = false;
+        self.stack.state.reset();
+        self.stack.clear();
+        self.provisionee.reset();
+        self.pb_adv = PbAdvLink::new(self.config.uuid, Instant::now());
+        self.retransmissions.clear();
+        for model in &mut self.models {
+            model.next_publication = None;
+        }
+        self.start_bearers()?;
+        if let Some(callback) = self.user_on_reset.as_mut() {
+            callback();
+        }
+        Ok(())
+    }
+
+    /// Advertises the unprovisioned device beacon periodically while no provisioner opened a
+    /// PB-ADV link
+    fn send_unprovisioned_beacon(&mut self, now: Instant) {
+        if self.pb_gatt.is_some() || self.pb_adv.is_open() || now < self.next_beacon {
+            return;
+        }
+        let beacon = unprovisioned_device_beacon(&self.config.uuid, self.config.oob_info);
+        self.bearer.send(MESH_BEACON_AD_TYPE, beacon);
+        self.next_beacon = now + UNPROVISIONED_BEACON_INTERVAL;
+    }
+
+    /// Handles the mesh PDUs received, retransmits the segments and publications, and
+    /// advertises the secure network beacon
+    fn update_network(
+        &mut self,
+        received: Vec<(u8, Vec<u8>)>,
+        now: Instant,
+    ) -> Result<(), MeshError> {
+        for (ad_type, payload) in received {
+            match ad_type {
+                // PDUs of other networks and malformed PDUs are ignored
+                MESH_MESSAGE_AD_TYPE => {
+                    if let Ok(outputs) = self.stack.receive(&payload, now) {
+                        self.handle_outputs(outputs, now)?;
+                    }
+                }
+                MESH_BEACON_AD_TYPE => {
+                    self.stack.receive_beacon(&payload);
+                }
+                _ => {}
+            }
+        }
+        let outputs = self.stack.poll(now)?;
+        self.handle_outputs(outputs, now)?;
+        if now >= self.next_beacon {
+            if let Some(beacon) = self.stack.secure_beacon() {
+                self.bearer.send(MESH_BEACON_AD_TYPE, beacon);
+            }
+            self.next_beacon = now + SECURE_BEACON_INTERVAL;
+        }
+        self.update_publications(now)
+    }
+
+    /// Publishes the state of the servers whose period elapsed, and sends again the published
+    /// messages that must be retransmitted
+    fn update_publications(&mut self, now: Instant) -> Result<(), MeshError> {
+        for index in 0..self.models.len() {
+            let model = &self.models[index];
+            if model.next_publication.is_some_and(|next| next <= now) {
+                let (element, id) = (model.element, model.id);
+                self.schedule_publication(element, id, now);
+                self.publish_status(index)?;
+            }
+        }
+        let (due, waiting): (Vec<Retransmission>, Vec<Retransmission>) =
+            std::mem::take(&mut self.retransmissions)
+                .into_iter()
+                .partition(|retransmission| retransmission.next_attempt <= now);
+        self.retransmissions = waiting;
+        for mut retransmission in due {
+            let Some(publication) = self
+                .stack
+                .state
+                .model(retransmission.element, retransmission.id)
+                .and_then(|model| model.publication)
+            else {
+                continue;
+            };
+            self.send_publication(
+                retransmission.element,
+                &publication,
+                &retransmission.message,
+                now,
+            )?;
+            retransmission.remaining -= 1;
+            if retransmission.remaining > 0 {
+                retransmission.next_attempt = now + publication.retransmit_interval();
+                self.retransmissions.push(retransmission);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles everything received since the last update, and advertises what the node must
+    /// send
+    fn _update_interrupt(&mut self) -> Result<(), Esp32FrameworkError> {
+        if let Some(gatt) = self.pb_gatt.as_mut() {
+            gatt.server().update_interrupt()?;
+        }
+        let now = Instant::now();
+        let received = self.bearer.received();
+        if !self.stack.state.is_provisioned() || self.pb_adv.is_open() {
+            self.update_provisioning(&received, now)?;
+        }
+        match self.stack.state.is_provisioned() {
+            true => self.update_network(received, now)?,
+            false => self.send_unprovisioned_beacon(now),
+        }
+        if self.pending_reset && !self.stack.is_busy() && !self.bearer.is_sending() {
+            self.leave_network()?;
+        }
+        self.storage.save(&self.stack.state)?;
+        self.bearer.update()?;
+        Ok(())
+    }
+}
+
+impl<'a> BleMeshNode<'a> {
+    /// Creates a new BleMeshNode, not provisioned, whose primary element only has the
+    /// Configuration Server
+    ///
+    /// # Arguments
+    ///
+    /// - `ble_device`: The BLEDevice, used by the Mesh Provisioning Service
+    /// - `config`: The [MeshNodeConfig] of the node
+    /// - `timer_driver`: A TimerDriver that wakes up the node periodically
+    /// - `nvs`: The default NVS partition, where the state of the node is kept
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] after a PDU is
+    ///   received
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::Ble`: If the Mesh Provisioning Service could not be set, or with
+    ///   `BleError::LegacyAdvertisingUnavailable` if the extended advertising is enabled
+    /// - `MeshError::StorageError`: If the NVS namespace of the node could not be opened
+    pub(crate) fn new(
+        ble_device: &mut BLEDevice,
+        config: MeshNodeConfig,
+        timer_driver: TimerDriver<'a>,
+        nvs: EspDefaultNvsPartition,
+        notifier: Notifier,
+    ) -> Result<Self, MeshError> {
+        Ok(Self {
+            inner: SharableRef::new_sharable(_BleMeshNode::new(
+                ble_device,
+                config,
+                timer_driver,
+                nvs,
+                notifier,
+            )?),
+        })
+    }
+
+    /// Adds a vendor model to an element. Its messages are given to the handler, which
+    /// returns the message answered to the sender, if any.
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element
+    /// - `company_id`: The company identifier assigned by the Bluetooth SIG
+    /// - `model_id`: The id of the model assigned by the company
+    /// - `handler`: A closure that receives the [MessageContext] and the [AccessMessage]
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::AlreadyProvisioned`: If the node is being provisioned or was provisioned
+    /// - `MeshError::InvalidElement`: If the element does not exist
+    /// - `MeshError::ModelAlreadyAdded`: If the element already has the model
+    pub fn add_vendor_model<
+        C: FnMut(&MessageContext, &AccessMessage) -> Option<AccessMessage> + 'a,
+    >(
+        &mut self,
+        element: usize,
+        company_id: u16,
+        model_id: u16,
+        handler: C,
+    ) -> Result<(), MeshError> {
+        self.inner.deref_mut().add_model(
+            element,
+            ModelId::Vendor {
+                company_id,
+                model_id,
+            },
+            ModelHandler::Vendor(Box::new(handler)),
+        )
+    }
+
+    /// Sets a handler executed when the node joins a network, which receives the address of
+    /// the primary element
+    ///
+    /// # Returns
+    ///
+    /// The BleMeshNode itself
+    pub fn on_provisioned<C: FnMut(u16) + 'a>(&mut self, handler: C) -> &mut Self {
+        self.inner.deref_mut().user_on_provisioned = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets a handler that shows the number of the output OOB authentication to the user, who
+    /// enters it in the provisioner
+    ///
+    /// # Returns
+    ///
+    /// The BleMeshNode itself
+    pub fn on_output_number<C: FnMut(u32) + 'a>(&mut self, handler: C) -> &mut Self {
+        self.inner.deref_mut().user_on_output_number = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets a handler executed when the node leaves its network, because the configuration
+    /// client removed it or [Self::reset] was called
+    ///
+    /// # Returns
+    ///
+    /// The BleMeshNode itself
+    pub fn on_reset<C: FnMut() + 'a>(&mut self, handler: C) -> &mut Self {
+        self.inner.deref_mut().user_on_reset = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets a handler for the statuses received by the Generic OnOff and Level clients of the
+    /// node
+    ///
+    /// # Returns
+    ///
+    /// The BleMeshNode itself
+    pub fn on_generic_status<C: FnMut(&MessageContext, GenericStatus) + 'a>(
+        &mut self,
+        handler: C,
+    ) -> &mut Self {
+        self.inner.deref_mut().user_on_generic_status = Some(Box::new(handler));
+        self
+    }
+}
+
+impl<'a> InterruptDriver<'a> for BleMeshNode<'a> {
+    fn update_interrupt(&mut self) -> Result<(), Esp32FrameworkError> {
+        self.inner.deref_mut()._update_interrupt()
+    }
+
+    fn get_updater(&self) -> Box<dyn InterruptDriver<'a> + 'a> {
+        Box::new(Self {
+            inner: self.inner.clone(),
+        })
+    }
+}
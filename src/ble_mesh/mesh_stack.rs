@@ -0,0 +1,814 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::{
+    access::{AccessMessage, MessageContext},
+    config_server::{handle_config_message, ConfigChange},
+    mesh_crypto::{ccm_decrypt, ccm_encrypt, MeshKey, MicSize},
+    network::{NetworkCache, NetworkKey, NetworkPdu},
+    node_state::{is_group, is_unicast, ModelId, NodeState, ALL_NODES_ADDRESS, ALL_RELAYS_ADDRESS},
+    transport::{
+        access_nonce, segment_access, AccessKeyType, LowerTransportPdu, OutgoingSegments,
+        ReassembledPdu, Reassembler, SegmentAck, SegmentResult, SegmentedKind,
+        MAX_ACCESS_PAYLOAD_SIZE, MAX_UNSEGMENTED_ACCESS_SIZE, SEGMENT_ACK_OPCODE,
+    },
+    MeshError,
+};
+
+const NETWORK_CACHE_CAPACITY: usize = 64;
+/// Amount of times the unacknowledged segments of a message are sent again
+const SEGMENT_RETRANSMISSIONS: u8 = 3;
+/// Base time between retransmissions of segments, increased by 50 ms per hop of TTL
+const SEGMENT_RETRANSMIT_BASE: Duration = Duration::from_millis(200);
+/// Biggest jump of the IV index accepted from a secure network beacon
+const MAX_IV_INDEX_RECOVERY: u32 = 42;
+
+/// The key an access message is encrypted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccessKey {
+    Application(u16),
+    Device,
+}
+
+/// An access message received for the models of the node
+/// - `context`: How the message was received
+/// - `message`: The message
+/// - `models`: The models that can receive it: those of the element it was sent to, or
+///   subscribed to its group, that are bound to its application key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IncomingAccess {
+    pub context: MessageContext,
+    pub message: AccessMessage,
+    pub models: Vec<(usize, ModelId)>,
+}
+
+/// Result of the processing of the stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StackOutput {
+    /// A network PDU to send through the bearer
+    Transmit(Vec<u8>),
+    /// An access message for the models of the node
+    Access(IncomingAccess),
+    /// The configuration client changed the publication of a model
+    PublicationChanged { element: usize, model: ModelId },
+    /// The configuration client removed the node from the network
+    Reset,
+}
+
+/// A segmented message being sent, with what is needed to encrypt its retransmissions
+struct PendingSegments {
+    segments: OutgoingSegments,
+    src: u16,
+    ttl: u8,
+    net_key_index: u16,
+}
+
+/// The network, transport and access layers of a provisioned node
+/// - `state`: The state of the node
+/// - `cache`: The network PDUs already received
+/// - `replay`: The IV index and last sequence number accepted of every source
+/// - `reassembler`: The segmented messages being received
+/// - `pending`: The segmented messages being sent
+pub(crate) struct MeshStack {
+    pub state: NodeState,
+    cache: NetworkCache,
+    replay: HashMap<u16, (u32, u32)>,
+    reassembler: Reassembler,
+    pending: Vec<PendingSegments>,
+}
+
+impl MeshStack {
+    pub(crate) fn new(state: NodeState) -> Self {
+        Self {
+            state,
+            cache: NetworkCache::new(NETWORK_CACHE_CAPACITY),
+            replay: HashMap::new(),
+            reassembler: Reassembler::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Forgets every message received and being sent, as done when the node leaves the network
+    pub(crate) fn clear(&mut self) {
+        self.cache.clear();
+        self.replay.clear();
+        self.reassembler = Reassembler::new();
+        self.pending.clear();
+    }
+
+    /// Handles a network PDU received from the bearer
+    ///
+    /// # Arguments
+    ///
+    /// - `data`: The network PDU
+    /// - `now`: When it was received
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the outputs of the PDU in order, or a `MeshError` if it could not be
+    /// processed
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidPdu`: If the PDU is not of a network of the node, or it is
+    ///   malformed
+    /// - `MeshError::SequenceExhausted`: If an answer could not be sent
+    pub(crate) fn receive(
+        &mut self,
+        data: &[u8],
+        now: Instant,
+    ) -> Result<Vec<StackOutput>, MeshError> {
+        let iv_index = self.state.iv_index;
+        let Some((net_key_index, pdu)) = self
+            .state
+            .net_keys
+            .iter()
+            .find_map(|key| key.decrypt(iv_index, data).ok().map(|pdu| (key.index, pdu)))
+        else {
+            return Err(MeshError::InvalidPdu);
+        };
+        if self.state.element_index(pdu.src).is_some() || !self.cache.insert(&pdu) {
+            return Ok(Vec::new());
+        }
+
+        let mut outputs = Vec::new();
+        let for_element = self.state.element_index(pdu.dst).is_some();
+        if self.state.relay && pdu.ttl >= 2 && !for_element {
+            let relayed = NetworkPdu {
+                ttl: pdu.ttl - 1,
+                ..pdu.clone()
+            };
+            outputs.push(StackOutput::Transmit(
+                self.encrypt(net_key_index, &relayed)?,
+            ));
+        }
+        if !self.is_for_node(pdu.dst) {
+            return Ok(outputs);
+        }
+        let lower = LowerTransportPdu::decode(pdu.ctl, &pdu.transport_pdu)?;
+        match lower {
+            LowerTransportPdu::UnsegmentedAccess {
+                akf,
+                aid,
+                upper_pdu,
+            } => {
+                if !self.accept_seq(pdu.src, pdu.seq) {
+                    return Ok(outputs);
+                }
+                let reassembled = ReassembledPdu {
+                    src: pdu.src,
+                    dst: pdu.dst,
+                    seq: pdu.seq,
+                    kind: SegmentedKind::Access {
+                        akf,
+                        aid,
+                        szmic: false,
+                    },
+                    data: upper_pdu,
+                };
+                outputs.extend(self.upper_access(net_key_index, &reassembled, pdu.ttl)?);
+            }
+            LowerTransportPdu::UnsegmentedControl { opcode, params } => {
+                if opcode == SEGMENT_ACK_OPCODE && self.accept_seq(pdu.src, pdu.seq) {
+                    if let Some(ack) = SegmentAck::decode(&params) {
+                        self.acknowledge(pdu.src, &ack);
+                    }
+                }
+            }
+            segment => {
+                let result = self
+                    .reassembler
+                    .push(pdu.src, pdu.dst, pdu.seq, &segment, now)?;
+                let (complete, ack) = match result {
+                    SegmentResult::Incomplete(ack) | SegmentResult::AlreadyComplete(ack) => {
+                        (None, ack)
+                    }
+                    SegmentResult::Complete(complete, ack) => (Some(complete), ack),
+                };
+                if is_unicast(pdu.dst) {
+                    let ack = self.control_pdu(net_key_index, pdu.dst, pdu.src, ack.to_pdu())?;
+                    outputs.push(StackOutput::Transmit(ack));
+                }
+                match complete {
+                    Some(complete) if self.accept_seq(complete.src, complete.seq) => {
+                        if let SegmentedKind::Access { .. } = complete.kind {
+                            outputs.extend(self.upper_access(net_key_index, &complete, pdu.ttl)?);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Gets whether a message sent to an address must be handled by the node
+    fn is_for_node(&self, dst: u16) -> bool {
+        self.state.element_index(dst).is_some()
+            || dst == ALL_NODES_ADDRESS
+            || (dst == ALL_RELAYS_ADDRESS && self.state.relay)
+            || self.state.elements.iter().any(|element| {
+                element
+                    .models
+                    .iter()
+                    .any(|model| model.subscriptions.contains(&dst))
+            })
+    }
+
+    /// Checks the replay protection of a message
+    ///
+    /// # Returns
+    ///
+    /// Whether the message is newer than the last one accepted of its source, and is accepted
+    fn accept_seq(&mut self, src: u16, seq: u32) -> bool {
+        let iv_index = self.state.iv_index;
+        match self.replay.get(&src) {
+            Some((last_iv, last_seq)) if *last_iv == iv_index && *last_seq >= seq => false,
+            None if self.replay.len() >= self.state.product.replay_capacity as usize => false,
+            _ => {
+                self.replay.insert(src, (iv_index, seq));
+                true
+            }
+        }
+    }
+
+    /// Decrypts an upper transport access PDU and finds who handles it
+    fn upper_access(
+        &mut self,
+        net_key_index: u16,
+        pdu: &ReassembledPdu,
+        ttl: u8,
+    ) -> Result<Vec<StackOutput>, MeshError> {
+        let SegmentedKind::Access { akf, aid, szmic } = pdu.kind else {
+            return Ok(Vec::new());
+        };
+        let mic = if szmic { MicSize::Long } else { MicSize::Short };
+        let (key_type, candidates): (AccessKeyType, Vec<(Option<u16>, MeshKey)>) = match akf {
+            false => (
+                AccessKeyType::Device,
+                self.state
+                    .device_key
+                    .map(|key| (None, key))
+                    .into_iter()
+                    .collect(),
+            ),
+            true => (
+                AccessKeyType::Application,
+                self.state
+                    .app_keys
+                    .iter()
+                    .filter(|key| key.aid == aid && key.net_key_index == net_key_index)
+                    .map(|key| (Some(key.index), key.key))
+                    .collect(),
+            ),
+        };
+        let nonce = access_nonce(
+            key_type,
+            szmic,
+            pdu.seq,
+            pdu.src,
+            pdu.dst,
+            self.state.iv_index,
+        );
+        let Some((app_key_index, payload)) = candidates.into_iter().find_map(|(index, key)| {
+            ccm_decrypt(&key, &nonce, &pdu.data, &[], mic)
+                .ok()
+                .map(|payload| (index, payload))
+        }) else {
+            return Ok(Vec::new());
+        };
+        let message = AccessMessage::decode(&payload)?;
+        let context = MessageContext {
+            src: pdu.src,
+            dst: pdu.dst,
+            app_key_index,
+            ttl,
+        };
+
+        let Some(app_key_index) = app_key_index else {
+            return self.config_message(&context, &message);
+        };
+        let models: Vec<(usize, ModelId)> = self
+            .state
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                pdu.dst == self.state.element_address(*index)
+                    || (pdu.dst == ALL_NODES_ADDRESS && *index == 0)
+                    || is_group(pdu.dst)
+            })
+            .flat_map(|(index, element)| {
+                element
+                    .models
+                    .iter()
+                    .filter(move |model| {
+                        model.app_keys.contains(&app_key_index)
+                            && (!is_group(pdu.dst)
+                                || (pdu.dst == ALL_NODES_ADDRESS && index == 0)
+                                || model.subscriptions.contains(&pdu.dst))
+                    })
+                    .map(move |model| (index, model.id))
+            })
+            .collect();
+        if models.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![StackOutput::Access(IncomingAccess {
+            context,
+            message,
+            models,
+        })])
+    }
+
+    /// Handles a message sent to the configuration server of the primary element
+    fn config_message(
+        &mut self,
+        context: &MessageContext,
+        message: &AccessMessage,
+    ) -> Result<Vec<StackOutput>, MeshError> {
+        if context.dst != self.state.address {
+            return Ok(Vec::new());
+        }
+        let Some((answer, change)) = handle_config_message(&mut self.state, message) else {
+            return Ok(Vec::new());
+        };
+        let mut outputs = self.send(
+            0,
+            context.src,
+            AccessKey::Device,
+            None,
+            &answer,
+            Instant::now(),
+        )?;
+        match change {
+            ConfigChange::None => {}
+            ConfigChange::Publication { element, model } => {
+                outputs.push(StackOutput::PublicationChanged { element, model })
+            }
+            ConfigChange::Reset => outputs.push(StackOutput::Reset),
+        }
+        Ok(outputs)
+    }
+
+    /// Marks the segments acknowledged by a receiver, forgetting the messages completed
+    fn acknowledge(&mut self, src: u16, ack: &SegmentAck) {
+        self.pending.retain_mut(|pending| {
+            pending.segments.dst != src || !pending.segments.acknowledge(ack)
+        });
+    }
+
+    /// Encrypts a network PDU with a network key of the node
+    fn encrypt(&self, net_key_index: u16, pdu: &NetworkPdu) -> Result<Vec<u8>, MeshError> {
+        let key = self.net_key(net_key_index)?;
+        Ok(key.encrypt(self.state.iv_index, pdu))
+    }
+
+    fn net_key(&self, net_key_index: u16) -> Result<&NetworkKey, MeshError> {
+        self.state
+            .net_keys
+            .iter()
+            .find(|key| key.index == net_key_index)
+            .ok_or(MeshError::InvalidKeyIndex(net_key_index))
+    }
+
+    /// Creates the network PDU of a control message
+    fn control_pdu(
+        &mut self,
+        net_key_index: u16,
+        src: u16,
+        dst: u16,
+        lower: LowerTransportPdu,
+    ) -> Result<Vec<u8>, MeshError> {
+        let pdu = NetworkPdu {
+            ctl: true,
+            ttl: self.state.default_ttl,
+            seq: self.state.next_seq()?,
+            src,
+            dst,
+            transport_pdu: lower.encode(),
+        };
+        self.encrypt(net_key_index, &pdu)
+    }
+
+    /// Sends an access message from an element
+    ///
+    /// # Arguments
+    ///
+    /// - `element`: The index of the element that sends the message
+    /// - `dst`: The address the message is sent to
+    /// - `key`: The key the message is encrypted with
+    /// - `ttl`: The TTL of the message, or `None` to use the default TTL
+    /// - `message`: The message
+    /// - `now`: When the message is sent
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the network PDUs to transmit, or a `MeshError` if the message can not be
+    /// sent
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::NotProvisioned`: If the node is not in a network
+    /// - `MeshError::InvalidKeyIndex`: If the application key does not exist
+    /// - `MeshError::MessageTooLong`: If the message does not fit in 32 segments
+    /// - `MeshError::SequenceExhausted`: If every sequence number of the IV index was used
+    pub(crate) fn send(
+        &mut self,
+        element: usize,
+        dst: u16,
+        key: AccessKey,
+        ttl: Option<u8>,
+        message: &AccessMessage,
+        now: Instant,
+    ) -> Result<Vec<StackOutput>, MeshError> {
+        if !self.state.is_provisioned() {
+            return Err(MeshError::NotProvisioned);
+        }
+        let payload = message.encode();
+        if payload.len() > MAX_ACCESS_PAYLOAD_SIZE {
+            return Err(MeshError::MessageTooLong(payload.len()));
+        }
+        let (key_type, access_key, aid, net_key_index) = match key {
+            AccessKey::Device => (
+                AccessKeyType::Device,
+                self.state.device_key.ok_or(MeshError::NotProvisioned)?,
+                0,
+                self.net_key_index_of_device()?,
+            ),
+            AccessKey::Application(index) => {
+                let app_key = self
+                    .state
+                    .app_key(index)
+                    .ok_or(MeshError::InvalidKeyIndex(index))?;
+                let net_key = self
+                    .state
+                    .net_key_of(app_key)
+                    .ok_or(MeshError::InvalidKeyIndex(app_key.net_key_index))?;
+                (
+                    AccessKeyType::Application,
+                    app_key.key,
+                    app_key.aid,
+                    net_key.index,
+                )
+            }
+        };
+        let akf = key_type == AccessKeyType::Application;
+        let src = self.state.element_address(element);
+        let ttl = self
+            .state
+            .ttl_or_default(ttl.unwrap_or(self.state.default_ttl));
+        let seq = self.state.next_seq()?;
+        let nonce = access_nonce(key_type, false, seq, src, dst, self.state.iv_index);
+        let upper_pdu = ccm_encrypt(&access_key, &nonce, &payload, &[], MicSize::Short);
+
+        if upper_pdu.len() <= MAX_UNSEGMENTED_ACCESS_SIZE {
+            let lower = LowerTransportPdu::UnsegmentedAccess {
+                akf,
+                aid,
+                upper_pdu,
+            };
+            let pdu = NetworkPdu {
+                ctl: false,
+                ttl,
+                seq,
+                src,
+                dst,
+                transport_pdu: lower.encode(),
+            };
+            return Ok(vec![StackOutput::Transmit(
+                self.encrypt(net_key_index, &pdu)?,
+            )]);
+        }
+
+        let segments = segment_access(akf, aid, false, seq, &upper_pdu)?;
+        let mut outputs = Vec::new();
+        for (index, segment) in segments.iter().enumerate() {
+            let pdu = NetworkPdu {
+                ctl: false,
+                ttl,
+                seq: if index == 0 {
+                    seq
+                } else {
+                    self.state.next_seq()?
+                },
+                src,
+                dst,
+                transport_pdu: segment.encode(),
+            };
+            outputs.push(StackOutput::Transmit(self.encrypt(net_key_index, &pdu)?));
+        }
+        // Groups do not acknowledge segments, so they are just sent again
+        let retransmit_after = SEGMENT_RETRANSMIT_BASE + Duration::from_millis(50 * ttl as u64);
+        self.pending.push(PendingSegments {
+            segments: OutgoingSegments::new(
+                dst,
+                segments,
+                SEGMENT_RETRANSMISSIONS,
+                now + retransmit_after,
+            ),
+            src,
+            ttl,
+            net_key_index,
+        });
+        Ok(outputs)
+    }
+
+    /// Gets the network key used with the device key, the first one of the node
+    fn net_key_index_of_device(&self) -> Result<u16, MeshError> {
+        self.state
+            .net_keys
+            .first()
+            .map(|key| key.index)
+            .ok_or(MeshError::NotProvisioned)
+    }
+
+    /// Retransmits the segments not acknowledged, and discards the incomplete messages received
+    /// long ago
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the network PDUs to transmit, or a `MeshError` if they could not be
+    /// created
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::SequenceExhausted`: If every sequence number of the IV index was used
+    pub(crate) fn poll(&mut self, now: Instant) -> Result<Vec<StackOutput>, MeshError> {
+        self.reassembler.expire(now);
+        let mut to_send = Vec::new();
+        self.pending.retain_mut(|pending| {
+            if now < pending.segments.next_attempt {
+                return true;
+            }
+            let retransmit_after =
+                SEGMENT_RETRANSMIT_BASE + Duration::from_millis(50 * pending.ttl as u64);
+            match pending.segments.retransmit(now + retransmit_after) {
+                Some(segments) => {
+                    to_send.push((
+                        pending.net_key_index,
+                        pending.src,
+                        pending.segments.dst,
+                        pending.ttl,
+                        segments,
+                    ));
+                    true
+                }
+                None => false,
+            }
+        });
+        let mut outputs = Vec::new();
+        for (net_key_index, src, dst, ttl, segments) in to_send {
+            for segment in segments {
+                let pdu = NetworkPdu {
+                    ctl: false,
+                    ttl,
+                    seq: self.state.next_seq()?,
+                    src,
+                    dst,
+                    transport_pdu: segment.encode(),
+                };
+                outputs.push(StackOutput::Transmit(self.encrypt(net_key_index, &pdu)?));
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Gets whether segmented messages are being sent or received
+    pub(crate) fn is_busy(&self) -> bool {
+        !self.pending.is_empty() || self.reassembler.is_receiving()
+    }
+
+    /// Gets the secure network beacon of the first network key, if the beacons are enabled
+    pub(crate) fn secure_beacon(&self) -> Option<Vec<u8>> {
+        let key = self.state.net_keys.first().filter(|_| self.state.beacon)?;
+        Some(key.secure_beacon(self.state.iv_index, self.state.iv_update))
+    }
+
+    /// Handles a secure network beacon received, following the IV index of the network
+    ///
+    /// # Returns
+    ///
+    /// Whether the IV index of the node changed
+    pub(crate) fn receive_beacon(&mut self, beacon: &[u8]) -> bool {
+        let Some((iv_index, iv_update)) = self
+            .state
+            .net_keys
+            .iter()
+            .find_map(|key| key.check_secure_beacon(beacon))
+        else {
+            return false;
+        };
+        let current = self.state.iv_index;
+        if iv_index <= current || iv_index > current + MAX_IV_INDEX_RECOVERY {
+            self.state.iv_update = iv_index == current && iv_update;
+            return false;
+        }
+        self.state.iv_index = iv_index;
+        self.state.iv_update = iv_update;
+        self.state.seq = 0;
+        self.replay.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::{
+        access::Opcode,
+        mesh_crypto::{hex, hex_key},
+        node_state::{AppKey, ModelConfig, ProductInfo},
+    };
+
+    const IV_INDEX: u32 = 0x12345678;
+    const NET_KEY_INDEX: u16 = 0x456;
+
+    fn stack(address: u16) -> MeshStack {
+        let mut state = NodeState::new(ProductInfo::default());
+        state.elements[0]
+            .models
+            .push(ModelConfig::new(ModelId::GENERIC_ONOFF_SERVER));
+        state.provision(
+            NetworkKey::new(NET_KEY_INDEX, hex_key("7dd7364cd842ad18c17c2b820c84c3d6")),
+            IV_INDEX,
+            false,
+            address,
+            hex_key("9d6dd0e96eb25dc19a40ed9914f8f03f"),
+        );
+        MeshStack::new(state)
+    }
+
+    fn transmitted(outputs: &[StackOutput]) -> Vec<Vec<u8>> {
+        outputs
+            .iter()
+            .filter_map(|output| match output {
+                StackOutput::Transmit(data) => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mesh_stack_01_handles_sample_message_6() {
+        let mut node = stack(0x1201);
+        let now = Instant::now();
+        let first = node
+            .receive(
+                &hex("68cab5c5348a230afba8c63d4e686364979deaf4fd40961145939cda0e"),
+                now,
+            )
+            .unwrap();
+        assert_eq!(transmitted(&first).len(), 1);
+        let second = node
+            .receive(
+                &hex("681615b5dd4a846cae0c032bf0746f44f1b8cc8ce5edc57e55beed49c0"),
+                now,
+            )
+            .unwrap();
+        assert_eq!(node.state.app_key(0x123).unwrap().aid, 0x26);
+
+        // The acknowledgment of both segments and the AppKey Status are sent to the provisioner
+        let sent = transmitted(&second);
+        assert_eq!(sent.len(), 2);
+        let key = &node.state.net_keys[0];
+        let ack = key.decrypt(IV_INDEX, &sent[0]).unwrap();
+        assert!(ack.ctl && ack.src == 0x1201 && ack.dst == 0x0003);
+        assert_eq!(
+            SegmentAck::decode(&ack.transport_pdu[1..])
+                .unwrap()
+                .block_ack,
+            0b11
+        );
+        let status = key.decrypt(IV_INDEX, &sent[1]).unwrap();
+        assert!(!status.ctl && status.dst == 0x0003);
+
+        // Replayed PDUs are discarded
+        assert!(node
+            .receive(
+                &hex("681615b5dd4a846cae0c032bf0746f44f1b8cc8ce5edc57e55beed49c0"),
+                now
+            )
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn mesh_stack_02_delivers_group_messages_to_subscribed_models() {
+        let now = Instant::now();
+        let app_key = AppKey::new(0x123, NET_KEY_INDEX, [0x63; 16]);
+        let mut sender = stack(0x0003);
+        sender.state.app_keys.push(app_key.clone());
+        let mut node = stack(0x1201);
+        node.state.app_keys.push(app_key);
+
+        let message = AccessMessage::new(Opcode::sig(0x8202), &[0x01, 0x07]);
+        let outputs = sender
+            .send(
+                0,
+                0xC000,
+                AccessKey::Application(0x123),
+                Some(3),
+                &message,
+                now,
+            )
+            .unwrap();
+        let pdu = transmitted(&outputs).remove(0);
+        // Not bound nor subscribed yet, so it is only relayed
+        assert!(node
+            .receive(&pdu, now)
+            .unwrap()
+            .iter()
+            .all(|output| matches!(output, StackOutput::Transmit(_))));
+
+        let model = node
+            .state
+            .model_mut(0, ModelId::GENERIC_ONOFF_SERVER)
+            .unwrap();
+        model.app_keys.push(0x123);
+        model.subscriptions.push(0xC000);
+        let outputs = sender
+            .send(
+                0,
+                0xC000,
+                AccessKey::Application(0x123),
+                Some(3),
+                &message,
+                now,
+            )
+            .unwrap();
+        let outputs = node.receive(&transmitted(&outputs)[0], now).unwrap();
+        // The message is relayed with a smaller TTL and delivered
+        let relayed = node.state.net_keys[0]
+            .decrypt(IV_INDEX, &transmitted(&outputs)[0])
+            .unwrap();
+        assert_eq!(relayed.ttl, 2);
+        let Some(StackOutput::Access(incoming)) = outputs.last() else {
+            panic!("the message must be delivered")
+        };
+        assert_eq!(incoming.message, message);
+        assert_eq!(incoming.models, [(0, ModelId::GENERIC_ONOFF_SERVER)]);
+        assert_eq!(incoming.context.app_key_index, Some(0x123));
+    }
+
+    #[test]
+    fn mesh_stack_03_retransmits_segments_until_acknowledged() {
+        let now = Instant::now();
+        let app_key = AppKey::new(0x000, NET_KEY_INDEX, [0x63; 16]);
+        let mut sender = stack(0x0003);
+        sender.state.app_keys.push(app_key.clone());
+        let mut node = stack(0x1201);
+        node.state.app_keys.push(app_key);
+        node.state
+            .model_mut(0, ModelId::GENERIC_ONOFF_SERVER)
+            .unwrap()
+            .app_keys
+            .push(0x000);
+
+        let message = AccessMessage::new(Opcode::sig(0x8202), &[0xAA; 18]);
+        let outputs = sender
+            .send(
+                0,
+                0x1201,
+                AccessKey::Application(0x000),
+                None,
+                &message,
+                now,
+            )
+            .unwrap();
+        let segments = transmitted(&outputs);
+        assert_eq!(segments.len(), 2);
+        assert!(sender.is_busy());
+
+        // The second segment is lost, and sent again
+        let acks = node.receive(&segments[0], now).unwrap();
+        sender.receive(&transmitted(&acks)[0], now).unwrap();
+        let later = now + Duration::from_secs(1);
+        let retransmitted = transmitted(&sender.poll(later).unwrap());
+        assert_eq!(retransmitted.len(), 1);
+
+        let outputs = node.receive(&retransmitted[0], later).unwrap();
+        let Some(StackOutput::Access(incoming)) = outputs.last() else {
+            panic!("the message must be delivered")
+        };
+        assert_eq!(incoming.message, message);
+        sender.receive(&transmitted(&outputs)[0], later).unwrap();
+        assert!(!sender.is_busy());
+    }
+
+    #[test]
+    fn mesh_stack_04_follows_iv_index_of_beacons() {
+        let mut node = stack(0x1201);
+        let mut other = stack(0x0003);
+        other.state.iv_index = IV_INDEX + 1;
+        node.state.seq = 100;
+        assert!(node.receive_beacon(&other.secure_beacon().unwrap()));
+        assert_eq!((node.state.iv_index, node.state.seq), (IV_INDEX + 1, 0));
+        other.state.iv_index = IV_INDEX + 100;
+        assert!(!node.receive_beacon(&other.secure_beacon().unwrap()));
+        other.state.beacon = false;
+        assert!(other.secure_beacon().is_none());
+    }
+}
@@ -0,0 +1,439 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+use super::{
+    mesh_crypto::MeshKey,
+    network::{NetworkKey, MAX_SEQ},
+    node_state::{AppKey, ElementConfig, ModelConfig, ModelId, NodeState, Publication},
+    MeshError,
+};
+
+const NAMESPACE: &str = "ble_mesh";
+const STATE_KEY: &str = "state";
+const SEQ_KEY: &str = "seq";
+/// Version of the encoding of the state, stored states of other versions are ignored
+const STATE_VERSION: u8 = 1;
+/// Amount of sequence numbers reserved each time the reservation is stored, so the flash is not
+/// written for every message sent
+pub(crate) const SEQ_BLOCK: u32 = 256;
+/// The next block is reserved once the node gets this close to the end of the current one, so a
+/// burst of messages sent in a single update never passes the stored reservation
+const SEQ_MARGIN: u32 = SEQ_BLOCK / 2;
+
+/// Gets the new reservation of sequence numbers to store, or `None` if the stored one still has
+/// room. A sequence number far below the reservation, as after an IV index update or a new
+/// provisioning, also gets a new reservation.
+///
+/// # Arguments
+///
+/// - `seq`: The next sequence number the node sends
+/// - `reserved`: The sequence number up to which the stored reservation goes
+pub(crate) fn seq_reservation(seq: u32, reserved: u32) -> Option<u32> {
+    (seq + SEQ_MARGIN > reserved || seq + SEQ_BLOCK < reserved)
+        .then(|| (seq + SEQ_BLOCK).min(MAX_SEQ + 1))
+}
+
+/// Writes the values of the state in a buffer, in little endian
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn key(&mut self, key: &MeshKey) {
+        self.data.extend_from_slice(key);
+    }
+
+    fn u16_list(&mut self, values: &[u16]) {
+        self.u8(values.len() as u8);
+        for value in values {
+            self.u16(*value);
+        }
+    }
+}
+
+/// Reads the values written by a [Writer], returning `None` when the data ends early
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let data = self.data;
+        if data.len() < N {
+            return None;
+        }
+        let (bytes, rest) = data.split_at(N);
+        self.data = rest;
+        bytes.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[value]| value)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        self.u8().map(|value| value != 0)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn key(&mut self) -> Option<MeshKey> {
+        self.bytes()
+    }
+
+    fn u16_list(&mut self) -> Option<Vec<u16>> {
+        (0..self.u8()?).map(|_| self.u16()).collect()
+    }
+}
+
+/// Encodes what the node must remember after a restart: its address, keys, IV index and the
+/// configuration of its models. The sequence number is stored apart, see [seq_reservation].
+pub(crate) fn encode_state(state: &NodeState) -> Vec<u8> {
+    let mut writer = Writer { data: Vec::new() };
+    writer.u8(STATE_VERSION);
+    writer.u16(state.address);
+    writer.u32(state.iv_index);
+    writer.u8(state.iv_update as u8);
+    writer.u8(state.default_ttl);
+    writer.u8(state.relay as u8);
+    writer.u8(state.relay_retransmit);
+    writer.u8(state.beacon as u8);
+    writer.key(&state.device_key.unwrap_or_default());
+    writer.u8(state.net_keys.len() as u8);
+    for net_key in &state.net_keys {
+        writer.u16(net_key.index);
+        writer.key(&net_key.key);
+    }
+    writer.u8(state.app_keys.len() as u8);
+    for app_key in &state.app_keys {
+        writer.u16(app_key.index);
+        writer.u16(app_key.net_key_index);
+        writer.key(&app_key.key);
+    }
+    writer.u8(state.elements.len() as u8);
+    for element in &state.elements {
+        writer.u16(element.location);
+        writer.u8(element.models.len() as u8);
+        for model in &element.models {
+            let id = model.id.encode();
+            writer.u8(id.len() as u8);
+            writer.data.extend(id);
+            writer.u16_list(&model.app_keys);
+            writer.u16_list(&model.subscriptions);
+            match &model.publication {
+                Some(publication) => {
+                    writer.u8(1);
+                    writer.u16(publication.address);
+                    writer.u16(publication.app_key_index);
+                    writer.u8(publication.credential_flag as u8);
+                    writer.u8(publication.ttl);
+                    writer.u8(publication.period);
+                    writer.u8(publication.retransmit);
+                }
+                None => writer.u8(0),
+            }
+        }
+    }
+    writer.data
+}
+
+/// Restores a state encoded by [encode_state] over the current one, whose elements and models
+/// must be the same as when it was stored. The sequence number is not restored.
+///
+/// # Returns
+///
+/// `true` if the state was restored, or `false` if the data is not valid or the elements of the
+/// node changed, in which case the state is left as it was
+pub(crate) fn restore_state(state: &mut NodeState, data: &[u8]) -> bool {
+    match decode_state(state, data) {
+        Some(restored) => {
+            *state = restored;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Decodes a state encoded by [encode_state], taking the fields that are not stored from the
+/// current state
+fn decode_state(current: &NodeState, data: &[u8]) -> Option<NodeState> {
+    let mut reader = Reader { data };
+    if reader.u8()? != STATE_VERSION {
+        return None;
+    }
+    let mut state = current.clone();
+    state.address = reader.u16()?;
+    state.iv_index = reader.u32()?;
+    state.iv_update = reader.bool()?;
+    state.default_ttl = reader.u8()?;
+    state.relay = reader.bool()?;
+    state.relay_retransmit = reader.u8()?;
+    state.beacon = reader.bool()?;
+    state.device_key = Some(reader.key()?);
+    state.net_keys = (0..reader.u8()?)
+        .map(|_| Some(NetworkKey::new(reader.u16()?, reader.key()?)))
+        .collect::<Option<_>>()?;
+    state.app_keys = (0..reader.u8()?)
+        .map(|_| Some(AppKey::new(reader.u16()?, reader.u16()?, reader.key()?)))
+        .collect::<Option<_>>()?;
+    state.elements = (0..reader.u8()?)
+        .map(|_| decode_element(&mut reader))
+        .collect::<Option<_>>()?;
+    let same_composition = state.elements.len() == current.elements.len()
+        && state
+            .elements
+            .iter()
+            .zip(&current.elements)
+            .all(|(stored, element)| {
+                stored.location == element.location
+                    && stored.models.len() == element.models.len()
+                    && stored
+                        .models
+                        .iter()
+                        .zip(&element.models)
+                        .all(|(stored, model)| stored.id == model.id)
+            });
+    (same_composition && reader.data.is_empty() && state.is_provisioned()).then_some(state)
+}
+
+/// Decodes an element and the configuration of its models
+fn decode_element(reader: &mut Reader) -> Option<ElementConfig> {
+    let location = reader.u16()?;
+    let models = (0..reader.u8()?)
+        .map(|_| {
+            let id = match reader.u8()? {
+                2 => ModelId::decode(&reader.bytes::<2>()?),
+                4 => ModelId::decode(&reader.bytes::<4>()?),
+                _ => None,
+            }?;
+            let app_keys = reader.u16_list()?;
+            let subscriptions = reader.u16_list()?;
+            let publication = match reader.bool()? {
+                true => Some(Publication {
+                    address: reader.u16()?,
+                    app_key_index: reader.u16()?,
+                    credential_flag: reader.bool()?,
+                    ttl: reader.u8()?,
+                    period: reader.u8()?,
+                    retransmit: reader.u8()?,
+                }),
+                false => None,
+            };
+            Some(ModelConfig {
+                id,
+                app_keys,
+                subscriptions,
+                publication,
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some(ElementConfig { location, models })
+}
+
+/// Keeps the state of a [super::BleMeshNode] in the NVS, so it stays in its network after a
+/// restart. The state is written only when it changes, and the sequence number is stored as a
+/// reservation of a block of numbers: after a restart the node continues from the end of the
+/// block, so it never sends a sequence number twice.
+/// - `nvs`: The namespace of the mesh in the default NVS partition
+/// - `stored`: The last state written, or `None` if there is no stored state
+/// - `reserved`: The sequence number up to which the stored reservation goes
+pub(crate) struct MeshStorage {
+    nvs: EspNvs<NvsDefault>,
+    stored: Option<Vec<u8>>,
+    reserved: u32,
+}
+
+impl MeshStorage {
+    /// Opens the mesh namespace of the default NVS partition
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the namespace could not be opened
+    pub(crate) fn new(partition: EspDefaultNvsPartition) -> Result<Self, MeshError> {
+        let nvs = EspNvs::new(partition, NAMESPACE, true).map_err(|_| MeshError::StorageError)?;
+        Ok(Self {
+            nvs,
+            stored: None,
+            reserved: 0,
+        })
+    }
+
+    /// Restores the stored state over the current one, continuing the sequence numbers from the
+    /// end of the stored reservation. A stored state that does not match the elements of the
+    /// node is erased.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with `true` if the node was restored into its network, or a `MeshError` if it
+    /// fails
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the NVS could not be read
+    pub(crate) fn restore(&mut self, state: &mut NodeState) -> Result<bool, MeshError> {
+        let Some(len) = self
+            .nvs
+            .blob_len(STATE_KEY)
+            .map_err(|_| MeshError::StorageError)?
+        else {
+            return Ok(false);
+        };
+        let mut buffer = vec![0; len];
+        let data = self
+            .nvs
+            .get_raw(STATE_KEY, &mut buffer)
+            .map_err(|_| MeshError::StorageError)?
+            .unwrap_or_default();
+        if !restore_state(state, data) {
+            self.clear()?;
+            return Ok(false);
+        }
+        self.stored = Some(data.to_vec());
+        self.reserved = self
+            .nvs
+            .get_u32(SEQ_KEY)
+            .map_err(|_| MeshError::StorageError)?
+            .unwrap_or_default();
+        state.seq = self.reserved;
+        Ok(true)
+    }
+
+    /// Writes the state if it changed since it was last written, and reserves the next block of
+    /// sequence numbers when the node gets close to the end of the stored one. The stored state
+    /// is erased once the node leaves its network.
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the NVS could not be written
+    pub(crate) fn save(&mut self, state: &NodeState) -> Result<(), MeshError> {
+        if !state.is_provisioned() {
+            if self.stored.is_some() {
+                self.clear()?;
+            }
+            return Ok(());
+        }
+        if let Some(reserved) = seq_reservation(state.seq, self.reserved) {
+            self.nvs
+                .set_u32(SEQ_KEY, reserved)
+                .map_err(|_| MeshError::StorageError)?;
+            self.reserved = reserved;
+        }
+        let data = encode_state(state);
+        if self.stored.as_ref() != Some(&data) {
+            self.nvs
+                .set_raw(STATE_KEY, &data)
+                .map_err(|_| MeshError::StorageError)?;
+            self.stored = Some(data);
+        }
+        Ok(())
+    }
+
+    /// Erases the stored state and sequence number reservation
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::StorageError`: If the NVS could not be written
+    fn clear(&mut self) -> Result<(), MeshError> {
+        self.nvs
+            .remove(STATE_KEY)
+            .map_err(|_| MeshError::StorageError)?;
+        self.nvs
+            .remove(SEQ_KEY)
+            .map_err(|_| MeshError::StorageError)?;
+        self.stored = None;
+        self.reserved = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::ProductInfo;
+
+    fn provisioned_state() -> NodeState {
+        let mut state = NodeState::new(ProductInfo::default());
+        state.elements[0]
+            .models
+            .push(ModelConfig::new(ModelId::GENERIC_ONOFF_SERVER));
+        state.elements.push(ElementConfig {
+            location: 0x0102,
+            models: vec![ModelConfig::new(ModelId::Vendor {
+                company_id: 0x02E5,
+                model_id: 0x0001,
+            })],
+        });
+        state.provision(NetworkKey::new(0, [1; 16]), 0x1234, false, 0x0A01, [2; 16]);
+        state.app_keys.push(AppKey::new(1, 0, [3; 16]));
+        let onoff = state.model_mut(0, ModelId::GENERIC_ONOFF_SERVER).unwrap();
+        onoff.app_keys.push(1);
+        onoff.subscriptions.push(0xC001);
+        onoff.publication = Some(Publication::new(0xC000, 1).ttl(5));
+        state.relay = false;
+        state.seq = 1000;
+        state
+    }
+
+    #[test]
+    fn mesh_storage_01_restores_keys_and_configuration() {
+        let stored = provisioned_state();
+        let mut state = stored.clone();
+        state.reset();
+        assert!(restore_state(&mut state, &encode_state(&stored)));
+        // The sequence number is restored from its reservation
+        assert_eq!(state.seq, 0);
+        state.seq = stored.seq;
+        assert_eq!(state, stored);
+    }
+
+    #[test]
+    fn mesh_storage_02_ignores_states_of_other_compositions() {
+        let stored = provisioned_state();
+        let mut state = stored.clone();
+        state.reset();
+        state.elements.pop();
+        let expected = state.clone();
+        assert!(!restore_state(&mut state, &encode_state(&stored)));
+        assert_eq!(state, expected);
+
+        let data = encode_state(&stored);
+        let mut state = stored.clone();
+        state.reset();
+        assert!(!restore_state(&mut state, &data[..data.len() - 1]));
+        let mut unprovisioned = stored.clone();
+        unprovisioned.reset();
+        assert!(!restore_state(&mut state, &encode_state(&unprovisioned)));
+    }
+
+    #[test]
+    fn mesh_storage_03_reserves_sequence_numbers_in_blocks() {
+        assert_eq!(seq_reservation(0, 0), Some(SEQ_BLOCK));
+        assert_eq!(seq_reservation(SEQ_MARGIN, SEQ_BLOCK), None);
+        assert_eq!(
+            seq_reservation(SEQ_MARGIN + 1, SEQ_BLOCK),
+            Some(SEQ_MARGIN + 1 + SEQ_BLOCK)
+        );
+        // After a restart the node continues from the reservation, and reserves the next block
+        assert_eq!(seq_reservation(SEQ_BLOCK, SEQ_BLOCK), Some(2 * SEQ_BLOCK));
+        // The IV index update starts the sequence numbers again
+        assert_eq!(seq_reservation(0, 10 * SEQ_BLOCK), Some(SEQ_BLOCK));
+        assert_eq!(seq_reservation(MAX_SEQ, MAX_SEQ), Some(MAX_SEQ + 1));
+    }
+}
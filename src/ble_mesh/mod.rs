@@ -0,0 +1,25 @@
+mod access;
+mod config_server;
+mod generic_models;
+mod mesh_bearer;
+mod mesh_config;
+mod mesh_crypto;
+mod mesh_error;
+mod mesh_node;
+mod mesh_stack;
+mod mesh_storage;
+mod network;
+mod node_state;
+mod provisioning;
+mod provisioning_bearer;
+mod provisioning_service;
+mod transport;
+
+pub use access::*;
+pub use generic_models::*;
+pub use mesh_config::*;
+pub use mesh_error::*;
+pub use mesh_node::*;
+pub use node_state::*;
+pub use provisioning::*;
+pub use provisioning_service::*;
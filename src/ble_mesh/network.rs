@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+
+use super::{
+    mesh_crypto::{
+        aes_cmac, ccm_decrypt, ccm_encrypt, e, k1, k2, k3, s1, MeshKey, MeshNonce, MicSize,
+    },
+    MeshError,
+};
+
+/// Size of the fields of a network PDU before the transport PDU: IVI and NID, CTL and TTL, SEQ,
+/// SRC and DST
+const NETWORK_HEADER_SIZE: usize = 9;
+/// Amount of bytes of the encrypted part used as privacy random
+const PRIVACY_RANDOM_SIZE: usize = 7;
+const NETWORK_NONCE: u8 = 0x00;
+const SECURE_NETWORK_BEACON: u8 = 0x01;
+const IV_UPDATE_FLAG: u8 = 0x02;
+/// Biggest value of the 24 bits sequence number
+pub(crate) const MAX_SEQ: u32 = 0x00FF_FFFF;
+
+/// A decrypted network PDU
+/// - `ctl`: Whether the transport PDU is a control message, instead of an access message
+/// - `ttl`: The time to live, the amount of relays the message can still go through
+/// - `seq`: The sequence number of the message for its source
+/// - `src`: The unicast address of the element that sent the message
+/// - `dst`: The address the message is sent to
+/// - `transport_pdu`: The lower transport PDU carried by the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NetworkPdu {
+    pub ctl: bool,
+    pub ttl: u8,
+    pub seq: u32,
+    pub src: u16,
+    pub dst: u16,
+    pub transport_pdu: Vec<u8>,
+}
+
+impl NetworkPdu {
+    /// Gets the size of the network MIC used by the PDU
+    fn mic(&self) -> MicSize {
+        mic_size(self.ctl)
+    }
+}
+
+/// Gets the size of the network MIC of access or control messages
+fn mic_size(ctl: bool) -> MicSize {
+    match ctl {
+        true => MicSize::Long,
+        false => MicSize::Short,
+    }
+}
+
+/// A network key with the keys derived from it
+/// - `index`: The global index of the key
+/// - `key`: The network key
+/// - `nid`: The 7 bits identifier sent in every PDU encrypted with the key
+/// - `encryption_key`: Encrypts the network PDUs
+/// - `privacy_key`: Obfuscates the headers of the network PDUs
+/// - `network_id`: Public identifier of the network
+/// - `beacon_key`: Authenticates the secure network beacons
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NetworkKey {
+    pub index: u16,
+    pub key: MeshKey,
+    pub nid: u8,
+    encryption_key: MeshKey,
+    privacy_key: MeshKey,
+    pub network_id: [u8; 8],
+    beacon_key: MeshKey,
+}
+
+impl NetworkKey {
+    /// Creates a new NetworkKey, deriving its keys
+    pub(crate) fn new(index: u16, key: MeshKey) -> Self {
+        let (nid, encryption_key, privacy_key) = k2(&key, &[0x00]);
+        let mut beacon_salt_input = b"id128".to_vec();
+        beacon_salt_input.push(0x01);
+        Self {
+            index,
+            key,
+            nid,
+            encryption_key,
+            privacy_key,
+            network_id: k3(&key),
+            beacon_key: k1(&key, &s1(b"nkbk"), &beacon_salt_input),
+        }
+    }
+
+    /// Encrypts and obfuscates a network PDU
+    ///
+    /// # Arguments
+    ///
+    /// - `iv_index`: The current IV index of the network
+    /// - `pdu`: The PDU to encrypt
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the PDU, ready to be sent through a bearer
+    pub(crate) fn encrypt(&self, iv_index: u32, pdu: &NetworkPdu) -> Vec<u8> {
+        let nonce = network_nonce(pdu.ctl, pdu.ttl, pdu.seq, pdu.src, iv_index);
+        let mut plaintext = pdu.dst.to_be_bytes().to_vec();
+        plaintext.extend_from_slice(&pdu.transport_pdu);
+        let encrypted = ccm_encrypt(&self.encryption_key, &nonce, &plaintext, &[], pdu.mic());
+
+        let mut data = vec![((iv_index as u8 & 0x01) << 7) | self.nid];
+        data.extend_from_slice(&nonce[1..7]);
+        data.extend_from_slice(&encrypted);
+        self.obfuscate(iv_index, &mut data);
+        data
+    }
+
+    /// Deobfuscates and decrypts a network PDU
+    ///
+    /// # Arguments
+    ///
+    /// - `iv_index`: The current IV index of the network. PDUs sent with the previous IV index
+    ///   are decrypted with it, as told by their IVI bit.
+    /// - `data`: The bytes received from the bearer
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the decrypted PDU, or a `MeshError` if it is not a PDU of this key
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidPdu`: If the PDU is too short or its NID is not the one of the key
+    /// - `MeshError::AuthenticationFailed`: If the network MIC does not match
+    pub(crate) fn decrypt(&self, iv_index: u32, data: &[u8]) -> Result<NetworkPdu, MeshError> {
+        if data.len() < NETWORK_HEADER_SIZE + MicSize::Short.len() + 1 || data[0] & 0x7F != self.nid
+        {
+            return Err(MeshError::InvalidPdu);
+        }
+        let iv_index = match (data[0] >> 7) as u32 == iv_index & 0x01 {
+            true => iv_index,
+            false => iv_index.wrapping_sub(1),
+        };
+        let mut data = data.to_vec();
+        self.obfuscate(iv_index, &mut data);
+
+        let ctl = data[1] & 0x80 != 0;
+        let mut nonce = [0; 13];
+        nonce[0] = NETWORK_NONCE;
+        nonce[1..7].copy_from_slice(&data[1..7]);
+        nonce[9..].copy_from_slice(&iv_index.to_be_bytes());
+        let plaintext = ccm_decrypt(&self.encryption_key, &nonce, &data[7..], &[], mic_size(ctl))?;
+        if plaintext.len() < 3 {
+            return Err(MeshError::InvalidPdu);
+        }
+        Ok(NetworkPdu {
+            ctl,
+            ttl: data[1] & 0x7F,
+            seq: u32::from_be_bytes([0, data[2], data[3], data[4]]),
+            src: u16::from_be_bytes([data[5], data[6]]),
+            dst: u16::from_be_bytes([plaintext[0], plaintext[1]]),
+            transport_pdu: plaintext[2..].to_vec(),
+        })
+    }
+
+    /// Obfuscates the CTL, TTL, SEQ and SRC of a PDU with its encrypted part. Obfuscating twice
+    /// gets the original data back.
+    fn obfuscate(&self, iv_index: u32, data: &mut [u8]) {
+        let mut privacy_plaintext = [0; 16];
+        privacy_plaintext[5..9].copy_from_slice(&iv_index.to_be_bytes());
+        privacy_plaintext[9..].copy_from_slice(&data[7..7 + PRIVACY_RANDOM_SIZE]);
+        let pecb = e(&self.privacy_key, &privacy_plaintext);
+        for (byte, mask) in data[1..7].iter_mut().zip(pecb) {
+            *byte ^= mask;
+        }
+    }
+
+    /// Creates the secure network beacon of the key
+    ///
+    /// # Arguments
+    ///
+    /// - `iv_index`: The current IV index of the network
+    /// - `iv_update`: Whether the IV update procedure is in progress
+    ///
+    /// # Returns
+    ///
+    /// The payload of the beacon
+    pub(crate) fn secure_beacon(&self, iv_index: u32, iv_update: bool) -> Vec<u8> {
+        let flags = if iv_update { IV_UPDATE_FLAG } else { 0 };
+        let mut beacon = vec![SECURE_NETWORK_BEACON, flags];
+        beacon.extend_from_slice(&self.network_id);
+        beacon.extend_from_slice(&iv_index.to_be_bytes());
+        let authentication = aes_cmac(&self.beacon_key, &[&beacon[1..]]);
+        beacon.extend_from_slice(&authentication[..8]);
+        beacon
+    }
+
+    /// Checks a secure network beacon of the network of the key
+    ///
+    /// # Returns
+    ///
+    /// The IV index and whether the IV update is in progress, or `None` if the beacon is not
+    /// of this network or was not authenticated
+    pub(crate) fn check_secure_beacon(&self, beacon: &[u8]) -> Option<(u32, bool)> {
+        if beacon.len() != 22 || beacon[0] != SECURE_NETWORK_BEACON {
+            return None;
+        }
+        if beacon[2..10] != self.network_id {
+            return None;
+        }
+        let authentication = aes_cmac(&self.beacon_key, &[&beacon[1..14]]);
+        if authentication[..8] != beacon[14..] {
+            return None;
+        }
+        let iv_index = u32::from_be_bytes(beacon[10..14].try_into().unwrap());
+        Some((iv_index, beacon[1] & IV_UPDATE_FLAG != 0))
+    }
+}
+
+/// Creates the nonce that encrypts the network PDUs
+fn network_nonce(ctl: bool, ttl: u8, seq: u32, src: u16, iv_index: u32) -> MeshNonce {
+    let mut nonce = [0; 13];
+    nonce[0] = NETWORK_NONCE;
+    nonce[1] = ((ctl as u8) << 7) | (ttl & 0x7F);
+    nonce[2..5].copy_from_slice(&seq.to_be_bytes()[1..]);
+    nonce[5..7].copy_from_slice(&src.to_be_bytes());
+    nonce[9..].copy_from_slice(&iv_index.to_be_bytes());
+    nonce
+}
+
+/// Remembers the last network PDUs received, so the ones received again through other relays
+/// are discarded
+pub(crate) struct NetworkCache {
+    entries: VecDeque<(u16, u32)>,
+    capacity: usize,
+}
+
+impl NetworkCache {
+    /// Creates a new NetworkCache that remembers up to `capacity` PDUs
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds a PDU to the cache
+    ///
+    /// # Returns
+    ///
+    /// Whether the PDU was not in the cache already
+    pub(crate) fn insert(&mut self, pdu: &NetworkPdu) -> bool {
+        let entry = (pdu.src, pdu.seq);
+        if self.entries.contains(&entry) {
+            return false;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        true
+    }
+
+    /// Forgets every PDU
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::mesh_crypto::{hex, hex_key};
+
+    const IV_INDEX: u32 = 0x12345678;
+
+    fn sample_key() -> NetworkKey {
+        NetworkKey::new(0, hex_key("7dd7364cd842ad18c17c2b820c84c3d6"))
+    }
+
+    #[test]
+    fn network_01_derives_sample_keys() {
+        let key = sample_key();
+        assert_eq!(key.nid, 0x68);
+        assert_eq!(
+            key.encryption_key,
+            hex_key("0953fa93e7caac9638f58820220a398e")
+        );
+        assert_eq!(key.privacy_key, hex_key("8b84eedec100067d670971dd2aa700cf"));
+        assert_eq!(key.network_id.to_vec(), hex("3ecaff672f673370"));
+    }
+
+    #[test]
+    fn network_02_encrypts_and_decrypts_sample_message_1() {
+        let pdu = NetworkPdu {
+            ctl: true,
+            ttl: 0,
+            seq: 1,
+            src: 0x1201,
+            dst: 0xFFFD,
+            transport_pdu: hex("034b50057e400000010000"),
+        };
+        assert_eq!(
+            network_nonce(pdu.ctl, pdu.ttl, pdu.seq, pdu.src, IV_INDEX).to_vec(),
+            hex("00800000011201000012345678")
+        );
+        let expected = hex("68eca487516765b5e5bfdacbaf6cb7fb6bff871f035444ce83a670df");
+        let key = sample_key();
+        assert_eq!(key.encrypt(IV_INDEX, &pdu), expected);
+        assert_eq!(key.decrypt(IV_INDEX, &expected).unwrap(), pdu);
+    }
+
+    #[test]
+    fn network_03_rejects_other_networks_and_tampered_pdus() {
+        let key = sample_key();
+        let mut data = hex("68eca487516765b5e5bfdacbaf6cb7fb6bff871f035444ce83a670df");
+        // The previous IV index is used when the IVI bit does not match
+        assert!(key.decrypt(IV_INDEX + 1, &data).is_ok());
+        assert!(matches!(
+            key.decrypt(IV_INDEX + 2, &data),
+            Err(MeshError::AuthenticationFailed)
+        ));
+        data[10] ^= 0x01;
+        assert!(matches!(
+            key.decrypt(IV_INDEX, &data),
+            Err(MeshError::AuthenticationFailed)
+        ));
+        data[0] = 0x69;
+        assert!(matches!(
+            key.decrypt(IV_INDEX, &data),
+            Err(MeshError::InvalidPdu)
+        ));
+    }
+
+    #[test]
+    fn network_04_secure_beacon_and_cache() {
+        let key = sample_key();
+        let beacon = key.secure_beacon(IV_INDEX, true);
+        assert_eq!(beacon.len(), 22);
+        assert_eq!(key.check_secure_beacon(&beacon), Some((IV_INDEX, true)));
+        let other = NetworkKey::new(1, [0x11; 16]);
+        assert_eq!(other.check_secure_beacon(&beacon), None);
+
+        let mut cache = NetworkCache::new(2);
+        let mut pdu = NetworkPdu {
+            ctl: false,
+            ttl: 5,
+            seq: 1,
+            src: 0x0001,
+            dst: 0xC000,
+            transport_pdu: vec![0; 5],
+        };
+        assert!(cache.insert(&pdu));
+        assert!(!cache.insert(&pdu));
+        pdu.seq = 2;
+        assert!(cache.insert(&pdu));
+        pdu.seq = 3;
+        assert!(cache.insert(&pdu));
+        pdu.seq = 1;
+        assert!(cache.insert(&pdu));
+    }
+}
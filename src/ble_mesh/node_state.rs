@@ -0,0 +1,523 @@
+use std::time::Duration;
+
+use super::{
+    mesh_crypto::{k4, MeshKey},
+    network::{NetworkKey, MAX_SEQ},
+    MeshError,
+};
+
+/// Address of no element, as the publication address of a model that does not publish
+pub const UNASSIGNED_ADDRESS: u16 = 0x0000;
+/// Group address of every proxy
+pub const ALL_PROXIES_ADDRESS: u16 = 0xFFFC;
+/// Group address of every friend
+pub const ALL_FRIENDS_ADDRESS: u16 = 0xFFFD;
+/// Group address of every relay
+pub const ALL_RELAYS_ADDRESS: u16 = 0xFFFE;
+/// Group address of every node
+pub const ALL_NODES_ADDRESS: u16 = 0xFFFF;
+/// TTL of a publication that uses the default TTL of the node
+pub const USE_DEFAULT_TTL: u8 = 0xFF;
+/// Biggest TTL a message can be sent with
+pub(crate) const MAX_TTL: u8 = 0x7F;
+/// Default TTL of the node before being configured
+const INITIAL_DEFAULT_TTL: u8 = 7;
+const RELAY_FEATURE: u16 = 0x0001;
+const MAX_PERIOD_STEPS: u32 = 0x3F;
+const PERIOD_RESOLUTIONS: [Duration; 4] = [
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+    Duration::from_secs(600),
+];
+const RETRANSMIT_INTERVAL_STEP: Duration = Duration::from_millis(50);
+
+/// Gets whether an address is the one of an element
+pub(crate) fn is_unicast(address: u16) -> bool {
+    address != UNASSIGNED_ADDRESS && address & 0x8000 == 0
+}
+
+/// Gets whether an address is a group address
+pub(crate) fn is_group(address: u16) -> bool {
+    address & 0xC000 == 0xC000
+}
+
+/// Identifier of a model: a 16 bits id defined by the Bluetooth SIG, or a vendor model id with
+/// the company identifier of the vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModelId {
+    Sig(u16),
+    Vendor { company_id: u16, model_id: u16 },
+}
+
+impl ModelId {
+    pub const CONFIG_SERVER: ModelId = ModelId::Sig(0x0000);
+    pub const CONFIG_CLIENT: ModelId = ModelId::Sig(0x0001);
+    pub const GENERIC_ONOFF_SERVER: ModelId = ModelId::Sig(0x1000);
+    pub const GENERIC_ONOFF_CLIENT: ModelId = ModelId::Sig(0x1001);
+    pub const GENERIC_LEVEL_SERVER: ModelId = ModelId::Sig(0x1002);
+    pub const GENERIC_LEVEL_CLIENT: ModelId = ModelId::Sig(0x1003);
+
+    /// Gets the bytes of the id as sent in configuration messages
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            ModelId::Sig(id) => id.to_le_bytes().to_vec(),
+            ModelId::Vendor {
+                company_id,
+                model_id,
+            } => [company_id.to_le_bytes(), model_id.to_le_bytes()].concat(),
+        }
+    }
+
+    /// Parses a model id of a configuration message, 2 bytes long for SIG models and 4 bytes
+    /// long for vendor models
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        match data {
+            [low, high] => Some(ModelId::Sig(u16::from_le_bytes([*low, *high]))),
+            [company_low, company_high, model_low, model_high] => Some(ModelId::Vendor {
+                company_id: u16::from_le_bytes([*company_low, *company_high]),
+                model_id: u16::from_le_bytes([*model_low, *model_high]),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Gets whether the model is defined by the Bluetooth SIG
+    pub fn is_sig(&self) -> bool {
+        matches!(self, ModelId::Sig(_))
+    }
+}
+
+/// Where and how a model publishes its messages
+/// - `address`: The address the messages are published to
+/// - `app_key_index`: The index of the application key the messages are encrypted with
+/// - `credential_flag`: Whether the friendship credentials are used, not supported by the node
+/// - `ttl`: The TTL of the messages, or [USE_DEFAULT_TTL]
+/// - `period`: The encoded period of the periodic publication: 6 bits of steps and 2 bits of
+///   resolution
+/// - `retransmit`: The encoded retransmissions of every message: 3 bits of count and 5 bits of
+///   interval steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Publication {
+    pub address: u16,
+    pub app_key_index: u16,
+    pub credential_flag: bool,
+    pub ttl: u8,
+    pub period: u8,
+    pub retransmit: u8,
+}
+
+impl Publication {
+    /// Creates a new Publication that sends each message once with the default TTL, and does not
+    /// publish periodically
+    pub fn new(address: u16, app_key_index: u16) -> Self {
+        Self {
+            address,
+            app_key_index,
+            credential_flag: false,
+            ttl: USE_DEFAULT_TTL,
+            period: 0,
+            retransmit: 0,
+        }
+    }
+
+    /// Sets the TTL of the published messages
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the period of the periodic publication. It is rounded down to the closest period
+    /// that can be encoded, up to 10.5 hours. A zero period disables the periodic publication.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = PERIOD_RESOLUTIONS
+            .iter()
+            .enumerate()
+            .find_map(|(resolution, step)| {
+                let steps = (period.as_millis() / step.as_millis()) as u32;
+                (steps <= MAX_PERIOD_STEPS).then_some(((steps as u8) << 2) | resolution as u8)
+            })
+            .unwrap_or(((MAX_PERIOD_STEPS as u8) << 2) | 0x03);
+        self
+    }
+
+    /// Sets how many times every published message is sent again
+    ///
+    /// # Arguments
+    ///
+    /// - `count`: Amount of retransmissions, up to 7
+    /// - `interval`: Time between retransmissions, multiple of 50 ms up to 1.6 s
+    pub fn retransmit(mut self, count: u8, interval: Duration) -> Self {
+        let steps = (interval.as_millis() / RETRANSMIT_INTERVAL_STEP.as_millis()).clamp(1, 32);
+        self.retransmit = (count.min(7) << 5) | (steps as u8 - 1);
+        self
+    }
+
+    /// Gets the period of the periodic publication, or `None` if it is disabled
+    pub fn period_duration(&self) -> Option<Duration> {
+        let steps = (self.period >> 2) as u32;
+        let resolution = PERIOD_RESOLUTIONS[(self.period & 0x03) as usize];
+        (steps != 0).then(|| resolution * steps)
+    }
+
+    /// Gets the amount of retransmissions of every message
+    pub(crate) fn retransmit_count(&self) -> u8 {
+        self.retransmit >> 5
+    }
+
+    /// Gets the time between retransmissions
+    pub(crate) fn retransmit_interval(&self) -> Duration {
+        RETRANSMIT_INTERVAL_STEP * ((self.retransmit & 0x1F) as u32 + 1)
+    }
+}
+
+/// Configuration of a model of an element
+/// - `id`: The id of the model
+/// - `app_keys`: The indexes of the application keys bound to the model
+/// - `subscriptions`: The group addresses the model receives messages from
+/// - `publication`: Where the model publishes, or `None` if it does not publish
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModelConfig {
+    pub id: ModelId,
+    pub app_keys: Vec<u16>,
+    pub subscriptions: Vec<u16>,
+    pub publication: Option<Publication>,
+}
+
+impl ModelConfig {
+    pub(crate) fn new(id: ModelId) -> Self {
+        Self {
+            id,
+            app_keys: Vec::new(),
+            subscriptions: Vec::new(),
+            publication: None,
+        }
+    }
+}
+
+/// An element of the node: an addressable entity with its models
+/// - `location`: The GATT namespace description of the location of the element
+/// - `models`: The configuration of the models of the element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ElementConfig {
+    pub location: u16,
+    pub models: Vec<ModelConfig>,
+}
+
+/// An application key with its AID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AppKey {
+    pub index: u16,
+    pub net_key_index: u16,
+    pub key: MeshKey,
+    pub aid: u8,
+}
+
+impl AppKey {
+    pub(crate) fn new(index: u16, net_key_index: u16, key: MeshKey) -> Self {
+        Self {
+            index,
+            net_key_index,
+            key,
+            aid: k4(&key),
+        }
+    }
+}
+
+/// Identification of the product of the node, sent in its composition data
+/// - `company_id`: The company identifier assigned by the Bluetooth SIG
+/// - `product_id`: The product identifier assigned by the vendor
+/// - `version_id`: The version of the product assigned by the vendor
+/// - `replay_capacity`: The amount of sources whose sequence numbers are remembered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductInfo {
+    pub company_id: u16,
+    pub product_id: u16,
+    pub version_id: u16,
+    pub replay_capacity: u16,
+}
+
+impl Default for ProductInfo {
+    fn default() -> Self {
+        Self {
+            company_id: 0x02E5,
+            product_id: 0,
+            version_id: 0,
+            replay_capacity: 32,
+        }
+    }
+}
+
+/// Everything the node knows about itself and its network
+/// - `address`: The unicast address of the primary element, or [UNASSIGNED_ADDRESS] while not
+///   provisioned
+/// - `net_keys`: The network keys the node belongs to
+/// - `app_keys`: The application keys added by the configuration client
+/// - `device_key`: The key shared only with the provisioner
+/// - `iv_index`: The current IV index of the network
+/// - `iv_update`: Whether the IV update procedure is in progress
+/// - `seq`: The next sequence number to send
+/// - `default_ttl`: The TTL of the messages sent without a given one
+/// - `relay`: Whether the node relays the messages of other nodes
+/// - `relay_retransmit`: Encoded amount and interval of the retransmissions of relayed messages
+/// - `beacon`: Whether the node sends secure network beacons
+/// - `elements`: The elements of the node, the first one is the primary element
+/// - `product`: The identification of the product
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NodeState {
+    pub address: u16,
+    pub net_keys: Vec<NetworkKey>,
+    pub app_keys: Vec<AppKey>,
+    pub device_key: Option<MeshKey>,
+    pub iv_index: u32,
+    pub iv_update: bool,
+    pub seq: u32,
+    pub default_ttl: u8,
+    pub relay: bool,
+    pub relay_retransmit: u8,
+    pub beacon: bool,
+    pub elements: Vec<ElementConfig>,
+    pub product: ProductInfo,
+}
+
+impl NodeState {
+    /// Creates a new NodeState, not provisioned, whose primary element has the configuration
+    /// server
+    pub(crate) fn new(product: ProductInfo) -> Self {
+        Self {
+            address: UNASSIGNED_ADDRESS,
+            net_keys: Vec::new(),
+            app_keys: Vec::new(),
+            device_key: None,
+            iv_index: 0,
+            iv_update: false,
+            seq: 0,
+            default_ttl: INITIAL_DEFAULT_TTL,
+            relay: true,
+            relay_retransmit: 0,
+            beacon: true,
+            elements: vec![ElementConfig {
+                location: 0,
+                models: vec![ModelConfig::new(ModelId::CONFIG_SERVER)],
+            }],
+            product,
+        }
+    }
+
+    /// Gets whether the node was provisioned into a network
+    pub(crate) fn is_provisioned(&self) -> bool {
+        self.address != UNASSIGNED_ADDRESS
+    }
+
+    /// Joins the network given by the provisioner
+    ///
+    /// # Arguments
+    ///
+    /// - `net_key`: The network key, with its index
+    /// - `iv_index`: The current IV index of the network
+    /// - `iv_update`: Whether the IV update procedure is in progress
+    /// - `address`: The unicast address of the primary element
+    /// - `device_key`: The device key derived during the provisioning
+    pub(crate) fn provision(
+        &mut self,
+        net_key: NetworkKey,
+        iv_index: u32,
+        iv_update: bool,
+        address: u16,
+        device_key: MeshKey,
+    ) {
+        self.net_keys = vec![net_key];
+        self.iv_index = iv_index;
+        self.iv_update = iv_update;
+        self.address = address;
+        self.device_key = Some(device_key);
+        self.seq = 0;
+    }
+
+    /// Leaves the network, forgetting the keys and the configuration of the models. The
+    /// elements and their models are kept.
+    pub(crate) fn reset(&mut self) {
+        let elements = self
+            .elements
+            .iter()
+            .map(|element| ElementConfig {
+                location: element.location,
+                models: element
+                    .models
+                    .iter()
+                    .map(|model| ModelConfig::new(model.id))
+                    .collect(),
+            })
+            .collect();
+        *self = Self {
+            elements,
+            ..Self::new(self.product)
+        };
+    }
+
+    /// Takes the next sequence number
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::SequenceExhausted`: If every sequence number of the IV index was used
+    pub(crate) fn next_seq(&mut self) -> Result<u32, MeshError> {
+        if self.seq > MAX_SEQ {
+            return Err(MeshError::SequenceExhausted);
+        }
+        self.seq += 1;
+        Ok(self.seq - 1)
+    }
+
+    /// Gets the unicast address of an element
+    pub(crate) fn element_address(&self, element: usize) -> u16 {
+        self.address + element as u16
+    }
+
+    /// Gets the index of the element with an address
+    pub(crate) fn element_index(&self, address: u16) -> Option<usize> {
+        let index = address.checked_sub(self.address)? as usize;
+        (self.is_provisioned() && index < self.elements.len()).then_some(index)
+    }
+
+    /// Gets the configuration of a model
+    pub(crate) fn model(&self, element: usize, id: ModelId) -> Option<&ModelConfig> {
+        self.elements
+            .get(element)?
+            .models
+            .iter()
+            .find(|model| model.id == id)
+    }
+
+    /// Gets the configuration of a model to modify it
+    pub(crate) fn model_mut(&mut self, element: usize, id: ModelId) -> Option<&mut ModelConfig> {
+        self.elements
+            .get_mut(element)?
+            .models
+            .iter_mut()
+            .find(|model| model.id == id)
+    }
+
+    /// Gets an application key by its index
+    pub(crate) fn app_key(&self, index: u16) -> Option<&AppKey> {
+        self.app_keys.iter().find(|key| key.index == index)
+    }
+
+    /// Gets the network key an application key is bound to
+    pub(crate) fn net_key_of(&self, app_key: &AppKey) -> Option<&NetworkKey> {
+        self.net_keys
+            .iter()
+            .find(|key| key.index == app_key.net_key_index)
+    }
+
+    /// Gets the TTL of a message, replacing [USE_DEFAULT_TTL] by the default TTL
+    pub(crate) fn ttl_or_default(&self, ttl: u8) -> u8 {
+        match ttl {
+            USE_DEFAULT_TTL => self.default_ttl,
+            ttl => ttl.min(MAX_TTL),
+        }
+    }
+
+    /// Gets the page 0 of the composition data, which describes the elements and models of
+    /// the node
+    pub(crate) fn composition_data(&self) -> Vec<u8> {
+        let product = &self.product;
+        let mut data = [
+            product.company_id,
+            product.product_id,
+            product.version_id,
+            product.replay_capacity,
+            RELAY_FEATURE,
+        ]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<u8>>();
+        for element in &self.elements {
+            let (sig, vendor): (Vec<&ModelConfig>, Vec<&ModelConfig>) =
+                element.models.iter().partition(|model| model.id.is_sig());
+            data.extend_from_slice(&element.location.to_le_bytes());
+            data.push(sig.len() as u8);
+            data.push(vendor.len() as u8);
+            for model in sig.into_iter().chain(vendor) {
+                data.extend(model.id.encode());
+            }
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn node_state_01_composition_data_lists_elements_and_models() {
+        let mut state = NodeState::new(ProductInfo {
+            company_id: 0x02E5,
+            product_id: 0x0001,
+            version_id: 0x0002,
+            replay_capacity: 0x0010,
+        });
+        let vendor = ModelId::Vendor {
+            company_id: 0x02E5,
+            model_id: 0x0001,
+        };
+        state.elements[0].models.push(ModelConfig::new(vendor));
+        state.elements[0]
+            .models
+            .push(ModelConfig::new(ModelId::GENERIC_ONOFF_SERVER));
+        state.elements.push(ElementConfig {
+            location: 0x0102,
+            models: vec![ModelConfig::new(ModelId::GENERIC_LEVEL_SERVER)],
+        });
+        assert_eq!(
+            state.composition_data(),
+            [
+                0xE5, 0x02, 0x01, 0x00, 0x02, 0x00, 0x10, 0x00, 0x01, 0x00, // Header
+                0x00, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x10, 0xE5, 0x02, 0x01, 0x00, // 1st
+                0x02, 0x01, 0x01, 0x00, 0x02, 0x10, // 2nd element
+            ]
+        );
+    }
+
+    #[test]
+    fn node_state_02_encodes_publication_period_and_retransmit() {
+        let publication = Publication::new(0xC000, 0)
+            .period(Duration::from_secs(5))
+            .retransmit(2, Duration::from_millis(100));
+        // 50 steps of 100 ms
+        assert_eq!(publication.period, 50 << 2);
+        assert_eq!(publication.period_duration(), Some(Duration::from_secs(5)));
+        assert_eq!(publication.retransmit_count(), 2);
+        assert_eq!(
+            publication.retransmit_interval(),
+            Duration::from_millis(100)
+        );
+        let long = publication.period(Duration::from_secs(120));
+        assert_eq!(long.period_duration(), Some(Duration::from_secs(120)));
+        assert_eq!(long.period(Duration::ZERO).period_duration(), None);
+    }
+
+    #[test]
+    fn node_state_03_addresses_elements_and_sequence_numbers() {
+        let mut state = NodeState::new(ProductInfo::default());
+        state.elements.push(ElementConfig {
+            location: 0,
+            models: Vec::new(),
+        });
+        assert_eq!(state.element_index(0x0000), None);
+        state.provision(NetworkKey::new(0, [0; 16]), 0, false, 0x1201, [1; 16]);
+        assert_eq!(state.element_index(0x1202), Some(1));
+        assert_eq!(state.element_index(0x1203), None);
+        assert!(is_unicast(0x1201) && !is_unicast(0xC000) && is_group(0xC000));
+
+        state.seq = MAX_SEQ;
+        assert_eq!(state.next_seq().unwrap(), MAX_SEQ);
+        assert!(matches!(
+            state.next_seq(),
+            Err(MeshError::SequenceExhausted)
+        ));
+        state.reset();
+        assert!(!state.is_provisioned());
+        assert_eq!(state.elements.len(), 2);
+    }
+}
@@ -0,0 +1,622 @@
+use p256::{
+    ecdh::diffie_hellman,
+    elliptic_curve::sec1::{EncodedPoint, FromEncodedPoint, ToEncodedPoint},
+    AffinePoint, NistP256, PublicKey, SecretKey,
+};
+
+use super::{
+    mesh_crypto::{aes_cmac, ccm_decrypt, k1, s1, MeshKey, MeshNonce, MicSize},
+    node_state::is_unicast,
+};
+
+const PROVISIONING_INVITE: u8 = 0x00;
+const PROVISIONING_CAPABILITIES: u8 = 0x01;
+const PROVISIONING_START: u8 = 0x02;
+const PROVISIONING_PUBLIC_KEY: u8 = 0x03;
+const PROVISIONING_CONFIRMATION: u8 = 0x05;
+const PROVISIONING_RANDOM: u8 = 0x06;
+const PROVISIONING_DATA: u8 = 0x07;
+const PROVISIONING_COMPLETE: u8 = 0x08;
+const PROVISIONING_FAILED: u8 = 0x09;
+const FIPS_P256_ALGORITHM: u16 = 0x0001;
+const NO_OOB_AUTHENTICATION: u8 = 0x00;
+const STATIC_OOB_AUTHENTICATION: u8 = 0x01;
+const OUTPUT_OOB_AUTHENTICATION: u8 = 0x02;
+const OUTPUT_NUMERIC_ACTION: u8 = 0x03;
+const KEY_REFRESH_FLAG: u8 = 0x01;
+const IV_UPDATE_FLAG: u8 = 0x02;
+const PUBLIC_KEY_SIZE: usize = 64;
+const PROVISIONING_DATA_SIZE: usize = 25;
+
+/// Fills a buffer with random bytes
+pub(crate) type RandomSource = fn(&mut [u8]);
+
+/// How the provisioner authenticates the node while provisioning it
+/// - `NoOob`: The node is not authenticated
+/// - `StaticOob`: Both know a 16 bytes value, as one printed on the device
+/// - `OutputNumeric`: The node shows a random number of up to 8 digits, that the user enters
+///   in the provisioner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningAuth {
+    NoOob,
+    StaticOob([u8; 16]),
+    OutputNumeric { digits: u8 },
+}
+
+/// Reasons the provisioning fails, sent to the provisioner in a Provisioning Failed PDU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningFailure {
+    InvalidPdu = 0x01,
+    InvalidFormat = 0x02,
+    UnexpectedPdu = 0x03,
+    ConfirmationFailed = 0x04,
+    OutOfResources = 0x05,
+    DecryptionFailed = 0x06,
+    UnexpectedError = 0x07,
+    CannotAssignAddresses = 0x08,
+}
+
+/// The network data given by the provisioner
+/// - `net_key`: The network key
+/// - `net_key_index`: The index of the network key
+/// - `key_refresh`: Whether the key refresh procedure is in progress
+/// - `iv_update`: Whether the IV update procedure is in progress
+/// - `iv_index`: The current IV index of the network
+/// - `address`: The unicast address of the primary element
+/// - `device_key`: The device key derived during the provisioning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProvisioningData {
+    pub net_key: MeshKey,
+    pub net_key_index: u16,
+    pub key_refresh: bool,
+    pub iv_update: bool,
+    pub iv_index: u32,
+    pub address: u16,
+    pub device_key: MeshKey,
+}
+
+/// What the node must do after receiving a provisioning PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ProvisioningStep {
+    /// Send a PDU to the provisioner
+    Send(Vec<u8>),
+    /// Show the number of the output OOB authentication to the user
+    ShowNumber(u32),
+    /// The provisioning finished, the node joins the network
+    Complete(ProvisioningData),
+    /// The provisioning failed, the node is still unprovisioned
+    Failed(ProvisioningFailure),
+}
+
+/// State of the provisioning procedure
+enum State {
+    Idle,
+    Invited,
+    Started,
+    KeysExchanged,
+    Confirmed { provisioner_confirmation: MeshKey },
+    Authenticated { provisioning_salt: MeshKey },
+    Done,
+}
+
+/// Provisionee role of the provisioning procedure, by which a provisioner adds the node to its
+/// network. Only the FIPS P-256 algorithm without OOB public key is supported.
+/// - `elements`: The amount of elements of the node
+/// - `auth`: The authentication offered to the provisioner
+/// - `fill_random`: Source of the random values
+/// - `confirmation_inputs`: The invite, capabilities, start and public keys exchanged
+/// - `auth_value`: The value both sides authenticate with
+/// - `secret`: The ephemeral private key of the node
+/// - `ecdh_secret`: The secret shared with the provisioner
+/// - `random`: The random of the node
+pub(crate) struct Provisionee {
+    elements: u8,
+    auth: ProvisioningAuth,
+    fill_random: RandomSource,
+    state: State,
+    confirmation_inputs: Vec<u8>,
+    auth_value: MeshKey,
+    secret: Option<SecretKey>,
+    ecdh_secret: [u8; 32],
+    random: MeshKey,
+}
+
+impl Provisionee {
+    /// Creates a new Provisionee, waiting for an invite
+    pub(crate) fn new(elements: u8, auth: ProvisioningAuth, fill_random: RandomSource) -> Self {
+        Self {
+            elements,
+            auth,
+            fill_random,
+            state: State::Idle,
+            confirmation_inputs: Vec::new(),
+            auth_value: [0; 16],
+            secret: None,
+            ecdh_secret: [0; 32],
+            random: [0; 16],
+        }
+    }
+
+    /// Forgets the procedure in progress, waiting for a new invite
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.elements, self.auth, self.fill_random);
+    }
+
+    /// Gets whether a procedure is in progress
+    pub(crate) fn in_progress(&self) -> bool {
+        !matches!(self.state, State::Idle | State::Done)
+    }
+
+    /// Handles a PDU received from the provisioner
+    ///
+    /// # Returns
+    ///
+    /// The steps the node must take, in order. After a `Failed` or `Complete` step the
+    /// procedure is over.
+    pub(crate) fn handle(&mut self, pdu: &[u8]) -> Vec<ProvisioningStep> {
+        match self.handle_pdu(pdu) {
+            Ok(steps) => steps,
+            Err(failure) => {
+                self.reset();
+                self.state = State::Done;
+                vec![
+                    ProvisioningStep::Send(vec![PROVISIONING_FAILED, failure as u8]),
+                    ProvisioningStep::Failed(failure),
+                ]
+            }
+        }
+    }
+
+    fn handle_pdu(&mut self, pdu: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let (&pdu_type, params) = pdu.split_first().ok_or(ProvisioningFailure::InvalidPdu)?;
+        if pdu_type > PROVISIONING_FAILED {
+            return Err(ProvisioningFailure::InvalidPdu);
+        }
+        match (&self.state, pdu_type) {
+            (State::Idle, PROVISIONING_INVITE) => self.invite(params),
+            (State::Invited, PROVISIONING_START) => self.start(params),
+            (State::Started, PROVISIONING_PUBLIC_KEY) => self.public_key(params),
+            (State::KeysExchanged, PROVISIONING_CONFIRMATION) => self.confirmation(params),
+            (State::Confirmed { .. }, PROVISIONING_RANDOM) => self.random(params),
+            (State::Authenticated { .. }, PROVISIONING_DATA) => self.data(params),
+            // A repeated invite after a failure restarts the procedure
+            (State::Done, PROVISIONING_INVITE) => {
+                self.reset();
+                self.invite(params)
+            }
+            _ => Err(ProvisioningFailure::UnexpectedPdu),
+        }
+    }
+
+    /// Gets the parameters of the capabilities PDU
+    fn capabilities(&self) -> [u8; 11] {
+        let (static_oob, output_size, output_action) = match self.auth {
+            ProvisioningAuth::NoOob => (0, 0, 0),
+            ProvisioningAuth::StaticOob(_) => (1, 0, 0),
+            ProvisioningAuth::OutputNumeric { digits } => {
+                (0, digits.clamp(1, 8), 1 << OUTPUT_NUMERIC_ACTION)
+            }
+        };
+        let algorithms = FIPS_P256_ALGORITHM.to_be_bytes();
+        let output_action = (output_action as u16).to_be_bytes();
+        [
+            self.elements,
+            algorithms[0],
+            algorithms[1],
+            0x00,
+            static_oob,
+            output_size,
+            output_action[0],
+            output_action[1],
+            0x00,
+            0x00,
+            0x00,
+        ]
+    }
+
+    fn invite(&mut self, params: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        if params.len() != 1 {
+            return Err(ProvisioningFailure::InvalidFormat);
+        }
+        let capabilities = self.capabilities();
+        self.confirmation_inputs = [params, &capabilities].concat();
+        self.state = State::Invited;
+        Ok(vec![ProvisioningStep::Send(
+            [&[PROVISIONING_CAPABILITIES], &capabilities[..]].concat(),
+        )])
+    }
+
+    fn start(&mut self, params: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let [algorithm, public_key, method, action, size] = params else {
+            return Err(ProvisioningFailure::InvalidFormat);
+        };
+        if *algorithm != 0 || *public_key != 0 {
+            return Err(ProvisioningFailure::InvalidFormat);
+        }
+        let mut steps = Vec::new();
+        self.auth_value = match (*method, self.auth) {
+            (NO_OOB_AUTHENTICATION, _) if *action == 0 && *size == 0 => [0; 16],
+            (STATIC_OOB_AUTHENTICATION, ProvisioningAuth::StaticOob(value))
+                if *action == 0 && *size == 0 =>
+            {
+                value
+            }
+            (OUTPUT_OOB_AUTHENTICATION, ProvisioningAuth::OutputNumeric { digits })
+                if *action == OUTPUT_NUMERIC_ACTION && (1..=digits.min(8)).contains(size) =>
+            {
+                let number = self.random_number(*size);
+                steps.push(ProvisioningStep::ShowNumber(number));
+                let mut auth_value = [0; 16];
+                auth_value[12..].copy_from_slice(&number.to_be_bytes());
+                auth_value
+            }
+            _ => return Err(ProvisioningFailure::InvalidFormat),
+        };
+        self.confirmation_inputs.extend_from_slice(params);
+        self.state = State::Started;
+        Ok(steps)
+    }
+
+    /// Gets a random number of up to `digits` decimal digits
+    fn random_number(&self, digits: u8) -> u32 {
+        let mut bytes = [0; 4];
+        (self.fill_random)(&mut bytes);
+        u32::from_be_bytes(bytes) % 10_u32.pow(digits as u32)
+    }
+
+    /// Creates a new ephemeral key pair
+    fn generate_secret(&self) -> SecretKey {
+        loop {
+            let mut bytes = [0; 32];
+            (self.fill_random)(&mut bytes);
+            // Fails only for the few values that are not valid scalars
+            if let Ok(secret) = SecretKey::from_bytes(&bytes.into()) {
+                return secret;
+            }
+        }
+    }
+
+    fn public_key(&mut self, params: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let provisioner_key =
+            decode_public_key(params).ok_or(ProvisioningFailure::InvalidFormat)?;
+        let secret = self.generate_secret();
+        let own_key = encode_public_key(&secret.public_key());
+        if own_key == params {
+            return Err(ProvisioningFailure::UnexpectedError);
+        }
+        let shared = diffie_hellman(secret.to_nonzero_scalar(), provisioner_key.as_affine());
+        self.ecdh_secret
+            .copy_from_slice(shared.raw_secret_bytes().as_slice());
+        self.confirmation_inputs.extend_from_slice(params);
+        self.confirmation_inputs.extend_from_slice(&own_key);
+        self.secret = Some(secret);
+        self.state = State::KeysExchanged;
+        Ok(vec![ProvisioningStep::Send(
+            [&[PROVISIONING_PUBLIC_KEY], &own_key[..]].concat(),
+        )])
+    }
+
+    /// Gets the salt and key of the confirmations
+    fn confirmation_key(&self) -> (MeshKey, MeshKey) {
+        let confirmation_salt = s1(&self.confirmation_inputs);
+        let key = k1(&self.ecdh_secret, &confirmation_salt, b"prck");
+        (confirmation_salt, key)
+    }
+
+    fn confirmation(
+        &mut self,
+        params: &[u8],
+    ) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let provisioner_confirmation: MeshKey = params
+            .try_into()
+            .map_err(|_| ProvisioningFailure::InvalidFormat)?;
+        (self.fill_random)(&mut self.random);
+        let (_, key) = self.confirmation_key();
+        let confirmation = aes_cmac(&key, &[&self.random, &self.auth_value]);
+        // A provisioner that sends back the confirmation of the node is not authenticated
+        if confirmation == provisioner_confirmation {
+            return Err(ProvisioningFailure::ConfirmationFailed);
+        }
+        self.state = State::Confirmed {
+            provisioner_confirmation,
+        };
+        Ok(vec![ProvisioningStep::Send(
+            [&[PROVISIONING_CONFIRMATION], &confirmation[..]].concat(),
+        )])
+    }
+
+    fn random(&mut self, params: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let State::Confirmed {
+            provisioner_confirmation,
+        } = self.state
+        else {
+            return Err(ProvisioningFailure::UnexpectedPdu);
+        };
+        let provisioner_random: MeshKey = params
+            .try_into()
+            .map_err(|_| ProvisioningFailure::InvalidFormat)?;
+        let (confirmation_salt, key) = self.confirmation_key();
+        let expected = aes_cmac(&key, &[&provisioner_random, &self.auth_value]);
+        if expected != provisioner_confirmation {
+            return Err(ProvisioningFailure::ConfirmationFailed);
+        }
+        let provisioning_salt =
+            s1(&[&confirmation_salt[..], &provisioner_random, &self.random].concat());
+        self.state = State::Authenticated { provisioning_salt };
+        Ok(vec![ProvisioningStep::Send(
+            [&[PROVISIONING_RANDOM], &self.random[..]].concat(),
+        )])
+    }
+
+    fn data(&mut self, params: &[u8]) -> Result<Vec<ProvisioningStep>, ProvisioningFailure> {
+        let State::Authenticated { provisioning_salt } = self.state else {
+            return Err(ProvisioningFailure::UnexpectedPdu);
+        };
+        if params.len() != PROVISIONING_DATA_SIZE + MicSize::Long.len() {
+            return Err(ProvisioningFailure::InvalidFormat);
+        }
+        let session_key = k1(&self.ecdh_secret, &provisioning_salt, b"prsk");
+        let session_nonce: MeshNonce = k1(&self.ecdh_secret, &provisioning_salt, b"prsn")[3..]
+            .try_into()
+            .unwrap();
+        let data = ccm_decrypt(&session_key, &session_nonce, params, &[], MicSize::Long)
+            .map_err(|_| ProvisioningFailure::DecryptionFailed)?;
+        let flags = data[18];
+        let address = u16::from_be_bytes([data[23], data[24]]);
+        if !is_unicast(address) || !is_unicast(address + self.elements.saturating_sub(1) as u16) {
+            return Err(ProvisioningFailure::CannotAssignAddresses);
+        }
+        let provisioning_data = ProvisioningData {
+            net_key: data[..16].try_into().unwrap(),
+            net_key_index: u16::from_be_bytes([data[16], data[17]]) & 0x0FFF,
+            key_refresh: flags & KEY_REFRESH_FLAG != 0,
+            iv_update: flags & IV_UPDATE_FLAG != 0,
+            iv_index: u32::from_be_bytes(data[19..23].try_into().unwrap()),
+            address,
+            device_key: k1(&self.ecdh_secret, &provisioning_salt, b"prdk"),
+        };
+        self.state = State::Done;
+        self.secret = None;
+        Ok(vec![
+            ProvisioningStep::Send(vec![PROVISIONING_COMPLETE]),
+            ProvisioningStep::Complete(provisioning_data),
+        ])
+    }
+}
+
+/// Parses a public key sent as its X and Y coordinates
+fn decode_public_key(data: &[u8]) -> Option<PublicKey> {
+    if data.len() != PUBLIC_KEY_SIZE {
+        return None;
+    }
+    let point = EncodedPoint::<NistP256>::from_untagged_bytes(data.into());
+    let point: Option<AffinePoint> = AffinePoint::from_encoded_point(&point).into();
+    PublicKey::from_affine(point?).ok()
+}
+
+/// Gets the X and Y coordinates of a public key
+fn encode_public_key(key: &PublicKey) -> Vec<u8> {
+    key.to_encoded_point(false).as_bytes()[1..].to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::mesh_crypto::ccm_encrypt;
+
+    fn fill_counter(buffer: &mut [u8]) {
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            *byte = index as u8 + 1;
+        }
+    }
+
+    /// Provisioner side of the procedure, to drive the provisionee
+    struct TestProvisioner {
+        secret: SecretKey,
+        random: MeshKey,
+        auth_value: MeshKey,
+        inputs: Vec<u8>,
+        ecdh_secret: Vec<u8>,
+    }
+
+    impl TestProvisioner {
+        fn new(auth_value: MeshKey) -> Self {
+            Self {
+                secret: SecretKey::from_bytes(&[0x42; 32].into()).unwrap(),
+                random: [0x55; 16],
+                auth_value,
+                inputs: Vec::new(),
+                ecdh_secret: Vec::new(),
+            }
+        }
+
+        fn send(&mut self, node: &mut Provisionee, pdu: &[u8]) -> Vec<ProvisioningStep> {
+            node.handle(pdu)
+        }
+
+        fn sent_pdu(steps: &[ProvisioningStep]) -> Vec<u8> {
+            match steps.last() {
+                Some(ProvisioningStep::Send(pdu)) => pdu.clone(),
+                other => panic!("expected a PDU, got {:?}", other),
+            }
+        }
+
+        fn exchange_keys(
+            &mut self,
+            node: &mut Provisionee,
+            start: [u8; 5],
+        ) -> Vec<ProvisioningStep> {
+            let capabilities = Self::sent_pdu(&self.send(node, &[PROVISIONING_INVITE, 0x05]));
+            assert_eq!(capabilities[0], PROVISIONING_CAPABILITIES);
+            let start_steps = self.send(node, &[&[PROVISIONING_START], &start[..]].concat());
+            let own_key = encode_public_key(&self.secret.public_key());
+            let node_key = Self::sent_pdu(&self.send(
+                node,
+                &[&[PROVISIONING_PUBLIC_KEY], own_key.as_slice()].concat(),
+            ));
+            self.inputs = [
+                &[0x05],
+                &capabilities[1..],
+                &start,
+                &own_key,
+                &node_key[1..],
+            ]
+            .concat();
+            let node_key = decode_public_key(&node_key[1..]).unwrap();
+            self.ecdh_secret =
+                diffie_hellman(self.secret.to_nonzero_scalar(), node_key.as_affine())
+                    .raw_secret_bytes()
+                    .to_vec();
+            start_steps
+        }
+
+        fn confirmation_key(&self) -> (MeshKey, MeshKey) {
+            let salt = s1(&self.inputs);
+            (salt, k1(&self.ecdh_secret, &salt, b"prck"))
+        }
+
+        fn authenticate(&mut self, node: &mut Provisionee) -> Vec<ProvisioningStep> {
+            let (_, key) = self.confirmation_key();
+            let confirmation = aes_cmac(&key, &[&self.random, &self.auth_value]);
+            let node_confirmation = Self::sent_pdu(&self.send(
+                node,
+                &[&[PROVISIONING_CONFIRMATION], &confirmation[..]].concat(),
+            ));
+            let steps = self.send(node, &[&[PROVISIONING_RANDOM], &self.random[..]].concat());
+            if let Some(ProvisioningStep::Send(random)) = steps.first() {
+                if random[0] == PROVISIONING_RANDOM {
+                    let expected = aes_cmac(&key, &[&random[1..], &self.auth_value]);
+                    assert_eq!(node_confirmation[1..], expected);
+                }
+            }
+            steps
+        }
+
+        fn send_data(
+            &self,
+            node: &mut Provisionee,
+            node_random: &[u8],
+            data: &[u8],
+        ) -> Vec<ProvisioningStep> {
+            let (salt, _) = self.confirmation_key();
+            let provisioning_salt = s1(&[&salt[..], &self.random, node_random].concat());
+            let session_key = k1(&self.ecdh_secret, &provisioning_salt, b"prsk");
+            let nonce: MeshNonce = k1(&self.ecdh_secret, &provisioning_salt, b"prsn")[3..]
+                .try_into()
+                .unwrap();
+            let encrypted = ccm_encrypt(&session_key, &nonce, data, &[], MicSize::Long);
+            node.handle(&[&[PROVISIONING_DATA], encrypted.as_slice()].concat())
+        }
+
+        fn device_key(&self, node_random: &[u8]) -> MeshKey {
+            let (salt, _) = self.confirmation_key();
+            let provisioning_salt = s1(&[&salt[..], &self.random, node_random].concat());
+            k1(&self.ecdh_secret, &provisioning_salt, b"prdk")
+        }
+    }
+
+    #[test]
+    fn provisioning_01_completes_with_static_oob() {
+        let static_oob = [0x77; 16];
+        let mut node = Provisionee::new(2, ProvisioningAuth::StaticOob(static_oob), fill_counter);
+        let mut provisioner = TestProvisioner::new(static_oob);
+        provisioner.exchange_keys(
+            &mut node,
+            [0x00, 0x00, STATIC_OOB_AUTHENTICATION, 0x00, 0x00],
+        );
+        assert_eq!(provisioner.inputs[1..3], [0x02, 0x00]);
+        let steps = provisioner.authenticate(&mut node);
+        let node_random = TestProvisioner::sent_pdu(&steps)[1..].to_vec();
+
+        let mut data = vec![0x7D; 16];
+        data.extend_from_slice(&[
+            0x00,
+            0x01,
+            IV_UPDATE_FLAG,
+            0x12,
+            0x34,
+            0x56,
+            0x78,
+            0x0B,
+            0x0C,
+        ]);
+        let steps = provisioner.send_data(&mut node, &node_random, &data);
+        assert_eq!(
+            steps[0],
+            ProvisioningStep::Send(vec![PROVISIONING_COMPLETE])
+        );
+        assert_eq!(
+            steps[1],
+            ProvisioningStep::Complete(ProvisioningData {
+                net_key: [0x7D; 16],
+                net_key_index: 0x0001,
+                key_refresh: false,
+                iv_update: true,
+                iv_index: 0x12345678,
+                address: 0x0B0C,
+                device_key: provisioner.device_key(&node_random),
+            })
+        );
+        assert!(!node.in_progress());
+    }
+
+    #[test]
+    fn provisioning_02_fails_with_wrong_auth_value() {
+        let mut node = Provisionee::new(
+            1,
+            ProvisioningAuth::OutputNumeric { digits: 4 },
+            fill_counter,
+        );
+        let mut provisioner = TestProvisioner::new([0; 16]);
+        let steps = provisioner.exchange_keys(
+            &mut node,
+            [
+                0x00,
+                0x00,
+                OUTPUT_OOB_AUTHENTICATION,
+                OUTPUT_NUMERIC_ACTION,
+                0x04,
+            ],
+        );
+        // The counter source gives 0x01020304 = 16909060, whose last 4 digits are shown
+        assert_eq!(steps, [ProvisioningStep::ShowNumber(9060)]);
+        assert!(node.in_progress());
+
+        let steps = provisioner.authenticate(&mut node);
+        assert_eq!(
+            steps,
+            [
+                ProvisioningStep::Send(vec![PROVISIONING_FAILED, 0x04]),
+                ProvisioningStep::Failed(ProvisioningFailure::ConfirmationFailed)
+            ]
+        );
+        assert!(!node.in_progress());
+    }
+
+    #[test]
+    fn provisioning_03_rejects_unexpected_and_invalid_pdus() {
+        let mut node = Provisionee::new(1, ProvisioningAuth::NoOob, fill_counter);
+        assert_eq!(
+            node.handle(&[PROVISIONING_START, 0, 0, 0, 0, 0])[1],
+            ProvisioningStep::Failed(ProvisioningFailure::UnexpectedPdu)
+        );
+        node.handle(&[PROVISIONING_INVITE, 0x00]);
+        // Static OOB was not offered
+        assert_eq!(
+            node.handle(&[PROVISIONING_START, 0, 0, STATIC_OOB_AUTHENTICATION, 0, 0])[1],
+            ProvisioningStep::Failed(ProvisioningFailure::InvalidFormat)
+        );
+        node.handle(&[PROVISIONING_INVITE, 0x00]);
+        node.handle(&[PROVISIONING_START, 0, 0, 0, 0, 0]);
+        let mut invalid_key = vec![PROVISIONING_PUBLIC_KEY];
+        invalid_key.extend_from_slice(&[0x01; 64]);
+        assert_eq!(
+            node.handle(&invalid_key)[1],
+            ProvisioningStep::Failed(ProvisioningFailure::InvalidFormat)
+        );
+        assert_eq!(
+            node.handle(&[0x0A])[1],
+            ProvisioningStep::Failed(ProvisioningFailure::InvalidPdu)
+        );
+    }
+}
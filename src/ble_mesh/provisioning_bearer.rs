@@ -0,0 +1,519 @@
+use std::time::{Duration, Instant};
+
+use super::MeshError;
+
+const TRANSACTION_START: u8 = 0b00;
+const TRANSACTION_ACK: u8 = 0b01;
+const TRANSACTION_CONTINUATION: u8 = 0b10;
+const BEARER_CONTROL: u8 = 0b11;
+const LINK_OPEN: u8 = 0x00;
+const LINK_ACK: u8 = 0x01;
+const LINK_CLOSE: u8 = 0x02;
+/// Link Close reason of a link closed after the provisioning failed
+const CLOSE_REASON_FAIL: u8 = 0x02;
+/// Biggest generic provisioning PDU sent in a PB-ADV advertisement
+const MAX_GENERIC_PDU_SIZE: usize = 24;
+const START_HEADER_SIZE: usize = 4;
+const CONTINUATION_HEADER_SIZE: usize = 1;
+/// First transaction number of the node, the provisioner uses the numbers below it
+const FIRST_NODE_TRANSACTION: u8 = 0x80;
+const UNPROVISIONED_DEVICE_BEACON: u8 = 0x00;
+/// Time between the retransmissions of a transaction not acknowledged
+const TRANSACTION_RETRANSMIT: Duration = Duration::from_millis(500);
+/// Time after which a link without activity is closed
+const LINK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Proxy PDU type of the provisioning PDUs sent through PB-GATT
+pub(crate) const PROXY_PROVISIONING_PDU: u8 = 0x03;
+const SAR_COMPLETE: u8 = 0b00;
+const SAR_FIRST: u8 = 0b01;
+const SAR_CONTINUATION: u8 = 0b10;
+const SAR_LAST: u8 = 0b11;
+
+/// Gets the frame check sequence of a provisioning PDU: the CRC-8 of 3GPP TS 27.010
+pub(crate) fn fcs(data: &[u8]) -> u8 {
+    let mut crc = 0xFF_u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = match crc & 0x01 {
+                0 => crc >> 1,
+                _ => (crc >> 1) ^ 0xE0,
+            };
+        }
+    }
+    0xFF - crc
+}
+
+/// Creates the unprovisioned device beacon, with which provisioners find the node
+///
+/// # Arguments
+///
+/// - `uuid`: The UUID of the node
+/// - `oob_info`: Where the OOB information of the node is available
+pub(crate) fn unprovisioned_device_beacon(uuid: &[u8; 16], oob_info: u16) -> Vec<u8> {
+    [
+        &[UNPROVISIONED_DEVICE_BEACON],
+        &uuid[..],
+        &oob_info.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// Splits a provisioning PDU into the generic provisioning PDUs of a transaction
+fn segment_transaction(pdu: &[u8]) -> Vec<Vec<u8>> {
+    let first_size = MAX_GENERIC_PDU_SIZE - START_HEADER_SIZE;
+    let continuation_size = MAX_GENERIC_PDU_SIZE - CONTINUATION_HEADER_SIZE;
+    let (first, rest) = pdu.split_at(pdu.len().min(first_size));
+    let continuations: Vec<&[u8]> = rest.chunks(continuation_size).collect();
+    let length = (pdu.len() as u16).to_be_bytes();
+    let mut segments = vec![[
+        &[((continuations.len() as u8) << 2) | TRANSACTION_START],
+        &length[..],
+        &[fcs(pdu)],
+        first,
+    ]
+    .concat()];
+    for (index, data) in continuations.into_iter().enumerate() {
+        segments.push(
+            [
+                &[(((index + 1) as u8) << 2) | TRANSACTION_CONTINUATION],
+                data,
+            ]
+            .concat(),
+        );
+    }
+    segments
+}
+
+/// What the node must do after an update of a PB-ADV link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LinkEvent {
+    /// Advertise a PB-ADV payload
+    Send(Vec<u8>),
+    /// A provisioning PDU was received
+    Received(Vec<u8>),
+    /// The provisioner opened the link
+    Opened,
+    /// The link was closed, by the provisioner or because of a timeout
+    Closed,
+}
+
+/// A transaction of the provisioner being received
+struct IncomingTransaction {
+    number: u8,
+    length: usize,
+    fcs: u8,
+    segments: Vec<Option<Vec<u8>>>,
+}
+
+/// A transaction of the node, sent again until acknowledged
+struct OutgoingTransaction {
+    number: u8,
+    payloads: Vec<Vec<u8>>,
+    next_attempt: Instant,
+}
+
+/// The node side of a PB-ADV link, that carries the provisioning PDUs in advertisements
+/// - `uuid`: The UUID of the node, the provisioner opens a link with it
+/// - `link_id`: The id of the open link
+/// - `incoming`: The transaction of the provisioner being received
+/// - `last_incoming`: The number of the last transaction of the provisioner received
+/// - `outgoing`: The transaction of the node not yet acknowledged
+/// - `next_number`: The number of the next transaction of the node
+/// - `last_activity`: When the provisioner sent the last PDU
+pub(crate) struct PbAdvLink {
+    uuid: [u8; 16],
+    link_id: Option<u32>,
+    incoming: Option<IncomingTransaction>,
+    last_incoming: Option<u8>,
+    outgoing: Option<OutgoingTransaction>,
+    next_number: u8,
+    last_activity: Instant,
+}
+
+impl PbAdvLink {
+    /// Creates a new PbAdvLink, waiting for a provisioner to open it
+    pub(crate) fn new(uuid: [u8; 16], now: Instant) -> Self {
+        Self {
+            uuid,
+            link_id: None,
+            incoming: None,
+            last_incoming: None,
+            outgoing: None,
+            next_number: FIRST_NODE_TRANSACTION,
+            last_activity: now,
+        }
+    }
+
+    /// Gets whether a provisioner opened the link
+    pub(crate) fn is_open(&self) -> bool {
+        self.link_id.is_some()
+    }
+
+    /// Wraps a generic provisioning PDU in a PB-ADV payload of the link
+    fn payload(&self, link_id: u32, number: u8, generic_pdu: &[u8]) -> Vec<u8> {
+        [&link_id.to_be_bytes()[..], &[number], generic_pdu].concat()
+    }
+
+    /// Handles a PB-ADV payload received
+    ///
+    /// # Returns
+    ///
+    /// The events of the link, in order
+    pub(crate) fn receive(&mut self, payload: &[u8], now: Instant) -> Vec<LinkEvent> {
+        if payload.len() < 6 {
+            return Vec::new();
+        }
+        let link_id = u32::from_be_bytes(payload[..4].try_into().unwrap());
+        let number = payload[4];
+        let generic_pdu = &payload[5..];
+        let control = generic_pdu[0];
+        match (control & 0x03, self.link_id) {
+            (BEARER_CONTROL, _) => {
+                self.bearer_control(link_id, control >> 2, &generic_pdu[1..], now)
+            }
+            (_, Some(open)) if open == link_id => {
+                self.last_activity = now;
+                match control & 0x03 {
+                    TRANSACTION_ACK => {
+                        if self
+                            .outgoing
+                            .as_ref()
+                            .is_some_and(|outgoing| outgoing.number == number)
+                        {
+                            self.outgoing = None;
+                        }
+                        Vec::new()
+                    }
+                    _ => self.transaction_segment(link_id, number, generic_pdu),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn bearer_control(
+        &mut self,
+        link_id: u32,
+        opcode: u8,
+        params: &[u8],
+        now: Instant,
+    ) -> Vec<LinkEvent> {
+        let ack = LinkEvent::Send(self.payload(link_id, 0, &[(LINK_ACK << 2) | BEARER_CONTROL]));
+        match (opcode, self.link_id) {
+            (LINK_OPEN, None) if params == self.uuid => {
+                *self = Self::new(self.uuid, now);
+                self.link_id = Some(link_id);
+                vec![ack, LinkEvent::Opened]
+            }
+            // The provisioner did not get the Link Ack
+            (LINK_OPEN, Some(open)) if open == link_id => vec![ack],
+            (LINK_CLOSE, Some(open)) if open == link_id => {
+                *self = Self::new(self.uuid, now);
+                vec![LinkEvent::Closed]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn transaction_segment(
+        &mut self,
+        link_id: u32,
+        number: u8,
+        generic_pdu: &[u8],
+    ) -> Vec<LinkEvent> {
+        let ack = LinkEvent::Send(self.payload(link_id, number, &[TRANSACTION_ACK]));
+        if self.last_incoming == Some(number) {
+            // The provisioner did not get the acknowledgment
+            return vec![ack];
+        }
+        let control = generic_pdu[0];
+        let (index, data) = match control & 0x03 {
+            TRANSACTION_START if generic_pdu.len() > START_HEADER_SIZE => {
+                let length = u16::from_be_bytes([generic_pdu[1], generic_pdu[2]]) as usize;
+                if self.incoming.as_ref().map(|incoming| incoming.number) != Some(number) {
+                    self.incoming = Some(IncomingTransaction {
+                        number,
+                        length,
+                        fcs: generic_pdu[3],
+                        segments: vec![None; (control >> 2) as usize + 1],
+                    });
+                }
+                (0, &generic_pdu[START_HEADER_SIZE..])
+            }
+            TRANSACTION_CONTINUATION => ((control >> 2) as usize, &generic_pdu[1..]),
+            _ => return Vec::new(),
+        };
+        let Some(incoming) = self
+            .incoming
+            .as_mut()
+            .filter(|incoming| incoming.number == number)
+        else {
+            // A continuation received before the start of its transaction
+            return Vec::new();
+        };
+        let Some(segment) = incoming.segments.get_mut(index) else {
+            return Vec::new();
+        };
+        *segment = Some(data.to_vec());
+        if incoming.segments.iter().any(Option::is_none) {
+            return Vec::new();
+        }
+        let pdu: Vec<u8> = incoming
+            .segments
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+        let valid = pdu.len() == incoming.length && fcs(&pdu) == incoming.fcs;
+        self.incoming = None;
+        if !valid {
+            return Vec::new();
+        }
+        self.last_incoming = Some(number);
+        vec![ack, LinkEvent::Received(pdu)]
+    }
+
+    /// Sends a provisioning PDU to the provisioner, as a new transaction of the node
+    ///
+    /// # Returns
+    ///
+    /// The events with the payloads to advertise, or no events if the link is not open
+    pub(crate) fn send(&mut self, pdu: &[u8], now: Instant) -> Vec<LinkEvent> {
+        let Some(link_id) = self.link_id else {
+            return Vec::new();
+        };
+        let number = self.next_number;
+        self.next_number = self.next_number.wrapping_add(1).max(FIRST_NODE_TRANSACTION);
+        let payloads: Vec<Vec<u8>> = segment_transaction(pdu)
+            .iter()
+            .map(|generic_pdu| self.payload(link_id, number, generic_pdu))
+            .collect();
+        let events = payloads.iter().cloned().map(LinkEvent::Send).collect();
+        self.outgoing = Some(OutgoingTransaction {
+            number,
+            payloads,
+            next_attempt: now + TRANSACTION_RETRANSMIT,
+        });
+        events
+    }
+
+    /// Closes the link after the provisioning failed
+    ///
+    /// # Returns
+    ///
+    /// The events with the Link Close to advertise
+    pub(crate) fn close(&mut self, now: Instant) -> Vec<LinkEvent> {
+        let Some(link_id) = self.link_id else {
+            return Vec::new();
+        };
+        let close = self.payload(
+            link_id,
+            0,
+            &[(LINK_CLOSE << 2) | BEARER_CONTROL, CLOSE_REASON_FAIL],
+        );
+        *self = Self::new(self.uuid, now);
+        vec![LinkEvent::Send(close), LinkEvent::Closed]
+    }
+
+    /// Retransmits the transaction of the node not acknowledged, and closes the link if the
+    /// provisioner is gone
+    ///
+    /// # Returns
+    ///
+    /// The events of the link, in order
+    pub(crate) fn poll(&mut self, now: Instant) -> Vec<LinkEvent> {
+        if self.is_open() && now.duration_since(self.last_activity) >= LINK_TIMEOUT {
+            return self.close(now);
+        }
+        let Some(outgoing) = self.outgoing.as_mut() else {
+            return Vec::new();
+        };
+        if now < outgoing.next_attempt {
+            return Vec::new();
+        }
+        outgoing.next_attempt = now + TRANSACTION_RETRANSMIT;
+        outgoing
+            .payloads
+            .iter()
+            .cloned()
+            .map(LinkEvent::Send)
+            .collect()
+    }
+}
+
+/// Splits a message into proxy PDUs that fit in the GATT writes and notifications
+///
+/// # Arguments
+///
+/// - `message_type`: The type of the proxy PDU
+/// - `data`: The message
+/// - `max_size`: The biggest proxy PDU, the ATT MTU minus 3
+pub(crate) fn segment_proxy_pdu(message_type: u8, data: &[u8], max_size: usize) -> Vec<Vec<u8>> {
+    let chunk_size = max_size.max(2) - 1;
+    let chunks: Vec<&[u8]> = data.chunks(chunk_size).collect();
+    let last = chunks.len().saturating_sub(1);
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let sar = match (index, last) {
+                (0, 0) => SAR_COMPLETE,
+                (0, _) => SAR_FIRST,
+                (index, last) if index == last => SAR_LAST,
+                _ => SAR_CONTINUATION,
+            };
+            [&[(sar << 6) | (message_type & 0x3F)], *chunk].concat()
+        })
+        .collect()
+}
+
+/// Joins the proxy PDUs of a message received through GATT
+pub(crate) struct ProxyReassembler {
+    message: Option<(u8, Vec<u8>)>,
+}
+
+impl ProxyReassembler {
+    pub(crate) fn new() -> Self {
+        Self { message: None }
+    }
+
+    /// Adds a proxy PDU
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the type and data of the message if it is complete, or a `MeshError` if
+    /// the PDU is not in the expected order
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidPdu`: If the PDU is empty, a continuation without a first PDU, or of
+    ///   another type than the message being received
+    pub(crate) fn push(&mut self, pdu: &[u8]) -> Result<Option<(u8, Vec<u8>)>, MeshError> {
+        let (&header, data) = pdu.split_first().ok_or(MeshError::InvalidPdu)?;
+        let message_type = header & 0x3F;
+        match (header >> 6, self.message.as_mut()) {
+            (SAR_COMPLETE, None) => Ok(Some((message_type, data.to_vec()))),
+            (SAR_FIRST, None) => {
+                self.message = Some((message_type, data.to_vec()));
+                Ok(None)
+            }
+            (SAR_CONTINUATION, Some((current, message))) if *current == message_type => {
+                message.extend_from_slice(data);
+                Ok(None)
+            }
+            (SAR_LAST, Some((current, message))) if *current == message_type => {
+                message.extend_from_slice(data);
+                Ok(self.message.take())
+            }
+            _ => {
+                self.message = None;
+                Err(MeshError::InvalidPdu)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UUID: [u8; 16] = [0xDD; 16];
+    const LINK_ID: u32 = 0x12345678;
+
+    fn open_link(now: Instant) -> PbAdvLink {
+        let mut link = PbAdvLink::new(UUID, now);
+        let open = [&LINK_ID.to_be_bytes()[..], &[0x00, 0x03], &UUID].concat();
+        let events = link.receive(&open, now);
+        assert_eq!(
+            events,
+            [
+                LinkEvent::Send(vec![0x12, 0x34, 0x56, 0x78, 0x00, 0x07]),
+                LinkEvent::Opened
+            ]
+        );
+        link
+    }
+
+    #[test]
+    fn provisioning_bearer_01_fcs_matches_sample_data() {
+        assert_eq!(fcs(&[0x03, 0x3F, 0x01]), 0x1C);
+        assert_eq!(
+            unprovisioned_device_beacon(&UUID, 0x0040)[16..],
+            [0xDD, 0x00, 0x40]
+        );
+    }
+
+    #[test]
+    fn provisioning_bearer_02_receives_segmented_transactions() {
+        let now = Instant::now();
+        let mut link = open_link(now);
+        let pdu: Vec<u8> = (0..65).collect();
+        let segments = segment_transaction(&pdu);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0][..4], [0x08, 0x00, 65, fcs(&pdu)]);
+        assert_eq!(segments[2][0], (2 << 2) | TRANSACTION_CONTINUATION);
+
+        let payloads: Vec<Vec<u8>> = segments
+            .iter()
+            .map(|segment| link.payload(LINK_ID, 0x01, segment))
+            .collect();
+        // A continuation received before the start is dropped, until it is retransmitted
+        assert!(link.receive(&payloads[2], now).is_empty());
+        assert!(link.receive(&payloads[0], now).is_empty());
+        assert!(link.receive(&payloads[1], now).is_empty());
+        let ack = LinkEvent::Send(vec![0x12, 0x34, 0x56, 0x78, 0x01, TRANSACTION_ACK]);
+        assert_eq!(
+            link.receive(&payloads[2], now),
+            [ack.clone(), LinkEvent::Received(pdu)]
+        );
+        // A retransmitted segment is only acknowledged again
+        assert_eq!(link.receive(&payloads[1], now), [ack]);
+        // Other links are ignored
+        let other = [&[0, 0, 0, 1][..], &payloads[0][4..]].concat();
+        assert!(link.receive(&other, now).is_empty());
+    }
+
+    #[test]
+    fn provisioning_bearer_03_retransmits_until_acknowledged_and_times_out() {
+        let now = Instant::now();
+        let mut link = open_link(now);
+        let sent = link.send(&[0x01; 12], now);
+        assert_eq!(sent.len(), 1);
+        assert!(link.poll(now).is_empty());
+        assert_eq!(link.poll(now + TRANSACTION_RETRANSMIT), sent);
+        let ack = [&LINK_ID.to_be_bytes()[..], &[0x80, TRANSACTION_ACK]].concat();
+        link.receive(&ack, now);
+        assert!(link.poll(now + TRANSACTION_RETRANSMIT * 2).is_empty());
+
+        let events = link.poll(now + LINK_TIMEOUT);
+        assert_eq!(events[1], LinkEvent::Closed);
+        assert!(!link.is_open());
+        let close = [&LINK_ID.to_be_bytes()[..], &[0x00, 0x0B, 0x00]].concat();
+        assert!(link.receive(&close, now).is_empty());
+    }
+
+    #[test]
+    fn provisioning_bearer_04_segments_and_joins_proxy_pdus() {
+        let data: Vec<u8> = (0..45).collect();
+        let pdus = segment_proxy_pdu(PROXY_PROVISIONING_PDU, &data, 20);
+        let headers: Vec<u8> = pdus.iter().map(|pdu| pdu[0]).collect();
+        assert_eq!(headers, [0x43, 0x83, 0xC3]);
+        let mut reassembler = ProxyReassembler::new();
+        assert_eq!(reassembler.push(&pdus[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&pdus[1]).unwrap(), None);
+        assert_eq!(
+            reassembler.push(&pdus[2]).unwrap(),
+            Some((PROXY_PROVISIONING_PDU, data))
+        );
+        assert_eq!(
+            reassembler.push(&[0x03, 0x00]).unwrap(),
+            Some((PROXY_PROVISIONING_PDU, vec![0x00]))
+        );
+        assert!(matches!(
+            reassembler.push(&[0x83, 0x00]),
+            Err(MeshError::InvalidPdu)
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use super::provisioning_bearer::{segment_proxy_pdu, ProxyReassembler, PROXY_PROVISIONING_PDU};
+use crate::{
+    ble::{
+        utils::{AdvertisementBuilder, Characteristic, Service, DEFAULT_MTU},
+        BleError, BleId, BleServer,
+    },
+    utils::notification::Notifier,
+};
+
+/// Id of the Mesh Provisioning Service
+pub const MESH_PROVISIONING_SERVICE_ID: BleId = BleId::FromUuid16(0x1827);
+/// Id of the Mesh Provisioning Data In characteristic, written by the provisioner
+pub const MESH_PROVISIONING_DATA_IN_ID: BleId = BleId::FromUuid16(0x2ADB);
+/// Id of the Mesh Provisioning Data Out characteristic, notified by the node
+pub const MESH_PROVISIONING_DATA_OUT_ID: BleId = BleId::FromUuid16(0x2ADC);
+/// Flags of the advertisement: general discoverable, BR/EDR not supported
+const ADVERTISING_FLAGS: u8 = 0x06;
+/// Size of the ATT header of a notification
+const ATT_HEADER_SIZE: u16 = 3;
+
+/// The PB-GATT bearer, the Mesh Provisioning Service through which a provisioner connected to
+/// the node provisions it
+/// - `server`: The BleServer offering the service
+/// - `data_out`: The characteristic notified with the PDUs of the node
+/// - `written`: The proxy PDUs written by the provisioner, not yet handled
+/// - `reassembler`: Joins the provisioning PDUs split in many proxy PDUs
+/// - `disconnected`: Whether the provisioner disconnected since the last update
+pub(crate) struct ProvisioningService<'a> {
+    server: BleServer<'a>,
+    data_out: Characteristic,
+    written: Arc<Mutex<Vec<Vec<u8>>>>,
+    reassembler: ProxyReassembler,
+    disconnected: Rc<Cell<bool>>,
+}
+
+impl<'a> ProvisioningService<'a> {
+    /// Sets the Mesh Provisioning Service on the server and advertises it
+    ///
+    /// # Arguments
+    ///
+    /// - `server`: The BleServer that will offer the service
+    /// - `uuid`: The UUID of the node, advertised as service data
+    /// - `oob_info`: Where the OOB information of the node is available
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] after a write
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service could not be set on the server
+    /// - `BleError::AdvertisementDoesNotFit`: If the advertisement is too long
+    pub(crate) fn new(
+        mut server: BleServer<'a>,
+        uuid: &[u8; 16],
+        oob_info: u16,
+        notifier: Notifier,
+    ) -> Result<Self, BleError> {
+        let data_in =
+            Characteristic::new(&MESH_PROVISIONING_DATA_IN_ID, vec![]).writable_no_rsp(true);
+        let data_out = Characteristic::new(&MESH_PROVISIONING_DATA_OUT_ID, vec![]).notifiable(true);
+        let service = Service::new(&MESH_PROVISIONING_SERVICE_ID, vec![])?
+            .add_characteristics(&vec![data_in, data_out.clone()]);
+        server.set_service(&service)?;
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let writes = written.clone();
+        server.on_raw_write(
+            &MESH_PROVISIONING_SERVICE_ID,
+            &MESH_PROVISIONING_DATA_IN_ID,
+            move |data| {
+                if let Ok(mut writes) = writes.lock() {
+                    writes.push(data.to_vec());
+                    notifier.notify();
+                }
+            },
+        )?;
+        let disconnected = Rc::new(Cell::new(false));
+        let lost = disconnected.clone();
+        server.disconnect_handler(move |_, _| lost.set(true));
+
+        let service_data = [&uuid[..], &oob_info.to_be_bytes()].concat();
+        server.set_advertisement(
+            &AdvertisementBuilder::new()
+                .flags(Some(ADVERTISING_FLAGS))
+                .service_uuid(&MESH_PROVISIONING_SERVICE_ID)
+                .service_data(&MESH_PROVISIONING_SERVICE_ID, &service_data),
+        )?;
+        Ok(Self {
+            server,
+            data_out,
+            written,
+            reassembler: ProxyReassembler::new(),
+            disconnected,
+        })
+    }
+
+    /// Starts advertising the service
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::StartingAdvertisementError`: If the advertising could not be started
+    pub(crate) fn start(&mut self) -> Result<(), BleError> {
+        self.server.start()
+    }
+
+    /// Stops advertising the service once the node joined the network. The provisioner
+    /// disconnects by itself after receiving the last PDU.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::StoppingFailure`: If the advertising could not be stopped
+    pub(crate) fn stop(&mut self) -> Result<(), BleError> {
+        self.server.stop_advertisement()
+    }
+
+    /// Gets whether the provisioner disconnected since the last call
+    pub(crate) fn take_disconnection(&mut self) -> bool {
+        self.disconnected.replace(false)
+    }
+
+    /// Takes the provisioning PDUs completely received since the last call. Malformed proxy PDUs
+    /// and those of other types are dropped.
+    pub(crate) fn received(&mut self) -> Vec<Vec<u8>> {
+        let written: Vec<Vec<u8>> = match self.written.lock() {
+            Ok(mut written) => written.drain(..).collect(),
+            Err(_) => return Vec::new(),
+        };
+        written
+            .iter()
+            .filter_map(|pdu| self.reassembler.push(pdu).ok().flatten())
+            .filter(|(message_type, _)| *message_type == PROXY_PROVISIONING_PDU)
+            .map(|(_, pdu)| pdu)
+            .collect()
+    }
+
+    /// Sends a provisioning PDU to the provisioner, split in notifications that fit the MTU
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::ServiceNotFound`: If the service is no longer set on the server
+    pub(crate) fn send(&mut self, pdu: &[u8]) -> Result<(), BleError> {
+        let mtu = self
+            .server
+            .list_clients()
+            .iter()
+            .map(|client| client.mtu)
+            .min()
+            .unwrap_or(DEFAULT_MTU);
+        let max_size = mtu.saturating_sub(ATT_HEADER_SIZE) as usize;
+        for proxy_pdu in segment_proxy_pdu(PROXY_PROVISIONING_PDU, pdu, max_size) {
+            self.data_out.update_data(proxy_pdu);
+            self.server
+                .notify_value(&MESH_PROVISIONING_SERVICE_ID, &self.data_out)?;
+        }
+        Ok(())
+    }
+
+    /// Gets the server, to execute its handlers on the updates of the node
+    pub(crate) fn server(&mut self) -> &mut BleServer<'a> {
+        &mut self.server
+    }
+}
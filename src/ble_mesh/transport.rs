@@ -0,0 +1,761 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::{mesh_crypto::MeshNonce, MeshError};
+
+/// Biggest upper transport PDU sent unsegmented, with its 4 bytes TransMIC
+pub(crate) const MAX_UNSEGMENTED_ACCESS_SIZE: usize = 15;
+/// Biggest access payload, which fills 32 segments with a 4 bytes TransMIC
+pub(crate) const MAX_ACCESS_PAYLOAD_SIZE: usize = 380;
+const ACCESS_SEGMENT_SIZE: usize = 12;
+const CONTROL_SEGMENT_SIZE: usize = 8;
+const MAX_SEGMENTS: usize = 32;
+const SEQ_ZERO_MASK: u32 = 0x1FFF;
+pub(crate) const SEGMENT_ACK_OPCODE: u8 = 0x00;
+const APPLICATION_NONCE: u8 = 0x01;
+const DEVICE_NONCE: u8 = 0x02;
+/// Time after which an incomplete segmented message is discarded
+const INCOMPLETE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Amount of completed segmented messages remembered, to acknowledge their late segments
+const COMPLETED_CAPACITY: usize = 16;
+
+/// A lower transport PDU, the payload of a network PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LowerTransportPdu {
+    UnsegmentedAccess {
+        akf: bool,
+        aid: u8,
+        upper_pdu: Vec<u8>,
+    },
+    SegmentedAccess {
+        akf: bool,
+        aid: u8,
+        szmic: bool,
+        header: SegmentHeader,
+        segment: Vec<u8>,
+    },
+    UnsegmentedControl {
+        opcode: u8,
+        params: Vec<u8>,
+    },
+    SegmentedControl {
+        opcode: u8,
+        header: SegmentHeader,
+        segment: Vec<u8>,
+    },
+}
+
+/// Position of a segment in its message
+/// - `seq_zero`: The 13 least significant bits of the sequence number of the first segment
+/// - `seg_o`: The index of the segment
+/// - `seg_n`: The index of the last segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SegmentHeader {
+    pub seq_zero: u16,
+    pub seg_o: u8,
+    pub seg_n: u8,
+}
+
+impl SegmentHeader {
+    /// Reads the 3 bytes with the SeqZero, SegO and SegN of a segment. The first bit is the
+    /// SZMIC of access messages.
+    fn decode(data: &[u8]) -> (bool, Self) {
+        let value = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+        let header = SegmentHeader {
+            seq_zero: ((value >> 10) & SEQ_ZERO_MASK) as u16,
+            seg_o: ((value >> 5) & 0x1F) as u8,
+            seg_n: (value & 0x1F) as u8,
+        };
+        (value & 0x80_0000 != 0, header)
+    }
+
+    /// Writes the SeqZero, SegO and SegN of a segment, with the first bit set by `szmic`
+    fn encode(&self, szmic: bool) -> [u8; 3] {
+        let value = ((szmic as u32) << 23)
+            | ((self.seq_zero as u32 & SEQ_ZERO_MASK) << 10)
+            | ((self.seg_o as u32 & 0x1F) << 5)
+            | (self.seg_n as u32 & 0x1F);
+        value.to_be_bytes()[1..].try_into().unwrap()
+    }
+}
+
+impl LowerTransportPdu {
+    /// Parses a lower transport PDU
+    ///
+    /// # Arguments
+    ///
+    /// - `ctl`: Whether the network PDU was a control message
+    /// - `data`: The decrypted transport PDU of the network PDU
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the PDU, or a `MeshError` if the data is not valid
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidPdu`: If the data is too short, or a segment is not consistent
+    pub(crate) fn decode(ctl: bool, data: &[u8]) -> Result<Self, MeshError> {
+        let Some((&first, rest)) = data.split_first() else {
+            return Err(MeshError::InvalidPdu);
+        };
+        let segmented = first & 0x80 != 0;
+        if !segmented {
+            return Ok(match ctl {
+                true => LowerTransportPdu::UnsegmentedControl {
+                    opcode: first & 0x7F,
+                    params: rest.to_vec(),
+                },
+                false => LowerTransportPdu::UnsegmentedAccess {
+                    akf: first & 0x40 != 0,
+                    aid: first & 0x3F,
+                    upper_pdu: rest.to_vec(),
+                },
+            });
+        }
+        if rest.len() < 4 {
+            return Err(MeshError::InvalidPdu);
+        }
+        let (szmic, header) = SegmentHeader::decode(rest);
+        if header.seg_o > header.seg_n {
+            return Err(MeshError::InvalidPdu);
+        }
+        let segment = rest[3..].to_vec();
+        Ok(match ctl {
+            true => LowerTransportPdu::SegmentedControl {
+                opcode: first & 0x7F,
+                header,
+                segment,
+            },
+            false => LowerTransportPdu::SegmentedAccess {
+                akf: first & 0x40 != 0,
+                aid: first & 0x3F,
+                szmic,
+                header,
+                segment,
+            },
+        })
+    }
+
+    /// Gets the bytes of the PDU
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            LowerTransportPdu::UnsegmentedAccess {
+                akf,
+                aid,
+                upper_pdu,
+            } => [&[access_header(false, *akf, *aid)], upper_pdu.as_slice()].concat(),
+            LowerTransportPdu::SegmentedAccess {
+                akf,
+                aid,
+                szmic,
+                header,
+                segment,
+            } => [
+                &[access_header(true, *akf, *aid)][..],
+                &header.encode(*szmic),
+                segment,
+            ]
+            .concat(),
+            LowerTransportPdu::UnsegmentedControl { opcode, params } => {
+                [&[opcode & 0x7F], params.as_slice()].concat()
+            }
+            LowerTransportPdu::SegmentedControl {
+                opcode,
+                header,
+                segment,
+            } => [
+                &[0x80 | (opcode & 0x7F)][..],
+                &header.encode(false),
+                segment,
+            ]
+            .concat(),
+        }
+    }
+}
+
+/// Gets the first byte of an access message: SEG, AKF and AID
+fn access_header(segmented: bool, akf: bool, aid: u8) -> u8 {
+    ((segmented as u8) << 7) | ((akf as u8) << 6) | (aid & 0x3F)
+}
+
+/// Acknowledgment of the segments received of a segmented message
+/// - `obo`: Whether a friend node answered on behalf of a low power node
+/// - `seq_zero`: The SeqZero of the acknowledged message
+/// - `block_ack`: Bit n set for every segment n received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SegmentAck {
+    pub obo: bool,
+    pub seq_zero: u16,
+    pub block_ack: u32,
+}
+
+impl SegmentAck {
+    /// Parses the parameters of a segment acknowledgment control message
+    pub(crate) fn decode(params: &[u8]) -> Option<Self> {
+        if params.len() != 6 {
+            return None;
+        }
+        let value = u16::from_be_bytes([params[0], params[1]]);
+        Some(Self {
+            obo: value & 0x8000 != 0,
+            seq_zero: (value >> 2) & SEQ_ZERO_MASK as u16,
+            block_ack: u32::from_be_bytes(params[2..].try_into().unwrap()),
+        })
+    }
+
+    /// Creates the control message of the acknowledgment
+    pub(crate) fn to_pdu(self) -> LowerTransportPdu {
+        let value = ((self.obo as u16) << 15) | ((self.seq_zero & SEQ_ZERO_MASK as u16) << 2);
+        LowerTransportPdu::UnsegmentedControl {
+            opcode: SEGMENT_ACK_OPCODE,
+            params: [
+                value.to_be_bytes().as_slice(),
+                &self.block_ack.to_be_bytes(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+/// Splits an upper transport access PDU into segments. Every segment is sent in its own network
+/// PDU, the first one with the sequence number `seq`.
+///
+/// # Arguments
+///
+/// - `akf`: Whether the PDU is encrypted with an application key, instead of the device key
+/// - `aid`: The AID of the application key
+/// - `szmic`: Whether the TransMIC of the PDU is 8 bytes long
+/// - `seq`: The sequence number used to encrypt the PDU
+/// - `upper_pdu`: The encrypted PDU
+///
+/// # Returns
+///
+/// A `Result` with the segments in order, or a `MeshError` if the PDU is too long
+///
+/// # Errors
+///
+/// - `MeshError::MessageTooLong`: If the PDU does not fit in 32 segments
+pub(crate) fn segment_access(
+    akf: bool,
+    aid: u8,
+    szmic: bool,
+    seq: u32,
+    upper_pdu: &[u8],
+) -> Result<Vec<LowerTransportPdu>, MeshError> {
+    let chunks: Vec<&[u8]> = upper_pdu.chunks(ACCESS_SEGMENT_SIZE).collect();
+    if chunks.len() > MAX_SEGMENTS {
+        return Err(MeshError::MessageTooLong(upper_pdu.len()));
+    }
+    let seg_n = chunks.len().saturating_sub(1) as u8;
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(seg_o, segment)| LowerTransportPdu::SegmentedAccess {
+            akf,
+            aid,
+            szmic,
+            header: SegmentHeader {
+                seq_zero: (seq & SEQ_ZERO_MASK) as u16,
+                seg_o: seg_o as u8,
+                seg_n,
+            },
+            segment: segment.to_vec(),
+        })
+        .collect())
+}
+
+/// Gets the sequence number of the first segment of a message, from the sequence number of any
+/// of its segments and its SeqZero
+///
+/// # Returns
+///
+/// The sequence number, or `None` if the SeqZero can not belong to the segment
+pub(crate) fn first_seq(seq: u32, seq_zero: u16) -> Option<u32> {
+    let distance = (seq.wrapping_sub(seq_zero as u32)) & SEQ_ZERO_MASK;
+    seq.checked_sub(distance)
+}
+
+/// Kind of key an access message is encrypted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccessKeyType {
+    Application,
+    Device,
+}
+
+/// Creates the nonce that encrypts an upper transport access PDU
+///
+/// # Arguments
+///
+/// - `key_type`: Whether an application or the device key encrypts the PDU
+/// - `szmic`: Whether the TransMIC is 8 bytes long
+/// - `seq`: The sequence number of the first segment of the message
+/// - `src`: The address of the sender
+/// - `dst`: The address of the receiver
+/// - `iv_index`: The IV index the message is sent with
+pub(crate) fn access_nonce(
+    key_type: AccessKeyType,
+    szmic: bool,
+    seq: u32,
+    src: u16,
+    dst: u16,
+    iv_index: u32,
+) -> MeshNonce {
+    let mut nonce = [0; 13];
+    nonce[0] = match key_type {
+        AccessKeyType::Application => APPLICATION_NONCE,
+        AccessKeyType::Device => DEVICE_NONCE,
+    };
+    nonce[1] = (szmic as u8) << 7;
+    nonce[2..5].copy_from_slice(&seq.to_be_bytes()[1..]);
+    nonce[5..7].copy_from_slice(&src.to_be_bytes());
+    nonce[7..9].copy_from_slice(&dst.to_be_bytes());
+    nonce[9..].copy_from_slice(&iv_index.to_be_bytes());
+    nonce
+}
+
+/// Kind of message carried by a segmented message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SegmentedKind {
+    Access { akf: bool, aid: u8, szmic: bool },
+    Control { opcode: u8 },
+}
+
+/// A segmented message whose segments were all received
+/// - `src`: The address of the sender
+/// - `dst`: The address of the receiver
+/// - `seq`: The sequence number of the first segment, used to decrypt access messages
+/// - `kind`: Whether it is an access or a control message
+/// - `data`: The upper transport PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReassembledPdu {
+    pub src: u16,
+    pub dst: u16,
+    pub seq: u32,
+    pub kind: SegmentedKind,
+    pub data: Vec<u8>,
+}
+
+/// Result of receiving a segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SegmentResult {
+    /// The message is still missing segments. The acknowledgment of the received ones is given.
+    Incomplete(SegmentAck),
+    /// The last missing segment was received
+    Complete(ReassembledPdu, SegmentAck),
+    /// The message was already complete, its acknowledgment is given to be sent again
+    AlreadyComplete(SegmentAck),
+}
+
+/// A segmented message being received
+struct Reassembly {
+    pdu: ReassembledPdu,
+    seq_zero: u16,
+    segments: Vec<Option<Vec<u8>>>,
+    last_segment: Instant,
+}
+
+impl Reassembly {
+    fn block_ack(&self) -> u32 {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.is_some())
+            .fold(0, |ack, (index, _)| ack | (1 << index))
+    }
+}
+
+/// Joins the segments of the segmented messages received
+/// - `incomplete`: The messages still missing segments
+/// - `completed`: The source, first sequence number and acknowledgment of the last messages
+///   completed
+pub(crate) struct Reassembler {
+    incomplete: Vec<Reassembly>,
+    completed: VecDeque<(u16, u32, SegmentAck)>,
+}
+
+impl Reassembler {
+    /// Creates a new Reassembler, with no messages
+    pub(crate) fn new() -> Self {
+        Self {
+            incomplete: Vec::new(),
+            completed: VecDeque::with_capacity(COMPLETED_CAPACITY),
+        }
+    }
+
+    /// Adds a segment to its message
+    ///
+    /// # Arguments
+    ///
+    /// - `src`: The address of the sender
+    /// - `dst`: The address of the receiver
+    /// - `seq`: The sequence number of the network PDU with the segment
+    /// - `pdu`: The segment
+    /// - `now`: The time the segment was received
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the state of the message, or a `MeshError` if the segment is not valid
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::InvalidPdu`: If the PDU is not a segment, it does not match the rest of
+    ///   the segments of its message, or its SeqZero does not match its sequence number
+    pub(crate) fn push(
+        &mut self,
+        src: u16,
+        dst: u16,
+        seq: u32,
+        pdu: &LowerTransportPdu,
+        now: Instant,
+    ) -> Result<SegmentResult, MeshError> {
+        let (kind, header, segment) = match pdu {
+            LowerTransportPdu::SegmentedAccess {
+                akf,
+                aid,
+                szmic,
+                header,
+                segment,
+            } => (
+                SegmentedKind::Access {
+                    akf: *akf,
+                    aid: *aid,
+                    szmic: *szmic,
+                },
+                header,
+                segment,
+            ),
+            LowerTransportPdu::SegmentedControl {
+                opcode,
+                header,
+                segment,
+            } => (SegmentedKind::Control { opcode: *opcode }, header, segment),
+            _ => return Err(MeshError::InvalidPdu),
+        };
+        let first = first_seq(seq, header.seq_zero).ok_or(MeshError::InvalidPdu)?;
+        if let Some((_, _, ack)) =
+            self.completed
+                .iter()
+                .find(|(completed_src, completed_seq, _)| {
+                    *completed_src == src && *completed_seq == first
+                })
+        {
+            return Ok(SegmentResult::AlreadyComplete(*ack));
+        }
+
+        let position = self
+            .incomplete
+            .iter()
+            .position(|reassembly| reassembly.pdu.src == src && reassembly.pdu.seq == first);
+        let position = match position {
+            Some(position) => position,
+            None => {
+                // A newer message of the same source replaces the incomplete one
+                self.incomplete
+                    .retain(|reassembly| reassembly.pdu.src != src);
+                self.incomplete.push(Reassembly {
+                    pdu: ReassembledPdu {
+                        src,
+                        dst,
+                        seq: first,
+                        kind,
+                        data: Vec::new(),
+                    },
+                    seq_zero: header.seq_zero,
+                    segments: vec![None; header.seg_n as usize + 1],
+                    last_segment: now,
+                });
+                self.incomplete.len() - 1
+            }
+        };
+        let reassembly = &mut self.incomplete[position];
+        if reassembly.pdu.kind != kind || reassembly.segments.len() != header.seg_n as usize + 1 {
+            return Err(MeshError::InvalidPdu);
+        }
+        let is_last = header.seg_o == header.seg_n;
+        let max_size = match kind {
+            SegmentedKind::Access { .. } => ACCESS_SEGMENT_SIZE,
+            SegmentedKind::Control { .. } => CONTROL_SEGMENT_SIZE,
+        };
+        if segment.is_empty() || segment.len() > max_size || (!is_last && segment.len() != max_size)
+        {
+            return Err(MeshError::InvalidPdu);
+        }
+        reassembly.segments[header.seg_o as usize] = Some(segment.clone());
+        reassembly.last_segment = now;
+
+        let ack = SegmentAck {
+            obo: false,
+            seq_zero: reassembly.seq_zero,
+            block_ack: reassembly.block_ack(),
+        };
+        if reassembly.segments.iter().any(Option::is_none) {
+            return Ok(SegmentResult::Incomplete(ack));
+        }
+        let mut reassembly = self.incomplete.remove(position);
+        reassembly.pdu.data = reassembly
+            .segments
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        if self.completed.len() == COMPLETED_CAPACITY {
+            self.completed.pop_front();
+        }
+        self.completed.push_back((src, first, ack));
+        Ok(SegmentResult::Complete(reassembly.pdu, ack))
+    }
+
+    /// Discards the messages that did not get a segment for too long
+    pub(crate) fn expire(&mut self, now: Instant) {
+        self.incomplete
+            .retain(|reassembly| now.duration_since(reassembly.last_segment) < INCOMPLETE_TIMEOUT);
+    }
+
+    /// Gets whether any message is missing segments
+    pub(crate) fn is_receiving(&self) -> bool {
+        !self.incomplete.is_empty()
+    }
+}
+
+/// A segmented message being sent, retransmitted until every segment is acknowledged
+/// - `dst`: The address of the receiver
+/// - `seq_zero`: The SeqZero of the message
+/// - `segments`: The segments of the message
+/// - `acknowledged`: Bit n set for every segment n acknowledged
+/// - `attempts_left`: Amount of retransmissions left before the message is given up
+/// - `next_attempt`: When the unacknowledged segments are sent again
+pub(crate) struct OutgoingSegments {
+    pub dst: u16,
+    pub seq_zero: u16,
+    segments: Vec<LowerTransportPdu>,
+    acknowledged: u32,
+    attempts_left: u8,
+    pub next_attempt: Instant,
+}
+
+impl OutgoingSegments {
+    /// Creates a new OutgoingSegments, whose first transmission is done by the caller
+    pub(crate) fn new(
+        dst: u16,
+        segments: Vec<LowerTransportPdu>,
+        attempts: u8,
+        next_attempt: Instant,
+    ) -> Self {
+        let seq_zero = match segments.first() {
+            Some(LowerTransportPdu::SegmentedAccess { header, .. })
+            | Some(LowerTransportPdu::SegmentedControl { header, .. }) => header.seq_zero,
+            _ => 0,
+        };
+        Self {
+            dst,
+            seq_zero,
+            segments,
+            acknowledged: 0,
+            attempts_left: attempts,
+            next_attempt,
+        }
+    }
+
+    /// Marks the segments acknowledged by a receiver
+    ///
+    /// # Returns
+    ///
+    /// Whether every segment is acknowledged
+    pub(crate) fn acknowledge(&mut self, ack: &SegmentAck) -> bool {
+        if ack.seq_zero == self.seq_zero {
+            self.acknowledged |= ack.block_ack;
+        }
+        self.is_complete()
+    }
+
+    /// Gets whether every segment is acknowledged
+    pub(crate) fn is_complete(&self) -> bool {
+        (0..self.segments.len()).all(|index| self.acknowledged & (1 << index) != 0)
+    }
+
+    /// Gets the segments to send again, consuming an attempt
+    ///
+    /// # Returns
+    ///
+    /// The unacknowledged segments, or `None` if there are no attempts left
+    pub(crate) fn retransmit(&mut self, next_attempt: Instant) -> Option<Vec<LowerTransportPdu>> {
+        self.attempts_left = self.attempts_left.checked_sub(1)?;
+        self.next_attempt = next_attempt;
+        Some(
+            self.segments
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.acknowledged & (1 << index) == 0)
+                .map(|(_, segment)| segment.clone())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ble_mesh::{
+        mesh_crypto::{ccm_decrypt, ccm_encrypt, hex, hex_key, MicSize},
+        network::{NetworkKey, NetworkPdu},
+    };
+
+    const IV_INDEX: u32 = 0x12345678;
+
+    /// Sample message #6 of the specification: a Config AppKey Add sent to a node with its
+    /// device key
+    fn sample_message_6() -> Vec<u8> {
+        let device_key = hex_key("9d6dd0e96eb25dc19a40ed9914f8f03f");
+        let nonce = access_nonce(
+            AccessKeyType::Device,
+            false,
+            0x3129AB,
+            0x0003,
+            0x1201,
+            IV_INDEX,
+        );
+        assert_eq!(nonce.to_vec(), hex("02003129ab0003120112345678"));
+        let payload = hex("0056341263964771734fbd76e3b40519d1d94a48");
+        let upper_pdu = ccm_encrypt(&device_key, &nonce, &payload, &[], MicSize::Short);
+        assert_eq!(
+            upper_pdu,
+            hex("ee9dddfd2169326d23f3afdfcfdc18c52fdef772e0e17308")
+        );
+        assert_eq!(
+            ccm_decrypt(&device_key, &nonce, &upper_pdu, &[], MicSize::Short).unwrap(),
+            payload
+        );
+        upper_pdu
+    }
+
+    #[test]
+    fn transport_01_segments_sample_message_6() {
+        let segments = segment_access(false, 0, false, 0x3129AB, &sample_message_6()).unwrap();
+        let encoded: Vec<Vec<u8>> = segments.iter().map(LowerTransportPdu::encode).collect();
+        assert_eq!(
+            encoded,
+            [
+                hex("8026ac01ee9dddfd2169326d23f3afdf"),
+                hex("8026ac21cfdc18c52fdef772e0e17308")
+            ]
+        );
+        assert_eq!(
+            LowerTransportPdu::decode(false, &encoded[1]).unwrap(),
+            segments[1]
+        );
+
+        let key = NetworkKey::new(0, hex_key("7dd7364cd842ad18c17c2b820c84c3d6"));
+        let network_pdus: Vec<Vec<u8>> = encoded
+            .into_iter()
+            .enumerate()
+            .map(|(index, transport_pdu)| {
+                let pdu = NetworkPdu {
+                    ctl: false,
+                    ttl: 4,
+                    seq: 0x3129AB + index as u32,
+                    src: 0x0003,
+                    dst: 0x1201,
+                    transport_pdu,
+                };
+                key.encrypt(IV_INDEX, &pdu)
+            })
+            .collect();
+        assert_eq!(
+            network_pdus,
+            [
+                hex("68cab5c5348a230afba8c63d4e686364979deaf4fd40961145939cda0e"),
+                hex("681615b5dd4a846cae0c032bf0746f44f1b8cc8ce5edc57e55beed49c0")
+            ]
+        );
+    }
+
+    #[test]
+    fn transport_02_reassembles_segments_in_any_order() {
+        let upper_pdu = sample_message_6();
+        let segments = segment_access(false, 0, false, 0x3129AB, &upper_pdu).unwrap();
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+
+        let result = reassembler.push(0x0003, 0x1201, 0x3129AC, &segments[1], now);
+        assert!(matches!(
+            result,
+            Ok(SegmentResult::Incomplete(SegmentAck {
+                block_ack: 0b10,
+                seq_zero: 0x09AB,
+                ..
+            }))
+        ));
+        assert!(reassembler.is_receiving());
+        let Ok(SegmentResult::Complete(pdu, ack)) =
+            reassembler.push(0x0003, 0x1201, 0x3129AB, &segments[0], now)
+        else {
+            panic!("the message must be complete")
+        };
+        assert_eq!(pdu.seq, 0x3129AB);
+        assert_eq!(pdu.data, upper_pdu);
+        assert_eq!(ack.block_ack, 0b11);
+        assert!(!reassembler.is_receiving());
+
+        // Late copies of a segment are acknowledged again
+        let result = reassembler.push(0x0003, 0x1201, 0x3129AC, &segments[1], now);
+        assert_eq!(result.unwrap(), SegmentResult::AlreadyComplete(ack));
+    }
+
+    #[test]
+    fn transport_03_discards_expired_and_invalid_segments() {
+        let segments = segment_access(true, 0x26, false, 0x100, &[0xAA; 30]).unwrap();
+        assert_eq!(segments.len(), 3);
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+        reassembler
+            .push(0x0005, 0xC000, 0x100, &segments[0], now)
+            .unwrap();
+        reassembler.expire(now + INCOMPLETE_TIMEOUT);
+        assert!(!reassembler.is_receiving());
+
+        let unsegmented = LowerTransportPdu::UnsegmentedAccess {
+            akf: true,
+            aid: 0x26,
+            upper_pdu: vec![0; 5],
+        };
+        assert!(matches!(
+            reassembler.push(0x0005, 0xC000, 0x100, &unsegmented, now),
+            Err(MeshError::InvalidPdu)
+        ));
+        assert!(matches!(
+            segment_access(true, 0x26, false, 0x100, &[0; 12 * 33]),
+            Err(MeshError::MessageTooLong(396))
+        ));
+        assert_eq!(first_seq(0x2000, 0x1FFF), Some(0x1FFF));
+        assert_eq!(first_seq(0x0001, 0x1FFF), None);
+    }
+
+    #[test]
+    fn transport_04_retransmits_until_acknowledged() {
+        let segments = segment_access(true, 0x26, false, 0x100, &[0xAA; 30]).unwrap();
+        let now = Instant::now();
+        let mut outgoing = OutgoingSegments::new(0x0002, segments, 2, now);
+        let ack = SegmentAck::decode(
+            &SegmentAck {
+                obo: false,
+                seq_zero: 0x100,
+                block_ack: 0b101,
+            }
+            .to_pdu()
+            .encode()[1..],
+        )
+        .unwrap();
+        assert!(!outgoing.acknowledge(&ack));
+        let missing = outgoing.retransmit(now).unwrap();
+        assert_eq!(missing.len(), 1);
+        assert!(outgoing.retransmit(now).is_some());
+        assert!(outgoing.retransmit(now).is_none());
+        assert!(outgoing.acknowledge(&SegmentAck {
+            obo: false,
+            seq_zero: 0x100,
+            block_ack: 0b010,
+        }));
+    }
+}
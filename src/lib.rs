@@ -5,11 +5,16 @@
 #![test_runner(test_runner_mod::esp_test_runner)]
 esp32_testing_macro::use_esp32_tests!(crate::esp_test);
 
+// Allows the code generated by the framework's derive macros to refer to it by name
+extern crate self as esp32framework;
+
 pub mod ble;
+pub mod ble_mesh;
 pub mod gpio;
 mod microcontroller_src;
 pub mod sensors;
 pub mod serial;
+pub mod telemetry;
 pub mod utils; //TODO private this
 pub mod wifi;
 pub mod external_peripheral {
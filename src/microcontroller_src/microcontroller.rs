@@ -1,8 +1,11 @@
+#[cfg(esp_idf_bt_nimble_ext_adv)]
+use crate::ble::BleAdvertiser;
 use crate::{
     ble::{
         utils::{Security, Service},
         BleBeacon, BleClient, BleError, BleServer,
     },
+    ble_mesh::{BleMeshNode, MeshError, MeshNodeConfig},
     gpio::{analog::*, digital::*},
     microcontroller_src::{interrupt_driver::InterruptDriver, peripherals::*},
     serial::{i2c::*, uart::*},
@@ -13,13 +16,18 @@ use crate::{
         notification::{Notification, Notifier},
         timer_driver::TimerDriver,
     },
-    wifi::{WifiDriver, WifiError},
+    wifi::{
+        mqtt::{MqttClient, MqttConfiguration, MqttError},
+        WifiDriver, WifiError,
+    },
 };
 use attenuation::adc_atten_t;
 use esp32_nimble::{enums::AuthReq, BLEDevice};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     hal::{adc::*, task::block_on},
+    nvs::EspDefaultNvsPartition,
+    sys::EspError,
 };
 use futures::future::{join, Future};
 use oneshot::AdcDriver;
@@ -43,6 +51,7 @@ static TAKEN: AtomicBool = AtomicBool::new(false);
 /// - `interrupt_drivers`: A vector of boxed `InterruptDriver` trait objects, representing the drivers responsible for handling hardware interrupts.
 /// - `adc_driver`: An optional shared instance of `SharableAdcDriver`, providing access to the ADC (Analog-to-Digital Converter) for analog input processing.
 /// - `notification`: An instance of `Notification`, used for managing notifications or signaling events within the microcontroller's operation.
+/// - `nvs`: The default NVS partition, taken once and shared by the drivers that store data in it.
 pub struct Microcontroller<'a> {
     peripherals: Peripherals,
     timer_drivers: Vec<TimerDriver<'a>>,
@@ -50,6 +59,7 @@ pub struct Microcontroller<'a> {
     adc_driver: Option<SharableAdcDriver<'a>>,
     notification: Notification,
     event_loop: EspSystemEventLoop,
+    nvs: Option<EspDefaultNvsPartition>,
 }
 
 impl<'a> Microcontroller<'a> {
@@ -79,6 +89,7 @@ impl<'a> Microcontroller<'a> {
             adc_driver: None,
             notification,
             event_loop: EspSystemEventLoop::take().expect("Error creating microcontroller"),
+            nvs: None,
         }
     }
 
@@ -105,6 +116,21 @@ impl<'a> Microcontroller<'a> {
         interrupt_driver
     }
 
+    /// Gets the default NVS partition, taking it the first time it is needed
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the partition, or an `EspError` if it was already taken outside of
+    /// the microcontroller
+    fn get_nvs_partition(&mut self) -> Result<EspDefaultNvsPartition, EspError> {
+        if let Some(nvs) = &self.nvs {
+            return Ok(nvs.clone());
+        }
+        let nvs = EspDefaultNvsPartition::take()?;
+        self.nvs = Some(nvs.clone());
+        Ok(nvs)
+    }
+
     /// Creates the timer_drivers for all timer groups
     ///
     /// #Returns
@@ -628,6 +654,7 @@ impl<'a> Microcontroller<'a> {
             services,
             self.notification.notifier(),
             self.notification.notifier(),
+            self.notification.notifier(),
         )?;
         Ok(self.keep_updater(ble_server))
     }
@@ -696,6 +723,7 @@ impl<'a> Microcontroller<'a> {
             services,
             self.notification.notifier(),
             self.notification.notifier(),
+            self.notification.notifier(),
         )?;
         Ok(self.keep_updater(ble_server))
     }
@@ -715,6 +743,86 @@ impl<'a> Microcontroller<'a> {
         Ok(self.keep_updater(ble_client))
     }
 
+    /// Configures a BLE client with the specified security settings, so it can pair with servers
+    /// that require encryption or a passkey. The pairing is started with
+    /// [BleClient::secure_connection].
+    ///
+    /// # Arguments
+    ///
+    /// - `security_config`: A `Security` configuration struct containing the desired security settings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `BleClient` instance, or an `BleError` if the
+    /// initialization fails.
+    ///
+    /// # Errors
+    ///
+    /// - `BleError::PeripheralError`: This error is returned if an issue occurs while initializing the BleDevice.
+    /// - `BleError::InvalidParameters`: This error is returned if there is an error in the `security_config` argument.
+    pub fn ble_secure_client(&mut self, security_config: Security) -> Result<BleClient, BleError> {
+        let ble_device = self.peripherals.get_ble_peripheral().into_ble_device()?;
+        self.config_bluetooth_security(ble_device, security_config)?;
+        let ble_client = BleClient::new(ble_device, self.notification.notifier());
+        Ok(self.keep_updater(ble_client))
+    }
+
+    /// Creates the driver of the Bluetooth 5 extended advertising, which runs many advertising
+    /// sets at the same time. It does not take the BLE peripheral, so it can be used together with
+    /// the [BleServer] whose services are served through its connectable sets, or with a
+    /// [BleClient].
+    ///
+    /// Needs `CONFIG_BT_NIMBLE_EXT_ADV=y` in the sdkconfig.
+    ///
+    /// # Returns
+    ///
+    /// The new `BleAdvertiser`, with no sets
+    #[cfg(esp_idf_bt_nimble_ext_adv)]
+    pub fn ble_advertiser(&mut self) -> BleAdvertiser<'a> {
+        let advertiser = BleAdvertiser::new(self.notification.notifier());
+        self.keep_updater(advertiser)
+    }
+
+    /// Configures a BLE device as a BLE Mesh node, which waits to be provisioned into a network.
+    /// Elements and models must be added to it before it is provisioned.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: The [MeshNodeConfig] of the node
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `BleMeshNode` instance, or a `MeshError` if the
+    /// initialization fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MeshError::Ble`: If an issue occurs while initializing the BleDevice, or the Mesh
+    ///   Provisioning Service could not be set. With `BleError::LegacyAdvertisingUnavailable` if
+    ///   the extended advertising is enabled, since the mesh bearers use legacy advertising.
+    /// - `MeshError::TimerDriver`: If there are no timers available.
+    /// - `MeshError::NvsAlreadyTaken`: If the NVS Default Partition was taken outside of the
+    ///   microcontroller.
+    /// - `MeshError::StorageError`: If the NVS namespace of the node could not be opened.
+    pub fn ble_mesh_node(&mut self, config: MeshNodeConfig) -> Result<BleMeshNode<'a>, MeshError> {
+        let ble_device = self
+            .peripherals
+            .get_ble_peripheral()
+            .into_ble_device()
+            .map_err(BleError::from)?;
+        let nvs = self
+            .get_nvs_partition()
+            .map_err(|_| MeshError::NvsAlreadyTaken)?;
+        let node = BleMeshNode::new(
+            ble_device,
+            config,
+            self.get_timer_driver()?,
+            nvs,
+            self.notification.notifier(),
+        )?;
+        Ok(self.keep_updater(node))
+    }
+
     /// Configures a WIFIDriver. This driver uses the
     /// By default this function takes the Non-Volatile Storage of the ESP in order to save
     /// wifi configuration. This is to improve connection times for future connections
@@ -728,9 +836,38 @@ impl<'a> Microcontroller<'a> {
     /// # Errors
     ///
     /// - `WifiError::PeripheralError`: This error is returned if an issue occurs while initializing the WifiModem.
+    /// - `WifiError::NvsAlreadyTaken`: If the NVS Default Partition was taken outside of the microcontroller.
     pub fn get_wifi_driver(&mut self) -> Result<WifiDriver<'a>, WifiError> {
         let modem = self.peripherals.get_wifi_peripheral().into_modem()?;
-        WifiDriver::new(self.event_loop.clone(), modem)
+        let nvs = self
+            .get_nvs_partition()
+            .map_err(|_| WifiError::NvsAlreadyTaken)?;
+        WifiDriver::new(self.event_loop.clone(), modem, nvs)
+    }
+
+    /// Creates an MqttClient that connects to the desired broker. Wifi must be connected
+    /// before the client is able to reach the broker, see [Self::get_wifi_driver].
+    ///
+    /// # Arguments
+    ///
+    /// - `uri`: The uri of the broker (e.g. `mqtt://192.168.0.10:1883` or `mqtts://broker.example.com`)
+    /// - `configuration`: The configuration of the connection, including credentials and last will
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `MqttClient` instance, or an `MqttError` if the
+    /// initialization fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::ConnectionError`: If the underlying client could not be initialized.
+    pub fn mqtt_client(
+        &mut self,
+        uri: &str,
+        configuration: &MqttConfiguration,
+    ) -> Result<MqttClient<'a>, MqttError> {
+        let client = MqttClient::new(uri, configuration, self.notification.notifier())?;
+        Ok(self.keep_updater(client))
     }
 
     /// Updates all assigned drivers of the microcontroller, handling interrupts and alarms as needed.
@@ -170,6 +170,15 @@ impl<'a> DS3231<'a> {
         DS3231 { i2c, mode }
     }
 
+    /// Gets the hour mode the DS3231 was configured with.
+    ///
+    /// # Returns
+    ///
+    /// The `HourMode` of the clock.
+    pub fn get_hour_mode(&self) -> HourMode {
+        self.mode
+    }
+
     /// Converts a decimal number to its Binary-Coded Decimal (BCD) representation.
     ///
     /// # Arguments
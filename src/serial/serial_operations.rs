@@ -5,6 +5,26 @@ use std::collections::HashMap;
 #[derive(Debug)]
 pub enum SerialError {
     ErrorInReadValue,
+    ErrorInWriteValue,
+}
+
+/// Parses a line of text made of `key=value` pairs, separated by commas or semicolons. Keys and
+/// values can also be separated by a colon, and surrounding whitespace is ignored. Pairs without
+/// a separator are ignored.
+///
+/// # Arguments
+///
+/// * `line`: The line to parse, for example `"temp=21.5, led:on"`.
+///
+/// # Returns
+///
+/// A `HashMap<String, String>` with the value of each key.
+pub fn parse_key_values(line: &str) -> HashMap<String, String> {
+    line.split([',', ';'])
+        .filter_map(|pair| pair.split_once(['=', ':']))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
 }
 
 /// Trait for performing reading and parsing operations.
@@ -291,3 +311,17 @@ pub trait ReaderWriter: READER + WRITER {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serial_operations_01_parse_key_values() {
+        let parsed = parse_key_values(" temp=21.5, led:on;mode = auto ,invalid, =empty\r");
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed["temp"], "21.5");
+        assert_eq!(parsed["led"], "on");
+        assert_eq!(parsed["mode"], "auto");
+    }
+}
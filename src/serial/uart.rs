@@ -1,5 +1,8 @@
+use std::collections::HashMap;
+
 use crate::{
     microcontroller_src::peripherals::{Peripheral, PeripheralError},
+    serial::{parse_key_values, SerialError, READER, WRITER},
     utils::auxiliary::micro_to_ticks,
 };
 use esp_idf_svc::hal::{
@@ -205,6 +208,36 @@ impl<'a> UART<'a> {
     }
 }
 
+impl READER for UART<'_> {
+    /// Blocks until a line ended in `\n` is received and parses its `key=value` pairs, as
+    /// [parse_key_values] does.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap<String, String>` with the value of each key. It is empty if the read fails.
+    fn read_and_parse(&mut self) -> HashMap<String, String> {
+        let mut line = Vec::new();
+        let mut byte = [0_u8];
+        while matches!(self.read(&mut byte), Ok(1)) && byte[0] != b'\n' {
+            line.push(byte[0]);
+        }
+        parse_key_values(&String::from_utf8_lossy(&line))
+    }
+}
+
+impl WRITER for UART<'_> {
+    /// Writes the bytes. The address is ignored, since the UART is a point to point link.
+    ///
+    /// # Errors
+    ///
+    /// - `SerialError::ErrorInWriteValue`: If the write operation failed.
+    fn parse_and_write(&mut self, _addr: u8, bytes_to_write: &[u8]) -> Result<(), SerialError> {
+        self.write(bytes_to_write)
+            .map(|_| ())
+            .map_err(|_| SerialError::ErrorInWriteValue)
+    }
+}
+
 /// Sets up the UART configuration based on the given parameters.
 ///
 /// # Arguments
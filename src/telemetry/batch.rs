@@ -0,0 +1,69 @@
+/// Accumulates items (points or metrics) so they can be sent together in a single request.
+/// Once `max_size` items have been added the batch is considered full and should be flushed.
+#[derive(Debug, Clone)]
+pub struct Batch<T> {
+    items: Vec<T>,
+    max_size: usize,
+}
+
+impl<T> Batch<T> {
+    /// Creates a new empty Batch
+    ///
+    /// # Arguments
+    ///
+    /// - `max_size`: The amount of items after which the batch is full. A value of 0 is treated as 1.
+    ///
+    /// # Returns
+    ///
+    /// The new Batch instance
+    pub fn new(max_size: usize) -> Self {
+        let max_size = max_size.max(1);
+        Batch {
+            items: Vec::with_capacity(max_size),
+            max_size,
+        }
+    }
+
+    /// Adds an item to the batch
+    ///
+    /// # Arguments
+    ///
+    /// - `item`: The item to add
+    ///
+    /// # Returns
+    ///
+    /// A bool. True if the batch is full after adding the item
+    pub fn push(&mut self, item: T) -> bool {
+        self.items.push(item);
+        self.is_full()
+    }
+
+    /// Removes every item from the batch
+    ///
+    /// # Returns
+    ///
+    /// A Vec with every item that was in the batch, in the order they were added
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::replace(&mut self.items, Vec::with_capacity(self.max_size))
+    }
+
+    /// Gets the items currently in the batch
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Gets the amount of items currently in the batch
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks if the batch has no items
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Checks if the batch reached its max size
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.max_size
+    }
+}
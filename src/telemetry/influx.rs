@@ -0,0 +1,294 @@
+use super::{FieldValue, Point, TelemetryError};
+
+/// Escapes the characters that have a special meaning in the line protocol.
+///
+/// # Arguments
+///
+/// - `value`: The string to escape
+/// - `special_chars`: The characters that need to be preceded by a backslash
+///
+/// # Returns
+///
+/// A String with every special character escaped
+fn escape(value: &str, special_chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            c if special_chars.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether a name or value has control characters, like newlines or tabs. The line
+/// protocol has no escape sequence for them, and a newline would end the line.
+fn has_control_chars(value: &str) -> bool {
+    value.chars().any(char::is_control)
+}
+
+/// Escapes a measurement name. Commas and spaces must be escaped.
+fn escape_measurement(measurement: &str) -> String {
+    escape(measurement, &[',', ' '])
+}
+
+/// Escapes a tag key, tag value or field key. Commas, equal signs and spaces must be escaped.
+fn escape_key(key: &str) -> String {
+    escape(key, &[',', '=', ' '])
+}
+
+/// Escapes a string field value. Double quotes and backslashes must be escaped.
+fn escape_string_field(value: &str) -> String {
+    escape(value, &['"', '\\'])
+}
+
+/// Formats a field value as the line protocol expects it
+///
+/// # Arguments
+///
+/// - `value`: The FieldValue to format
+///
+/// # Returns
+///
+/// A `Result` with the formatted value, or a `TelemetryError` if the value can not be
+/// represented in the line protocol.
+///
+/// # Errors
+///
+/// - `TelemetryError::InvalidValue`: If the value is a NaN or infinite float, or a string with
+///   control characters.
+fn format_field_value(value: &FieldValue) -> Result<String, TelemetryError> {
+    Ok(match value {
+        FieldValue::Boolean(b) => b.to_string(),
+        FieldValue::Float(f) => {
+            if !f.is_finite() {
+                return Err(TelemetryError::InvalidValue(f.to_string()));
+            }
+            f.to_string()
+        }
+        FieldValue::Integer(i) => format!("{}i", i),
+        FieldValue::UInteger(u) => format!("{}u", u),
+        FieldValue::String(s) => {
+            if has_control_chars(s) {
+                return Err(TelemetryError::InvalidValue(s.clone()));
+            }
+            format!("\"{}\"", escape_string_field(s))
+        }
+    })
+}
+
+/// Encodes a point into a single line of the InfluxDB line protocol. Tags are sorted
+/// by key, as recommended by InfluxDB for better write performance.
+///
+/// # Arguments
+///
+/// - `point`: The point to encode
+///
+/// # Returns
+///
+/// A `Result` with the encoded line (without a trailing newline), or a `TelemetryError`
+/// if the point can not be encoded.
+///
+/// # Errors
+///
+/// - `TelemetryError::InvalidName`: If the measurement, a tag key or a field key is empty or has
+///   control characters.
+/// - `TelemetryError::NoFields`: If the point has no fields.
+/// - `TelemetryError::InvalidValue`: If a tag value is empty, a tag value or string field has
+///   control characters, or a float field is NaN or infinite.
+pub fn to_line_protocol(point: &Point) -> Result<String, TelemetryError> {
+    if point.measurement.is_empty() || has_control_chars(&point.measurement) {
+        return Err(TelemetryError::InvalidName(point.measurement.clone()));
+    }
+    if point.fields.is_empty() {
+        return Err(TelemetryError::NoFields);
+    }
+
+    let mut line = escape_measurement(&point.measurement);
+
+    let mut tags: Vec<&(String, String)> = point.tags.iter().collect();
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, value) in tags {
+        if key.is_empty() || has_control_chars(key) {
+            return Err(TelemetryError::InvalidName(key.clone()));
+        }
+        if value.is_empty() || has_control_chars(value) {
+            return Err(TelemetryError::InvalidValue(value.clone()));
+        }
+        line.push_str(&format!(",{}={}", escape_key(key), escape_key(value)));
+    }
+
+    let mut fields = Vec::with_capacity(point.fields.len());
+    for (key, value) in &point.fields {
+        if key.is_empty() || has_control_chars(key) {
+            return Err(TelemetryError::InvalidName(key.clone()));
+        }
+        fields.push(format!(
+            "{}={}",
+            escape_key(key),
+            format_field_value(value)?
+        ));
+    }
+    line.push(' ');
+    line.push_str(&fields.join(","));
+
+    if let Some(timestamp) = point.timestamp {
+        line.push_str(&format!(" {}", timestamp));
+    }
+    Ok(line)
+}
+
+/// Encodes multiple points into an InfluxDB line protocol body, one point per line.
+///
+/// # Arguments
+///
+/// - `points`: The points to encode
+///
+/// # Returns
+///
+/// A `Result` with the encoded body, or a `TelemetryError` if any of the points can not be encoded.
+///
+/// # Errors
+///
+/// - `TelemetryError::EmptyBatch`: If there are no points to encode.
+/// - Any error returned by [to_line_protocol].
+pub fn to_line_protocol_batch(points: &[Point]) -> Result<String, TelemetryError> {
+    if points.is_empty() {
+        return Err(TelemetryError::EmptyBatch);
+    }
+    let lines = points
+        .iter()
+        .map(to_line_protocol)
+        .collect::<Result<Vec<String>, TelemetryError>>()?;
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn influx_01_simple_point() {
+        let point = Point::new("temperature")
+            .tag("room", "kitchen")
+            .field("value", 21.5)
+            .timestamp(1_700_000_000_000_000_000);
+        assert_eq!(
+            to_line_protocol(&point).unwrap(),
+            "temperature,room=kitchen value=21.5 1700000000000000000"
+        );
+    }
+
+    #[test]
+    fn influx_02_field_types() {
+        let point = Point::new("m")
+            .field("f", 1.0)
+            .field("i", -3_i32)
+            .field("u", 7_u8)
+            .field("b", true)
+            .field("s", "on");
+        assert_eq!(
+            to_line_protocol(&point).unwrap(),
+            "m f=1,i=-3i,u=7u,b=true,s=\"on\""
+        );
+    }
+
+    #[test]
+    fn influx_03_tags_are_sorted() {
+        let point = Point::new("m")
+            .tag("z", "1")
+            .tag("a", "2")
+            .field("v", 1_i64);
+        assert_eq!(to_line_protocol(&point).unwrap(), "m,a=2,z=1 v=1i");
+    }
+
+    #[test]
+    fn influx_04_escapes_measurement_tags_and_keys() {
+        let point = Point::new("my measure,ment")
+            .tag("tag key", "a=b,c")
+            .field("field=key", 1_i64);
+        assert_eq!(
+            to_line_protocol(&point).unwrap(),
+            "my\\ measure\\,ment,tag\\ key=a\\=b\\,c field\\=key=1i"
+        );
+    }
+
+    #[test]
+    fn influx_05_escapes_string_fields() {
+        let point = Point::new("m").field("s", "say \"hi\" \\ bye");
+        assert_eq!(
+            to_line_protocol(&point).unwrap(),
+            "m s=\"say \\\"hi\\\" \\\\ bye\""
+        );
+    }
+
+    #[test]
+    fn influx_06_control_characters_fail() {
+        let invalid_names = [
+            Point::new("m\n").field("v", 1_i64),
+            Point::new("m").tag("t\r", "v").field("v", 1_i64),
+            Point::new("m").field("v\t", 1_i64),
+        ];
+        for point in invalid_names {
+            assert!(matches!(
+                to_line_protocol(&point),
+                Err(TelemetryError::InvalidName(_))
+            ));
+        }
+        let invalid_values = [
+            Point::new("m").tag("t", "a\nb").field("v", 1_i64),
+            Point::new("m").field("s", "x\ny"),
+            Point::new("m").field("s", "x\u{7}"),
+        ];
+        for point in invalid_values {
+            assert!(matches!(
+                to_line_protocol(&point),
+                Err(TelemetryError::InvalidValue(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn influx_07_point_without_fields_fails() {
+        let point = Point::new("m").tag("t", "v");
+        assert!(matches!(
+            to_line_protocol(&point),
+            Err(TelemetryError::NoFields)
+        ));
+    }
+
+    #[test]
+    fn influx_08_non_finite_float_fails() {
+        let point = Point::new("m").field("v", f64::NAN);
+        assert!(matches!(
+            to_line_protocol(&point),
+            Err(TelemetryError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn influx_09_batch_is_newline_separated() {
+        let points = vec![
+            Point::new("a").field("v", 1_i64),
+            Point::new("b").field("v", 2_i64),
+        ];
+        assert_eq!(to_line_protocol_batch(&points).unwrap(), "a v=1i\nb v=2i");
+    }
+
+    #[test]
+    fn influx_10_empty_batch_fails() {
+        assert!(matches!(
+            to_line_protocol_batch(&[]),
+            Err(TelemetryError::EmptyBatch)
+        ));
+    }
+
+    #[test]
+    fn influx_11_repeated_field_replaces_value() {
+        let point = Point::new("m").field("v", 1_i64).field("v", 2_i64);
+        assert_eq!(to_line_protocol(&point).unwrap(), "m v=2i");
+    }
+}
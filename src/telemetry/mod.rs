@@ -0,0 +1,15 @@
+mod batch;
+mod influx;
+mod point;
+mod prometheus;
+mod push_client;
+mod telemetry_error;
+mod timestamp;
+
+pub use batch::*;
+pub use influx::*;
+pub use point::*;
+pub use prometheus::*;
+pub use push_client::*;
+pub use telemetry_error::*;
+pub use timestamp::*;
@@ -0,0 +1,245 @@
+/// Value of a field of a [Point]. InfluxDB distinguishes between each of these types,
+/// so integers and floats are kept apart instead of being converted to a single type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Boolean(bool),
+    Float(f64),
+    Integer(i64),
+    String(String),
+    UInteger(u64),
+}
+
+macro_rules! impl_from_for_field_value {
+    ($( $variant:ident => $value_type:ty ),* $(,)?) => {
+        $(
+            impl From<$value_type> for FieldValue {
+                fn from(value: $value_type) -> Self {
+                    Self::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_field_value! {
+    Boolean => bool,
+    Float => f32,
+    Float => f64,
+    Integer => i8,
+    Integer => i16,
+    Integer => i32,
+    Integer => i64,
+    String => &str,
+    String => String,
+    UInteger => u8,
+    UInteger => u16,
+    UInteger => u32,
+    UInteger => u64,
+}
+
+/// A single measurement to be reported to a time-series database such as InfluxDB.
+/// It contains:
+/// - `measurement`: The name of the measurement.
+/// - `tags`: Indexed key-value pairs that describe the measurement (e.g. the device or the room).
+/// - `fields`: The measured values.
+/// - `timestamp`: The unix timestamp of the measurement in nanoseconds. If `None` the
+///   database will use the time in which the point was received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    pub timestamp: Option<i64>,
+}
+
+impl Point {
+    /// Creates a new Point without tags, fields or timestamp
+    ///
+    /// # Arguments
+    ///
+    /// - `measurement`: The name of the measurement
+    ///
+    /// # Returns
+    ///
+    /// The new Point instance
+    pub fn new(measurement: &str) -> Self {
+        Point {
+            measurement: measurement.to_string(),
+            tags: vec![],
+            fields: vec![],
+            timestamp: None,
+        }
+    }
+
+    /// Adds a tag to the point. If the key was already present its value is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// - `key`: The key of the tag
+    /// - `value`: The value of the tag
+    ///
+    /// # Returns
+    ///
+    /// The same point with the added tag
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// Adds a field to the point. If the key was already present its value is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// - `key`: The key of the field
+    /// - `value`: Any value that can be converted into a [FieldValue]
+    ///
+    /// # Returns
+    ///
+    /// The same point with the added field
+    pub fn field<V: Into<FieldValue>>(mut self, key: &str, value: V) -> Self {
+        let value = value.into();
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    /// Sets the timestamp of the point
+    ///
+    /// # Arguments
+    ///
+    /// - `timestamp_ns`: The unix timestamp of the measurement in nanoseconds
+    ///
+    /// # Returns
+    ///
+    /// The same point with the timestamp set
+    pub fn timestamp(mut self, timestamp_ns: i64) -> Self {
+        self.timestamp = Some(timestamp_ns);
+        self
+    }
+}
+
+/// Types of metrics in the Prometheus exposition format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Untyped,
+}
+
+impl MetricType {
+    /// Gets the name used for the metric type in the `# TYPE` line
+    ///
+    /// # Returns
+    ///
+    /// An &str with the name of the type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Untyped => "untyped",
+        }
+    }
+}
+
+/// A single sample to be reported to Prometheus. It contains:
+/// - `name`: The name of the metric.
+/// - `help`: An optional description of the metric.
+/// - `metric_type`: The type of the metric.
+/// - `labels`: Key-value pairs that identify the sample.
+/// - `value`: The sampled value.
+/// - `timestamp`: The unix timestamp of the sample in milliseconds. If `None` the time
+///   of the scrape is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub name: String,
+    pub help: Option<String>,
+    pub metric_type: MetricType,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    pub timestamp: Option<i64>,
+}
+
+impl Metric {
+    /// Creates a new Metric without labels
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the metric
+    /// - `metric_type`: The type of the metric
+    /// - `value`: The sampled value
+    ///
+    /// # Returns
+    ///
+    /// The new Metric instance
+    pub fn new(name: &str, metric_type: MetricType, value: f64) -> Self {
+        Metric {
+            name: name.to_string(),
+            help: None,
+            metric_type,
+            labels: vec![],
+            value,
+            timestamp: None,
+        }
+    }
+
+    /// Creates a new gauge Metric without labels
+    pub fn gauge(name: &str, value: f64) -> Self {
+        Self::new(name, MetricType::Gauge, value)
+    }
+
+    /// Creates a new counter Metric without labels
+    pub fn counter(name: &str, value: f64) -> Self {
+        Self::new(name, MetricType::Counter, value)
+    }
+
+    /// Sets the help text of the metric
+    ///
+    /// # Arguments
+    ///
+    /// - `help`: The description of the metric
+    ///
+    /// # Returns
+    ///
+    /// The same metric with the help text set
+    pub fn help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Adds a label to the metric. If the key was already present its value is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// - `key`: The name of the label
+    /// - `value`: The value of the label
+    ///
+    /// # Returns
+    ///
+    /// The same metric with the added label
+    pub fn label(mut self, key: &str, value: &str) -> Self {
+        match self.labels.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.labels.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// Sets the timestamp of the metric
+    ///
+    /// # Arguments
+    ///
+    /// - `timestamp_ms`: The unix timestamp of the sample in milliseconds
+    ///
+    /// # Returns
+    ///
+    /// The same metric with the timestamp set
+    pub fn timestamp(mut self, timestamp_ms: i64) -> Self {
+        self.timestamp = Some(timestamp_ms);
+        self
+    }
+}
@@ -0,0 +1,220 @@
+use super::{Metric, TelemetryError};
+
+/// Checks if a name is a valid Prometheus metric name (`[a-zA-Z_:][a-zA-Z0-9_:]*`)
+fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Checks if a name is a valid Prometheus label name (`[a-zA-Z_][a-zA-Z0-9_]*`)
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escapes the help text. Backslashes and line feeds must be escaped.
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Escapes a label value. Backslashes, double quotes and line feeds must be escaped.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Formats a sample value, using the special representations for NaN and infinities.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Encodes a single metric into a sample line of the Prometheus text exposition format.
+///
+/// # Arguments
+///
+/// - `metric`: The metric to encode
+///
+/// # Returns
+///
+/// A `Result` with the encoded sample line (without a trailing newline), or a `TelemetryError`
+/// if the metric can not be encoded.
+///
+/// # Errors
+///
+/// - `TelemetryError::InvalidName`: If the metric name or any of its label names is not valid.
+fn encode_sample(metric: &Metric) -> Result<String, TelemetryError> {
+    let mut line = metric.name.clone();
+    if !metric.labels.is_empty() {
+        let mut labels = Vec::with_capacity(metric.labels.len());
+        for (key, value) in &metric.labels {
+            if !is_valid_label_name(key) || key.starts_with("__") {
+                return Err(TelemetryError::InvalidName(key.clone()));
+            }
+            labels.push(format!("{}=\"{}\"", key, escape_label_value(value)));
+        }
+        line.push_str(&format!("{{{}}}", labels.join(",")));
+    }
+    line.push_str(&format!(" {}", format_value(metric.value)));
+    if let Some(timestamp) = metric.timestamp {
+        line.push_str(&format!(" {}", timestamp));
+    }
+    Ok(line)
+}
+
+/// Encodes metrics into the Prometheus text exposition format (version 0.0.4). Samples
+/// that share a name are grouped together under a single `# HELP` and `# TYPE` line,
+/// which are taken from the first metric with that name.
+///
+/// # Arguments
+///
+/// - `metrics`: The metrics to encode
+///
+/// # Returns
+///
+/// A `Result` with the encoded body, ending with a newline, or a `TelemetryError` if any
+/// of the metrics can not be encoded.
+///
+/// # Errors
+///
+/// - `TelemetryError::EmptyBatch`: If there are no metrics to encode.
+/// - `TelemetryError::InvalidName`: If a metric name or label name is not valid.
+pub fn to_text_exposition(metrics: &[Metric]) -> Result<String, TelemetryError> {
+    if metrics.is_empty() {
+        return Err(TelemetryError::EmptyBatch);
+    }
+
+    let mut names: Vec<&str> = vec![];
+    for metric in metrics {
+        if !is_valid_metric_name(&metric.name) {
+            return Err(TelemetryError::InvalidName(metric.name.clone()));
+        }
+        if !names.contains(&metric.name.as_str()) {
+            names.push(&metric.name);
+        }
+    }
+
+    let mut body = String::new();
+    for name in names {
+        let mut family = metrics.iter().filter(|m| m.name == name).peekable();
+        if let Some(first) = family.peek() {
+            if let Some(help) = &first.help {
+                body.push_str(&format!("# HELP {} {}\n", name, escape_help(help)));
+            }
+            body.push_str(&format!("# TYPE {} {}\n", name, first.metric_type.as_str()));
+        }
+        for metric in family {
+            body.push_str(&encode_sample(metric)?);
+            body.push('\n');
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prometheus_01_simple_gauge() {
+        let metrics = vec![Metric::gauge("temperature_celsius", 21.5)];
+        assert_eq!(
+            to_text_exposition(&metrics).unwrap(),
+            "# TYPE temperature_celsius gauge\ntemperature_celsius 21.5\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_02_help_labels_and_timestamp() {
+        let metrics = vec![Metric::counter("button_presses_total", 3.0)
+            .help("Times the button was pressed")
+            .label("pin", "9")
+            .timestamp(1_700_000_000_000)];
+        assert_eq!(
+            to_text_exposition(&metrics).unwrap(),
+            "# HELP button_presses_total Times the button was pressed\n\
+             # TYPE button_presses_total counter\n\
+             button_presses_total{pin=\"9\"} 3 1700000000000\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_03_samples_are_grouped_by_name() {
+        let metrics = vec![
+            Metric::gauge("a", 1.0).label("x", "1"),
+            Metric::gauge("b", 2.0),
+            Metric::gauge("a", 3.0).label("x", "2"),
+        ];
+        assert_eq!(
+            to_text_exposition(&metrics).unwrap(),
+            "# TYPE a gauge\na{x=\"1\"} 1\na{x=\"2\"} 3\n# TYPE b gauge\nb 2\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_04_escapes_label_values_and_help() {
+        let metrics = vec![Metric::gauge("m", 1.0)
+            .help("line\\one\nline two")
+            .label("l", "a\"b\\c\nd")];
+        assert_eq!(
+            to_text_exposition(&metrics).unwrap(),
+            "# HELP m line\\\\one\\nline two\n# TYPE m gauge\nm{l=\"a\\\"b\\\\c\\nd\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_05_special_values() {
+        let metrics = vec![
+            Metric::gauge("nan", f64::NAN),
+            Metric::gauge("inf", f64::INFINITY),
+            Metric::gauge("neg_inf", f64::NEG_INFINITY),
+        ];
+        assert_eq!(
+            to_text_exposition(&metrics).unwrap(),
+            "# TYPE nan gauge\nnan NaN\n# TYPE inf gauge\ninf +Inf\n# TYPE neg_inf gauge\nneg_inf -Inf\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_06_invalid_metric_name_fails() {
+        let metrics = vec![Metric::gauge("1invalid-name", 1.0)];
+        assert!(matches!(
+            to_text_exposition(&metrics),
+            Err(TelemetryError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn prometheus_07_invalid_label_name_fails() {
+        let metrics = vec![Metric::gauge("m", 1.0).label("__reserved", "v")];
+        assert!(matches!(
+            to_text_exposition(&metrics),
+            Err(TelemetryError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn prometheus_08_empty_batch_fails() {
+        assert!(matches!(
+            to_text_exposition(&[]),
+            Err(TelemetryError::EmptyBatch)
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+use super::{to_line_protocol_batch, to_text_exposition, Batch, Metric, Point, TelemetryError};
+use crate::wifi::http::{Http, HttpHeader, HttpHeaderType};
+
+const DEFAULT_BATCH_SIZE: usize = 20;
+const RESPONSE_BUFFER_SIZE: usize = 256;
+
+/// Percent-encodes a string so it can be used as part of an URI
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Sends a POST request with the given body and checks the response status.
+///
+/// # Arguments
+///
+/// - `client`: The client used to make the request
+/// - `uri`: The uri of the request
+/// - `headers`: The headers of the request
+/// - `body`: The body of the request
+///
+/// # Returns
+///
+/// A `Result` with Ok if the server answered with a 2xx status, or a `TelemetryError` otherwise.
+///
+/// # Errors
+///
+/// - `TelemetryError::Http`: If the request could not be sent or the response could not be read.
+/// - `TelemetryError::UnexpectedStatus`: If the server answered with a non 2xx status.
+fn post_and_check<H: Http>(
+    client: &mut H,
+    uri: &str,
+    headers: Vec<HttpHeader>,
+    body: String,
+) -> Result<(), TelemetryError> {
+    client.post(uri, headers, Some(body))?;
+    let mut buffer = [0_u8; RESPONSE_BUFFER_SIZE];
+    client.wait_for_response(&mut buffer)?;
+    let status = client.response_status();
+    if !(200..300).contains(&status) {
+        return Err(TelemetryError::UnexpectedStatus(status));
+    }
+    Ok(())
+}
+
+/// Configuration of an InfluxDB v2 server. It contains:
+/// - `url`: The base url of the server (e.g. `https://influx.example.com:8086`).
+/// - `org`: The organization that owns the bucket.
+/// - `bucket`: The bucket where the points are written.
+/// - `token`: An optional API token with write permission on the bucket.
+#[derive(Debug, Clone)]
+pub struct InfluxDbConfig {
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: Option<String>,
+}
+
+impl InfluxDbConfig {
+    /// Creates the uri of the write endpoint, using nanosecond precision.
+    fn write_uri(&self) -> String {
+        format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=ns",
+            self.url.trim_end_matches('/'),
+            percent_encode(&self.org),
+            percent_encode(&self.bucket)
+        )
+    }
+}
+
+/// Pushes points to an InfluxDB v2 server using the line protocol. Points are accumulated
+/// in a batch and sent once the batch is full or when [InfluxDbPusher::flush] is called.
+/// Any client that implements [Http] can be used, [crate::wifi::http::HttpsClient] is
+/// expected for servers behind TLS.
+pub struct InfluxDbPusher<H: Http> {
+    client: H,
+    config: InfluxDbConfig,
+    batch: Batch<Point>,
+}
+
+impl<H: Http> InfluxDbPusher<H> {
+    /// Creates a new InfluxDbPusher with the default batch size
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: The Http client used to push the points
+    /// - `config`: The configuration of the server
+    ///
+    /// # Returns
+    ///
+    /// The new InfluxDbPusher instance
+    pub fn new(client: H, config: InfluxDbConfig) -> Self {
+        Self::new_with_batch_size(client, config, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Creates a new InfluxDbPusher with the desired batch size
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: The Http client used to push the points
+    /// - `config`: The configuration of the server
+    /// - `batch_size`: The amount of points after which the batch is automatically pushed
+    ///
+    /// # Returns
+    ///
+    /// The new InfluxDbPusher instance
+    pub fn new_with_batch_size(client: H, config: InfluxDbConfig, batch_size: usize) -> Self {
+        InfluxDbPusher {
+            client,
+            config,
+            batch: Batch::new(batch_size),
+        }
+    }
+
+    /// Adds a point to the batch, pushing the whole batch if it becomes full.
+    ///
+    /// # Arguments
+    ///
+    /// - `point`: The point to add
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the point was added (and pushed if necessary), or a `TelemetryError`
+    /// if the push failed.
+    ///
+    /// # Errors
+    ///
+    /// Same as [InfluxDbPusher::flush].
+    pub fn add(&mut self, point: Point) -> Result<(), TelemetryError> {
+        if self.batch.push(point) {
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    /// Pushes every point in the batch to the server. The batch is emptied even if the push fails.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the points were accepted or there was nothing to push, or a
+    /// `TelemetryError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `TelemetryError::InvalidName`, `TelemetryError::InvalidValue`, `TelemetryError::NoFields`:
+    ///   If a point could not be encoded.
+    /// - `TelemetryError::Http`: If the request failed.
+    /// - `TelemetryError::UnexpectedStatus`: If the server did not accept the points.
+    pub fn flush(&mut self) -> Result<(), TelemetryError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let points = self.batch.take();
+        let body = to_line_protocol_batch(&points)?;
+
+        let mut headers = vec![HttpHeader::new(
+            HttpHeaderType::ContentType,
+            String::from("text/plain; charset=utf-8"),
+        )];
+        if let Some(token) = &self.config.token {
+            headers.push(HttpHeader::new(
+                HttpHeaderType::Authorization,
+                format!("Token {}", token),
+            ));
+        }
+        post_and_check(&mut self.client, &self.config.write_uri(), headers, body)
+    }
+
+    /// Gets the amount of points waiting to be pushed
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+}
+
+/// Pushes metrics to a Prometheus Pushgateway using the text exposition format. Metrics are
+/// accumulated in a batch and sent once the batch is full or when [PrometheusPusher::flush]
+/// is called. Every push replaces the metrics of the same name previously pushed for the job.
+pub struct PrometheusPusher<H: Http> {
+    client: H,
+    uri: String,
+    batch: Batch<Metric>,
+}
+
+impl<H: Http> PrometheusPusher<H> {
+    /// Creates a new PrometheusPusher with the default batch size
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: The Http client used to push the metrics
+    /// - `url`: The base url of the Pushgateway (e.g. `http://pushgateway:9091`)
+    /// - `job`: The job the metrics are grouped under
+    ///
+    /// # Returns
+    ///
+    /// The new PrometheusPusher instance
+    pub fn new(client: H, url: &str, job: &str) -> Self {
+        Self::new_with_batch_size(client, url, job, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Creates a new PrometheusPusher with the desired batch size
+    ///
+    /// # Arguments
+    ///
+    /// - `client`: The Http client used to push the metrics
+    /// - `url`: The base url of the Pushgateway (e.g. `http://pushgateway:9091`)
+    /// - `job`: The job the metrics are grouped under
+    /// - `batch_size`: The amount of metrics after which the batch is automatically pushed
+    ///
+    /// # Returns
+    ///
+    /// The new PrometheusPusher instance
+    pub fn new_with_batch_size(client: H, url: &str, job: &str, batch_size: usize) -> Self {
+        PrometheusPusher {
+            client,
+            uri: format!(
+                "{}/metrics/job/{}",
+                url.trim_end_matches('/'),
+                percent_encode(job)
+            ),
+            batch: Batch::new(batch_size),
+        }
+    }
+
+    /// Adds a grouping label to the uri, so metrics of different instances of the same job
+    /// do not overwrite each other.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the label (e.g. `instance`)
+    /// - `value`: The value of the label
+    ///
+    /// # Returns
+    ///
+    /// The same PrometheusPusher with the grouping label added
+    pub fn with_grouping_label(mut self, name: &str, value: &str) -> Self {
+        self.uri.push_str(&format!(
+            "/{}/{}",
+            percent_encode(name),
+            percent_encode(value)
+        ));
+        self
+    }
+
+    /// Adds a metric to the batch, pushing the whole batch if it becomes full.
+    ///
+    /// # Arguments
+    ///
+    /// - `metric`: The metric to add
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the metric was added (and pushed if necessary), or a `TelemetryError`
+    /// if the push failed.
+    ///
+    /// # Errors
+    ///
+    /// Same as [PrometheusPusher::flush].
+    pub fn add(&mut self, metric: Metric) -> Result<(), TelemetryError> {
+        if self.batch.push(metric) {
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    /// Pushes every metric in the batch to the Pushgateway. The batch is emptied even if the push fails.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the metrics were accepted or there was nothing to push, or a
+    /// `TelemetryError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `TelemetryError::InvalidName`: If a metric could not be encoded.
+    /// - `TelemetryError::Http`: If the request failed.
+    /// - `TelemetryError::UnexpectedStatus`: If the Pushgateway did not accept the metrics.
+    pub fn flush(&mut self) -> Result<(), TelemetryError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let metrics = self.batch.take();
+        let body = to_text_exposition(&metrics)?;
+        let headers = vec![HttpHeader::new(
+            HttpHeaderType::ContentType,
+            String::from("text/plain; version=0.0.4"),
+        )];
+        let uri = self.uri.clone();
+        post_and_check(&mut self.client, &uri, headers, body)
+    }
+
+    /// Gets the amount of metrics waiting to be pushed
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_client_01_percent_encode() {
+        assert_eq!(percent_encode("my org/bucket"), "my%20org%2Fbucket");
+        assert_eq!(percent_encode("plain-value_1.0~"), "plain-value_1.0~");
+    }
+
+    #[test]
+    fn push_client_02_influx_write_uri() {
+        let config = InfluxDbConfig {
+            url: String::from("https://influx.local:8086/"),
+            org: String::from("home"),
+            bucket: String::from("sensors"),
+            token: None,
+        };
+        assert_eq!(
+            config.write_uri(),
+            "https://influx.local:8086/api/v2/write?org=home&bucket=sensors&precision=ns"
+        );
+    }
+}
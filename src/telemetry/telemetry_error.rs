@@ -0,0 +1,27 @@
+use crate::{serial::i2c::I2CError, wifi::http::HttpError};
+
+/// Enums the different errors possible when working with telemetry
+#[derive(Debug)]
+pub enum TelemetryError {
+    ClockNotSynchronized,
+    EmptyBatch,
+    Http(HttpError),
+    I2c(I2CError),
+    InvalidName(String),
+    InvalidValue(String),
+    NoFields,
+    SntpError,
+    UnexpectedStatus(u16),
+}
+
+impl From<HttpError> for TelemetryError {
+    fn from(value: HttpError) -> Self {
+        TelemetryError::Http(value)
+    }
+}
+
+impl From<I2CError> for TelemetryError {
+    fn from(value: I2CError) -> Self {
+        TelemetryError::I2c(value)
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+
+use super::TelemetryError;
+use crate::sensors::{DateTime, HourMode, Meridiem, DS3231};
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Any clock that can provide the current unix time to timestamp points and metrics.
+pub trait TimestampSource {
+    /// Gets the current unix time
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the amount of nanoseconds elapsed since 1970-01-01T00:00:00Z, or
+    /// a `TelemetryError` if the time could not be obtained.
+    fn now_ns(&mut self) -> Result<i64, TelemetryError>;
+
+    /// Gets the current unix time in milliseconds, as used by Prometheus
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the amount of milliseconds elapsed since 1970-01-01T00:00:00Z, or
+    /// a `TelemetryError` if the time could not be obtained.
+    fn now_ms(&mut self) -> Result<i64, TelemetryError> {
+        Ok(self.now_ns()? / 1_000_000)
+    }
+}
+
+/// Converts a civil date into the number of days since 1970-01-01.
+///
+/// # Arguments
+///
+/// - `year`: The full year (e.g. 2024)
+/// - `month`: The month (1-12)
+/// - `day`: The day of the month (1-31)
+///
+/// # Returns
+///
+/// The amount of days since the unix epoch. Negative for dates before it.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Converts a DS3231 [DateTime] (in 24 hour format) into a unix timestamp in seconds.
+///
+/// # Arguments
+///
+/// - `date_time`: The date and time to convert. Its year is interpreted as 2000 + year.
+///
+/// # Returns
+///
+/// The amount of seconds elapsed since 1970-01-01T00:00:00Z
+pub fn date_time_to_unix_seconds(date_time: &DateTime) -> i64 {
+    let days = days_from_civil(
+        2000 + date_time.year as i64,
+        date_time.month as i64,
+        date_time.date as i64,
+    );
+    days * SECONDS_PER_DAY
+        + date_time.hour as i64 * 3600
+        + date_time.minute as i64 * 60
+        + date_time.second as i64
+}
+
+impl TimestampSource for DS3231<'_> {
+    /// Gets the current unix time from the DS3231. The DS3231 has no notion of time zones,
+    /// so it is expected to be set in UTC.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the amount of nanoseconds elapsed since the unix epoch, or a
+    /// `TelemetryError` if the DS3231 could not be read.
+    ///
+    /// # Errors
+    ///
+    /// - `TelemetryError::I2c`: If reading the meridiem of the DS3231 fails.
+    fn now_ns(&mut self) -> Result<i64, TelemetryError> {
+        let mut date_time = self.get_date_time();
+        if !matches!(self.get_hour_mode(), HourMode::TwentyFourHour) {
+            date_time.hour %= 12;
+            if self.meridiem()? == Meridiem::PM {
+                date_time.hour += 12;
+            }
+        }
+        Ok(date_time_to_unix_seconds(&date_time) * NANOS_PER_SECOND)
+    }
+}
+
+/// Timestamp source that uses the system clock of the ESP32. The system clock starts at the
+/// unix epoch on every boot, so it must be synchronized (for example with [SystemClock::new_with_sntp])
+/// before its timestamps are meaningful.
+pub struct SystemClock {
+    sntp: Option<EspSntp<'static>>,
+}
+
+impl SystemClock {
+    /// Creates a new SystemClock that trusts the current system time
+    ///
+    /// # Returns
+    ///
+    /// The new SystemClock instance
+    pub fn new() -> Self {
+        SystemClock { sntp: None }
+    }
+
+    /// Creates a new SystemClock that keeps the system time synchronized with the default
+    /// SNTP servers. Wifi must be connected for the synchronization to happen.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the new SystemClock instance, or a `TelemetryError` if the SNTP
+    /// service could not be started.
+    ///
+    /// # Errors
+    ///
+    /// - `TelemetryError::SntpError`: If the SNTP service could not be started.
+    pub fn new_with_sntp() -> Result<Self, TelemetryError> {
+        let sntp = EspSntp::new_default().map_err(|_| TelemetryError::SntpError)?;
+        Ok(SystemClock { sntp: Some(sntp) })
+    }
+
+    /// Checks if the system time is synchronized. A SystemClock created without SNTP is
+    /// always considered synchronized.
+    ///
+    /// # Returns
+    ///
+    /// A bool. True if the time can be trusted
+    pub fn is_synchronized(&self) -> bool {
+        match &self.sntp {
+            Some(sntp) => sntp.get_sync_status() == SyncStatus::Completed,
+            None => true,
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimestampSource for SystemClock {
+    /// Gets the current unix time from the system clock.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the amount of nanoseconds elapsed since the unix epoch, or a
+    /// `TelemetryError` if the clock is not synchronized.
+    ///
+    /// # Errors
+    ///
+    /// - `TelemetryError::ClockNotSynchronized`: If SNTP has not completed a synchronization
+    ///   or the system time is before the unix epoch.
+    fn now_ns(&mut self) -> Result<i64, TelemetryError> {
+        if !self.is_synchronized() {
+            return Err(TelemetryError::ClockNotSynchronized);
+        }
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| TelemetryError::ClockNotSynchronized)?;
+        Ok(elapsed.as_nanos() as i64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date_time(year: u8, month: u8, date: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime {
+            second,
+            minute,
+            hour,
+            week_day: 1,
+            date,
+            month,
+            year,
+        }
+    }
+
+    #[test]
+    fn timestamp_01_start_of_century() {
+        assert_eq!(
+            date_time_to_unix_seconds(&date_time(0, 1, 1, 0, 0, 0)),
+            946_684_800
+        );
+    }
+
+    #[test]
+    fn timestamp_02_leap_day() {
+        assert_eq!(
+            date_time_to_unix_seconds(&date_time(24, 2, 29, 12, 30, 15)),
+            1_709_209_815
+        );
+    }
+
+    #[test]
+    fn timestamp_03_end_of_year() {
+        assert_eq!(
+            date_time_to_unix_seconds(&date_time(23, 12, 31, 23, 59, 59)),
+            1_704_067_199
+        );
+    }
+}
@@ -2,14 +2,16 @@ use esp_idf_svc::sys::EspError;
 
 use crate::{
     ble::BleError,
+    ble_mesh::MeshError,
     gpio::{
         analog::{AnalogInError, AnalogInPwmError, AnalogOutError},
         digital::{DigitalInError, DigitalOutError},
     },
     microcontroller_src::peripherals::PeripheralError,
     serial::{i2c::I2CError, uart::UARTError},
+    telemetry::TelemetryError,
     utils::timer_driver::TimerDriverError,
-    wifi::{http::HttpError, WifiError},
+    wifi::{http::HttpError, mqtt::MqttError, WifiError},
 };
 
 /// Represents various error conditions encountered in the ESP32 framework.
@@ -24,7 +26,10 @@ pub enum Esp32FrameworkError {
     DigitalOut(DigitalOutError),
     HttpError(HttpError),
     I2c(I2CError),
+    Mesh(MeshError),
+    Mqtt(MqttError),
     PeripheralError(PeripheralError),
+    Telemetry(TelemetryError),
     TimerDriver(TimerDriverError),
     Uart(UARTError),
     Wifi(WifiError),
@@ -58,7 +63,10 @@ impl_from_for_esp32_error! {
     DigitalOut => DigitalOutError,
     HttpError => HttpError,
     I2c => I2CError,
+    Mesh => MeshError,
+    Mqtt => MqttError,
     PeripheralError => PeripheralError,
+    Telemetry => TelemetryError,
     TimerDriver => TimerDriverError,
     Uart => UARTError,
     Wifi => WifiError,
@@ -1,5 +1,5 @@
 use esp_idf_svc::hal::task::{asynch::Notification as AsyncNotif, block_on};
-use std::sync::Arc;
+use std::{sync::Arc, task::Wake};
 
 /// Used for receiving a notification from an ISR context
 pub struct Notification {
@@ -76,6 +76,14 @@ impl Notifier {
     }
 }
 
+impl Wake for Notifier {
+    /// Notifies the associated `Notification`, so futures polled by a driver on its update are
+    /// polled again once they can make progress
+    fn wake(self: Arc<Self>) {
+        self.notify();
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
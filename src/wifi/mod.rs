@@ -1,4 +1,5 @@
 pub mod http;
+pub mod mqtt;
 mod wifi_driver;
 
 pub use wifi_driver::*;
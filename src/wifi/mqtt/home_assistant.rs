@@ -0,0 +1,709 @@
+use esp_idf_svc::hal::gpio::Level;
+use serde_json::{json, Value};
+
+use super::{LastWill, MqttClient, MqttConfiguration, MqttError, MqttQos};
+use crate::{
+    gpio::{analog::AnalogIn, analog::AnalogOut, digital::DigitalIn, digital::DigitalOut},
+    sensors::DS3231,
+    utils::auxiliary::{SharableRef, SharableRefExt},
+    Microcontroller,
+};
+
+const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+const TOPIC_BASE: &str = "esp32framework";
+const PAYLOAD_ONLINE: &str = "online";
+const PAYLOAD_OFFLINE: &str = "offline";
+const PAYLOAD_ON: &str = "ON";
+const PAYLOAD_OFF: &str = "OFF";
+const MAX_BRIGHTNESS: u8 = 255;
+
+/// Home Assistant entity types that can be created from framework drivers
+/// - `BinarySensor`: Read only on/off entity, created from a `DigitalIn`.
+/// - `Light`: Dimmable light, created from an `AnalogOut`.
+/// - `Sensor`: Read only numeric entity, created from an `AnalogIn`, a `DS3231` or a closure.
+/// - `Switch`: On/off entity that can be commanded, created from a `DigitalOut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeAssistantComponent {
+    BinarySensor,
+    Light,
+    Sensor,
+    Switch,
+}
+
+impl HomeAssistantComponent {
+    /// Gets the name Home Assistant uses for the component in discovery topics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HomeAssistantComponent::BinarySensor => "binary_sensor",
+            HomeAssistantComponent::Light => "light",
+            HomeAssistantComponent::Sensor => "sensor",
+            HomeAssistantComponent::Switch => "switch",
+        }
+    }
+}
+
+/// Converts an entity name into an id that can be used in topics and unique ids,
+/// keeping only lowercase ascii alphanumerics and replacing everything else with `_`.
+///
+/// # Arguments
+///
+/// - `name`: The human readable name of the entity
+///
+/// # Returns
+///
+/// A String with the object id
+pub fn object_id_from_name(name: &str) -> String {
+    let mut object_id = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            object_id.push(c.to_ascii_lowercase());
+        } else if !object_id.ends_with('_') {
+            object_id.push('_');
+        }
+    }
+    object_id.trim_matches('_').to_string()
+}
+
+/// Information needed to build the discovery configs and topics of a device
+/// - `discovery_prefix`: The prefix Home Assistant listens to for discovery configs.
+/// - `node_id`: The unique id of the device.
+/// - `device_name`: The human readable name of the device.
+#[derive(Debug, Clone)]
+struct DiscoveryInfo {
+    discovery_prefix: String,
+    node_id: String,
+    device_name: String,
+}
+
+impl DiscoveryInfo {
+    /// Gets the topic where the device publishes its availability
+    fn availability_topic(&self) -> String {
+        format!("{}/{}/availability", TOPIC_BASE, self.node_id)
+    }
+
+    /// Gets the topic Home Assistant publishes its own status to
+    fn status_topic(&self) -> String {
+        format!("{}/status", self.discovery_prefix)
+    }
+
+    /// Gets a topic of an entity (e.g. `state` or `set`)
+    fn entity_topic(
+        &self,
+        component: HomeAssistantComponent,
+        object_id: &str,
+        suffix: &str,
+    ) -> String {
+        format!(
+            "{}/{}/{}/{}/{}",
+            TOPIC_BASE,
+            self.node_id,
+            component.as_str(),
+            object_id,
+            suffix
+        )
+    }
+
+    /// Gets the topic where the discovery config of an entity is published
+    fn config_topic(&self, component: HomeAssistantComponent, object_id: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            self.discovery_prefix,
+            component.as_str(),
+            self.node_id,
+            object_id
+        )
+    }
+
+    /// Creates the discovery config shared by every entity: name, unique id, state topic,
+    /// availability and device information
+    fn base_config(&self, component: HomeAssistantComponent, object_id: &str, name: &str) -> Value {
+        json!({
+            "name": name,
+            "unique_id": format!("{}_{}", self.node_id, object_id),
+            "state_topic": self.entity_topic(component, object_id, "state"),
+            "availability_topic": self.availability_topic(),
+            "payload_available": PAYLOAD_ONLINE,
+            "payload_not_available": PAYLOAD_OFFLINE,
+            "device": {
+                "identifiers": [self.node_id],
+                "name": self.device_name,
+                "manufacturer": "Espressif",
+                "model": "ESP32",
+            },
+        })
+    }
+}
+
+/// State of a dimmable light
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LightState {
+    on: bool,
+    brightness: u8,
+}
+
+impl LightState {
+    /// Applies a Home Assistant JSON schema light command (e.g. `{"state":"ON","brightness":128}`)
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidPayload`: If the payload is not a valid light command.
+    fn apply_command(&mut self, payload: &[u8]) -> Result<(), MqttError> {
+        let command: Value =
+            serde_json::from_slice(payload).map_err(|_| MqttError::InvalidPayload)?;
+        if let Some(brightness) = command.get("brightness") {
+            let brightness = brightness.as_u64().ok_or(MqttError::InvalidPayload)?;
+            self.brightness = brightness.min(MAX_BRIGHTNESS as u64) as u8;
+        }
+        match command.get("state").and_then(Value::as_str) {
+            Some(PAYLOAD_ON) => self.on = true,
+            Some(PAYLOAD_OFF) => self.on = false,
+            _ => return Err(MqttError::InvalidPayload),
+        }
+        Ok(())
+    }
+
+    /// Gets the ratio of high level output that represents the state
+    fn output_ratio(&self) -> f32 {
+        match self.on {
+            true => self.brightness as f32 / MAX_BRIGHTNESS as f32,
+            false => 0.0,
+        }
+    }
+
+    /// Gets the state as the JSON schema Home Assistant expects
+    fn to_payload(self) -> String {
+        json!({
+            "state": on_off_payload(self.on),
+            "brightness": self.brightness,
+        })
+        .to_string()
+    }
+}
+
+/// Gets the payload that represents an on/off state
+fn on_off_payload(on: bool) -> &'static str {
+    match on {
+        true => PAYLOAD_ON,
+        false => PAYLOAD_OFF,
+    }
+}
+
+/// An entity of the device together with a closure that reads its current state
+struct HomeAssistantEntity<'a> {
+    component: HomeAssistantComponent,
+    object_id: String,
+    config: Value,
+    read_state: Box<dyn FnMut() -> Option<String> + 'a>,
+}
+
+/// Exposes framework drivers as Home Assistant entities using MQTT discovery.
+/// Upon every connection to the broker (and every time Home Assistant comes back online)
+/// the availability, discovery configs and states of every entity are published. The
+/// last will of the connection marks the device as unavailable if it disconnects.
+struct _HomeAssistantDevice<'a> {
+    mqtt: MqttClient<'a>,
+    info: DiscoveryInfo,
+    entities: Vec<HomeAssistantEntity<'a>>,
+}
+
+/// Exposes framework drivers as Home Assistant entities using MQTT discovery.
+/// Upon every connection to the broker (and every time Home Assistant comes back online)
+/// the availability, discovery configs and states of every entity are published. The
+/// last will of the connection marks the device as unavailable if it disconnects.
+///
+/// Note: Commands sent from Home Assistant are executed on the [crate::Microcontroller] update
+/// loop, so [crate::Microcontroller::wait_for_updates] must be called periodicly, unless using an
+/// async aproach in which case [crate::Microcontroller::block_on] must be used.
+pub struct HomeAssistantDevice<'a> {
+    inner: SharableRef<_HomeAssistantDevice<'a>>,
+}
+
+impl<'a> HomeAssistantDevice<'a> {
+    /// Creates a new HomeAssistantDevice, connecting to the broker with an availability last will.
+    ///
+    /// # Arguments
+    ///
+    /// - `micro`: The microcontroller, used to create the `MqttClient`
+    /// - `uri`: The uri of the broker Home Assistant is connected to
+    /// - `configuration`: The configuration of the connection. Its last will is replaced by the availability one.
+    /// - `node_id`: A unique id for the device. Only ascii alphanumerics, `_` and `-` should be used.
+    /// - `device_name`: The name of the device shown in Home Assistant
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `HomeAssistantDevice`, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::ConnectionError`: If the mqtt client could not be initialized.
+    /// - `MqttError::InvalidTopic`: If the node id is empty.
+    pub fn new(
+        micro: &mut Microcontroller<'a>,
+        uri: &str,
+        configuration: MqttConfiguration,
+        node_id: &str,
+        device_name: &str,
+    ) -> Result<Self, MqttError> {
+        Self::new_with_discovery_prefix(
+            micro,
+            uri,
+            configuration,
+            node_id,
+            device_name,
+            DEFAULT_DISCOVERY_PREFIX,
+        )
+    }
+
+    /// Same as [Self::new] but with a custom discovery prefix, for Home Assistant instances
+    /// that do not use the default `homeassistant` one.
+    pub fn new_with_discovery_prefix(
+        micro: &mut Microcontroller<'a>,
+        uri: &str,
+        configuration: MqttConfiguration,
+        node_id: &str,
+        device_name: &str,
+        discovery_prefix: &str,
+    ) -> Result<Self, MqttError> {
+        if node_id.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        let info = DiscoveryInfo {
+            discovery_prefix: discovery_prefix.to_string(),
+            node_id: node_id.to_string(),
+            device_name: device_name.to_string(),
+        };
+        let configuration = configuration.set_last_will(LastWill {
+            topic: info.availability_topic(),
+            payload: PAYLOAD_OFFLINE.as_bytes().to_vec(),
+            qos: MqttQos::AtLeastOnce,
+            retain: true,
+        });
+        let mut mqtt = micro.mqtt_client(uri, &configuration)?;
+
+        let device = HomeAssistantDevice {
+            inner: SharableRef::new_sharable(_HomeAssistantDevice {
+                mqtt: mqtt.clone(),
+                info: info.clone(),
+                entities: vec![],
+            }),
+        };
+
+        let mut inner_ref = device.inner.clone();
+        mqtt.on_connect(move || {
+            if let Err(err) = inner_ref.deref_mut().announce() {
+                log::warn!("Could not announce device to Home Assistant: {:?}", err)
+            }
+        });
+
+        let mut inner_ref = device.inner.clone();
+        mqtt.subscribe(&info.status_topic(), MqttQos::AtLeastOnce, move |msg| {
+            if msg.payload_str() == Some(PAYLOAD_ONLINE) {
+                if let Err(err) = inner_ref.deref_mut().announce() {
+                    log::warn!("Could not announce device to Home Assistant: {:?}", err)
+                }
+            }
+        })?;
+        Ok(device)
+    }
+}
+
+#[sharable_reference_macro::sharable_reference_wrapper]
+impl<'a> _HomeAssistantDevice<'a> {
+    /// Gets a clone of the `MqttClient` used by the device, in order to publish or subscribe
+    /// to other topics over the same connection.
+    pub fn mqtt_client(&self) -> MqttClient<'a> {
+        self.mqtt.clone()
+    }
+
+    /// Publishes the availability, every discovery config and every state
+    fn announce(&mut self) -> Result<(), MqttError> {
+        let availability_topic = self.info.availability_topic();
+        self.mqtt.publish_str(
+            &availability_topic,
+            PAYLOAD_ONLINE,
+            MqttQos::AtLeastOnce,
+            true,
+        )?;
+        for i in 0..self.entities.len() {
+            self.publish_config(i)?;
+        }
+        self.publish_states()
+    }
+
+    /// Publishes the discovery config of the entity at the given index
+    fn publish_config(&mut self, index: usize) -> Result<(), MqttError> {
+        let entity = &self.entities[index];
+        let topic = self.info.config_topic(entity.component, &entity.object_id);
+        let payload = entity.config.to_string();
+        self.mqtt
+            .publish_str(&topic, &payload, MqttQos::AtLeastOnce, true)
+    }
+
+    /// Publishes the current state of the entity at the given index, if it could be read
+    fn publish_state(&mut self, index: usize) -> Result<(), MqttError> {
+        let entity = &mut self.entities[index];
+        let topic = self
+            .info
+            .entity_topic(entity.component, &entity.object_id, "state");
+        match (entity.read_state)() {
+            Some(state) => self
+                .mqtt
+                .publish_str(&topic, &state, MqttQos::AtMostOnce, true),
+            None => Ok(()),
+        }
+    }
+
+    /// Publishes the current state of every entity. Sensors and binary sensors are only updated
+    /// when this method is called, so it should be called periodicly.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if every state was published, or an `MqttError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::PublishError`: If a state could not be published.
+    pub fn publish_states(&mut self) -> Result<(), MqttError> {
+        if !self.mqtt.is_connected() {
+            return Ok(());
+        }
+        for i in 0..self.entities.len() {
+            self.publish_state(i)?;
+        }
+        Ok(())
+    }
+
+    /// Registers a new entity, announcing it right away if the client is connected
+    fn add_entity(
+        &mut self,
+        component: HomeAssistantComponent,
+        name: &str,
+        extra_config: Value,
+        read_state: Box<dyn FnMut() -> Option<String> + 'a>,
+    ) -> Result<String, MqttError> {
+        let object_id = object_id_from_name(name);
+        if object_id.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        let mut config = self.info.base_config(component, &object_id, name);
+        if let (Some(config), Value::Object(extra)) = (config.as_object_mut(), extra_config) {
+            config.extend(extra);
+        }
+        self.entities.push(HomeAssistantEntity {
+            component,
+            object_id: object_id.clone(),
+            config,
+            read_state,
+        });
+        if self.mqtt.is_connected() {
+            self.publish_config(self.entities.len() - 1)?;
+            self.publish_state(self.entities.len() - 1)?;
+        }
+        Ok(object_id)
+    }
+
+    /// Exposes a `DigitalOut` as a switch. Turning the switch on or off from Home Assistant
+    /// sets the pin high or low.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the entity
+    /// - `digital_out`: The DigitalOut controlled by the switch
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the entity was added, or an `MqttError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the name has no alphanumeric characters.
+    /// - `MqttError::SubscribeError`: If the command topic could not be subscribed.
+    /// - `MqttError::PublishError`: If the entity could not be announced.
+    pub fn add_switch(&mut self, name: &str, digital_out: DigitalOut<'a>) -> Result<(), MqttError> {
+        let component = HomeAssistantComponent::Switch;
+        let object_id = object_id_from_name(name);
+        let command_topic = self.info.entity_topic(component, &object_id, "set");
+        let state_topic = self.info.entity_topic(component, &object_id, "state");
+        let digital_out = SharableRef::new_sharable(digital_out);
+
+        let mut state_ref = digital_out.clone();
+        self.add_entity(
+            component,
+            name,
+            json!({
+                "command_topic": command_topic,
+                "payload_on": PAYLOAD_ON,
+                "payload_off": PAYLOAD_OFF,
+            }),
+            Box::new(move || {
+                let on = state_ref.deref_mut().get_level() == Level::High;
+                Some(on_off_payload(on).to_string())
+            }),
+        )?;
+
+        let mut mqtt = self.mqtt.clone();
+        let mut command_ref = digital_out;
+        self.mqtt
+            .subscribe(&command_topic, MqttQos::AtLeastOnce, move |msg| {
+                let result = match msg.payload_str() {
+                    Some(PAYLOAD_ON) => command_ref.deref_mut().set_high(),
+                    Some(PAYLOAD_OFF) => command_ref.deref_mut().set_low(),
+                    _ => return,
+                };
+                if result.is_ok() {
+                    let on = command_ref.deref_mut().get_level() == Level::High;
+                    let _ = mqtt.publish_str(
+                        &state_topic,
+                        on_off_payload(on),
+                        MqttQos::AtLeastOnce,
+                        true,
+                    );
+                }
+            })
+    }
+
+    /// Exposes an `AnalogOut` as a dimmable light, using the JSON schema. The brightness set from
+    /// Home Assistant (0-255) is mapped to the high level output ratio of the pin.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the entity
+    /// - `analog_out`: The AnalogOut controlled by the light
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the entity was added, or an `MqttError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the name has no alphanumeric characters.
+    /// - `MqttError::SubscribeError`: If the command topic could not be subscribed.
+    /// - `MqttError::PublishError`: If the entity could not be announced.
+    pub fn add_light(&mut self, name: &str, analog_out: AnalogOut<'a>) -> Result<(), MqttError> {
+        let component = HomeAssistantComponent::Light;
+        let object_id = object_id_from_name(name);
+        let command_topic = self.info.entity_topic(component, &object_id, "set");
+        let state_topic = self.info.entity_topic(component, &object_id, "state");
+        let light = SharableRef::new_sharable((
+            LightState {
+                on: false,
+                brightness: MAX_BRIGHTNESS,
+            },
+            analog_out,
+        ));
+
+        let state_ref = light.clone();
+        self.add_entity(
+            component,
+            name,
+            json!({
+                "schema": "json",
+                "command_topic": command_topic,
+                "brightness": true,
+                "brightness_scale": MAX_BRIGHTNESS,
+            }),
+            Box::new(move || Some(state_ref.deref().0.to_payload())),
+        )?;
+
+        let mut mqtt = self.mqtt.clone();
+        let mut command_ref = light;
+        self.mqtt
+            .subscribe(&command_topic, MqttQos::AtLeastOnce, move |msg| {
+                let mut light = command_ref.deref_mut();
+                let (state, analog_out) = &mut *light;
+                if state.apply_command(&msg.payload).is_err() {
+                    return;
+                }
+                if analog_out
+                    .set_high_level_output_ratio(state.output_ratio())
+                    .is_ok()
+                {
+                    let _ = mqtt.publish_str(
+                        &state_topic,
+                        &state.to_payload(),
+                        MqttQos::AtLeastOnce,
+                        true,
+                    );
+                }
+            })
+    }
+
+    /// Exposes a numeric value as a sensor. The value is read every time the states are published.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the entity
+    /// - `unit`: An optional unit of measurement (e.g. `°C`)
+    /// - `device_class`: An optional Home Assistant sensor device class (e.g. `temperature`)
+    /// - `read`: A closure that returns the current value, or None if it could not be read
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the entity was added, or an `MqttError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the name has no alphanumeric characters.
+    /// - `MqttError::PublishError`: If the entity could not be announced.
+    pub fn add_sensor<F: FnMut() -> Option<f32> + 'a>(
+        &mut self,
+        name: &str,
+        unit: Option<&str>,
+        device_class: Option<&str>,
+        read: F,
+    ) -> Result<(), MqttError> {
+        let mut read = read;
+        let mut extra = json!({ "state_class": "measurement" });
+        if let Some(unit) = unit {
+            extra["unit_of_measurement"] = json!(unit);
+        }
+        if let Some(device_class) = device_class {
+            extra["device_class"] = json!(device_class);
+        }
+        self.add_entity(
+            HomeAssistantComponent::Sensor,
+            name,
+            extra,
+            Box::new(move || read().map(|value| value.to_string())),
+        )?;
+        Ok(())
+    }
+
+    /// Exposes the reading of an `AnalogIn` as a sensor. See [Self::add_sensor].
+    pub fn add_analog_in_sensor(
+        &mut self,
+        name: &str,
+        analog_in: AnalogIn<'a>,
+    ) -> Result<(), MqttError> {
+        let mut analog_in = analog_in;
+        self.add_sensor(name, None, None, move || {
+            analog_in.read().ok().map(|value| value as f32)
+        })
+    }
+
+    /// Exposes the temperature of a `DS3231` as a temperature sensor in Celsius. See [Self::add_sensor].
+    pub fn add_ds3231_temperature_sensor(
+        &mut self,
+        name: &str,
+        ds3231: DS3231<'a>,
+    ) -> Result<(), MqttError> {
+        let mut ds3231 = ds3231;
+        self.add_sensor(name, Some("°C"), Some("temperature"), move || {
+            ds3231.get_temperature().ok()
+        })
+    }
+
+    /// Exposes a `DigitalIn` as a binary sensor, which is on while the pin is high. The level
+    /// is read every time the states are published.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the entity
+    /// - `device_class`: An optional Home Assistant binary sensor device class (e.g. `door`)
+    /// - `digital_in`: The DigitalIn read by the binary sensor
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the entity was added, or an `MqttError` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the name has no alphanumeric characters.
+    /// - `MqttError::PublishError`: If the entity could not be announced.
+    pub fn add_binary_sensor(
+        &mut self,
+        name: &str,
+        device_class: Option<&str>,
+        digital_in: DigitalIn<'a>,
+    ) -> Result<(), MqttError> {
+        let mut extra = json!({
+            "payload_on": PAYLOAD_ON,
+            "payload_off": PAYLOAD_OFF,
+        });
+        if let Some(device_class) = device_class {
+            extra["device_class"] = json!(device_class);
+        }
+        self.add_entity(
+            HomeAssistantComponent::BinarySensor,
+            name,
+            extra,
+            Box::new(move || Some(on_off_payload(digital_in.is_high()).to_string())),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info() -> DiscoveryInfo {
+        DiscoveryInfo {
+            discovery_prefix: String::from("homeassistant"),
+            node_id: String::from("kitchen_esp32"),
+            device_name: String::from("Kitchen"),
+        }
+    }
+
+    #[test]
+    fn home_assistant_01_object_id_from_name() {
+        assert_eq!(object_id_from_name("Kitchen Light"), "kitchen_light");
+        assert_eq!(object_id_from_name("  Temp. (°C) "), "temp_c");
+        assert_eq!(object_id_from_name("!!"), "");
+    }
+
+    #[test]
+    fn home_assistant_02_topics() {
+        let info = info();
+        assert_eq!(
+            info.availability_topic(),
+            "esp32framework/kitchen_esp32/availability"
+        );
+        assert_eq!(
+            info.config_topic(HomeAssistantComponent::Switch, "led"),
+            "homeassistant/switch/kitchen_esp32/led/config"
+        );
+        assert_eq!(
+            info.entity_topic(HomeAssistantComponent::BinarySensor, "door", "state"),
+            "esp32framework/kitchen_esp32/binary_sensor/door/state"
+        );
+    }
+
+    #[test]
+    fn home_assistant_03_base_config() {
+        let config = info().base_config(HomeAssistantComponent::Sensor, "temp", "Temp");
+        assert_eq!(config["unique_id"], "kitchen_esp32_temp");
+        assert_eq!(
+            config["state_topic"],
+            "esp32framework/kitchen_esp32/sensor/temp/state"
+        );
+        assert_eq!(
+            config["availability_topic"],
+            "esp32framework/kitchen_esp32/availability"
+        );
+        assert_eq!(config["device"]["identifiers"][0], "kitchen_esp32");
+    }
+
+    #[test]
+    fn home_assistant_04_light_command() {
+        let mut state = LightState {
+            on: false,
+            brightness: 255,
+        };
+        state
+            .apply_command(br#"{"state":"ON","brightness":51}"#)
+            .unwrap();
+        assert_eq!(
+            state,
+            LightState {
+                on: true,
+                brightness: 51
+            }
+        );
+        assert_eq!(state.output_ratio(), 0.2);
+        state.apply_command(br#"{"state":"OFF"}"#).unwrap();
+        assert!(!state.on);
+        assert_eq!(state.output_ratio(), 0.0);
+        assert!(state.apply_command(b"ON").is_err());
+    }
+}
@@ -0,0 +1,9 @@
+mod home_assistant;
+mod mqtt_client;
+mod rpc;
+mod shadow;
+
+pub use home_assistant::*;
+pub use mqtt_client::*;
+pub use rpc::*;
+pub use shadow::*;
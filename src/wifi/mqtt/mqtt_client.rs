@@ -0,0 +1,611 @@
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::Duration,
+};
+
+use esp_idf_svc::mqtt::client::{
+    Details, EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration, QoS,
+};
+
+use crate::{
+    utils::{
+        auxiliary::{SharableRef, SharableRefExt},
+        esp32_framework_error::Esp32FrameworkError,
+        notification::Notifier,
+    },
+    InterruptDriver,
+};
+
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Error types related to MQTT operations.
+#[derive(Debug)]
+pub enum MqttError {
+    ConnectionError,
+    InvalidPayload,
+    InvalidTopic,
+    NotConnected,
+    PublishError,
+    SubscribeError,
+    Timeout,
+}
+
+/// Quality of service levels of MQTT messages
+/// - `AtMostOnce`: The message is sent once and may be lost.
+/// - `AtLeastOnce`: The message is resent until acknowledged, so it may arrive more than once.
+/// - `ExactlyOnce`: The message arrives exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MqttQos {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl From<MqttQos> for QoS {
+    fn from(value: MqttQos) -> Self {
+        match value {
+            MqttQos::AtMostOnce => QoS::AtMostOnce,
+            MqttQos::AtLeastOnce => QoS::AtLeastOnce,
+            MqttQos::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
+/// Message the broker will publish on behalf of the client if it disconnects ungracefully.
+/// - `topic`: The topic the message is published to.
+/// - `payload`: The content of the message.
+/// - `qos`: The quality of service of the message.
+/// - `retain`: Whether the broker should retain the message.
+#[derive(Debug, Clone)]
+pub struct LastWill {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: MqttQos,
+    pub retain: bool,
+}
+
+/// Configuration used to connect to an MQTT broker. Created with [MqttConfiguration::new] and
+/// customized with its builder methods.
+#[derive(Debug, Clone)]
+pub struct MqttConfiguration {
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    keep_alive: Duration,
+    last_will: Option<LastWill>,
+}
+
+impl MqttConfiguration {
+    /// Creates a new MqttConfiguration without credentials nor last will
+    ///
+    /// # Arguments
+    ///
+    /// - `client_id`: The id the client uses to identify itself to the broker. Must be unique per broker.
+    ///
+    /// # Returns
+    ///
+    /// The new MqttConfiguration instance
+    pub fn new(client_id: &str) -> Self {
+        MqttConfiguration {
+            client_id: client_id.to_string(),
+            username: None,
+            password: None,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            last_will: None,
+        }
+    }
+
+    /// Sets the credentials used to authenticate with the broker
+    pub fn set_credentials(mut self, username: &str, password: &str) -> Self {
+        self.username = Some(username.to_string());
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Sets the keep alive interval of the connection
+    pub fn set_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Sets the last will of the client
+    pub fn set_last_will(mut self, last_will: LastWill) -> Self {
+        self.last_will = Some(last_will);
+        self
+    }
+
+    /// Gets the client id
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Gets the last will, if it was set
+    pub fn last_will(&self) -> Option<&LastWill> {
+        self.last_will.as_ref()
+    }
+
+    /// Creates the esp-idf configuration, which borrows from self
+    fn as_esp_configuration(&self) -> MqttClientConfiguration {
+        MqttClientConfiguration {
+            client_id: Some(&self.client_id),
+            username: self.username.as_deref(),
+            password: self.password.as_deref(),
+            keep_alive_interval: Some(self.keep_alive),
+            lwt: self.last_will.as_ref().map(|last_will| LwtConfiguration {
+                topic: &last_will.topic,
+                payload: &last_will.payload,
+                qos: last_will.qos.into(),
+                retain: last_will.retain,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// A message received from the broker
+/// - `topic`: The topic the message was published to.
+/// - `payload`: The content of the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+impl MqttMessage {
+    /// Gets the payload as a string slice
+    ///
+    /// # Returns
+    ///
+    /// An Option with the payload if it is valid UTF-8, otherwise None
+    pub fn payload_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.payload).ok()
+    }
+}
+
+/// Events sent from the mqtt task to the [MqttClient] updater
+enum MqttEvent {
+    Connected,
+    Disconnected,
+    Message(MqttMessage),
+}
+
+/// Checks if a topic matches a topic filter, where `+` matches exactly one level and
+/// `#` matches any amount of remaining levels.
+///
+/// # Arguments
+///
+/// - `filter`: The topic filter, as used when subscribing
+/// - `topic`: The topic of a published message
+///
+/// # Returns
+///
+/// A bool. True if the topic matches the filter
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return !topic.starts_with('$') || !filter.starts_with('#'),
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+type MessageCallback<'a> = Box<dyn FnMut(&MqttMessage) + 'a>;
+
+/// A subscription to a topic filter and the callback executed for every matching message
+struct Subscription<'a> {
+    filter: String,
+    qos: MqttQos,
+    callback: MessageCallback<'a>,
+}
+
+/// Driver that connects to an MQTT broker, can publish messages and subscribe to topics
+struct _MqttClient {
+    client: EspMqttClient<'static>,
+    connected: Arc<AtomicBool>,
+}
+
+/// Auxiliary struct used for the updating of the client. Holds the messages received from the
+/// mqtt task and the user callbacks
+/// - `dispatching`: Whether the callbacks are being executed, while they are taken out of the
+///   updater
+struct MqttClientUpdater<'a> {
+    receiver: Receiver<MqttEvent>,
+    subscriptions: Vec<Subscription<'a>>,
+    on_connect: Vec<Box<dyn FnMut() + 'a>>,
+    on_disconnect: Vec<Box<dyn FnMut() + 'a>>,
+    dispatching: bool,
+}
+
+/// Driver that connects to an MQTT broker, can publish messages and subscribe to topics.
+/// Subscription callbacks are executed on the [crate::Microcontroller] update loop, so
+/// [crate::Microcontroller::wait_for_updates] must be called periodicly, unless using an
+/// async aproach in which case [crate::Microcontroller::block_on] must be used.
+///
+/// Clones of an MqttClient share the same connection, so they can be moved into callbacks
+/// that need to publish.
+pub struct MqttClient<'a> {
+    inner: SharableRef<_MqttClient>,
+    updater: SharableRef<MqttClientUpdater<'a>>,
+}
+
+impl Clone for MqttClient<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            updater: self.updater.clone(),
+        }
+    }
+}
+
+/// Forwards an event to the updater and wakes up the microcontroller
+fn send_event(sender: &Sender<MqttEvent>, notifier: &Notifier, event: MqttEvent) {
+    if sender.send(event).is_ok() {
+        notifier.notify();
+    }
+}
+
+impl<'a> MqttClient<'a> {
+    /// Creates a new MqttClient and starts connecting to the broker. If the connection is
+    /// lost the client reconnects automatically, subscribing again to every topic filter.
+    ///
+    /// # Arguments
+    ///
+    /// - `uri`: The uri of the broker (e.g. `mqtt://192.168.0.10:1883` or `mqtts://broker.example.com`)
+    /// - `configuration`: The configuration of the connection
+    /// - `notifier`: A notifier in order to wake up the [crate::Microcontroller] upon received messages
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `MqttClient`, or an `MqttError` if the client could not be created.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::ConnectionError`: If the underlying client could not be initialized.
+    pub(crate) fn new(
+        uri: &str,
+        configuration: &MqttConfiguration,
+        notifier: Notifier,
+    ) -> Result<Self, MqttError> {
+        let (sender, receiver) = channel();
+        let connected = Arc::new(AtomicBool::new(false));
+        let connected_ref = connected.clone();
+        let mut chunked_message: Option<MqttMessage> = None;
+
+        let client =
+            EspMqttClient::new_cb(uri, &configuration.as_esp_configuration(), move |event| {
+                match event.payload() {
+                    EventPayload::Connected(_) => {
+                        connected_ref.store(true, Ordering::SeqCst);
+                        send_event(&sender, &notifier, MqttEvent::Connected)
+                    }
+                    EventPayload::Disconnected => {
+                        connected_ref.store(false, Ordering::SeqCst);
+                        send_event(&sender, &notifier, MqttEvent::Disconnected)
+                    }
+                    EventPayload::Received {
+                        topic,
+                        data,
+                        details,
+                        ..
+                    } => match details {
+                        Details::Complete => {
+                            let message = MqttMessage {
+                                topic: topic.unwrap_or_default().to_string(),
+                                payload: data.to_vec(),
+                            };
+                            send_event(&sender, &notifier, MqttEvent::Message(message))
+                        }
+                        Details::InitialChunk(chunk) => {
+                            let mut payload = Vec::with_capacity(chunk.total_data_size);
+                            payload.extend_from_slice(data);
+                            chunked_message = Some(MqttMessage {
+                                topic: topic.unwrap_or_default().to_string(),
+                                payload,
+                            });
+                        }
+                        Details::SubsequentChunk(chunk) => {
+                            if let Some(message) = chunked_message.as_mut() {
+                                message.payload.extend_from_slice(data);
+                                if message.payload.len() >= chunk.total_data_size {
+                                    let message = chunked_message.take().unwrap();
+                                    send_event(&sender, &notifier, MqttEvent::Message(message))
+                                }
+                            }
+                        }
+                    },
+                    _ => {}
+                }
+            })
+            .map_err(|_| MqttError::ConnectionError)?;
+
+        Ok(MqttClient {
+            inner: SharableRef::new_sharable(_MqttClient { client, connected }),
+            updater: SharableRef::new_sharable(MqttClientUpdater {
+                receiver,
+                subscriptions: vec![],
+                on_connect: vec![],
+                on_disconnect: vec![],
+                dispatching: false,
+            }),
+        })
+    }
+
+    /// Checks if the client is currently connected to the broker
+    pub fn is_connected(&self) -> bool {
+        self.inner.deref().connected.load(Ordering::SeqCst)
+    }
+
+    /// Publishes a message
+    ///
+    /// # Arguments
+    ///
+    /// - `topic`: The topic to publish the message to
+    /// - `payload`: The content of the message
+    /// - `qos`: The quality of service of the message
+    /// - `retain`: Whether the broker should retain the message for future subscribers
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the message was handed to the mqtt task, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the topic is empty or contains wildcards.
+    /// - `MqttError::PublishError`: If the message could not be published.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: MqttQos,
+        retain: bool,
+    ) -> Result<(), MqttError> {
+        if topic.is_empty() || topic.contains(['+', '#']) {
+            return Err(MqttError::InvalidTopic);
+        }
+        let mut inner = self.inner.deref_mut();
+        let result = match qos {
+            MqttQos::AtMostOnce => inner.client.publish(topic, qos.into(), retain, payload),
+            _ => inner.client.enqueue(topic, qos.into(), retain, payload),
+        };
+        result.map(|_| ()).map_err(|_| MqttError::PublishError)
+    }
+
+    /// Publishes a string message. Same as [Self::publish].
+    pub fn publish_str(
+        &mut self,
+        topic: &str,
+        payload: &str,
+        qos: MqttQos,
+        retain: bool,
+    ) -> Result<(), MqttError> {
+        self.publish(topic, payload.as_bytes(), qos, retain)
+    }
+
+    /// Subscribes to a topic filter, executing the callback for every message that matches it.
+    /// If the client is not connected the subscription is sent once it connects.
+    ///
+    /// Note: For the callback to be executed, the method [crate::Microcontroller::wait_for_updates] must
+    /// be called periodicly, unless using an async aproach in which case [crate::Microcontroller::block_on]
+    /// must be used.
+    ///
+    /// # Arguments
+    ///
+    /// - `filter`: The topic filter, which may contain the `+` and `#` wildcards
+    /// - `qos`: The maximum quality of service of the received messages
+    /// - `callback`: A closure that receives every matching `MqttMessage`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the subscription was registered, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the filter is empty.
+    /// - `MqttError::SubscribeError`: If the client is connected and the subscription could not be sent.
+    pub fn subscribe<F: FnMut(&MqttMessage) + 'a>(
+        &mut self,
+        filter: &str,
+        qos: MqttQos,
+        callback: F,
+    ) -> Result<(), MqttError> {
+        if filter.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        if self.is_connected() {
+            self.send_subscription(filter, qos)?;
+        }
+        self.updater.deref_mut().subscriptions.push(Subscription {
+            filter: filter.to_string(),
+            qos,
+            callback: Box::new(callback),
+        });
+        Ok(())
+    }
+
+    /// Unsubscribes from a topic filter, removing every callback registered for it
+    ///
+    /// # Arguments
+    ///
+    /// - `filter`: The same topic filter used when subscribing
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with Ok if the subscription was removed, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::SubscribeError`: If the client is connected and the unsubscription could not be sent.
+    pub fn unsubscribe(&mut self, filter: &str) -> Result<(), MqttError> {
+        self.updater
+            .deref_mut()
+            .subscriptions
+            .retain(|subscription| subscription.filter != filter);
+        if self.is_connected() {
+            self.inner
+                .deref_mut()
+                .client
+                .unsubscribe(filter)
+                .map_err(|_| MqttError::SubscribeError)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a callback to be executed every time the client connects (or reconnects) to the broker.
+    /// Callbacks are accumulated, so setting a new one does not remove the previous ones.
+    pub fn on_connect<F: FnMut() + 'a>(&mut self, callback: F) {
+        self.updater.deref_mut().on_connect.push(Box::new(callback));
+    }
+
+    /// Sets a callback to be executed every time the client loses the connection to the broker.
+    /// Callbacks are accumulated, so setting a new one does not remove the previous ones.
+    pub fn on_disconnect<F: FnMut() + 'a>(&mut self, callback: F) {
+        self.updater
+            .deref_mut()
+            .on_disconnect
+            .push(Box::new(callback));
+    }
+
+    /// Sends a subscription to the broker
+    fn send_subscription(&mut self, filter: &str, qos: MqttQos) -> Result<(), MqttError> {
+        self.inner
+            .deref_mut()
+            .client
+            .subscribe(filter, qos.into())
+            .map(|_| ())
+            .map_err(|_| MqttError::SubscribeError)
+    }
+
+    /// Subscribes again to every topic filter, since subscriptions may be lost on reconnection
+    fn resubscribe_all(&mut self) -> Result<(), MqttError> {
+        let filters: Vec<(String, MqttQos)> = self
+            .updater
+            .deref()
+            .subscriptions
+            .iter()
+            .map(|subscription| (subscription.filter.clone(), subscription.qos))
+            .collect();
+        for (filter, qos) in filters {
+            self.send_subscription(&filter, qos)?;
+        }
+        Ok(())
+    }
+
+    /// Executes the connection callbacks. They are taken out of the updater during their
+    /// execution, so they can register new callbacks or subscriptions.
+    fn execute_connection_callbacks(&mut self, connected: bool) {
+        let mut callbacks = match connected {
+            true => mem::take(&mut self.updater.deref_mut().on_connect),
+            false => mem::take(&mut self.updater.deref_mut().on_disconnect),
+        };
+        for callback in callbacks.iter_mut() {
+            callback()
+        }
+        let mut updater = self.updater.deref_mut();
+        let registered = match connected {
+            true => &mut updater.on_connect,
+            false => &mut updater.on_disconnect,
+        };
+        callbacks.append(registered);
+        *registered = callbacks;
+    }
+
+    /// Executes the callbacks of every subscription that matches the message topic. They are
+    /// taken out of the updater during their execution, so they can subscribe or publish.
+    fn execute_subscription_callbacks(&mut self, message: &MqttMessage) {
+        let mut subscriptions = mem::take(&mut self.updater.deref_mut().subscriptions);
+        for subscription in subscriptions.iter_mut() {
+            if topic_matches(&subscription.filter, &message.topic) {
+                (subscription.callback)(message)
+            }
+        }
+        let mut updater = self.updater.deref_mut();
+        subscriptions.append(&mut updater.subscriptions);
+        updater.subscriptions = subscriptions;
+    }
+
+    /// Handles every event received from the mqtt task, executing the corresponding callbacks.
+    /// Nothing is done if it is called from inside a callback, the events wait for the next
+    /// update. A failed resubscription does not stop the dispatch of the following events, its error is
+    /// returned once every event was handled.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::SubscribeError`: If the subscriptions could not be sent again after a
+    ///   reconnection
+    pub(crate) fn process_events(&mut self) -> Result<(), MqttError> {
+        if self.is_dispatching() {
+            return Ok(());
+        }
+        let events: Vec<MqttEvent> = self.updater.deref().receiver.try_iter().collect();
+        let mut result = Ok(());
+        self.updater.deref_mut().dispatching = true;
+        for event in events {
+            match event {
+                MqttEvent::Connected => {
+                    if let Err(err) = self.resubscribe_all() {
+                        result = Err(err);
+                    }
+                    self.execute_connection_callbacks(true)
+                }
+                MqttEvent::Disconnected => self.execute_connection_callbacks(false),
+                MqttEvent::Message(message) => self.execute_subscription_callbacks(&message),
+            }
+        }
+        self.updater.deref_mut().dispatching = false;
+        result
+    }
+
+    /// Gets whether the callbacks of the client are being executed. Events can not be processed
+    /// from inside a callback, since the subscriptions are taken out of the client meanwhile.
+    pub(crate) fn is_dispatching(&self) -> bool {
+        self.updater.deref().dispatching
+    }
+}
+
+impl<'a> InterruptDriver<'a> for MqttClient<'a> {
+    /// Executes the callbacks of the received messages and connection changes
+    fn update_interrupt(&mut self) -> Result<(), Esp32FrameworkError> {
+        self.process_events().map_err(Esp32FrameworkError::Mqtt)
+    }
+
+    fn get_updater(&self) -> Box<dyn InterruptDriver<'a> + 'a> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mqtt_01_exact_topic_matches() {
+        assert!(topic_matches("home/kitchen/light", "home/kitchen/light"));
+        assert!(!topic_matches("home/kitchen/light", "home/kitchen"));
+        assert!(!topic_matches("home/kitchen", "home/kitchen/light"));
+    }
+
+    #[test]
+    fn mqtt_02_single_level_wildcard() {
+        assert!(topic_matches("home/+/light", "home/kitchen/light"));
+        assert!(!topic_matches("home/+/light", "home/kitchen/bath/light"));
+        assert!(topic_matches("home/+", "home/"));
+    }
+
+    #[test]
+    fn mqtt_03_multi_level_wildcard() {
+        assert!(topic_matches("home/#", "home/kitchen/light"));
+        assert!(topic_matches("home/#", "home"));
+        assert!(topic_matches("#", "home/kitchen"));
+        assert!(!topic_matches("#", "$SYS/broker/uptime"));
+    }
+}
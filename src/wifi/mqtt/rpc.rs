@@ -0,0 +1,671 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use esp_idf_svc::hal::delay::FreeRtos;
+use futures::future::select;
+use serde_json::{json, Value};
+
+use super::{topic_matches, MqttClient, MqttError, MqttQos};
+use crate::{
+    timer_driver::TimerDriver,
+    utils::{
+        auxiliary::{SharableRef, SharableRefExt},
+        notification::{Notification, Notifier},
+    },
+};
+
+const TOPIC_BASE: &str = "esp32framework";
+const BLOCKING_POLL_MS: u32 = 10;
+
+const INVALID_REQUEST_CODE: i32 = -32600;
+const METHOD_NOT_FOUND_CODE: i32 = -32601;
+const INVALID_PARAMS_CODE: i32 = -32602;
+const INTERNAL_ERROR_CODE: i32 = -32603;
+
+/// Gets the topic where a device receives its rpc requests
+fn request_topic(device_id: &str) -> String {
+    format!("{}/{}/rpc/request", TOPIC_BASE, device_id)
+}
+
+/// Gets the topic where a device answers the requests that do not say where to reply
+fn response_topic(device_id: &str) -> String {
+    format!("{}/{}/rpc/response", TOPIC_BASE, device_id)
+}
+
+/// Gets the topic where a device receives the responses to the calls it made
+fn reply_topic(device_id: &str) -> String {
+    format!("{}/{}/rpc/reply", TOPIC_BASE, device_id)
+}
+
+/// Checks that a topic requested for a response is the reply topic of some device. Responses
+/// are never published outside of that namespace, so a request can not make the device publish
+/// on arbitrary topics.
+fn is_reply_topic(topic: &str) -> bool {
+    !topic.contains(['+', '#']) && topic_matches(&reply_topic("+"), topic)
+}
+
+/// Error returned by a procedure, following the JSON-RPC error codes.
+/// - `code`: The code of the error.
+/// - `message`: A description of the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl RpcError {
+    /// Creates a new RpcError with a custom code
+    pub fn new(code: i32, message: &str) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// Error for requests that are not valid JSON or lack the required fields
+    pub fn invalid_request() -> Self {
+        Self::new(INVALID_REQUEST_CODE, "Invalid request")
+    }
+
+    /// Error for requests of methods that were not registered
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            METHOD_NOT_FOUND_CODE,
+            &format!("Method not found: {}", method),
+        )
+    }
+
+    /// Error for requests whose params are not the ones the method expects
+    pub fn invalid_params(message: &str) -> Self {
+        Self::new(INVALID_PARAMS_CODE, message)
+    }
+
+    /// Error for procedures that failed while executing
+    pub fn internal(message: &str) -> Self {
+        Self::new(INTERNAL_ERROR_CODE, message)
+    }
+}
+
+/// Errors possible when calling a procedure of another device
+/// - `InvalidResponse`: The response could not be parsed.
+/// - `Mqtt`: The request could not be sent.
+/// - `ReentrantCall`: A blocking call was made from inside an MQTT callback, where the response
+///   can not be received.
+/// - `Remote`: The procedure was executed and returned an error.
+/// - `Timeout`: No response arrived in time.
+#[derive(Debug)]
+pub enum RpcCallError {
+    InvalidResponse,
+    Mqtt(MqttError),
+    ReentrantCall,
+    Remote(RpcError),
+    Timeout,
+}
+
+impl From<MqttError> for RpcCallError {
+    fn from(value: MqttError) -> Self {
+        RpcCallError::Mqtt(value)
+    }
+}
+
+/// A request to execute a procedure. Its JSON representation is
+/// `{"id": "...", "method": "...", "params": ..., "reply_to": "..."}`, where `params` and
+/// `reply_to` are optional. `reply_to` must be a reply topic,
+/// `esp32framework/<client_id>/rpc/reply`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcRequest {
+    pub id: String,
+    pub method: String,
+    pub params: Value,
+    pub reply_to: Option<String>,
+}
+
+impl RpcRequest {
+    /// Parses a request from the payload of a message
+    ///
+    /// # Errors
+    ///
+    /// - `RpcError` with the invalid request code: If the payload is not a valid request. If the
+    ///   id could be parsed it is returned alongside the error, so the error can still be answered.
+    pub fn from_payload(payload: &[u8]) -> Result<Self, (Option<String>, RpcError)> {
+        let value: Value =
+            serde_json::from_slice(payload).map_err(|_| (None, RpcError::invalid_request()))?;
+        let id = match value.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => return Err((None, RpcError::invalid_request())),
+        };
+        let method = match value.get("method").and_then(Value::as_str) {
+            Some(method) => method.to_string(),
+            None => return Err((Some(id), RpcError::invalid_request())),
+        };
+        Ok(RpcRequest {
+            id,
+            method,
+            params: value.get("params").cloned().unwrap_or(Value::Null),
+            reply_to: value
+                .get("reply_to")
+                .and_then(Value::as_str)
+                .map(String::from),
+        })
+    }
+
+    /// Gets the JSON payload of the request
+    pub fn to_payload(&self) -> String {
+        let mut request = json!({
+            "id": self.id,
+            "method": self.method,
+            "params": self.params,
+        });
+        if let Some(reply_to) = &self.reply_to {
+            request["reply_to"] = json!(reply_to);
+        }
+        request.to_string()
+    }
+}
+
+/// Creates the JSON payload of a response. Its representation is `{"id": "...", "result": ...}`
+/// on success or `{"id": "...", "error": {"code": ..., "message": "..."}}` on failure.
+///
+/// # Arguments
+///
+/// - `id`: The id of the request being answered
+/// - `result`: The result of the procedure
+///
+/// # Returns
+///
+/// A String with the payload
+pub fn response_payload(id: &str, result: &Result<Value, RpcError>) -> String {
+    match result {
+        Ok(value) => json!({ "id": id, "result": value }),
+        Err(err) => json!({
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    }
+    .to_string()
+}
+
+/// Parses the payload of a response
+///
+/// # Returns
+///
+/// A `Result` with the id of the request and the result of the procedure, or an
+/// `RpcCallError::InvalidResponse` if the payload is not a valid response.
+pub fn parse_response(payload: &[u8]) -> Result<(String, Result<Value, RpcError>), RpcCallError> {
+    let value: Value =
+        serde_json::from_slice(payload).map_err(|_| RpcCallError::InvalidResponse)?;
+    let id = match value.get("id") {
+        Some(Value::String(id)) => id.clone(),
+        Some(Value::Number(id)) => id.to_string(),
+        _ => return Err(RpcCallError::InvalidResponse),
+    };
+    if let Some(error) = value.get("error") {
+        let code = error
+            .get("code")
+            .and_then(Value::as_i64)
+            .ok_or(RpcCallError::InvalidResponse)?;
+        let message = error.get("message").and_then(Value::as_str).unwrap_or("");
+        return Ok((id, Err(RpcError::new(code as i32, message))));
+    }
+    match value.get("result") {
+        Some(result) => Ok((id, Ok(result.clone()))),
+        None => Err(RpcCallError::InvalidResponse),
+    }
+}
+
+type RpcHandler<'a> = Box<dyn FnMut(&Value) -> Result<Value, RpcError> + 'a>;
+
+/// Executes procedures requested by other devices over MQTT. Requests are received on
+/// `esp32framework/<device_id>/rpc/request` and answered on the `reply_to` topic of each request,
+/// or on `esp32framework/<device_id>/rpc/response` if it has none. Requests whose `reply_to` is
+/// not the reply topic of a device are answered with an invalid request error on the default
+/// topic.
+///
+/// Note: Handlers are executed on the [crate::Microcontroller] update loop, so
+/// [crate::Microcontroller::wait_for_updates] must be called periodicly, unless using an
+/// async aproach in which case [crate::Microcontroller::block_on] must be used.
+pub struct RpcServer<'a> {
+    handlers: SharableRef<HashMap<String, RpcHandler<'a>>>,
+    request_topic: String,
+}
+
+impl<'a> RpcServer<'a> {
+    /// Creates a new RpcServer and subscribes to its request topic
+    ///
+    /// # Arguments
+    ///
+    /// - `mqtt`: The client used to receive requests and send responses
+    /// - `device_id`: The id other devices use to call procedures on this one
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the new RpcServer, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the device id is empty.
+    /// - `MqttError::SubscribeError`: If the request topic could not be subscribed.
+    pub fn new(mqtt: &MqttClient<'a>, device_id: &str) -> Result<Self, MqttError> {
+        if device_id.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        let handlers: SharableRef<HashMap<String, RpcHandler<'a>>> =
+            SharableRef::new_sharable(HashMap::new());
+        let request_topic = request_topic(device_id);
+        let default_response_topic = response_topic(device_id);
+
+        let mut handlers_ref = handlers.clone();
+        let mut publisher = mqtt.clone();
+        mqtt.clone()
+            .subscribe(&request_topic, MqttQos::AtLeastOnce, move |msg| {
+                let Some((topic, payload)) =
+                    answer_request(&mut handlers_ref, &msg.payload, &default_response_topic)
+                else {
+                    return;
+                };
+                if let Err(err) =
+                    publisher.publish_str(&topic, &payload, MqttQos::AtLeastOnce, false)
+                {
+                    log::warn!("Could not send rpc response: {:?}", err)
+                }
+            })?;
+
+        Ok(RpcServer {
+            handlers,
+            request_topic,
+        })
+    }
+
+    /// Registers a procedure. If a procedure with the same name already existed it is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// - `method`: The name of the procedure
+    /// - `handler`: A closure that receives the params of the request and returns its result
+    pub fn register<F: FnMut(&Value) -> Result<Value, RpcError> + 'a>(
+        &mut self,
+        method: &str,
+        handler: F,
+    ) {
+        self.handlers
+            .deref_mut()
+            .insert(method.to_string(), Box::new(handler));
+    }
+
+    /// Removes a procedure
+    pub fn unregister(&mut self, method: &str) {
+        self.handlers.deref_mut().remove(method);
+    }
+
+    /// Gets the topic where requests are received
+    pub fn request_topic(&self) -> &str {
+        &self.request_topic
+    }
+}
+
+/// Executes the procedure of a request and creates its response
+///
+/// # Arguments
+///
+/// - `handlers`: The registered procedures
+/// - `payload`: The payload of the request
+/// - `default_response_topic`: Where the response is sent if the request has no `reply_to`
+///
+/// # Returns
+///
+/// The topic and payload of the response, or `None` if the request has no id to answer to
+fn answer_request(
+    handlers: &mut SharableRef<HashMap<String, RpcHandler<'_>>>,
+    payload: &[u8],
+    default_response_topic: &str,
+) -> Option<(String, String)> {
+    let (id, reply_to, result) = match RpcRequest::from_payload(payload) {
+        Ok(request) if !request.reply_to.as_deref().map_or(true, is_reply_topic) => {
+            (Some(request.id), None, Err(RpcError::invalid_request()))
+        }
+        Ok(request) => {
+            let result = execute_handler(handlers, &request);
+            (Some(request.id), request.reply_to, result)
+        }
+        Err((id, err)) => (id, None, Err(err)),
+    };
+    let topic = reply_to.unwrap_or_else(|| default_response_topic.to_string());
+    Some((topic, response_payload(&id?, &result)))
+}
+
+/// Executes the handler of a request. The handler is taken out of the map during its
+/// execution, so it can register or remove procedures.
+fn execute_handler(
+    handlers: &mut SharableRef<HashMap<String, RpcHandler<'_>>>,
+    request: &RpcRequest,
+) -> Result<Value, RpcError> {
+    let handler = handlers.deref_mut().remove(&request.method);
+    match handler {
+        Some(mut handler) => {
+            let result = handler(&request.params);
+            handlers
+                .deref_mut()
+                .entry(request.method.clone())
+                .or_insert(handler);
+            result
+        }
+        None => Err(RpcError::method_not_found(&request.method)),
+    }
+}
+
+/// A call waiting for its response
+#[derive(Default)]
+struct PendingCall {
+    notifier: Option<Notifier>,
+    result: Option<Result<Value, RpcError>>,
+}
+
+/// Calls procedures of other devices over MQTT. Each request carries a correlation id and the
+/// topic where the response should be sent, `esp32framework/<client_id>/rpc/reply`.
+pub struct RpcClient<'a> {
+    mqtt: MqttClient<'a>,
+    timer_driver: TimerDriver<'a>,
+    client_id: String,
+    reply_topic: String,
+    next_id: u32,
+    pending: SharableRef<HashMap<String, PendingCall>>,
+}
+
+impl<'a> RpcClient<'a> {
+    /// Creates a new RpcClient and subscribes to its reply topic
+    ///
+    /// # Arguments
+    ///
+    /// - `mqtt`: The client used to send requests and receive responses
+    /// - `timer_driver`: A TimerDriver used for the timeouts of [Self::call_async]
+    /// - `client_id`: An id unique among the devices, used to build the reply topic and correlation ids
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the new RpcClient, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the client id is empty.
+    /// - `MqttError::SubscribeError`: If the reply topic could not be subscribed.
+    pub fn new(
+        mqtt: &MqttClient<'a>,
+        timer_driver: TimerDriver<'a>,
+        client_id: &str,
+    ) -> Result<Self, MqttError> {
+        if client_id.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        let pending: SharableRef<HashMap<String, PendingCall>> =
+            SharableRef::new_sharable(HashMap::new());
+        let reply_topic = reply_topic(client_id);
+
+        let mut pending_ref = pending.clone();
+        let mut mqtt = mqtt.clone();
+        mqtt.subscribe(&reply_topic, MqttQos::AtLeastOnce, move |msg| {
+            let Ok((id, result)) = parse_response(&msg.payload) else {
+                return;
+            };
+            if let Some(call) = pending_ref.deref_mut().get_mut(&id) {
+                call.result = Some(result);
+                if let Some(notifier) = &call.notifier {
+                    notifier.notify();
+                }
+            }
+        })?;
+
+        Ok(RpcClient {
+            mqtt,
+            timer_driver,
+            client_id: client_id.to_string(),
+            reply_topic,
+            next_id: 0,
+            pending,
+        })
+    }
+
+    /// Publishes a request, registering it as pending
+    fn send_request(
+        &mut self,
+        device_id: &str,
+        method: &str,
+        params: Value,
+        notifier: Option<Notifier>,
+    ) -> Result<String, RpcCallError> {
+        let id = format!("{}-{}", self.client_id, self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        let request = RpcRequest {
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+            reply_to: Some(self.reply_topic.clone()),
+        };
+        self.pending.deref_mut().insert(
+            id.clone(),
+            PendingCall {
+                notifier,
+                result: None,
+            },
+        );
+        let published = self.mqtt.publish_str(
+            &request_topic(device_id),
+            &request.to_payload(),
+            MqttQos::AtLeastOnce,
+            false,
+        );
+        if let Err(err) = published {
+            self.pending.deref_mut().remove(&id);
+            return Err(err.into());
+        }
+        Ok(id)
+    }
+
+    /// Takes the result of a call if its response arrived
+    fn take_result(&mut self, id: &str) -> Option<Result<Value, RpcCallError>> {
+        let mut pending = self.pending.deref_mut();
+        pending.get(id)?.result.as_ref()?;
+        let result = pending.remove(id)?.result?;
+        Some(result.map_err(RpcCallError::Remote))
+    }
+
+    /// Blocking method that calls a procedure of another device and waits for its response.
+    /// While waiting, the messages received by the MQTT client are processed, so it can not be
+    /// called from inside an MQTT callback. There [Self::call_async] or a request sent from the
+    /// main loop must be used instead.
+    ///
+    /// # Arguments
+    ///
+    /// - `device_id`: The id of the device that executes the procedure
+    /// - `method`: The name of the procedure
+    /// - `params`: The params of the procedure
+    /// - `timeout`: The maximum time to wait for the response
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the result of the procedure, or an `RpcCallError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `RpcCallError::Mqtt`: If the request could not be sent.
+    /// - `RpcCallError::ReentrantCall`: If it is called from inside an MQTT callback.
+    /// - `RpcCallError::Remote`: If the procedure returned an error.
+    /// - `RpcCallError::Timeout`: If no response arrived before the timeout.
+    pub fn call(
+        &mut self,
+        device_id: &str,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcCallError> {
+        if self.mqtt.is_dispatching() {
+            return Err(RpcCallError::ReentrantCall);
+        }
+        let deadline = Instant::now() + timeout;
+        let id = self.send_request(device_id, method, params, None)?;
+        loop {
+            self.mqtt.process_events()?;
+            if let Some(result) = self.take_result(&id) {
+                return result;
+            }
+            if Instant::now() >= deadline {
+                self.pending.deref_mut().remove(&id);
+                return Err(RpcCallError::Timeout);
+            }
+            FreeRtos::delay_ms(BLOCKING_POLL_MS);
+        }
+    }
+
+    /// Non blocking async version of [Self::call]
+    ///
+    /// Note: For the response to be received, [crate::Microcontroller::block_on] must be used.
+    pub async fn call_async(
+        &mut self,
+        device_id: &str,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcCallError> {
+        let notification = Notification::new();
+        let id = self.send_request(device_id, method, params, Some(notification.notifier()))?;
+
+        let timeout_ms = timeout.as_millis().min(u32::MAX as u128) as u32;
+        let response = Box::pin(notification.wait());
+        let delay = Box::pin(self.timer_driver.delay(timeout_ms));
+        // Whichever finishes first, the other future is dropped here
+        let _ = select(response, delay).await;
+        match self.take_result(&id) {
+            Some(result) => result,
+            None => {
+                self.pending.deref_mut().remove(&id);
+                Err(RpcCallError::Timeout)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rpc_01_parse_request() {
+        let request = RpcRequest::from_payload(
+            br#"{"id":"a-1","method":"set_led","params":{"on":true},"reply_to":"x/y"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            RpcRequest {
+                id: String::from("a-1"),
+                method: String::from("set_led"),
+                params: json!({"on": true}),
+                reply_to: Some(String::from("x/y")),
+            }
+        );
+    }
+
+    #[test]
+    fn rpc_02_parse_request_without_params_nor_reply_to() {
+        let request = RpcRequest::from_payload(br#"{"id":7,"method":"ping"}"#).unwrap();
+        assert_eq!(request.id, "7");
+        assert_eq!(request.params, Value::Null);
+        assert_eq!(request.reply_to, None);
+    }
+
+    #[test]
+    fn rpc_03_invalid_requests() {
+        let (id, err) = RpcRequest::from_payload(b"not json").unwrap_err();
+        assert_eq!((id, err.code), (None, INVALID_REQUEST_CODE));
+        let (id, err) = RpcRequest::from_payload(br#"{"id":"a"}"#).unwrap_err();
+        assert_eq!(
+            (id, err.code),
+            (Some(String::from("a")), INVALID_REQUEST_CODE)
+        );
+    }
+
+    #[test]
+    fn rpc_04_request_round_trip() {
+        let request = RpcRequest {
+            id: String::from("c-3"),
+            method: String::from("read"),
+            params: json!([1, 2]),
+            reply_to: Some(String::from("esp32framework/c/rpc/reply")),
+        };
+        assert_eq!(
+            RpcRequest::from_payload(request.to_payload().as_bytes()).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn rpc_05_response_round_trip() {
+        let ok = response_payload("1", &Ok(json!({"temp": 21.5})));
+        let (id, result) = parse_response(ok.as_bytes()).unwrap();
+        assert_eq!(id, "1");
+        assert_eq!(result, Ok(json!({"temp": 21.5})));
+
+        let err = response_payload("2", &Err(RpcError::method_not_found("foo")));
+        let (id, result) = parse_response(err.as_bytes()).unwrap();
+        assert_eq!(id, "2");
+        assert_eq!(result.unwrap_err().code, METHOD_NOT_FOUND_CODE);
+    }
+
+    #[test]
+    fn rpc_06_invalid_response() {
+        assert!(matches!(
+            parse_response(br#"{"id":"1"}"#),
+            Err(RpcCallError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn rpc_07_only_reply_topics_are_answered() {
+        assert!(is_reply_topic("esp32framework/client/rpc/reply"));
+        assert!(!is_reply_topic("esp32framework/client/rpc/request"));
+        assert!(!is_reply_topic("home/door/set"));
+        assert!(!is_reply_topic("esp32framework/a/b/rpc/reply"));
+        assert!(!is_reply_topic("esp32framework/+/rpc/reply"));
+        assert!(!is_reply_topic("esp32framework/#"));
+    }
+
+    #[test]
+    fn rpc_08_round_trip_on_default_response_topic() {
+        let mut handlers: SharableRef<HashMap<String, RpcHandler<'_>>> =
+            SharableRef::new_sharable(HashMap::new());
+        handlers
+            .deref_mut()
+            .insert(String::from("ping"), Box::new(|_| Ok(json!("pong"))));
+        let request = RpcRequest {
+            id: String::from("p-1"),
+            method: String::from("ping"),
+            params: Value::Null,
+            reply_to: None,
+        };
+        let (topic, payload) = answer_request(
+            &mut handlers,
+            request.to_payload().as_bytes(),
+            &response_topic("device"),
+        )
+        .unwrap();
+        assert_eq!(topic, "esp32framework/device/rpc/response");
+        assert_eq!(
+            parse_response(payload.as_bytes()).unwrap(),
+            (String::from("p-1"), Ok(json!("pong")))
+        );
+
+        let request = RpcRequest {
+            reply_to: Some(reply_topic("client")),
+            ..request
+        };
+        let (topic, _) = answer_request(
+            &mut handlers,
+            request.to_payload().as_bytes(),
+            &response_topic("device"),
+        )
+        .unwrap();
+        assert_eq!(topic, "esp32framework/client/rpc/reply");
+    }
+}
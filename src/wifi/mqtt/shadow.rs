@@ -0,0 +1,277 @@
+use serde_json::{Map, Value};
+
+use super::{MqttClient, MqttError, MqttQos};
+use crate::utils::auxiliary::{SharableRef, SharableRefExt};
+
+const TOPIC_BASE: &str = "esp32framework";
+
+/// Applies a JSON merge patch (RFC 7386) to a document. Objects are merged recursively, `null`
+/// values remove the key and any other value replaces the previous one.
+///
+/// # Arguments
+///
+/// - `target`: The document to modify
+/// - `patch`: The patch to apply
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Computes the difference between the desired and the reported state of a device. Objects
+/// are compared recursively, any other value is part of the delta if it differs from the
+/// reported one. Keys with a `null` desired value are ignored.
+///
+/// # Arguments
+///
+/// - `desired`: The state the device should have
+/// - `reported`: The state the device has
+///
+/// # Returns
+///
+/// An `Option` with the values of the desired state that are not yet reported, or `None`
+/// if the device is already in the desired state.
+pub fn compute_delta(desired: &Value, reported: &Value) -> Option<Value> {
+    match desired {
+        Value::Null => None,
+        Value::Object(desired) => {
+            let delta: Map<String, Value> = desired
+                .iter()
+                .filter_map(|(key, value)| {
+                    let reported = reported.get(key).unwrap_or(&Value::Null);
+                    compute_delta(value, reported).map(|delta| (key.clone(), delta))
+                })
+                .collect();
+            match delta.is_empty() {
+                true => None,
+                false => Some(Value::Object(delta)),
+            }
+        }
+        _ if desired == reported => None,
+        _ => Some(desired.clone()),
+    }
+}
+
+type DeltaHandler<'a> = Box<dyn FnMut(&Value) -> Option<Value> + 'a>;
+
+struct ShadowState<'a> {
+    desired: Value,
+    reported: Value,
+    handler: Option<DeltaHandler<'a>>,
+}
+
+/// A JSON document that keeps the state of a device synchronized with the broker.
+/// - `esp32framework/<device_id>/shadow/desired`: Retained topic with the state other clients
+///   want the device to have. Every message received is applied as a JSON merge patch.
+/// - `esp32framework/<device_id>/shadow/reported`: Retained topic where the device publishes
+///   its current state every time it changes and on every connection.
+///
+/// Each time the desired state differs from the reported one, the delta handler is called.
+///
+/// Note: The handler is executed on the [crate::Microcontroller] update loop, so
+/// [crate::Microcontroller::wait_for_updates] must be called periodicly, unless using an
+/// async aproach in which case [crate::Microcontroller::block_on] must be used.
+pub struct DeviceShadow<'a> {
+    mqtt: MqttClient<'a>,
+    state: SharableRef<ShadowState<'a>>,
+    reported_topic: String,
+}
+
+impl<'a> DeviceShadow<'a> {
+    /// Creates a new DeviceShadow with an empty state and subscribes to its desired topic
+    ///
+    /// # Arguments
+    ///
+    /// - `mqtt`: The client used to receive the desired state and publish the reported one
+    /// - `device_id`: The id of the device, used to build the shadow topics
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the new DeviceShadow, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::InvalidTopic`: If the device id is empty.
+    /// - `MqttError::SubscribeError`: If the desired topic could not be subscribed.
+    pub fn new(mqtt: &MqttClient<'a>, device_id: &str) -> Result<Self, MqttError> {
+        if device_id.is_empty() {
+            return Err(MqttError::InvalidTopic);
+        }
+        let mut shadow = DeviceShadow {
+            mqtt: mqtt.clone(),
+            state: SharableRef::new_sharable(ShadowState {
+                desired: Value::Object(Map::new()),
+                reported: Value::Object(Map::new()),
+                handler: None,
+            }),
+            reported_topic: format!("{}/{}/shadow/reported", TOPIC_BASE, device_id),
+        };
+        let desired_topic = format!("{}/{}/shadow/desired", TOPIC_BASE, device_id);
+
+        let mut shadow_ref = shadow.clone();
+        shadow
+            .mqtt
+            .subscribe(
+                &desired_topic,
+                MqttQos::AtLeastOnce,
+                move |msg| match serde_json::from_slice::<Value>(&msg.payload) {
+                    Ok(patch) => shadow_ref.update_desired(&patch),
+                    Err(_) => log::warn!("Invalid desired state on {}", msg.topic),
+                },
+            )?;
+
+        let shadow_ref = shadow.clone();
+        shadow.mqtt.on_connect(move || {
+            if let Err(err) = shadow_ref.publish_reported() {
+                log::warn!("Could not publish reported state: {:?}", err)
+            }
+        });
+        Ok(shadow)
+    }
+
+    /// Sets the handler called when the desired state differs from the reported one. The
+    /// handler receives the delta and returns the values that were applied, which are then
+    /// reported. If it returns `None` nothing is reported. Setting a new handler replaces the
+    /// previous one.
+    pub fn on_delta<F: FnMut(&Value) -> Option<Value> + 'a>(&mut self, handler: F) {
+        self.state.deref_mut().handler = Some(Box::new(handler));
+    }
+
+    /// Applies a merge patch to the reported state and publishes the result
+    ///
+    /// # Arguments
+    ///
+    /// - `patch`: The values that changed. A `null` value removes the key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with `Ok` if the reported state could be published, or an `MqttError` if it fails.
+    ///
+    /// # Errors
+    ///
+    /// - `MqttError::PublishError`: If the message could not be published. The reported state is
+    ///   still updated and will be published again when the connection is established.
+    pub fn report(&mut self, patch: &Value) -> Result<(), MqttError> {
+        merge_patch(&mut self.state.deref_mut().reported, patch);
+        self.publish_reported()
+    }
+
+    /// Gets the desired state
+    pub fn desired(&self) -> Value {
+        self.state.deref().desired.clone()
+    }
+
+    /// Gets the reported state
+    pub fn reported(&self) -> Value {
+        self.state.deref().reported.clone()
+    }
+
+    /// Gets the values of the desired state not yet reported, if any
+    pub fn delta(&self) -> Option<Value> {
+        let state = self.state.deref();
+        compute_delta(&state.desired, &state.reported)
+    }
+
+    /// Publishes the reported state as a retained message
+    fn publish_reported(&self) -> Result<(), MqttError> {
+        let payload = self.state.deref().reported.to_string();
+        self.mqtt
+            .clone()
+            .publish_str(&self.reported_topic, &payload, MqttQos::AtLeastOnce, true)
+    }
+
+    /// Applies a patch to the desired state, calling the handler if there is a delta. The
+    /// handler is taken out of the state during its execution, so it can use the shadow.
+    fn update_desired(&mut self, patch: &Value) {
+        merge_patch(&mut self.state.deref_mut().desired, patch);
+        let Some(delta) = self.delta() else { return };
+        let Some(mut handler) = self.state.deref_mut().handler.take() else {
+            return;
+        };
+        let applied = handler(&delta);
+        self.state.deref_mut().handler.get_or_insert(handler);
+        if let Some(applied) = applied {
+            if let Err(err) = self.report(&applied) {
+                log::warn!("Could not publish reported state: {:?}", err)
+            }
+        }
+    }
+}
+
+impl Clone for DeviceShadow<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            mqtt: self.mqtt.clone(),
+            state: self.state.clone(),
+            reported_topic: self.reported_topic.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn shadow_01_merge_patch_adds_replaces_and_removes() {
+        let mut doc = json!({"led": true, "color": {"r": 1, "g": 2}, "name": "a"});
+        merge_patch(
+            &mut doc,
+            &json!({"led": false, "color": {"g": null, "b": 3}, "name": null, "new": 1}),
+        );
+        assert_eq!(
+            doc,
+            json!({"led": false, "color": {"r": 1, "b": 3}, "new": 1})
+        );
+    }
+
+    #[test]
+    fn shadow_02_merge_patch_non_object() {
+        let mut doc = json!({"a": 1});
+        merge_patch(&mut doc, &json!([1, 2]));
+        assert_eq!(doc, json!([1, 2]));
+        merge_patch(&mut doc, &json!({"a": {"b": 1}}));
+        assert_eq!(doc, json!({"a": {"b": 1}}));
+    }
+
+    #[test]
+    fn shadow_03_delta_of_equal_states_is_none() {
+        let state = json!({"led": true, "color": {"r": 1}});
+        assert_eq!(compute_delta(&state, &state), None);
+        assert_eq!(compute_delta(&json!({}), &state), None);
+    }
+
+    #[test]
+    fn shadow_04_delta_only_has_differences() {
+        let desired = json!({"led": true, "color": {"r": 1, "g": 5}, "period": 10, "x": null});
+        let reported = json!({"led": true, "color": {"r": 1, "g": 2}, "extra": 3});
+        assert_eq!(
+            compute_delta(&desired, &reported),
+            Some(json!({"color": {"g": 5}, "period": 10}))
+        );
+    }
+
+    #[test]
+    fn shadow_05_delta_when_reported_type_differs() {
+        let desired = json!({"color": {"r": 1}});
+        let reported = json!({"color": "red"});
+        assert_eq!(
+            compute_delta(&desired, &reported),
+            Some(json!({"color": {"r": 1}}))
+        );
+    }
+}
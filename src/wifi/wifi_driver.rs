@@ -67,7 +67,7 @@ pub struct WifiDriver<'a> {
 impl<'a> WifiDriver<'a> {
     /// Creates a new WifiDriver.
     ///
-    /// By default this function takes the Non-Volatile Storage of the ESP in order to save
+    /// The Non-Volatile Storage of the ESP is used in order to save the
     /// wifi configuration. This is to improve connection times for future connections
     /// to the same network.
     ///
@@ -75,6 +75,7 @@ impl<'a> WifiDriver<'a> {
     ///
     /// - `event_loop`: Microcontroller's event loop.
     /// - `modem`: Microcontroller's modem peripheral.
+    /// - `nvs`: The default NVS partition, where the wifi configuration is saved.
     ///
     /// # Returns
     ///
@@ -83,13 +84,12 @@ impl<'a> WifiDriver<'a> {
     ///
     /// # Errors
     ///
-    /// - `WifiError::NvsAlreadyTaken`: If the NVS Default Partition was already taken.
     /// - `WifiError::StartingError`: If there is an error initializing the driver.
     pub(crate) fn new(
         event_loop: EspSystemEventLoop,
         modem: modem::Modem,
+        nvs: EspDefaultNvsPartition,
     ) -> Result<Self, WifiError> {
-        let nvs = EspDefaultNvsPartition::take().map_err(|_| WifiError::NvsAlreadyTaken)?;
         let timer_service = EspTaskTimerService::new().map_err(|_| WifiError::StartingError)?;
         Ok(WifiDriver {
             controller: AsyncWifi::wrap(